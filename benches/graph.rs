@@ -0,0 +1,97 @@
+//! Parsing, event processing and scene construction on generated graphs
+//!
+//! Run with `cargo bench`; criterion compares each run with the last one.
+
+use bevy::prelude::*;
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use dotspace::aggregate::Aggregation;
+use dotspace::generate::{GraphModel, generate_dot};
+use dotspace::scene_build::{SceneBuild, spawn_scene_chunk};
+use dotspace::sources::dot::DotSource;
+use dotspace::types::{LayoutSettings, Timeline, ViewSettings};
+use dotspace::visualization::refresh_graph_scene;
+use dotspace::{GraphEventSource, GraphState};
+use std::hint::black_box;
+
+/// Nodes in each benchmarked graph, with three edges per node
+const SIZES: [usize; 2] = [1_000, 10_000];
+
+fn dot_graph(nodes: usize) -> String {
+    generate_dot(GraphModel::Barabasi, nodes, nodes * 3, 0)
+}
+
+fn parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_dot");
+    for nodes in SIZES {
+        let dot = dot_graph(nodes);
+        group.bench_with_input(BenchmarkId::from_parameter(nodes), &dot, |b, dot| {
+            b.iter(|| DotSource::from_content(dot).events().unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn event_processing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process_events");
+    for nodes in SIZES {
+        let events = DotSource::new(dot_graph(nodes)).events().unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(nodes), &events, |b, events| {
+            b.iter_batched(
+                || events.clone(),
+                |events| {
+                    let mut state = GraphState::new();
+                    state.process_events(events);
+                    black_box(state)
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+/// An app with just what building the scene needs, holding `state`
+fn scene_app(state: GraphState) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .init_resource::<Assets<Mesh>>()
+        .init_resource::<Assets<StandardMaterial>>()
+        .init_resource::<LayoutSettings>()
+        .init_resource::<ViewSettings>()
+        .init_resource::<Timeline>()
+        .init_resource::<Aggregation>()
+        .insert_resource(state)
+        .add_systems(Update, (refresh_graph_scene, spawn_scene_chunk).chain());
+    app.world_mut().resource_mut::<GraphState>().set_changed();
+    app
+}
+
+fn scene_construction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build_scene");
+    group.sample_size(10);
+    for nodes in SIZES {
+        let events = DotSource::new(dot_graph(nodes)).events().unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(nodes), &events, |b, events| {
+            b.iter_batched(
+                || {
+                    let mut state = GraphState::new();
+                    state.process_events(events.clone());
+                    scene_app(state)
+                },
+                |mut app| {
+                    // Spawning is spread over frames
+                    app.update();
+                    while app.world().contains_resource::<SceneBuild>() {
+                        app.update();
+                    }
+                    app
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parsing, event_processing, scene_construction);
+criterion_main!(benches);
@@ -0,0 +1,41 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    control_service_stubs();
+}
+
+/// Generates the gRPC server for `proto/dotspace.proto` without needing
+/// `protoc`; the messages are written out by hand in `src/grpc.rs`
+#[cfg(feature = "grpc")]
+fn control_service_stubs() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("crate::grpc::{input}"))
+            .output_type(format!("crate::grpc::{output}"))
+            .codec_path("tonic::codec::ProstCodec")
+            .build()
+    };
+    let service = Service::builder()
+        .name("Control")
+        .package("dotspace")
+        .method(method(
+            "send_events",
+            "SendEvents",
+            "EventBatch",
+            "SendReply",
+        ))
+        .method(method("get_nodes", "GetNodes", "Empty", "NodeList"))
+        .method(method(
+            "get_selection",
+            "GetSelection",
+            "Empty",
+            "Selection",
+        ))
+        .method(method("get_camera", "GetCamera", "Empty", "CameraPose"))
+        .build();
+    Builder::new().build_client(false).compile(&[service]);
+}
@@ -0,0 +1,203 @@
+//! Larger and bolder text for people who find the default look hard to read
+//!
+//! A scale for the overlays and labels, and a high-contrast look with opaque
+//! panels, outlined labels and thicker highlight outlines.
+
+use crate::highlight::OUTLINE_SCALE;
+use crate::keybindings::{Action, KeyBindings};
+use crate::theme::Theme;
+use crate::types::{NodeOutline, SearchState};
+use bevy::prelude::*;
+use bevy_egui::EguiContextSettings;
+use std::ops::RangeInclusive;
+
+/// Smallest and largest UI scale
+pub const UI_SCALE_RANGE: RangeInclusive<f32> = 0.5..=3.0;
+
+/// How much the UI scale keys change it by
+pub const UI_SCALE_STEP: f32 = 0.25;
+
+/// How much larger than its node an outline shell is drawn in high contrast
+const HIGH_CONTRAST_OUTLINE_SCALE: f32 = 1.35;
+
+/// How big and how bold text is drawn
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Accessibility {
+    /// Size of the overlays, settings windows and node labels; 1 is normal
+    pub ui_scale: f32,
+    /// Opaque panels, outlined labels that don't fade, and thicker outlines
+    pub high_contrast: bool,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.0,
+            high_contrast: false,
+        }
+    }
+}
+
+impl Accessibility {
+    /// How much larger than its node an outline shell is drawn
+    pub const fn outline_scale(self) -> f32 {
+        if self.high_contrast {
+            HIGH_CONTRAST_OUTLINE_SCALE
+        } else {
+            OUTLINE_SCALE
+        }
+    }
+
+    /// Color of overlay text, pure black or white in high contrast
+    pub const fn text(self, theme: Theme) -> Color {
+        match (self.high_contrast, theme) {
+            (false, _) => theme.text(),
+            (true, Theme::Dark) => Color::WHITE,
+            (true, Theme::Light) => Color::BLACK,
+        }
+    }
+
+    /// Background of the overlays behind text, opaque in high contrast
+    pub fn panel(self, theme: Theme) -> Color {
+        if self.high_contrast {
+            theme.panel().with_alpha(1.0)
+        } else {
+            theme.panel()
+        }
+    }
+
+    /// The same settings with the UI scale moved by `steps` and kept in range
+    #[must_use]
+    pub fn rescaled(self, steps: f32) -> Self {
+        Self {
+            ui_scale: UI_SCALE_STEP
+                .mul_add(steps, self.ui_scale)
+                .clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end()),
+            ..self
+        }
+    }
+}
+
+/// Parses a `--ui-scale` value, a factor from 0.5 to 3
+pub fn parse_ui_scale(s: &str) -> Result<f32, String> {
+    let scale: f32 = s
+        .parse()
+        .map_err(|_| format!("invalid UI scale '{s}' (expected a number such as 1.5)"))?;
+    if UI_SCALE_RANGE.contains(&scale) {
+        Ok(scale)
+    } else {
+        Err(format!(
+            "UI scale {scale} is out of range (expected {} to {})",
+            UI_SCALE_RANGE.start(),
+            UI_SCALE_RANGE.end()
+        ))
+    }
+}
+
+/// Applies the UI scale and high-contrast settings and their keys
+pub struct AccessibilityPlugin;
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Accessibility>()
+            .add_systems(Update, toggle_accessibility)
+            .add_systems(Update, (apply_ui_scale, apply_outline_scale));
+    }
+}
+
+/// Switches high contrast and steps the UI scale up and down
+pub fn toggle_accessibility(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    mut accessibility: ResMut<Accessibility>,
+) {
+    if search_state.active {
+        return;
+    }
+    let mut wanted = *accessibility;
+    if bindings.just_pressed(Action::ToggleHighContrast, &keyboard_input) {
+        wanted.high_contrast = !wanted.high_contrast;
+    }
+    if bindings.just_pressed(Action::IncreaseUiScale, &keyboard_input) {
+        wanted = wanted.rescaled(1.0);
+    }
+    if bindings.just_pressed(Action::DecreaseUiScale, &keyboard_input) {
+        wanted = wanted.rescaled(-1.0);
+    }
+    accessibility.set_if_neq(wanted);
+}
+
+/// Scales the overlays and every egui context, including ones opened after
+/// the setting changed
+pub fn apply_ui_scale(
+    accessibility: Res<Accessibility>,
+    mut ui_scale: ResMut<UiScale>,
+    mut egui_query: Query<&mut EguiContextSettings>,
+) {
+    if accessibility.is_changed() {
+        ui_scale.0 = accessibility.ui_scale;
+    }
+    for mut settings in &mut egui_query {
+        if accessibility.is_changed() || settings.is_added() {
+            settings.scale_factor = accessibility.ui_scale;
+        }
+    }
+}
+
+/// Sizes node outline shells for the contrast setting, including the
+/// shells of nodes added since
+pub fn apply_outline_scale(
+    accessibility: Res<Accessibility>,
+    mut outline_query: Query<(&mut Transform, Ref<NodeOutline>)>,
+) {
+    let scale = Vec3::splat(accessibility.outline_scale());
+    for (mut transform, outline) in &mut outline_query {
+        if (accessibility.is_changed() || outline.is_added()) && transform.scale != scale {
+            transform.scale = scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ui_scale() {
+        assert_eq!(parse_ui_scale("1.5"), Ok(1.5));
+        assert!(parse_ui_scale("10").is_err());
+        assert!(parse_ui_scale("big").is_err());
+    }
+
+    #[test]
+    fn test_ui_scale_keys_stay_in_range() {
+        let big = Accessibility {
+            ui_scale: 2.9,
+            ..default()
+        };
+        assert!((big.rescaled(1.0).ui_scale - 3.0).abs() < f32::EPSILON);
+        assert!((Accessibility::default().rescaled(-1.0).ui_scale - 0.75).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_high_contrast_thickens_outlines() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Accessibility>()
+            .add_systems(Update, apply_outline_scale);
+        let outline = app
+            .world_mut()
+            .spawn((Transform::default(), NodeOutline))
+            .id();
+        app.update();
+        let scale = |app: &App| app.world().get::<Transform>(outline).unwrap().scale.x;
+        assert!((scale(&app) - OUTLINE_SCALE).abs() < f32::EPSILON);
+
+        app.world_mut()
+            .resource_mut::<Accessibility>()
+            .high_contrast = true;
+        app.update();
+        assert!((scale(&app) - HIGH_CONTRAST_OUTLINE_SCALE).abs() < f32::EPSILON);
+    }
+}
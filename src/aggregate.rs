@@ -0,0 +1,273 @@
+use crate::graph_state::{EdgeInfo, GraphData, NodeInfo};
+use crate::keybindings::{Action, KeyBindings};
+use crate::types::{self, GraphNode, HoverState, SearchState};
+use bevy::prelude::*;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+/// Graphs with more nodes than this are aggregated, unless set otherwise
+pub const DEFAULT_THRESHOLD: usize = 50_000;
+
+/// Node type of cluster super-nodes
+pub const CLUSTER_TYPE: &str = "cluster";
+
+/// Start of a super-node's id, followed by its hub's id
+const CLUSTER_PREFIX: &str = "cluster:";
+
+/// Stands in for the hub of nodes that aren't connected to any hub
+const NO_HUB: &str = "*";
+
+/// Shows huge graphs as their best-connected nodes (hubs) plus one
+/// super-node per hub for the nodes nearest it, so they stay responsive
+///
+/// Clusters are expanded into their nodes one at a time, from the keyboard.
+#[derive(Resource, Debug, Clone)]
+pub struct Aggregation {
+    /// Graphs with more nodes than this are aggregated; 0 never aggregates
+    pub threshold: usize,
+    /// How many of the best-connected nodes stay visible as hubs
+    pub hubs: usize,
+    /// Hubs whose clusters are shown node by node
+    pub expanded: HashSet<String>,
+}
+
+impl Default for Aggregation {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_THRESHOLD,
+            hubs: 500,
+            expanded: HashSet::new(),
+        }
+    }
+}
+
+impl Aggregation {
+    /// Whether a graph of this many nodes is aggregated
+    pub const fn applies_to(&self, nodes: usize) -> bool {
+        self.threshold > 0 && nodes > self.threshold
+    }
+
+    /// The graph to show: `data` itself, or its hubs and clusters if it's too big
+    pub fn shown(&self, data: GraphData) -> GraphData {
+        if self.applies_to(data.graph.node_count()) {
+            self.aggregate(&data)
+        } else {
+            data
+        }
+    }
+
+    /// Hubs, the expanded clusters' nodes and a super-node for every other
+    /// cluster, with edges between them merged
+    fn aggregate(&self, data: &GraphData) -> GraphData {
+        let ids = |node: NodeIndex| data.graph[node].id.as_str();
+        let mut by_degree: Vec<NodeIndex> = data.graph.node_indices().collect();
+        by_degree.sort_by_cached_key(|&node| {
+            (
+                Reverse(data.graph.neighbors_undirected(node).count()),
+                ids(node),
+            )
+        });
+        by_degree.truncate(self.hubs);
+
+        // Every node joins the cluster of the hub nearest it, ignoring direction
+        let mut hub_of: HashMap<NodeIndex, NodeIndex> =
+            by_degree.iter().map(|&hub| (hub, hub)).collect();
+        let mut queue: VecDeque<NodeIndex> = by_degree.iter().copied().collect();
+        while let Some(node) = queue.pop_front() {
+            let hub = hub_of[&node];
+            for next in data.graph.neighbors_undirected(node) {
+                if let Entry::Vacant(entry) = hub_of.entry(next) {
+                    entry.insert(hub);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        // What each node is shown as: itself, or its cluster's super-node
+        let mut clusters: BTreeMap<String, (Option<NodeIndex>, usize)> = BTreeMap::new();
+        let shown_as: Vec<String> = data
+            .graph
+            .node_indices()
+            .map(|node| {
+                let hub = hub_of.get(&node).copied();
+                let hub_id = hub.map_or(NO_HUB, ids);
+                if hub == Some(node) || self.expanded.contains(hub_id) {
+                    ids(node).to_string()
+                } else {
+                    let id = format!("{CLUSTER_PREFIX}{hub_id}");
+                    clusters.entry(id.clone()).or_insert((hub, 0)).1 += 1;
+                    id
+                }
+            })
+            .collect();
+
+        let mut shown = GraphData {
+            graph: DiGraph::new(),
+            node_map: HashMap::new(),
+            hints: data.hints.clone(),
+        };
+        for (node, id) in data.graph.node_indices().zip(&shown_as) {
+            if !clusters.contains_key(id) {
+                let idx = shown.graph.add_node(data.graph[node].clone());
+                shown.node_map.insert(id.clone(), idx);
+            }
+        }
+        for (id, (hub, size)) in clusters {
+            let hub = hub.map(|hub| &data.graph[hub]);
+            let info = NodeInfo {
+                id: id.clone(),
+                name: format!("{} +{size}", hub.map_or("unconnected", |hub| &hub.name)),
+                node_type: Some(CLUSTER_TYPE.to_string()),
+                level: hub.map_or(0, |hub| hub.level),
+                metrics: BTreeMap::new(),
+                state: None,
+                properties: BTreeMap::new(),
+            };
+            let idx = shown.graph.add_node(info);
+            shown.node_map.insert(id, idx);
+        }
+
+        let mut linked = HashSet::new();
+        for edge in data.graph.edge_references() {
+            let (from, to) = (
+                &shown_as[edge.source().index()],
+                &shown_as[edge.target().index()],
+            );
+            let ends = (shown.node_map[from], shown.node_map[to]);
+            if from == to || !linked.insert(ends) {
+                continue;
+            }
+            // Edges merged into a cluster's stand for many, so lose their details
+            let merged = from.starts_with(CLUSTER_PREFIX) || to.starts_with(CLUSTER_PREFIX);
+            let info = if merged {
+                EdgeInfo::default()
+            } else {
+                edge.weight().clone()
+            };
+            shown.graph.add_edge(ends.0, ends.1, info);
+        }
+        shown
+    }
+}
+
+pub struct AggregatePlugin;
+
+impl Plugin for AggregatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Aggregation>()
+            .add_systems(Update, toggle_cluster);
+    }
+}
+
+/// Expands the hovered cluster into its nodes, or collapses the hovered
+/// hub's cluster again
+pub fn toggle_cluster(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    search_state: Res<SearchState>,
+    hover: Res<HoverState>,
+    graph_data: Option<Res<types::GraphData>>,
+    node_query: Query<&GraphNode>,
+    mut aggregation: ResMut<Aggregation>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::ToggleCluster, &keyboard_input) {
+        return;
+    }
+    let (Some(node), Some(graph_data)) = (
+        hover.node.and_then(|entity| node_query.get(entity).ok()),
+        graph_data,
+    ) else {
+        return;
+    };
+    let is_cluster = graph_data
+        .graph
+        .node_weight(node.index)
+        .is_some_and(|info| info.node_type.as_deref() == Some(CLUSTER_TYPE));
+    if let Some(hub) = node.id.strip_prefix(CLUSTER_PREFIX).filter(|_| is_cluster) {
+        aggregation.expanded.insert(hub.to_string());
+    } else if aggregation.expanded.contains(&node.id) {
+        aggregation.expanded.remove(&node.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EventNodeInfo, GraphEvent};
+    use crate::graph_state::GraphState;
+
+    /// Two hubs with `leaves` nodes around each, a bridge between them and
+    /// a pair of nodes connected to neither
+    fn two_stars(leaves: usize) -> GraphData {
+        let mut state = GraphState::new();
+        let mut add_node = |id: String| {
+            let info = EventNodeInfo {
+                name: id.clone(),
+                node_type: None,
+                level: 0,
+                ..Default::default()
+            };
+            state.process_event(GraphEvent::AddNode { id, info });
+        };
+        for id in ["a", "b", "x", "y"] {
+            add_node(id.to_string());
+        }
+        for hub in ["a", "b"] {
+            for i in 0..leaves {
+                add_node(format!("{hub}{i}"));
+            }
+        }
+        let mut add_edge = |from: String, to: String| {
+            state.process_event(GraphEvent::AddEdge { from, to });
+        };
+        for hub in ["a", "b"] {
+            for i in 0..leaves {
+                add_edge(hub.to_string(), format!("{hub}{i}"));
+            }
+        }
+        add_edge("a0".to_string(), "b0".to_string());
+        add_edge("x".to_string(), "y".to_string());
+        state.as_graph_data()
+    }
+
+    #[test]
+    fn test_big_graphs_show_hubs_and_clusters() {
+        let data = two_stars(10);
+        let mut aggregation = Aggregation {
+            threshold: 20,
+            hubs: 2,
+            ..default()
+        };
+
+        let shown = aggregation.shown(data.clone());
+        let mut ids: Vec<_> = shown.node_map.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, ["a", "b", "cluster:*", "cluster:a", "cluster:b"]);
+        let name = |id: &str| shown.graph[shown.node_map[id]].name.clone();
+        assert_eq!(name("cluster:a"), "a +10");
+        assert_eq!(name("cluster:*"), "unconnected +2");
+        // The bridge between the stars joins their clusters, once
+        let edge = |from: &str, to: &str| {
+            shown
+                .graph
+                .find_edge(shown.node_map[from], shown.node_map[to])
+                .is_some()
+        };
+        assert!(edge("a", "cluster:a"));
+        assert!(edge("cluster:a", "cluster:b"));
+        assert_eq!(shown.graph.edge_count(), 3);
+
+        aggregation.expanded.insert("a".to_string());
+        let shown = aggregation.shown(data.clone());
+        assert_eq!(shown.graph.node_count(), 2 + 10 + 2);
+        assert!(shown.node_map.contains_key("a3"));
+        assert!(!shown.node_map.contains_key("cluster:a"));
+
+        // Small graphs are shown whole
+        aggregation.threshold = 0;
+        assert_eq!(aggregation.shown(data).graph.node_count(), 24);
+    }
+}
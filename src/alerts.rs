@@ -0,0 +1,278 @@
+use crate::events::AlertState;
+use crate::gauges::{FlowParticle, spawn_flow_particles};
+use crate::graph_state;
+use crate::keybindings::{Action, KeyBindings};
+use crate::status::StatusMessage;
+use crate::types::{GraphData, GraphEdge, GraphNode, ReducedMotion, ScreenClusters, SearchState};
+use crate::visualization::refresh_graph_scene;
+use bevy::pbr::NotShadowCaster;
+use bevy::picking::Pickable;
+use bevy::prelude::*;
+use petgraph::graph::NodeIndex;
+use std::collections::HashSet;
+
+/// Critical rings blink this many times per second
+const BLINK_HZ: f32 = 2.0;
+
+/// Hides everything but alerting nodes and their neighbors while active
+#[derive(Resource, Default)]
+pub struct AlertFilter {
+    pub active: bool,
+}
+
+/// Colored ring around a node with an alert state
+#[derive(Component)]
+pub struct AlertRing {
+    pub state: AlertState,
+}
+
+/// Rings around nodes in the state set by `SetNodeState` events, and a filter
+/// down to the nodes that need attention
+pub struct AlertsPlugin;
+
+impl Plugin for AlertsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AlertFilter>().add_systems(
+            Update,
+            (
+                spawn_alert_rings.after(refresh_graph_scene),
+                update_alert_rings,
+                toggle_alert_filter,
+                filter_alerting_nodes
+                    .run_if(
+                        resource_changed::<AlertFilter>
+                            .or(resource_changed::<ScreenClusters>)
+                            .or(resource_exists_and_changed::<GraphData>),
+                    )
+                    .after(toggle_alert_filter)
+                    .after(spawn_flow_particles),
+            ),
+        );
+    }
+}
+
+const fn ring_color(state: AlertState) -> Color {
+    match state {
+        AlertState::Ok => Color::srgb(0.2, 0.85, 0.3),
+        AlertState::Warn => Color::srgb(1.0, 0.75, 0.1),
+        AlertState::Critical => Color::srgb(1.0, 0.15, 0.1),
+        AlertState::Unknown => Color::srgb(0.55, 0.55, 0.6),
+    }
+}
+
+/// Alerting nodes and their neighbors in either direction
+pub fn alerting_neighborhood(data: &graph_state::GraphData) -> HashSet<NodeIndex> {
+    data.graph
+        .node_indices()
+        .filter(|&node| data.graph[node].state.is_some_and(AlertState::is_alerting))
+        .flat_map(|node| std::iter::once(node).chain(data.graph.neighbors_undirected(node)))
+        .collect()
+}
+
+/// Gives each new node with an alert state a ring in that state's color
+#[allow(clippy::type_complexity)]
+pub fn spawn_alert_rings(
+    mut commands: Commands,
+    graph_data: Option<Res<GraphData>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut ring_assets: Local<Option<(Handle<Mesh>, Vec<Handle<StandardMaterial>>)>>,
+    node_query: Query<(Entity, &GraphNode), Added<GraphNode>>,
+) {
+    let Some(data) = graph_data else {
+        return;
+    };
+    for (entity, node) in &node_query {
+        let Some(state) = data
+            .graph
+            .node_weight(node.index)
+            .and_then(|info| info.state)
+        else {
+            continue;
+        };
+        let (mesh, ring_materials) = ring_assets.get_or_insert_with(|| {
+            let materials = AlertState::ALL
+                .map(|state| {
+                    let color = ring_color(state);
+                    materials.add(StandardMaterial {
+                        base_color: color,
+                        emissive: color.to_linear() * 2.0,
+                        unlit: true,
+                        ..default()
+                    })
+                })
+                .to_vec();
+            (meshes.add(Torus::new(0.7, 0.8)), materials)
+        });
+        commands.entity(entity).with_child((
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(ring_materials[state as usize].clone()),
+            Transform::default(),
+            NotShadowCaster,
+            Pickable::IGNORE,
+            AlertRing { state },
+        ));
+    }
+}
+
+/// Turns rings to face the camera and blinks the critical ones, which stay
+/// lit under reduced motion
+pub fn update_alert_rings(
+    time: Res<Time>,
+    motion: Res<ReducedMotion>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    node_query: Query<&GlobalTransform, With<GraphNode>>,
+    mut ring_query: Query<(&ChildOf, &AlertRing, &mut Transform, &mut Visibility)>,
+) {
+    let Ok(camera_transform) = camera_query.single() else {
+        return;
+    };
+    let camera_rotation = camera_transform.compute_transform().rotation;
+    let blink_on = motion.enabled || (time.elapsed_secs() * BLINK_HZ).fract() < 0.6;
+
+    for (child_of, ring, mut transform, mut visibility) in &mut ring_query {
+        let Ok(node_transform) = node_query.get(child_of.parent()) else {
+            continue;
+        };
+        // The torus lies flat, so tip it up into the camera's view plane
+        let (_, node_rotation, _) = node_transform.to_scale_rotation_translation();
+        transform.rotation = node_rotation.inverse()
+            * camera_rotation
+            * Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+        let shown = ring.state != AlertState::Critical || blink_on;
+        visibility.set_if_neq(if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+/// Turns the alert filter on and off
+pub fn toggle_alert_filter(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    search_state: Res<SearchState>,
+    graph_data: Option<Res<GraphData>>,
+    time: Res<Time>,
+    mut filter: ResMut<AlertFilter>,
+    mut message: ResMut<StatusMessage>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::ToggleAlertFilter, &keyboard_input) {
+        return;
+    }
+    filter.active = !filter.active;
+    let text = if filter.active {
+        let alerting = graph_data.map_or(0, |data| {
+            data.graph
+                .node_weights()
+                .filter(|info| info.state.is_some_and(AlertState::is_alerting))
+                .count()
+        });
+        format!("Showing {alerting} alerting nodes and their neighbors")
+    } else {
+        "Showing all nodes".to_string()
+    };
+    message.show(text, &time);
+}
+
+/// Hides nodes outside the alerting neighborhood, along with edges and flow
+/// particles that touch them
+///
+/// Nodes folded into a screen cluster's badge are hidden too, with the edges
+/// between them.
+#[allow(clippy::type_complexity)]
+pub fn filter_alerting_nodes(
+    filter: Res<AlertFilter>,
+    clusters: Res<ScreenClusters>,
+    graph_data: Option<Res<GraphData>>,
+    mut node_query: Query<(&GraphNode, &mut Visibility)>,
+    mut edge_query: Query<(Entity, &GraphEdge, &mut Visibility), Without<GraphNode>>,
+    mut particle_query: Query<
+        (&FlowParticle, &mut Visibility),
+        (Without<GraphNode>, Without<GraphEdge>),
+    >,
+) {
+    let shown = graph_data
+        .filter(|_| filter.active)
+        .map(|data| alerting_neighborhood(&data));
+    let is_shown = |node: NodeIndex| shown.as_ref().is_none_or(|shown| shown.contains(&node));
+    let visibility_of = |shown: bool| {
+        if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        }
+    };
+
+    let folded = |node: NodeIndex| clusters.hidden.contains(&node);
+    for (node, mut visibility) in &mut node_query {
+        visibility.set_if_neq(visibility_of(is_shown(node.index) && !folded(node.index)));
+    }
+    let mut hidden_edges = HashSet::new();
+    for (entity, edge, mut visibility) in &mut edge_query {
+        let edge_shown =
+            is_shown(edge.from) && is_shown(edge.to) && !(folded(edge.from) && folded(edge.to));
+        if !edge_shown {
+            hidden_edges.insert(entity);
+        }
+        visibility.set_if_neq(visibility_of(edge_shown));
+    }
+    // Shafts and arrow heads inherit their edge's visibility
+    for (particle, mut visibility) in &mut particle_query {
+        visibility.set_if_neq(visibility_of(!hidden_edges.contains(&particle.edge)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::GraphEvent;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_alerting_neighborhood() {
+        let content = r"digraph {
+            web -> api
+            api -> db
+            db -> disk
+            batch -> queue
+        }";
+        let (mut state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let set = |id: &str, state| GraphEvent::SetNodeState {
+            id: id.to_string(),
+            state,
+        };
+        state.process_events(vec![
+            set("db", Some(AlertState::Critical)),
+            set("queue", Some(AlertState::Ok)),
+            set("batch", Some(AlertState::Warn)),
+        ]);
+        // States survive updates to the node itself
+        state.process_event(GraphEvent::UpdateNode {
+            id: "db".to_string(),
+            info: (state.get_node("db").unwrap()).into(),
+        });
+
+        let data = state.as_graph_data();
+        let shown = alerting_neighborhood(&data);
+        let mut names: Vec<_> = shown
+            .iter()
+            .map(|&node| data.graph[node].name.as_str())
+            .collect();
+        names.sort_unstable();
+        assert_eq!(names, ["api", "batch", "db", "disk", "queue"]);
+
+        // Clearing a state undoes to the state it had
+        let clear = set("db", None);
+        let undo = state.inverse_of(&clear);
+        state.process_event(clear);
+        assert!(state.get_node("db").unwrap().state.is_none());
+        state.process_events(undo);
+        assert_eq!(
+            state.get_node("db").unwrap().state,
+            Some(AlertState::Critical)
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Anti-aliasing of the 3D view: multisampling by default, or temporal
+//! anti-aliasing, which calms the shimmer of thin edges better but can smear
+//! moving nodes a little
+
+use bevy::core_pipeline::experimental::taa::{TemporalAntiAliasPlugin, TemporalAntiAliasing};
+use bevy::core_pipeline::prepass::{DepthPrepass, MotionVectorPrepass};
+use bevy::prelude::*;
+use bevy::render::camera::{MipBias, TemporalJitter};
+
+/// Multisampling choices, from none to the most samples per pixel
+pub const MSAA_CHOICES: [Msaa; 4] = [Msaa::Off, Msaa::Sample2, Msaa::Sample4, Msaa::Sample8];
+
+/// How the camera smooths jagged edges
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AntiAliasing {
+    pub msaa: Msaa,
+    /// Temporal anti-aliasing, which turns multisampling off while it's on
+    pub taa: bool,
+}
+
+impl AntiAliasing {
+    /// Multisampling and whether TAA is on for a camera with `projection`
+    ///
+    /// TAA doesn't work with orthographic projections, so the flat view
+    /// multisamples instead.
+    pub const fn for_projection(self, projection: &Projection) -> (Msaa, bool) {
+        let taa = self.taa && !matches!(projection, Projection::Orthographic(_));
+        (if taa { Msaa::Off } else { self.msaa }, taa)
+    }
+}
+
+/// Name of a multisampling choice, as `--msaa` takes it
+pub const fn msaa_name(msaa: Msaa) -> &'static str {
+    match msaa {
+        Msaa::Off => "off",
+        Msaa::Sample2 => "2",
+        Msaa::Sample4 => "4",
+        Msaa::Sample8 => "8",
+    }
+}
+
+/// Parses a `--msaa` value: off, 2, 4 or 8
+pub fn parse_msaa(s: &str) -> Result<Msaa, String> {
+    MSAA_CHOICES
+        .into_iter()
+        .find(|&msaa| msaa_name(msaa).eq_ignore_ascii_case(s))
+        .ok_or_else(|| format!("unknown MSAA '{s}' (expected off, 2, 4 or 8)"))
+}
+
+/// Keeps the camera's anti-aliasing in step with the settings
+pub struct AntiAliasingPlugin;
+
+impl Plugin for AntiAliasingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(TemporalAntiAliasPlugin)
+            .init_resource::<AntiAliasing>()
+            .add_systems(Update, apply_anti_aliasing);
+    }
+}
+
+/// Sets the multisampling of each 3D camera and adds or removes TAA, only
+/// touching cameras whose anti-aliasing is out of date
+#[allow(clippy::type_complexity)]
+pub fn apply_anti_aliasing(
+    mut commands: Commands,
+    anti_aliasing: Res<AntiAliasing>,
+    camera_query: Query<
+        (
+            Entity,
+            &Projection,
+            Option<&Msaa>,
+            Has<TemporalAntiAliasing>,
+        ),
+        With<Camera3d>,
+    >,
+) {
+    for (camera, projection, msaa, has_taa) in &camera_query {
+        let (wanted_msaa, wanted_taa) = anti_aliasing.for_projection(projection);
+        let mut camera = commands.entity(camera);
+        if msaa != Some(&wanted_msaa) {
+            camera.insert(wanted_msaa);
+        }
+        if wanted_taa && !has_taa {
+            camera.insert(TemporalAntiAliasing::default());
+        } else if !wanted_taa && has_taa {
+            // Along with what TAA brought in, so the prepasses stop running
+            camera.remove::<(
+                TemporalAntiAliasing,
+                TemporalJitter,
+                MipBias,
+                DepthPrepass,
+                MotionVectorPrepass,
+            )>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_msaa() {
+        assert_eq!(parse_msaa("OFF"), Ok(Msaa::Off));
+        assert_eq!(parse_msaa("8"), Ok(Msaa::Sample8));
+        assert!(parse_msaa("16").is_err());
+    }
+
+    #[test]
+    fn test_taa_turns_multisampling_off_outside_the_flat_view() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(AntiAliasing {
+                msaa: Msaa::Sample8,
+                taa: true,
+            })
+            .add_systems(Update, apply_anti_aliasing);
+        let camera = app
+            .world_mut()
+            .spawn((Camera3d::default(), Projection::default()))
+            .id();
+        app.update();
+
+        let camera_state = |app: &App| {
+            let camera = app.world().entity(camera);
+            (
+                camera.get::<Msaa>().copied(),
+                camera.contains::<TemporalAntiAliasing>(),
+            )
+        };
+        assert_eq!(camera_state(&app), (Some(Msaa::Off), true));
+
+        *app.world_mut().get_mut::<Projection>(camera).unwrap() =
+            Projection::Orthographic(OrthographicProjection::default_3d());
+        app.update();
+        assert_eq!(camera_state(&app), (Some(Msaa::Sample8), false));
+        assert!(!app.world().entity(camera).contains::<MotionVectorPrepass>());
+    }
+}
@@ -0,0 +1,203 @@
+use crate::events::AlertState;
+use crate::live::LiveEvents;
+use crate::types::GraphData;
+use crate::visualization::refresh_graph_scene;
+use bevy::audio::{Pitch, Volume};
+use bevy::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::Duration;
+
+/// Volume of a cue given without one
+pub const DEFAULT_VOLUME: f32 = 0.3;
+
+/// Shortest time between two plays of the same cue, so a burst of changes
+/// sounds once
+const MIN_GAP_SECS: f32 = 0.5;
+
+/// A kind of live change that can play a sound
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Cue {
+    /// A node was added
+    Add,
+    /// A node was removed
+    Remove,
+    /// A node went into the warn or critical state
+    Alert,
+}
+
+impl Cue {
+    pub const ALL: [Self; 3] = [Self::Add, Self::Remove, Self::Alert];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Remove => "remove",
+            Self::Alert => "alert",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|cue| cue.name().eq_ignore_ascii_case(name))
+    }
+
+    /// A short tone: high for additions, low for removals and longer for alerts
+    fn tone(self) -> Pitch {
+        let (frequency, millis) = match self {
+            Self::Add => (880.0, 70),
+            Self::Remove => (440.0, 70),
+            Self::Alert => (660.0, 250),
+        };
+        Pitch::new(frequency, Duration::from_millis(millis))
+    }
+}
+
+/// Parses an `--audio-cue` value of the form `KIND` or `KIND=VOLUME`
+pub fn parse_cue(s: &str) -> Result<(Cue, f32), String> {
+    let (name, volume) = s.split_once('=').unwrap_or((s, ""));
+    let names = Cue::ALL.map(Cue::name).join(", ");
+    let cue =
+        Cue::parse(name).ok_or_else(|| format!("unknown cue '{name}', expected one of {names}"))?;
+    let volume = if volume.is_empty() {
+        DEFAULT_VOLUME
+    } else {
+        volume
+            .parse::<f32>()
+            .ok()
+            .filter(|volume| (0.0..=1.0).contains(volume))
+            .ok_or_else(|| format!("volume must be between 0 and 1, got '{volume}'"))?
+    };
+    Ok((cue, volume))
+}
+
+/// The cues that play, and how loud
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct AudioCues(pub BTreeMap<Cue, f32>);
+
+impl Default for AudioCues {
+    fn default() -> Self {
+        Self(Cue::ALL.map(|cue| (cue, DEFAULT_VOLUME)).into())
+    }
+}
+
+/// Plays subtle sounds as live sources add and remove nodes and raise
+/// alerts, for dashboards that aren't watched all the time
+pub struct AudioCuesPlugin;
+
+impl Plugin for AudioCuesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AudioCues>().add_systems(
+            Update,
+            play_live_cues.after(refresh_graph_scene).run_if(
+                resource_exists::<LiveEvents>.and(resource_exists_and_changed::<GraphData>),
+            ),
+        );
+    }
+}
+
+/// Whether each node is alerting, by id
+fn alerting_by_id(data: &GraphData) -> HashMap<String, bool> {
+    data.node_map
+        .iter()
+        .map(|(id, &index)| {
+            let alerting = data.graph[index].state.is_some_and(AlertState::is_alerting);
+            (id.clone(), alerting)
+        })
+        .collect()
+}
+
+/// The cues for going from one graph to the next, given whether each node
+/// is alerting
+#[allow(clippy::implicit_hasher)]
+pub fn cues_between(
+    before: &HashMap<String, bool>,
+    after: &HashMap<String, bool>,
+) -> BTreeSet<Cue> {
+    let mut cues = BTreeSet::new();
+    for (id, &alerting) in after {
+        let was = before.get(id);
+        if was.is_none() {
+            cues.insert(Cue::Add);
+        }
+        if alerting && was != Some(&true) {
+            cues.insert(Cue::Alert);
+        }
+    }
+    if before.keys().any(|id| !after.contains_key(id)) {
+        cues.insert(Cue::Remove);
+    }
+    cues
+}
+
+/// Plays the cues for the latest live changes; the graph first shown is
+/// taken as it is, silently
+#[allow(clippy::implicit_hasher)]
+pub fn play_live_cues(
+    mut commands: Commands,
+    time: Res<Time>,
+    graph_data: Res<GraphData>,
+    cues: Res<AudioCues>,
+    mut tones: ResMut<Assets<Pitch>>,
+    mut seen: Local<Option<HashMap<String, bool>>>,
+    mut last_played: Local<HashMap<Cue, f32>>,
+) {
+    let now = time.elapsed_secs();
+    let after = alerting_by_id(&graph_data);
+    let due = seen
+        .as_ref()
+        .map(|before| cues_between(before, &after))
+        .unwrap_or_default();
+    *seen = Some(after);
+    for cue in due {
+        let Some(&volume) = cues.0.get(&cue) else {
+            continue;
+        };
+        if last_played
+            .get(&cue)
+            .is_some_and(|&played| now - played < MIN_GAP_SECS)
+        {
+            continue;
+        }
+        last_played.insert(cue, now);
+        commands.spawn((
+            AudioPlayer(tones.add(cue.tone())),
+            PlaybackSettings::DESPAWN.with_volume(Volume::Linear(volume)),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cues_follow_live_changes() {
+        let nodes = |nodes: &[(&str, bool)]| -> HashMap<String, bool> {
+            nodes
+                .iter()
+                .map(|&(id, alerting)| (id.to_string(), alerting))
+                .collect()
+        };
+        let before = nodes(&[("a", false), ("b", false)]);
+
+        assert!(cues_between(&before, &before).is_empty());
+        assert_eq!(
+            cues_between(&before, &nodes(&[("a", true), ("c", false)])),
+            BTreeSet::from(Cue::ALL)
+        );
+        assert_eq!(
+            cues_between(&before, &nodes(&[("a", false), ("b", false), ("c", true)])),
+            BTreeSet::from([Cue::Add, Cue::Alert])
+        );
+        // Staying in alert, or recovering, is quiet
+        let alerting = nodes(&[("a", true), ("b", false)]);
+        assert!(cues_between(&alerting, &alerting).is_empty());
+        assert!(cues_between(&alerting, &before).is_empty());
+
+        assert_eq!(parse_cue("alert"), Ok((Cue::Alert, DEFAULT_VOLUME)));
+        assert_eq!(parse_cue("Add=0.8"), Ok((Cue::Add, 0.8)));
+        assert!(parse_cue("remove=2").is_err());
+        assert!(parse_cue("beep").is_err());
+    }
+}
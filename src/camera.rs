@@ -1,6 +1,32 @@
-use crate::types::SearchState;
+use crate::grid::ground_height;
+use crate::hover::NODE_PICK_RADIUS;
+use crate::keybindings::{Action, KeyBindings};
+use crate::selection::CLICK_DISTANCE;
+use crate::types::{
+    CameraSettings, EditState, GraphNode, GridSettings, HoverState, LayoutSettings, SearchState,
+};
+use bevy::core_pipeline::bloom::Bloom;
 use bevy::prelude::*;
-use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
+use bevy::render::camera::ScalingMode;
+use bevy::window::PrimaryWindow;
+use bevy_panorbit_camera::{EguiWantsFocus, PanOrbitCamera, PanOrbitCameraPlugin};
+use std::f32::consts::FRAC_PI_2;
+
+/// Pitch the camera starts at, looking slightly down on the graph
+const START_PITCH: f32 = 0.5;
+
+/// Longest time between the two clicks of a double-click
+const DOUBLE_CLICK_SECS: f32 = 0.4;
+
+/// How far from a double-clicked node the camera settles, per unit of the
+/// node's scale
+const FOCUS_DISTANCE: f32 = 6.0;
+
+/// Closest the camera comes to the surface of a node or the ground
+const CLEARANCE: f32 = 0.3;
+
+/// Drift slower than this, in radians or units per second, comes to a stop
+const MIN_DRIFT: f32 = 1e-3;
 
 pub struct CameraPlugin;
 
@@ -8,15 +34,36 @@ impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(PanOrbitCameraPlugin)
             .add_systems(Update, keyboard_camera_controls)
+            .add_systems(Update, match_flattened_layout)
+            .add_systems(
+                Update,
+                apply_camera_tuning.run_if(resource_changed::<CameraSettings>),
+            )
+            .add_systems(Update, drift_after_drag)
+            .add_systems(Update, focus_double_clicked_node)
+            .add_systems(
+                Update,
+                avoid_collisions
+                    .after(keyboard_camera_controls)
+                    .after(drift_after_drag)
+                    .after(focus_double_clicked_node),
+            )
             .add_systems(Update, exit_on_q)
             .add_systems(Update, debug_camera_state);
     }
 }
 
-pub fn setup_camera(commands: &mut Commands, initial_distance: f32, _speed: f32) {
+pub fn setup_camera(commands: &mut Commands, settings: &CameraSettings) {
+    let initial_distance = settings.distance;
     // Spawn camera with PanOrbitCamera component
     commands.spawn((
         Camera3d::default(),
+        // HDR lets highlight outlines glow through bloom
+        Camera {
+            hdr: true,
+            ..default()
+        },
+        Bloom::NATURAL,
         Transform::from_translation(Vec3::new(0.0, initial_distance * 0.5, initial_distance))
             .looking_at(Vec3::ZERO, Vec3::Y),
         PanOrbitCamera {
@@ -24,42 +71,300 @@ pub fn setup_camera(commands: &mut Commands, initial_distance: f32, _speed: f32)
             focus: Vec3::ZERO,
             radius: Some(initial_distance),
             yaw: Some(0.0),
-            pitch: Some(0.5),
+            pitch: Some(START_PITCH),
 
             // Initialize targets to match
             target_focus: Vec3::ZERO,
             target_radius: initial_distance,
             target_yaw: 0.0,
-            target_pitch: 0.5,
+            target_pitch: START_PITCH,
 
             // Mouse button configuration
             button_orbit: MouseButton::Left,
             button_pan: MouseButton::Right,
 
-            // Sensitivity settings
-            pan_sensitivity: 1.0,
-            orbit_sensitivity: 1.0,
-            zoom_sensitivity: 0.5,
-
-            // Smoothing
-            pan_smoothness: 0.8,
-            orbit_smoothness: 0.8,
-            zoom_smoothness: 0.8,
-
-            // Limits
-            pitch_upper_limit: Some(1.4),
-            pitch_lower_limit: Some(-1.4),
-
             // Make sure it's enabled
             enabled: true,
 
-            ..default()
+            ..tuned(PanOrbitCamera::default(), settings)
         },
     ));
 }
 
-fn debug_camera_state(cameras: Query<&PanOrbitCamera>, keyboard: Res<ButtonInput<KeyCode>>) {
-    if keyboard.just_pressed(KeyCode::KeyD) {
+/// The camera with the sensitivity, smoothing and pitch limits of `settings`
+fn tuned(mut cam: PanOrbitCamera, settings: &CameraSettings) -> PanOrbitCamera {
+    cam.pan_sensitivity = settings.pan_sensitivity;
+    cam.orbit_sensitivity = settings.orbit_sensitivity;
+    cam.zoom_sensitivity = settings.zoom_sensitivity;
+    cam.pan_smoothness = settings.smoothness;
+    cam.orbit_smoothness = settings.smoothness;
+    cam.zoom_smoothness = settings.smoothness;
+    cam.pitch_upper_limit = Some(settings.pitch_limit);
+    cam.pitch_lower_limit = Some(-settings.pitch_limit);
+    cam
+}
+
+/// Retunes the camera when its settings are edited, leaving the pitch of a
+/// flattened view locked
+fn apply_camera_tuning(
+    settings: Res<CameraSettings>,
+    layout: Res<LayoutSettings>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+) {
+    for mut cam in &mut cameras {
+        let limits = (cam.pitch_lower_limit, cam.pitch_upper_limit);
+        *cam = tuned(*cam, &settings);
+        if layout.flatten {
+            (cam.pitch_lower_limit, cam.pitch_upper_limit) = limits;
+        }
+    }
+}
+
+/// Looks straight down on a flattened layout through an orthographic lens,
+/// with the left button panning instead of orbiting, and goes back to the
+/// orbiting perspective camera when the layout stands up again
+///
+/// The orthographic lens is scaled so the camera's radius is the height of
+/// the view, which keeps zooming working the same way.
+fn match_flattened_layout(
+    layout: Res<LayoutSettings>,
+    settings: Res<CameraSettings>,
+    mut flat: Local<bool>,
+    mut cameras: Query<(&mut PanOrbitCamera, &mut Projection)>,
+) {
+    if layout.flatten == *flat {
+        return;
+    }
+    *flat = layout.flatten;
+    for (mut cam, mut projection) in &mut cameras {
+        if layout.flatten {
+            *projection = Projection::Orthographic(OrthographicProjection {
+                scaling_mode: ScalingMode::FixedVertical {
+                    viewport_height: 1.0,
+                },
+                ..OrthographicProjection::default_3d()
+            });
+            cam.target_yaw = 0.0;
+            cam.target_pitch = FRAC_PI_2;
+            (cam.yaw_lower_limit, cam.yaw_upper_limit) = (Some(0.0), Some(0.0));
+            (cam.pitch_lower_limit, cam.pitch_upper_limit) = (Some(FRAC_PI_2), Some(FRAC_PI_2));
+            (cam.button_pan, cam.button_orbit) = (MouseButton::Left, MouseButton::Right);
+        } else {
+            *projection = Projection::Perspective(PerspectiveProjection::default());
+            cam.target_pitch = START_PITCH;
+            (cam.yaw_lower_limit, cam.yaw_upper_limit) = (None, None);
+            (cam.pitch_lower_limit, cam.pitch_upper_limit) =
+                (Some(-settings.pitch_limit), Some(settings.pitch_limit));
+            (cam.button_pan, cam.button_orbit) = (MouseButton::Right, MouseButton::Left);
+        }
+        cam.force_update = true;
+    }
+}
+
+/// Double-clicking a node makes it the point the camera orbits, and moves
+/// the camera in or out to frame it
+#[allow(clippy::too_many_arguments)]
+pub fn focus_double_clicked_node(
+    mouse: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    egui_focus: Option<Res<EguiWantsFocus>>,
+    hover: Res<HoverState>,
+    edit: Res<EditState>,
+    time: Res<Time>,
+    node_query: Query<&GlobalTransform, With<GraphNode>>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+    mut pressed_at: Local<Option<Vec2>>,
+    mut last_click: Local<Option<(Entity, f32)>>,
+) {
+    let cursor = window_query.single().ok().and_then(Window::cursor_position);
+    // Edit mode uses clicks for its own gestures
+    if edit.active || egui_focus.is_some_and(|focus| focus.curr) {
+        *pressed_at = None;
+        return;
+    }
+    if mouse.just_pressed(MouseButton::Left) {
+        *pressed_at = cursor;
+    }
+    if !mouse.just_released(MouseButton::Left) {
+        return;
+    }
+    // Drags orbit the camera rather than click
+    let Some((from, to)) = pressed_at.take().zip(cursor) else {
+        return;
+    };
+    if from.distance(to) > CLICK_DISTANCE {
+        return;
+    }
+
+    let now = time.elapsed_secs();
+    let previous = last_click.take();
+    let Some(node) = hover.node else {
+        return;
+    };
+    let double =
+        previous.is_some_and(|(clicked, at)| clicked == node && now - at <= DOUBLE_CLICK_SECS);
+    if !double {
+        *last_click = Some((node, now));
+        return;
+    }
+    let Ok(transform) = node_query.get(node) else {
+        return;
+    };
+    let (scale, _, center) = transform.to_scale_rotation_translation();
+    for mut camera in &mut cameras {
+        camera.target_focus = center;
+        camera.target_radius = (FOCUS_DISTANCE * scale.max_element()).clamp(2.0, 100.0);
+    }
+}
+
+/// How fast a dragged camera was turning and moving when last seen
+#[derive(Default)]
+pub struct Drift {
+    /// Targets of the previous frame of a drag
+    last: Option<(Vec2, Vec3)>,
+    /// Yaw and pitch per second
+    turn: Vec2,
+    /// Focus movement per second
+    pan: Vec3,
+}
+
+/// Lets the camera glide on after a drag, the way it was going when the
+/// mouse was released, slowing down over the configured inertia
+pub fn drift_after_drag(
+    mouse: Res<ButtonInput<MouseButton>>,
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+    mut drift: Local<Drift>,
+) {
+    let delta = time.delta_secs();
+    let Ok(mut cam) = cameras.single_mut() else {
+        return;
+    };
+    if delta <= 0.0 {
+        return;
+    }
+    let angles = Vec2::new(cam.target_yaw, cam.target_pitch);
+    if !cam.enabled || mouse.any_just_pressed([cam.button_orbit, cam.button_pan]) {
+        *drift = Drift::default();
+    }
+    if cam.enabled && mouse.any_pressed([cam.button_orbit, cam.button_pan]) {
+        if let Some((last_angles, last_focus)) = drift.last {
+            // Averaged over a couple of frames, as mouse motion comes in bursts
+            drift.turn = drift.turn.lerp((angles - last_angles) / delta, 0.5);
+            drift.pan = drift.pan.lerp((cam.target_focus - last_focus) / delta, 0.5);
+        }
+        drift.last = Some((angles, cam.target_focus));
+        return;
+    }
+    drift.last = None;
+    if settings.inertia <= 0.0 || drift.turn.length() + drift.pan.length() < MIN_DRIFT {
+        drift.turn = Vec2::ZERO;
+        drift.pan = Vec3::ZERO;
+        return;
+    }
+    let turn = drift.turn * delta;
+    cam.target_yaw += turn.x;
+    cam.target_pitch += turn.y;
+    cam.target_focus += drift.pan * delta;
+    let slowdown = (-delta / settings.inertia).exp();
+    drift.turn *= slowdown;
+    drift.pan *= slowdown;
+}
+
+/// Direction from the focus to a camera at this yaw and pitch
+fn orbit_direction(yaw: f32, pitch: f32) -> Vec3 {
+    Quat::from_rotation_y(yaw) * Quat::from_rotation_x(-pitch) * Vec3::Z
+}
+
+/// The first distance from `focus` along `direction` at or past `radius`
+/// that's clear of every sphere
+fn clear_radius(
+    focus: Vec3,
+    direction: Vec3,
+    radius: f32,
+    spheres: impl IntoIterator<Item = (Vec3, f32)>,
+) -> f32 {
+    // Stretches of the line inside each sphere, nearest first
+    let mut inside: Vec<(f32, f32)> = spheres
+        .into_iter()
+        .filter_map(|(center, sphere_radius)| {
+            let to_center = center - focus;
+            let along = to_center.dot(direction);
+            let miss_sq = sphere_radius.mul_add(
+                sphere_radius,
+                along.mul_add(along, -to_center.length_squared()),
+            );
+            (miss_sq > 0.0).then(|| (along - miss_sq.sqrt(), along + miss_sq.sqrt()))
+        })
+        .collect();
+    inside.sort_by(|a, b| a.0.total_cmp(&b.0));
+    inside.into_iter().fold(radius, |radius, (enter, exit)| {
+        if enter <= radius && radius < exit {
+            exit
+        } else {
+            radius
+        }
+    })
+}
+
+/// Lowest pitch that keeps a camera `radius` from `focus_y` at or above `floor`
+fn lowest_pitch(focus_y: f32, radius: f32, floor: f32) -> f32 {
+    ((floor - focus_y) / radius).clamp(-1.0, 1.0).asin()
+}
+
+/// Keeps the camera from passing into nodes, and from going under the
+/// ground unless it may
+///
+/// Only the camera's targets are moved, so it eases away instead of jumping.
+pub fn avoid_collisions(
+    layout: Res<LayoutSettings>,
+    grid: Res<GridSettings>,
+    node_query: Query<&GlobalTransform, With<GraphNode>>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+) {
+    // Looking straight down through an orthographic lens, nothing is in the way
+    if layout.flatten || node_query.is_empty() {
+        return;
+    }
+    let ground = ground_height(node_query.iter().map(GlobalTransform::translation));
+    for mut cam in &mut cameras {
+        if !grid.below_ground {
+            let floor = ground + CLEARANCE;
+            if cam.target_focus.y < floor {
+                cam.target_focus.y = floor;
+            }
+            let lowest = lowest_pitch(cam.target_focus.y, cam.target_radius, floor);
+            if cam.target_pitch < lowest {
+                cam.target_pitch = lowest;
+            }
+        }
+
+        let direction = orbit_direction(cam.target_yaw, cam.target_pitch);
+        let radius = clear_radius(
+            cam.target_focus,
+            direction,
+            cam.target_radius,
+            node_query.iter().map(|transform| {
+                let (scale, _, center) = transform.to_scale_rotation_translation();
+                (
+                    center,
+                    NODE_PICK_RADIUS.mul_add(scale.max_element(), CLEARANCE),
+                )
+            }),
+        );
+        if radius > cam.target_radius {
+            cam.target_radius = radius;
+        }
+    }
+}
+
+fn debug_camera_state(
+    cameras: Query<&PanOrbitCamera>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+) {
+    if bindings.just_pressed(Action::DebugCamera, &keyboard) {
         for cam in &cameras {
             eprintln!("Camera state:");
             eprintln!("  enabled: {}", cam.enabled);
@@ -76,17 +381,20 @@ pub fn keyboard_camera_controls(
     time: Res<Time>,
     mut cameras: Query<&mut PanOrbitCamera>,
     search_state: Res<SearchState>,
+    camera_settings: Res<CameraSettings>,
+    bindings: Res<KeyBindings>,
+    edit: Res<EditState>,
 ) {
     for mut cam in &mut cameras {
-        // Disable camera when searching
-        cam.enabled = !search_state.active;
+        // Disable camera when searching, and hold it still while dragging an edge
+        cam.enabled = !search_state.active && edit.drag_from.is_none();
 
         if search_state.active {
             continue;
         }
 
         let delta = time.delta_secs();
-        let pan_speed = 5.0 * delta;
+        let pan_speed = camera_settings.speed * delta;
         let rotation_speed = 2.0 * delta;
         let zoom_speed = 10.0 * delta;
 
@@ -96,65 +404,204 @@ pub fn keyboard_camera_controls(
         // More intuitive controls:
         // Arrow keys without shift = pan camera view
         // Arrow keys with shift = orbit around focus point
+        let pressed = |action| bindings.pressed(action, &keyboard_input);
 
-        if keyboard_input.pressed(KeyCode::ShiftLeft) || keyboard_input.pressed(KeyCode::ShiftRight)
-        {
-            // Orbit mode: Rotate camera around the focus point
-            if keyboard_input.pressed(KeyCode::ArrowLeft) {
-                cam.target_yaw -= rotation_speed;
-            }
-            if keyboard_input.pressed(KeyCode::ArrowRight) {
-                cam.target_yaw += rotation_speed;
-            }
-            if keyboard_input.pressed(KeyCode::ArrowUp) {
-                cam.target_pitch = (cam.target_pitch + rotation_speed).min(1.4);
-            }
-            if keyboard_input.pressed(KeyCode::ArrowDown) {
-                cam.target_pitch = (cam.target_pitch - rotation_speed).max(-1.4);
-            }
-        } else {
-            // Pan mode: Move the camera and focus together
-            // Calculate movement in world space based on camera orientation
-            let forward = Vec3::new(current_yaw.sin(), 0.0, -current_yaw.cos());
-            let right = Vec3::new(current_yaw.cos(), 0.0, current_yaw.sin());
-
-            if keyboard_input.pressed(KeyCode::ArrowUp) {
-                // Move forward (into the scene)
-                cam.target_focus += forward * pan_speed;
-            }
-            if keyboard_input.pressed(KeyCode::ArrowDown) {
-                // Move backward
-                cam.target_focus -= forward * pan_speed;
-            }
-            if keyboard_input.pressed(KeyCode::ArrowLeft) {
-                // Move left
-                cam.target_focus -= right * pan_speed;
-            }
-            if keyboard_input.pressed(KeyCode::ArrowRight) {
-                // Move right
-                cam.target_focus += right * pan_speed;
-            }
+        // Orbit mode: Rotate camera around the focus point
+        if pressed(Action::OrbitLeft) {
+            cam.target_yaw -= rotation_speed;
+        }
+        if pressed(Action::OrbitRight) {
+            cam.target_yaw += rotation_speed;
+        }
+        if pressed(Action::OrbitUp) {
+            cam.target_pitch = (cam.target_pitch + rotation_speed).min(camera_settings.pitch_limit);
+        }
+        if pressed(Action::OrbitDown) {
+            cam.target_pitch =
+                (cam.target_pitch - rotation_speed).max(-camera_settings.pitch_limit);
+        }
+
+        // Pan mode: Move the camera and focus together
+        // Calculate movement in world space based on camera orientation
+        let forward = Vec3::new(current_yaw.sin(), 0.0, -current_yaw.cos());
+        let right = Vec3::new(current_yaw.cos(), 0.0, current_yaw.sin());
+
+        if pressed(Action::PanForward) {
+            // Move forward (into the scene)
+            cam.target_focus += forward * pan_speed;
+        }
+        if pressed(Action::PanBack) {
+            // Move backward
+            cam.target_focus -= forward * pan_speed;
+        }
+        if pressed(Action::PanLeft) {
+            // Move left
+            cam.target_focus -= right * pan_speed;
+        }
+        if pressed(Action::PanRight) {
+            // Move right
+            cam.target_focus += right * pan_speed;
         }
 
         // Zoom with +/- and PageUp/PageDown
-        if keyboard_input.pressed(KeyCode::Equal)
-            || keyboard_input.pressed(KeyCode::NumpadAdd)
-            || keyboard_input.pressed(KeyCode::PageUp)
-        {
+        if pressed(Action::ZoomIn) {
             cam.target_radius = (cam.target_radius - zoom_speed).max(2.0);
         }
-        if keyboard_input.pressed(KeyCode::Minus)
-            || keyboard_input.pressed(KeyCode::NumpadSubtract)
-            || keyboard_input.pressed(KeyCode::PageDown)
-        {
+        if pressed(Action::ZoomOut) {
             cam.target_radius = (cam.target_radius + zoom_speed).min(100.0);
         }
     }
 }
 
-pub fn exit_on_q(keyboard_input: Res<ButtonInput<KeyCode>>, mut exit: EventWriter<AppExit>) {
+pub fn exit_on_q(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut exit: EventWriter<AppExit>,
+) {
     // Only Q exits the application
-    if keyboard_input.just_pressed(KeyCode::KeyQ) {
+    if bindings.just_pressed(Action::Quit, &keyboard_input) {
         exit.write(AppExit::Success);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::NodeIndex;
+
+    #[test]
+    fn test_double_click_focuses_the_node() {
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<HoverState>()
+            .init_resource::<EditState>()
+            .init_resource::<Time>()
+            .add_systems(Update, focus_double_clicked_node);
+
+        let mut window = Window::default();
+        window.set_cursor_position(Some(Vec2::new(100.0, 100.0)));
+        app.world_mut().spawn((window, PrimaryWindow));
+        let camera = app.world_mut().spawn(PanOrbitCamera::default()).id();
+        let node = app
+            .world_mut()
+            .spawn((
+                GraphNode {
+                    id: "a".to_string(),
+                    name: "a".to_string(),
+                    index: NodeIndex::new(0),
+                },
+                GlobalTransform::from(
+                    Transform::from_xyz(4.0, 2.0, 0.0).with_scale(Vec3::splat(0.5)),
+                ),
+            ))
+            .id();
+        app.world_mut().resource_mut::<HoverState>().node = Some(node);
+
+        let click = |app: &mut App| {
+            for pressed in [true, false] {
+                let mut mouse = app.world_mut().resource_mut::<ButtonInput<MouseButton>>();
+                mouse.clear();
+                if pressed {
+                    mouse.press(MouseButton::Left);
+                } else {
+                    mouse.release(MouseButton::Left);
+                }
+                app.update();
+            }
+        };
+        let focus = |app: &App| {
+            app.world()
+                .get::<PanOrbitCamera>(camera)
+                .unwrap()
+                .target_focus
+        };
+
+        click(&mut app);
+        assert_eq!(focus(&app), Vec3::ZERO);
+        click(&mut app);
+        assert_eq!(focus(&app), Vec3::new(4.0, 2.0, 0.0));
+        let radius = app
+            .world()
+            .get::<PanOrbitCamera>(camera)
+            .unwrap()
+            .target_radius;
+        assert!((radius - 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_camera_drifts_after_a_drag() {
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<Time>()
+            .init_resource::<CameraSettings>()
+            .add_systems(Update, drift_after_drag);
+        let camera = app.world_mut().spawn(PanOrbitCamera::default()).id();
+        let step = |app: &mut App, yaw: Option<f32>| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(std::time::Duration::from_millis(100));
+            if let Some(yaw) = yaw {
+                let mut cam = app.world_mut().get_mut::<PanOrbitCamera>(camera).unwrap();
+                cam.target_yaw = yaw;
+            }
+            app.update();
+            app.world_mut()
+                .resource_mut::<ButtonInput<MouseButton>>()
+                .clear();
+            app.world()
+                .get::<PanOrbitCamera>(camera)
+                .unwrap()
+                .target_yaw
+        };
+
+        // Dragged at a steady 1 radian per second
+        app.world_mut()
+            .resource_mut::<ButtonInput<MouseButton>>()
+            .press(MouseButton::Left);
+        for i in 0..10 {
+            #[allow(clippy::cast_precision_loss)]
+            step(&mut app, Some(i as f32 * 0.1));
+        }
+        app.world_mut()
+            .resource_mut::<ButtonInput<MouseButton>>()
+            .release(MouseButton::Left);
+
+        // Glides on, slowing down
+        let released = 0.9;
+        let first = step(&mut app, None) - released;
+        let second = step(&mut app, Some(released + first)) - (released + first);
+        assert!(
+            first > 0.05 && second > 0.0 && second < first,
+            "{first} {second}"
+        );
+
+        // And stops at once without inertia
+        app.world_mut().resource_mut::<CameraSettings>().inertia = 0.0;
+        let yaw = step(&mut app, None);
+        assert!((step(&mut app, None) - yaw).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_camera_stays_clear_of_nodes_and_ground() {
+        // A camera looking along the line from above, at the same height
+        let direction = orbit_direction(0.0, 0.0);
+        assert!(direction.abs_diff_eq(Vec3::Z, 1e-6));
+        let node = |z| (Vec3::new(0.0, 0.0, z), 1.0);
+
+        // Nothing in the way
+        assert!((clear_radius(Vec3::ZERO, direction, 5.0, [node(10.0)]) - 5.0).abs() < 1e-4);
+        // Backed out past a node it zoomed into, and past the one touching it
+        assert!(
+            (clear_radius(Vec3::ZERO, direction, 5.0, [node(7.5), node(5.5)]) - 8.5).abs() < 1e-4
+        );
+        // A node off to the side doesn't matter
+        let aside = (Vec3::new(3.0, 0.0, 5.0), 1.0);
+        assert!((clear_radius(Vec3::ZERO, direction, 5.0, [aside]) - 5.0).abs() < 1e-4);
+
+        // A camera 10 away from a focus 5 above the floor can't look up from
+        // lower than 30 degrees under it
+        let lowest = lowest_pitch(5.0, 10.0, 0.0);
+        assert!((lowest + 30f32.to_radians()).abs() < 1e-4);
+        assert!((orbit_direction(0.0, lowest) * 10.0).y + 5.0 > -1e-4);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::events::GraphEvent;
+use crate::highlight::HighlightKind;
+use crate::theme::Theme;
+use crate::types::{Callout, CaptionBar, GraphNode, ThemedPanel, ThemedText};
+use bevy::prelude::*;
+
+/// Pixels right of and above its node that a callout's left edge is placed
+const CALLOUT_OFFSET: Vec2 = Vec2::new(60.0, -70.0);
+
+/// Widest a caption grows before its text wraps, in pixels
+const CAPTION_WIDTH: f32 = 360.0;
+
+/// Asks for a caption over the scene, from a tour step, a live source or a
+/// script
+///
+/// A caption with a `duration` goes away after that many seconds; one without
+/// stays until the next caption without one replaces it. A caption anchored to
+/// a node is drawn beside it, with a leader line to the node.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ShowCaption {
+    pub text: String,
+    pub duration: Option<f32>,
+    pub anchor_node: Option<String>,
+}
+
+impl ShowCaption {
+    /// The caption a `show_caption` graph event asks for
+    pub fn from_event(event: &GraphEvent) -> Option<Self> {
+        match event {
+            GraphEvent::ShowCaption {
+                text,
+                duration,
+                anchor_node,
+            } => Some(Self {
+                text: text.clone(),
+                duration: *duration,
+                anchor_node: anchor_node.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Shows captions asked for with `ShowCaption` as callout boxes
+pub struct CaptionsPlugin;
+
+impl Plugin for CaptionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ShowCaption>()
+            .add_systems(Startup, setup_caption_bar)
+            .add_systems(
+                Update,
+                (show_captions, expire_captions, place_callouts).chain(),
+            );
+    }
+}
+
+fn setup_caption_bar(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(60.0),
+            left: Val::Px(0.0),
+            right: Val::Px(0.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(8.0),
+            ..default()
+        },
+        Pickable::IGNORE,
+        CaptionBar,
+    ));
+}
+
+/// Spawns a callout for each caption asked for, replacing the sticky caption
+/// on screen with a new one
+pub fn show_captions(
+    mut commands: Commands,
+    mut requests: EventReader<ShowCaption>,
+    time: Res<Time>,
+    theme: Res<Theme>,
+    bar_query: Query<Entity, With<CaptionBar>>,
+    callout_query: Query<(Entity, &Callout)>,
+) {
+    let requests: Vec<_> = requests.read().collect();
+    // Of several sticky captions at once, only the last would be seen
+    let last_sticky = requests
+        .iter()
+        .rposition(|caption| caption.duration.is_none());
+    if last_sticky.is_some() {
+        for (entity, callout) in &callout_query {
+            if callout.until.is_none() {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+
+    let now = time.elapsed_secs();
+    for (index, caption) in requests.into_iter().enumerate() {
+        if caption.duration.is_none() && Some(index) != last_sticky {
+            continue;
+        }
+        let anchored = caption.anchor_node.is_some();
+        let mut callout = commands.spawn((
+            Text::new(caption.text.clone()),
+            TextFont {
+                font_size: 18.0,
+                ..default()
+            },
+            TextColor(theme.text()),
+            Node {
+                position_type: if anchored {
+                    PositionType::Absolute
+                } else {
+                    PositionType::Relative
+                },
+                max_width: Val::Px(CAPTION_WIDTH),
+                padding: UiRect::axes(Val::Px(14.0), Val::Px(8.0)),
+                border: UiRect::all(Val::Px(1.0)),
+                ..default()
+            },
+            BackgroundColor(theme.panel()),
+            BorderColor(HighlightKind::Selection.color()),
+            BorderRadius::all(Val::Px(6.0)),
+            // Anchored callouts are shown once they've been placed
+            if anchored {
+                Visibility::Hidden
+            } else {
+                Visibility::Inherited
+            },
+            Callout {
+                anchor_node: caption.anchor_node.clone(),
+                until: caption.duration.map(|duration| now + duration.max(0.0)),
+            },
+            ThemedText,
+            ThemedPanel,
+        ));
+        if !anchored && let Ok(bar) = bar_query.single() {
+            callout.insert(ChildOf(bar));
+        }
+    }
+}
+
+/// Takes captions down once their time is up
+pub fn expire_captions(
+    mut commands: Commands,
+    time: Res<Time>,
+    callout_query: Query<(Entity, &Callout)>,
+) {
+    let now = time.elapsed_secs();
+    for (entity, callout) in &callout_query {
+        if callout.until.is_some_and(|until| until <= now) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Keeps each anchored callout beside its node, with a line leading from the
+/// node to the callout's edge; callouts whose node is gone or off screen are
+/// hidden
+pub fn place_callouts(
+    mut gizmos: Gizmos,
+    theme: Res<Theme>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    node_query: Query<(&GraphNode, &GlobalTransform)>,
+    mut callout_query: Query<(&Callout, &mut Node, &mut Visibility, &ComputedNode)>,
+) {
+    let Ok((camera, camera_transform)) = camera_query.single() else {
+        return;
+    };
+    for (callout, mut style, mut visibility, computed) in &mut callout_query {
+        let Some(id) = &callout.anchor_node else {
+            continue;
+        };
+        let placed = node_query
+            .iter()
+            .find(|(node, _)| &node.id == id)
+            .map(|(_, transform)| transform.translation())
+            .and_then(|at| {
+                let on_screen = camera.world_to_viewport(camera_transform, at).ok()?;
+                Some((at, on_screen))
+            });
+        let Some((at, on_screen)) = placed else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+
+        let corner = on_screen + CALLOUT_OFFSET;
+        style.left = Val::Px(corner.x);
+        style.top = Val::Px(corner.y);
+        visibility.set_if_neq(Visibility::Visible);
+
+        // Lead to the middle of the callout's left edge, as far from the
+        // camera as the node
+        let height = computed.size().y * computed.inverse_scale_factor();
+        if let Ok(ray) =
+            camera.viewport_to_world(camera_transform, corner + Vec2::new(0.0, height / 2.0))
+        {
+            let to = ray.get_point(ray.origin.distance(at));
+            gizmos.line(at, to, theme.text());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sticky_captions_replace_each_other_and_timed_ones_expire() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<Theme>()
+            .add_event::<ShowCaption>()
+            .add_systems(Startup, setup_caption_bar)
+            .add_systems(Update, (show_captions, expire_captions).chain());
+        let caption = |text: &str, duration, anchor_node: Option<&str>| ShowCaption {
+            text: text.to_string(),
+            duration,
+            anchor_node: anchor_node.map(str::to_string),
+        };
+        let captions = |app: &mut App| {
+            let mut query = app.world_mut().query::<(&Text, &Callout)>();
+            let mut captions: Vec<_> = query
+                .iter(app.world())
+                .map(|(text, callout)| (text.0.clone(), callout.anchor_node.clone()))
+                .collect();
+            captions.sort_unstable();
+            captions
+        };
+
+        app.world_mut().send_event(caption("one", None, None));
+        app.world_mut()
+            .send_event(caption("two", None, Some("gateway")));
+        app.world_mut()
+            .send_event(caption("brief", Some(2.0), None));
+        app.update();
+        assert_eq!(
+            captions(&mut app),
+            [
+                ("brief".to_string(), None),
+                ("two".to_string(), Some("gateway".to_string())),
+            ]
+        );
+
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs(3));
+        app.world_mut().send_event(caption("three", None, None));
+        app.update();
+        assert_eq!(captions(&mut app), [("three".to_string(), None)]);
+
+        // Unanchored captions stack in the bar along the bottom
+        let mut query = app.world_mut().query_filtered::<&ChildOf, With<Callout>>();
+        let bar = query.single(app.world()).unwrap().parent();
+        assert!(app.world().get::<CaptionBar>(bar).is_some());
+    }
+}
@@ -0,0 +1,62 @@
+use crate::scene_build::SceneBuild;
+use bevy::prelude::*;
+use bevy::render::view::screenshot::{Screenshot, ScreenshotCaptured};
+use std::path::PathBuf;
+
+/// Screenshot to save once the scene has had `delay` seconds to settle and
+/// has finished building in, after which the app exits
+#[derive(Resource, Clone)]
+pub struct CaptureRequest {
+    pub path: PathBuf,
+    pub delay: f32,
+}
+
+/// Takes the screenshot asked for by a `CaptureRequest`, if there is one
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            capture_when_settled.run_if(resource_exists::<CaptureRequest>),
+        );
+    }
+}
+
+/// Captures the window once the delay has passed and the scene is built,
+/// saves it and exits
+pub fn capture_when_settled(
+    mut commands: Commands,
+    time: Res<Time>,
+    request: Res<CaptureRequest>,
+    build: Option<Res<SceneBuild>>,
+    mut requested: Local<bool>,
+) {
+    if *requested || time.elapsed_secs() < request.delay || build.is_some() {
+        return;
+    }
+    *requested = true;
+    let path = request.path.clone();
+    commands.spawn(Screenshot::primary_window()).observe(
+        move |trigger: Trigger<ScreenshotCaptured>, mut exit: EventWriter<AppExit>| {
+            let saved = trigger
+                .event()
+                .0
+                .clone()
+                .try_into_dynamic()
+                .map_err(|err| err.to_string())
+                // The alpha channel holds brightness when HDR is on
+                .and_then(|image| image.to_rgb8().save(&path).map_err(|err| err.to_string()));
+            match saved {
+                Ok(()) => {
+                    eprintln!("Saved {}", path.display());
+                    exit.write(AppExit::Success);
+                }
+                Err(err) => {
+                    eprintln!("Error saving '{}': {err}", path.display());
+                    exit.write(AppExit::error());
+                }
+            }
+        },
+    );
+}
@@ -0,0 +1,259 @@
+use crate::types::{Appearing, GraphNode, NodeChange, ReducedMotion, Vanishing};
+use crate::visualization::{refresh_graph_scene, update_edge_positions};
+use bevy::color::Mix;
+use bevy::prelude::*;
+
+/// How long a changed node takes to reach its new look
+const CHANGE_SECS: f32 = 1.0;
+
+/// How much bigger a changed node swells at the peak of its pulse
+const PULSE_SCALE: f32 = 0.35;
+
+/// Glow at the peak of the pulse, relative to the node's color
+const PULSE_GLOW: f32 = 1.5;
+
+/// How long added nodes and edges take to grow in
+const APPEAR_SECS: f32 = 0.5;
+
+/// How long removed nodes and edges take to shrink away
+const VANISH_SECS: f32 = 0.5;
+
+/// Animating live changes: nodes and edges that are added or removed, and
+/// nodes whose type or level is updated
+pub struct ChangesPlugin;
+
+impl Plugin for ChangesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (animate_node_changes, animate_appearing, animate_vanishing)
+                .after(refresh_graph_scene)
+                .before(update_edge_positions),
+        );
+    }
+}
+
+/// Smoothstep from 0 to 1 as `elapsed` goes from 0 to `duration`
+fn ease(elapsed: f32, duration: f32) -> f32 {
+    let t = (elapsed / duration).clamp(0.0, 1.0);
+    t * t * 2.0f32.mul_add(-t, 3.0)
+}
+
+impl Appearing {
+    /// How far grown in, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        ease(self.elapsed, APPEAR_SECS)
+    }
+}
+
+/// Grows added nodes to full size; added edges grow along their length as
+/// they are positioned
+pub fn animate_appearing(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(
+        Entity,
+        &mut Appearing,
+        Option<&mut Transform>,
+        Has<GraphNode>,
+    )>,
+) {
+    for (entity, mut appearing, transform, is_node) in &mut query {
+        appearing.elapsed += time.delta_secs();
+        if is_node && let Some(mut transform) = transform {
+            transform.scale = Vec3::splat(appearing.size * appearing.progress());
+        }
+        if appearing.elapsed >= APPEAR_SECS {
+            commands.entity(entity).remove::<Appearing>();
+        }
+    }
+}
+
+/// Shrinks removed nodes and edges away, then despawns them
+pub fn animate_vanishing(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut Vanishing), Without<Appearing>>,
+) {
+    for (entity, mut transform, mut vanishing) in &mut query {
+        vanishing.elapsed += time.delta_secs();
+        transform.scale = vanishing.scale * (1.0 - ease(vanishing.elapsed, VANISH_SECS));
+        if vanishing.elapsed >= VANISH_SECS {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Eases changed nodes to their new color and size, pulsing once on the way
+/// unless motion is reduced
+pub fn animate_node_changes(
+    mut commands: Commands,
+    time: Res<Time>,
+    motion: Res<ReducedMotion>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut node_query: Query<(Entity, &mut Transform, &mut NodeChange)>,
+) {
+    for (entity, mut transform, mut change) in &mut node_query {
+        change.elapsed += time.delta_secs();
+        let t = (change.elapsed / CHANGE_SECS).min(1.0);
+        let eased = ease(change.elapsed, CHANGE_SECS);
+        let pulse = if motion.enabled {
+            0.0
+        } else {
+            (t * std::f32::consts::PI).sin()
+        };
+
+        let scale = change.from_scale.lerp(change.to_scale, eased);
+        transform.scale = Vec3::splat(scale * PULSE_SCALE.mul_add(pulse, 1.0));
+        // The node has a material of its own while it changes
+        if let Some(material) = materials.get_mut(&change.material) {
+            let color = change.from_color.mix(&change.to_color, eased);
+            material.base_color = color;
+            material.emissive = color.to_linear() * PULSE_GLOW * pulse;
+        }
+        if t >= 1.0 {
+            commands.entity(entity).remove::<NodeChange>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregate::Aggregation;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    #[test]
+    fn test_changed_node_pulses_into_its_new_look() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                CHANGE_SECS / 4.0,
+            )))
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<ReducedMotion>()
+            .add_systems(Update, animate_node_changes);
+
+        let material = app
+            .world_mut()
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial::default());
+        let node = app
+            .world_mut()
+            .spawn((
+                Transform::default(),
+                NodeChange {
+                    from_color: Color::BLACK,
+                    to_color: Color::WHITE,
+                    from_scale: 1.0,
+                    to_scale: 2.0,
+                    material: material.clone(),
+                    elapsed: 0.0,
+                },
+            ))
+            .id();
+
+        // The first update only starts the clock
+        for _ in 0..3 {
+            app.update();
+        }
+        let halfway = app.world().get::<Transform>(node).unwrap().scale.x;
+        assert!(halfway > 2.0, "swells past both sizes mid-pulse: {halfway}");
+
+        for _ in 0..3 {
+            app.update();
+        }
+        assert!(app.world().get::<NodeChange>(node).is_none());
+        let scale = app.world().get::<Transform>(node).unwrap().scale.x;
+        assert!((scale - 2.0).abs() < 1e-4);
+        let materials = app.world().resource::<Assets<StandardMaterial>>();
+        assert_eq!(materials.get(&material).unwrap().base_color, Color::WHITE);
+    }
+
+    #[test]
+    fn test_changed_node_eases_without_pulsing_under_reduced_motion() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                CHANGE_SECS / 4.0,
+            )))
+            .init_resource::<Assets<StandardMaterial>>()
+            .insert_resource(ReducedMotion { enabled: true })
+            .add_systems(Update, animate_node_changes);
+
+        let material = app
+            .world_mut()
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial::default());
+        let node = app
+            .world_mut()
+            .spawn((
+                Transform::default(),
+                NodeChange {
+                    from_color: Color::BLACK,
+                    to_color: Color::WHITE,
+                    from_scale: 1.0,
+                    to_scale: 2.0,
+                    material: material.clone(),
+                    elapsed: 0.0,
+                },
+            ))
+            .id();
+
+        for _ in 0..3 {
+            app.update();
+        }
+        let halfway = app.world().get::<Transform>(node).unwrap().scale.x;
+        assert!(
+            (1.0..=2.0).contains(&halfway),
+            "stays between sizes: {halfway}"
+        );
+        let materials = app.world().resource::<Assets<StandardMaterial>>();
+        assert_eq!(materials.get(&material).unwrap().emissive, LinearRgba::NONE);
+    }
+
+    #[test]
+    fn test_removed_nodes_and_edges_shrink_away() {
+        use crate::graph_state::GraphState;
+        use crate::loader::load_graph;
+        use crate::sources::SourceRegistry;
+        use crate::types::{GraphData, GraphEdge, LayoutSettings, Timeline, ViewSettings};
+
+        let content = r"digraph {
+            a -> b
+        }";
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<LayoutSettings>()
+            .init_resource::<ViewSettings>()
+            .init_resource::<Timeline>()
+            .init_resource::<Aggregation>()
+            .insert_resource(GraphData(GraphState::new().as_graph_data()))
+            .insert_resource(state)
+            .add_systems(
+                Update,
+                (refresh_graph_scene, animate_appearing, animate_vanishing).chain(),
+            );
+
+        // Everything is new to the empty graph that was shown before
+        app.update();
+        let world = app.world_mut();
+        assert_eq!(world.query::<&Appearing>().iter(world).count(), 3);
+
+        world
+            .resource_mut::<GraphState>()
+            .process_event(crate::events::GraphEvent::RemoveNode {
+                id: "b".to_string(),
+            });
+        app.update();
+        let world = app.world_mut();
+        assert_eq!(world.query::<&GraphNode>().iter(world).count(), 1);
+        assert_eq!(world.query::<&GraphEdge>().iter(world).count(), 0);
+        // The node and its edge, which takes its shaft and arrow head along
+        assert_eq!(world.query::<&Vanishing>().iter(world).count(), 2);
+    }
+}
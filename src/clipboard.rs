@@ -0,0 +1,182 @@
+//! Copying the picked nodes and their edges to the clipboard, as sentences
+//! or as JSON, so findings can be pasted into tickets
+
+use crate::describe::{describe_nodes, picked_nodes};
+use crate::events::AlertState;
+use crate::export::edge_json;
+use crate::graph_state;
+use crate::keybindings::{Action, KeyBindings};
+use crate::status::StatusMessage;
+use crate::types::{GraphData, GraphNode, HoverState, SearchState, SelectedNodes};
+use bevy::prelude::*;
+use bevy_egui::EguiClipboard;
+use petgraph::Direction;
+use petgraph::visit::EdgeRef;
+use serde_json::{Value, json};
+use std::collections::BTreeSet;
+
+/// Copying to the OS clipboard with Ctrl+C (text) and Ctrl+Shift+C (JSON)
+pub struct ClipboardPlugin;
+
+impl Plugin for ClipboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, copy_picked_nodes);
+    }
+}
+
+/// Copies the selected nodes, or else the one chosen in search, or else the
+/// hovered one, with their attributes and neighbors
+#[allow(clippy::too_many_arguments)]
+pub fn copy_picked_nodes(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    selection: Res<SelectedNodes>,
+    hover: Res<HoverState>,
+    node_query: Query<&GraphNode>,
+    graph_data: Option<Res<GraphData>>,
+    mut clipboard: ResMut<EguiClipboard>,
+    time: Res<Time>,
+    mut message: ResMut<StatusMessage>,
+) {
+    // Ctrl+C toggles case matching while searching
+    if search_state.active {
+        return;
+    }
+    let as_json = if bindings.just_pressed(Action::CopyNodesJson, &keyboard_input) {
+        true
+    } else if bindings.just_pressed(Action::CopyNodes, &keyboard_input) {
+        false
+    } else {
+        return;
+    };
+    let Some(data) = graph_data else {
+        return;
+    };
+    let ids = picked_nodes(&selection, &search_state, &hover, &node_query);
+    if ids.is_empty() {
+        message.show("Nothing to copy: select or hover a node first", &time);
+        return;
+    }
+
+    let text = if as_json {
+        serde_json::to_string_pretty(&nodes_json(&data, &ids)).unwrap_or_default()
+    } else {
+        describe_nodes(&data, &ids)
+    };
+    clipboard.set_text(&text);
+    let what = match ids.as_slice() {
+        [id] => id.clone(),
+        _ => format!("{} nodes", ids.len()),
+    };
+    let format = if as_json { "JSON" } else { "text" };
+    message.show(format!("Copied {what} to the clipboard as {format}"), &time);
+}
+
+/// The nodes in `ids` with their attributes and neighbors, and every edge
+/// touching them, as JSON with `nodes` and `edges` arrays like an export
+pub fn nodes_json(data: &graph_state::GraphData, ids: &[String]) -> Value {
+    let indices: Vec<_> = ids
+        .iter()
+        .filter_map(|id| data.node_map.get(id).copied())
+        .collect();
+
+    let nodes: Vec<Value> = indices
+        .iter()
+        .map(|&index| {
+            let info = &data.graph[index];
+            let neighbors = |direction| {
+                data.graph
+                    .neighbors_directed(index, direction)
+                    .map(|neighbor| data.graph[neighbor].id.as_str())
+                    .collect::<BTreeSet<_>>()
+            };
+            json!({
+                "id": info.id,
+                "name": info.name,
+                "type": info.node_type,
+                "level": info.level,
+                "state": info.state.map(AlertState::name),
+                "metrics": info.metrics,
+                "properties": info.properties,
+                "successors": neighbors(Direction::Outgoing),
+                "predecessors": neighbors(Direction::Incoming),
+            })
+        })
+        .collect();
+
+    // Edges between two picked nodes are listed once
+    let mut edges: Vec<_> = indices
+        .iter()
+        .flat_map(|&index| {
+            data.graph
+                .edges_directed(index, Direction::Outgoing)
+                .chain(data.graph.edges_directed(index, Direction::Incoming))
+        })
+        .map(|edge| edge.id())
+        .collect();
+    edges.sort_unstable();
+    edges.dedup();
+    let edges: Vec<Value> = edges
+        .into_iter()
+        .filter_map(|edge| {
+            let (from, to) = data.graph.edge_endpoints(edge)?;
+            Some(edge_json(
+                &data.graph[from].id,
+                &data.graph[to].id,
+                &data.graph[edge],
+            ))
+        })
+        .collect();
+
+    json!({ "nodes": nodes, "edges": edges })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EventEdgeInfo, EventNodeInfo, GraphEvent};
+    use crate::graph_state::GraphState;
+
+    #[test]
+    fn test_copied_json_has_the_nodes_neighbors_and_edges() {
+        let mut state = GraphState::new();
+        for id in ["web", "api", "db"] {
+            state.process_event(GraphEvent::AddNode {
+                id: id.to_string(),
+                info: EventNodeInfo {
+                    name: id.to_string(),
+                    ..default()
+                },
+            });
+        }
+        state.process_event(GraphEvent::AddEdge {
+            from: "web".to_string(),
+            to: "api".to_string(),
+        });
+        state.process_event(GraphEvent::AddRichEdge {
+            from: "api".to_string(),
+            to: "db".to_string(),
+            info: EventEdgeInfo {
+                label: Some("writes".to_string()),
+                ..default()
+            },
+        });
+
+        let json = nodes_json(
+            &state.as_graph_data(),
+            &["api".to_string(), "db".to_string()],
+        );
+        let nodes = json["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0]["successors"], json!(["db"]));
+        assert_eq!(nodes[0]["predecessors"], json!(["web"]));
+        let edges = json["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 2, "api -> db is listed once");
+        assert!(
+            edges
+                .iter()
+                .any(|edge| edge["to"] == "db" && edge["label"] == "writes")
+        );
+    }
+}
@@ -0,0 +1,570 @@
+use crate::keybindings::{Action, KeyBindings, KeyChord};
+use crate::lighting::LightingPreset;
+use crate::palette::Palette;
+use crate::quality::Quality;
+use crate::theme::{self, Theme};
+use crate::type_rules::TypeRule;
+use crate::types::{GroupBy, LayoutMode, LevelSource};
+use crate::window;
+use bevy::prelude::Color;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Per-project config file, looked for in the working directory and its parents
+pub const PROJECT_FILE: &str = ".bevydot.toml";
+
+/// Defaults for the command line, read from `~/.config/bevydot/config.toml`
+/// and the nearest `.bevydot.toml`
+///
+/// Options given on the command line win over the config. Named profiles
+/// hold the same settings and are applied on top with `--profile NAME`:
+///
+/// ```toml
+/// distance = 40
+/// inertia = 0.5
+/// theme = "light"
+/// layout = "topological"
+/// type_rules = ['name ~ "-db$" -> database']
+///
+/// [keybindings]
+/// toggle_theme = "Shift+T"
+/// open_search = ["/", "Ctrl+F"]
+///
+/// [formats]
+/// txt = "dot"
+///
+/// [models]
+/// database = "models/rack.glb"
+///
+/// [profiles.demo]
+/// distance = 60
+/// lighting = "dark"
+/// fullscreen = true
+/// kiosk = true
+/// monitor = 1
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub distance: Option<f32>,
+    pub speed: Option<f32>,
+    pub label_distance: Option<f32>,
+    pub label_width: Option<usize>,
+    pub wrap_labels: Option<bool>,
+    pub pan_sensitivity: Option<f32>,
+    pub orbit_sensitivity: Option<f32>,
+    pub zoom_sensitivity: Option<f32>,
+    pub smoothness: Option<f32>,
+    pub pitch_limit: Option<f32>,
+    pub inertia: Option<f32>,
+    pub theme: Option<Theme>,
+    pub background: Option<Color>,
+    pub lighting: Option<LightingPreset>,
+    pub skybox: Option<bool>,
+    pub fog: Option<f32>,
+    pub palette: Option<Palette>,
+    pub distinct_shapes: Option<bool>,
+    pub quality: Option<Quality>,
+    pub reduced_motion: Option<bool>,
+    pub ui_scale: Option<f32>,
+    pub high_contrast: Option<bool>,
+    pub layout: Option<LayoutMode>,
+    pub group_by: Option<GroupBy>,
+    pub levels: Option<LevelSource>,
+    pub fullscreen: Option<bool>,
+    pub kiosk: Option<bool>,
+    pub window_size: Option<(u32, u32)>,
+    pub monitor: Option<usize>,
+    /// Command the inspector opens the input in, e.g. `code --goto {file}:{line}`
+    pub editor: Option<String>,
+    /// Font files labels fall back on for characters the built-in font lacks
+    pub fonts: Vec<PathBuf>,
+    /// Keys that replace an action's default keys
+    pub keybindings: Vec<(Action, Vec<KeyChord>)>,
+    /// Format to read files with an extension as, e.g. "txt" to "dot"
+    pub formats: HashMap<String, String>,
+    /// glTF/GLB models to draw nodes of a type with, relative to the config
+    /// file they're set in
+    pub models: HashMap<String, PathBuf>,
+    /// Rules giving nodes a type, e.g. `name ~ "-db$" -> database`
+    pub type_rules: Vec<TypeRule>,
+}
+
+impl Config {
+    /// Reads the user config, then the project config over it, then the
+    /// named profile from either
+    pub fn load(profile: Option<&str>) -> Result<Self, String> {
+        let mut files = Vec::new();
+        for path in [user_config_path(), project_config_path()]
+            .into_iter()
+            .flatten()
+        {
+            match fs::read_to_string(&path) {
+                Ok(content) => files.push((path, content)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("{}: {e}", path.display())),
+            }
+        }
+        Self::layered(&files, profile)
+    }
+
+    /// Merges config files, later ones overriding earlier ones
+    fn layered(files: &[(PathBuf, String)], profile: Option<&str>) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut profiles = Vec::new();
+        for (path, content) in files {
+            let (mut settings, mut file_profiles) =
+                parse_file(content).map_err(|e| format!("{}: {e}", path.display()))?;
+            let dir = path.parent().unwrap_or_else(|| Path::new(""));
+            settings.resolve_models(dir);
+            for (_, settings) in &mut file_profiles {
+                settings.resolve_models(dir);
+            }
+            config.merge(settings);
+            profiles.extend(file_profiles);
+        }
+        if let Some(name) = profile {
+            let mut found = false;
+            for (_, settings) in profiles.iter_mut().filter(|(n, _)| n == name) {
+                config.merge(std::mem::take(settings));
+                found = true;
+            }
+            if !found {
+                let mut names: Vec<_> = profiles.iter().map(|(n, _)| n.as_str()).collect();
+                names.sort_unstable();
+                names.dedup();
+                return Err(if names.is_empty() {
+                    format!("no profile named '{name}' (no profiles are defined)")
+                } else {
+                    format!("no profile named '{name}' (expected {})", names.join(", "))
+                });
+            }
+        }
+        Ok(config)
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.distance = other.distance.or(self.distance);
+        self.speed = other.speed.or(self.speed);
+        self.label_distance = other.label_distance.or(self.label_distance);
+        self.label_width = other.label_width.or(self.label_width);
+        self.wrap_labels = other.wrap_labels.or(self.wrap_labels);
+        self.pan_sensitivity = other.pan_sensitivity.or(self.pan_sensitivity);
+        self.orbit_sensitivity = other.orbit_sensitivity.or(self.orbit_sensitivity);
+        self.zoom_sensitivity = other.zoom_sensitivity.or(self.zoom_sensitivity);
+        self.smoothness = other.smoothness.or(self.smoothness);
+        self.pitch_limit = other.pitch_limit.or(self.pitch_limit);
+        self.inertia = other.inertia.or(self.inertia);
+        self.theme = other.theme.or(self.theme);
+        self.background = other.background.or(self.background);
+        self.lighting = other.lighting.or(self.lighting);
+        self.skybox = other.skybox.or(self.skybox);
+        self.fog = other.fog.or(self.fog);
+        self.palette = other.palette.or(self.palette);
+        self.distinct_shapes = other.distinct_shapes.or(self.distinct_shapes);
+        self.quality = other.quality.or(self.quality);
+        self.reduced_motion = other.reduced_motion.or(self.reduced_motion);
+        self.ui_scale = other.ui_scale.or(self.ui_scale);
+        self.high_contrast = other.high_contrast.or(self.high_contrast);
+        self.layout = other.layout.or(self.layout);
+        self.group_by = other.group_by.or(self.group_by);
+        self.levels = other.levels.or(self.levels);
+        self.fullscreen = other.fullscreen.or(self.fullscreen);
+        self.kiosk = other.kiosk.or(self.kiosk);
+        self.window_size = other.window_size.or(self.window_size);
+        self.monitor = other.monitor.or(self.monitor);
+        self.editor = other.editor.or_else(|| self.editor.take());
+        if !other.fonts.is_empty() {
+            self.fonts = other.fonts;
+        }
+        // Applied in order, so later bindings of an action win
+        self.keybindings.extend(other.keybindings);
+        self.formats.extend(other.formats);
+        self.models.extend(other.models);
+        // Later rules win, so the project's and the profile's go last
+        self.type_rules.extend(other.type_rules);
+    }
+
+    /// Makes model paths relative to `dir`, the directory of their file
+    fn resolve_models(&mut self, dir: &Path) {
+        for path in self.models.values_mut() {
+            *path = dir.join(&*path);
+        }
+    }
+
+    /// The default bindings with the configured ones applied
+    pub fn key_bindings(&self) -> KeyBindings {
+        let mut bindings = KeyBindings::default();
+        for (action, keys) in &self.keybindings {
+            bindings.rebind(*action, keys.clone());
+        }
+        bindings
+    }
+
+    /// The format configured for a file's extension, if any
+    pub fn format_for(&self, location: &str) -> Option<&str> {
+        let extension = Path::new(location).extension()?.to_str()?;
+        self.formats
+            .get(&extension.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/bevydot/config.toml`, or `~/.config/bevydot/config.toml`
+fn user_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("bevydot").join("config.toml"))
+}
+
+/// The nearest `.bevydot.toml` in the working directory or one of its parents
+fn project_config_path() -> Option<PathBuf> {
+    let dir = env::current_dir().ok()?;
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+/// The top-level settings of a config file and its named profiles
+fn parse_file(content: &str) -> Result<(Config, Vec<(String, Config)>), String> {
+    let mut table: Table = content
+        .parse()
+        .map_err(|err: toml::de::Error| err.message().to_string())?;
+    let profiles = match table.remove("profiles") {
+        None => Vec::new(),
+        Some(Value::Table(profiles)) => profiles
+            .into_iter()
+            .map(|(name, settings)| match settings {
+                Value::Table(settings) => parse_settings(&settings)
+                    .map(|config| (name.clone(), config))
+                    .map_err(|e| format!("profile '{name}': {e}")),
+                _ => Err(format!("profile '{name}' must be a table")),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err("\"profiles\" must be a table of profiles".to_string()),
+    };
+    Ok((parse_settings(&table)?, profiles))
+}
+
+fn parse_settings(table: &Table) -> Result<Config, String> {
+    let mut config = Config::default();
+    for (key, value) in table {
+        match key.as_str() {
+            "distance" => config.distance = Some(number(key, value)?),
+            "speed" => config.speed = Some(number(key, value)?),
+            "label_distance" => config.label_distance = Some(number(key, value)?),
+            "label_width" => {
+                config.label_width = Some(
+                    value
+                        .as_integer()
+                        .and_then(|n| usize::try_from(n).ok())
+                        .ok_or_else(|| format!("\"{key}\" must be a number of characters"))?,
+                );
+            }
+            "wrap_labels" => config.wrap_labels = Some(flag(key, value)?),
+            "pan_sensitivity" => config.pan_sensitivity = Some(number(key, value)?),
+            "orbit_sensitivity" => config.orbit_sensitivity = Some(number(key, value)?),
+            "zoom_sensitivity" => config.zoom_sensitivity = Some(number(key, value)?),
+            "smoothness" => config.smoothness = Some(number(key, value)?),
+            "pitch_limit" => config.pitch_limit = Some(number(key, value)?),
+            "inertia" => config.inertia = Some(number(key, value)?),
+            "theme" => config.theme = Some(text(key, value)?.parse()?),
+            "background" => config.background = Some(theme::parse_background(text(key, value)?)?),
+            "lighting" => config.lighting = Some(text(key, value)?.parse()?),
+            "skybox" => config.skybox = Some(flag(key, value)?),
+            "fog" => config.fog = Some(number(key, value)?),
+            "palette" => config.palette = Some(text(key, value)?.parse()?),
+            "distinct_shapes" => config.distinct_shapes = Some(flag(key, value)?),
+            "quality" => config.quality = Some(text(key, value)?.parse()?),
+            "reduced_motion" => config.reduced_motion = Some(flag(key, value)?),
+            "ui_scale" => config.ui_scale = Some(number(key, value)?),
+            "high_contrast" => config.high_contrast = Some(flag(key, value)?),
+            "layout" => config.layout = Some(text(key, value)?.parse()?),
+            "group_by" => config.group_by = Some(text(key, value)?.parse()?),
+            "levels" => config.levels = Some(text(key, value)?.parse()?),
+            "fullscreen" => config.fullscreen = Some(flag(key, value)?),
+            "editor" => config.editor = Some(text(key, value)?.to_string()),
+            "kiosk" => config.kiosk = Some(flag(key, value)?),
+            "window_size" => {
+                config.window_size = Some(window::parse_window_size(text(key, value)?)?);
+            }
+            "monitor" => {
+                config.monitor = Some(
+                    value
+                        .as_integer()
+                        .and_then(|n| usize::try_from(n).ok())
+                        .ok_or_else(|| format!("\"{key}\" must be a monitor number"))?,
+                );
+            }
+            "keybindings" => {
+                for (action, keys) in table_of(key, value)? {
+                    let keys = match keys {
+                        Value::Array(keys) => keys.iter().map(|k| text(action, k)).collect(),
+                        keys => text(action, keys).map(|k| vec![k]),
+                    }?;
+                    config.keybindings.push((
+                        action.parse()?,
+                        keys.into_iter().map(str::parse).collect::<Result<_, _>>()?,
+                    ));
+                }
+            }
+            "fonts" => {
+                let fonts = value
+                    .as_array()
+                    .ok_or_else(|| format!("\"{key}\" must be a list of font files"))?;
+                for font in fonts {
+                    config.fonts.push(PathBuf::from(text(key, font)?));
+                }
+            }
+            "type_rules" => {
+                let rules = value
+                    .as_array()
+                    .ok_or_else(|| format!("\"{key}\" must be a list of rules"))?;
+                for rule in rules {
+                    config.type_rules.push(text(key, rule)?.parse()?);
+                }
+            }
+            "formats" => {
+                for (extension, format) in table_of(key, value)? {
+                    config.formats.insert(
+                        extension.trim_start_matches('.').to_ascii_lowercase(),
+                        text(extension, format)?.to_string(),
+                    );
+                }
+            }
+            "models" => {
+                for (node_type, path) in table_of(key, value)? {
+                    config
+                        .models
+                        .insert(node_type.clone(), PathBuf::from(text(node_type, path)?));
+                }
+            }
+            _ => return Err(format!("unknown setting \"{key}\"")),
+        }
+    }
+    Ok(config)
+}
+
+#[allow(clippy::cast_possible_truncation)] // Distances and speeds are small
+fn number(key: &str, value: &Value) -> Result<f32, String> {
+    match value {
+        Value::Integer(n) => Ok(*n as f32),
+        Value::Float(n) => Ok(*n as f32),
+        _ => Err(format!("\"{key}\" must be a number")),
+    }
+}
+
+fn flag(key: &str, value: &Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| format!("\"{key}\" must be true or false"))
+}
+
+fn text<'a>(key: &str, value: &'a Value) -> Result<&'a str, String> {
+    value
+        .as_str()
+        .ok_or_else(|| format!("\"{key}\" must be a string"))
+}
+
+fn table_of<'a>(key: &str, value: &'a Value) -> Result<&'a Table, String> {
+    value
+        .as_table()
+        .ok_or_else(|| format!("\"{key}\" must be a table"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::prelude::KeyCode;
+
+    #[test]
+    fn test_project_and_profile_layer_over_user_config() {
+        let user = r#"
+            distance = 40
+            theme = "light"
+            palette = "deuteranopia"
+
+            [keybindings]
+            toggle_theme = "Shift+T"
+
+            [formats]
+            ".txt" = "dot"
+
+            [models]
+            database = "models/db.glb"
+            user = "models/person.glb"
+
+            [profiles.demo]
+            distance = 60
+            skybox = true
+            fog = 0.02
+            quality = "high"
+            pitch_limit = 60
+            levels = "attr"
+            window_size = "1920x1080"
+        "#;
+        let project = r#"
+            distance = 30
+            layout = "topological"
+            group_by = "type"
+            editor = "code --goto {file}:{line}"
+            type_rules = ['name ~ "-db$" -> database']
+            fonts = ["fonts/NotoSansCJK.ttc", "fonts/NotoColorEmoji.ttf"]
+            label_width = 24
+            wrap_labels = true
+            reduced_motion = true
+            ui_scale = 1.5
+            high_contrast = true
+
+            [keybindings]
+            open_search = ["/", "Ctrl+F"]
+
+            [models]
+            database = "assets/rack.glb"
+
+            [profiles.demo]
+            lighting = "dark"
+        "#;
+        let files = [
+            (PathBuf::from("home/config.toml"), user.to_string()),
+            (
+                PathBuf::from("project").join(PROJECT_FILE),
+                project.to_string(),
+            ),
+        ];
+
+        let config = Config::layered(&files, None).unwrap();
+        assert_eq!(config.distance, Some(30.0));
+        assert_eq!(config.theme, Some(Theme::Light));
+        assert_eq!(config.layout, Some(LayoutMode::Topological));
+        assert_eq!(config.group_by, Some(GroupBy::Type));
+        assert_eq!(config.editor.as_deref(), Some("code --goto {file}:{line}"));
+        assert_eq!(config.skybox, None);
+        assert_eq!(config.format_for("notes/graph.TXT"), Some("dot"));
+        assert_eq!(config.type_rules.len(), 1);
+        assert_eq!(config.fonts.len(), 2);
+        assert_eq!(config.label_width, Some(24));
+        assert_eq!(config.wrap_labels, Some(true));
+        assert_eq!(config.reduced_motion, Some(true));
+        assert_eq!(config.ui_scale, Some(1.5));
+        assert_eq!(config.high_contrast, Some(true));
+        assert_eq!(config.palette, Some(Palette::Deuteranopia));
+        // Model paths are relative to their file, and the project's win
+        assert_eq!(
+            config.models["database"],
+            Path::new("project/assets/rack.glb")
+        );
+        assert_eq!(config.models["user"], Path::new("home/models/person.glb"));
+        let bindings = config.key_bindings();
+        assert_eq!(
+            bindings.keys(Action::ToggleTheme),
+            [KeyChord::shift(KeyCode::KeyT)]
+        );
+        assert_eq!(bindings.keys(Action::OpenSearch).len(), 2);
+
+        let demo = Config::layered(&files, Some("demo")).unwrap();
+        assert_eq!(demo.distance, Some(60.0));
+        assert_eq!(demo.skybox, Some(true));
+        assert_eq!(demo.fog, Some(0.02));
+        assert_eq!(demo.quality, Some(Quality::High));
+        assert_eq!(demo.pitch_limit, Some(60.0));
+        assert_eq!(demo.lighting, Some(LightingPreset::Dark));
+        assert_eq!(demo.window_size, Some((1920, 1080)));
+        assert_eq!(demo.levels, Some(LevelSource::Attr));
+
+        let err = Config::layered(&files, Some("talk")).unwrap_err();
+        assert!(err.contains("expected demo"), "{err}");
+        let broken = [(PathBuf::from("config.toml"), "zoom = 2".to_string())];
+        let err = Config::layered(&broken, None).unwrap_err();
+        assert_eq!(err, "config.toml: unknown setting \"zoom\"");
+    }
+
+    fn files(user: &str, project: &str) -> [(PathBuf, String); 2] {
+        [
+            (PathBuf::from("home/config.toml"), user.to_string()),
+            (
+                PathBuf::from("project").join(PROJECT_FILE),
+                project.to_string(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_unknown_profile_names_the_known_ones() {
+        let files = files(
+            "[profiles.demo]\ndistance = 60",
+            "[profiles.talk]\ndistance = 80",
+        );
+        let err = Config::layered(&files, Some("kiosk")).unwrap_err();
+        assert_eq!(err, "no profile named 'kiosk' (expected demo, talk)");
+
+        let files = files("distance = 40", "");
+        let err = Config::layered(&files, Some("demo")).unwrap_err();
+        assert_eq!(err, "no profile named 'demo' (no profiles are defined)");
+    }
+
+    #[test]
+    fn test_project_file_overrides_user_file() {
+        let user = r#"
+            distance = 40
+            speed = 2
+            formats = { txt = "dot", log = "jsonl" }
+
+            [keybindings]
+            open_search = "/"
+        "#;
+        let project = r#"
+            distance = 25
+            formats = { txt = "mermaid" }
+
+            [keybindings]
+            open_search = "Ctrl+F"
+        "#;
+        let config = Config::layered(&files(user, project), None).unwrap();
+        assert_eq!(config.distance, Some(25.0));
+        // Settings the project leaves out keep the user's value
+        assert_eq!(config.speed, Some(2.0));
+        assert_eq!(config.format_for("graph.txt"), Some("mermaid"));
+        assert_eq!(config.format_for("events.log"), Some("jsonl"));
+        assert_eq!(
+            config.key_bindings().keys(Action::OpenSearch),
+            [KeyChord::key(KeyCode::KeyF).with_ctrl()]
+        );
+    }
+
+    #[test]
+    fn test_model_paths_are_relative_to_their_config_file() {
+        let user = r#"
+            [models]
+            user = "models/person.glb"
+            server = "/opt/models/rack.glb"
+
+            [profiles.demo.models]
+            user = "demo/person.glb"
+        "#;
+        let project = r#"
+            [models]
+            database = "../shared/db.glb"
+        "#;
+        let config = Config::layered(&files(user, project), None).unwrap();
+        assert_eq!(config.models["user"], Path::new("home/models/person.glb"));
+        assert_eq!(
+            config.models["database"],
+            Path::new("project/../shared/db.glb")
+        );
+        // Absolute paths are kept as they are
+        assert_eq!(config.models["server"], Path::new("/opt/models/rack.glb"));
+
+        let demo = Config::layered(&files(user, project), Some("demo")).unwrap();
+        assert_eq!(demo.models["user"], Path::new("home/demo/person.glb"));
+
+        let bare = [(PathBuf::from(PROJECT_FILE), project.to_string())];
+        let config = Config::layered(&bare, None).unwrap();
+        assert_eq!(config.models["database"], Path::new("../shared/db.glb"));
+    }
+}
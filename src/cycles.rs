@@ -0,0 +1,259 @@
+use crate::graph_state;
+use crate::keybindings::{Action, KeyBindings};
+use crate::settings::settings_panel_ui;
+use crate::status::StatusMessage;
+use crate::types::{CycleAnalysis, GraphData, GraphEdge, GraphNode, SearchState};
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use bevy_panorbit_camera::PanOrbitCamera;
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+
+/// Most node names listed for a cycle in the panel
+const LISTED_NAMES: usize = 4;
+
+/// Cycle detection: tints every strongly connected component its own color
+/// and lists them in a panel
+pub struct CyclesPlugin;
+
+impl Plugin for CyclesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CycleAnalysis>()
+            .add_systems(
+                Update,
+                (
+                    toggle_cycle_analysis,
+                    find_cycles_in_scene.run_if(
+                        resource_changed::<CycleAnalysis>
+                            .or(resource_exists_and_changed::<GraphData>),
+                    ),
+                    draw_cycle_edges,
+                )
+                    .chain(),
+            )
+            .add_systems(EguiContextPass, cycle_panel_ui.after(settings_panel_ui));
+    }
+}
+
+impl CycleAnalysis {
+    /// Tint for the `index`th component, spread around the color wheel
+    pub fn color(index: usize) -> Color {
+        #[allow(clippy::cast_precision_loss)]
+        let hue = (index as f32 * 137.5) % 360.0;
+        Color::hsl(hue, 0.85, 0.55)
+    }
+
+    /// Tint of the component a node belongs to, if it is on a cycle
+    pub fn node_color(&self, node: NodeIndex) -> Option<Color> {
+        if !self.active {
+            return None;
+        }
+        self.membership.get(&node).copied().map(Self::color)
+    }
+}
+
+/// Strongly connected components that contain a cycle, largest first
+///
+/// A single node only counts if it has an edge to itself.
+pub fn find_cycles(data: &graph_state::GraphData) -> Vec<Vec<NodeIndex>> {
+    let mut components: Vec<_> = tarjan_scc(&data.graph)
+        .into_iter()
+        .filter(|component| {
+            component.len() > 1 || data.graph.find_edge(component[0], component[0]).is_some()
+        })
+        .collect();
+    components.sort_by_key(|component| std::cmp::Reverse(component.len()));
+    components
+}
+
+pub fn toggle_cycle_analysis(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    search_state: Res<SearchState>,
+    mut analysis: ResMut<CycleAnalysis>,
+) {
+    if !search_state.active && bindings.just_pressed(Action::ToggleCycles, &keyboard_input) {
+        analysis.active = !analysis.active;
+    }
+}
+
+/// Recomputes the components when the analysis is turned on or the graph changes
+pub fn find_cycles_in_scene(
+    graph_data: Option<Res<GraphData>>,
+    time: Res<Time>,
+    mut analysis: ResMut<CycleAnalysis>,
+    mut message: ResMut<StatusMessage>,
+) {
+    // Only the flag changed when it is turned off, so nothing to recompute
+    let Some(data) = graph_data.filter(|_| analysis.active) else {
+        return;
+    };
+    let toggled = analysis.is_changed();
+    let components = find_cycles(&data);
+    // Bypass change detection so writing the results doesn't re-trigger this system
+    let analysis = analysis.bypass_change_detection();
+    analysis.membership = components
+        .iter()
+        .enumerate()
+        .flat_map(|(index, component)| component.iter().map(move |&node| (node, index)))
+        .collect();
+    analysis.components = components;
+    // Only report when turned on, not on every live update
+    if !toggled {
+        return;
+    }
+    message.show(
+        match analysis.components.len() {
+            0 => "No cycles: the graph is acyclic".to_string(),
+            n => format!("{n} cycles found"),
+        },
+        &time,
+    );
+}
+
+/// Draws the edges inside each component over the scene in its tint
+pub fn draw_cycle_edges(
+    mut gizmos: Gizmos,
+    analysis: Res<CycleAnalysis>,
+    node_query: Query<(&GraphNode, &GlobalTransform)>,
+    edge_query: Query<&GraphEdge>,
+) {
+    if !analysis.active || analysis.components.is_empty() {
+        return;
+    }
+    let positions: HashMap<NodeIndex, Vec3> = node_query
+        .iter()
+        .map(|(node, transform)| (node.index, transform.translation()))
+        .collect();
+
+    for edge in &edge_query {
+        let (Some(&from), Some(&to)) = (
+            analysis.membership.get(&edge.from),
+            analysis.membership.get(&edge.to),
+        ) else {
+            continue;
+        };
+        if from != to {
+            continue;
+        }
+        if let (Some(&start), Some(&end)) = (positions.get(&edge.from), positions.get(&edge.to)) {
+            gizmos.line(start, end, CycleAnalysis::color(from));
+        }
+    }
+}
+
+/// Lists the cycles; clicking one flies the camera to it
+pub fn cycle_panel_ui(
+    mut contexts: EguiContexts,
+    mut analysis: ResMut<CycleAnalysis>,
+    graph_data: Option<Res<GraphData>>,
+    node_query: Query<(&GraphNode, &GlobalTransform)>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    if !analysis.active {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let mut open = true;
+    let mut jump_to = None;
+    egui::Window::new("Cycles")
+        .open(&mut open)
+        .default_pos([10.0, 400.0])
+        .default_width(280.0)
+        .show(ctx, |ui| {
+            if analysis.components.is_empty() {
+                ui.label("No cycles: the graph is acyclic");
+                return;
+            }
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    for (index, component) in analysis.components.iter().enumerate() {
+                        let [r, g, b, _] = CycleAnalysis::color(index).to_srgba().to_u8_array();
+                        let names = graph_data
+                            .as_deref()
+                            .map(|data| {
+                                let mut names: Vec<_> = component
+                                    .iter()
+                                    .take(LISTED_NAMES)
+                                    .map(|&node| data.graph[node].name.as_str())
+                                    .collect();
+                                if component.len() > LISTED_NAMES {
+                                    names.push("…");
+                                }
+                                names.join(", ")
+                            })
+                            .unwrap_or_default();
+                        let text =
+                            egui::RichText::new(format!("{} nodes: {names}", component.len()))
+                                .color(egui::Color32::from_rgb(r, g, b));
+                        if ui.selectable_label(false, text).clicked() {
+                            jump_to = Some(index);
+                        }
+                    }
+                });
+        });
+
+    if !open {
+        analysis.active = false;
+    }
+    if let Some(index) = jump_to {
+        let component = &analysis.components[index];
+        let positions: Vec<Vec3> = node_query
+            .iter()
+            .filter(|(node, _)| component.contains(&node.index))
+            .map(|(_, transform)| transform.translation())
+            .collect();
+        if positions.is_empty() {
+            return;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let center = positions.iter().sum::<Vec3>() / positions.len() as f32;
+        let extent = positions
+            .iter()
+            .map(|position| position.distance(center))
+            .fold(0.0, f32::max);
+        for mut camera in &mut camera_query {
+            camera.target_focus = center;
+            camera.target_radius = (extent * 3.0).clamp(5.0, 100.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_find_cycles() {
+        let content = r"digraph {
+            A -> B
+            B -> C
+            C -> A
+            C -> D
+            D -> E
+            E -> D
+            F -> F
+            F -> G
+        }";
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+
+        let components = find_cycles(&data);
+        let sizes: Vec<_> = components.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [3, 2, 1]);
+        assert!(components[2].contains(&data.node_map["F"]));
+        // G is on no cycle
+        assert!(
+            components
+                .iter()
+                .all(|component| !component.contains(&data.node_map["G"]))
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! Plain-text descriptions of what is in view, for screen readers and for
+//! pasting into chat: the selected or hovered nodes and their neighbors, or
+//! the graph as a whole when there are none
+
+use crate::graph_state;
+use crate::keybindings::{Action, KeyBindings};
+use crate::settings::settings_panel_ui;
+use crate::types::{GraphData, GraphNode, HoverState, SearchState, SelectedNodes};
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use petgraph::Direction;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Most neighbors listed each way before the rest are only counted
+const LISTED_NEIGHBORS: usize = 10;
+
+/// The last description, shown in a panel until it is closed
+#[derive(Resource, Default)]
+pub struct DescriptionPanel {
+    pub text: Option<String>,
+}
+
+/// Describing the view in text on a key press, printed to stdout and shown
+/// in a panel
+pub struct DescribePlugin;
+
+impl Plugin for DescribePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DescriptionPanel>()
+            .add_systems(Update, describe_view)
+            .add_systems(
+                EguiContextPass,
+                description_panel_ui.after(settings_panel_ui),
+            );
+    }
+}
+
+/// Describes the picked nodes, or sums up the graph when none are picked
+#[allow(clippy::too_many_arguments)]
+pub fn describe_view(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    selection: Res<SelectedNodes>,
+    hover: Res<HoverState>,
+    node_query: Query<&GraphNode>,
+    graph_data: Option<Res<GraphData>>,
+    mut panel: ResMut<DescriptionPanel>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::DescribeView, &keyboard_input) {
+        return;
+    }
+    let Some(data) = graph_data else {
+        return;
+    };
+    let ids = picked_nodes(&selection, &search_state, &hover, &node_query);
+    let text = if ids.is_empty() {
+        describe_graph(&data)
+    } else {
+        describe_nodes(&data, &ids)
+    };
+    println!("{text}");
+    panel.text = Some(text);
+}
+
+/// Ids of the selected nodes, or else of the one chosen in search, or else
+/// of the hovered one, sorted
+pub fn picked_nodes(
+    selection: &SelectedNodes,
+    search_state: &SearchState,
+    hover: &HoverState,
+    node_query: &Query<&GraphNode>,
+) -> Vec<String> {
+    let mut ids: Vec<String> = selection.0.iter().cloned().collect();
+    if ids.is_empty() {
+        ids.extend(
+            search_state
+                .selected_node
+                .or(hover.node)
+                .and_then(|entity| node_query.get(entity).ok())
+                .map(|node| node.id.clone()),
+        );
+    }
+    ids.sort_unstable();
+    ids
+}
+
+/// Sentences describing each node in `ids` and the edges joining it to its
+/// neighbors; ids not in the graph are skipped
+pub fn describe_nodes(data: &graph_state::GraphData, ids: &[String]) -> String {
+    let mut text = String::new();
+    if ids.len() > 1 {
+        let _ = writeln!(text, "{} nodes selected.", ids.len());
+    }
+    for id in ids {
+        let Some(&index) = data.node_map.get(id) else {
+            continue;
+        };
+        let info = &data.graph[index];
+        if !text.is_empty() {
+            text.push('\n');
+        }
+
+        let _ = write!(text, "Node {}", info.name);
+        if info.name != info.id {
+            let _ = write!(text, " (id {})", info.id);
+        }
+        let node_type = info.node_type.as_deref().unwrap_or("none");
+        let _ = write!(text, ", type {node_type}, level {}", info.level);
+        if let Some(state) = info.state {
+            let _ = write!(text, ", state {}", state.name());
+        }
+        text.push_str(".\n");
+        if !info.metrics.is_empty() {
+            let metrics: Vec<_> = info
+                .metrics
+                .iter()
+                .map(|(key, value)| format!("{key} {value}"))
+                .collect();
+            let _ = writeln!(text, "Metrics: {}.", metrics.join(", "));
+        }
+        if !info.properties.is_empty() {
+            let properties: Vec<_> = info
+                .properties
+                .iter()
+                .map(|(key, value)| format!("{key} {value}"))
+                .collect();
+            let _ = writeln!(text, "Attributes: {}.", properties.join(", "));
+        }
+        describe_edges(&mut text, data, index, Direction::Outgoing);
+        describe_edges(&mut text, data, index, Direction::Incoming);
+    }
+    text.trim_end().to_string()
+}
+
+/// A sentence listing a node's edges one way, e.g. "2 outgoing edges: to
+/// ledger (writes), to fraud."
+fn describe_edges(
+    text: &mut String,
+    data: &graph_state::GraphData,
+    index: NodeIndex,
+    direction: Direction,
+) {
+    let (way, preposition) = match direction {
+        Direction::Outgoing => ("outgoing", "to"),
+        Direction::Incoming => ("incoming", "from"),
+    };
+    let mut edges: Vec<_> = data
+        .graph
+        .edges_directed(index, direction)
+        .map(|edge| {
+            let neighbor = match direction {
+                Direction::Outgoing => edge.target(),
+                Direction::Incoming => edge.source(),
+            };
+            let mut item = format!("{preposition} {}", data.graph[neighbor].name);
+            if let Some(label) = &edge.weight().label {
+                let _ = write!(item, " ({label})");
+            }
+            item
+        })
+        .collect();
+    edges.sort_unstable();
+
+    let count = edges.len();
+    let noun = if count == 1 { "edge" } else { "edges" };
+    if count == 0 {
+        let _ = writeln!(text, "No {way} edges.");
+        return;
+    }
+    let mut listed = edges[..count.min(LISTED_NEIGHBORS)].join(", ");
+    if count > LISTED_NEIGHBORS {
+        let _ = write!(listed, " and {} more", count - LISTED_NEIGHBORS);
+    }
+    let _ = writeln!(text, "{count} {way} {noun}: {listed}.");
+}
+
+/// Sentences summing up the whole graph, for when nothing is picked out
+pub fn describe_graph(data: &graph_state::GraphData) -> String {
+    let mut types: BTreeMap<&str, usize> = BTreeMap::new();
+    for info in data.graph.node_weights() {
+        *types
+            .entry(info.node_type.as_deref().unwrap_or("untyped"))
+            .or_default() += 1;
+    }
+    let types: Vec<_> = types
+        .iter()
+        .map(|(node_type, count)| format!("{count} {node_type}"))
+        .collect();
+
+    let mut text = format!(
+        "Graph of {} nodes and {} edges.",
+        data.graph.node_count(),
+        data.graph.edge_count()
+    );
+    if !types.is_empty() {
+        let _ = write!(text, "\nTypes: {}.", types.join(", "));
+    }
+    let mut roots: Vec<_> = data
+        .graph
+        .node_indices()
+        .filter(|&index| {
+            data.graph
+                .neighbors_directed(index, Direction::Incoming)
+                .next()
+                .is_none()
+        })
+        .map(|index| data.graph[index].name.as_str())
+        .collect();
+    roots.sort_unstable();
+    if !roots.is_empty() && roots.len() <= LISTED_NEIGHBORS {
+        let _ = write!(text, "\nNodes nothing leads to: {}.", roots.join(", "));
+    }
+    text.push_str("\nSelect or hover a node to describe it.");
+    text
+}
+
+/// Shows the last description as selectable text, so it can be read out or
+/// copied
+pub fn description_panel_ui(mut contexts: EguiContexts, mut panel: ResMut<DescriptionPanel>) {
+    let Some(text) = panel.text.as_deref() else {
+        return;
+    };
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+    let mut open = true;
+    egui::Window::new("Description")
+        .open(&mut open)
+        .default_pos([10.0, 120.0])
+        .default_width(360.0)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(400.0)
+                .show(ui, |ui| {
+                    let mut text = text;
+                    ui.add(
+                        egui::TextEdit::multiline(&mut text)
+                            .desired_width(f32::INFINITY)
+                            .desired_rows(4),
+                    );
+                });
+        });
+    if !open {
+        panel.text = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EventEdgeInfo, EventNodeInfo, GraphEvent};
+    use crate::graph_state::GraphState;
+
+    fn graph() -> graph_state::GraphData {
+        let mut state = GraphState::new();
+        let nodes = [
+            ("web", "web", None),
+            ("api", "api", Some("service")),
+            ("db", "Ledger DB", Some("database")),
+            ("cache", "cache", None),
+        ];
+        for (id, name, node_type) in nodes {
+            let mut info = EventNodeInfo {
+                name: name.to_string(),
+                node_type: node_type.map(str::to_string),
+                ..default()
+            };
+            if id == "api" {
+                info.properties
+                    .insert("owner".to_string(), "payments".to_string());
+            }
+            state.process_event(GraphEvent::AddNode {
+                id: id.to_string(),
+                info,
+            });
+        }
+        for (from, to, label) in [
+            ("web", "api", None),
+            ("api", "db", Some("writes")),
+            ("api", "cache", None),
+        ] {
+            state.process_event(GraphEvent::AddRichEdge {
+                from: from.to_string(),
+                to: to.to_string(),
+                info: EventEdgeInfo {
+                    label: label.map(str::to_string),
+                    ..default()
+                },
+            });
+        }
+        state.as_graph_data()
+    }
+
+    #[test]
+    fn test_describes_a_node_and_its_neighbors() {
+        let text = describe_nodes(&graph(), &["api".to_string()]);
+        assert_eq!(
+            text,
+            "Node api, type service, level 0.\n\
+             Attributes: owner payments.\n\
+             2 outgoing edges: to Ledger DB (writes), to cache.\n\
+             1 incoming edge: from web."
+        );
+    }
+
+    #[test]
+    fn test_describes_the_graph_when_nothing_is_picked() {
+        let text = describe_graph(&graph());
+        assert!(text.starts_with("Graph of 4 nodes and 3 edges."));
+        assert!(text.contains("Types: 1 database, 1 service, 2 untyped."));
+        assert!(text.contains("Nodes nothing leads to: web."));
+    }
+}
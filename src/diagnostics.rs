@@ -0,0 +1,174 @@
+use crate::keybindings::{Action, KeyBindings};
+use crate::types::{
+    DiagnosticsOverlay, GraphEdge, GraphNode, NodeLabel, SearchState, ThemedPanel, ThemedText,
+};
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticPath, DiagnosticsStore, FrameTimeDiagnosticsPlugin, RegisterDiagnostic,
+};
+use bevy::prelude::*;
+use std::fmt::Write;
+
+/// Milliseconds spent applying live events to the graph each frame
+pub const EVENT_PROCESSING_TIME: DiagnosticPath =
+    DiagnosticPath::const_new("dotspace/event_processing_time");
+
+/// Seconds between refreshes of the overlay, so its numbers can be read
+const REFRESH_SECS: f32 = 0.25;
+
+/// A corner overlay of frame rate, entity counts and the time spent on live
+/// events, for tuning big graphs; toggled with F3
+pub struct DiagnosticsOverlayPlugin;
+
+impl Plugin for DiagnosticsOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin::default());
+        }
+        app.register_diagnostic(Diagnostic::new(EVENT_PROCESSING_TIME).with_suffix("ms"))
+            .add_systems(Startup, setup_diagnostics_overlay)
+            .add_systems(
+                Update,
+                (toggle_diagnostics_overlay, update_diagnostics_overlay).chain(),
+            );
+    }
+}
+
+/// How many of each kind of entity the scene has
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntityCounts {
+    pub nodes: usize,
+    pub edges: usize,
+    pub labels: usize,
+    /// Meshes in view this frame, each drawn unless batched with others
+    pub visible_meshes: usize,
+    pub total: usize,
+}
+
+fn setup_diagnostics_overlay(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            // Under the help hint, clear of the error panel at the top right
+            top: Val::Px(36.0),
+            left: Val::Px(10.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.05, 0.05, 0.1, 0.85)),
+        Visibility::Hidden,
+        DiagnosticsOverlay,
+        ThemedText,
+        ThemedPanel,
+    ));
+}
+
+fn toggle_diagnostics_overlay(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    mut overlay_query: Query<&mut Visibility, With<DiagnosticsOverlay>>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::ToggleDiagnostics, &keyboard_input) {
+        return;
+    }
+    if let Ok(mut visibility) = overlay_query.single_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+/// Fills in the overlay while it's shown
+#[allow(clippy::too_many_arguments)]
+fn update_diagnostics_overlay(
+    time: Res<Time>,
+    mut since_refresh: Local<f32>,
+    store: Res<DiagnosticsStore>,
+    entities: Query<()>,
+    nodes: Query<(), With<GraphNode>>,
+    edges: Query<(), With<GraphEdge>>,
+    labels: Query<(), With<NodeLabel>>,
+    meshes: Query<&ViewVisibility, With<Mesh3d>>,
+    mut overlay_query: Query<(&mut Text, &Visibility), With<DiagnosticsOverlay>>,
+) {
+    let Ok((mut text, visibility)) = overlay_query.single_mut() else {
+        return;
+    };
+    *since_refresh += time.delta_secs();
+    if *visibility == Visibility::Hidden || (*since_refresh < REFRESH_SECS && !text.0.is_empty()) {
+        return;
+    }
+    *since_refresh = 0.0;
+
+    let counts = EntityCounts {
+        nodes: nodes.iter().len(),
+        edges: edges.iter().len(),
+        labels: labels.iter().len(),
+        visible_meshes: meshes.iter().filter(|v| v.get()).count(),
+        total: entities.iter().len(),
+    };
+    let smoothed = |path: &DiagnosticPath| store.get(path).and_then(Diagnostic::smoothed);
+    text.0 = overlay_text(
+        smoothed(&FrameTimeDiagnosticsPlugin::FPS),
+        smoothed(&FrameTimeDiagnosticsPlugin::FRAME_TIME),
+        smoothed(&EVENT_PROCESSING_TIME),
+        counts,
+    );
+}
+
+/// The overlay's lines; measurements not taken yet show as "-"
+pub fn overlay_text(
+    fps: Option<f64>,
+    frame_ms: Option<f64>,
+    events_ms: Option<f64>,
+    counts: EntityCounts,
+) -> String {
+    let measure = |value: Option<f64>, decimals: usize| {
+        value.map_or_else(|| "-".to_string(), |v| format!("{v:.decimals$}"))
+    };
+    let mut text = format!(
+        "FPS {} ({} ms/frame)\n",
+        measure(fps, 0),
+        measure(frame_ms, 1)
+    );
+    let _ = writeln!(
+        text,
+        "{} nodes, {} edges, {} labels",
+        counts.nodes, counts.edges, counts.labels
+    );
+    let _ = writeln!(
+        text,
+        "{} entities, {} meshes in view",
+        counts.total, counts.visible_meshes
+    );
+    let _ = write!(text, "live events {} ms/frame", measure(events_ms, 2));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlay_text() {
+        let counts = EntityCounts {
+            nodes: 10,
+            edges: 12,
+            labels: 10,
+            visible_meshes: 30,
+            total: 80,
+        };
+        assert_eq!(
+            overlay_text(Some(59.94), Some(16.68), None, counts),
+            "FPS 60 (16.7 ms/frame)\n10 nodes, 12 edges, 10 labels\n\
+             80 entities, 30 meshes in view\nlive events - ms/frame"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::keybindings::{Action, KeyBindings};
+use crate::types::{EdgeArrowHead, EdgeShaft, GraphEdge, SearchState};
+use crate::visualization::update_edge_positions;
+use bevy::prelude::*;
+
+/// Length of the tip of a line edge's arrow
+const ARROW_TIP_LENGTH: f32 = 0.3;
+
+/// Whether edges are drawn as thin lines instead of their tube and cone
+/// meshes, which is far cheaper for big graphs
+///
+/// Edges keep their entities, so they can still be hovered, highlighted and
+/// faded; the lines take the color of their shaft's material.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub struct EdgeLines {
+    pub enabled: bool,
+}
+
+/// Switches edges between meshes and lines
+pub struct EdgeLinesPlugin;
+
+impl Plugin for EdgeLinesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EdgeLines>().add_systems(
+            Update,
+            (
+                toggle_edge_lines,
+                hide_edge_meshes,
+                draw_edge_lines.after(update_edge_positions),
+            )
+                .chain(),
+        );
+    }
+}
+
+pub fn toggle_edge_lines(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    mut lines: ResMut<EdgeLines>,
+) {
+    if !search_state.active && bindings.just_pressed(Action::ToggleEdgeLines, &keyboard_input) {
+        lines.enabled = !lines.enabled;
+    }
+}
+
+/// Hides the shafts and arrow heads of edges while they're drawn as lines
+#[allow(clippy::type_complexity)]
+pub fn hide_edge_meshes(
+    lines: Res<EdgeLines>,
+    mut part_query: Query<&mut Visibility, Or<(With<EdgeShaft>, With<EdgeArrowHead>)>>,
+) {
+    let visibility = if lines.enabled {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+    for mut part in &mut part_query {
+        part.set_if_neq(visibility);
+    }
+}
+
+/// Draws each shown edge as a line along its shaft, ending in an arrow where
+/// it has an arrow head
+#[allow(clippy::type_complexity)]
+pub fn draw_edge_lines(
+    mut gizmos: Gizmos,
+    lines: Res<EdgeLines>,
+    materials: Res<Assets<StandardMaterial>>,
+    edge_query: Query<(&Transform, &Children, &InheritedVisibility), With<GraphEdge>>,
+    part_query: Query<
+        (
+            &Transform,
+            &MeshMaterial3d<StandardMaterial>,
+            Has<EdgeArrowHead>,
+        ),
+        Or<(With<EdgeShaft>, With<EdgeArrowHead>)>,
+    >,
+) {
+    if !lines.enabled {
+        return;
+    }
+    for (edge_transform, children, visibility) in &edge_query {
+        if !visibility.get() {
+            continue;
+        }
+        let mut shaft = None;
+        let mut tip = None;
+        for (transform, material, is_arrow_head) in part_query.iter_many(children) {
+            if is_arrow_head {
+                tip = Some(edge_transform.transform_point(transform.translation));
+            } else {
+                // The shaft is a unit cylinder stretched between the nodes
+                let shaft_transform = edge_transform.mul_transform(*transform);
+                let ends = (
+                    shaft_transform.transform_point(Vec3::NEG_Y * 0.5),
+                    shaft_transform.transform_point(Vec3::Y * 0.5),
+                );
+                shaft = Some((ends, materials.get(&material.0).map(line_color)));
+            }
+        }
+        let Some(((from, to), color)) = shaft else {
+            continue;
+        };
+        let color = color.unwrap_or(Color::WHITE);
+        match tip {
+            Some(tip) => {
+                gizmos
+                    .arrow(from, tip, color)
+                    .with_tip_length(ARROW_TIP_LENGTH);
+            }
+            None => gizmos.line(from, to, color),
+        }
+    }
+}
+
+/// Color of a line drawn in place of a mesh with `material`, brightened by
+/// its glow
+fn line_color(material: &StandardMaterial) -> Color {
+    let base = material.base_color.to_linear();
+    let glow = material.emissive;
+    LinearRgba::new(
+        base.red + glow.red,
+        base.green + glow.green,
+        base.blue + glow.blue,
+        base.alpha,
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregate::Aggregation;
+    use crate::graph_state::GraphState;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+    use crate::types::{LayoutSettings, Timeline, ViewSettings};
+    use crate::visualization::refresh_graph_scene;
+
+    #[test]
+    fn test_edge_meshes_hide_while_drawn_as_lines() {
+        let content = r#"{
+            "nodes": [{"id": "a"}, {"id": "b"}],
+            "edges": [{"from": "a", "to": "b"}]
+        }"#;
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("json")).unwrap();
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<LayoutSettings>()
+            .init_resource::<ViewSettings>()
+            .init_resource::<Timeline>()
+            .init_resource::<Aggregation>()
+            .insert_resource(EdgeLines { enabled: true })
+            .insert_resource(state)
+            .add_systems(Update, (refresh_graph_scene, hide_edge_meshes).chain());
+        app.world_mut().resource_mut::<GraphState>().set_changed();
+        app.update();
+
+        let parts = |world: &mut World| {
+            world
+                .query_filtered::<&Visibility, Or<(With<EdgeShaft>, With<EdgeArrowHead>)>>()
+                .iter(world)
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(parts(app.world_mut()), [Visibility::Hidden; 2]);
+
+        app.world_mut().resource_mut::<EdgeLines>().enabled = false;
+        app.update();
+        assert_eq!(parts(app.world_mut()), [Visibility::Inherited; 2]);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::camera::keyboard_camera_controls;
+use crate::events::{EventNodeInfo, GraphEvent};
+use crate::graph_state::GraphState;
+use crate::highlight::HighlightKind;
+use crate::history::EditHistory;
+use crate::hover::update_hovered_node;
+use crate::keybindings::{Action, KeyBindings};
+use crate::status::StatusMessage;
+use crate::types::{
+    EditState, GraphNode, HoverState, RenameBox, SearchState, ThemedPanel, ThemedText,
+};
+use bevy::input::ButtonState;
+use bevy::input::InputSystem;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_panorbit_camera::EguiWantsFocus;
+
+/// Longest gap between the clicks of a double-click
+const DOUBLE_CLICK_SECS: f32 = 0.35;
+
+/// How far the cursor may move between the clicks of a double-click
+const DOUBLE_CLICK_DISTANCE: f32 = 8.0;
+
+/// Name given to nodes created by double-clicking, until they are renamed
+const NEW_NODE_NAME: &str = "New node";
+
+/// Edit mode: adding, connecting, renaming and deleting nodes at runtime
+pub struct EditPlugin;
+
+impl Plugin for EditPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditState>()
+            .add_systems(Startup, setup_rename_box)
+            .add_systems(PreUpdate, handle_rename_input.after(InputSystem))
+            .add_systems(
+                Update,
+                (toggle_edit_mode, handle_edit_clicks, handle_edit_keys)
+                    .chain()
+                    .after(update_hovered_node)
+                    .before(keyboard_camera_controls),
+            )
+            .add_systems(Update, (update_rename_box, draw_edge_drag));
+    }
+}
+
+fn setup_rename_box(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+        Visibility::Hidden,
+        RenameBox,
+        ThemedText,
+        ThemedPanel,
+    ));
+}
+
+pub fn toggle_edit_mode(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    search_state: Res<SearchState>,
+    time: Res<Time>,
+    mut edit: ResMut<EditState>,
+    mut message: ResMut<StatusMessage>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::ToggleEditMode, &keyboard_input) {
+        return;
+    }
+    edit.active = !edit.active;
+    edit.drag_from = None;
+    edit.renaming = None;
+    message.show(
+        if edit.active {
+            "Edit mode: double-click to add a node, drag between nodes to connect them"
+        } else {
+            "Edit mode off"
+        },
+        &time,
+    );
+}
+
+/// Double-clicking empty space adds a node and double-clicking a node renames
+/// it; pressing on a node starts dragging an edge from it
+#[allow(clippy::too_many_arguments)]
+pub fn handle_edit_clicks(
+    mouse: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    egui_focus: Option<Res<EguiWantsFocus>>,
+    hover: Res<HoverState>,
+    node_query: Query<&GraphNode>,
+    time: Res<Time>,
+    mut edit: ResMut<EditState>,
+    mut history: ResMut<EditHistory>,
+    mut graph_state: ResMut<GraphState>,
+    mut last_click: Local<Option<(f32, Vec2)>>,
+) {
+    if !edit.active || edit.renaming.is_some() {
+        return;
+    }
+    let hovered = hover
+        .node
+        .and_then(|entity| node_query.get(entity).ok())
+        .map(|node| node.id.clone());
+
+    if mouse.just_released(MouseButton::Left)
+        && let Some(from) = edit.drag_from.take()
+        && let Some(to) = hovered.clone().filter(|to| *to != from)
+    {
+        history.apply(&mut graph_state, vec![GraphEvent::AddEdge { from, to }]);
+    }
+
+    if !mouse.just_pressed(MouseButton::Left) || egui_focus.is_some_and(|focus| focus.curr) {
+        return;
+    }
+    let Some(cursor) = window_query.single().ok().and_then(Window::cursor_position) else {
+        return;
+    };
+    let now = time.elapsed_secs();
+    let double_click = last_click.is_some_and(|(at, position)| {
+        now - at < DOUBLE_CLICK_SECS && position.distance(cursor) < DOUBLE_CLICK_DISTANCE
+    });
+    // A third click starts a new double-click rather than completing another
+    *last_click = (!double_click).then_some((now, cursor));
+
+    match (double_click, hovered) {
+        (true, Some(id)) => start_rename(&mut edit, id),
+        (true, None) => {
+            let id = unique_node_id(&graph_state);
+            history.apply(
+                &mut graph_state,
+                vec![GraphEvent::AddNode {
+                    id: id.clone(),
+                    info: EventNodeInfo {
+                        name: NEW_NODE_NAME.to_string(),
+                        node_type: None,
+                        level: 0,
+                        ..Default::default()
+                    },
+                }],
+            );
+            start_rename(&mut edit, id);
+        }
+        (false, Some(id)) => edit.drag_from = Some(id),
+        (false, None) => {}
+    }
+}
+
+fn start_rename(edit: &mut EditState, id: String) {
+    edit.drag_from = None;
+    edit.renaming = Some(id);
+    edit.name.clear();
+}
+
+/// First `nodeN` id not already in the graph
+fn unique_node_id(state: &GraphState) -> String {
+    let mut n = state.node_count() + 1;
+    loop {
+        let id = format!("node{n}");
+        if state.get_node(&id).is_none() {
+            return id;
+        }
+        n += 1;
+    }
+}
+
+/// Renames or deletes the hovered node from the keyboard
+#[allow(clippy::too_many_arguments)]
+pub fn handle_edit_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    search_state: Res<SearchState>,
+    hover: Res<HoverState>,
+    node_query: Query<&GraphNode>,
+    mut edit: ResMut<EditState>,
+    mut history: ResMut<EditHistory>,
+    mut graph_state: ResMut<GraphState>,
+) {
+    // Backspace edits the search query rather than deleting nodes
+    if !edit.active || edit.renaming.is_some() || search_state.active {
+        return;
+    }
+    let Some(id) = hover
+        .node
+        .and_then(|entity| node_query.get(entity).ok())
+        .map(|node| node.id.clone())
+    else {
+        return;
+    };
+
+    if bindings.just_pressed(Action::RenameNode, &keyboard_input) {
+        start_rename(&mut edit, id);
+    } else if bindings.just_pressed(Action::DeleteNode, &keyboard_input) {
+        history.apply(&mut graph_state, vec![GraphEvent::RemoveNode { id }]);
+    }
+}
+
+/// Types into the rename box, committing on Enter and cancelling on Escape
+///
+/// Runs before `Update` and swallows the keyboard while a node is being
+/// renamed, so typing a name doesn't also trigger shortcuts.
+pub fn handle_rename_input(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    mut edit: ResMut<EditState>,
+    mut history: ResMut<EditHistory>,
+    mut graph_state: ResMut<GraphState>,
+) {
+    let Some(id) = edit.renaming.clone() else {
+        keyboard_events.clear();
+        return;
+    };
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Enter => {
+                if let Some(event) = rename_event(&graph_state, &id, &edit.name) {
+                    history.apply(&mut graph_state, vec![event]);
+                }
+                edit.renaming = None;
+                break;
+            }
+            Key::Escape => {
+                edit.renaming = None;
+                break;
+            }
+            Key::Backspace => {
+                edit.name.pop();
+            }
+            Key::Space => edit.name.push(' '),
+            Key::Character(text) => {
+                edit.name.extend(text.chars().filter(|c| !c.is_control()));
+            }
+            _ => {}
+        }
+    }
+    keyboard_input.reset_all();
+}
+
+/// Event giving node `id` the typed name, unless it is blank or unchanged
+fn rename_event(state: &GraphState, id: &str, name: &str) -> Option<GraphEvent> {
+    let node = state.get_node(id)?;
+    let name = name.trim();
+    (!name.is_empty() && name != node.name).then(|| GraphEvent::UpdateNode {
+        id: id.to_string(),
+        info: EventNodeInfo {
+            name: name.to_string(),
+            ..node.into()
+        },
+    })
+}
+
+/// Shows the name being typed next to the node being renamed
+pub fn update_rename_box(
+    edit: Res<EditState>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    node_query: Query<(&GraphNode, &GlobalTransform)>,
+    mut box_query: Query<(&mut Text, &mut Node, &mut Visibility), With<RenameBox>>,
+) {
+    let Ok((mut text, mut style, mut visibility)) = box_query.single_mut() else {
+        return;
+    };
+
+    // A node created this frame has no entity until the scene is rebuilt
+    let position = edit.renaming.as_ref().and_then(|id| {
+        let (_, transform) = node_query.iter().find(|(node, _)| node.id == *id)?;
+        let (camera, camera_transform) = camera_query.single().ok()?;
+        camera
+            .world_to_viewport(camera_transform, transform.translation())
+            .ok()
+    });
+    let Some(position) = position else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+
+    let content = format!("Name: {}_", edit.name);
+    if text.0 != content {
+        text.0 = content;
+    }
+    style.left = Val::Px(position.x + 16.0);
+    style.top = Val::Px(position.y - 16.0);
+    visibility.set_if_neq(Visibility::Visible);
+}
+
+/// Draws the edge being dragged from its node to the cursor
+pub fn draw_edge_drag(
+    mut gizmos: Gizmos,
+    edit: Res<EditState>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    node_query: Query<(&GraphNode, &GlobalTransform)>,
+) {
+    let Some(from) = edit.drag_from.as_ref().and_then(|id| {
+        node_query
+            .iter()
+            .find(|(node, _)| node.id == *id)
+            .map(|(_, transform)| transform.translation())
+    }) else {
+        return;
+    };
+    let Some(ray) = window_query
+        .single()
+        .ok()
+        .and_then(Window::cursor_position)
+        .zip(camera_query.single().ok())
+        .and_then(|(cursor, (camera, camera_transform))| {
+            camera.viewport_to_world(camera_transform, cursor).ok()
+        })
+    else {
+        return;
+    };
+
+    // Keep the loose end as far from the camera as the node it starts at
+    let to = ray.get_point(ray.origin.distance(from));
+    gizmos.line(from, to, HighlightKind::Selection.color());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_node_ids_are_unique() {
+        let mut state = GraphState::new();
+        for _ in 0..3 {
+            let id = unique_node_id(&state);
+            state.process_event(GraphEvent::AddNode {
+                id,
+                info: EventNodeInfo {
+                    name: NEW_NODE_NAME.to_string(),
+                    node_type: None,
+                    level: 0,
+                    ..Default::default()
+                },
+            });
+        }
+        assert_eq!(state.node_count(), 3);
+    }
+
+    #[test]
+    fn test_rename_keeps_type_and_level() {
+        let mut state = GraphState::new();
+        state.process_event(GraphEvent::AddNode {
+            id: "db".to_string(),
+            info: EventNodeInfo {
+                name: "db".to_string(),
+                node_type: Some("database".to_string()),
+                level: 2,
+                ..Default::default()
+            },
+        });
+
+        assert!(rename_event(&state, "db", "  ").is_none());
+        assert!(rename_event(&state, "db", "db").is_none());
+        let event = rename_event(&state, "db", " orders ").unwrap();
+        state.process_event(event);
+        let node = state.get_node("db").unwrap();
+        assert_eq!(node.name, "orders");
+        assert_eq!(node.node_type.as_deref(), Some("database"));
+        assert_eq!(node.level, 2);
+    }
+}
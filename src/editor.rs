@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+
+/// Command that opens the input file at a line, with `{file}` and `{line}`
+/// standing for them, e.g. `code --goto {file}:{line}`
+///
+/// A command without `{file}` is taken to be an editor that reads
+/// `+LINE FILE`, like vi, nano or emacs. Unless set, it is `$VISUAL` or
+/// `$EDITOR`.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct EditorCommand(pub String);
+
+impl Default for EditorCommand {
+    fn default() -> Self {
+        Self(
+            env::var("VISUAL")
+                .or_else(|_| env::var("EDITOR"))
+                .unwrap_or_else(|_| "vi".to_string()),
+        )
+    }
+}
+
+impl EditorCommand {
+    /// The program and its arguments for opening `file` at `line`
+    #[allow(clippy::literal_string_with_formatting_args)]
+    pub fn args(&self, file: &str, line: usize) -> Vec<String> {
+        let words = self.0.split_whitespace();
+        if self.0.contains("{file}") {
+            words
+                .map(|word| {
+                    word.replace("{file}", file)
+                        .replace("{line}", &line.to_string())
+                })
+                .collect()
+        } else {
+            words
+                .map(str::to_string)
+                .chain([format!("+{line}"), file.to_string()])
+                .collect()
+        }
+    }
+
+    /// Starts the editor on `file` at `line`, without waiting for it
+    pub fn open(&self, file: &Path, line: usize) -> Result<(), String> {
+        let file = std::path::absolute(file).map_err(|e| format!("{}: {e}", file.display()))?;
+        let args = self.args(&file.to_string_lossy(), line);
+        let (program, args) = args.split_first().ok_or("no editor command is set")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .spawn()
+            .map_err(|e| format!("couldn't run {program}: {e}"))?;
+        // Reap the editor once it exits, so it doesn't linger as a zombie
+        thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editor_commands_get_file_and_line() {
+        let code = EditorCommand("code --goto {file}:{line}".to_string());
+        assert_eq!(
+            code.args("/tmp/g.dot", 12),
+            ["code", "--goto", "/tmp/g.dot:12"]
+        );
+
+        let vi = EditorCommand("nvim -R".to_string());
+        assert_eq!(vi.args("/tmp/g.dot", 3), ["nvim", "-R", "+3", "/tmp/g.dot"]);
+
+        let empty = EditorCommand(String::new());
+        assert!(empty.open(Path::new("g.dot"), 1).is_err());
+    }
+}
@@ -1,19 +1,82 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
 use std::fmt;
 
+/// Version of the JSON event format this build writes and the newest it reads
+pub const EVENT_FORMAT_VERSION: u64 = 1;
+
+/// JSON schema of the event format, as printed by `dotspace schema`
+pub const EVENT_SCHEMA: &str = include_str!("../docs/event-schema.json");
+
 /// Simplified node information for events
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct EventNodeInfo {
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub node_type: Option<String>,
+    #[serde(default)]
     pub level: u32,
+    /// The input's other attributes of the node, e.g. `owner` or `tier`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>,
 }
 
 /// Edge properties for rich edge information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct EventEdgeInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub edge_type: Option<String>, // e.g., "sync", "async", "return"
-    pub sequence: Option<u32>,     // For sequence diagrams
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u32>, // For sequence diagrams
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<f32>, // Calls per second, for live traffic sources
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_rate: Option<f32>, // Fraction of failed calls (0.0 - 1.0)
+    /// Port of the source node the edge leaves from, as in DOT's `a:out -> b`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_port: Option<String>,
+    /// Port of the target node the edge arrives at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_port: Option<String>,
+}
+
+/// Health of a node as reported by a monitoring source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    Ok,
+    Warn,
+    Critical,
+    Unknown,
+}
+
+impl AlertState {
+    pub const ALL: [Self; 4] = [Self::Ok, Self::Warn, Self::Critical, Self::Unknown];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warn => "warn",
+            Self::Critical => "critical",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Parses a state by its name, ignoring case
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|state| state.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the node needs attention
+    pub const fn is_alerting(self) -> bool {
+        matches!(self, Self::Warn | Self::Critical)
+    }
 }
 
 /// Events that can modify the graph structure
@@ -39,9 +102,41 @@ pub enum GraphEvent {
         info: EventEdgeInfo,
     },
 
+    /// Replace the properties of an existing edge
+    UpdateEdge {
+        from: String,
+        to: String,
+        info: EventEdgeInfo,
+    },
+
     /// Remove an edge between two nodes
     RemoveEdge { from: String, to: String },
 
+    /// Set a live measurement on a node, e.g. its CPU load
+    SetNodeMetric { id: String, key: String, value: f32 },
+
+    /// Set a node's alert state, or clear it with `None`
+    SetNodeState {
+        id: String,
+        state: Option<AlertState>,
+    },
+
+    /// Set a live measurement on an edge, e.g. requests per second over it
+    SetEdgeMetric {
+        from: String,
+        to: String,
+        key: String,
+        value: f32,
+    },
+
+    /// Show a caption over the scene, pointing at a node if it names one,
+    /// for `duration` seconds or until the next caption without one
+    ShowCaption {
+        text: String,
+        duration: Option<f32>,
+        anchor_node: Option<String>,
+    },
+
     /// Clear the entire graph
     Clear,
 
@@ -52,22 +147,268 @@ pub enum GraphEvent {
     BatchEnd,
 }
 
+/// Refuses events written by a newer version of the format
+fn check_version(version: Option<&Value>) -> Result<(), String> {
+    match version.map(Value::as_u64) {
+        Some(Some(version)) if version > EVENT_FORMAT_VERSION => Err(format!(
+            "event format version {version} is newer than this build reads \
+             ({EVENT_FORMAT_VERSION})"
+        )),
+        Some(None) => Err("\"version\" must be a positive integer".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// A node's `"properties"` object, with values that aren't strings written
+/// as JSON
+fn properties_from_json(value: Option<&Value>) -> Result<BTreeMap<String, String>, String> {
+    match value {
+        None | Some(Value::Null) => Ok(BTreeMap::new()),
+        Some(Value::Object(properties)) => Ok(properties
+            .iter()
+            .map(|(key, value)| match value {
+                Value::String(text) => (key.clone(), text.clone()),
+                value => (key.clone(), value.to_string()),
+            })
+            .collect()),
+        Some(_) => Err("\"properties\" must be an object".to_string()),
+    }
+}
+
 impl GraphEvent {
     /// Returns true if this event modifies node data
     #[allow(dead_code)] // For future use
     pub fn affects_node(&self, node_id: &str) -> bool {
         match self {
-            Self::AddNode { id, .. } | Self::UpdateNode { id, .. } | Self::RemoveNode { id } => {
-                id == node_id
-            }
-            Self::AddEdge { from, to } | Self::RemoveEdge { from, to } => {
-                from == node_id || to == node_id
-            }
-            Self::AddRichEdge { from, to, .. } => from == node_id || to == node_id,
+            Self::AddNode { id, .. }
+            | Self::UpdateNode { id, .. }
+            | Self::RemoveNode { id }
+            | Self::SetNodeMetric { id, .. }
+            | Self::SetNodeState { id, .. } => id == node_id,
+            Self::AddEdge { from, to }
+            | Self::RemoveEdge { from, to }
+            | Self::AddRichEdge { from, to, .. }
+            | Self::UpdateEdge { from, to, .. }
+            | Self::SetEdgeMetric { from, to, .. } => from == node_id || to == node_id,
             Self::Clear => true,
-            Self::BatchStart | Self::BatchEnd => false,
+            Self::ShowCaption { .. } | Self::BatchStart | Self::BatchEnd => false,
+        }
+    }
+
+    /// The node ids the event names, for renaming them
+    pub fn node_ids_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Self::AddNode { id, .. }
+            | Self::UpdateNode { id, .. }
+            | Self::RemoveNode { id }
+            | Self::SetNodeMetric { id, .. }
+            | Self::SetNodeState { id, .. } => vec![id],
+            Self::AddEdge { from, to }
+            | Self::RemoveEdge { from, to }
+            | Self::AddRichEdge { from, to, .. }
+            | Self::UpdateEdge { from, to, .. }
+            | Self::SetEdgeMetric { from, to, .. } => vec![from, to],
+            Self::ShowCaption { anchor_node, .. } => anchor_node.iter_mut().collect(),
+            Self::Clear | Self::BatchStart | Self::BatchEnd => Vec::new(),
         }
     }
+
+    /// The event in the JSON form sessions are recorded in, without unset
+    /// properties
+    pub fn to_json(&self) -> Map<String, Value> {
+        let node = |kind: &str, id: &str, info: &EventNodeInfo| {
+            json!({
+                "type": kind,
+                "id": id,
+                "name": info.name,
+                "node_type": info.node_type,
+                "level": info.level,
+                "properties": (!info.properties.is_empty()).then_some(&info.properties),
+            })
+        };
+        let edge =
+            |kind: &str, from: &str, to: &str| json!({ "type": kind, "from": from, "to": to });
+        let rich_edge = |kind: &str, from: &str, to: &str, info: &EventEdgeInfo| {
+            json!({
+                "type": kind,
+                "from": from,
+                "to": to,
+                "label": info.label,
+                "edge_type": info.edge_type,
+                "sequence": info.sequence,
+                "rate": info.rate,
+                "error_rate": info.error_rate,
+                "from_port": info.from_port,
+                "to_port": info.to_port,
+            })
+        };
+
+        let value = match self {
+            Self::AddNode { id, info } => node("add_node", id, info),
+            Self::UpdateNode { id, info } => node("update_node", id, info),
+            Self::RemoveNode { id } => json!({ "type": "remove_node", "id": id }),
+            Self::AddEdge { from, to } => edge("add_edge", from, to),
+            Self::RemoveEdge { from, to } => edge("remove_edge", from, to),
+            Self::SetNodeMetric { id, key, value } => {
+                json!({ "type": "set_node_metric", "id": id, "key": key, "value": value })
+            }
+            Self::SetNodeState { id, state } => json!({
+                "type": "set_node_state",
+                "id": id,
+                "state": state.map(AlertState::name),
+            }),
+            Self::SetEdgeMetric {
+                from,
+                to,
+                key,
+                value,
+            } => json!({
+                "type": "set_edge_metric",
+                "from": from,
+                "to": to,
+                "key": key,
+                "value": value,
+            }),
+            Self::AddRichEdge { from, to, info } => rich_edge("add_rich_edge", from, to, info),
+            Self::UpdateEdge { from, to, info } => rich_edge("update_edge", from, to, info),
+            Self::ShowCaption {
+                text,
+                duration,
+                anchor_node,
+            } => json!({
+                "type": "show_caption",
+                "text": text,
+                "duration": duration,
+                "anchor_node": anchor_node,
+            }),
+            Self::Clear => json!({ "type": "clear" }),
+            Self::BatchStart => json!({ "type": "batch_start" }),
+            Self::BatchEnd => json!({ "type": "batch_end" }),
+        };
+        let Value::Object(mut map) = value else {
+            unreachable!("events are written as objects")
+        };
+        // Leave unset properties out rather than writing nulls
+        map.retain(|_, value| !value.is_null());
+        map.insert("version".to_string(), json!(EVENT_FORMAT_VERSION));
+        map
+    }
+
+    /// Reads one event in the JSON form sessions are recorded in
+    ///
+    /// Live sources that carry JSON events, such as MQTT, share this encoding.
+    /// A node's name defaults to its id and unknown fields are ignored, but
+    /// events from a newer version of the format are refused.
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        check_version(value.get("version"))?;
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        let required = |key: &str| text(key).ok_or_else(|| format!("missing \"{key}\""));
+        let float = |key: &str| {
+            #[allow(clippy::cast_possible_truncation)]
+            value.get(key).and_then(Value::as_f64).map(|v| v as f32)
+        };
+        let node_info = || -> Result<EventNodeInfo, String> {
+            Ok(EventNodeInfo {
+                name: text("name").map_or_else(|| required("id"), Ok)?,
+                node_type: text("node_type"),
+                level: value
+                    .get("level")
+                    .and_then(Value::as_u64)
+                    .and_then(|level| u32::try_from(level).ok())
+                    .unwrap_or_default(),
+                properties: properties_from_json(value.get("properties"))?,
+            })
+        };
+        let edge_info = || EventEdgeInfo {
+            label: text("label"),
+            edge_type: text("edge_type"),
+            sequence: value
+                .get("sequence")
+                .and_then(Value::as_u64)
+                .and_then(|sequence| u32::try_from(sequence).ok()),
+            rate: float("rate"),
+            error_rate: float("error_rate"),
+            from_port: text("from_port"),
+            to_port: text("to_port"),
+        };
+
+        let kind = required("type")?;
+        Ok(match kind.as_str() {
+            "add_node" => Self::AddNode {
+                id: required("id")?,
+                info: node_info()?,
+            },
+            "update_node" => Self::UpdateNode {
+                id: required("id")?,
+                info: node_info()?,
+            },
+            "remove_node" => Self::RemoveNode {
+                id: required("id")?,
+            },
+            "add_edge" => Self::AddEdge {
+                from: required("from")?,
+                to: required("to")?,
+            },
+            "remove_edge" => Self::RemoveEdge {
+                from: required("from")?,
+                to: required("to")?,
+            },
+            "add_rich_edge" => Self::AddRichEdge {
+                from: required("from")?,
+                to: required("to")?,
+                info: edge_info(),
+            },
+            "update_edge" => Self::UpdateEdge {
+                from: required("from")?,
+                to: required("to")?,
+                info: edge_info(),
+            },
+            "set_node_metric" => Self::SetNodeMetric {
+                id: required("id")?,
+                key: required("key")?,
+                value: float("value").ok_or("missing \"value\"")?,
+            },
+            "set_node_state" => Self::SetNodeState {
+                id: required("id")?,
+                // A missing state clears it
+                state: text("state")
+                    .map(|state| {
+                        AlertState::parse(&state)
+                            .ok_or_else(|| format!("unknown state \"{state}\""))
+                    })
+                    .transpose()?,
+            },
+            "set_edge_metric" => Self::SetEdgeMetric {
+                from: required("from")?,
+                to: required("to")?,
+                key: required("key")?,
+                value: float("value").ok_or("missing \"value\"")?,
+            },
+            "show_caption" => Self::ShowCaption {
+                text: required("text")?,
+                duration: float("duration"),
+                anchor_node: text("anchor_node"),
+            },
+            "clear" => Self::Clear,
+            "batch_start" => Self::BatchStart,
+            "batch_end" => Self::BatchEnd,
+            other => return Err(format!("unknown event type \"{other}\"")),
+        })
+    }
+}
+
+// Events are (de)serialized in their session JSON form, so sessions, MQTT
+// messages, scripts and snapshots share one wire format
+impl Serialize for GraphEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GraphEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::from_json(&Value::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
 }
 
 impl fmt::Display for GraphEvent {
@@ -84,7 +425,23 @@ impl fmt::Display for GraphEvent {
                 }
                 write!(f, ")")
             }
+            Self::UpdateEdge { from, to, .. } => write!(f, "UpdateEdge({from} -> {to})"),
             Self::RemoveEdge { from, to } => write!(f, "RemoveEdge({from} -> {to})"),
+            Self::SetNodeMetric { id, key, value } => {
+                write!(f, "SetNodeMetric({id}: {key}={value})")
+            }
+            Self::SetNodeState { id, state } => write!(
+                f,
+                "SetNodeState({id}: {})",
+                state.map_or("none", AlertState::name)
+            ),
+            Self::SetEdgeMetric {
+                from,
+                to,
+                key,
+                value,
+            } => write!(f, "SetEdgeMetric({from} -> {to}: {key}={value})"),
+            Self::ShowCaption { text, .. } => write!(f, "ShowCaption({text})"),
             Self::Clear => write!(f, "Clear"),
             Self::BatchStart => write!(f, "BatchStart"),
             Self::BatchEnd => write!(f, "BatchEnd"),
@@ -119,6 +476,7 @@ mod tests {
                 name: "Node A".to_string(),
                 node_type: None,
                 level: 0,
+                ..Default::default()
             },
         };
 
@@ -126,6 +484,111 @@ mod tests {
         assert!(!event.affects_node("B"));
     }
 
+    #[test]
+    fn test_events_match_the_schema() {
+        let schema: Value = serde_json::from_str(EVENT_SCHEMA).unwrap();
+        assert_eq!(schema["x-version"], EVENT_FORMAT_VERSION);
+        let defs = &schema["$defs"];
+        let info = EventEdgeInfo {
+            label: Some("call".to_string()),
+            ..Default::default()
+        };
+        let node = GraphEvent::AddNode {
+            id: "a".into(),
+            info: EventNodeInfo {
+                name: "A".into(),
+                properties: BTreeMap::from([("owner".into(), "payments".into())]),
+                ..Default::default()
+            },
+        };
+        let read = GraphEvent::from_json(&Value::Object(node.to_json())).unwrap();
+        assert_eq!(read.to_json(), node.to_json());
+        let events = [
+            node,
+            GraphEvent::RemoveNode { id: "a".into() },
+            GraphEvent::AddRichEdge {
+                from: "a".into(),
+                to: "b".into(),
+                info,
+            },
+            GraphEvent::SetNodeState {
+                id: "a".into(),
+                state: Some(AlertState::Warn),
+            },
+            GraphEvent::SetEdgeMetric {
+                from: "a".into(),
+                to: "b".into(),
+                key: "rps".into(),
+                value: 2.0,
+            },
+            GraphEvent::ShowCaption {
+                text: "hello".into(),
+                duration: Some(5.0),
+                anchor_node: Some("a".into()),
+            },
+            GraphEvent::BatchEnd,
+        ];
+        for event in events {
+            let json = event.to_json();
+            let kind = json["type"].as_str().unwrap();
+            let def = &defs[kind];
+            for field in def["required"].as_array().unwrap() {
+                assert!(
+                    json.contains_key(field.as_str().unwrap()),
+                    "{kind}: {field}"
+                );
+            }
+            for field in json.keys().filter(|&key| key != "version" && key != "type") {
+                let known = def["properties"].get(field).is_some()
+                    || def["allOf"][0]["$ref"]
+                        .as_str()
+                        .and_then(|path| {
+                            defs[path.trim_start_matches("#/$defs/")].get("properties")
+                        })
+                        .is_some_and(|properties| properties.get(field).is_some());
+                assert!(known, "{kind}: {field} is missing from the schema");
+            }
+        }
+
+        // Every event the schema describes can be read with just its required fields
+        for variant in schema["oneOf"].as_array().unwrap() {
+            let kind = variant["$ref"]
+                .as_str()
+                .unwrap()
+                .trim_start_matches("#/$defs/");
+            let mut event = Map::new();
+            for field in defs[kind]["required"].as_array().unwrap() {
+                let field = field.as_str().unwrap();
+                let value = match field {
+                    "type" => json!(kind),
+                    "value" => json!(1.0),
+                    _ => json!("a"),
+                };
+                event.insert(field.to_string(), value);
+            }
+            let read = GraphEvent::from_json(&Value::Object(event));
+            assert!(read.is_ok(), "{kind}: {read:?}");
+        }
+
+        // Unknown fields are fine, newer versions are not
+        let event = json!({"type": "clear", "version": 1, "source": "sensor-hub"});
+        assert!(matches!(
+            GraphEvent::from_json(&event),
+            Ok(GraphEvent::Clear)
+        ));
+        let event = json!({"type": "add_node", "id": "a", "properties": {"replicas": 3}});
+        let Ok(GraphEvent::AddNode { info, .. }) = GraphEvent::from_json(&event) else {
+            panic!("add_node not read");
+        };
+        assert_eq!(info.properties["replicas"], "3");
+        let event = json!({"type": "clear", "version": 2});
+        assert!(
+            GraphEvent::from_json(&event)
+                .unwrap_err()
+                .contains("version 2")
+        );
+    }
+
     #[test]
     fn test_edge_events_affect_both_nodes() {
         let event = GraphEvent::AddEdge {
@@ -0,0 +1,584 @@
+use crate::graph_state::{EdgeInfo, GraphData, GraphState, NodeInfo};
+use crate::keybindings::{Action, KeyBindings};
+use crate::loader::InputSource;
+use crate::sources::session::write_session;
+use crate::status::StatusMessage;
+use crate::types::{
+    self, ExportSettings, GraphNode, HoverState, NodeHighlight, SearchState, SelectedNodes,
+};
+use bevy::prelude::*;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use serde_json::{Value, json};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The seed nodes plus everything within `radius` hops of them, ignoring
+/// edge direction, with the edges among those nodes
+pub fn extract_subgraph(data: &GraphData, seeds: &[NodeIndex], radius: u32) -> GraphData {
+    let mut distance: HashMap<NodeIndex, u32> = seeds.iter().map(|&seed| (seed, 0)).collect();
+    let mut queue: VecDeque<NodeIndex> = seeds.iter().copied().collect();
+    while let Some(node) = queue.pop_front() {
+        let next = distance[&node] + 1;
+        if next > radius {
+            continue;
+        }
+        for neighbor in data.graph.neighbors_undirected(node) {
+            if let Entry::Vacant(entry) = distance.entry(neighbor) {
+                entry.insert(next);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    let mut graph = DiGraph::new();
+    let mut node_map = HashMap::new();
+    let mut new_indices = HashMap::new();
+    // Keep the original order so exports of the same selection are stable
+    for (id, &old) in sorted_ids(data) {
+        if distance.contains_key(&old) {
+            let new = graph.add_node(data.graph[old].clone());
+            node_map.insert(id.clone(), new);
+            new_indices.insert(old, new);
+        }
+    }
+    for edge in data.graph.edge_references() {
+        if let (Some(&from), Some(&to)) = (
+            new_indices.get(&edge.source()),
+            new_indices.get(&edge.target()),
+        ) {
+            graph.add_edge(from, to, edge.weight().clone());
+        }
+    }
+
+    GraphData {
+        graph,
+        node_map,
+        hints: data.hints.clone(),
+    }
+}
+
+/// Node ids ordered by their index in the graph
+fn sorted_ids(data: &GraphData) -> Vec<(&String, &NodeIndex)> {
+    let mut ids: Vec<_> = data.node_map.iter().collect();
+    ids.sort_by_key(|&(_, index)| *index);
+    ids
+}
+
+/// Writes the graph as DOT, using the `type` and `level` attributes dotspace reads
+pub fn to_dot(data: &GraphData, name: &str) -> String {
+    let ids: HashMap<NodeIndex, &String> = data
+        .node_map
+        .iter()
+        .map(|(id, &index)| (index, id))
+        .collect();
+
+    let mut dot = format!("digraph {} {{\n", quote(name));
+    for (id, &index) in sorted_ids(data) {
+        let _ = writeln!(
+            dot,
+            "    {} [{}];",
+            quote(id),
+            node_attributes(&data.graph[index])
+        );
+    }
+    let end = |index: NodeIndex, port: Option<&String>| {
+        port.map_or_else(
+            || quote(ids[&index]),
+            |port| format!("{}:{}", quote(ids[&index]), quote(port)),
+        )
+    };
+    for edge in data.graph.edge_references() {
+        let info = edge.weight();
+        let _ = write!(
+            dot,
+            "    {} -> {}",
+            end(edge.source(), info.from_port.as_ref()),
+            end(edge.target(), info.to_port.as_ref())
+        );
+        let attributes = edge_attributes(edge.weight());
+        if attributes.is_empty() {
+            // No `;` here: dotparser reads `"B";` as a node named `B"`
+            dot.push('\n');
+        } else {
+            let _ = writeln!(dot, " [{attributes}];");
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn node_attributes(node: &NodeInfo) -> String {
+    let mut attributes = vec![format!("label={}", quote(&node.name))];
+    if let Some(node_type) = &node.node_type {
+        attributes.push(format!("type={}", quote(node_type)));
+    }
+    attributes.push(format!("level=\"{}\"", node.level));
+    attributes.join(", ")
+}
+
+fn edge_attributes(edge: &EdgeInfo) -> String {
+    let mut attributes = Vec::new();
+    if let Some(label) = &edge.label {
+        attributes.push(format!("label={}", quote(label)));
+    }
+    if let Some(edge_type) = &edge.edge_type {
+        attributes.push(format!("type={}", quote(edge_type)));
+    }
+    if let Some(sequence) = edge.sequence {
+        attributes.push(format!("sequence=\"{sequence}\""));
+    }
+    if let Some(rate) = edge.rate {
+        attributes.push(format!("rate=\"{rate}\""));
+    }
+    if let Some(error_rate) = edge.error_rate {
+        attributes.push(format!("error_rate=\"{error_rate}\""));
+    }
+    attributes.join(", ")
+}
+
+fn quote(s: &str) -> String {
+    format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+/// Writes the graph as JSON with `nodes` and `edges` arrays
+pub fn to_json(data: &GraphData) -> Value {
+    let ids: HashMap<NodeIndex, &String> = data
+        .node_map
+        .iter()
+        .map(|(id, &index)| (index, id))
+        .collect();
+
+    let nodes: Vec<Value> = sorted_ids(data)
+        .into_iter()
+        .map(|(id, &index)| {
+            let node = &data.graph[index];
+            json!({
+                "id": id,
+                "name": node.name,
+                "type": node.node_type,
+                "level": node.level,
+            })
+        })
+        .collect();
+    let edges: Vec<Value> = data
+        .graph
+        .edge_references()
+        .map(|edge| edge_json(ids[&edge.source()], ids[&edge.target()], edge.weight()))
+        .collect();
+
+    json!({ "nodes": nodes, "edges": edges })
+}
+
+/// An edge as an entry of the JSON `edges` array
+pub fn edge_json(from: &str, to: &str, info: &EdgeInfo) -> Value {
+    json!({
+        "from": from,
+        "to": to,
+        "label": info.label,
+        "type": info.edge_type,
+        "sequence": info.sequence,
+        "rate": info.rate,
+        "error_rate": info.error_rate,
+        "from_port": info.from_port,
+        "to_port": info.to_port,
+    })
+}
+
+/// Writes the graph as `GraphML`, with `<data>` keys for the fields dotspace reads
+pub fn to_graphml(data: &GraphData) -> String {
+    const KEYS: [(&str, &str, &str); 8] = [
+        ("node", "label", "string"),
+        ("node", "type", "string"),
+        ("node", "level", "int"),
+        ("edge", "label", "string"),
+        ("edge", "type", "string"),
+        ("edge", "sequence", "int"),
+        ("edge", "rate", "double"),
+        ("edge", "error_rate", "double"),
+    ];
+    let ids: HashMap<NodeIndex, &String> = data
+        .node_map
+        .iter()
+        .map(|(id, &index)| (index, id))
+        .collect();
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+    );
+    for (domain, name, kind) in KEYS {
+        let _ = writeln!(
+            xml,
+            "  <key id=\"{domain}_{name}\" for=\"{domain}\" attr.name=\"{name}\" attr.type=\"{kind}\"/>"
+        );
+    }
+    xml.push_str("  <graph edgedefault=\"directed\">\n");
+    for (id, &index) in sorted_ids(data) {
+        let node = &data.graph[index];
+        let _ = writeln!(xml, "    <node id=\"{}\">", escape_xml(id));
+        write_data(&mut xml, "node_label", Some(&node.name));
+        write_data(&mut xml, "node_type", node.node_type.as_ref());
+        write_data(&mut xml, "node_level", Some(&node.level));
+        xml.push_str("    </node>\n");
+    }
+    for edge in data.graph.edge_references() {
+        let info = edge.weight();
+        let _ = write!(
+            xml,
+            "    <edge source=\"{}\" target=\"{}\"",
+            escape_xml(ids[&edge.source()]),
+            escape_xml(ids[&edge.target()])
+        );
+        for (attribute, port) in [
+            ("sourceport", &info.from_port),
+            ("targetport", &info.to_port),
+        ] {
+            if let Some(port) = port {
+                let _ = write!(xml, " {attribute}=\"{}\"", escape_xml(port));
+            }
+        }
+        xml.push_str(">\n");
+        write_data(&mut xml, "edge_label", info.label.as_ref());
+        write_data(&mut xml, "edge_type", info.edge_type.as_ref());
+        write_data(&mut xml, "edge_sequence", info.sequence.as_ref());
+        write_data(&mut xml, "edge_rate", info.rate.as_ref());
+        write_data(&mut xml, "edge_error_rate", info.error_rate.as_ref());
+        xml.push_str("    </edge>\n");
+    }
+    xml.push_str("  </graph>\n</graphml>\n");
+    xml
+}
+
+fn write_data(xml: &mut String, key: &str, value: Option<&impl ToString>) {
+    if let Some(value) = value {
+        let _ = writeln!(
+            xml,
+            "      <data key=\"{key}\">{}</data>",
+            escape_xml(&value.to_string())
+        );
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A format `dotspace convert` can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Dot,
+    Json,
+    GraphMl,
+}
+
+impl OutputFormat {
+    pub const ALL: [Self; 3] = [Self::Dot, Self::Json, Self::GraphMl];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Dot => "dot",
+            Self::Json => "json",
+            Self::GraphMl => "graphml",
+        }
+    }
+
+    /// The format a file's extension asks for, e.g. `graph.gv` is DOT
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "dot" | "gv" => Some(Self::Dot),
+            "json" => Some(Self::Json),
+            "graphml" | "xml" => Some(Self::GraphMl),
+            _ => None,
+        }
+    }
+
+    /// Writes the graph in this format; `name` is used where the format has
+    /// a graph name
+    pub fn write(self, data: &GraphData, name: &str) -> String {
+        match self {
+            Self::Dot => to_dot(data, name),
+            Self::Json => {
+                // Serializing a `Value` can't fail
+                serde_json::to_string_pretty(&to_json(data)).unwrap_or_default() + "\n"
+            }
+            Self::GraphMl => to_graphml(data),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown output format '{s}' (expected dot, json or graphml)"))
+    }
+}
+
+/// Exports the selected nodes and their neighborhood to DOT and JSON files
+///
+/// The selection is every search-highlighted or clicked node plus the one
+/// search selected, or the hovered node when none of those exist.
+#[allow(clippy::too_many_arguments)]
+pub fn export_subgraph(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    settings: Res<ExportSettings>,
+    search_state: Res<SearchState>,
+    selection: Res<SelectedNodes>,
+    hover: Res<HoverState>,
+    graph_data: Option<Res<types::GraphData>>,
+    input_source: Option<Res<InputSource>>,
+    node_query: Query<(Entity, &GraphNode, Has<NodeHighlight>)>,
+    time: Res<Time>,
+    mut message: ResMut<StatusMessage>,
+) {
+    if !bindings.just_pressed(Action::ExportSubgraph, &keyboard_input) {
+        return;
+    }
+    let Some(graph_data) = graph_data else {
+        return;
+    };
+
+    let mut seeds: HashSet<NodeIndex> = node_query
+        .iter()
+        .filter(|&(entity, node, highlighted)| {
+            highlighted
+                || search_state.selected_node == Some(entity)
+                || selection.0.contains(&node.id)
+        })
+        .map(|(_, node, _)| node.index)
+        .collect();
+    if seeds.is_empty() {
+        seeds.extend(
+            hover
+                .node
+                .and_then(|entity| node_query.get(entity).ok())
+                .map(|(_, node, _)| node.index),
+        );
+    }
+    if seeds.is_empty() {
+        message.show(
+            "Nothing to export: search for nodes or hover one first",
+            &time,
+        );
+        return;
+    }
+    let seeds: Vec<_> = seeds.into_iter().collect();
+    let subgraph = extract_subgraph(&graph_data.0, &seeds, settings.radius);
+    let location = input_source.as_deref().and_then(|s| s.location.as_deref());
+
+    match write_export(&subgraph, location, "subgraph") {
+        Ok((dot_path, json_path)) => message.show(
+            format!(
+                "Exported {} nodes to {} and {}",
+                subgraph.graph.node_count(),
+                dot_path.display(),
+                json_path.display()
+            ),
+            &time,
+        ),
+        Err(e) => message.show(format!("Export failed: {e}"), &time),
+    }
+}
+
+/// Saves the whole graph, including runtime edits, to DOT and JSON files
+pub fn save_graph(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    graph_state: Res<GraphState>,
+    input_source: Option<Res<InputSource>>,
+    time: Res<Time>,
+    mut message: ResMut<StatusMessage>,
+) {
+    if !bindings.just_pressed(Action::SaveGraph, &keyboard_input) {
+        return;
+    }
+    let location = input_source.as_deref().and_then(|s| s.location.as_deref());
+    match write_export(&graph_state.as_graph_data(), location, "edited") {
+        Ok((dot_path, json_path)) => message.show(
+            format!(
+                "Saved to {} and {}",
+                dot_path.display(),
+                json_path.display()
+            ),
+            &time,
+        ),
+        Err(e) => message.show(format!("Save failed: {e}"), &time),
+    }
+}
+
+/// Saves every event applied so far as a session recording that can be
+/// replayed with `dotspace replay`
+pub fn save_session(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    graph_state: Res<GraphState>,
+    input_source: Option<Res<InputSource>>,
+    time: Res<Time>,
+    mut message: ResMut<StatusMessage>,
+) {
+    if !bindings.just_pressed(Action::SaveSession, &keyboard_input) {
+        return;
+    }
+    let location = input_source.as_deref().and_then(|s| s.location.as_deref());
+    let [path] = unused_paths(location, "session", ["jsonl"]);
+    let result = fs::File::create(&path).and_then(|file| {
+        let mut writer = std::io::BufWriter::new(file);
+        write_session(graph_state.event_log(), &mut writer)?;
+        writer.flush()
+    });
+    match result {
+        Ok(()) => message.show(
+            format!(
+                "Saved {} events to {}",
+                graph_state.event_log().len(),
+                path.display()
+            ),
+            &time,
+        ),
+        Err(e) => message.show(format!("Saving session failed: {e}"), &time),
+    }
+}
+
+/// Writes `data` to unused `<stem>.<kind>` DOT and JSON files and returns their paths
+fn write_export(
+    data: &GraphData,
+    location: Option<&str>,
+    kind: &str,
+) -> std::io::Result<(PathBuf, PathBuf)> {
+    let (dot_path, json_path) = export_paths(location, kind);
+    let name = dot_path
+        .file_stem()
+        .map_or_else(|| kind.into(), |stem| stem.to_string_lossy());
+    fs::write(&dot_path, to_dot(data, &name))?;
+    let json = serde_json::to_string_pretty(&to_json(data)).map_err(std::io::Error::from)?;
+    fs::write(&json_path, json)?;
+    Ok((dot_path, json_path))
+}
+
+/// Unused `<stem>.<kind>[-N].dot`/`.json` paths next to a local input
+/// file, or in the working directory for stdin and URLs
+fn export_paths(location: Option<&str>, kind: &str) -> (PathBuf, PathBuf) {
+    unused_paths(location, kind, ["dot", "json"]).into()
+}
+
+/// Unused `<stem>.<kind>[-N].<extension>` paths, one per extension, that
+/// share a name
+fn unused_paths<const N: usize>(
+    location: Option<&str>,
+    kind: &str,
+    extensions: [&str; N],
+) -> [PathBuf; N] {
+    let local = location
+        .filter(|location| !crate::input::is_url(location))
+        .map(Path::new);
+    let dir = local
+        .and_then(Path::parent)
+        .map_or_else(PathBuf::new, Path::to_path_buf);
+    let stem = local
+        .and_then(Path::file_stem)
+        .map_or_else(|| "graph".into(), |stem| stem.to_string_lossy());
+
+    let mut base = format!("{stem}.{kind}");
+    let mut n = 1;
+    loop {
+        let paths = extensions.map(|extension| dir.join(format!("{base}.{extension}")));
+        if !paths.iter().any(|path| path.exists()) {
+            return paths;
+        }
+        n += 1;
+        base = format!("{stem}.{kind}-{n}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    fn chain() -> GraphData {
+        let content = r#"digraph {
+            A [type="team", level="1"];
+            A -> B;
+            B -> C;
+            C -> D;
+        }"#;
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        state.as_graph_data()
+    }
+
+    #[test]
+    fn test_extract_subgraph_within_radius() {
+        let data = chain();
+        let c = data.node_map["C"];
+
+        let subgraph = extract_subgraph(&data, &[c], 1);
+        let mut ids: Vec<_> = subgraph.node_map.keys().cloned().collect();
+        ids.sort();
+        assert_eq!(ids, ["B", "C", "D"]);
+        assert_eq!(subgraph.graph.edge_count(), 2);
+    }
+
+    #[test]
+    fn test_exported_dot_round_trips() {
+        let data = chain();
+        let subgraph = extract_subgraph(&data, &[data.node_map["A"]], 1);
+        let dot = to_dot(&subgraph, "export");
+
+        let (state, _) = load_graph(&SourceRegistry::default(), &dot, Some("dot")).unwrap();
+        let reloaded = state.as_graph_data();
+        assert_eq!(reloaded.graph.node_count(), 2);
+        assert_eq!(reloaded.graph.edge_count(), 1);
+        let a = &reloaded.graph[reloaded.node_map["A"]];
+        assert_eq!(a.node_type.as_deref(), Some("team"));
+        assert_eq!(a.level, 1);
+
+        let json = to_json(&subgraph);
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 2);
+        assert_eq!(json["edges"][0]["from"], "A");
+    }
+
+    #[test]
+    fn test_every_output_format_round_trips() {
+        let content = r#"digraph {
+            A [label="Gate & <Way>", type="team", level="2"];
+            A -> B [label="calls", rate="4.5"];
+            B -> C;
+            C:out -> A;
+        }"#;
+        let registry = SourceRegistry::default();
+        let (state, _) = load_graph(&registry, content, Some("dot")).unwrap();
+        for format in OutputFormat::ALL {
+            let written = format.write(&state.as_graph_data(), "graph");
+            let (reloaded, _) = load_graph(&registry, &written, None).unwrap();
+            assert!(
+                reloaded.changes_to(&state).is_empty(),
+                "{} changed the graph:\n{written}",
+                format.name()
+            );
+        }
+        assert_eq!(
+            OutputFormat::from_path(Path::new("out.GV")),
+            Some(OutputFormat::Dot)
+        );
+        assert_eq!("GraphML".parse(), Ok(OutputFormat::GraphMl));
+    }
+}
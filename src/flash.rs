@@ -0,0 +1,145 @@
+use crate::types::{GraphNode, NodeHighlight};
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// Lines naming nodes to light up, read in the background from a second
+/// input such as a pipe on file descriptor 3, so log tailing pipelines can
+/// mark activity on the graph
+#[derive(Resource)]
+pub struct FlashFeed {
+    receiver: Mutex<Receiver<String>>,
+}
+
+impl FlashFeed {
+    /// A feed with nothing reading into it yet, and the sender for its lines
+    pub fn new() -> (Self, Sender<String>) {
+        let (sender, receiver) = mpsc::channel();
+        let feed = Self {
+            receiver: Mutex::new(receiver),
+        };
+        (feed, sender)
+    }
+
+    /// Reads lines from `path`, or stdin for `-`, until it ends
+    ///
+    /// The file is opened on the reading thread, as opening a FIFO waits for
+    /// its writer.
+    pub fn open(path: String) -> Self {
+        let (feed, sender) = Self::new();
+        thread::spawn(move || {
+            let reader: Box<dyn BufRead> = if path == "-" {
+                Box::new(io::stdin().lock())
+            } else {
+                match File::open(&path) {
+                    Ok(file) => Box::new(BufReader::new(file)),
+                    Err(e) => {
+                        eprintln!("Error opening '{path}' to flash nodes from: {e}");
+                        return;
+                    }
+                }
+            };
+            for line in reader.lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        feed
+    }
+}
+
+/// Lights up the nodes named by lines of a `FlashFeed`, if there is one
+pub struct FlashPlugin;
+
+impl Plugin for FlashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            flash_named_nodes.run_if(resource_exists::<FlashFeed>),
+        );
+    }
+}
+
+/// The words of a log line that could be node ids, the whole line first
+pub fn candidate_ids(line: &str) -> impl Iterator<Item = &str> {
+    let line = line.trim();
+    std::iter::once(line).chain(
+        line.split(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')))
+            .filter(move |word| !word.is_empty() && *word != line),
+    )
+}
+
+/// Gives the nodes named since the last frame a search highlight, which
+/// fades out as usual
+pub fn flash_named_nodes(
+    mut commands: Commands,
+    feed: Res<FlashFeed>,
+    node_query: Query<(Entity, &GraphNode)>,
+) {
+    let Ok(receiver) = feed.receiver.lock() else {
+        return;
+    };
+    let lines: Vec<String> = receiver.try_iter().collect();
+    if lines.is_empty() {
+        return;
+    }
+    let nodes: HashMap<&str, Entity> = node_query
+        .iter()
+        .map(|(entity, node)| (node.id.as_str(), entity))
+        .collect();
+    for id in lines.iter().flat_map(|line| candidate_ids(line)) {
+        if let Some(&entity) = nodes.get(id) {
+            commands
+                .entity(entity)
+                .try_insert(NodeHighlight { fade_timer: 1.0 });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::NodeIndex;
+
+    #[test]
+    fn test_lines_naming_nodes_light_them_up() {
+        let words: Vec<_> =
+            candidate_ids("12:00:01 WARN [orders-db] slow query from api.v2").collect();
+        assert!(words.contains(&"orders-db"));
+        assert!(words.contains(&"api.v2"));
+        assert_eq!(
+            candidate_ids(" payment service ").next(),
+            Some("payment service")
+        );
+
+        let (feed, sender) = FlashFeed::new();
+        let mut app = App::new();
+        app.insert_resource(feed)
+            .add_systems(Update, flash_named_nodes);
+        let mut spawn_node = |id: &str, index| {
+            app.world_mut()
+                .spawn(GraphNode {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    index: NodeIndex::new(index),
+                })
+                .id()
+        };
+        let named = spawn_node("orders-db", 0);
+        let spaced = spawn_node("payment service", 1);
+        let quiet = spawn_node("api", 2);
+
+        for line in ["GET /orders -> orders-db 200", "payment service"] {
+            sender.send(line.to_string()).unwrap();
+        }
+        app.update();
+        assert!(app.world().get::<NodeHighlight>(named).is_some());
+        assert!(app.world().get::<NodeHighlight>(spaced).is_some());
+        assert!(app.world().get::<NodeHighlight>(quiet).is_none());
+    }
+}
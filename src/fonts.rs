@@ -0,0 +1,99 @@
+//! Fallback fonts for label text the built-in font has no glyphs for, like
+//! CJK, emoji, Arabic or Hebrew
+//!
+//! Both the node labels and the UI text keep bevy's built-in font first and
+//! take any character it lacks from the first font in the stack that has it.
+//! Right-to-left scripts are laid out by the text shaper once a font covers
+//! them.
+
+use bevy::prelude::*;
+use bevy::text::CosmicFontSystem;
+use bevy_rich_text3d::LoadFonts;
+use std::path::{Path, PathBuf};
+
+/// Font files to fall back on, in order
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FontStack(pub Vec<PathBuf>);
+
+impl FontStack {
+    /// The stack, once every file is found to be a font
+    pub fn open(paths: Vec<PathBuf>) -> Result<Self, String> {
+        for path in &paths {
+            check_font(path)?;
+        }
+        Ok(Self(paths))
+    }
+}
+
+fn check_font(path: &Path) -> Result<(), String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    Font::try_from_bytes(bytes)
+        .map(drop)
+        .map_err(|e| format!("{}: not a font ({e})", path.display()))
+}
+
+/// Gives the node labels and the UI text the fonts of the [`FontStack`]
+pub struct FontsPlugin;
+
+impl Plugin for FontsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FontStack>()
+            .add_systems(Startup, load_ui_fonts);
+    }
+
+    // Labels read their fonts when the text plugin cleans up, which is after
+    // every plugin has finished, so a stack inserted after this plugin counts
+    fn finish(&self, app: &mut App) {
+        let paths: Vec<String> = app
+            .world()
+            .resource::<FontStack>()
+            .0
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        app.world_mut()
+            .get_resource_or_init::<LoadFonts>()
+            .font_paths
+            .extend(paths);
+    }
+}
+
+/// Loads the stack into the font database of the UI text, which falls back
+/// on any font in it
+fn load_ui_fonts(stack: Res<FontStack>, mut font_system: ResMut<CosmicFontSystem>) {
+    for path in &stack.0 {
+        if let Err(e) = font_system.0.db_mut().load_font_file(path) {
+            warn!("Couldn't load font {}: {e}", path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack_reaches_label_and_ui_fonts() {
+        let font = std::env::temp_dir().join(format!("dotspace-font-{}.ttf", std::process::id()));
+        std::fs::write(&font, bevy::text::DEFAULT_FONT_DATA).unwrap();
+        let stack = FontStack::open(vec![font.clone()]).unwrap();
+        let mut app = App::new();
+        app.add_plugins(FontsPlugin)
+            .init_resource::<CosmicFontSystem>()
+            .insert_resource(stack);
+        app.finish();
+        assert_eq!(
+            app.world().resource::<LoadFonts>().font_paths,
+            [font.to_string_lossy()]
+        );
+
+        let faces = |app: &App| app.world().resource::<CosmicFontSystem>().0.db().len();
+        let before = faces(&app);
+        app.update();
+        assert!(faces(&app) > before);
+        std::fs::remove_file(&font).unwrap();
+
+        let err = FontStack::open(vec![PathBuf::from("Cargo.toml")]).unwrap_err();
+        assert!(err.starts_with("Cargo.toml: not a font"), "{err}");
+    }
+}
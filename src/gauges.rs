@@ -0,0 +1,319 @@
+use crate::graph_state;
+use crate::staleness::fade_stale_nodes;
+use crate::types::{
+    Appearing, GraphData, GraphEdge, GraphNode, NodeChange, OriginalMaterial, ReducedMotion,
+};
+use crate::visualization::{get_node_appearance, refresh_graph_scene};
+use bevy::prelude::*;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Hue of the lowest metric value (blue); the highest is red
+const COLD_HUE: f32 = 240.0;
+
+/// Pulses per second at the lowest and highest metric values
+const PULSE_HZ: (f32, f32) = (0.5, 3.0);
+
+/// How much bigger a pulsing node gets at the peak of each pulse
+const PULSE_AMPLITUDE: f32 = 0.2;
+
+/// Particles on an edge at the lowest and highest flow values
+const FLOW_PARTICLES: (usize, usize) = (1, 6);
+
+/// Particle speed in world units per second at the lowest and highest flow values
+const FLOW_SPEED: (f32, f32) = (1.0, 6.0);
+
+/// Node metrics that drive node color and pulsing, and the edge metric that
+/// drives particle flow, by key
+#[derive(Resource, Default, Clone)]
+pub struct GaugeBindings {
+    pub color: Option<String>,
+    pub pulse: Option<String>,
+    pub flow: Option<String>,
+}
+
+/// A particle travelling along an edge to show traffic over it
+#[derive(Component)]
+pub struct FlowParticle {
+    pub edge: Entity,
+    /// How far along the edge the particle is, from 0 at the source to 1
+    pub progress: f32,
+    pub speed: f32,
+}
+
+/// Live heat-maps of node metrics set by `SetNodeMetric` events, and
+/// traffic along edges from `SetEdgeMetric` events
+pub struct GaugesPlugin;
+
+impl Plugin for GaugesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GaugeBindings>().add_systems(
+            Update,
+            (
+                color_nodes_by_metric
+                    .run_if(resource_exists_and_changed::<GraphData>)
+                    .after(refresh_graph_scene)
+                    .before(fade_stale_nodes),
+                pulse_nodes_by_metric.after(refresh_graph_scene),
+                spawn_flow_particles
+                    .run_if(resource_exists_and_changed::<GraphData>)
+                    .after(refresh_graph_scene),
+                move_flow_particles.after(spawn_flow_particles),
+            ),
+        );
+    }
+}
+
+/// Each node's value of a metric, scaled from 0 at the lowest value in the
+/// graph to 1 at the highest; nodes without the metric are left out
+pub fn metric_levels(data: &graph_state::GraphData, key: &str) -> HashMap<NodeIndex, f32> {
+    scale_levels(
+        data.graph
+            .node_indices()
+            .filter_map(|node| Some((node, data.graph[node].value(key)?)))
+            .collect(),
+    )
+}
+
+/// Each edge's value of a metric, keyed by its endpoints and scaled like
+/// `metric_levels`
+pub fn edge_metric_levels(
+    data: &graph_state::GraphData,
+    key: &str,
+) -> HashMap<(NodeIndex, NodeIndex), f32> {
+    scale_levels(
+        data.graph
+            .edge_references()
+            .filter_map(|edge| {
+                let value = *edge.weight().metrics.get(key)?;
+                Some(((edge.source(), edge.target()), value))
+            })
+            .collect(),
+    )
+}
+
+fn scale_levels<K: Eq + Hash>(values: Vec<(K, f32)>) -> HashMap<K, f32> {
+    let (low, high) = values.iter().fold(
+        (f32::INFINITY, f32::NEG_INFINITY),
+        |(low, high), &(_, value)| (low.min(value), high.max(value)),
+    );
+    values
+        .into_iter()
+        .map(|(key, value)| {
+            // All equal values sit in the middle of the scale
+            let level = if high > low {
+                (value - low) / (high - low)
+            } else {
+                0.5
+            };
+            (key, level)
+        })
+        .collect()
+}
+
+/// Colors nodes from blue to red by the bound metric, on materials of their own
+///
+/// Runs when the scene is rebuilt, which happens whenever a metric changes.
+#[allow(clippy::type_complexity)]
+pub fn color_nodes_by_metric(
+    bindings: Res<GaugeBindings>,
+    graph_data: Res<GraphData>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut node_query: Query<
+        (&GraphNode, &mut MeshMaterial3d<StandardMaterial>),
+        (Without<OriginalMaterial>, Without<NodeChange>),
+    >,
+) {
+    let Some(key) = &bindings.color else {
+        return;
+    };
+    let levels = metric_levels(&graph_data, key);
+    for (node, mut material) in &mut node_query {
+        let Some(&level) = levels.get(&node.index) else {
+            continue;
+        };
+        let Some(copy) = materials.get(&material.0).cloned() else {
+            continue;
+        };
+        material.0 = materials.add(StandardMaterial {
+            base_color: Color::hsl(COLD_HUE * (1.0 - level), 0.85, 0.5),
+            ..copy
+        });
+    }
+}
+
+/// Pulses nodes faster the higher their value of the bound metric, holding
+/// them still at their size under reduced motion
+#[allow(clippy::type_complexity)]
+pub fn pulse_nodes_by_metric(
+    bindings: Res<GaugeBindings>,
+    graph_data: Option<Res<GraphData>>,
+    time: Res<Time>,
+    motion: Res<ReducedMotion>,
+    mut node_query: Query<(&GraphNode, &mut Transform), (Without<Appearing>, Without<NodeChange>)>,
+) {
+    let (Some(key), Some(data)) = (&bindings.pulse, graph_data) else {
+        return;
+    };
+    let levels = metric_levels(&data, key);
+    for (node, mut transform) in &mut node_query {
+        let Some(&level) = levels.get(&node.index) else {
+            continue;
+        };
+        let (_, size) = get_node_appearance(data.graph[node.index].node_type.as_deref());
+        let hz = (PULSE_HZ.1 - PULSE_HZ.0).mul_add(level, PULSE_HZ.0);
+        let wave = if motion.enabled {
+            0.0
+        } else {
+            (time.elapsed_secs() * hz * std::f32::consts::TAU).sin()
+        };
+        transform.scale = Vec3::splat(size * PULSE_AMPLITUDE.mul_add(wave, 1.0));
+    }
+}
+
+/// Replaces the particles on every edge with the bound flow metric, more and
+/// faster ones the higher its value
+///
+/// Runs when the scene is rebuilt, which happens whenever a metric changes.
+#[allow(
+    clippy::too_many_arguments,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub fn spawn_flow_particles(
+    mut commands: Commands,
+    bindings: Res<GaugeBindings>,
+    graph_data: Res<GraphData>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut particle_assets: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+    edge_query: Query<(Entity, &GraphEdge)>,
+    particle_query: Query<Entity, With<FlowParticle>>,
+) {
+    let Some(key) = &bindings.flow else {
+        return;
+    };
+    for particle in &particle_query {
+        commands.entity(particle).despawn();
+    }
+    let (mesh, material) = particle_assets
+        .get_or_insert_with(|| {
+            (
+                meshes.add(Sphere::new(0.08)),
+                materials.add(StandardMaterial {
+                    base_color: Color::srgb(0.6, 0.9, 1.0),
+                    emissive: LinearRgba::rgb(1.2, 2.4, 3.0),
+                    unlit: true,
+                    ..default()
+                }),
+            )
+        })
+        .clone();
+
+    let levels = edge_metric_levels(&graph_data, key);
+    for (edge, graph_edge) in &edge_query {
+        let Some(&level) = levels.get(&(graph_edge.from, graph_edge.to)) else {
+            continue;
+        };
+        let range = (FLOW_PARTICLES.1 - FLOW_PARTICLES.0) as f32;
+        let count = FLOW_PARTICLES.0 + (range * level).round() as usize;
+        let speed = (FLOW_SPEED.1 - FLOW_SPEED.0).mul_add(level, FLOW_SPEED.0);
+        for i in 0..count {
+            commands.spawn((
+                FlowParticle {
+                    edge,
+                    progress: i as f32 / count as f32,
+                    speed,
+                },
+                Mesh3d(mesh.clone()),
+                MeshMaterial3d(material.clone()),
+                Transform::default(),
+            ));
+        }
+    }
+}
+
+/// Moves flow particles along their edges, dropping those whose edge is gone
+///
+/// Under reduced motion the particles stay where they are, still spaced out
+/// more densely the busier the edge.
+pub fn move_flow_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    motion: Res<ReducedMotion>,
+    node_query: Query<(&Transform, &GraphNode), Without<FlowParticle>>,
+    edge_query: Query<&GraphEdge>,
+    mut particle_query: Query<(Entity, &mut FlowParticle, &mut Transform)>,
+) {
+    let positions: HashMap<_, _> = node_query
+        .iter()
+        .map(|(transform, node)| (node.index, transform.translation))
+        .collect();
+    for (entity, mut particle, mut transform) in &mut particle_query {
+        let Ok(edge) = edge_query.get(particle.edge) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        let (Some(&from), Some(&to)) = (positions.get(&edge.from), positions.get(&edge.to)) else {
+            continue;
+        };
+        if !motion.enabled {
+            let length = from.distance(to).max(0.01);
+            particle.progress =
+                (particle.progress + time.delta_secs() * particle.speed / length).fract();
+        }
+        transform.translation = from.lerp(to, particle.progress);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::GraphEvent;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_metric_levels_span_the_graph() {
+        let content = r"digraph {
+            web -> api
+            api -> db
+        }";
+        let (mut state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let cpu = |id: &str, value| GraphEvent::SetNodeMetric {
+            id: id.to_string(),
+            key: "cpu".to_string(),
+            value,
+        };
+        state.process_events(vec![cpu("web", 20.0), cpu("api", 90.0), cpu("db", 40.0)]);
+        // Metrics survive updates to the node itself
+        state.process_event(GraphEvent::UpdateNode {
+            id: "db".to_string(),
+            info: (state.get_node("db").unwrap()).into(),
+        });
+
+        let data = state.as_graph_data();
+        let levels = metric_levels(&data, "cpu");
+        let level = |id: &str| levels[&data.node_map[id]];
+        assert!(level("web").abs() < f32::EPSILON);
+        assert!((level("api") - 1.0).abs() < f32::EPSILON);
+        assert!((level("db") - 2.0 / 7.0).abs() < 1e-5);
+        assert!(metric_levels(&data, "memory").is_empty());
+
+        let rps = |from: &str, to: &str, value| GraphEvent::SetEdgeMetric {
+            from: from.to_string(),
+            to: to.to_string(),
+            key: "rps".to_string(),
+            value,
+        };
+        state.process_events(vec![rps("web", "api", 10.0), rps("api", "db", 50.0)]);
+        let data = state.as_graph_data();
+        let levels = edge_metric_levels(&data, "rps");
+        let level = |from: &str, to: &str| levels[&(data.node_map[from], data.node_map[to])];
+        assert!(level("web", "api").abs() < f32::EPSILON);
+        assert!((level("api", "db") - 1.0).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,193 @@
+//! Synthetic graphs for trying the viewer, and its benchmarks, at scale
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// The shape of a generated graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GraphModel {
+    /// Scale-free: each new node links to nodes in proportion to their
+    /// degree, so a few hubs gather most edges
+    #[default]
+    Barabasi,
+    /// Nodes on a square lattice, each linked to its right and lower neighbor
+    Grid,
+    /// A tree where each node has up to three children
+    Tree,
+}
+
+impl GraphModel {
+    pub const ALL: [Self; 3] = [Self::Barabasi, Self::Grid, Self::Tree];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Barabasi => "barabasi",
+            Self::Grid => "grid",
+            Self::Tree => "tree",
+        }
+    }
+}
+
+impl FromStr for GraphModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|model| model.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown graph model '{s}' (expected barabasi, grid or tree)"))
+    }
+}
+
+/// Children of each node of a generated tree
+const TREE_BRANCHING: usize = 3;
+
+/// A DOT digraph of `nodes` nodes, `n0` on, and as many of `edges` edges as
+/// fit without repeats or self-loops
+///
+/// The model's own edges come first; if it has fewer than asked for, random
+/// ones make up the rest. The same seed always gives the same graph.
+pub fn generate_dot(model: GraphModel, nodes: usize, edges: usize, seed: u64) -> String {
+    let mut rng = SplitMix64(seed);
+    let mut links = match model {
+        GraphModel::Barabasi => barabasi_edges(nodes, edges, &mut rng),
+        GraphModel::Grid => grid_edges(nodes),
+        GraphModel::Tree => (1..nodes).map(|i| ((i - 1) / TREE_BRANCHING, i)).collect(),
+    };
+    links.truncate(edges);
+    add_random_edges(&mut links, nodes, edges, &mut rng);
+
+    let mut dot = format!("digraph {} {{\n", model.name());
+    for i in 0..nodes {
+        let _ = writeln!(dot, "  n{i} [label=\"n{i}\"];");
+    }
+    for (from, to) in links {
+        let _ = writeln!(dot, "  n{from} -> n{to};");
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Preferential attachment, spreading the edges evenly over the nodes
+fn barabasi_edges(nodes: usize, edges: usize, rng: &mut SplitMix64) -> Vec<(usize, usize)> {
+    let mut links = Vec::with_capacity(edges);
+    // Each edge's ends, so a uniform pick from it favors high degrees
+    let mut ends: Vec<usize> = Vec::with_capacity(edges * 2);
+    let newcomers = nodes.saturating_sub(1).max(1);
+    for node in 1..nodes {
+        let quota = edges * node / newcomers - edges * (node - 1) / newcomers;
+        // A list rather than a set, so the ends are recorded in a fixed order
+        let mut targets = Vec::new();
+        for _ in 0..quota.min(node) {
+            // Retry a few times on a repeat, then take any earlier node
+            let target = (0..4)
+                .map(|_| ends.get(rng.below(ends.len().max(1))).copied())
+                .find(|target| target.is_some_and(|t| !targets.contains(&t)))
+                .flatten()
+                .unwrap_or_else(|| rng.below(node));
+            if !targets.contains(&target) {
+                targets.push(target);
+                links.push((node, target));
+            }
+        }
+        for &target in &targets {
+            ends.extend([node, target]);
+        }
+    }
+    links
+}
+
+fn grid_edges(nodes: usize) -> Vec<(usize, usize)> {
+    let width = nodes.isqrt().max(1) + usize::from(nodes.isqrt().pow(2) < nodes);
+    let mut links = Vec::new();
+    for i in 0..nodes {
+        if (i + 1) % width != 0 && i + 1 < nodes {
+            links.push((i, i + 1));
+        }
+        if i + width < nodes {
+            links.push((i, i + width));
+        }
+    }
+    links
+}
+
+/// Tops `links` up to `edges` with random ones it doesn't have yet, giving up
+/// if the graph is too dense to find them
+fn add_random_edges(
+    links: &mut Vec<(usize, usize)>,
+    nodes: usize,
+    edges: usize,
+    rng: &mut SplitMix64,
+) {
+    if nodes < 2 {
+        return;
+    }
+    let mut seen: HashSet<(usize, usize)> = links.iter().copied().collect();
+    let mut attempts = 4 * edges + 100;
+    while links.len() < edges && attempts > 0 {
+        attempts -= 1;
+        let link = (rng.below(nodes), rng.below(nodes));
+        if link.0 != link.1 && seen.insert(link) {
+            links.push(link);
+        }
+    }
+}
+
+/// A small, fast generator, so graphs come out the same everywhere
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    const fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number below `n`, which must not be 0
+    #[allow(clippy::cast_possible_truncation)] // The result is below n
+    const fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_state::GraphQuery;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_models_give_the_asked_for_size() {
+        let registry = SourceRegistry::default();
+        for model in GraphModel::ALL {
+            let dot = generate_dot(model, 200, 500, 7);
+            assert_eq!(dot, generate_dot(model, 200, 500, 7));
+            let (state, _) = load_graph(&registry, &dot, Some("dot")).unwrap();
+            assert_eq!(state.node_count(), 200, "{}", model.name());
+            assert_eq!(state.edge_count(), 500, "{}", model.name());
+        }
+
+        // A tree with no extra edges is one, rooted at n0
+        let dot = generate_dot(GraphModel::Tree, 13, 12, 0);
+        let (state, _) = load_graph(&registry, &dot, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+        assert_eq!(data.successors("n0").len(), 3);
+        assert_eq!(data.predecessors("n12"), ["n3"]);
+
+        // Scale-free graphs grow hubs
+        let dot = generate_dot(GraphModel::Barabasi, 1000, 3000, 1);
+        let (state, _) = load_graph(&registry, &dot, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+        let most = (0..1000)
+            .map(|i| data.predecessors(&format!("n{i}")).len())
+            .max();
+        assert!(most > Some(30), "{most:?}");
+
+        assert_eq!("Grid".parse(), Ok(GraphModel::Grid));
+        assert!("ring".parse::<GraphModel>().is_err());
+    }
+}
@@ -1,22 +1,66 @@
-use crate::events::{EventEdgeInfo, EventNodeInfo, EventResult, GraphEvent};
+use crate::events::{AlertState, EventEdgeInfo, EventNodeInfo, EventResult, GraphEvent};
+use crate::type_rules::TypeRules;
 use bevy::prelude::*;
+use petgraph::Direction;
 use petgraph::graph::{DiGraph, NodeIndex};
-use std::collections::HashMap;
+use petgraph::visit::EdgeRef;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::time::SystemTime;
+
+/// Most events kept in the log; older ones are folded into its starting point
+const LOG_LIMIT: usize = 100_000;
 
 /// Node information stored in the graph
 #[derive(Debug, Clone)]
 pub struct NodeInfo {
+    /// Stable identity, the node's key in `node_map`; unlike the name, which
+    /// updates may change and other nodes may share, it never changes
+    pub id: String,
     pub name: String,
     pub node_type: Option<String>,
     pub level: u32,
+    /// Live measurements set by `SetNodeMetric`, by key
+    pub metrics: BTreeMap<String, f32>,
+    /// Alert state set by `SetNodeState`
+    pub state: Option<AlertState>,
+    /// The input's other attributes of the node, by name
+    pub properties: BTreeMap<String, String>,
 }
 
-impl From<EventNodeInfo> for NodeInfo {
-    fn from(info: EventNodeInfo) -> Self {
+impl NodeInfo {
+    /// The node `id` as an event describes it, before any metrics or state
+    pub fn new(id: String, info: EventNodeInfo) -> Self {
         Self {
+            id,
             name: info.name,
             node_type: info.node_type,
             level: info.level,
+            metrics: BTreeMap::new(),
+            state: None,
+            properties: info.properties,
+        }
+    }
+
+    /// A number the node carries under `key`: a live metric, or a property
+    /// that reads as a number
+    pub fn value(&self, key: &str) -> Option<f32> {
+        self.metrics.get(key).copied().or_else(|| {
+            self.properties
+                .get(key)
+                .and_then(|value| value.trim().parse().ok())
+        })
+    }
+}
+
+impl From<&NodeInfo> for EventNodeInfo {
+    fn from(info: &NodeInfo) -> Self {
+        Self {
+            name: info.name.clone(),
+            node_type: info.node_type.clone(),
+            level: info.level,
+            properties: info.properties.clone(),
         }
     }
 }
@@ -27,6 +71,12 @@ pub struct EdgeInfo {
     pub label: Option<String>,
     pub edge_type: Option<String>,
     pub sequence: Option<u32>,
+    pub rate: Option<f32>,
+    pub error_rate: Option<f32>,
+    pub from_port: Option<String>,
+    pub to_port: Option<String>,
+    /// Live measurements set by `SetEdgeMetric`, by key
+    pub metrics: BTreeMap<String, f32>,
 }
 
 impl From<EventEdgeInfo> for EdgeInfo {
@@ -35,16 +85,235 @@ impl From<EventEdgeInfo> for EdgeInfo {
             label: info.label,
             edge_type: info.edge_type,
             sequence: info.sequence,
+            rate: info.rate,
+            error_rate: info.error_rate,
+            from_port: info.from_port,
+            to_port: info.to_port,
+            metrics: BTreeMap::new(),
         }
     }
 }
 
+impl From<&EdgeInfo> for EventEdgeInfo {
+    fn from(info: &EdgeInfo) -> Self {
+        Self {
+            label: info.label.clone(),
+            edge_type: info.edge_type.clone(),
+            sequence: info.sequence,
+            rate: info.rate,
+            error_rate: info.error_rate,
+            from_port: info.from_port.clone(),
+            to_port: info.to_port.clone(),
+        }
+    }
+}
+
+/// Direction ranks follow each other in, as given by DOT's `rankdir`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankDir {
+    TopToBottom,
+    BottomToTop,
+    LeftToRight,
+    RightToLeft,
+}
+
+impl RankDir {
+    /// Reads a `rankdir` value such as `LR`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "TB" => Some(Self::TopToBottom),
+            "BT" => Some(Self::BottomToTop),
+            "LR" => Some(Self::LeftToRight),
+            "RL" => Some(Self::RightToLeft),
+            _ => None,
+        }
+    }
+
+    /// The direction in the scene that later ranks are placed in
+    pub const fn axis(self) -> Vec3 {
+        match self {
+            Self::TopToBottom => Vec3::NEG_Y,
+            Self::BottomToTop => Vec3::Y,
+            Self::LeftToRight => Vec3::X,
+            Self::RightToLeft => Vec3::NEG_X,
+        }
+    }
+}
+
+/// How the source wants the graph arranged, beyond what its events say
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayoutHints {
+    /// Direction of the layout's ranks, or the layout's own if None
+    pub rank_dir: Option<RankDir>,
+    /// Node ids that share a rank, as in DOT's `{rank=same; a; b}`
+    pub same_rank: Vec<Vec<String>>,
+    /// Where pinned nodes go, whatever the layout, by node id
+    pub pinned: HashMap<String, Vec3>,
+    /// Levels nodes are put on, so the nodes of a layer share a plane
+    pub layers: HashMap<String, u32>,
+    /// Groups whose nodes are kept next to each other within their rank
+    pub groups: HashMap<String, String>,
+}
+
+/// Lines of the input the nodes and edges were written on, 1-based, for
+/// jumping from the scene to the source
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceLines {
+    /// Each node's own statement, or else the first edge naming it
+    pub nodes: HashMap<String, usize>,
+    pub edges: HashMap<(String, String), usize>,
+}
+
 /// Graph data structure
 #[derive(Debug, Clone)]
 pub struct GraphData {
     pub graph: DiGraph<NodeInfo, EdgeInfo>,
-    #[allow(dead_code)]
     pub node_map: HashMap<String, NodeIndex>,
+    pub hints: LayoutHints,
+}
+
+/// Questions about the graph's shape, answered with node ids
+///
+/// Implemented for both the live `GraphState` and the `GraphData` snapshot
+/// the scene shows, so callers don't have to walk the petgraph themselves.
+/// Results are sorted by id.
+pub trait GraphQuery {
+    fn graph(&self) -> &DiGraph<NodeInfo, EdgeInfo>;
+    fn node_map(&self) -> &HashMap<String, NodeIndex>;
+
+    /// Nodes an edge from `id` leads to
+    fn successors(&self, id: &str) -> Vec<String> {
+        adjacent(self, id, &[Direction::Outgoing])
+    }
+
+    /// Nodes with an edge to `id`
+    fn predecessors(&self, id: &str) -> Vec<String> {
+        adjacent(self, id, &[Direction::Incoming])
+    }
+
+    /// Nodes joined to `id` by an edge in either direction
+    fn neighbors(&self, id: &str) -> Vec<String> {
+        adjacent(self, id, &[Direction::Outgoing, Direction::Incoming])
+    }
+
+    /// Nodes with a path to `id`
+    fn ancestors(&self, id: &str) -> Vec<String> {
+        reachable(self, id, Direction::Incoming)
+    }
+
+    /// Nodes a path from `id` leads to
+    fn descendants(&self, id: &str) -> Vec<String> {
+        reachable(self, id, Direction::Outgoing)
+    }
+
+    /// The ids along a shortest path that follows edges from `from` to `to`,
+    /// including both ends
+    fn find_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let (&start, &end) = (self.node_map().get(from)?, self.node_map().get(to)?);
+        let mut parents = HashMap::from([(start, start)]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            if node == end {
+                break;
+            }
+            for next in self.graph().neighbors(node) {
+                if let Entry::Vacant(entry) = parents.entry(next) {
+                    entry.insert(node);
+                    queue.push_back(next);
+                }
+            }
+        }
+        parents.contains_key(&end).then(|| {
+            let mut path = vec![end];
+            while let Some(&node) = path.last().filter(|&&node| node != start) {
+                path.push(parents[&node]);
+            }
+            path.into_iter()
+                .rev()
+                .map(|node| self.graph()[node].id.clone())
+                .collect()
+        })
+    }
+
+    /// Ids of the nodes `matches` accepts
+    fn nodes_matching(&self, matches: impl Fn(&str, &NodeInfo) -> bool) -> Vec<String>
+    where
+        Self: Sized,
+    {
+        let mut ids: Vec<_> = self
+            .node_map()
+            .iter()
+            .filter(|&(id, &index)| matches(id, &self.graph()[index]))
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+fn sorted_ids(graph: &DiGraph<NodeInfo, EdgeInfo>, nodes: HashSet<NodeIndex>) -> Vec<String> {
+    let mut found: Vec<_> = nodes
+        .into_iter()
+        .map(|node| graph[node].id.clone())
+        .collect();
+    found.sort_unstable();
+    found
+}
+
+/// Nodes one edge away from `id` in any of `directions`
+fn adjacent<Q: GraphQuery + ?Sized>(query: &Q, id: &str, directions: &[Direction]) -> Vec<String> {
+    let Some(&index) = query.node_map().get(id) else {
+        return Vec::new();
+    };
+    let nodes = directions
+        .iter()
+        .flat_map(|&direction| query.graph().neighbors_directed(index, direction))
+        .collect();
+    sorted_ids(query.graph(), nodes)
+}
+
+/// Nodes any number of edges away from `id` in `direction`
+fn reachable<Q: GraphQuery + ?Sized>(query: &Q, id: &str, direction: Direction) -> Vec<String> {
+    let Some(&start) = query.node_map().get(id) else {
+        return Vec::new();
+    };
+    let mut seen = HashSet::new();
+    let mut stack = vec![start];
+    while let Some(node) = stack.pop() {
+        for next in query.graph().neighbors_directed(node, direction) {
+            if seen.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+    // A node on a cycle isn't counted as its own ancestor or descendant
+    seen.remove(&start);
+    sorted_ids(query.graph(), seen)
+}
+
+impl GraphQuery for GraphData {
+    fn graph(&self) -> &DiGraph<NodeInfo, EdgeInfo> {
+        &self.graph
+    }
+
+    fn node_map(&self) -> &HashMap<String, NodeIndex> {
+        &self.node_map
+    }
+}
+
+/// An event that was applied to the graph, and when
+#[derive(Debug, Clone)]
+pub struct LoggedEvent {
+    pub at: SystemTime,
+    pub event: GraphEvent,
+}
+
+/// Every event applied to the graph, in order, so past states can be rebuilt
+#[derive(Default)]
+struct EventLog {
+    /// Events that rebuild the graph as it was before the first entry
+    base: Vec<GraphEvent>,
+    entries: Vec<LoggedEvent>,
 }
 
 /// Manages the current state of the graph based on events
@@ -58,6 +327,14 @@ pub struct GraphState {
     in_batch: bool,
     /// Events accumulated during batch
     batch_events: Vec<GraphEvent>,
+    /// None for states rebuilt from a log, which don't need one of their own
+    log: Option<EventLog>,
+    /// Arrangement asked for by the file the graph was loaded from
+    hints: LayoutHints,
+    /// Where the file the graph was loaded from writes each node and edge
+    lines: SourceLines,
+    /// Types given to nodes by the config, whatever their source says
+    type_rules: TypeRules,
 }
 
 impl GraphState {
@@ -68,7 +345,31 @@ impl GraphState {
             node_map: HashMap::new(),
             in_batch: false,
             batch_events: Vec::new(),
+            log: Some(EventLog::default()),
+            hints: LayoutHints::default(),
+            lines: SourceLines::default(),
+            type_rules: TypeRules::default(),
+        }
+    }
+
+    /// An empty state whose nodes are retyped by `rules` as they are added
+    pub fn with_type_rules(rules: TypeRules) -> Self {
+        Self {
+            type_rules: rules,
+            ..Self::new()
+        }
+    }
+
+    /// Sets the rules that retype nodes, and retypes the nodes already there
+    pub fn set_type_rules(&mut self, rules: TypeRules) {
+        for node in self.graph.node_weights_mut() {
+            node.node_type = rules.apply(&node.id, EventNodeInfo::from(&*node)).node_type;
         }
+        self.type_rules = rules;
+    }
+
+    pub const fn type_rules(&self) -> &TypeRules {
+        &self.type_rules
     }
 
     /// Processes a graph event and updates the state
@@ -79,23 +380,45 @@ impl GraphState {
             return EventResult::Success;
         }
 
+        // Batch markers aren't logged; the events they wrap are, once applied
+        let logged = (self.log.is_some()
+            && !matches!(event, GraphEvent::BatchStart | GraphEvent::BatchEnd))
+        .then(|| event.clone());
+        let result = self.apply_event(event);
+        if let Some(event) = logged
+            && matches!(result, EventResult::Success)
+        {
+            self.log_event(event);
+        }
+        result
+    }
+
+    fn apply_event(&mut self, event: GraphEvent) -> EventResult {
         match event {
             GraphEvent::AddNode { id, info } => {
                 if self.node_map.contains_key(&id) {
                     EventResult::NodeExists
                 } else {
-                    let idx = self.graph.add_node(info.into());
+                    let info = self.type_rules.apply(&id, info);
+                    let idx = self.graph.add_node(NodeInfo::new(id.clone(), info));
                     self.node_map.insert(id, idx);
                     EventResult::Success
                 }
             }
 
             GraphEvent::UpdateNode { id, info } => {
+                let info = self.type_rules.apply(&id, info);
                 if let Some(&idx) = self.node_map.get(&id) {
                     self.graph
                         .node_weight_mut(idx)
                         .map_or(EventResult::NodeNotFound, |node| {
-                            *node = info.into();
+                            // Metrics and states come from their own events, so they
+                            // survive updates
+                            *node = NodeInfo {
+                                metrics: std::mem::take(&mut node.metrics),
+                                state: node.state,
+                                ..NodeInfo::new(id, info)
+                            };
                             EventResult::Success
                         })
                 } else {
@@ -106,42 +429,22 @@ impl GraphState {
             GraphEvent::RemoveNode { id } => {
                 if let Some(idx) = self.node_map.remove(&id) {
                     self.graph.remove_node(idx);
-                    // Note: petgraph automatically removes connected edges
+                    // Note: petgraph automatically removes connected edges, and
+                    // moves the last node into the freed index
+                    if let Some(moved) = self.graph.node_weight(idx) {
+                        self.node_map.insert(moved.id.clone(), idx);
+                    }
                     EventResult::Success
                 } else {
                     EventResult::NodeNotFound
                 }
             }
 
-            GraphEvent::AddEdge { from, to } => {
-                match (self.node_map.get(&from), self.node_map.get(&to)) {
-                    (Some(&from_idx), Some(&to_idx)) => {
-                        // Check if edge already exists
-                        if self.graph.find_edge(from_idx, to_idx).is_some() {
-                            EventResult::EdgeExists
-                        } else {
-                            self.graph.add_edge(from_idx, to_idx, EdgeInfo::default());
-                            EventResult::Success
-                        }
-                    }
-                    _ => EventResult::NodeNotFound,
-                }
-            }
+            GraphEvent::AddEdge { from, to } => self.add_edge(&from, &to, EdgeInfo::default()),
 
-            GraphEvent::AddRichEdge { from, to, info } => {
-                match (self.node_map.get(&from), self.node_map.get(&to)) {
-                    (Some(&from_idx), Some(&to_idx)) => {
-                        // Check if edge already exists
-                        if self.graph.find_edge(from_idx, to_idx).is_some() {
-                            EventResult::EdgeExists
-                        } else {
-                            self.graph.add_edge(from_idx, to_idx, info.into());
-                            EventResult::Success
-                        }
-                    }
-                    _ => EventResult::NodeNotFound,
-                }
-            }
+            GraphEvent::AddRichEdge { from, to, info } => self.add_edge(&from, &to, info.into()),
+
+            GraphEvent::UpdateEdge { from, to, info } => self.update_edge(&from, &to, info.into()),
 
             GraphEvent::RemoveEdge { from, to } => {
                 match (self.node_map.get(&from), self.node_map.get(&to)) {
@@ -157,6 +460,20 @@ impl GraphState {
                 }
             }
 
+            GraphEvent::SetNodeMetric { id, key, value } => self.set_node_metric(&id, key, value),
+
+            GraphEvent::SetNodeState { id, state } => self.set_node_state(&id, state),
+
+            GraphEvent::SetEdgeMetric {
+                from,
+                to,
+                key,
+                value,
+            } => self.set_edge_metric(&from, &to, key, value),
+
+            // Captions are shown by the viewer; they're logged but change nothing
+            GraphEvent::ShowCaption { .. } => EventResult::Success,
+
             GraphEvent::Clear => {
                 self.graph.clear();
                 self.node_map.clear();
@@ -181,6 +498,315 @@ impl GraphState {
         }
     }
 
+    fn log_event(&mut self, event: GraphEvent) {
+        let Some(log) = &mut self.log else {
+            return;
+        };
+        log.entries.push(LoggedEvent {
+            at: SystemTime::now(),
+            event,
+        });
+        if log.entries.len() > LOG_LIMIT {
+            self.trim_log(LOG_LIMIT / 2);
+        }
+    }
+
+    /// Folds all but the last `keep` entries into the log's starting point
+    fn trim_log(&mut self, keep: usize) {
+        let mut start = self.unlogged();
+        let Some(log) = &mut self.log else {
+            return;
+        };
+        let dropped = log.entries.len().saturating_sub(keep);
+        start.process_events(std::mem::take(&mut log.base));
+        start.process_events(
+            log.entries
+                .drain(..dropped)
+                .map(|entry| entry.event)
+                .collect(),
+        );
+        log.base = start.inverse_of(&GraphEvent::Clear);
+    }
+
+    /// An empty state that retypes nodes like this one
+    fn unlogged(&self) -> Self {
+        Self {
+            log: None,
+            type_rules: self.type_rules.clone(),
+            ..Self::new()
+        }
+    }
+
+    /// Events applied so far, oldest first
+    pub fn event_log(&self) -> &[LoggedEvent] {
+        self.log.as_ref().map_or(&[], |log| &log.entries)
+    }
+
+    /// The graph as it was after the first `count` logged events
+    #[must_use]
+    pub fn state_at(&self, count: usize) -> Self {
+        let mut state = self.unlogged();
+        if let Some(log) = &self.log {
+            state.process_events(log.base.clone());
+            state.process_events(
+                log.entries
+                    .iter()
+                    .take(count)
+                    .map(|entry| entry.event.clone())
+                    .collect(),
+            );
+        }
+        state
+    }
+
+    fn set_node_metric(&mut self, id: &str, key: String, value: f32) -> EventResult {
+        let node = self
+            .node_map
+            .get(id)
+            .and_then(|&idx| self.graph.node_weight_mut(idx));
+        node.map_or(EventResult::NodeNotFound, |node| {
+            node.metrics.insert(key, value);
+            EventResult::Success
+        })
+    }
+
+    fn set_node_state(&mut self, id: &str, state: Option<AlertState>) -> EventResult {
+        let node = self
+            .node_map
+            .get(id)
+            .and_then(|&idx| self.graph.node_weight_mut(idx));
+        node.map_or(EventResult::NodeNotFound, |node| {
+            node.state = state;
+            EventResult::Success
+        })
+    }
+
+    fn add_edge(&mut self, from: &str, to: &str, info: EdgeInfo) -> EventResult {
+        match (self.node_map.get(from), self.node_map.get(to)) {
+            (Some(&from_idx), Some(&to_idx)) => {
+                // Check if edge already exists
+                if self.graph.find_edge(from_idx, to_idx).is_some() {
+                    EventResult::EdgeExists
+                } else {
+                    self.graph.add_edge(from_idx, to_idx, info);
+                    EventResult::Success
+                }
+            }
+            _ => EventResult::NodeNotFound,
+        }
+    }
+
+    fn set_edge_metric(&mut self, from: &str, to: &str, key: String, value: f32) -> EventResult {
+        match (self.node_map.get(from), self.node_map.get(to)) {
+            (Some(&from_idx), Some(&to_idx)) => self
+                .graph
+                .find_edge(from_idx, to_idx)
+                .and_then(|edge| self.graph.edge_weight_mut(edge))
+                .map_or(EventResult::EdgeNotFound, |edge| {
+                    edge.metrics.insert(key, value);
+                    EventResult::Success
+                }),
+            _ => EventResult::NodeNotFound,
+        }
+    }
+
+    /// Replaces the properties of the edge between two nodes, keeping its metrics
+    fn update_edge(&mut self, from: &str, to: &str, info: EdgeInfo) -> EventResult {
+        match (self.node_map.get(from), self.node_map.get(to)) {
+            (Some(&from_idx), Some(&to_idx)) => self
+                .graph
+                .find_edge(from_idx, to_idx)
+                .and_then(|edge| self.graph.edge_weight_mut(edge))
+                .map_or(EventResult::EdgeNotFound, |edge| {
+                    *edge = EdgeInfo {
+                        metrics: std::mem::take(&mut edge.metrics),
+                        ..info
+                    };
+                    EventResult::Success
+                }),
+            _ => EventResult::NodeNotFound,
+        }
+    }
+
+    /// Events that undo `event` if it is applied to the graph as it is now
+    ///
+    /// Events that would fail (e.g. adding a node that exists) have no inverse.
+    pub fn inverse_of(&self, event: &GraphEvent) -> Vec<GraphEvent> {
+        match event {
+            GraphEvent::AddNode { id, .. } if !self.node_map.contains_key(id) => {
+                vec![GraphEvent::RemoveNode { id: id.clone() }]
+            }
+            GraphEvent::UpdateNode { id, .. } => self
+                .get_node(id)
+                .map(|node| GraphEvent::UpdateNode {
+                    id: id.clone(),
+                    info: node.into(),
+                })
+                .into_iter()
+                .collect(),
+            GraphEvent::RemoveNode { id } => self.node_map.get(id).map_or_else(Vec::new, |&idx| {
+                let mut events = self.restore_node(id, idx);
+                events.extend(
+                    self.graph
+                        .edges_directed(idx, Direction::Outgoing)
+                        .chain(self.graph.edges_directed(idx, Direction::Incoming))
+                        .flat_map(|edge| {
+                            Self::restore_edge(
+                                &self.id_of(edge.source()),
+                                &self.id_of(edge.target()),
+                                edge.weight(),
+                            )
+                        }),
+                );
+                events
+            }),
+            GraphEvent::AddEdge { from, to } | GraphEvent::AddRichEdge { from, to, .. } => {
+                match (self.node_map.get(from), self.node_map.get(to)) {
+                    (Some(&from_idx), Some(&to_idx))
+                        if self.graph.find_edge(from_idx, to_idx).is_none() =>
+                    {
+                        vec![GraphEvent::RemoveEdge {
+                            from: from.clone(),
+                            to: to.clone(),
+                        }]
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            GraphEvent::UpdateEdge { from, to, .. } => self
+                .find_edge_info(from, to)
+                .map(|info| GraphEvent::UpdateEdge {
+                    from: from.clone(),
+                    to: to.clone(),
+                    info: info.into(),
+                })
+                .into_iter()
+                .collect(),
+            GraphEvent::RemoveEdge { from, to } => self
+                .find_edge_info(from, to)
+                .map(|info| Self::restore_edge(from, to, info))
+                .unwrap_or_default(),
+            GraphEvent::SetNodeMetric { .. }
+            | GraphEvent::SetNodeState { .. }
+            | GraphEvent::SetEdgeMetric { .. } => self.live_inverse_of(event).into_iter().collect(),
+            GraphEvent::Clear => self.snapshot(),
+            GraphEvent::AddNode { .. }
+            | GraphEvent::ShowCaption { .. }
+            | GraphEvent::BatchStart
+            | GraphEvent::BatchEnd => Vec::new(),
+        }
+    }
+
+    /// Events that rebuild the graph as it is now, with its live metrics and
+    /// alert states, nodes in id order
+    pub fn snapshot(&self) -> Vec<GraphEvent> {
+        let mut nodes: Vec<_> = self.node_map.iter().collect();
+        nodes.sort_unstable();
+        let mut events: Vec<_> = nodes
+            .into_iter()
+            .flat_map(|(id, &idx)| self.restore_node(id, idx))
+            .collect();
+        events.extend(self.graph.edge_references().flat_map(|edge| {
+            Self::restore_edge(
+                &self.id_of(edge.source()),
+                &self.id_of(edge.target()),
+                edge.weight(),
+            )
+        }));
+        events
+    }
+
+    fn find_edge_info(&self, from: &str, to: &str) -> Option<&EdgeInfo> {
+        let (&from_idx, &to_idx) = (self.node_map.get(from)?, self.node_map.get(to)?);
+        self.graph
+            .find_edge(from_idx, to_idx)
+            .and_then(|edge| self.graph.edge_weight(edge))
+    }
+
+    /// Inverse of an event that sets a live metric or state
+    ///
+    /// A metric can't be unset, so setting it the first time has no inverse.
+    fn live_inverse_of(&self, event: &GraphEvent) -> Option<GraphEvent> {
+        match event {
+            GraphEvent::SetNodeMetric { id, key, .. } => self
+                .get_node(id)
+                .and_then(|node| node.metrics.get(key))
+                .map(|&value| GraphEvent::SetNodeMetric {
+                    id: id.clone(),
+                    key: key.clone(),
+                    value,
+                }),
+            GraphEvent::SetNodeState { id, .. } => {
+                self.get_node(id).map(|node| GraphEvent::SetNodeState {
+                    id: id.clone(),
+                    state: node.state,
+                })
+            }
+            GraphEvent::SetEdgeMetric { from, to, key, .. } => self
+                .find_edge_info(from, to)
+                .and_then(|info| info.metrics.get(key))
+                .map(|&value| GraphEvent::SetEdgeMetric {
+                    from: from.clone(),
+                    to: to.clone(),
+                    key: key.clone(),
+                    value,
+                }),
+            _ => None,
+        }
+    }
+
+    /// Events that re-add an indexed node along with its metrics and state
+    fn restore_node(&self, id: &str, idx: NodeIndex) -> Vec<GraphEvent> {
+        let node = &self.graph[idx];
+        let mut events = vec![GraphEvent::AddNode {
+            id: id.to_string(),
+            info: node.into(),
+        }];
+        events.extend(
+            node.metrics
+                .iter()
+                .map(|(key, &value)| GraphEvent::SetNodeMetric {
+                    id: id.to_string(),
+                    key: key.clone(),
+                    value,
+                }),
+        );
+        if node.state.is_some() {
+            events.push(GraphEvent::SetNodeState {
+                id: id.to_string(),
+                state: node.state,
+            });
+        }
+        events
+    }
+
+    fn id_of(&self, idx: NodeIndex) -> String {
+        self.graph
+            .node_weight(idx)
+            .map(|node| node.id.clone())
+            .unwrap_or_default()
+    }
+
+    /// Events that re-add an edge along with its metrics
+    fn restore_edge(from: &str, to: &str, info: &EdgeInfo) -> Vec<GraphEvent> {
+        let mut events = vec![GraphEvent::AddRichEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            info: info.into(),
+        }];
+        events.extend(
+            info.metrics
+                .iter()
+                .map(|(key, &value)| GraphEvent::SetEdgeMetric {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    key: key.clone(),
+                    value,
+                }),
+        );
+        events
+    }
+
     /// Processes multiple events
     pub fn process_events(&mut self, events: Vec<GraphEvent>) -> Vec<EventResult> {
         events.into_iter().map(|e| self.process_event(e)).collect()
@@ -191,51 +817,45 @@ impl GraphState {
         let mut new_graph = DiGraph::new();
         let mut new_map = HashMap::new();
 
-        // Rebuild the graph
+        // Rebuild the graph, remembering where each node went
+        let mut new_indices = HashMap::new();
         for (id, &old_idx) in &self.node_map {
             if let Some(node_info) = self.graph.node_weight(old_idx) {
-                let new_idx = new_graph.add_node(NodeInfo {
-                    name: node_info.name.clone(),
-                    node_type: node_info.node_type.clone(),
-                    level: node_info.level,
-                });
+                let new_idx = new_graph.add_node(node_info.clone());
                 new_map.insert(id.clone(), new_idx);
+                new_indices.insert(old_idx, new_idx);
             }
         }
 
-        // Copy edges
-        for edge in self.graph.edge_indices() {
-            if let Some((from, to)) = self.graph.edge_endpoints(edge) {
-                // Find the corresponding new indices
-                let from_id = self
-                    .node_map
-                    .iter()
-                    .find(|&(_, &idx)| idx == from)
-                    .map(|(id, _)| id);
-                let to_id = self
-                    .node_map
-                    .iter()
-                    .find(|&(_, &idx)| idx == to)
-                    .map(|(id, _)| id);
-
-                if let (Some(from_id), Some(to_id)) = (from_id, to_id) {
-                    if let (Some(&new_from), Some(&new_to)) =
-                        (new_map.get(from_id), new_map.get(to_id))
-                    {
-                        // Copy edge with its properties
-                        let edge_info = self.graph.edge_weight(edge).cloned().unwrap_or_default();
-                        new_graph.add_edge(new_from, new_to, edge_info);
-                    }
-                }
+        // Copy edges with their labels, types, sequence numbers and rates
+        for edge in self.graph.edge_references() {
+            if let (Some(&new_from), Some(&new_to)) = (
+                new_indices.get(&edge.source()),
+                new_indices.get(&edge.target()),
+            ) {
+                new_graph.add_edge(new_from, new_to, edge.weight().clone());
             }
         }
 
         GraphData {
             graph: new_graph,
             node_map: new_map,
+            hints: self.hints.clone(),
         }
     }
 
+    pub fn set_layout_hints(&mut self, hints: LayoutHints) {
+        self.hints = hints;
+    }
+
+    pub fn set_source_lines(&mut self, lines: SourceLines) {
+        self.lines = lines;
+    }
+
+    pub const fn source_lines(&self) -> &SourceLines {
+        &self.lines
+    }
+
     /// Returns the number of nodes in the graph
     #[allow(dead_code)] // Used in tests
     pub fn node_count(&self) -> usize {
@@ -255,6 +875,99 @@ impl GraphState {
             .get(id)
             .and_then(|&idx| self.graph.node_weight(idx))
     }
+
+    /// Events that turn this graph into `target`: removed edges first, then
+    /// removed, added and changed nodes, then added and changed edges
+    ///
+    /// Live measurements and alert states aren't compared.
+    pub fn changes_to(&self, target: &Self) -> Vec<GraphEvent> {
+        let (before, after) = (self.as_graph_data(), target.as_graph_data());
+        let (old_edges, new_edges) = (edge_infos(&before), edge_infos(&after));
+        let mut node_ids: Vec<_> = before
+            .node_map
+            .keys()
+            .chain(after.node_map.keys())
+            .collect();
+        node_ids.sort_unstable();
+        node_ids.dedup();
+
+        let mut events: Vec<_> = old_edges
+            .keys()
+            .filter(|key| !new_edges.contains_key(*key))
+            .map(|(from, to)| GraphEvent::RemoveEdge {
+                from: from.clone(),
+                to: to.clone(),
+            })
+            .collect();
+        for id in node_ids {
+            let old = before.node_map.get(id).map(|&idx| &before.graph[idx]);
+            let new = after.node_map.get(id).map(|&idx| &after.graph[idx]);
+            events.extend(match (old, new) {
+                (Some(_), None) => Some(GraphEvent::RemoveNode { id: id.clone() }),
+                (None, Some(new)) => Some(GraphEvent::AddNode {
+                    id: id.clone(),
+                    info: new.into(),
+                }),
+                (Some(old), Some(new)) if EventNodeInfo::from(old) != EventNodeInfo::from(new) => {
+                    Some(GraphEvent::UpdateNode {
+                        id: id.clone(),
+                        info: new.into(),
+                    })
+                }
+                _ => None,
+            });
+        }
+        for ((from, to), info) in new_edges {
+            let (from, to) = (from.clone(), to.clone());
+            match old_edges.get(&(from.clone(), to.clone())) {
+                None => events.push(GraphEvent::AddRichEdge { from, to, info }),
+                Some(old) if *old != info => events.push(GraphEvent::UpdateEdge { from, to, info }),
+                Some(_) => {}
+            }
+        }
+        events
+    }
+}
+
+/// Edge properties by the ids of the nodes they connect, in id order
+fn edge_infos(data: &GraphData) -> BTreeMap<(String, String), EventEdgeInfo> {
+    data.graph
+        .edge_references()
+        .map(|edge| {
+            (
+                (
+                    data.graph[edge.source()].id.clone(),
+                    data.graph[edge.target()].id.clone(),
+                ),
+                edge.weight().into(),
+            )
+        })
+        .collect()
+}
+
+/// Serialized as its snapshot, without the event log
+impl Serialize for GraphState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.snapshot().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GraphState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut state = Self::new();
+        state.process_events(Vec::deserialize(deserializer)?);
+        Ok(state)
+    }
+}
+
+impl GraphQuery for GraphState {
+    fn graph(&self) -> &DiGraph<NodeInfo, EdgeInfo> {
+        &self.graph
+    }
+
+    fn node_map(&self) -> &HashMap<String, NodeIndex> {
+        &self.node_map
+    }
 }
 
 impl Default for GraphState {
@@ -279,6 +992,7 @@ mod tests {
                 name: "Node A".to_string(),
                 node_type: None,
                 level: 0,
+                ..Default::default()
             },
         });
 
@@ -292,6 +1006,7 @@ mod tests {
                 name: "Node A".to_string(),
                 node_type: None,
                 level: 0,
+                ..Default::default()
             },
         });
 
@@ -307,6 +1022,51 @@ mod tests {
         assert_eq!(state.node_count(), 0);
     }
 
+    #[test]
+    fn test_nodes_are_known_by_id_not_name() {
+        let node = |name: &str| EventNodeInfo {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let mut state = GraphState::new();
+        state.process_events(vec![
+            GraphEvent::AddNode {
+                id: "db1".to_string(),
+                info: node("Database"),
+            },
+            GraphEvent::AddNode {
+                id: "db2".to_string(),
+                info: node("Database"),
+            },
+            GraphEvent::AddNode {
+                id: "api".to_string(),
+                info: node("API"),
+            },
+            GraphEvent::AddEdge {
+                from: "api".to_string(),
+                to: "db2".to_string(),
+            },
+        ]);
+        assert_eq!(state.node_count(), 3);
+
+        // Renaming keeps the node's id and edges
+        state.process_event(GraphEvent::UpdateNode {
+            id: "db2".to_string(),
+            info: node("Replica"),
+        });
+        assert_eq!(state.get_node("db2").unwrap().name, "Replica");
+        assert_eq!(state.predecessors("db2"), ["api"]);
+
+        // Removing a node moves another into its index, which keeps its id
+        state.process_event(GraphEvent::RemoveNode {
+            id: "db1".to_string(),
+        });
+        for (id, &index) in &state.node_map {
+            assert_eq!(&state.graph[index].id, id);
+        }
+        assert_eq!(state.successors("api"), ["db2"]);
+    }
+
     #[test]
     fn test_batch_processing() {
         let mut state = GraphState::new();
@@ -319,6 +1079,7 @@ mod tests {
                     name: "A".to_string(),
                     node_type: None,
                     level: 0,
+                    ..Default::default()
                 },
             },
             GraphEvent::AddNode {
@@ -327,6 +1088,7 @@ mod tests {
                     name: "B".to_string(),
                     node_type: None,
                     level: 0,
+                    ..Default::default()
                 },
             },
             GraphEvent::AddEdge {
@@ -341,4 +1103,281 @@ mod tests {
         assert_eq!(state.node_count(), 2);
         assert_eq!(state.edge_count(), 1);
     }
+
+    #[test]
+    fn test_update_edge_replaces_properties() {
+        let mut state = GraphState::new();
+
+        for id in ["A", "B"] {
+            state.process_event(GraphEvent::AddNode {
+                id: id.to_string(),
+                info: EventNodeInfo {
+                    name: id.to_string(),
+                    node_type: None,
+                    level: 0,
+                    ..Default::default()
+                },
+            });
+        }
+        state.process_event(GraphEvent::AddRichEdge {
+            from: "A".to_string(),
+            to: "B".to_string(),
+            info: crate::events::EventEdgeInfo {
+                label: Some("login".to_string()),
+                edge_type: Some("sync".to_string()),
+                sequence: Some(1),
+                ..Default::default()
+            },
+        });
+        let data = state.as_graph_data();
+        let edge = &data.graph[data.graph.edge_indices().next().unwrap()];
+        assert_eq!(edge.label.as_deref(), Some("login"));
+        assert_eq!(edge.edge_type.as_deref(), Some("sync"));
+        assert_eq!(edge.sequence, Some(1));
+
+        let result = state.process_event(GraphEvent::UpdateEdge {
+            from: "A".to_string(),
+            to: "B".to_string(),
+            info: crate::events::EventEdgeInfo {
+                rate: Some(4.0),
+                error_rate: Some(0.25),
+                ..Default::default()
+            },
+        });
+        assert!(matches!(result, EventResult::Success));
+
+        let data = state.as_graph_data();
+        let edge = data.graph.edge_indices().next().unwrap();
+        assert_eq!(data.graph[edge].rate, Some(4.0));
+        assert_eq!(data.graph[edge].error_rate, Some(0.25));
+
+        // Updating a missing edge is reported, not silently added
+        let result = state.process_event(GraphEvent::UpdateEdge {
+            from: "B".to_string(),
+            to: "A".to_string(),
+            info: crate::events::EventEdgeInfo::default(),
+        });
+        assert!(matches!(result, EventResult::EdgeNotFound));
+    }
+
+    #[test]
+    fn test_queries_answer_with_ids() {
+        let mut state = GraphState::new();
+        for id in ["web", "api", "db", "cache", "batch"] {
+            state.process_event(GraphEvent::AddNode {
+                id: id.to_string(),
+                info: EventNodeInfo {
+                    name: id.to_uppercase(),
+                    node_type: (id == "db").then(|| "database".to_string()),
+                    level: 0,
+                    ..Default::default()
+                },
+            });
+        }
+        for (from, to) in [
+            ("web", "api"),
+            ("api", "db"),
+            ("api", "cache"),
+            ("cache", "api"),
+        ] {
+            state.process_event(GraphEvent::AddEdge {
+                from: from.to_string(),
+                to: to.to_string(),
+            });
+        }
+
+        assert_eq!(state.successors("api"), ["cache", "db"]);
+        assert_eq!(state.predecessors("api"), ["cache", "web"]);
+        assert_eq!(state.neighbors("api"), ["cache", "db", "web"]);
+        assert_eq!(state.ancestors("db"), ["api", "cache", "web"]);
+        assert_eq!(state.descendants("web"), ["api", "cache", "db"]);
+        // On a cycle, but not its own ancestor
+        assert_eq!(state.ancestors("api"), ["cache", "web"]);
+        assert!(state.neighbors("batch").is_empty());
+        assert!(state.successors("missing").is_empty());
+
+        assert_eq!(state.find_path("web", "db").unwrap(), ["web", "api", "db"]);
+        assert_eq!(state.find_path("db", "db").unwrap(), ["db"]);
+        assert_eq!(state.find_path("db", "web"), None);
+        assert_eq!(
+            state.nodes_matching(|_, info| info.node_type.is_some()),
+            ["db"]
+        );
+        assert_eq!(
+            state
+                .as_graph_data()
+                .nodes_matching(|id, info| info.name == "WEB" || id.starts_with('c')),
+            ["cache", "web"]
+        );
+    }
+
+    #[test]
+    fn test_state_round_trips_through_its_snapshot() {
+        let mut state = GraphState::new();
+        state.process_events(vec![
+            GraphEvent::AddNode {
+                id: "api".to_string(),
+                info: EventNodeInfo {
+                    name: "API".to_string(),
+                    node_type: Some("service".to_string()),
+                    level: 1,
+                    ..Default::default()
+                },
+            },
+            GraphEvent::AddNode {
+                id: "db".to_string(),
+                info: EventNodeInfo {
+                    name: "db".to_string(),
+                    node_type: None,
+                    level: 2,
+                    ..Default::default()
+                },
+            },
+            GraphEvent::AddRichEdge {
+                from: "api".to_string(),
+                to: "db".to_string(),
+                info: crate::events::EventEdgeInfo {
+                    label: Some("query".to_string()),
+                    ..Default::default()
+                },
+            },
+            GraphEvent::SetNodeState {
+                id: "db".to_string(),
+                state: Some(AlertState::Critical),
+            },
+        ]);
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(
+            json.starts_with(r#"[{"id":"api","level":1,"name":"API""#),
+            "{json}"
+        );
+        assert!(json.contains(r#""state":"critical""#), "{json}");
+        let restored: GraphState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.snapshot().len(), state.snapshot().len());
+        assert_eq!(
+            restored.get_node("db").unwrap().state,
+            Some(AlertState::Critical)
+        );
+        assert_eq!(restored.successors("api"), ["db"]);
+
+        // Events read back the way sessions write them
+        let event: GraphEvent =
+            serde_json::from_str(r#"{"type": "add_node", "id": "web"}"#).unwrap();
+        assert!(matches!(event, GraphEvent::AddNode { info, .. } if info.name == "web"));
+        assert!(serde_json::from_str::<GraphEvent>(r#"{"type": "explode"}"#).is_err());
+    }
+
+    #[test]
+    fn test_event_log_rebuilds_past_states() {
+        let node = |id: &str| GraphEvent::AddNode {
+            id: id.to_string(),
+            info: EventNodeInfo {
+                name: id.to_string(),
+                node_type: None,
+                level: 0,
+                ..Default::default()
+            },
+        };
+        let mut state = GraphState::new();
+        state.process_events(vec![
+            GraphEvent::BatchStart,
+            node("A"),
+            node("B"),
+            GraphEvent::BatchEnd,
+            GraphEvent::AddEdge {
+                from: "A".to_string(),
+                to: "B".to_string(),
+            },
+            // Failed events aren't logged
+            node("A"),
+            GraphEvent::RemoveNode {
+                id: "A".to_string(),
+            },
+        ]);
+
+        assert_eq!(state.event_log().len(), 4);
+        let counts = |state: &GraphState| (state.node_count(), state.edge_count());
+        assert_eq!(counts(&state.state_at(0)), (0, 0));
+        assert_eq!(counts(&state.state_at(3)), (2, 1));
+        assert_eq!(counts(&state.state_at(4)), (1, 0));
+
+        // Trimming keeps every state that is still in the log
+        state.trim_log(2);
+        assert_eq!(state.event_log().len(), 2);
+        assert_eq!(counts(&state.state_at(0)), (2, 0));
+        assert_eq!(counts(&state.state_at(1)), (2, 1));
+        assert!(state.state_at(2).get_node("A").is_none());
+    }
+
+    #[test]
+    fn test_changes_to_rebuild_the_target() {
+        let node = |id: &str, node_type: Option<&str>| GraphEvent::AddNode {
+            id: id.to_string(),
+            info: EventNodeInfo {
+                name: id.to_string(),
+                node_type: node_type.map(str::to_string),
+                level: 0,
+                ..Default::default()
+            },
+        };
+        let edge = |from: &str, to: &str, label: Option<&str>| GraphEvent::AddRichEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            info: EventEdgeInfo {
+                label: label.map(str::to_string),
+                ..Default::default()
+            },
+        };
+        let build = |events| {
+            let mut state = GraphState::new();
+            state.process_events(events);
+            state
+        };
+        let before = || {
+            build(vec![
+                node("web", None),
+                node("api", None),
+                node("db", None),
+                node("cache", None),
+                edge("web", "api", None),
+                edge("api", "db", None),
+                edge("api", "cache", Some("reads")),
+            ])
+        };
+        let after = build(vec![
+            node("web", None),
+            node("api", None),
+            node("db", Some("database")),
+            node("queue", None),
+            edge("web", "api", None),
+            edge("api", "db", Some("writes")),
+            edge("api", "queue", None),
+        ]);
+
+        let changes = before().changes_to(&after);
+        let kinds: Vec<_> = changes
+            .iter()
+            .map(|event| event.to_string().split('(').next().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "RemoveEdge",
+                "RemoveNode",
+                "UpdateNode",
+                "AddNode",
+                "UpdateEdge",
+                "AddRichEdge"
+            ]
+        );
+
+        let mut rebuilt = before();
+        rebuilt.process_events(changes);
+        assert!(rebuilt.changes_to(&after).is_empty());
+        assert_eq!(
+            (rebuilt.node_count(), rebuilt.edge_count()),
+            (after.node_count(), after.edge_count())
+        );
+    }
 }
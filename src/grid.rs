@@ -0,0 +1,116 @@
+use crate::keybindings::{Action, KeyBindings};
+use crate::theme::Theme;
+use crate::types::{GraphNode, GridSettings, SearchState};
+use bevy::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+
+/// Roughly how many grid cells span the graph's widest extent
+const TARGET_CELLS: f32 = 20.0;
+
+/// How far the grid reaches past the graph, as a multiple of its extent
+const GRID_MARGIN: f32 = 1.5;
+
+/// Distance between the grid and the lowest node
+const GRID_DROP: f32 = 1.0;
+
+pub fn toggle_reference_gizmos(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    mut grid: ResMut<GridSettings>,
+) {
+    if search_state.active {
+        return;
+    }
+    if bindings.just_pressed(Action::ToggleGrid, &keyboard_input) {
+        grid.show_grid = !grid.show_grid;
+    }
+    if bindings.just_pressed(Action::ToggleAxes, &keyboard_input) {
+        grid.show_axes = !grid.show_axes;
+    }
+}
+
+/// Corners of the box holding the origin and every node
+pub fn node_bounds(positions: impl IntoIterator<Item = Vec3>) -> (Vec3, Vec3) {
+    positions
+        .into_iter()
+        .fold((Vec3::ZERO, Vec3::ZERO), |(min, max), position| {
+            (min.min(position), max.max(position))
+        })
+}
+
+/// Height of the ground the grid is drawn on, just under the lowest node
+pub fn ground_height(positions: impl IntoIterator<Item = Vec3>) -> f32 {
+    node_bounds(positions).0.y - GRID_DROP
+}
+
+/// Draws the reference grid under the graph and the XYZ axes at the origin,
+/// both sized to the current node bounds
+///
+/// The grid is left out while the camera is under it, if it may go there.
+pub fn draw_reference_gizmos(
+    mut gizmos: Gizmos,
+    grid: Res<GridSettings>,
+    theme: Res<Theme>,
+    node_query: Query<&GlobalTransform, With<GraphNode>>,
+    camera_query: Query<&GlobalTransform, With<Camera>>,
+) {
+    if !grid.show_grid && !grid.show_axes {
+        return;
+    }
+
+    let (min, max) = node_bounds(node_query.iter().map(GlobalTransform::translation));
+    let extent = (max - min).max_element().max(1.0);
+    let below_ground = grid.below_ground
+        && camera_query
+            .single()
+            .is_ok_and(|camera| camera.translation().y < min.y - GRID_DROP);
+
+    if grid.show_grid && !below_ground {
+        let spacing = grid_spacing(extent);
+        // Centered on the graph and snapped to whole cells so it doesn't
+        // shimmer as the graph changes
+        let center = ((min + max) / 2.0 / spacing).round() * spacing;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let cells = ((extent * GRID_MARGIN * 2.0 / spacing).ceil() as u32).max(2);
+        gizmos.grid(
+            Isometry3d::new(
+                Vec3::new(center.x, min.y - GRID_DROP, center.z),
+                Quat::from_rotation_x(FRAC_PI_2),
+            ),
+            UVec2::splat(cells),
+            Vec2::splat(spacing),
+            theme.grid(),
+        );
+    }
+
+    if grid.show_axes {
+        gizmos.axes(Transform::IDENTITY, extent / 2.0);
+    }
+}
+
+/// A 1, 2 or 5 times power-of-ten cell size giving about `TARGET_CELLS`
+/// cells across `extent`
+fn grid_spacing(extent: f32) -> f32 {
+    let raw = extent / TARGET_CELLS;
+    let magnitude = 10f32.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .find(|step| step * magnitude >= raw)
+        .unwrap_or(10.0);
+    step * magnitude
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_spacing_is_round() {
+        assert!((grid_spacing(20.0) - 1.0).abs() < 1e-4);
+        assert!((grid_spacing(30.0) - 2.0).abs() < 1e-4);
+        assert!((grid_spacing(80.0) - 5.0).abs() < 1e-4);
+        assert!((grid_spacing(1000.0) - 50.0).abs() < 1e-3);
+        assert!((grid_spacing(3.0) - 0.2).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,388 @@
+use crate::events::{AlertState, GraphEvent};
+use crate::sources::{GraphEventSource, SourceError};
+use crate::types::{GraphData, GraphNode, SelectedNodes};
+use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use tokio::sync::oneshot;
+use tonic::transport::Server;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
+
+#[allow(clippy::all, clippy::pedantic, clippy::nursery)]
+mod control {
+    include!(concat!(env!("OUT_DIR"), "/dotspace.Control.rs"));
+}
+
+use control::control_server::{Control, ControlServer};
+
+// Messages of `proto/dotspace.proto`
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct Empty {}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct EventBatch {
+    #[prost(string, repeated, tag = "1")]
+    pub events: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct SendReply {
+    #[prost(uint32, tag = "1")]
+    pub accepted: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct Vec3 {
+    #[prost(float, tag = "1")]
+    pub x: f32,
+    #[prost(float, tag = "2")]
+    pub y: f32,
+    #[prost(float, tag = "3")]
+    pub z: f32,
+}
+
+impl From<bevy::math::Vec3> for Vec3 {
+    fn from(v: bevy::math::Vec3) -> Self {
+        Self {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+#[allow(clippy::struct_field_names)] // Named as in the proto
+pub struct Node {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(string, tag = "3")]
+    pub node_type: String,
+    #[prost(uint32, tag = "4")]
+    pub level: u32,
+    #[prost(string, tag = "5")]
+    pub state: String,
+    #[prost(map = "string, float", tag = "6")]
+    pub metrics: HashMap<String, f32>,
+    #[prost(message, optional, tag = "7")]
+    pub position: Option<Vec3>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct NodeList {
+    #[prost(message, repeated, tag = "1")]
+    pub nodes: Vec<Node>,
+}
+
+#[derive(Clone, PartialEq, Eq, prost::Message)]
+pub struct Selection {
+    #[prost(string, repeated, tag = "1")]
+    pub ids: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CameraPose {
+    #[prost(message, optional, tag = "1")]
+    pub position: Option<Vec3>,
+    #[prost(message, optional, tag = "2")]
+    pub focus: Option<Vec3>,
+    #[prost(float, tag = "3")]
+    pub radius: f32,
+    #[prost(float, tag = "4")]
+    pub yaw: f32,
+    #[prost(float, tag = "5")]
+    pub pitch: f32,
+}
+
+/// A question for the app, answered from its world on the next frame
+pub enum ControlQuery {
+    Nodes(oneshot::Sender<NodeList>),
+    Selection(oneshot::Sender<Selection>),
+    Camera(oneshot::Sender<CameraPose>),
+}
+
+/// Queries waiting for the app to answer them
+#[derive(Resource)]
+pub struct GrpcQueries(pub Mutex<Receiver<ControlQuery>>);
+
+/// Live source that serves the `dotspace.Control` gRPC service
+///
+/// Events sent to it are applied like those of any live source; queries are
+/// passed to the app and answered by `answer_grpc_queries`.
+pub struct GrpcSource {
+    addr: SocketAddr,
+    queries: Sender<ControlQuery>,
+}
+
+impl GrpcSource {
+    /// Creates a source that serves on `addr` once subscribed, along with the
+    /// receiving end of its queries
+    pub fn new(addr: SocketAddr) -> (Self, GrpcQueries) {
+        let (queries, receiver) = mpsc::channel();
+        (Self { addr, queries }, GrpcQueries(Mutex::new(receiver)))
+    }
+}
+
+impl GraphEventSource for GrpcSource {
+    fn source_name(&self) -> &'static str {
+        "gRPC"
+    }
+
+    fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+        Ok(Vec::new())
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    fn subscribe(&self, sender: Sender<GraphEvent>) -> Result<(), SourceError> {
+        // Bind here so a taken address is reported at startup
+        let listener = TcpListener::bind(self.addr)?;
+        listener.set_nonblocking(true)?;
+        let service = ControlService {
+            events: sender,
+            queries: self.queries.clone(),
+        };
+
+        thread::Builder::new()
+            .name("grpc-server".to_string())
+            .spawn(move || {
+                if let Err(err) = serve(listener, service) {
+                    eprintln!("gRPC server stopped: {err}");
+                }
+            })?;
+        Ok(())
+    }
+}
+
+fn serve(
+    listener: TcpListener,
+    service: ControlService,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        Server::builder()
+            .add_service(ControlServer::new(service))
+            .serve_with_incoming(TcpIncoming::from_listener(listener, true, None)?)
+            .await?;
+        Ok(())
+    })
+}
+
+struct ControlService {
+    events: Sender<GraphEvent>,
+    queries: Sender<ControlQuery>,
+}
+
+impl ControlService {
+    /// Hands a query to the app and waits for its answer
+    async fn ask<T>(
+        &self,
+        query: impl FnOnce(oneshot::Sender<T>) -> ControlQuery,
+    ) -> Result<T, Status> {
+        let (reply, answer) = oneshot::channel();
+        self.queries
+            .send(query(reply))
+            .map_err(|_| Status::unavailable("the viewer has exited"))?;
+        answer
+            .await
+            .map_err(|_| Status::unavailable("the viewer has exited"))
+    }
+}
+
+#[tonic::async_trait]
+impl Control for ControlService {
+    async fn send_events(
+        &self,
+        request: Request<EventBatch>,
+    ) -> Result<Response<SendReply>, Status> {
+        let events = request
+            .into_inner()
+            .events
+            .iter()
+            .enumerate()
+            .map(|(i, json)| {
+                serde_json::from_str(json)
+                    .map_err(|err| err.to_string())
+                    .and_then(|value| GraphEvent::from_json(&value))
+                    .map_err(|err| format!("event {i}: {err}"))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Status::invalid_argument)?;
+        let accepted = u32::try_from(events.len()).unwrap_or(u32::MAX);
+        for event in events {
+            self.events
+                .send(event)
+                .map_err(|_| Status::unavailable("the viewer has exited"))?;
+        }
+        Ok(Response::new(SendReply { accepted }))
+    }
+
+    async fn get_nodes(&self, _: Request<Empty>) -> Result<Response<NodeList>, Status> {
+        self.ask(ControlQuery::Nodes).await.map(Response::new)
+    }
+
+    async fn get_selection(&self, _: Request<Empty>) -> Result<Response<Selection>, Status> {
+        self.ask(ControlQuery::Selection).await.map(Response::new)
+    }
+
+    async fn get_camera(&self, _: Request<Empty>) -> Result<Response<CameraPose>, Status> {
+        self.ask(ControlQuery::Camera).await.map(Response::new)
+    }
+}
+
+/// Answers the queries that arrived since the last frame
+pub fn answer_grpc_queries(
+    queries: Res<GrpcQueries>,
+    graph_data: Option<Res<GraphData>>,
+    selection: Res<SelectedNodes>,
+    node_query: Query<(&GraphNode, &Transform)>,
+    camera_query: Query<(&GlobalTransform, &PanOrbitCamera)>,
+) {
+    let Ok(receiver) = queries.0.lock() else {
+        return;
+    };
+    for query in receiver.try_iter() {
+        // A client that hung up no longer needs its answer
+        let _ = match query {
+            ControlQuery::Nodes(reply) => reply
+                .send(node_list(graph_data.as_deref(), &node_query))
+                .is_ok(),
+            ControlQuery::Selection(reply) => {
+                let mut ids: Vec<_> = selection.0.iter().cloned().collect();
+                ids.sort_unstable();
+                reply.send(Selection { ids }).is_ok()
+            }
+            ControlQuery::Camera(reply) => {
+                let pose = camera_query
+                    .single()
+                    .map(|(transform, camera)| CameraPose {
+                        position: Some(transform.translation().into()),
+                        focus: Some(camera.focus.into()),
+                        radius: camera.radius.unwrap_or(camera.target_radius),
+                        yaw: camera.yaw.unwrap_or(camera.target_yaw),
+                        pitch: camera.pitch.unwrap_or(camera.target_pitch),
+                    })
+                    .unwrap_or_default();
+                reply.send(pose).is_ok()
+            }
+        };
+    }
+}
+
+fn node_list(
+    graph_data: Option<&GraphData>,
+    node_query: &Query<(&GraphNode, &Transform)>,
+) -> NodeList {
+    let Some(data) = graph_data else {
+        return NodeList::default();
+    };
+    let positions: HashMap<_, _> = node_query
+        .iter()
+        .map(|(node, transform)| (node.index, transform.translation))
+        .collect();
+    let mut nodes: Vec<_> = data
+        .node_map
+        .iter()
+        .map(|(id, &index)| {
+            let info = &data.graph[index];
+            Node {
+                id: id.clone(),
+                name: info.name.clone(),
+                node_type: info.node_type.clone().unwrap_or_default(),
+                level: info.level,
+                state: info
+                    .state
+                    .map(AlertState::name)
+                    .unwrap_or_default()
+                    .to_string(),
+                metrics: info.metrics.clone().into_iter().collect(),
+                position: positions.get(&index).map(|&position| position.into()),
+            }
+        })
+        .collect();
+    nodes.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+    NodeList { nodes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_queries_are_answered_from_the_world() {
+        let content = r"digraph {
+            web -> api
+        }";
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let (source, queries) = GrpcSource::new("127.0.0.1:0".parse().unwrap());
+
+        let mut app = App::new();
+        app.insert_resource(queries)
+            .insert_resource(GraphData(state.as_graph_data()))
+            .insert_resource(SelectedNodes(["api".to_string()].into()))
+            .add_systems(Update, answer_grpc_queries);
+        let api = state.as_graph_data().node_map["api"];
+        app.world_mut().spawn((
+            GraphNode {
+                index: api,
+                id: "api".to_string(),
+                name: "api".to_string(),
+            },
+            Transform::from_xyz(1.0, 2.0, 3.0),
+        ));
+
+        let (nodes_reply, mut nodes) = oneshot::channel();
+        let (selection_reply, mut selection) = oneshot::channel();
+        let (camera_reply, mut camera) = oneshot::channel();
+        source
+            .queries
+            .send(ControlQuery::Nodes(nodes_reply))
+            .unwrap();
+        source
+            .queries
+            .send(ControlQuery::Selection(selection_reply))
+            .unwrap();
+        source
+            .queries
+            .send(ControlQuery::Camera(camera_reply))
+            .unwrap();
+        app.update();
+
+        let nodes = nodes.try_recv().unwrap().nodes;
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|node| node.id.as_str())
+                .collect::<Vec<_>>(),
+            ["api", "web"]
+        );
+        assert_eq!(
+            nodes[0].position,
+            Some(Vec3 {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0
+            })
+        );
+        assert_eq!(nodes[1].position, None);
+        assert_eq!(selection.try_recv().unwrap().ids, ["api"]);
+        // No camera in this world
+        assert_eq!(camera.try_recv().unwrap(), CameraPose::default());
+    }
+}
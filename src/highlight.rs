@@ -0,0 +1,363 @@
+use crate::types::{
+    CycleAnalysis, EdgeArrowHead, EdgeHighlight, EdgeShaft, GraphEdge, GraphNode, HoverState,
+    NodeHighlight, NodeOutline, OriginalMaterial, Reachability, SearchState, SelectedNodes,
+};
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::render::render_resource::Face;
+
+/// How much larger than its node an outline shell is drawn
+pub const OUTLINE_SCALE: f32 = 1.18;
+
+/// Emissive strength of a full-intensity outline; values above 1 bloom
+const OUTLINE_GLOW: f32 = 4.0;
+
+/// Intensity of the outline tinting nodes on a cycle, below any highlight
+const CYCLE_INTENSITY: f32 = 0.6;
+
+/// Emissive strength of a search-matched node's own surface
+const BODY_GLOW: f32 = 0.5;
+
+/// Emissive strength of a search-matched edge; edges are thin, so they need more
+const EDGE_GLOW: f32 = 3.0;
+
+/// Outlines and glow for hovered, selected and search-matched nodes and edges
+pub struct HighlightPlugin;
+
+impl Plugin for HighlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                spawn_node_outlines,
+                apply_highlight_visuals,
+                apply_highlight_materials,
+                apply_edge_highlight_materials,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Why a node is highlighted, in increasing order of precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HighlightKind {
+    Search,
+    Selection,
+    Hover,
+}
+
+impl HighlightKind {
+    pub const fn color(self) -> Color {
+        match self {
+            Self::Search => Color::srgb(1.0, 0.8, 0.1),
+            Self::Selection => Color::srgb(0.2, 0.85, 1.0),
+            Self::Hover => Color::WHITE,
+        }
+    }
+}
+
+/// Gives each new node a hidden outline shell with a material of its own,
+/// so highlighting one node never changes the look of another
+pub fn spawn_node_outlines(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    node_query: Query<(Entity, &Mesh3d), Added<GraphNode>>,
+) {
+    for (entity, mesh) in &node_query {
+        // Front faces are culled so only the rim around the node shows
+        let material = materials.add(StandardMaterial {
+            unlit: true,
+            cull_mode: Some(Face::Front),
+            alpha_mode: AlphaMode::Blend,
+            ..default()
+        });
+        commands.entity(entity).with_child((
+            Mesh3d(mesh.0.clone()),
+            MeshMaterial3d(material),
+            Transform::from_scale(Vec3::splat(OUTLINE_SCALE)),
+            Visibility::Hidden,
+            NotShadowCaster,
+            Pickable::IGNORE,
+            NodeOutline,
+        ));
+    }
+}
+
+/// Shows the outline of every hovered, selected or search-matched node in
+/// the color of its strongest highlight, fading search matches over time
+///
+/// Nodes with no highlight are shaded by reachability from the chosen node
+/// or, failing that, outlined in their cycle's tint.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_highlight_visuals(
+    hover: Res<HoverState>,
+    search_state: Res<SearchState>,
+    selection: Res<SelectedNodes>,
+    reachability: Res<Reachability>,
+    cycles: Res<CycleAnalysis>,
+    node_query: Query<&GraphNode>,
+    highlight_query: Query<&NodeHighlight>,
+    mut outline_query: Query<
+        (&ChildOf, &mut Visibility, &MeshMaterial3d<StandardMaterial>),
+        With<NodeOutline>,
+    >,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (child_of, mut visibility, material) in &mut outline_query {
+        let node = child_of.parent();
+        let graph_node = node_query.get(node).ok();
+        let highlight = if hover.node == Some(node) {
+            Some((HighlightKind::Hover, 1.0))
+        } else if search_state.selected_node == Some(node)
+            || graph_node.is_some_and(|graph_node| selection.0.contains(&graph_node.id))
+        {
+            Some((HighlightKind::Selection, 1.0))
+        } else {
+            highlight_query
+                .get(node)
+                .ok()
+                .map(|highlight| (HighlightKind::Search, highlight.fade_timer.clamp(0.0, 1.0)))
+        };
+        let outline = highlight
+            .map(|(kind, intensity)| (kind.color(), intensity))
+            .or_else(|| graph_node.and_then(|node| reachability.node_shade(node.index)))
+            .or_else(|| {
+                graph_node
+                    .and_then(|graph_node| cycles.node_color(graph_node.index))
+                    .map(|color| (color, CYCLE_INTENSITY))
+            });
+
+        let Some((base, intensity)) = outline else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        visibility.set_if_neq(Visibility::Inherited);
+
+        let color = base.with_alpha(intensity);
+        let emissive = base.to_linear() * OUTLINE_GLOW * intensity;
+        // Only touch the material when the look actually changes
+        if materials
+            .get(&material.0)
+            .is_some_and(|m| m.base_color != color || m.emissive != emissive)
+            && let Some(material) = materials.get_mut(&material.0)
+        {
+            material.base_color = color;
+            material.emissive = emissive;
+        }
+    }
+}
+
+/// Makes search-matched nodes glow through a copy of their material
+///
+/// Node materials are shared by every node of a type, so a highlighted node
+/// gets its own copy and the shared handle is put back when the highlight ends.
+#[allow(clippy::type_complexity)]
+pub fn apply_highlight_materials(
+    mut commands: Commands,
+    mut node_query: Query<
+        (
+            Entity,
+            &mut MeshMaterial3d<StandardMaterial>,
+            Option<&NodeHighlight>,
+            Option<&OriginalMaterial>,
+        ),
+        With<GraphNode>,
+    >,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, mut material, highlight, original) in &mut node_query {
+        let glow = highlight.map(|highlight| BODY_GLOW * highlight.fade_timer.clamp(0.0, 1.0));
+        swap_glow_material(
+            &mut commands,
+            entity,
+            &mut material,
+            original,
+            glow,
+            &mut materials,
+        );
+    }
+}
+
+/// Makes search-matched edges glow like nodes, through copies of the
+/// materials of their shaft and arrow head
+#[allow(clippy::type_complexity)]
+pub fn apply_edge_highlight_materials(
+    mut commands: Commands,
+    edge_query: Query<(&Children, Option<&EdgeHighlight>), With<GraphEdge>>,
+    mut part_query: Query<
+        (
+            &mut MeshMaterial3d<StandardMaterial>,
+            Option<&OriginalMaterial>,
+        ),
+        Or<(With<EdgeShaft>, With<EdgeArrowHead>)>,
+    >,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (children, highlight) in &edge_query {
+        let glow = highlight.map(|highlight| EDGE_GLOW * highlight.fade_timer.clamp(0.0, 1.0));
+        for &part in children {
+            if let Ok((mut material, original)) = part_query.get_mut(part) {
+                swap_glow_material(
+                    &mut commands,
+                    part,
+                    &mut material,
+                    original,
+                    glow,
+                    &mut materials,
+                );
+            }
+        }
+    }
+}
+
+/// Gives `entity` its own copy of its material glowing with `glow`, or puts
+/// the shared material back once the glow is gone
+fn swap_glow_material(
+    commands: &mut Commands,
+    entity: Entity,
+    material: &mut MeshMaterial3d<StandardMaterial>,
+    original: Option<&OriginalMaterial>,
+    glow: Option<f32>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    match (glow, original) {
+        (Some(glow), original) => {
+            let emissive = HighlightKind::Search.color().to_linear() * glow;
+            if original.is_none() {
+                let Some(mut copy) = materials.get(&material.0).cloned() else {
+                    return;
+                };
+                copy.emissive = emissive;
+                let shared = std::mem::replace(&mut material.0, materials.add(copy));
+                commands.entity(entity).insert(OriginalMaterial(shared));
+            } else if let Some(copy) = materials.get_mut(&material.0) {
+                copy.emissive = emissive;
+            }
+        }
+        (None, Some(original)) => {
+            // Dropping the copy's last handle frees it
+            material.0 = original.0.clone();
+            commands.entity(entity).remove::<OriginalMaterial>();
+        }
+        (None, None) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::NodeIndex;
+
+    #[test]
+    fn test_only_highlighted_node_shows_outline() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<HoverState>()
+            .init_resource::<SearchState>()
+            .init_resource::<SelectedNodes>()
+            .init_resource::<Reachability>()
+            .init_resource::<CycleAnalysis>()
+            .add_systems(
+                Update,
+                (spawn_node_outlines, apply_highlight_visuals).chain(),
+            );
+
+        let mesh = app
+            .world_mut()
+            .resource_mut::<Assets<Mesh>>()
+            .add(Sphere::new(0.5));
+        let mut spawn_node = |name: &str, index| {
+            app.world_mut()
+                .spawn((
+                    Mesh3d(mesh.clone()),
+                    GraphNode {
+                        id: name.to_string(),
+                        name: name.to_string(),
+                        index: NodeIndex::new(index),
+                    },
+                ))
+                .id()
+        };
+        let matched = spawn_node("a", 0);
+        spawn_node("b", 1);
+        app.world_mut()
+            .entity_mut(matched)
+            .insert(NodeHighlight { fade_timer: 1.0 });
+
+        // Outlines are spawned on the first update and shown on the next
+        app.update();
+        app.update();
+
+        let mut outlines = app
+            .world_mut()
+            .query_filtered::<(&ChildOf, &Visibility), With<NodeOutline>>();
+        let outlines: Vec<_> = outlines
+            .iter(app.world())
+            .map(|(child_of, visibility)| (child_of.parent(), *visibility))
+            .collect();
+        assert_eq!(outlines.len(), 2);
+        for (node, visibility) in outlines {
+            let expected = if node == matched {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+            assert_eq!(visibility, expected);
+        }
+    }
+
+    #[test]
+    fn test_highlight_copies_and_restores_shared_material() {
+        let mut app = App::new();
+        app.init_resource::<Assets<StandardMaterial>>()
+            .add_systems(Update, apply_highlight_materials);
+
+        let shared = app
+            .world_mut()
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial::default());
+        let mut spawn_node = |name: &str, index| {
+            app.world_mut()
+                .spawn((
+                    MeshMaterial3d(shared.clone()),
+                    GraphNode {
+                        id: name.to_string(),
+                        name: name.to_string(),
+                        index: NodeIndex::new(index),
+                    },
+                ))
+                .id()
+        };
+        let matched = spawn_node("a", 0);
+        let other = spawn_node("b", 1);
+        app.world_mut()
+            .entity_mut(matched)
+            .insert(NodeHighlight { fade_timer: 1.0 });
+        app.update();
+
+        let handle = |app: &App, entity| {
+            app.world()
+                .get::<MeshMaterial3d<StandardMaterial>>(entity)
+                .unwrap()
+                .0
+                .clone()
+        };
+        let materials = app.world().resource::<Assets<StandardMaterial>>();
+        assert_ne!(handle(&app, matched), shared);
+        assert_eq!(handle(&app, other), shared);
+        assert_eq!(materials.get(&shared).unwrap().emissive, LinearRgba::BLACK);
+        assert_ne!(
+            materials.get(&handle(&app, matched)).unwrap().emissive,
+            LinearRgba::BLACK
+        );
+
+        app.world_mut()
+            .entity_mut(matched)
+            .remove::<NodeHighlight>();
+        app.update();
+        assert_eq!(handle(&app, matched), shared);
+        assert!(app.world().get::<OriginalMaterial>(matched).is_none());
+    }
+}
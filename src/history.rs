@@ -0,0 +1,295 @@
+use crate::events::{EventResult, GraphEvent};
+use crate::graph_state::GraphState;
+use crate::keybindings::{Action, KeyBindings};
+use crate::status::StatusMessage;
+use crate::types::{LayoutSettings, SearchState};
+use bevy::prelude::*;
+
+/// Most edits kept on the undo stack
+const HISTORY_LIMIT: usize = 200;
+
+/// Layout changes closer together than this undo as one step, so dragging
+/// a slider is a single edit
+const LAYOUT_MERGE_SECS: f32 = 1.0;
+
+/// One undoable step
+enum Edit {
+    /// Events applied to the graph and the events that revert them
+    Graph {
+        events: Vec<GraphEvent>,
+        inverse: Vec<GraphEvent>,
+    },
+    Layout {
+        before: LayoutSettings,
+        after: LayoutSettings,
+        at: f32,
+    },
+}
+
+/// Undo and redo stacks for graph edits and layout changes
+///
+/// Graph edits are recorded as the events that revert them, computed before
+/// each event is applied.
+#[derive(Resource, Default)]
+pub struct EditHistory {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// Layout as of the last recorded change
+    layout: Option<LayoutSettings>,
+}
+
+impl EditHistory {
+    /// Applies events to the graph as a single undoable edit
+    ///
+    /// Events that fail are left out of the edit; their results are returned.
+    pub fn apply(&mut self, state: &mut GraphState, events: Vec<GraphEvent>) -> Vec<EventResult> {
+        let mut applied = Vec::new();
+        let mut inverses = Vec::new();
+        let mut results = Vec::new();
+        for event in events {
+            // Batching would defer events past the point their inverse was taken
+            if matches!(event, GraphEvent::BatchStart | GraphEvent::BatchEnd) {
+                continue;
+            }
+            let inverse = state.inverse_of(&event);
+            let result = state.process_event(event.clone());
+            if matches!(result, EventResult::Success) {
+                applied.push(event);
+                inverses.push(inverse);
+            }
+            results.push(result);
+        }
+
+        if !applied.is_empty() {
+            // Revert the last event first
+            let inverse = inverses.into_iter().rev().flatten().collect();
+            self.push(Edit::Graph {
+                events: applied,
+                inverse,
+            });
+        }
+        results
+    }
+
+    /// Records a layout change, merging it into the previous one if that was
+    /// also a layout change made moments ago
+    pub fn record_layout(&mut self, layout: &LayoutSettings, now: f32) {
+        let Some(before) = self.layout.replace(layout.clone()) else {
+            return;
+        };
+        if before == *layout {
+            return;
+        }
+        if let Some(Edit::Layout { after, at, .. }) = self.undo.last_mut()
+            && self.redo.is_empty()
+            && now - *at < LAYOUT_MERGE_SECS
+        {
+            after.clone_from(layout);
+            *at = now;
+            return;
+        }
+        self.push(Edit::Layout {
+            before,
+            after: layout.clone(),
+            at: now,
+        });
+    }
+
+    /// Reverts the most recent edit; false if there was nothing to undo
+    pub fn undo(&mut self, state: &mut GraphState, layout: &mut LayoutSettings) -> bool {
+        let Some(edit) = self.undo.pop() else {
+            return false;
+        };
+        match &edit {
+            Edit::Graph { inverse, .. } => {
+                state.process_events(inverse.clone());
+            }
+            Edit::Layout { before, .. } => self.set_layout(layout, before),
+        }
+        self.redo.push(edit);
+        true
+    }
+
+    /// Reapplies the most recently undone edit; false if there was nothing to redo
+    pub fn redo(&mut self, state: &mut GraphState, layout: &mut LayoutSettings) -> bool {
+        let Some(edit) = self.redo.pop() else {
+            return false;
+        };
+        match &edit {
+            Edit::Graph { events, .. } => {
+                state.process_events(events.clone());
+            }
+            Edit::Layout { after, .. } => self.set_layout(layout, after),
+        }
+        self.undo.push(edit);
+        true
+    }
+
+    /// Forgets every edit, e.g. when the graph is replaced by a reload
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    #[allow(dead_code)] // Used in tests
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    #[allow(dead_code)] // Used in tests
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    fn push(&mut self, edit: Edit) {
+        self.redo.clear();
+        self.undo.push(edit);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Changes the layout without recording it as a new edit
+    fn set_layout(&mut self, layout: &mut LayoutSettings, value: &LayoutSettings) {
+        layout.clone_from(value);
+        self.layout = Some(value.clone());
+    }
+}
+
+/// Records changes to the layout settings so they can be undone
+pub fn record_layout_changes(
+    layout: Res<LayoutSettings>,
+    time: Res<Time>,
+    mut history: ResMut<EditHistory>,
+) {
+    if !layout.is_changed() {
+        return;
+    }
+    history.record_layout(&layout, time.elapsed_secs());
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn undo_redo(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    search_state: Res<SearchState>,
+    time: Res<Time>,
+    mut history: ResMut<EditHistory>,
+    mut graph_state: ResMut<GraphState>,
+    mut layout: ResMut<LayoutSettings>,
+    mut message: ResMut<StatusMessage>,
+) {
+    if search_state.active {
+        return;
+    }
+    let text = if bindings.just_pressed(Action::Redo, &keyboard_input) {
+        if history.redo(&mut graph_state, &mut layout) {
+            "Redone"
+        } else {
+            "Nothing to redo"
+        }
+    } else if bindings.just_pressed(Action::Undo, &keyboard_input) {
+        if history.undo(&mut graph_state, &mut layout) {
+            "Undone"
+        } else {
+            "Nothing to undo"
+        }
+    } else {
+        return;
+    };
+    message.show(text, &time);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EventEdgeInfo, EventNodeInfo};
+
+    fn node(id: &str) -> GraphEvent {
+        GraphEvent::AddNode {
+            id: id.to_string(),
+            info: EventNodeInfo {
+                name: id.to_string(),
+                node_type: Some("service".to_string()),
+                level: 1,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_undo_remove_node_restores_its_edges() {
+        let mut state = GraphState::new();
+        state.process_events(vec![
+            node("A"),
+            node("B"),
+            node("C"),
+            GraphEvent::AddRichEdge {
+                from: "A".to_string(),
+                to: "B".to_string(),
+                info: EventEdgeInfo {
+                    label: Some("calls".to_string()),
+                    ..Default::default()
+                },
+            },
+            GraphEvent::AddEdge {
+                from: "C".to_string(),
+                to: "A".to_string(),
+            },
+        ]);
+
+        let mut history = EditHistory::default();
+        let mut layout = LayoutSettings::default();
+        history.apply(
+            &mut state,
+            vec![GraphEvent::RemoveNode {
+                id: "A".to_string(),
+            }],
+        );
+        assert_eq!((state.node_count(), state.edge_count()), (2, 0));
+
+        assert!(history.undo(&mut state, &mut layout));
+        assert_eq!((state.node_count(), state.edge_count()), (3, 2));
+        let data = state.as_graph_data();
+        let edge = data
+            .graph
+            .find_edge(data.node_map["A"], data.node_map["B"])
+            .unwrap();
+        assert_eq!(data.graph[edge].label.as_deref(), Some("calls"));
+        assert_eq!(
+            state.get_node("A").unwrap().node_type.as_deref(),
+            Some("service")
+        );
+
+        assert!(history.redo(&mut state, &mut layout));
+        assert_eq!((state.node_count(), state.edge_count()), (2, 0));
+        assert!(!history.redo(&mut state, &mut layout));
+    }
+
+    #[test]
+    fn test_layout_changes_merge_and_undo() {
+        let mut state = GraphState::new();
+        let mut history = EditHistory::default();
+        let mut layout = LayoutSettings::default();
+        let original = layout.clone();
+        history.record_layout(&layout, 0.0);
+
+        // A slider drag: several changes in quick succession
+        for (i, radius) in [6.0, 7.0, 8.0].into_iter().enumerate() {
+            layout.base_radius = radius;
+            history.record_layout(&layout, (i as f32).mul_add(0.1, 10.0));
+        }
+        layout.level_height = 9.0;
+        history.record_layout(&layout, 20.0);
+
+        assert!(history.undo(&mut state, &mut layout));
+        assert!((layout.base_radius - 8.0).abs() < f32::EPSILON);
+        assert!(history.undo(&mut state, &mut layout));
+        assert!(layout == original);
+        assert!(!history.can_undo());
+
+        // Restoring a layout is not itself recorded
+        history.record_layout(&layout, 30.0);
+        assert!(history.can_redo());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::types::{
+    GraphData, GraphNode, HoverState, HoverTooltip, NodeNotes, ThemedPanel, ThemedText,
+};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_panorbit_camera::EguiWantsFocus;
+use petgraph::Direction;
+use std::fmt::Write as _;
+
+/// Seconds the cursor must rest on a node before its tooltip appears
+const TOOLTIP_DELAY_SECS: f32 = 0.4;
+
+/// Node meshes are roughly this radius before scaling
+pub const NODE_PICK_RADIUS: f32 = 0.6;
+
+pub fn setup_hover_tooltip(commands: &mut Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.05, 0.05, 0.1, 0.85)),
+        Visibility::Hidden,
+        HoverTooltip,
+        ThemedText,
+        ThemedPanel,
+    ));
+}
+
+/// Casts a ray from the cursor and records the nearest node it passes through
+pub fn update_hovered_node(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    node_query: Query<(Entity, &GlobalTransform), With<GraphNode>>,
+    egui_focus: Option<Res<EguiWantsFocus>>,
+    time: Res<Time>,
+    mut hover: ResMut<HoverState>,
+) {
+    let over_ui = egui_focus.is_some_and(|focus| focus.curr);
+    let ray = window_query
+        .single()
+        .ok()
+        .and_then(Window::cursor_position)
+        .filter(|_| !over_ui)
+        .zip(camera_query.single().ok())
+        .and_then(|(cursor, (camera, camera_transform))| {
+            camera.viewport_to_world(camera_transform, cursor).ok()
+        });
+
+    let hovered = ray.and_then(|ray| {
+        node_query
+            .iter()
+            .filter_map(|(entity, transform)| {
+                let (scale, _, center) = transform.to_scale_rotation_translation();
+                ray_hits_sphere(ray, center, NODE_PICK_RADIUS * scale.max_element())
+                    .map(|distance| (entity, distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(entity, _)| entity)
+    });
+
+    if hovered == hover.node {
+        hover.elapsed += time.delta_secs();
+    } else {
+        hover.node = hovered;
+        hover.elapsed = 0.0;
+    }
+}
+
+/// Shows name, type, level, degree, alert state, metrics and properties of the hovered node next to the cursor
+pub fn update_hover_tooltip(
+    hover: Res<HoverState>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    node_query: Query<&GraphNode>,
+    graph_data: Option<Res<GraphData>>,
+    notes: Res<NodeNotes>,
+    mut tooltip_query: Query<(&mut Text, &mut Node, &mut Visibility), With<HoverTooltip>>,
+) {
+    let Ok((mut text, mut style, mut visibility)) = tooltip_query.single_mut() else {
+        return;
+    };
+
+    let content = hover
+        .node
+        .filter(|_| hover.elapsed >= TOOLTIP_DELAY_SECS)
+        .and_then(|entity| node_query.get(entity).ok())
+        .zip(graph_data)
+        .and_then(|(graph_node, data)| {
+            let info = data.graph.node_weight(graph_node.index)?;
+            let incoming = data
+                .graph
+                .edges_directed(graph_node.index, Direction::Incoming)
+                .count();
+            let outgoing = data
+                .graph
+                .edges_directed(graph_node.index, Direction::Outgoing)
+                .count();
+            let mut content = format!(
+                "{}\nType: {}\nLevel: {}\nDegree: {} ({incoming} in, {outgoing} out)",
+                info.name,
+                info.node_type.as_deref().unwrap_or("none"),
+                info.level,
+                incoming + outgoing,
+            );
+            if let Some(state) = info.state {
+                let _ = write!(content, "\nState: {}", state.name());
+            }
+            for (key, value) in &info.metrics {
+                let _ = write!(content, "\n{key}: {value}");
+            }
+            for (key, value) in &info.properties {
+                let _ = write!(content, "\n{key}: {value}");
+            }
+            // Just the first line; the inspector shows the whole note
+            if let Some(note) = notes
+                .0
+                .get(&graph_node.id)
+                .and_then(|note| note.lines().next())
+            {
+                content.push_str("\nNote: ");
+                content.push_str(note);
+            }
+            Some(content)
+        });
+    let cursor = window_query.single().ok().and_then(Window::cursor_position);
+
+    if let (Some(content), Some(cursor)) = (content, cursor) {
+        if text.0 != content {
+            text.0 = content;
+        }
+        style.left = Val::Px(cursor.x + 16.0);
+        style.top = Val::Px(cursor.y + 16.0);
+        *visibility = Visibility::Visible;
+    } else {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+/// Distance along `ray` to where it enters the sphere, if it hits at all
+fn ray_hits_sphere(ray: Ray3d, center: Vec3, radius: f32) -> Option<f32> {
+    let to_center = center - ray.origin;
+    let along = to_center.dot(*ray.direction);
+    if along < 0.0 {
+        return None;
+    }
+    // Squared distance between the ray and the sphere's center
+    let offset_sq = along.mul_add(-along, to_center.length_squared());
+    let miss_sq = radius.mul_add(radius, -offset_sq);
+    (miss_sq >= 0.0).then(|| along - miss_sq.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ray_hits_sphere() {
+        let ray = Ray3d::new(Vec3::ZERO, Dir3::X);
+
+        let hit = ray_hits_sphere(ray, Vec3::new(5.0, 0.0, 0.0), 1.0).unwrap();
+        assert!((hit - 4.0).abs() < 1e-5);
+
+        // Off to the side, and behind the origin
+        assert!(ray_hits_sphere(ray, Vec3::new(5.0, 2.0, 0.0), 1.0).is_none());
+        assert!(ray_hits_sphere(ray, Vec3::new(-5.0, 0.0, 0.0), 1.0).is_none());
+    }
+}
@@ -0,0 +1,278 @@
+use crate::input;
+use std::path::{Component, Path, PathBuf};
+
+/// The diagram read from one input along with the files it includes
+///
+/// DOT files include whole graphs with a `// @include other.dot` comment;
+/// those are loaded alongside the input and merged with it by node id.
+/// `PlantUML`'s `!include other.puml` pastes the file in where it's written,
+/// as `PlantUML` itself does. Paths are relative to the including file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Included {
+    /// Location and content of the input and each graph it includes, the
+    /// input first, with `PlantUML` includes already pasted in
+    pub documents: Vec<(String, String)>,
+    /// Every file read for an include, to watch them for changes
+    pub files: Vec<String>,
+}
+
+/// An include written in a diagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Directive<'a> {
+    /// `// @include path`, a graph of its own to merge with this one
+    Graph(&'a str),
+    /// `!include path`, text to paste in place of the line
+    Text(&'a str),
+}
+
+fn directive(line: &str) -> Option<Directive<'_>> {
+    let line = line.trim();
+    if let Some(rest) = line.strip_prefix("//") {
+        let path = rest.trim_start().strip_prefix("@include")?;
+        return Some(Directive::Graph(unquoted(path)));
+    }
+    let rest = line
+        .strip_prefix("!include_once")
+        .or_else(|| line.strip_prefix("!include"))?;
+    // Other directives, like !includeurl, and standard library includes
+    // such as `!include <C4/C4_Container>` are left to PlantUML
+    if !rest.starts_with(char::is_whitespace) || rest.trim().starts_with('<') {
+        return None;
+    }
+    Some(Directive::Text(unquoted(rest)))
+}
+
+fn unquoted(path: &str) -> &str {
+    path.trim().trim_matches('"')
+}
+
+/// Where `path`, written in the file at `location`, points
+fn relative_to(location: Option<&str>, path: &str) -> String {
+    if input::is_url(path) {
+        return path.to_string();
+    }
+    match location {
+        Some(location) if input::is_url(location) => {
+            let base = location
+                .rfind('/')
+                .map_or(location, |end| &location[..=end]);
+            format!("{base}{path}")
+        }
+        _ => {
+            let base = location
+                .and_then(|location| Path::new(location).parent())
+                .unwrap_or_else(|| Path::new(""));
+            normalized(&base.join(path))
+        }
+    }
+}
+
+/// A path with `.` and `..` worked out, so one file always has one name
+fn normalized(path: &Path) -> String {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normal.file_name().is_some() => {
+                normal.pop();
+            }
+            component => normal.push(component),
+        }
+    }
+    normal.to_string_lossy().into_owned()
+}
+
+/// Reads the files the diagram read from `location` (`None` for stdin)
+/// includes, and the files those include, with `read`
+///
+/// A file that ends up including itself is an error naming the chain of
+/// includes; a graph included twice by different files is loaded once.
+pub fn resolve(
+    location: Option<&str>,
+    content: String,
+    read: &mut dyn FnMut(&str) -> Result<String, String>,
+) -> Result<Included, String> {
+    let mut resolver = Resolver {
+        read,
+        chain: Vec::new(),
+        included: Included::default(),
+    };
+    let name = location.map_or_else(|| "<stdin>".to_string(), normalized_location);
+    resolver.add_document(location.map(|_| name.as_str()), &name, &content)?;
+    Ok(resolver.included)
+}
+
+fn normalized_location(location: &str) -> String {
+    if input::is_url(location) {
+        location.to_string()
+    } else {
+        normalized(Path::new(location))
+    }
+}
+
+struct Resolver<'a> {
+    read: &'a mut dyn FnMut(&str) -> Result<String, String>,
+    /// The files being included, outermost first, to catch cycles
+    chain: Vec<String>,
+    included: Included,
+}
+
+impl Resolver<'_> {
+    fn add_document(
+        &mut self,
+        location: Option<&str>,
+        name: &str,
+        content: &str,
+    ) -> Result<(), String> {
+        self.chain.push(name.to_string());
+        let mut graphs = Vec::new();
+        let text = self.paste_includes(location, content, &mut graphs)?;
+        self.included.documents.push((name.to_string(), text));
+        for path in graphs {
+            let loaded = self
+                .included
+                .documents
+                .iter()
+                .any(|(done, _)| *done == path);
+            if loaded && !self.chain.contains(&path) {
+                continue;
+            }
+            let content = self.include(&path)?;
+            self.add_document(Some(&path), &path, &content)?;
+        }
+        self.chain.pop();
+        Ok(())
+    }
+
+    /// The content with each `!include` replaced by the file it names,
+    /// collecting the graphs it includes into `graphs`
+    fn paste_includes(
+        &mut self,
+        location: Option<&str>,
+        content: &str,
+        graphs: &mut Vec<String>,
+    ) -> Result<String, String> {
+        let mut text = String::with_capacity(content.len());
+        for line in content.lines() {
+            match directive(line) {
+                Some(Directive::Graph(path)) => {
+                    graphs.push(relative_to(location, path));
+                    text.push_str(line);
+                }
+                Some(Directive::Text(path)) => {
+                    let path = relative_to(location, path);
+                    let content = self.include(&path)?;
+                    // The included file may be a whole diagram of its own
+                    let mut pasted = String::with_capacity(content.len());
+                    for line in content.lines().filter(|line| {
+                        let line = line.trim_start();
+                        !line.starts_with("@startuml") && !line.starts_with("@enduml")
+                    }) {
+                        pasted.push_str(line);
+                        pasted.push('\n');
+                    }
+                    self.chain.push(path.clone());
+                    text.push_str(
+                        self.paste_includes(Some(&path), &pasted, graphs)?
+                            .trim_end(),
+                    );
+                    self.chain.pop();
+                }
+                None => text.push_str(line),
+            }
+            text.push('\n');
+        }
+        Ok(text)
+    }
+
+    /// Reads an included file, unless including it would go round in a circle
+    fn include(&mut self, path: &str) -> Result<String, String> {
+        if self.chain.iter().any(|name| name == path) {
+            let chain = self.chain.join(" -> ");
+            return Err(format!("include cycle: {chain} -> {path}"));
+        }
+        let content = (self.read)(path).map_err(|e| format!("can't include {path}: {e}"))?;
+        if !self.included.files.iter().any(|file| file == path) {
+            self.included.files.push(path.to_string());
+        }
+        Ok(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_includes_resolve_relative_paths_and_catch_cycles() {
+        let files: HashMap<&str, &str> = HashMap::from([
+            (
+                "arch/billing.dot",
+                "// @include \"shared/db.dot\"\ndigraph {\n  billing -> db;\n}",
+            ),
+            (
+                "arch/shared/db.dot",
+                "digraph {\n  db [type=\"database\"];\n}",
+            ),
+            ("arch/actors.puml", "@startuml\nactor User\n@enduml"),
+            ("loop/a.dot", "// @include b.dot\ndigraph { a }"),
+            ("loop/b.dot", "// @include ./a.dot\ndigraph { b }"),
+            ("loop/self.puml", "!include self.puml"),
+        ]);
+        let mut read = |path: &str| {
+            files
+                .get(path)
+                .map(|content| (*content).to_string())
+                .ok_or_else(|| "no such file".to_string())
+        };
+
+        let main = "// @include billing.dot\n// @include shared/db.dot\ndigraph { api -> billing }";
+        let included = resolve(Some("arch/./main.dot"), main.to_string(), &mut read).unwrap();
+        let names: Vec<_> = included
+            .documents
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            ["arch/main.dot", "arch/billing.dot", "arch/shared/db.dot"]
+        );
+        assert_eq!(included.files, ["arch/billing.dot", "arch/shared/db.dot"]);
+
+        let sequence =
+            "@startuml\n!include actors.puml\n!include <C4/C4_Container>\nUser -> Api\n@enduml";
+        let included = resolve(Some("arch/seq.puml"), sequence.to_string(), &mut read).unwrap();
+        assert_eq!(
+            included.documents,
+            [(
+                "arch/seq.puml".to_string(),
+                "@startuml\nactor User\n!include <C4/C4_Container>\nUser -> Api\n@enduml\n"
+                    .to_string()
+            )]
+        );
+
+        let err = resolve(
+            Some("loop/a.dot"),
+            files["loop/a.dot"].to_string(),
+            &mut read,
+        );
+        assert_eq!(
+            err.unwrap_err(),
+            "include cycle: loop/a.dot -> loop/b.dot -> loop/a.dot"
+        );
+        let err = resolve(
+            Some("loop/self.puml"),
+            files["loop/self.puml"].to_string(),
+            &mut read,
+        );
+        assert!(err.unwrap_err().starts_with("include cycle"));
+        let err = resolve(None, "// @include missing.dot".to_string(), &mut read);
+        assert_eq!(err.unwrap_err(), "can't include missing.dot: no such file");
+
+        assert_eq!(
+            relative_to(Some("https://example.com/arch/main.dot"), "../db.dot"),
+            "https://example.com/arch/../db.dot"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use flate2::read::GzDecoder;
+use std::fmt;
+use std::io::{self, Read};
+
+/// Largest remote document we are willing to download
+const MAX_DOWNLOAD_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Magic bytes at the start of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Errors that can occur while reading diagram input
+#[derive(Debug)]
+pub enum InputError {
+    /// Local file or stdin could not be read
+    Io(io::Error),
+    /// Remote input could not be fetched
+    Http(String),
+    /// Content (after decompression) is not valid UTF-8 text
+    NotUtf8,
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Http(msg) => write!(f, "download failed: {msg}"),
+            Self::NotUtf8 => write!(f, "input is not UTF-8 text"),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+impl From<io::Error> for InputError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Returns true if the input location should be fetched over HTTP
+pub fn is_url(location: &str) -> bool {
+    location.starts_with("https://") || location.starts_with("http://")
+}
+
+/// Reads diagram text from a file path or URL, decompressing gzip transparently
+pub fn read_location(location: &str) -> Result<String, InputError> {
+    let bytes = if is_url(location) {
+        fetch(location)?
+    } else {
+        std::fs::read(location)?
+    };
+    decode(bytes)
+}
+
+/// Reads diagram text from any reader (e.g. stdin), decompressing gzip transparently
+pub fn read_from(mut reader: impl Read) -> Result<String, InputError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    decode(bytes)
+}
+
+fn fetch(url: &str) -> Result<Vec<u8>, InputError> {
+    let mut response = ureq::get(url)
+        .call()
+        .map_err(|err| InputError::Http(err.to_string()))?;
+    response
+        .body_mut()
+        .with_config()
+        .limit(MAX_DOWNLOAD_BYTES)
+        .read_to_vec()
+        .map_err(|err| InputError::Http(err.to_string()))
+}
+
+/// Converts raw bytes to text, gunzipping first if they carry the gzip magic
+///
+/// Detection is by content rather than file extension so that `.gz` files,
+/// gzip-encoded downloads, and compressed stdin all work the same way.
+pub fn decode(bytes: Vec<u8>) -> Result<String, InputError> {
+    let bytes = if bytes.starts_with(&GZIP_MAGIC) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(bytes.as_slice()).read_to_end(&mut decompressed)?;
+        decompressed
+    } else {
+        bytes
+    };
+    String::from_utf8(bytes).map_err(|_| InputError::NotUtf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    #[test]
+    fn test_decode_plain_and_gzip() {
+        let dot = "digraph { A -> B }";
+        assert_eq!(decode(dot.as_bytes().to_vec()).unwrap(), dot);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(dot.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(decode(compressed).unwrap(), dot);
+    }
+
+    #[test]
+    fn test_decode_rejects_binary() {
+        assert!(matches!(
+            decode(vec![0xff, 0xfe, 0x00]),
+            Err(InputError::NotUtf8)
+        ));
+    }
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://example.com/graph.dot"));
+        assert!(is_url("http://localhost:8000/graph.dot.gz"));
+        assert!(!is_url("examples/dot/hierarchy.dot"));
+        assert!(!is_url("https.dot"));
+    }
+}
@@ -0,0 +1,738 @@
+use bevy::prelude::*;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Something the user can trigger from the keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    PanForward,
+    PanBack,
+    PanLeft,
+    PanRight,
+    OrbitUp,
+    OrbitDown,
+    OrbitLeft,
+    OrbitRight,
+    ZoomIn,
+    ZoomOut,
+    ShowAllLabels,
+    PinLabel,
+    OpenSearch,
+    CloseSearch,
+    ToggleMatchCase,
+    ToggleWholeWord,
+    ToggleSettings,
+    InspectNode,
+    DescribeView,
+    ToggleTheme,
+    ToggleHighContrast,
+    IncreaseUiScale,
+    DecreaseUiScale,
+    ToggleGrid,
+    ToggleAxes,
+    ToggleTopologicalView,
+    ToggleFlatten,
+    ToggleXray,
+    ToggleScreenClusters,
+    ToggleAlertFilter,
+    ToggleHelp,
+    ToggleLegend,
+    ToggleDiagnostics,
+    ToggleEdgeLines,
+    NextTourStep,
+    PreviousTourStep,
+    ToggleCycles,
+    ToggleReachability,
+    ToggleRankings,
+    ToggleTimeline,
+    ToggleCluster,
+    ToggleEditMode,
+    RenameNode,
+    DeleteNode,
+    Undo,
+    Redo,
+    SaveGraph,
+    ExportSubgraph,
+    CopyNodes,
+    CopyNodesJson,
+    SaveSession,
+    Reload,
+    ToggleConsole,
+    DebugCamera,
+    Quit,
+}
+
+/// Heading an action is listed under in the help overlay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Camera,
+    Labels,
+    Search,
+    View,
+    Analysis,
+    Edit,
+    General,
+}
+
+impl Category {
+    /// Display order in the help overlay
+    pub const ALL: [Self; 7] = [
+        Self::Camera,
+        Self::Labels,
+        Self::Search,
+        Self::View,
+        Self::Analysis,
+        Self::Edit,
+        Self::General,
+    ];
+
+    pub const fn title(self) -> &'static str {
+        match self {
+            Self::Camera => "Camera",
+            Self::Labels => "Labels",
+            Self::Search => "Search",
+            Self::View => "View",
+            Self::Analysis => "Analysis",
+            Self::Edit => "Edit",
+            Self::General => "General",
+        }
+    }
+}
+
+impl Action {
+    /// Name used in config files, e.g. `toggle_theme`
+    pub fn name(self) -> String {
+        let mut name = String::new();
+        for c in format!("{self:?}").chars() {
+            if c.is_ascii_uppercase() && !name.is_empty() {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+        }
+        name
+    }
+
+    pub const fn category(self) -> Category {
+        match self {
+            Self::PanForward
+            | Self::PanBack
+            | Self::PanLeft
+            | Self::PanRight
+            | Self::OrbitUp
+            | Self::OrbitDown
+            | Self::OrbitLeft
+            | Self::OrbitRight
+            | Self::ZoomIn
+            | Self::ZoomOut => Category::Camera,
+            Self::ShowAllLabels | Self::PinLabel => Category::Labels,
+            Self::OpenSearch
+            | Self::CloseSearch
+            | Self::ToggleMatchCase
+            | Self::ToggleWholeWord => Category::Search,
+            Self::ToggleSettings
+            | Self::InspectNode
+            | Self::DescribeView
+            | Self::ToggleTheme
+            | Self::ToggleHighContrast
+            | Self::IncreaseUiScale
+            | Self::DecreaseUiScale
+            | Self::ToggleGrid
+            | Self::ToggleAxes
+            | Self::ToggleTopologicalView
+            | Self::ToggleFlatten
+            | Self::ToggleXray
+            | Self::ToggleScreenClusters
+            | Self::ToggleAlertFilter
+            | Self::ToggleHelp
+            | Self::ToggleLegend
+            | Self::ToggleDiagnostics
+            | Self::ToggleEdgeLines
+            | Self::NextTourStep
+            | Self::PreviousTourStep => Category::View,
+            Self::ToggleCycles
+            | Self::ToggleReachability
+            | Self::ToggleRankings
+            | Self::ToggleTimeline
+            | Self::ToggleCluster => Category::Analysis,
+            Self::ToggleEditMode
+            | Self::RenameNode
+            | Self::DeleteNode
+            | Self::Undo
+            | Self::Redo
+            | Self::SaveGraph => Category::Edit,
+            Self::ExportSubgraph
+            | Self::CopyNodes
+            | Self::CopyNodesJson
+            | Self::SaveSession
+            | Self::Reload
+            | Self::ToggleConsole
+            | Self::DebugCamera
+            | Self::Quit => Category::General,
+        }
+    }
+
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::PanForward => "Pan forward",
+            Self::PanBack => "Pan back",
+            Self::PanLeft => "Pan left",
+            Self::PanRight => "Pan right",
+            Self::OrbitUp => "Orbit up",
+            Self::OrbitDown => "Orbit down",
+            Self::OrbitLeft => "Orbit left",
+            Self::OrbitRight => "Orbit right",
+            Self::ZoomIn => "Zoom in",
+            Self::ZoomOut => "Zoom out",
+            Self::ShowAllLabels => "Show all labels",
+            Self::PinLabel => "Pin/unpin hovered node's label",
+            Self::OpenSearch => "Search nodes",
+            Self::CloseSearch => "Close search",
+            Self::ToggleMatchCase => "Match case (while searching)",
+            Self::ToggleWholeWord => "Match whole words (while searching)",
+            Self::ToggleSettings => "Settings panel",
+            Self::InspectNode => "Inspect hovered node and its notes",
+            Self::DescribeView => "Describe the selection, hovered node or graph in text",
+            Self::ToggleTheme => "Switch dark/light theme",
+            Self::ToggleHighContrast => "High contrast",
+            Self::IncreaseUiScale => "Larger text and overlays",
+            Self::DecreaseUiScale => "Smaller text and overlays",
+            Self::ToggleGrid => "Reference grid",
+            Self::ToggleAxes => "XYZ axes",
+            Self::ToggleTopologicalView => "Topological order view (DAGs)",
+            Self::ToggleFlatten => "Flat top-down 2D view",
+            Self::ToggleXray => "Show selected and matched nodes through others",
+            Self::ToggleScreenClusters => "Badges for crowds of distant nodes",
+            Self::ToggleAlertFilter => "Show only alerting nodes and their neighbors",
+            Self::ToggleHelp => "This help",
+            Self::ToggleLegend => "Legend of the node types",
+            Self::ToggleDiagnostics => "Frame rate, entity counts and event timing",
+            Self::ToggleEdgeLines => "Draw edges as thin lines instead of tubes",
+            Self::NextTourStep => "Next tour step (with --tour)",
+            Self::PreviousTourStep => "Previous tour step (with --tour)",
+            Self::ToggleCycles => "Find cycles (strongly connected components)",
+            Self::ToggleReachability => "Shade what the selected node reaches and depends on",
+            Self::ToggleRankings => "Rank nodes by degree, betweenness or PageRank",
+            Self::ToggleTimeline => "Event timeline (scrub through past states)",
+            Self::ToggleCluster => "Expand hovered cluster / collapse hovered hub's (big graphs)",
+            Self::ToggleEditMode => "Edit mode",
+            Self::RenameNode => "Rename hovered node (edit mode)",
+            Self::DeleteNode => "Delete hovered node (edit mode)",
+            Self::Undo => "Undo last edit",
+            Self::Redo => "Redo",
+            Self::SaveGraph => "Save the whole graph",
+            Self::ExportSubgraph => "Export selection and neighbors",
+            Self::CopyNodes => "Copy selected or hovered nodes as text",
+            Self::CopyNodesJson => "Copy selected or hovered nodes as JSON",
+            Self::SaveSession => "Save the event log as a session recording",
+            Self::Reload => "Reload file",
+            Self::ToggleConsole => "Scripting console",
+            Self::DebugCamera => "Print camera state",
+            Self::Quit => "Exit",
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KeyBindings::default()
+            .bindings
+            .into_iter()
+            .map(|(action, _)| action)
+            .find(|action| action.name() == s)
+            .ok_or_else(|| format!("unknown action '{s}'"))
+    }
+}
+
+/// A key plus what Shift and Ctrl have to be doing for it to count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    pub key: KeyCode,
+    /// `Some(true)` needs Shift held, `Some(false)` needs it released, `None` ignores it
+    pub shift: Option<bool>,
+    /// Whether Ctrl (or Cmd on macOS) must be held; it must be released otherwise
+    pub ctrl: bool,
+}
+
+impl KeyChord {
+    /// The key on its own, whether or not Shift is held
+    pub const fn key(key: KeyCode) -> Self {
+        Self {
+            key,
+            shift: None,
+            ctrl: false,
+        }
+    }
+
+    /// The key with Shift held
+    pub const fn shift(key: KeyCode) -> Self {
+        Self {
+            key,
+            shift: Some(true),
+            ctrl: false,
+        }
+    }
+
+    /// The key without Shift, for keys whose shifted form means something else
+    pub const fn no_shift(key: KeyCode) -> Self {
+        Self {
+            key,
+            shift: Some(false),
+            ctrl: false,
+        }
+    }
+
+    /// The same chord with Ctrl held
+    #[must_use]
+    pub const fn with_ctrl(self) -> Self {
+        Self { ctrl: true, ..self }
+    }
+
+    fn modifiers_match(self, input: &ButtonInput<KeyCode>) -> bool {
+        let ctrl = input.any_pressed([
+            KeyCode::ControlLeft,
+            KeyCode::ControlRight,
+            KeyCode::SuperLeft,
+            KeyCode::SuperRight,
+        ]);
+        ctrl == self.ctrl
+            && self.shift.is_none_or(|shift| {
+                input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) == shift
+            })
+    }
+
+    /// Human-readable form, e.g. "Shift+Up"
+    pub fn label(self) -> String {
+        let key = match self.key {
+            KeyCode::ArrowUp => "Up".to_string(),
+            KeyCode::ArrowDown => "Down".to_string(),
+            KeyCode::ArrowLeft => "Left".to_string(),
+            KeyCode::ArrowRight => "Right".to_string(),
+            KeyCode::Slash => "/".to_string(),
+            KeyCode::Equal => "=".to_string(),
+            KeyCode::Minus => "-".to_string(),
+            KeyCode::NumpadAdd => "Num+".to_string(),
+            KeyCode::NumpadSubtract => "Num-".to_string(),
+            KeyCode::Escape => "Esc".to_string(),
+            KeyCode::Backquote => "`".to_string(),
+            other => {
+                let name = format!("{other:?}");
+                name.strip_prefix("Key")
+                    .or_else(|| name.strip_prefix("Digit"))
+                    .map_or_else(|| name.clone(), str::to_string)
+            }
+        };
+        let chord = match (self.shift, self.key) {
+            // Reads better as the character it produces
+            (Some(true), KeyCode::Slash) => "?".to_string(),
+            (Some(true), _) => format!("Shift+{key}"),
+            _ => key,
+        };
+        if self.ctrl {
+            format!("Ctrl+{chord}")
+        } else {
+            chord
+        }
+    }
+}
+
+/// Parses chords as written in config files, e.g. "Ctrl+Shift+S", "F5" or "?"
+///
+/// A key on its own fires whether or not Shift is held, like `KeyChord::key`.
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<_> = s.split('+').map(str::trim).collect();
+        // "Num+" and "Ctrl++" end in an empty part for the plus itself
+        if parts.len() > 1 && parts.last() == Some(&"") {
+            parts.pop();
+            let last = parts.len() - 1;
+            parts[last] = if parts[last] == "Num" { "Num+" } else { "+" };
+        }
+        let (key, modifiers) = parts.split_last().ok_or("empty key")?;
+        let mut chord = match *key {
+            // Shifted characters stand for their key with Shift held
+            "?" => Self::shift(KeyCode::Slash),
+            "+" => Self::shift(KeyCode::Equal),
+            key => Self::key(key_code(key).ok_or_else(|| format!("unknown key '{key}'"))?),
+        };
+        for modifier in modifiers {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "cmd" => chord.ctrl = true,
+                "shift" => chord.shift = Some(true),
+                _ => return Err(format!("unknown modifier '{modifier}' in '{s}'")),
+            }
+        }
+        Ok(chord)
+    }
+}
+
+/// The key with this name: the inverse of `KeyChord::label`, plus common aliases
+fn key_code(name: &str) -> Option<KeyCode> {
+    use KeyCode as C;
+    let upper = name.to_ascii_uppercase();
+    let code = match upper.as_str() {
+        "UP" => C::ArrowUp,
+        "DOWN" => C::ArrowDown,
+        "LEFT" => C::ArrowLeft,
+        "RIGHT" => C::ArrowRight,
+        "/" => C::Slash,
+        "=" => C::Equal,
+        "-" => C::Minus,
+        "`" => C::Backquote,
+        "NUM+" => C::NumpadAdd,
+        "NUM-" => C::NumpadSubtract,
+        "ESC" | "ESCAPE" => C::Escape,
+        "TAB" => C::Tab,
+        "SPACE" => C::Space,
+        "ENTER" | "RETURN" => C::Enter,
+        "BACKSPACE" => C::Backspace,
+        "DELETE" | "DEL" => C::Delete,
+        "HOME" => C::Home,
+        "END" => C::End,
+        "PAGEUP" => C::PageUp,
+        "PAGEDOWN" => C::PageDown,
+        _ => {
+            let mut chars = upper.chars();
+            return match (chars.next()?, chars.as_str()) {
+                (c @ 'A'..='Z', "") => LETTERS.get(usize::from(c as u8 - b'A')).copied(),
+                (c @ '0'..='9', "") => DIGITS.get(usize::from(c as u8 - b'0')).copied(),
+                ('F', n) => n
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| FUNCTION_KEYS.get(n.checked_sub(1)?).copied()),
+                _ => None,
+            };
+        }
+    };
+    Some(code)
+}
+
+const LETTERS: [KeyCode; 26] = {
+    use KeyCode as C;
+    [
+        C::KeyA,
+        C::KeyB,
+        C::KeyC,
+        C::KeyD,
+        C::KeyE,
+        C::KeyF,
+        C::KeyG,
+        C::KeyH,
+        C::KeyI,
+        C::KeyJ,
+        C::KeyK,
+        C::KeyL,
+        C::KeyM,
+        C::KeyN,
+        C::KeyO,
+        C::KeyP,
+        C::KeyQ,
+        C::KeyR,
+        C::KeyS,
+        C::KeyT,
+        C::KeyU,
+        C::KeyV,
+        C::KeyW,
+        C::KeyX,
+        C::KeyY,
+        C::KeyZ,
+    ]
+};
+
+const DIGITS: [KeyCode; 10] = {
+    use KeyCode as C;
+    [
+        C::Digit0,
+        C::Digit1,
+        C::Digit2,
+        C::Digit3,
+        C::Digit4,
+        C::Digit5,
+        C::Digit6,
+        C::Digit7,
+        C::Digit8,
+        C::Digit9,
+    ]
+};
+
+const FUNCTION_KEYS: [KeyCode; 12] = {
+    use KeyCode as C;
+    [
+        C::F1,
+        C::F2,
+        C::F3,
+        C::F4,
+        C::F5,
+        C::F6,
+        C::F7,
+        C::F8,
+        C::F9,
+        C::F10,
+        C::F11,
+        C::F12,
+    ]
+};
+
+/// Keyboard bindings for every action, in help-overlay order
+///
+/// Systems look actions up here rather than matching keys directly, so
+/// remapping a binding changes both the behavior and the help overlay.
+#[derive(Resource, Debug, Clone)]
+pub struct KeyBindings {
+    bindings: Vec<(Action, Vec<KeyChord>)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use KeyChord as K;
+        use KeyCode as C;
+        Self {
+            bindings: vec![
+                (Action::PanForward, vec![K::no_shift(C::ArrowUp)]),
+                (Action::PanBack, vec![K::no_shift(C::ArrowDown)]),
+                (Action::PanLeft, vec![K::no_shift(C::ArrowLeft)]),
+                (Action::PanRight, vec![K::no_shift(C::ArrowRight)]),
+                (Action::OrbitUp, vec![K::shift(C::ArrowUp)]),
+                (Action::OrbitDown, vec![K::shift(C::ArrowDown)]),
+                (Action::OrbitLeft, vec![K::shift(C::ArrowLeft)]),
+                (Action::OrbitRight, vec![K::shift(C::ArrowRight)]),
+                (
+                    Action::ZoomIn,
+                    vec![K::key(C::Equal), K::key(C::NumpadAdd), K::key(C::PageUp)],
+                ),
+                (
+                    Action::ZoomOut,
+                    vec![
+                        K::key(C::Minus),
+                        K::key(C::NumpadSubtract),
+                        K::key(C::PageDown),
+                    ],
+                ),
+                (Action::ShowAllLabels, vec![K::key(C::KeyL)]),
+                (Action::PinLabel, vec![K::key(C::KeyP)]),
+                (Action::OpenSearch, vec![K::no_shift(C::Slash)]),
+                (Action::CloseSearch, vec![K::key(C::Escape)]),
+                (Action::ToggleMatchCase, vec![K::key(C::KeyC).with_ctrl()]),
+                (Action::ToggleWholeWord, vec![K::key(C::KeyW).with_ctrl()]),
+                (Action::ToggleSettings, vec![K::key(C::Tab)]),
+                (Action::InspectNode, vec![K::key(C::KeyI)]),
+                (Action::DescribeView, vec![K::key(C::KeyN)]),
+                (Action::ToggleTheme, vec![K::no_shift(C::KeyT)]),
+                (Action::ToggleHighContrast, vec![K::shift(C::KeyT)]),
+                (
+                    Action::IncreaseUiScale,
+                    vec![
+                        K::key(C::Equal).with_ctrl(),
+                        K::key(C::NumpadAdd).with_ctrl(),
+                    ],
+                ),
+                (
+                    Action::DecreaseUiScale,
+                    vec![
+                        K::key(C::Minus).with_ctrl(),
+                        K::key(C::NumpadSubtract).with_ctrl(),
+                    ],
+                ),
+                (Action::ToggleGrid, vec![K::key(C::KeyG)]),
+                (Action::ToggleAxes, vec![K::key(C::KeyX)]),
+                (Action::ToggleTopologicalView, vec![K::key(C::KeyO)]),
+                (Action::ToggleFlatten, vec![K::key(C::KeyF)]),
+                (Action::ToggleXray, vec![K::key(C::KeyV)]),
+                (Action::ToggleScreenClusters, vec![K::key(C::KeyB)]),
+                (Action::ToggleAlertFilter, vec![K::key(C::KeyA)]),
+                (Action::ToggleHelp, vec![K::shift(C::Slash), K::key(C::F1)]),
+                (Action::ToggleLegend, vec![K::key(C::KeyK)]),
+                (Action::ToggleDiagnostics, vec![K::key(C::F3)]),
+                (Action::ToggleEdgeLines, vec![K::key(C::KeyW)]),
+                (Action::NextTourStep, vec![K::key(C::PageDown)]),
+                (Action::PreviousTourStep, vec![K::key(C::PageUp)]),
+                (Action::ToggleCycles, vec![K::key(C::KeyC)]),
+                (Action::ToggleReachability, vec![K::key(C::KeyR)]),
+                (Action::ToggleRankings, vec![K::key(C::KeyM)]),
+                (Action::ToggleTimeline, vec![K::key(C::KeyH)]),
+                (Action::ToggleCluster, vec![K::key(C::KeyU)]),
+                (Action::ToggleEditMode, vec![K::key(C::KeyE)]),
+                (Action::RenameNode, vec![K::key(C::F2)]),
+                (
+                    Action::DeleteNode,
+                    vec![K::key(C::Delete), K::key(C::Backspace)],
+                ),
+                (Action::Undo, vec![K::no_shift(C::KeyZ).with_ctrl()]),
+                (
+                    Action::Redo,
+                    vec![K::key(C::KeyY).with_ctrl(), K::shift(C::KeyZ).with_ctrl()],
+                ),
+                (Action::SaveGraph, vec![K::no_shift(C::KeyS).with_ctrl()]),
+                (Action::ExportSubgraph, vec![K::shift(C::KeyE).with_ctrl()]),
+                (Action::CopyNodes, vec![K::no_shift(C::KeyC).with_ctrl()]),
+                (Action::CopyNodesJson, vec![K::shift(C::KeyC).with_ctrl()]),
+                (Action::SaveSession, vec![K::shift(C::KeyS).with_ctrl()]),
+                (Action::Reload, vec![K::key(C::F5)]),
+                (Action::ToggleConsole, vec![K::key(C::Backquote)]),
+                (Action::DebugCamera, vec![K::key(C::KeyD)]),
+                (Action::Quit, vec![K::key(C::KeyQ)]),
+            ],
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Keys bound to an action
+    pub fn keys(&self, action: Action) -> &[KeyChord] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map_or(&[], |(_, keys)| keys.as_slice())
+    }
+
+    /// Replaces the keys bound to an action
+    pub fn rebind(&mut self, action: Action, keys: Vec<KeyChord>) {
+        if let Some((_, bound)) = self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            *bound = keys;
+        } else {
+            self.bindings.push((action, keys));
+        }
+    }
+
+    /// Takes the keys of an action away from every other action, for modes
+    /// that need keys the defaults already use
+    pub fn give_priority(&mut self, action: Action) {
+        let keys = self.keys(action).to_vec();
+        for (other, bound) in &mut self.bindings {
+            if *other != action {
+                bound.retain(|chord| !keys.contains(chord));
+            }
+        }
+    }
+
+    /// Whether any key for the action was pressed this frame
+    pub fn just_pressed(&self, action: Action, input: &ButtonInput<KeyCode>) -> bool {
+        self.keys(action)
+            .iter()
+            .any(|chord| input.just_pressed(chord.key) && chord.modifiers_match(input))
+    }
+
+    /// Whether any key for the action is held down
+    pub fn pressed(&self, action: Action, input: &ButtonInput<KeyCode>) -> bool {
+        self.keys(action)
+            .iter()
+            .any(|chord| input.pressed(chord.key) && chord.modifiers_match(input))
+    }
+
+    /// The help text: every binding, grouped by category
+    pub fn help_text(&self) -> String {
+        let mut text = String::new();
+        for category in Category::ALL {
+            let entries: Vec<_> = self
+                .bindings
+                .iter()
+                .filter(|(action, keys)| action.category() == category && !keys.is_empty())
+                .collect();
+            if entries.is_empty() {
+                continue;
+            }
+
+            let _ = writeln!(text, "{}", category.title());
+            for (action, keys) in entries {
+                let keys: Vec<_> = keys.iter().map(|k| k.label()).collect();
+                let _ = writeln!(text, "  {:<24} {}", keys.join(" / "), action.description());
+            }
+            // Mouse controls aren't remappable
+            let mouse_controls: &[_] = match category {
+                Category::Camera => &MOUSE_CONTROLS,
+                Category::Edit => &EDIT_MOUSE_CONTROLS,
+                _ => &[],
+            };
+            for (control, description) in mouse_controls {
+                let _ = writeln!(text, "  {control:<24} {description}");
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+const MOUSE_CONTROLS: [(&str, &str); 3] = [
+    ("Left drag", "Orbit"),
+    ("Right drag", "Pan"),
+    ("Scroll", "Zoom"),
+];
+
+const EDIT_MOUSE_CONTROLS: [(&str, &str); 3] = [
+    ("Double-click space", "Add a node (edit mode)"),
+    ("Double-click node", "Rename it (edit mode)"),
+    ("Drag node to node", "Add an edge (edit mode)"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shift_distinguishes_bindings() {
+        let bindings = KeyBindings::default();
+        let mut input = ButtonInput::<KeyCode>::default();
+
+        input.press(KeyCode::Slash);
+        assert!(bindings.just_pressed(Action::OpenSearch, &input));
+        assert!(!bindings.just_pressed(Action::ToggleHelp, &input));
+
+        input.clear();
+        input.press(KeyCode::ShiftLeft);
+        input.release(KeyCode::Slash);
+        input.clear();
+        input.press(KeyCode::Slash);
+        assert!(bindings.just_pressed(Action::ToggleHelp, &input));
+        assert!(!bindings.just_pressed(Action::OpenSearch, &input));
+    }
+
+    #[test]
+    fn test_help_text_follows_rebinding() {
+        let mut bindings = KeyBindings::default();
+        assert!(bindings.help_text().contains("L "));
+
+        bindings.rebind(Action::ShowAllLabels, vec![KeyChord::key(KeyCode::KeyK)]);
+        let help = bindings.help_text();
+        assert!(help.contains("Labels\n  K "));
+        assert!(help.contains('?'));
+
+        // A tour takes Page Up and Page Down away from zooming
+        bindings.give_priority(Action::NextTourStep);
+        bindings.give_priority(Action::PreviousTourStep);
+        assert_eq!(bindings.keys(Action::ZoomIn).len(), 2);
+        assert_eq!(bindings.keys(Action::ZoomOut).len(), 2);
+        assert_eq!(
+            bindings.keys(Action::NextTourStep),
+            [KeyChord::key(KeyCode::PageDown)]
+        );
+    }
+
+    #[test]
+    fn test_parses_config_names() {
+        assert_eq!("toggle_theme".parse(), Ok(Action::ToggleTheme));
+        assert_eq!(
+            Action::ToggleTopologicalView.name(),
+            "toggle_topological_view"
+        );
+        assert!("warp_drive".parse::<Action>().is_err());
+
+        let chord: KeyChord = "Ctrl+Shift+s".parse().unwrap();
+        assert_eq!(chord, KeyChord::shift(KeyCode::KeyS).with_ctrl());
+        assert_eq!("F5".parse(), Ok(KeyChord::key(KeyCode::F5)));
+        assert_eq!("?".parse(), Ok(KeyChord::shift(KeyCode::Slash)));
+        assert_eq!("Num+".parse(), Ok(KeyChord::key(KeyCode::NumpadAdd)));
+        // Every label reads back as the key it was made from
+        for (_, keys) in KeyBindings::default().bindings {
+            for chord in keys {
+                assert_eq!(
+                    chord.label().parse::<KeyChord>().map(|c| c.key),
+                    Ok(chord.key)
+                );
+            }
+        }
+        assert!("Hyper+K".parse::<KeyChord>().is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use crate::types::{GraphData, GraphNode, NodeHighlight};
+use bevy::input::mouse::{MouseMotion, MouseWheel};
+use bevy::prelude::*;
+use bevy_egui::{EguiPostUpdateSet, EguiRenderOutput};
+use bevy_panorbit_camera::PanOrbitCamera;
+use petgraph::graph::NodeIndex;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Seconds without input before kiosk mode takes over again, unless set
+/// otherwise
+pub const DEFAULT_IDLE_SECS: f32 = 30.0;
+
+/// Radians per second the camera orbits the graph while idle
+const ORBIT_SPEED: f32 = 0.1;
+
+/// Seconds each node stays in the spotlight
+const SPOTLIGHT_SECS: f32 = 6.0;
+
+/// How many of the best-connected nodes take turns in the spotlight
+const SPOTLIGHT_NODES: usize = 12;
+
+/// Unattended mode for wall displays and booths: while nobody touches it,
+/// the camera slowly orbits the graph, its best-connected nodes light up in
+/// turn and the UI is hidden
+///
+/// Any key, click, scroll or mouse movement hands control back, and the show
+/// starts again once input stops for `idle_after` seconds.
+#[derive(Resource, Debug, Clone)]
+pub struct Kiosk {
+    pub enabled: bool,
+    /// Seconds without input before the show starts
+    pub idle_after: f32,
+    /// Seconds since the last input
+    idle: f32,
+    /// Seconds until the next node takes the spotlight
+    next_spotlight: f32,
+    /// How many nodes have had the spotlight since the show started
+    turns: usize,
+    /// The node in the spotlight
+    spotlight: Option<Entity>,
+}
+
+impl Default for Kiosk {
+    fn default() -> Self {
+        Self::new(false, DEFAULT_IDLE_SECS)
+    }
+}
+
+impl Kiosk {
+    /// The show starts straight away when enabled, as if input had stopped
+    /// long ago
+    pub const fn new(enabled: bool, idle_after: f32) -> Self {
+        Self {
+            enabled,
+            idle_after,
+            idle: idle_after,
+            next_spotlight: 0.0,
+            turns: 0,
+            spotlight: None,
+        }
+    }
+
+    /// Whether the show is on
+    pub fn active(&self) -> bool {
+        self.enabled && self.idle >= self.idle_after
+    }
+}
+
+fn kiosk_enabled(kiosk: Res<Kiosk>) -> bool {
+    kiosk.enabled
+}
+
+fn kiosk_active(kiosk: Res<Kiosk>) -> bool {
+    kiosk.active()
+}
+
+pub struct KioskPlugin;
+
+impl Plugin for KioskPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Kiosk>()
+            .add_systems(
+                Update,
+                (
+                    track_kiosk_input,
+                    (orbit_while_idle, spotlight_nodes, hide_screen_ui),
+                )
+                    .chain()
+                    .run_if(kiosk_enabled),
+            )
+            .add_systems(
+                PostUpdate,
+                hide_egui_windows
+                    .after(EguiPostUpdateSet::ProcessOutput)
+                    .before(EguiPostUpdateSet::PostProcessOutput)
+                    .run_if(kiosk_active),
+            );
+    }
+}
+
+/// Up to `count` nodes, most connected first
+pub fn best_connected(data: &GraphData, count: usize) -> Vec<NodeIndex> {
+    let mut nodes: Vec<NodeIndex> = data.graph.node_indices().collect();
+    nodes.sort_by_cached_key(|&node| Reverse(data.graph.neighbors_undirected(node).count()));
+    nodes.truncate(count);
+    nodes
+}
+
+/// Restarts the idle time on any input, ending the show until it runs out
+#[allow(clippy::too_many_arguments)]
+pub fn track_kiosk_input(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    time: Res<Time>,
+    mut kiosk: ResMut<Kiosk>,
+) {
+    let touched = keys.get_pressed().next().is_some()
+        || mouse.get_pressed().next().is_some()
+        || motion.read().count() > 0
+        || wheel.read().count() > 0;
+    if !touched {
+        kiosk.idle += time.delta_secs();
+        return;
+    }
+    if kiosk.active() {
+        if let Some(node) = kiosk.spotlight.take() {
+            commands.entity(node).try_remove::<NodeHighlight>();
+        }
+        kiosk.turns = 0;
+        kiosk.next_spotlight = 0.0;
+    }
+    kiosk.idle = 0.0;
+}
+
+pub fn orbit_while_idle(
+    kiosk: Res<Kiosk>,
+    time: Res<Time>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+) {
+    if !kiosk.active() {
+        return;
+    }
+    for mut cam in &mut cameras {
+        cam.target_yaw += ORBIT_SPEED * time.delta_secs();
+    }
+}
+
+/// Lights up the best-connected nodes one after another
+pub fn spotlight_nodes(
+    mut commands: Commands,
+    time: Res<Time>,
+    graph_data: Option<Res<GraphData>>,
+    node_query: Query<(Entity, &GraphNode)>,
+    mut kiosk: ResMut<Kiosk>,
+) {
+    if !kiosk.active() {
+        return;
+    }
+    kiosk.next_spotlight -= time.delta_secs();
+    if kiosk.next_spotlight > 0.0 {
+        return;
+    }
+    kiosk.next_spotlight = SPOTLIGHT_SECS;
+    if let Some(node) = kiosk.spotlight.take() {
+        commands.entity(node).try_remove::<NodeHighlight>();
+    }
+    let nodes = graph_data.map_or_else(Vec::new, |data| best_connected(&data, SPOTLIGHT_NODES));
+    let Some(&next) = nodes.get(kiosk.turns % nodes.len().max(1)) else {
+        return;
+    };
+    kiosk.turns += 1;
+    if let Some((entity, _)) = node_query.iter().find(|(_, node)| node.index == next) {
+        commands
+            .entity(entity)
+            .insert(NodeHighlight { fade_timer: 1.0 });
+        kiosk.spotlight = Some(entity);
+    }
+}
+
+/// Takes the on-screen panels, hints and badges out of the layout during the
+/// show, and puts them back once it ends
+#[allow(clippy::implicit_hasher)]
+pub fn hide_screen_ui(
+    kiosk: Res<Kiosk>,
+    mut root_query: Query<(Entity, &mut Node), Without<ChildOf>>,
+    mut hidden: Local<HashSet<Entity>>,
+) {
+    if kiosk.active() {
+        for (entity, mut node) in &mut root_query {
+            if node.display != Display::None {
+                hidden.insert(entity);
+                node.display = Display::None;
+            }
+        }
+    } else if !hidden.is_empty() {
+        for entity in hidden.drain() {
+            if let Ok((_, mut node)) = root_query.get_mut(entity) {
+                node.display = Display::Flex;
+            }
+        }
+    }
+}
+
+/// Drops whatever egui drew this frame, so its windows stay out of the show
+/// without being closed
+pub fn hide_egui_windows(mut outputs: Query<&mut EguiRenderOutput>) {
+    for mut output in &mut outputs {
+        output.paint_jobs = Arc::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EventNodeInfo, GraphEvent};
+    use crate::graph_state::GraphState;
+
+    #[test]
+    fn test_kiosk_spotlights_hubs_until_touched() {
+        let mut state = GraphState::new();
+        for id in ["a", "b", "c", "d"] {
+            let info = EventNodeInfo {
+                name: id.to_string(),
+                node_type: None,
+                level: 0,
+                ..Default::default()
+            };
+            state.process_event(GraphEvent::AddNode {
+                id: id.to_string(),
+                info,
+            });
+        }
+        for (from, to) in [("b", "a"), ("b", "c"), ("b", "d"), ("c", "d")] {
+            state.process_event(GraphEvent::AddEdge {
+                from: from.to_string(),
+                to: to.to_string(),
+            });
+        }
+        let data = GraphData(state.as_graph_data());
+        let index = |id: &str| data.node_map[id];
+        assert_eq!(best_connected(&data, 1), [index("b")]);
+        assert_eq!(best_connected(&data, 10).last(), Some(&index("a")));
+
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<Time>()
+            .add_event::<MouseMotion>()
+            .add_event::<MouseWheel>()
+            .insert_resource(Kiosk::new(true, 10.0))
+            .add_systems(Update, (track_kiosk_input, spotlight_nodes).chain());
+        let hub = app
+            .world_mut()
+            .spawn(GraphNode {
+                id: "b".to_string(),
+                name: "b".to_string(),
+                index: index("b"),
+            })
+            .id();
+        app.insert_resource(data);
+
+        app.update();
+        assert!(app.world().get::<NodeHighlight>(hub).is_some());
+
+        // A key press ends the show and clears the spotlight
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Space);
+        app.update();
+        assert!(!app.world().resource::<Kiosk>().active());
+        assert!(app.world().get::<NodeHighlight>(hub).is_none());
+    }
+}
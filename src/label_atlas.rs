@@ -0,0 +1,472 @@
+//! Node labels drawn from a texture atlas, for scenes with too many nodes for
+//! a text entity each
+//!
+//! Each label's text is rasterized into an atlas page once, and every frame
+//! the labels in view are drawn as camera-facing quads of one mesh per page.
+//! They fade and pin like text labels, but can't be clicked, and hovered and
+//! selected nodes keep their fitted text.
+
+use crate::accessibility::Accessibility;
+use crate::theme::Theme;
+use crate::types::{GraphNode, LabelSettings, NodeLabel, PinnedLabels};
+use crate::ui::{
+    LABEL_FONT, LABEL_OFFSET, LABEL_WORLD_SCALE, create_node_labels, fitted_label, label_color,
+};
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::view::NoFrustumCulling;
+use bevy::text::cosmic_text::{Attrs, Buffer, Family, FontSystem, Metrics, Shaping};
+use bevy::text::{CosmicFontSystem, DEFAULT_FONT_DATA, SwashCache};
+use std::collections::HashMap;
+
+/// Width and height of an atlas page in pixels
+const PAGE_SIZE: u32 = 2048;
+
+/// Size labels are rasterized at
+const FONT_SIZE: f32 = 20.0;
+
+/// Height of a line of rasterized text in pixels
+const LINE_HEIGHT: u32 = 24;
+
+/// Empty pixels around each label in its page, so neighbors don't bleed in
+const PADDING: u32 = 2;
+
+/// How node labels are drawn
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LabelBackend {
+    /// A text entity under each node
+    #[default]
+    Text,
+    /// Quads of a few meshes, textured from an atlas of the labels' text
+    Atlas,
+}
+
+impl LabelBackend {
+    /// The backend for a scene of `nodes` nodes
+    pub const fn for_nodes(nodes: usize, settings: &LabelSettings) -> Self {
+        if settings.atlas_above > 0 && nodes > settings.atlas_above {
+            Self::Atlas
+        } else {
+            Self::Text
+        }
+    }
+}
+
+/// Switches labels to the atlas for big scenes and draws them from it
+pub struct LabelAtlasPlugin;
+
+impl Plugin for LabelAtlasPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LabelBackend>()
+            .init_resource::<LabelAtlas>()
+            .add_systems(Startup, load_label_font)
+            .add_systems(
+                Update,
+                (
+                    choose_label_backend.before(create_node_labels),
+                    draw_atlas_labels,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Where a label's text is in the atlas
+#[derive(Clone, Copy)]
+struct AtlasEntry {
+    page: usize,
+    rect: URect,
+}
+
+/// An atlas page, and the entity drawing its labels once it has one
+struct AtlasPage {
+    image: Handle<Image>,
+    drawn_by: Option<(Entity, Handle<Mesh>)>,
+}
+
+/// Label text rasterized so far, packed into pages row by row
+#[derive(Resource, Default)]
+pub struct LabelAtlas {
+    pages: Vec<AtlasPage>,
+    /// None for text with nothing to draw
+    entries: HashMap<String, Option<AtlasEntry>>,
+    /// Where the next label goes on the last page
+    cursor: UVec2,
+    row_height: u32,
+}
+
+impl LabelAtlas {
+    /// Drops every page, despawning what draws them
+    fn clear(&mut self, commands: &mut Commands) {
+        for page in self.pages.drain(..) {
+            if let Some((entity, _)) = page.drawn_by {
+                commands.entity(entity).despawn();
+            }
+        }
+        self.entries.clear();
+        self.cursor = UVec2::ZERO;
+        self.row_height = 0;
+    }
+
+    /// Where `text` is in the atlas, rasterizing it there the first time
+    fn entry(
+        &mut self,
+        text: &str,
+        font_system: &mut FontSystem,
+        swash_cache: &mut SwashCache,
+        images: &mut Assets<Image>,
+    ) -> Option<AtlasEntry> {
+        if let Some(&entry) = self.entries.get(text) {
+            return entry;
+        }
+        let entry = rasterize(text, font_system, swash_cache).map(|(size, coverage)| {
+            let (page, origin) = self.place(size, images);
+            if let Some(data) = images
+                .get_mut(&self.pages[page].image)
+                .and_then(|image| image.data.as_mut())
+            {
+                for y in 0..size.y {
+                    for x in 0..size.x {
+                        let pixel = ((origin.y + y) * PAGE_SIZE + origin.x + x) as usize;
+                        data[pixel * 4 + 3] = coverage[(y * size.x + x) as usize];
+                    }
+                }
+            }
+            AtlasEntry {
+                page,
+                rect: URect::from_corners(origin, origin + size),
+            }
+        });
+        self.entries.insert(text.to_string(), entry);
+        entry
+    }
+
+    /// Room for a label of `size` pixels: its page and top left corner
+    fn place(&mut self, size: UVec2, images: &mut Assets<Image>) -> (usize, UVec2) {
+        let padded = size + UVec2::splat(PADDING);
+        if self.cursor.x + padded.x > PAGE_SIZE {
+            self.cursor = UVec2::new(0, self.cursor.y + self.row_height);
+            self.row_height = 0;
+        }
+        if self.pages.is_empty() || self.cursor.y + padded.y > PAGE_SIZE {
+            // White everywhere, so only coverage needs writing
+            let image = Image::new_fill(
+                Extent3d {
+                    width: PAGE_SIZE,
+                    height: PAGE_SIZE,
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                &[255, 255, 255, 0],
+                TextureFormat::Rgba8UnormSrgb,
+                RenderAssetUsages::default(),
+            );
+            self.pages.push(AtlasPage {
+                image: images.add(image),
+                drawn_by: None,
+            });
+            self.cursor = UVec2::ZERO;
+            self.row_height = 0;
+        }
+        let origin = self.cursor;
+        self.cursor.x += padded.x;
+        self.row_height = self.row_height.max(padded.y);
+        (self.pages.len() - 1, origin)
+    }
+}
+
+/// The size of `text` in pixels and how much of each pixel it covers, row by
+/// row, or None if it draws nothing
+///
+/// Text too big for a page is cut off.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_possible_wrap,
+    clippy::cast_sign_loss
+)]
+fn rasterize(
+    text: &str,
+    font_system: &mut FontSystem,
+    swash_cache: &mut SwashCache,
+) -> Option<(UVec2, Vec<u8>)> {
+    let mut buffer = Buffer::new(font_system, Metrics::new(FONT_SIZE, LINE_HEIGHT as f32));
+    buffer.set_size(font_system, None, None);
+    buffer.set_text(
+        font_system,
+        text,
+        Attrs::new().family(Family::Name(LABEL_FONT)),
+        Shaping::Advanced,
+    );
+    buffer.shape_until_scroll(font_system, false);
+    let width = buffer
+        .layout_runs()
+        .map(|run| run.line_w)
+        .fold(0.0, f32::max)
+        .ceil() as u32;
+    let height = buffer.layout_runs().count() as u32 * LINE_HEIGHT;
+    let size = UVec2::new(width, height).min(UVec2::splat(PAGE_SIZE - PADDING));
+    if size.x == 0 || size.y == 0 {
+        return None;
+    }
+
+    let mut coverage = vec![0; (size.x * size.y) as usize];
+    let white = bevy::text::cosmic_text::Color::rgb(255, 255, 255);
+    buffer.draw(
+        font_system,
+        &mut swash_cache.0,
+        white,
+        |x, y, w, h, color| {
+            for py in y.max(0)..(y + h as i32).min(size.y as i32) {
+                for px in x.max(0)..(x + w as i32).min(size.x as i32) {
+                    let pixel = &mut coverage[py as usize * size.x as usize + px as usize];
+                    *pixel = (*pixel).max(color.a());
+                }
+            }
+        },
+    );
+    Some((size, coverage))
+}
+
+/// Makes sure the label font is in the font system the atlas draws with, as
+/// the UI only loads it once some text uses it
+fn load_label_font(mut font_system: ResMut<CosmicFontSystem>) {
+    let db = font_system.0.db_mut();
+    let loaded = db
+        .faces()
+        .any(|face| face.families.iter().any(|(family, _)| family == LABEL_FONT));
+    if !loaded {
+        db.load_font_data(DEFAULT_FONT_DATA.to_vec());
+    }
+}
+
+/// Moves labels to the atlas once the scene gets big enough, and back to
+/// text once it shrinks, dropping what the other backend drew
+pub fn choose_label_backend(
+    mut commands: Commands,
+    mut backend: ResMut<LabelBackend>,
+    mut atlas: ResMut<LabelAtlas>,
+    label_settings: Res<LabelSettings>,
+    node_query: Query<(), With<GraphNode>>,
+    label_query: Query<Entity, With<NodeLabel>>,
+) {
+    let chosen = LabelBackend::for_nodes(node_query.iter().count(), &label_settings);
+    if !backend.set_if_neq(chosen) {
+        return;
+    }
+    match chosen {
+        LabelBackend::Atlas => {
+            for label in &label_query {
+                commands.entity(label).despawn();
+            }
+        }
+        LabelBackend::Text => atlas.clear(&mut commands),
+    }
+}
+
+/// Vertices of the label quads of one atlas page
+#[derive(Default)]
+struct Quads {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl Quads {
+    /// Adds a quad with `corners` in top left, top right, bottom right,
+    /// bottom left order, showing `rect` of the page
+    #[allow(clippy::cast_possible_truncation)]
+    fn push(&mut self, corners: [Vec3; 4], rect: URect, color: Color) {
+        let first = self.positions.len() as u32;
+        let (min, max) = (
+            rect.min.as_vec2() / PAGE_SIZE as f32,
+            rect.max.as_vec2() / PAGE_SIZE as f32,
+        );
+        self.positions.extend(corners.map(|v| v.to_array()));
+        self.uvs.extend([
+            [min.x, min.y],
+            [max.x, min.y],
+            [max.x, max.y],
+            [min.x, max.y],
+        ]);
+        self.colors.extend([color.to_linear().to_f32_array(); 4]);
+        self.indices
+            .extend([0, 3, 2, 0, 2, 1].map(|corner| first + corner));
+    }
+
+    fn into_mesh(self) -> Mesh {
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, self.colors)
+        .with_inserted_indices(Indices::U32(self.indices))
+    }
+}
+
+/// Draws the labels in view as quads facing the camera, rasterizing any text
+/// the atlas doesn't have yet
+///
+/// The atlas starts over when the label width or wrapping changes, as every
+/// label's text does.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_atlas_labels(
+    mut commands: Commands,
+    backend: Res<LabelBackend>,
+    mut atlas: ResMut<LabelAtlas>,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut swash_cache: ResMut<SwashCache>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    camera_query: Query<(&GlobalTransform, &Projection), With<Camera3d>>,
+    node_query: Query<(&GlobalTransform, &GraphNode)>,
+    label_settings: Res<LabelSettings>,
+    pinned: Res<PinnedLabels>,
+    theme: Res<Theme>,
+    accessibility: Res<Accessibility>,
+    mut fitted_to: Local<Option<(usize, bool)>>,
+) {
+    if *backend != LabelBackend::Atlas {
+        return;
+    }
+    let Ok((camera_transform, projection)) = camera_query.single() else {
+        return;
+    };
+    let fit = (label_settings.max_width, label_settings.wrap);
+    if fitted_to.replace(fit).is_some_and(|before| before != fit) {
+        atlas.clear(&mut commands);
+    }
+
+    let (right, up) = (*camera_transform.right(), *camera_transform.up());
+    let mut pages: Vec<Quads> = Vec::new();
+    for (node_transform, graph_node) in &node_query {
+        let distance = match projection {
+            Projection::Orthographic(ortho) => ortho.scale,
+            _ => camera_transform
+                .translation()
+                .distance(node_transform.translation()),
+        };
+        let is_pinned = pinned.0.contains(&graph_node.id);
+        let Some(color) = label_color(distance, is_pinned, &label_settings, *theme, *accessibility)
+        else {
+            continue;
+        };
+        let text = fitted_label(&graph_node.name, &label_settings);
+        let Some(entry) = atlas.entry(&text, &mut font_system.0, &mut swash_cache, &mut images)
+        else {
+            continue;
+        };
+
+        // Centered over the node like a text label, at the same size
+        let size = entry.rect.size().as_vec2() / LINE_HEIGHT as f32
+            * LABEL_WORLD_SCALE
+            * accessibility.ui_scale;
+        let bottom = node_transform.transform_point(Vec3::Y * LABEL_OFFSET);
+        let (half_width, height) = (right * size.x / 2.0, up * size.y);
+        let corners = [
+            bottom - half_width + height,
+            bottom + half_width + height,
+            bottom + half_width,
+            bottom - half_width,
+        ];
+        if pages.len() <= entry.page {
+            pages.resize_with(entry.page + 1, Quads::default);
+        }
+        pages[entry.page].push(corners, entry.rect, color);
+    }
+
+    pages.resize_with(atlas.pages.len(), Quads::default);
+    for (page, quads) in atlas.pages.iter_mut().zip(pages) {
+        let mesh = quads.into_mesh();
+        if let Some((_, handle)) = &page.drawn_by {
+            if let Some(existing) = meshes.get_mut(handle) {
+                *existing = mesh;
+            }
+        } else {
+            let handle = meshes.add(mesh);
+            // Colors, fading included, come from the vertices
+            let material = materials.add(StandardMaterial {
+                base_color_texture: Some(page.image.clone()),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                cull_mode: None,
+                ..default()
+            });
+            let entity = commands
+                .spawn((
+                    Mesh3d(handle.clone()),
+                    MeshMaterial3d(material),
+                    Transform::default(),
+                    // Its bounds change every frame
+                    NoFrustumCulling,
+                ))
+                .id();
+            page.drawn_by = Some((entity, handle));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::NodeIndex;
+
+    #[test]
+    fn test_big_scenes_draw_labels_from_the_atlas() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, LabelAtlasPlugin))
+            .init_resource::<Assets<Image>>()
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<CosmicFontSystem>()
+            .init_resource::<SwashCache>()
+            .init_resource::<PinnedLabels>()
+            .init_resource::<Theme>()
+            .init_resource::<Accessibility>()
+            .insert_resource(LabelSettings {
+                show_all_labels: true,
+                atlas_above: 2,
+                ..default()
+            });
+        app.world_mut().spawn((
+            Camera3d::default(),
+            Projection::default(),
+            GlobalTransform::from(Transform::from_xyz(0.0, 0.0, 10.0)),
+        ));
+        for (i, name) in ["api", "db", "api"].into_iter().enumerate() {
+            app.world_mut().spawn((
+                GraphNode {
+                    id: i.to_string(),
+                    name: name.to_string(),
+                    index: NodeIndex::new(i),
+                },
+                GlobalTransform::from(Transform::from_xyz(i as f32, 0.0, 0.0)),
+            ));
+        }
+        app.update();
+
+        assert_eq!(*app.world().resource::<LabelBackend>(), LabelBackend::Atlas);
+        // Each name is rasterized once, but every node gets a quad
+        assert_eq!(app.world().resource::<LabelAtlas>().entries.len(), 2);
+        let world = app.world_mut();
+        let mesh = world
+            .query_filtered::<&Mesh3d, With<NoFrustumCulling>>()
+            .single(world)
+            .unwrap()
+            .0
+            .clone();
+        let mesh = world.resource::<Assets<Mesh>>().get(&mesh).unwrap();
+        assert_eq!(mesh.count_vertices(), 3 * 4);
+
+        app.world_mut().resource_mut::<LabelSettings>().atlas_above = 0;
+        app.update();
+        let world = app.world_mut();
+        assert_eq!(*world.resource::<LabelBackend>(), LabelBackend::Text);
+        assert_eq!(world.query::<&Mesh3d>().iter(world).count(), 0);
+    }
+}
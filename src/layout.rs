@@ -0,0 +1,724 @@
+use crate::graph_state::{self, RankDir};
+use crate::keybindings::{Action, KeyBindings};
+use crate::status::StatusMessage;
+use crate::types::{
+    GraphData, LayoutMode, LayoutSettings, LayoutTransition, LevelSource, SearchState,
+};
+use crate::visualization::{refresh_graph_scene, update_edge_positions};
+use bevy::prelude::*;
+use bevy::tasks::{ComputeTaskPool, ParallelSlice, TaskPool};
+use petgraph::algo::toposort;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Distance along the X axis between consecutive topological ranks
+const RANK_SPACING: f32 = 3.0;
+
+/// Distance between neighboring nodes that share a rank
+const TIE_SPACING: f32 = 1.5;
+
+/// How long nodes take to move to a new layout
+const TRANSITION_SECS: f32 = 0.8;
+
+/// Switching between layouts, and animating nodes to their new positions
+pub struct LayoutPlugin;
+
+impl Plugin for LayoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                toggle_topological_view,
+                toggle_flatten,
+                animate_layout_transitions
+                    .after(refresh_graph_scene)
+                    .before(update_edge_positions),
+            ),
+        );
+    }
+}
+
+/// Gap between neighboring `--group-by` lanes
+const LANE_GAP: f32 = 4.0;
+
+/// A band of the layout holding the nodes that share a `--group-by` value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lane {
+    pub name: String,
+    /// Where the lane starts and ends along the lane axis
+    pub start: f32,
+    pub end: f32,
+}
+
+/// Node positions, and the lanes they are grouped into
+#[derive(Debug, Default)]
+pub struct NodeLayout {
+    pub positions: HashMap<NodeIndex, Vec3>,
+    /// Empty unless nodes are grouped
+    pub lanes: Vec<Lane>,
+    /// Direction the lanes follow each other in
+    pub lane_axis: Vec3,
+}
+
+/// Where each node goes, laying out each group of nodes on its own if they
+/// are grouped
+///
+/// Lanes sit side by side along X in the hierarchical layout and are stacked
+/// top to bottom in the topological one, whose ranks already run along X.
+/// Nodes the hints pin go where they say, and take no room from the others.
+/// A flattened layout is laid on the ground, with what was up facing away
+/// from the camera's default position.
+pub fn layout_nodes(
+    data: &graph_state::GraphData,
+    nodes: &[NodeIndex],
+    layout: &LayoutSettings,
+) -> NodeLayout {
+    let pinned: HashMap<NodeIndex, Vec3> = data
+        .hints
+        .pinned
+        .iter()
+        .filter_map(|(id, &position)| Some((*data.node_map.get(id)?, position)))
+        .map(|(node, position)| (node, flattened(position, layout.flatten)))
+        .collect();
+    let free: Vec<NodeIndex> = nodes
+        .iter()
+        .copied()
+        .filter(|node| !pinned.contains_key(node))
+        .collect();
+    let mut result = free_layout(data, &free, layout);
+    result.positions.extend(
+        nodes
+            .iter()
+            .filter_map(|node| Some((*node, *pinned.get(node)?))),
+    );
+    result
+}
+
+fn free_layout(
+    data: &graph_state::GraphData,
+    nodes: &[NodeIndex],
+    layout: &LayoutSettings,
+) -> NodeLayout {
+    let levels = node_levels(data, layout.levels);
+    let Some(group_by) = layout.group_by else {
+        return NodeLayout {
+            positions: node_positions(data, nodes, &levels, layout),
+            ..default()
+        };
+    };
+    // Lanes run across the ranks
+    let rank_axis = data
+        .hints
+        .rank_dir
+        .map_or_else(|| native_rank_axis(layout.mode), RankDir::axis);
+    let lane_axis = flattened(
+        if rank_axis.x == 0.0 {
+            Vec3::X
+        } else {
+            Vec3::NEG_Y
+        },
+        layout.flatten,
+    );
+
+    let mut groups: BTreeMap<String, Vec<NodeIndex>> = BTreeMap::new();
+    for &node in nodes {
+        groups
+            .entry(group_by.value_of(&data.graph[node], levels[node.index()]))
+            .or_default()
+            .push(node);
+    }
+    // Each lane is laid out on its own, so they can be laid out at once
+    let groups: Vec<_> = groups.into_iter().collect();
+    let laid_out = par_map(&groups, |(name, members)| {
+        let positions = node_positions(data, members, &levels, layout);
+        let (min, max) = positions
+            .values()
+            .map(|position| position.dot(lane_axis))
+            .fold((f32::MAX, f32::MIN), |(min, max), x| {
+                (min.min(x), max.max(x))
+            });
+        (name.clone(), positions, min, max)
+    });
+
+    // Center the lanes on the origin, where the camera looks
+    let widths: f32 = laid_out.iter().map(|(.., min, max)| max - min).sum();
+    let total = LANE_GAP.mul_add(laid_out.len().saturating_sub(1) as f32, widths);
+    let mut start = -total / 2.0;
+    let mut result = NodeLayout {
+        lane_axis,
+        ..default()
+    };
+    for (name, positions, min, max) in laid_out {
+        let shift = lane_axis * (start - min);
+        result.positions.extend(
+            positions
+                .into_iter()
+                .map(|(node, position)| (node, position + shift)),
+        );
+        let end = start + (max - min);
+        result.lanes.push(Lane { name, start, end });
+        start = end + LANE_GAP;
+    }
+    result
+}
+
+/// `f` applied to each of `items` on the compute task pool, in their order
+pub fn par_map<T: Sync, R: Send + 'static>(
+    items: &[T],
+    f: impl Fn(&T) -> R + Send + Sync,
+) -> Vec<R> {
+    let pool = ComputeTaskPool::get_or_init(TaskPool::default);
+    items
+        .par_splat_map(pool, None, |_, chunk| {
+            chunk.iter().map(&f).collect::<Vec<_>>()
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Where each node goes in the current layout mode
+///
+/// The topological view falls back to the hierarchical layout if the graph
+/// has a cycle, e.g. one added by a live update. Nodes the hints put in a
+/// group sit next to each other in their rank.
+fn node_positions(
+    data: &graph_state::GraphData,
+    nodes: &[NodeIndex],
+    levels: &[u32],
+    layout: &LayoutSettings,
+) -> HashMap<NodeIndex, Vec3> {
+    let group_of: HashMap<NodeIndex, &str> = data
+        .hints
+        .groups
+        .iter()
+        .filter_map(|(id, group)| Some((*data.node_map.get(id)?, group.as_str())))
+        .collect();
+    let mut nodes = nodes.to_vec();
+    nodes.sort_by_key(|node| group_of.get(node).copied());
+
+    let topological = match layout.mode {
+        LayoutMode::Topological => topological_positions(data, &nodes, layout.flatten),
+        LayoutMode::Hierarchical => None,
+    };
+    let (positions, mode) = topological.map_or_else(
+        || {
+            let positions = hierarchical_positions(&nodes, levels, layout);
+            (positions, LayoutMode::Hierarchical)
+        },
+        |positions| (positions, LayoutMode::Topological),
+    );
+    let rotation = data.hints.rank_dir.map_or(Quat::IDENTITY, |rank_dir| {
+        Quat::from_rotation_arc(native_rank_axis(mode), rank_dir.axis())
+    });
+    positions
+        .into_iter()
+        .map(|(node, position)| (node, flattened(rotation * position, layout.flatten)))
+        .collect()
+}
+
+/// A position of the upright layout laid on the ground if `flatten` is set,
+/// so up becomes away from the camera's default position
+const fn flattened(position: Vec3, flatten: bool) -> Vec3 {
+    if flatten {
+        Vec3::new(position.x, 0.0, -position.y)
+    } else {
+        position
+    }
+}
+
+/// Where the `i`th of `count` nodes that share a rank sits across it: on a
+/// ring of `radius`, or in a row centered on the rank in flattened layouts
+#[allow(clippy::cast_precision_loss)]
+fn across_rank(i: usize, count: usize, radius: f32, flatten: bool) -> Vec2 {
+    if flatten {
+        Vec2::new(TIE_SPACING * (i as f32 - (count - 1) as f32 / 2.0), 0.0)
+    } else {
+        Vec2::from_angle(2.0 * std::f32::consts::PI * i as f32 / count as f32) * radius
+    }
+}
+
+/// The direction a layout places later ranks in, before any `rankdir`
+const fn native_rank_axis(mode: LayoutMode) -> Vec3 {
+    match mode {
+        LayoutMode::Hierarchical => Vec3::Y,
+        LayoutMode::Topological => Vec3::X,
+    }
+}
+
+/// The level each node is laid out on, by node index
+///
+/// Auto levels are the nodes' depth when none of them has a level: its
+/// topological rank, or if the graph has a cycle the fewest steps to it
+/// from a root. The hints' layers override all of them.
+fn node_levels(data: &graph_state::GraphData, source: LevelSource) -> Vec<u32> {
+    let attr = || data.graph.node_weights().map(|info| info.level).collect();
+    let mut levels: Vec<u32> = match source {
+        LevelSource::None => vec![0; data.graph.node_count()],
+        LevelSource::Attr => attr(),
+        LevelSource::Auto if data.graph.node_weights().any(|info| info.level > 0) => attr(),
+        LevelSource::Auto => {
+            let depths = ranks(data).unwrap_or_else(|| depths_from_roots(data));
+            data.graph
+                .node_indices()
+                .map(|node| u32::try_from(depths[&node]).unwrap_or(u32::MAX))
+                .collect()
+        }
+    };
+    // Nodes ranked together all take the deepest one's level
+    for group in same_rank_groups(data) {
+        let deepest = group.iter().map(|node| levels[node.index()]).max();
+        for node in group {
+            levels[node.index()] = deepest.unwrap_or(0);
+        }
+    }
+    for (id, &layer) in &data.hints.layers {
+        if let Some(node) = data.node_map.get(id) {
+            levels[node.index()] = layer;
+        }
+    }
+    levels
+}
+
+/// The hints' same-rank groups, as the nodes of them that are in the graph
+fn same_rank_groups(data: &graph_state::GraphData) -> impl Iterator<Item = Vec<NodeIndex>> + '_ {
+    data.hints.same_rank.iter().map(|ids| {
+        ids.iter()
+            .filter_map(|id| data.node_map.get(id).copied())
+            .collect()
+    })
+}
+
+/// Breadth-first depth from the nodes nothing points to
+///
+/// Cycles nothing leads into are entered at their first node.
+fn depths_from_roots(data: &graph_state::GraphData) -> HashMap<NodeIndex, usize> {
+    let mut depths = HashMap::new();
+    let mut queue: VecDeque<_> = data
+        .graph
+        .externals(petgraph::Direction::Incoming)
+        .map(|root| (root, 0))
+        .collect();
+    let mut unvisited = data.graph.node_indices();
+    loop {
+        while let Some((node, depth)) = queue.pop_front() {
+            if depths.contains_key(&node) {
+                continue;
+            }
+            depths.insert(node, depth);
+            queue.extend(data.graph.neighbors(node).map(|next| (next, depth + 1)));
+        }
+        match unvisited.find(|node| !depths.contains_key(node)) {
+            Some(node) => queue.push_back((node, 0)),
+            None => return depths,
+        }
+    }
+}
+
+/// Nodes on rings, one ring per level, stacked vertically; rows rather
+/// than rings if the layout is flattened
+#[allow(clippy::cast_precision_loss)]
+fn hierarchical_positions(
+    nodes: &[NodeIndex],
+    levels: &[u32],
+    layout: &LayoutSettings,
+) -> HashMap<NodeIndex, Vec3> {
+    let mut level_counts = HashMap::new();
+    for &node in nodes {
+        *level_counts.entry(levels[node.index()]).or_insert(0) += 1;
+    }
+
+    let mut level_indices = HashMap::new();
+    nodes
+        .iter()
+        .map(|&node| {
+            let level = levels[node.index()];
+            let level_idx = level_indices.entry(level).or_insert(0);
+            let count_at_level = level_counts[&level];
+
+            let level_radius = (level as f32).mul_add(layout.ring_spacing, layout.base_radius);
+            let across = across_rank(*level_idx, count_at_level, level_radius, layout.flatten);
+            *level_idx += 1;
+
+            let position = Vec3::new(across.x, level as f32 * layout.level_height, across.y);
+            (node, position)
+        })
+        .collect()
+}
+
+/// Nodes along the X axis by topological rank, so every edge points towards +X,
+/// with nodes of the same rank spread on a ring around the axis, or along Y
+/// if `flatten` is set
+///
+/// A node's rank is the length of the longest path to it. None if the graph
+/// has a cycle.
+#[allow(clippy::cast_precision_loss)]
+pub fn topological_positions(
+    data: &graph_state::GraphData,
+    nodes: &[NodeIndex],
+    flatten: bool,
+) -> Option<HashMap<NodeIndex, Vec3>> {
+    let ranks = ranks(data)?;
+    let mut by_rank: BTreeMap<usize, Vec<NodeIndex>> = BTreeMap::new();
+    for &node in nodes {
+        by_rank.entry(ranks[&node]).or_default().push(node);
+    }
+    // Center the graph on the origin, where the camera looks
+    let last_rank = by_rank.keys().next_back().copied().unwrap_or(0);
+    let offset = last_rank as f32 * RANK_SPACING / 2.0;
+
+    let mut positions = HashMap::new();
+    for (rank, tied) in by_rank {
+        let x = (rank as f32).mul_add(RANK_SPACING, -offset);
+        let count = tied.len();
+        // Ring just wide enough to keep neighbors TIE_SPACING apart
+        let radius = if count > 1 {
+            TIE_SPACING / (2.0 * (std::f32::consts::PI / count as f32).sin())
+        } else {
+            0.0
+        };
+        for (i, node) in tied.into_iter().enumerate() {
+            let across = across_rank(i, count, radius, flatten);
+            positions.insert(node, Vec3::new(x, across.x, across.y));
+        }
+    }
+    Some(positions)
+}
+
+/// The length of the longest path to each node, or None if the graph has a cycle
+///
+/// Nodes the hints rank together share a rank, unless that would take an
+/// edge backwards.
+fn ranks(data: &graph_state::GraphData) -> Option<HashMap<NodeIndex, usize>> {
+    let mut same_rank = HashMap::new();
+    for group in same_rank_groups(data) {
+        for &node in &group {
+            same_rank.entry(node).or_insert(group[0]);
+        }
+    }
+    longest_paths(data, &same_rank).or_else(|| longest_paths(data, &HashMap::new()))
+}
+
+/// Longest paths through the graph with each node in `merged` folded into
+/// the node it maps to
+fn longest_paths(
+    data: &graph_state::GraphData,
+    merged: &HashMap<NodeIndex, NodeIndex>,
+) -> Option<HashMap<NodeIndex, usize>> {
+    let target = |node| merged.get(&node).copied().unwrap_or(node);
+    let mut folded = data.graph.map(|_, _| (), |_, _| ());
+    folded.clear_edges();
+    for edge in data.graph.edge_references() {
+        let (from, to) = (target(edge.source()), target(edge.target()));
+        if from != to {
+            folded.add_edge(from, to, ());
+        }
+    }
+
+    let order = toposort(&folded, None).ok()?;
+    let mut ranks: HashMap<NodeIndex, usize> = HashMap::new();
+    for node in order {
+        let rank = folded
+            .neighbors_directed(node, petgraph::Direction::Incoming)
+            .map(|parent| ranks[&parent] + 1)
+            .max()
+            .unwrap_or(0);
+        ranks.insert(node, rank);
+    }
+    Some(
+        data.graph
+            .node_indices()
+            .map(|node| (node, ranks[&target(node)]))
+            .collect(),
+    )
+}
+
+/// Switches between the hierarchical and topological layouts
+///
+/// Graphs with a cycle have no topological order, so they stay hierarchical.
+pub fn toggle_topological_view(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    search_state: Res<SearchState>,
+    graph_data: Option<Res<GraphData>>,
+    time: Res<Time>,
+    mut layout: ResMut<LayoutSettings>,
+    mut message: ResMut<StatusMessage>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::ToggleTopologicalView, &keyboard_input)
+    {
+        return;
+    }
+    layout.mode = match layout.mode {
+        LayoutMode::Hierarchical => {
+            let cyclic = graph_data.is_some_and(|data| toposort(&data.graph, None).is_err());
+            if cyclic {
+                message.show(
+                    "The graph has cycles, so it has no topological order (press C to find them)",
+                    &time,
+                );
+                return;
+            }
+            LayoutMode::Topological
+        }
+        LayoutMode::Topological => LayoutMode::Hierarchical,
+    };
+}
+
+/// Lays the graph flat on the ground for the top-down 2D view, or stands it
+/// back up
+pub fn toggle_flatten(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    mut layout: ResMut<LayoutSettings>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::ToggleFlatten, &keyboard_input) {
+        return;
+    }
+    layout.flatten = !layout.flatten;
+}
+
+/// Eases nodes from their old layout position to their new one
+pub fn animate_layout_transitions(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut node_query: Query<(Entity, &mut Transform, &mut LayoutTransition)>,
+) {
+    for (entity, mut transform, mut transition) in &mut node_query {
+        transition.elapsed += time.delta_secs();
+        let t = (transition.elapsed / TRANSITION_SECS).min(1.0);
+        let eased = t * t * 2.0f32.mul_add(-t, 3.0);
+        transform.translation = transition.from.lerp(transition.to, eased);
+        if t >= 1.0 {
+            commands.entity(entity).remove::<LayoutTransition>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+    use crate::types::GroupBy;
+
+    #[test]
+    fn test_topological_positions_follow_dependencies() {
+        let content = r"digraph {
+            build -> test
+            build -> lint
+            test -> release
+            lint -> release
+            build -> release
+        }";
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+        let nodes: Vec<_> = data.graph.node_indices().collect();
+        let positions = topological_positions(&data, &nodes, false).unwrap();
+        let x = |id: &str| positions[&data.node_map[id]].x;
+
+        // The shortcut edge doesn't pull release forward: it ranks by its longest path
+        assert!(x("build") < x("test"));
+        assert!(x("test") < x("release"));
+        assert!((x("test") - x("lint")).abs() < f32::EPSILON);
+        assert!((x("build") + x("release")).abs() < f32::EPSILON);
+        // Ties are spread apart rather than stacked
+        let test = positions[&data.node_map["test"]];
+        let lint = positions[&data.node_map["lint"]];
+        assert!((test.distance(lint) - TIE_SPACING).abs() < 1e-4);
+
+        let content = r"digraph {
+            a -> b
+            b -> a
+        }";
+        let (cyclic, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let cyclic = cyclic.as_graph_data();
+        let nodes: Vec<_> = cyclic.graph.node_indices().collect();
+        assert!(topological_positions(&cyclic, &nodes, false).is_none());
+    }
+
+    #[test]
+    fn test_missing_levels_are_inferred() {
+        let levels_of = |content: &str, source| {
+            let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+            let data = state.as_graph_data();
+            let levels = node_levels(&data, source);
+            let mut ids: Vec<_> = data.node_map.iter().collect();
+            ids.sort();
+            ids.into_iter()
+                .map(|(id, node)| format!("{id}={}", levels[node.index()]))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        let dag = r"digraph {
+            a -> b
+            b -> c
+            a -> c
+        }";
+        assert_eq!(levels_of(dag, LevelSource::Auto), "a=0 b=1 c=2");
+        assert_eq!(levels_of(dag, LevelSource::Attr), "a=0 b=0 c=0");
+        // Cycles fall back to the distance from a root, entering rootless ones anywhere
+        let cyclic = r"digraph {
+            r -> x
+            x -> y
+            y -> x
+            p -> q
+            q -> p
+        }";
+        let levels = levels_of(cyclic, LevelSource::Auto);
+        assert!(levels.ends_with("r=0 x=1 y=2"), "{levels}");
+        assert!(levels.starts_with("p=0 q=1") || levels.starts_with("p=1 q=0"));
+
+        let leveled = r#"digraph {
+            a [level="2"]
+            a -> b
+        }"#;
+        assert_eq!(levels_of(leveled, LevelSource::Auto), "a=2 b=0");
+        assert_eq!(levels_of(leveled, LevelSource::None), "a=0 b=0");
+    }
+
+    #[test]
+    fn test_rankdir_and_same_ranks_orient_the_layout() {
+        let content = r"digraph {
+            rankdir=TB
+            { rank=same; b; d }
+            a -> b
+            a -> c
+            c -> d
+        }";
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+        let nodes: Vec<_> = data.graph.node_indices().collect();
+
+        for mode in LayoutMode::ALL {
+            let settings = LayoutSettings { mode, ..default() };
+            let positions = layout_nodes(&data, &nodes, &settings).positions;
+            let y = |id: &str| positions[&data.node_map[id]].y;
+            // Top to bottom, with b held back to d's rank
+            assert!(y("a") > y("c"), "{mode:?}");
+            assert!(y("c") > y("b"), "{mode:?}");
+            assert!((y("b") - y("d")).abs() < 1e-4, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn test_pins_layers_and_groups_constrain_the_layout() {
+        let content = r#"digraph {
+            p [pos="72,36!"]
+            b [layer=3]
+            c [group="g"]
+            e [group="g"]
+            a -> b
+            a -> c
+            a -> d
+            a -> e
+            a -> f
+        }"#;
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+        let nodes: Vec<_> = data.graph.node_indices().collect();
+        let settings = LayoutSettings::default();
+        let positions = layout_nodes(&data, &nodes, &settings).positions;
+        let at = |id: &str| positions[&data.node_map[id]];
+
+        assert_eq!(at("p"), Vec3::new(2.0, 1.0, 0.0));
+        let layer_y = 3.0 * settings.level_height;
+        assert!((at("b").y - layer_y).abs() < 1e-4);
+        // c, d, e and f share a ring, with the grouped pair side by side
+        // rather than across it
+        let radius = at("c").xz().length();
+        assert!(at("c").distance(at("e")) < 1.5 * radius);
+    }
+
+    #[test]
+    fn test_flattened_layouts_lie_on_the_ground() {
+        let content = r#"digraph {
+            p [pos="72,36!"]
+            a -> b
+            a -> c
+            a -> d
+            b -> d
+        }"#;
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+        let nodes: Vec<_> = data.graph.node_indices().collect();
+
+        for mode in LayoutMode::ALL {
+            let settings = LayoutSettings {
+                mode,
+                flatten: true,
+                ..default()
+            };
+            let positions = layout_nodes(&data, &nodes, &settings).positions;
+            let at = |id: &str| positions[&data.node_map[id]];
+            assert!(positions.values().all(|p| p.y == 0.0), "{mode:?}");
+            assert_eq!(at("p"), Vec3::new(2.0, 0.0, -1.0));
+            // Ties sit in a row, TIE_SPACING apart, rather than on a ring
+            assert!(
+                (at("b").distance(at("c")) - TIE_SPACING).abs() < 1e-4,
+                "{mode:?}"
+            );
+        }
+        // What was up is away from the camera
+        let settings = LayoutSettings {
+            flatten: true,
+            ..default()
+        };
+        let positions = layout_nodes(&data, &nodes, &settings).positions;
+        assert!(positions[&data.node_map["d"]].z < positions[&data.node_map["a"]].z);
+    }
+
+    #[test]
+    fn test_grouped_nodes_get_their_own_lanes() {
+        let content = r#"digraph {
+            web [type="service"]
+            api [type="service"]
+            db [type="database"]
+            cache [type="database"]
+            queue
+            web -> api
+            api -> db
+            api -> cache
+            api -> queue
+        }"#;
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+        let nodes: Vec<_> = data.graph.node_indices().collect();
+        let lane_of = |layout: &NodeLayout, id: &str| {
+            let along = layout.positions[&data.node_map[id]].dot(layout.lane_axis);
+            layout
+                .lanes
+                .iter()
+                .position(|lane| (lane.start - 1e-4..=lane.end + 1e-4).contains(&along))
+                .unwrap()
+        };
+
+        for mode in LayoutMode::ALL {
+            let settings = LayoutSettings {
+                mode,
+                group_by: Some(GroupBy::Type),
+                ..default()
+            };
+            let layout = layout_nodes(&data, &nodes, &settings);
+            let names: Vec<_> = layout.lanes.iter().map(|lane| lane.name.as_str()).collect();
+            assert_eq!(names, ["database", "service", "untyped"]);
+            assert_eq!(lane_of(&layout, "db"), 0);
+            assert_eq!(lane_of(&layout, "cache"), 0);
+            assert_eq!(lane_of(&layout, "api"), 1);
+            assert_eq!(lane_of(&layout, "queue"), 2);
+            // Lanes are apart and centered on the origin
+            for pair in layout.lanes.windows(2) {
+                assert!(pair[1].start - pair[0].end >= LANE_GAP - 1e-4);
+            }
+            let (first, last) = (&layout.lanes[0], &layout.lanes[2]);
+            assert!((first.start + last.end).abs() < 1e-3);
+        }
+
+        let layout = layout_nodes(&data, &nodes, &LayoutSettings::default());
+        assert!(layout.lanes.is_empty());
+        assert_eq!(layout.positions.len(), 5);
+    }
+}
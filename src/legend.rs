@@ -0,0 +1,162 @@
+use crate::graph_state::{GraphQuery, GraphState};
+use crate::keybindings::{Action, KeyBindings};
+use crate::theme::Theme;
+use crate::types::{Legend, SearchState, ThemedPanel, ThemedText, ViewSettings};
+use crate::visualization::get_node_shape_name;
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+
+/// Side of the color swatch in front of each type
+const SWATCH_SIZE: f32 = 12.0;
+
+/// A corner panel listing each node type in the graph with its color, shape
+/// and how many nodes have it
+pub struct LegendPlugin;
+
+impl Plugin for LegendPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_legend).add_systems(
+            Update,
+            (
+                toggle_legend,
+                update_legend
+                    .run_if(resource_changed::<GraphState>.or(resource_changed::<ViewSettings>)),
+            ),
+        );
+    }
+}
+
+/// How many nodes have each type, by type name; untyped nodes come last
+pub fn type_counts(graph_state: &GraphState) -> Vec<(Option<String>, usize)> {
+    let mut counts: BTreeMap<Option<&str>, usize> = BTreeMap::new();
+    for info in graph_state.graph().node_weights() {
+        *counts.entry(info.node_type.as_deref()).or_insert(0) += 1;
+    }
+    let untyped = counts.remove(&None);
+    counts
+        .into_iter()
+        .map(|(node_type, count)| (node_type.map(str::to_string), count))
+        .chain(untyped.map(|count| (None, count)))
+        .collect()
+}
+
+fn setup_legend(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(40.0),
+            right: Val::Px(10.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.0),
+            padding: UiRect::all(Val::Px(10.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.05, 0.05, 0.1, 0.85)),
+        Visibility::Hidden,
+        Legend,
+        ThemedPanel,
+    ));
+}
+
+pub fn toggle_legend(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    mut legend_query: Query<&mut Visibility, With<Legend>>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::ToggleLegend, &keyboard_input) {
+        return;
+    }
+    if let Ok(mut visibility) = legend_query.single_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+/// Rebuilds the legend's rows from the node types now in the graph, in the
+/// colors and shapes they are drawn with
+pub fn update_legend(
+    mut commands: Commands,
+    graph_state: Res<GraphState>,
+    view: Res<ViewSettings>,
+    theme: Res<Theme>,
+    legend_query: Query<Entity, With<Legend>>,
+) {
+    let Ok(legend) = legend_query.single() else {
+        return;
+    };
+    let font = TextFont {
+        font_size: 14.0,
+        ..default()
+    };
+
+    let mut legend = commands.entity(legend);
+    legend.despawn_related::<Children>();
+    legend.with_children(|panel| {
+        panel.spawn((
+            Text::new("Node types"),
+            font.clone(),
+            TextColor(theme.text()),
+            ThemedText,
+        ));
+        for (node_type, count) in type_counts(&graph_state) {
+            let color = view.palette.node_color(node_type.as_deref());
+            let shape = get_node_shape_name(node_type.as_deref(), view.distinct_shapes);
+            let name = node_type.as_deref().unwrap_or("untyped");
+            panel
+                .spawn(Node {
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(6.0),
+                    ..default()
+                })
+                .with_children(|row| {
+                    row.spawn((
+                        Node {
+                            width: Val::Px(SWATCH_SIZE),
+                            height: Val::Px(SWATCH_SIZE),
+                            ..default()
+                        },
+                        BackgroundColor(color),
+                    ));
+                    row.spawn((
+                        Text::new(format!("{name} ({shape}) {count}")),
+                        font.clone(),
+                        TextColor(theme.text()),
+                        ThemedText,
+                    ));
+                });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_counts_the_types_in_the_graph() {
+        let content = r#"digraph {
+            web [type="site"]
+            api [type="team"]
+            auth [type="team"]
+            misc
+            web -> api
+            api -> auth
+            api -> misc
+        }"#;
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        assert_eq!(
+            type_counts(&state),
+            [
+                (Some("site".to_string()), 1),
+                (Some("team".to_string()), 2),
+                (None, 1),
+            ]
+        );
+        assert!(type_counts(&GraphState::new()).is_empty());
+    }
+}
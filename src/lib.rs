@@ -2,13 +2,81 @@
 #![allow(clippy::needless_pass_by_value)] // Bevy systems require owned Res parameters
 #![allow(clippy::multiple_crate_versions)] // Bevy dependencies have multiple versions
 
+pub mod accessibility;
+pub mod aggregate;
+pub mod alerts;
+pub mod antialiasing;
+#[cfg(feature = "audio")]
+pub mod audio;
 pub mod camera;
+pub mod captions;
+pub mod capture;
+pub mod changes;
+pub mod clipboard;
+pub mod config;
+pub mod cycles;
+pub mod describe;
+pub mod diagnostics;
+pub mod edge_lines;
+pub mod edit;
+pub mod editor;
 pub mod events;
+pub mod export;
+pub mod flash;
+pub mod fonts;
+pub mod gauges;
+pub mod generate;
 pub mod graph_state;
+pub mod grid;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod highlight;
+pub mod history;
+pub mod hover;
+pub mod includes;
+pub mod input;
+pub mod keybindings;
+pub mod kiosk;
+pub mod label_atlas;
+pub mod layout;
+pub mod legend;
+pub mod lighting;
+pub mod live;
+pub mod loader;
+pub mod merge;
+pub mod merged_edges;
+pub mod metrics;
+pub mod models;
+pub mod notes;
+pub mod palette;
+pub mod plugin;
+pub mod quality;
+pub mod reachability;
+pub mod scene_build;
+pub mod screen_clusters;
+pub mod scripting;
 pub mod search;
+pub mod selection;
+pub mod settings;
+pub mod sidecar;
 pub mod sources;
+pub mod staleness;
+pub mod status;
+pub mod theme;
+pub mod timeline;
+pub mod tour;
+pub mod type_rules;
 pub mod types;
 pub mod ui;
+pub mod validate;
 pub mod visualization;
+pub mod window;
+pub mod xray;
 
-pub use types::GraphData;
+pub use events::{EventEdgeInfo, EventNodeInfo, GraphEvent};
+pub use graph_state::{EdgeInfo, GraphQuery, GraphState, LayoutHints, NodeInfo, RankDir};
+pub use live::LiveEvents;
+pub use plugin::{DotspaceAppExt, DotspacePlugin};
+pub use search::{MatchOptions, SearchQuery};
+pub use sources::{GraphEventSource, Progress, SourceError, SourceFormat, SourceRegistry};
+pub use types::{GraphData, GraphEdge, GraphNode};
@@ -0,0 +1,273 @@
+use crate::types::{LayoutSettings, SceneLight};
+use bevy::asset::RenderAssetUsages;
+use bevy::core_pipeline::Skybox;
+use bevy::pbr::{DistanceFog, FogFalloff};
+use bevy::prelude::*;
+use bevy::render::render_resource::{
+    Extent3d, TextureDimension, TextureFormat, TextureViewDescriptor, TextureViewDimension,
+};
+use std::str::FromStr;
+
+/// Edge length in pixels of each generated skybox face
+const SKY_FACE_SIZE: u32 = 64;
+
+/// Light rig the scene is lit with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LightingPreset {
+    /// Key, fill and rim lights so no side of a node falls into shadow
+    #[default]
+    Studio,
+    /// A single warm sun under a bright sky
+    Outdoor,
+    /// Dim, moody light for dark backgrounds
+    Dark,
+}
+
+impl LightingPreset {
+    pub const ALL: [Self; 3] = [Self::Studio, Self::Outdoor, Self::Dark];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Studio => "studio",
+            Self::Outdoor => "outdoor",
+            Self::Dark => "dark",
+        }
+    }
+
+    pub const fn ambient(self) -> AmbientLight {
+        let (color, brightness) = match self {
+            Self::Studio => (Color::WHITE, 400.0),
+            Self::Outdoor => (Color::srgb(0.75, 0.85, 1.0), 600.0),
+            Self::Dark => (Color::srgb(0.6, 0.6, 0.8), 60.0),
+        };
+        AmbientLight {
+            color,
+            brightness,
+            affects_lightmapped_meshes: true,
+        }
+    }
+
+    /// Directional lights as (color, illuminance, pitch, yaw)
+    fn rig(self) -> Vec<(Color, f32, f32, f32)> {
+        match self {
+            Self::Studio => vec![
+                (Color::WHITE, 7000.0, -0.6, -0.6),
+                (Color::srgb(0.9, 0.95, 1.0), 3000.0, -0.3, 2.4),
+                (Color::WHITE, 2000.0, 0.5, 3.6),
+            ],
+            Self::Outdoor => vec![(Color::srgb(1.0, 0.95, 0.85), 12000.0, -0.9, -0.4)],
+            Self::Dark => vec![
+                (Color::srgb(0.7, 0.75, 1.0), 2500.0, -0.5, -0.5),
+                (Color::srgb(0.5, 0.5, 0.8), 800.0, 0.3, 2.6),
+            ],
+        }
+    }
+
+    /// Zenith, horizon and ground colors of the preset's skybox
+    const fn sky(self) -> [Srgba; 3] {
+        match self {
+            Self::Studio => [
+                Srgba::rgb(0.35, 0.37, 0.42),
+                Srgba::rgb(0.6, 0.62, 0.66),
+                Srgba::rgb(0.25, 0.25, 0.27),
+            ],
+            Self::Outdoor => [
+                Srgba::rgb(0.25, 0.45, 0.85),
+                Srgba::rgb(0.75, 0.85, 0.95),
+                Srgba::rgb(0.35, 0.33, 0.3),
+            ],
+            Self::Dark => [
+                Srgba::rgb(0.01, 0.01, 0.03),
+                Srgba::rgb(0.06, 0.06, 0.12),
+                Srgba::rgb(0.02, 0.02, 0.02),
+            ],
+        }
+    }
+}
+
+/// Chosen light rig, whether a matching skybox is drawn and how thick the fog is
+#[derive(Resource, Debug, Clone, PartialEq, Default)]
+pub struct Lighting {
+    pub preset: LightingPreset,
+    pub skybox: bool,
+    /// Whether the lights cast shadows, which costs a render of the scene
+    /// per light
+    pub shadows: bool,
+    /// Density of the fog that fades far parts of the graph out; 0 for none
+    pub fog: f32,
+}
+
+impl FromStr for LightingPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown lighting '{s}' (expected studio, outdoor or dark)"))
+    }
+}
+
+/// Replaces the light rig, ambient light and skybox when the lighting changes
+pub fn apply_lighting(
+    mut commands: Commands,
+    lighting: Res<Lighting>,
+    light_query: Query<Entity, With<SceneLight>>,
+    camera_query: Query<Entity, With<Camera3d>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for entity in &light_query {
+        commands.entity(entity).despawn();
+    }
+    for (color, illuminance, pitch, yaw) in lighting.preset.rig() {
+        commands.spawn((
+            DirectionalLight {
+                color,
+                illuminance,
+                shadows_enabled: lighting.shadows,
+                ..default()
+            },
+            Transform::from_rotation(Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.0)),
+            SceneLight,
+        ));
+    }
+    commands.insert_resource(lighting.preset.ambient());
+
+    let sky = lighting
+        .skybox
+        .then(|| images.add(sky_image(lighting.preset.sky())));
+    for camera in &camera_query {
+        match &sky {
+            Some(image) => {
+                commands.entity(camera).insert(Skybox {
+                    image: image.clone(),
+                    brightness: 1000.0,
+                    ..default()
+                });
+            }
+            None => {
+                commands.entity(camera).remove::<Skybox>();
+            }
+        }
+    }
+}
+
+/// Thickens, thins or clears the fog, which fades into the background or,
+/// under a skybox, into the horizon
+///
+/// The flat view has no fog, as everything in it is as far from the camera.
+pub fn apply_fog(
+    mut commands: Commands,
+    lighting: Res<Lighting>,
+    layout: Res<LayoutSettings>,
+    clear_color: Res<ClearColor>,
+    camera_query: Query<Entity, With<Camera3d>>,
+) {
+    let color = if lighting.skybox {
+        lighting.preset.sky()[1].into()
+    } else {
+        clear_color.0
+    };
+    for camera in &camera_query {
+        if lighting.fog > 0.0 && !layout.flatten {
+            commands.entity(camera).insert(DistanceFog {
+                color,
+                falloff: FogFalloff::Exponential {
+                    density: lighting.fog,
+                },
+                ..default()
+            });
+        } else {
+            commands.entity(camera).remove::<DistanceFog>();
+        }
+    }
+}
+
+/// Whether anything the fog depends on changed
+pub fn fog_changed(
+    lighting: Res<Lighting>,
+    layout: Res<LayoutSettings>,
+    clear_color: Res<ClearColor>,
+) -> bool {
+    lighting.is_changed() || layout.is_changed() || clear_color.is_changed()
+}
+
+/// Builds a cubemap fading from the ground color through the horizon to the zenith
+fn sky_image([zenith, horizon, ground]: [Srgba; 3]) -> Image {
+    let size = SKY_FACE_SIZE;
+    let mut data = Vec::with_capacity((size * size * 6 * 4) as usize);
+    for face in 0..6 {
+        for row in 0..size {
+            for column in 0..size {
+                #[allow(clippy::cast_precision_loss)]
+                let (u, v) = (
+                    ((column as f32 + 0.5) / size as f32).mul_add(2.0, -1.0),
+                    ((row as f32 + 0.5) / size as f32).mul_add(2.0, -1.0),
+                );
+                let color = sky_color(face_direction(face, u, v).y, zenith, horizon, ground);
+                data.extend_from_slice(&color.to_u8_array());
+            }
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d {
+            width: size,
+            height: size * 6,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.reinterpret_stacked_2d_as_array(6);
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        ..default()
+    });
+    image
+}
+
+/// Direction through a point on a cubemap face, in the +X, -X, +Y, -Y, +Z, -Z order
+fn face_direction(face: u32, u: f32, v: f32) -> Vec3 {
+    match face {
+        0 => Vec3::new(1.0, -v, -u),
+        1 => Vec3::new(-1.0, -v, u),
+        2 => Vec3::new(u, 1.0, v),
+        3 => Vec3::new(u, -1.0, -v),
+        4 => Vec3::new(u, -v, 1.0),
+        _ => Vec3::new(-u, -v, -1.0),
+    }
+    .normalize()
+}
+
+fn sky_color(height: f32, zenith: Srgba, horizon: Srgba, ground: Srgba) -> Srgba {
+    if height >= 0.0 {
+        horizon.mix(&zenith, height.sqrt())
+    } else {
+        horizon.mix(&ground, (-height * 4.0).min(1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lighting_preset_from_str() {
+        assert_eq!("Outdoor".parse(), Ok(LightingPreset::Outdoor));
+        assert!("disco".parse::<LightingPreset>().is_err());
+    }
+
+    #[test]
+    fn test_sky_image_is_a_cubemap() {
+        let image = sky_image(LightingPreset::Outdoor.sky());
+        assert_eq!(image.texture_descriptor.size.depth_or_array_layers, 6);
+
+        // Straight up is the zenith color, straight down the ground color
+        let [zenith, horizon, ground] = LightingPreset::Outdoor.sky();
+        assert_eq!(sky_color(1.0, zenith, horizon, ground), zenith);
+        assert_eq!(sky_color(-1.0, zenith, horizon, ground), ground);
+    }
+}
@@ -0,0 +1,328 @@
+use crate::alerts::AlertsPlugin;
+use crate::captions::ShowCaption;
+use crate::changes::ChangesPlugin;
+use crate::diagnostics::EVENT_PROCESSING_TIME;
+use crate::events::GraphEvent;
+use crate::gauges::GaugesPlugin;
+use crate::graph_state::GraphState;
+use crate::sources::{GraphEventSource, SourceError};
+use crate::staleness::{NodeActivity, StalenessPlugin};
+use bevy::diagnostic::{DiagnosticMeasurement, DiagnosticsStore};
+use bevy::prelude::*;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Instant;
+
+/// Channel that live sources push their events into
+///
+/// Any number of sources can share it, and new ones can be subscribed while
+/// the app is running.
+#[derive(Resource)]
+pub struct LiveEvents {
+    sender: Sender<GraphEvent>,
+    receiver: Mutex<Receiver<GraphEvent>>,
+}
+
+impl LiveEvents {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+        }
+    }
+
+    /// Returns a sender for pushing events from outside a source
+    #[allow(dead_code)] // Used by crates that embed the viewer
+    pub fn sender(&self) -> Sender<GraphEvent> {
+        self.sender.clone()
+    }
+
+    /// Starts a live source delivering events into this channel
+    pub fn subscribe(&self, source: &dyn GraphEventSource) -> Result<(), SourceError> {
+        source.subscribe(self.sender.clone())
+    }
+}
+
+impl Default for LiveEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Live updates and what they change: animating added, removed and updated
+/// nodes, fading quiet ones and showing their metrics and alert states
+pub struct LivePlugin;
+
+impl Plugin for LivePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LiveStatus>().add_plugins((
+            AlertsPlugin,
+            ChangesPlugin,
+            GaugesPlugin,
+            StalenessPlugin,
+        ));
+    }
+}
+
+/// How long a live source may go quiet before it is reported as idle
+const IDLE_AFTER_SECS: f32 = 5.0;
+
+/// Most events applied in one frame; the rest wait in the backlog so a flood
+/// doesn't stall rendering
+const MAX_EVENTS_PER_FRAME: usize = 1000;
+
+/// Names of the subscribed live sources and how busy they are, for the status bar
+#[derive(Resource, Default)]
+pub struct LiveStatus {
+    pub sources: Vec<&'static str>,
+    /// Events received per second, measured over the last full second
+    pub events_per_sec: f32,
+    /// App time in seconds when the last event arrived
+    pub last_event: Option<f32>,
+    /// Events received but not yet applied
+    pub backlog: usize,
+    window_start: f32,
+    window_count: usize,
+}
+
+impl LiveStatus {
+    /// Counts events that arrived at app time `now`
+    pub fn record(&mut self, count: usize, now: f32) {
+        if count > 0 {
+            self.last_event = Some(now);
+        }
+        self.window_count += count;
+
+        let elapsed = now - self.window_start;
+        if elapsed >= 1.0 {
+            #[allow(clippy::cast_precision_loss)] // Event counts are far below f32 precision
+            let count = self.window_count as f32;
+            self.events_per_sec = count / elapsed;
+            self.window_start = now;
+            self.window_count = 0;
+        }
+    }
+
+    /// Short description of the connection at app time `now`
+    pub fn connection_state(&self, now: f32) -> &'static str {
+        match self.last_event {
+            None => "waiting for events",
+            Some(last) if now - last > IDLE_AFTER_SECS => "idle",
+            Some(_) => "receiving",
+        }
+    }
+}
+
+/// Drops queued events whose effect a later event overrides
+///
+/// Anything before a `Clear`, anything about a node or edge that is later
+/// removed, and updates that are later replaced leave the final graph the
+/// same whether they are applied or not. Batch markers and captions are always
+/// kept.
+pub fn coalesce_events(events: &mut VecDeque<GraphEvent>) {
+    let keep: Vec<bool> = {
+        let mut cleared = false;
+        let mut removed_nodes = HashSet::new();
+        let mut removed_edges = HashSet::new();
+        let mut updated_nodes = HashSet::new();
+        let mut updated_edges = HashSet::new();
+        let mut set_metrics = HashSet::new();
+        let mut set_edge_metrics = HashSet::new();
+        let mut set_states = HashSet::new();
+        let mut keep: Vec<bool> = events
+            .iter()
+            .rev()
+            .map(|event| match event {
+                GraphEvent::ShowCaption { .. } | GraphEvent::BatchStart | GraphEvent::BatchEnd => {
+                    true
+                }
+                _ if cleared => false,
+                GraphEvent::Clear => {
+                    cleared = true;
+                    true
+                }
+                GraphEvent::RemoveNode { id } => removed_nodes.insert(id.as_str()),
+                GraphEvent::AddNode { id, .. } => !removed_nodes.contains(id.as_str()),
+                GraphEvent::UpdateNode { id, .. } => {
+                    !removed_nodes.contains(id.as_str()) && updated_nodes.insert(id.as_str())
+                }
+                GraphEvent::SetNodeMetric { id, key, .. } => {
+                    !removed_nodes.contains(id.as_str())
+                        && set_metrics.insert((id.as_str(), key.as_str()))
+                }
+                GraphEvent::SetNodeState { id, .. } => {
+                    !removed_nodes.contains(id.as_str()) && set_states.insert(id.as_str())
+                }
+                GraphEvent::SetEdgeMetric { from, to, key, .. } => {
+                    !removed_nodes.contains(from.as_str())
+                        && !removed_nodes.contains(to.as_str())
+                        && !removed_edges.contains(&(from.as_str(), to.as_str()))
+                        && set_edge_metrics.insert((from.as_str(), to.as_str(), key.as_str()))
+                }
+                GraphEvent::AddEdge { from, to }
+                | GraphEvent::AddRichEdge { from, to, .. }
+                | GraphEvent::UpdateEdge { from, to, .. }
+                | GraphEvent::RemoveEdge { from, to } => {
+                    let edge = (from.as_str(), to.as_str());
+                    if removed_nodes.contains(edge.0)
+                        || removed_nodes.contains(edge.1)
+                        || removed_edges.contains(&edge)
+                    {
+                        false
+                    } else if matches!(event, GraphEvent::RemoveEdge { .. }) {
+                        removed_edges.insert(edge)
+                    } else if matches!(event, GraphEvent::UpdateEdge { .. }) {
+                        updated_edges.insert(edge)
+                    } else {
+                        true
+                    }
+                }
+            })
+            .collect();
+        keep.reverse();
+        keep
+    };
+    let mut keep = keep.into_iter();
+    events.retain(|_| keep.next().unwrap_or(true));
+}
+
+/// Applies events received since the last frame to the graph state, at most
+/// `MAX_EVENTS_PER_FRAME` at a time, and shows the captions among them
+#[allow(clippy::too_many_arguments)]
+pub fn apply_live_events(
+    live_events: Res<LiveEvents>,
+    time: Res<Time>,
+    mut backlog: Local<VecDeque<GraphEvent>>,
+    mut status: ResMut<LiveStatus>,
+    mut activity: ResMut<NodeActivity>,
+    mut graph_state: ResMut<GraphState>,
+    mut captions: EventWriter<ShowCaption>,
+    diagnostics: Option<ResMut<DiagnosticsStore>>,
+) {
+    let started = Instant::now();
+    let Ok(receiver) = live_events.receiver.lock() else {
+        return;
+    };
+
+    let queued = backlog.len();
+    backlog.extend(receiver.try_iter());
+    let arrived = backlog.len() - queued;
+    status.record(arrived, time.elapsed_secs());
+    if arrived > 0 {
+        coalesce_events(&mut backlog);
+    }
+
+    // Only touch the state (and trigger a scene refresh) when something is waiting
+    if !backlog.is_empty() {
+        let count = backlog.len().min(MAX_EVENTS_PER_FRAME);
+        let events: Vec<_> = backlog.drain(..count).collect();
+        for event in &events {
+            activity.record(event, time.elapsed_secs());
+        }
+        captions.write_batch(events.iter().filter_map(ShowCaption::from_event));
+        graph_state.process_events(events);
+    }
+    status.backlog = backlog.len();
+    // Apps without bevy's diagnostics just don't record the timing
+    if let Some(timing) =
+        diagnostics.and_then(|store| store.into_inner().get_mut(&EVENT_PROCESSING_TIME))
+    {
+        timing.add_measurement(DiagnosticMeasurement {
+            time: Instant::now(),
+            value: started.elapsed().as_secs_f64() * 1000.0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_live_status_rates_and_state() {
+        let mut status = LiveStatus::default();
+        assert_eq!(status.connection_state(0.0), "waiting for events");
+
+        status.record(3, 0.5);
+        status.record(2, 1.0);
+        assert!((status.events_per_sec - 5.0).abs() < f32::EPSILON);
+        assert_eq!(status.connection_state(2.0), "receiving");
+
+        status.record(0, 2.0);
+        assert!(status.events_per_sec.abs() < f32::EPSILON);
+        assert_eq!(status.connection_state(10.0), "idle");
+    }
+
+    #[test]
+    fn test_coalesce_drops_overridden_events() {
+        let node = |name: &str| crate::events::EventNodeInfo {
+            name: name.to_string(),
+            node_type: None,
+            level: 0,
+            ..Default::default()
+        };
+        let add = |id: &str| GraphEvent::AddNode {
+            id: id.to_string(),
+            info: node(id),
+        };
+        let update = |id: &str, name: &str| GraphEvent::UpdateNode {
+            id: id.to_string(),
+            info: node(name),
+        };
+        let edge = |from: &str, to: &str| GraphEvent::AddEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+        let events = vec![
+            add("gone"),
+            add("a"),
+            add("b"),
+            edge("a", "gone"),
+            update("a", "first"),
+            edge("a", "b"),
+            GraphEvent::BatchStart,
+            update("a", "second"),
+            GraphEvent::RemoveNode {
+                id: "gone".to_string(),
+            },
+            GraphEvent::BatchEnd,
+        ];
+
+        let mut coalesced: VecDeque<_> = events.clone().into();
+        coalesce_events(&mut coalesced);
+        assert_eq!(
+            coalesced
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                add("a"),
+                add("b"),
+                edge("a", "b"),
+                GraphEvent::BatchStart,
+                update("a", "second"),
+                GraphEvent::RemoveNode {
+                    id: "gone".to_string(),
+                },
+                GraphEvent::BatchEnd,
+            ]
+            .map(|event| event.to_string())
+        );
+
+        // Applying either gives the same graph
+        let mut full = GraphState::new();
+        full.process_events(events);
+        let mut merged = GraphState::new();
+        merged.process_events(coalesced.into());
+        assert_eq!(full.node_count(), merged.node_count());
+        assert_eq!(full.edge_count(), merged.edge_count());
+        assert_eq!(merged.get_node("a").unwrap().name, "second");
+
+        // Nothing before a clear survives
+        let mut cleared: VecDeque<_> = vec![add("a"), GraphEvent::Clear, add("b")].into();
+        coalesce_events(&mut cleared);
+        assert_eq!(cleared.len(), 2);
+    }
+}
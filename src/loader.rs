@@ -0,0 +1,429 @@
+use crate::graph_state::GraphState;
+use crate::history::EditHistory;
+use crate::includes;
+use crate::input;
+use crate::keybindings::{Action, KeyBindings};
+use crate::merge::{self, MergeMode, MergedInputs};
+use crate::sources::{GraphEventSource, LOW_CONFIDENCE, Progress, SourceError, SourceRegistry};
+use bevy::prelude::*;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often a watched file's modification time is checked
+const WATCH_INTERVAL_SECS: f32 = 0.5;
+
+/// Inputs smaller than this load too quickly to need a progress line
+const PROGRESS_MIN_BYTES: usize = 16 * 1024 * 1024;
+
+/// How often the progress line is redrawn
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Where the diagram was read from, so it can be loaded again after edits
+#[derive(Resource)]
+pub struct InputSource {
+    /// File path or URL; `None` for stdin, which can't be re-read
+    pub location: Option<String>,
+    /// Format given with `--format`, or `None` to detect it on every load
+    pub format: Option<String>,
+    /// Reload automatically when the file changes on disk
+    pub watch: bool,
+    /// Format of the last successful load, given or detected
+    pub loaded_format: Option<&'static str>,
+    /// All the inputs, `location` first, when several are merged into the graph
+    pub merged: Option<MergedInputs>,
+    /// Files the input includes, which are watched along with it
+    included: Vec<String>,
+    last_modified: Option<SystemTime>,
+    watch_timer: Timer,
+}
+
+impl InputSource {
+    pub fn new(location: Option<String>, format: Option<String>, watch: bool) -> Self {
+        let last_modified = location.as_deref().and_then(modified_time);
+        Self {
+            location,
+            format,
+            watch,
+            loaded_format: None,
+            merged: None,
+            included: Vec::new(),
+            last_modified,
+            watch_timer: Timer::from_seconds(WATCH_INTERVAL_SECS, TimerMode::Repeating),
+        }
+    }
+
+    /// Reads `merged` instead of the one location, which is its first input
+    #[must_use]
+    pub fn merging(mut self, merged: MergedInputs) -> Self {
+        self.location = merged.inputs.first().map(|(location, _)| location.clone());
+        self.merged = Some(merged);
+        self.last_modified = self.modified();
+        self
+    }
+
+    /// Whether the input can be read again (files and URLs, but not stdin)
+    pub const fn can_reload(&self) -> bool {
+        self.location.is_some()
+    }
+
+    /// Watches the files the last load included, as well as the input
+    pub fn set_included(&mut self, files: Vec<String>) {
+        self.included = files;
+        self.last_modified = self.modified();
+    }
+
+    /// When the input last changed on disk, the latest of merged and
+    /// included files
+    fn modified(&self) -> Option<SystemTime> {
+        let merged = self
+            .merged
+            .iter()
+            .flat_map(|merged| merged.inputs.iter().map(|(location, _)| location));
+        self.location
+            .iter()
+            .chain(merged)
+            .chain(&self.included)
+            .filter_map(|location| modified_time(location))
+            .max()
+    }
+}
+
+/// The most recent load failure, shown on screen until a load succeeds
+#[derive(Resource, Default)]
+pub struct LoadError(pub Option<String>);
+
+/// Parses diagram content in the given (or detected) format into a graph,
+/// returning it along with the name of the format that was used
+#[allow(dead_code)] // Part of the library API
+pub fn load_graph(
+    registry: &SourceRegistry,
+    content: &str,
+    format: Option<&str>,
+) -> Result<(GraphState, &'static str), SourceError> {
+    load_graph_with_progress(registry, content, format, &mut |_| {})
+}
+
+/// Like `load_graph`, passing on the source's reports of how far it has got
+pub fn load_graph_with_progress(
+    registry: &SourceRegistry,
+    content: &str,
+    format: Option<&str>,
+    progress: &mut dyn FnMut(Progress),
+) -> Result<(GraphState, &'static str), SourceError> {
+    let (source, format) = source_for_content(registry, content, format)?;
+
+    let mut graph_state = GraphState::new();
+    graph_state.process_events(source.events_with_progress(progress)?);
+    graph_state.set_layout_hints(source.layout_hints());
+    graph_state.set_source_lines(source.source_lines());
+    Ok((graph_state, format))
+}
+
+/// Like `load_graph_with_progress` for content read from `location` (`None`
+/// for stdin), loading the graphs it includes along with it
+///
+/// The files read for includes are returned too, so they can be watched.
+pub fn load_input(
+    registry: &SourceRegistry,
+    location: Option<&str>,
+    content: String,
+    format: Option<&str>,
+    progress: &mut dyn FnMut(Progress),
+) -> Result<(GraphState, &'static str, Vec<String>), SourceError> {
+    let included = includes::resolve(location, content, &mut read_include)
+        .map_err(SourceError::InvalidInput)?;
+    let (graph_state, format) = if let [(_, content)] = included.documents.as_slice() {
+        load_graph_with_progress(registry, content, format, progress)?
+    } else {
+        let inputs: Vec<_> = included
+            .documents
+            .iter()
+            .enumerate()
+            .map(|(index, (name, content))| {
+                (
+                    name.as_str(),
+                    content.as_str(),
+                    format.filter(|_| index == 0),
+                )
+            })
+            .collect();
+        load_merged(registry, &inputs, MergeMode::Unify).map_err(SourceError::InvalidInput)?
+    };
+    Ok((graph_state, format, included.files))
+}
+
+fn read_include(location: &str) -> Result<String, String> {
+    input::read_location(location).map_err(|e| e.to_string())
+}
+
+/// Parses several inputs, each a location, its content and its format if
+/// known, into one graph, returning the format of the first input
+///
+/// The layout hints and source lines are the first input's, as that's the
+/// file the graph is saved beside and opened in an editor from.
+pub fn load_merged(
+    registry: &SourceRegistry,
+    inputs: &[(&str, &str, Option<&str>)],
+    mode: MergeMode,
+) -> Result<(GraphState, &'static str), String> {
+    let names = merge::namespaces(inputs.iter().map(|(location, ..)| *location));
+    let all: HashSet<&str> = names.iter().map(String::as_str).collect();
+    let mut events = Vec::new();
+    let mut first = None;
+    for ((location, content, format), namespace) in inputs.iter().zip(&names) {
+        let parsed = source_for_content(registry, content, *format)
+            .and_then(|(source, format)| Ok((source.events()?, source, format)));
+        let (source_events, source, format) = parsed.map_err(|e| format!("{location}: {e}"))?;
+        if first.is_none() {
+            let mut lines = source.source_lines();
+            if mode == MergeMode::Namespace {
+                let rename = |id: String| merge::namespaced(&id, namespace, &all);
+                lines.nodes = lines
+                    .nodes
+                    .into_iter()
+                    .map(|(id, line)| (rename(id), line))
+                    .collect();
+                lines.edges = lines
+                    .edges
+                    .into_iter()
+                    .map(|((from, to), line)| ((rename(from), rename(to)), line))
+                    .collect();
+            }
+            first = Some((source.layout_hints(), lines, format));
+        }
+        events.push((namespace.clone(), source_events));
+    }
+    let (hints, lines, format) = first.ok_or("there are no inputs to merge")?;
+
+    let mut graph_state = GraphState::new();
+    graph_state.process_events(merge::merge_events(events, mode));
+    graph_state.set_layout_hints(hints);
+    graph_state.set_source_lines(lines);
+    Ok((graph_state, format))
+}
+
+/// Reads and merges the inputs of `merged`, and the graphs they include,
+/// into one graph, returning the files read for includes as well
+pub fn load_merged_inputs(
+    registry: &SourceRegistry,
+    merged: &MergedInputs,
+) -> Result<(GraphState, &'static str, Vec<String>), String> {
+    let mut documents = Vec::new();
+    let mut files = Vec::new();
+    for (location, format) in &merged.inputs {
+        let content = read_include(location).map_err(|e| format!("{location}: {e}"))?;
+        let included = includes::resolve(Some(location), content, &mut read_include)?;
+        files.extend(included.files);
+        for (index, (name, content)) in included.documents.into_iter().enumerate() {
+            documents.push((name, content, format.as_deref().filter(|_| index == 0)));
+        }
+    }
+    let inputs: Vec<_> = documents
+        .iter()
+        .map(|(name, content, format)| (name.as_str(), content.as_str(), *format))
+        .collect();
+    let (graph_state, format) = load_merged(registry, &inputs, merged.mode)?;
+    Ok((graph_state, format, files))
+}
+
+/// The source for `content` in the given format, or the one it's detected as
+fn source_for_content(
+    registry: &SourceRegistry,
+    content: &str,
+    format: Option<&str>,
+) -> Result<(Box<dyn GraphEventSource>, &'static str), SourceError> {
+    let format = if let Some(format) = format {
+        registry.get(format).ok_or(SourceError::UnknownFormat)?.name
+    } else {
+        let detection = registry.detect(content)?;
+        if detection.confidence < LOW_CONFIDENCE {
+            eprintln!(
+                "Warning: input looks like {} but detection is uncertain; use --format to override",
+                detection.format
+            );
+        }
+        detection.format
+    };
+
+    Ok((registry.source_for(format, content)?, format))
+}
+
+/// Shows how far loading a big input has got, on one line of stderr
+///
+/// Small inputs and output that isn't a terminal get no progress line.
+pub struct ProgressLine {
+    name: String,
+    total_bytes: usize,
+    enabled: bool,
+    last_drawn: Option<Instant>,
+}
+
+impl ProgressLine {
+    pub fn new(name: &str, total_bytes: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            total_bytes,
+            enabled: total_bytes >= PROGRESS_MIN_BYTES && io::stderr().is_terminal(),
+            last_drawn: None,
+        }
+    }
+
+    /// Redraws the line, at most every `PROGRESS_INTERVAL`
+    pub fn report(&mut self, progress: Progress) {
+        let now = Instant::now();
+        if !self.enabled
+            || self
+                .last_drawn
+                .is_some_and(|drawn| now - drawn < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+        self.last_drawn = Some(now);
+        eprint!("\r{}", self.message(progress));
+    }
+
+    fn message(&self, progress: Progress) -> String {
+        let percent = progress.bytes.min(self.total_bytes) * 100 / self.total_bytes.max(1);
+        format!(
+            "Loading {}: {percent}% of {} MB, {} events",
+            self.name,
+            self.total_bytes / (1024 * 1024),
+            progress.events
+        )
+    }
+
+    /// Clears the line once loading is over
+    pub fn finish(&self) {
+        if self.last_drawn.is_some() {
+            eprint!("\r\x1b[2K");
+        }
+    }
+}
+
+/// Re-reads the input when F5 is pressed or a watched file changes.
+/// On failure the previous graph stays on screen alongside the error.
+#[allow(clippy::too_many_arguments)]
+pub fn reload_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    bindings: Res<KeyBindings>,
+    registry: Res<SourceRegistry>,
+    mut input_source: ResMut<InputSource>,
+    mut graph_state: ResMut<GraphState>,
+    mut load_error: ResMut<LoadError>,
+    mut history: ResMut<EditHistory>,
+) {
+    let Some(location) = input_source.location.clone() else {
+        return;
+    };
+
+    let mut reload = bindings.just_pressed(Action::Reload, &keyboard_input);
+
+    if input_source.watch && !input::is_url(&location) {
+        input_source.watch_timer.tick(time.delta());
+        if input_source.watch_timer.just_finished() {
+            let modified = input_source.modified();
+            if modified != input_source.last_modified {
+                input_source.last_modified = modified;
+                reload = true;
+            }
+        }
+    }
+
+    if !reload {
+        return;
+    }
+
+    let result = input_source.merged.as_ref().map_or_else(
+        || {
+            input::read_location(&location)
+                .map_err(|e| e.to_string())
+                .and_then(|content| {
+                    let mut progress = ProgressLine::new(&location, content.len());
+                    let loaded = load_input(
+                        &registry,
+                        Some(&location),
+                        content,
+                        input_source.format.as_deref(),
+                        &mut |p| progress.report(p),
+                    );
+                    progress.finish();
+                    loaded.map_err(|e| e.to_string())
+                })
+        },
+        |merged| load_merged_inputs(&registry, merged),
+    );
+
+    match result {
+        Ok((mut state, format, included)) => {
+            state.set_type_rules(graph_state.type_rules().clone());
+            *graph_state = state;
+            // Recorded edits refer to the graph that was just replaced
+            history.clear();
+            input_source.loaded_format = Some(format);
+            input_source.set_included(included);
+            load_error.0 = None;
+        }
+        Err(message) => {
+            eprintln!("Error reloading '{location}': {message}");
+            load_error.0 = Some(message);
+        }
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_graph_reports_position() {
+        let registry = SourceRegistry::default();
+        let result = load_graph(&registry, "digraph {\n  A -> B;\n", Some("dot"));
+        assert!(matches!(
+            result,
+            Err(SourceError::SyntaxError {
+                line: 1,
+                column: 9,
+                ..
+            })
+        ));
+
+        let (state, format) = load_graph(&registry, "digraph {\n  A -> B;\n}\n", None).unwrap();
+        assert_eq!(state.node_count(), 2);
+        assert_eq!(format, "dot");
+    }
+
+    #[test]
+    fn test_sources_report_progress() {
+        let content = "*Vertices 3\n1 \"a\"\n2 \"b\"\n*Arcs\n1 2\n2 3\n";
+        let mut reports = Vec::new();
+        let (state, _) =
+            load_graph_with_progress(&SourceRegistry::default(), content, None, &mut |progress| {
+                reports.push(progress);
+            })
+            .unwrap();
+        assert_eq!(state.node_count(), 3);
+        assert_eq!(reports.len(), content.lines().count());
+        assert!(reports.windows(2).all(|w| w[0].bytes < w[1].bytes));
+        let last = reports.last().unwrap();
+        assert_eq!(last.bytes, content.len());
+        // Reported before the last line: BatchStart, two nodes and an edge
+        assert_eq!(last.events, 4);
+
+        let line = ProgressLine::new("big.net", 300 * 1024 * 1024);
+        let halfway = Progress {
+            bytes: 150 * 1024 * 1024,
+            events: 1234,
+        };
+        assert_eq!(
+            line.message(halfway),
+            "Loading big.net: 50% of 300 MB, 1234 events"
+        );
+    }
+}
@@ -3,37 +3,366 @@
 #![allow(clippy::multiple_crate_versions)] // Bevy dependencies have multiple versions
 
 use bevy::prelude::*;
-use clap::Parser;
-use std::io::{self, IsTerminal, Read};
+use bevy::window::WindowResolution;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::io::{self, IsTerminal};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+mod accessibility;
+mod aggregate;
+mod alerts;
+mod antialiasing;
+#[cfg(feature = "audio")]
+mod audio;
 mod camera;
+mod captions;
+mod capture;
+mod changes;
+mod clipboard;
+mod config;
+mod cycles;
+mod describe;
+mod diagnostics;
+mod edge_lines;
+mod edit;
+mod editor;
 mod events;
+mod export;
+mod flash;
+mod fonts;
+mod gauges;
+mod generate;
 mod graph_state;
+mod grid;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod highlight;
+mod history;
+mod hover;
+mod includes;
+mod input;
+mod keybindings;
+mod kiosk;
+mod label_atlas;
+mod layout;
+mod legend;
+mod lighting;
+mod live;
+mod loader;
+mod merge;
+mod merged_edges;
+mod metrics;
+mod models;
+mod notes;
+mod palette;
+mod plugin;
+mod quality;
+mod reachability;
+mod scene_build;
+mod screen_clusters;
+mod scripting;
 mod search;
+mod selection;
+mod settings;
+mod sidecar;
 mod sources;
+mod staleness;
+mod status;
+mod theme;
+mod timeline;
+mod tour;
+mod type_rules;
 mod types;
 mod ui;
+mod validate;
 mod visualization;
+mod window;
+mod xray;
 
-use camera::{CameraPlugin, setup_camera};
+use accessibility::{Accessibility, UI_SCALE_RANGE};
+use aggregate::Aggregation;
+use antialiasing::AntiAliasing;
+use capture::CaptureRequest;
+use config::Config;
+use edge_lines::EdgeLines;
+use editor::EditorCommand;
+use export::OutputFormat;
+use flash::FlashFeed;
+use fonts::FontStack;
+use gauges::GaugeBindings;
+use generate::GraphModel;
 use graph_state::GraphState;
-use search::{
-    apply_highlight_visuals, handle_search_input, setup_search_ui, toggle_search,
-    update_node_highlighting,
+use keybindings::{Action, KeyBindings};
+use kiosk::Kiosk;
+use lighting::{Lighting, LightingPreset};
+use loader::{InputSource, LoadError, ProgressLine, load_input, load_merged_inputs};
+use merge::{MergeMode, MergedInputs};
+use models::NodeModels;
+use palette::Palette;
+use plugin::{DotspaceAppExt, DotspacePlugin};
+use quality::Quality;
+use settings::SavedSettings;
+use sidecar::SidecarFile;
+use sources::diff::DiffSource;
+use sources::mqtt::MqttSource;
+use sources::otel::OtelSource;
+use sources::prometheus::PrometheusSource;
+use sources::session::{SessionSource, parse_speed};
+use sources::{SourceError, SourceRegistry};
+use staleness::Staleness;
+use theme::Theme;
+use tour::Tour;
+use type_rules::TypeRules;
+use types::{
+    CameraSettings, GridSettings, GroupBy, LabelSettings, LayoutMode, LayoutSettings, LevelSource,
+    ReducedMotion, ViewSettings,
 };
-use sources::dot::DotSource;
-use sources::plantuml::PlantUMLSource;
-use sources::{GraphEventSource, detect_format};
-use types::{CameraSettings, DotContent, LabelSettings, SearchState};
-use ui::{create_node_labels, setup_ui, toggle_label_visibility, update_node_label_positions};
-use visualization::{create_graph_visualization, update_edge_positions};
+use validate::Severity;
+use window::WindowPlacement;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Explore your Graphviz dot files in interactive 3D space", long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
-    /// Optional dot file path. If not provided, reads from stdin.
-    file: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Apply this profile from the config files on top of their defaults
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// With no subcommand, `dotspace FILE` views the file
+    #[command(flatten)]
+    view: ViewArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Open a diagram, or a live source, in the viewer (the default)
+    View(ViewArgs),
+
+    /// Open a diagram, save a screenshot of it once the layout settles, and exit
+    Render {
+        /// Diagram file path or http(s) URL; reads stdin if not given
+        file: Option<String>,
+
+        /// Image to write; its extension picks the format (png, jpg, ...)
+        #[arg(short, long, value_name = "IMAGE")]
+        output: PathBuf,
+
+        /// Diagram format (see --list-formats); `auto` detects it from the content
+        #[arg(long, default_value = "auto")]
+        format: String,
+
+        /// Image width in pixels
+        #[arg(long, default_value = "1920")]
+        width: u32,
+
+        /// Image height in pixels
+        #[arg(long, default_value = "1080")]
+        height: u32,
+
+        /// Seconds to let the scene settle before capturing it
+        #[arg(long, value_name = "SECS", default_value = "2.0")]
+        delay: f32,
+
+        #[command(flatten)]
+        display: DisplayArgs,
+    },
+
+    /// Show one diagram turning into another: added nodes and edges grow in,
+    /// removed ones shrink away and changed nodes pulse
+    Diff {
+        /// The diagram as it was
+        old: String,
+
+        /// The diagram as it is now
+        new: String,
+
+        /// Format of both diagrams (see --list-formats); `auto` detects each
+        #[arg(long, default_value = "auto")]
+        format: String,
+
+        /// Seconds to show the old diagram before changing it
+        #[arg(long, value_name = "SECS", default_value = "1.5")]
+        delay: f32,
+
+        #[command(flatten)]
+        display: DisplayArgs,
+    },
+
+    /// Convert a diagram to dot, json (node-link) or graphml without opening a window
+    Convert {
+        /// Diagram file path or http(s) URL; reads stdin if not given
+        file: Option<String>,
+
+        /// File to write; writes to stdout if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Format to read (see --list-formats); `auto` detects it from the content
+        #[arg(long, default_value = "auto")]
+        from: String,
+
+        /// Format to write: dot, json or graphml; defaults to the output's extension
+        #[arg(long)]
+        to: Option<OutputFormat>,
+    },
+
+    /// Check diagrams for problems without opening a window, exiting nonzero
+    /// if any has errors
+    Validate {
+        /// Diagram files or http(s) URLs; reads stdin if none are given
+        files: Vec<String>,
+
+        /// Diagram format (see --list-formats); `auto` detects each
+        #[arg(long, default_value = "auto")]
+        format: String,
+
+        /// Print the reports as a JSON array
+        #[arg(long)]
+        json: bool,
+
+        /// Fail on warnings as well as errors
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+
+    /// Write a synthetic DOT graph, for trying the viewer at scale
+    Generate {
+        /// Number of nodes
+        #[arg(long, default_value_t = 1000)]
+        nodes: usize,
+
+        /// Number of edges, made up with random ones if the model has fewer
+        #[arg(long, default_value_t = 3000)]
+        edges: usize,
+
+        /// Shape of the graph: barabasi (scale-free, with hubs), grid or tree
+        #[arg(long, default_value = "barabasi")]
+        model: GraphModel,
+
+        /// Seed for the random choices; the same seed gives the same graph
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
+        /// File to write; writes to stdout if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Print the JSON schema of the graph events read from sessions, MQTT,
+    /// scripts and the gRPC API
+    Schema,
+
+    /// Replay a session recorded with Ctrl+Shift+S, with its original timing
+    Replay {
+        /// Session recording file or http(s) URL
+        session: String,
+
+        /// How much faster than recorded to replay, e.g. 4x
+        #[arg(long, default_value = "1x", value_parser = parse_speed)]
+        speed: f32,
+
+        #[command(flatten)]
+        display: DisplayArgs,
+    },
+}
+
+/// What to view and where its updates come from
+#[derive(clap::Args, Debug)]
+#[allow(clippy::struct_excessive_bools)] // Each bool is an independent CLI flag
+struct ViewArgs {
+    /// Optional diagram file paths or http(s) URLs, optionally gzip-compressed;
+    /// several are shown as one graph with --merge. If not provided, reads
+    /// from stdin.
+    #[arg(value_name = "FILE")]
+    files: Vec<String>,
+
+    /// Diagram format (see --list-formats); `auto` detects it from the content
+    #[arg(long, default_value = "auto")]
+    format: String,
+
+    /// Show the files as one graph: `unify` joins nodes with the same id
+    /// across files, `namespace` prefixes each file's ids with its name
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "unify"
+    )]
+    merge: Option<MergeMode>,
+
+    /// List the supported diagram formats and exit
+    #[arg(long)]
+    list_formats: bool,
+
+    /// Listen for OTLP/HTTP JSON trace exports on this address (e.g. 127.0.0.1:4318)
+    /// and build a live service graph instead of reading a file
+    #[arg(long, value_name = "ADDR")]
+    otlp: Option<SocketAddr>,
+
+    /// Subscribe to JSON graph events on this MQTT broker (host or host:port)
+    /// and stream the graph they build instead of reading a file
+    #[arg(long, value_name = "BROKER")]
+    mqtt: Option<String>,
+
+    /// MQTT topic filter to subscribe to; + and # wildcards are allowed
+    #[arg(long, value_name = "TOPIC", default_value = "dotspace/events")]
+    mqtt_topic: String,
+
+    /// Serve the gRPC control API on this address (e.g. 127.0.0.1:50051) so
+    /// other tools can send events and query nodes, selection and camera
+    #[cfg(feature = "grpc")]
+    #[arg(long, value_name = "ADDR")]
+    grpc: Option<SocketAddr>,
+
+    /// Play a short sound as live sources add or remove nodes or raise alerts
+    #[cfg(feature = "audio")]
+    #[arg(long)]
+    audio: bool,
+
+    /// Play only these cues, as KIND or KIND=VOLUME with a volume from 0 to 1
+    /// (repeatable; kinds are add, remove and alert)
+    #[cfg(feature = "audio")]
+    #[arg(long = "audio-cue", value_name = "KIND[=VOLUME]", value_parser = audio::parse_cue)]
+    audio_cues: Vec<(audio::Cue, f32)>,
+
+    /// Scrape the Prometheus endpoint named in this TOML mapping file and set
+    /// the node and edge metrics it maps
+    #[arg(long, value_name = "MAPPING")]
+    prometheus: Option<String>,
+
+    /// Light up the nodes named in lines read from this file, FIFO or
+    /// descriptor (e.g. /dev/fd/3), or from stdin with `-`
+    #[arg(long, value_name = "PATH")]
+    flash_from: Option<String>,
+
+    /// Fade nodes of live sources that get no events for this many seconds;
+    /// they are fully faded after twice as long
+    #[arg(long, value_name = "SECS")]
+    stale_after: Option<f32>,
+
+    /// Remove nodes once they have fully faded (needs --stale-after)
+    #[arg(long, requires = "stale_after")]
+    remove_stale: bool,
+
+    /// Reload the file whenever it changes on disk
+    #[arg(short, long)]
+    watch: bool,
+
+    #[command(flatten)]
+    display: DisplayArgs,
+}
 
+/// How the graph is drawn, shared by every command that opens the viewer
+#[derive(clap::Args, Debug)]
+#[allow(clippy::struct_excessive_bools)] // Each bool is an independent CLI flag
+struct DisplayArgs {
     /// Initial camera distance from center
     #[arg(short, long, default_value = "25.0")]
     distance: f32,
@@ -45,120 +374,971 @@ struct Args {
     /// Label visibility distance
     #[arg(short = 'v', long, default_value = "15.0")]
     label_distance: f32,
+
+    /// Characters a label line may take before it's cut short with an
+    /// ellipsis, or wrapped with --wrap-labels; 0 for no limit. Hovered and
+    /// selected nodes show their full label
+    #[arg(long, value_name = "CHARS", default_value_t = LabelSettings::DEFAULT_MAX_WIDTH)]
+    label_width: usize,
+
+    /// Wrap long labels at spaces onto a few lines instead of cutting them short
+    #[arg(long)]
+    wrap_labels: bool,
+
+    /// Draw labels from a texture atlas, as one mesh of quads, once the
+    /// scene has more nodes than this; 0 always draws them as text
+    #[arg(long, value_name = "NODES", default_value_t = LabelSettings::DEFAULT_ATLAS_ABOVE)]
+    atlas_labels_above: usize,
+
+    /// How far the camera pans per pixel dragged
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0)]
+    pan_sensitivity: f32,
+
+    /// How far the camera orbits per pixel dragged
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0)]
+    orbit_sensitivity: f32,
+
+    /// How far the camera zooms per scroll step
+    #[arg(long, value_name = "FACTOR", default_value_t = 0.5)]
+    zoom_sensitivity: f32,
+
+    /// How smoothly the camera eases into its moves, from 0 (not at all) to
+    /// just under 1
+    #[arg(long, value_name = "FACTOR", default_value_t = 0.8)]
+    smoothness: f32,
+
+    /// How far the camera can orbit over or under the graph
+    #[arg(long, value_name = "DEGREES", default_value_t = 80.0)]
+    pitch_limit: f32,
+
+    /// Seconds the camera keeps gliding after a drag; 0 stops it at once
+    #[arg(long, value_name = "SECS", default_value_t = 0.3)]
+    inertia: f32,
+
+    /// Color nodes by this live node metric, from blue (lowest) to red (highest)
+    #[arg(long, value_name = "KEY")]
+    metric_color: Option<String>,
+
+    /// Pulse nodes faster the higher their value of this live node metric
+    #[arg(long, value_name = "KEY")]
+    metric_pulse: Option<String>,
+
+    /// Send particles along edges, more and faster the higher this live edge metric
+    #[arg(long, value_name = "KEY")]
+    edge_flow: Option<String>,
+
+    /// UI theme: dark or light (press T to switch)
+    #[arg(long, default_value = "dark")]
+    theme: Theme,
+
+    /// Window background as a hex color (e.g. #1e1e2e), or `transparent`;
+    /// defaults to the theme's background
+    #[arg(long, value_name = "COLOR", value_parser = theme::parse_background)]
+    background: Option<Color>,
+
+    /// Light rig: studio, outdoor or dark
+    #[arg(long, default_value = "studio")]
+    lighting: LightingPreset,
+
+    /// Initial layout: hierarchical or topological (press O to switch)
+    #[arg(long, default_value = "hierarchical")]
+    layout: LayoutMode,
+
+    /// Split the layout into lanes by a node attribute: type, level or state
+    #[arg(long, value_name = "ATTR")]
+    group_by: Option<GroupBy>,
+
+    /// Where levels come from: auto (the `level` attribute, or depth in the
+    /// graph if no node has one), attr or none
+    #[arg(long, value_name = "SOURCE", default_value = "auto")]
+    levels: LevelSource,
+
+    /// Show graphs with more nodes than this as hubs and clusters (press U
+    /// to expand one); 0 always shows every node
+    #[arg(long, value_name = "NODES", default_value_t = aggregate::DEFAULT_THRESHOLD)]
+    aggregate_above: usize,
+
+    /// Draw all edges as lines in one mesh, cutting entities and draw calls
+    /// for huge static graphs; edges can't then be hovered or faded one by one
+    #[arg(long)]
+    merge_edges: bool,
+
+    /// Start with edges drawn as thin lines instead of tubes (W toggles)
+    #[arg(long)]
+    edge_lines: bool,
+
+    /// Node type colors: standard, or a palette safe for deuteranopia,
+    /// protanopia or tritanopia
+    #[arg(long, value_name = "PALETTE", default_value = "standard")]
+    palette: Palette,
+
+    /// Give every known node type a shape of its own, so types can be told
+    /// apart by shape rather than color
+    #[arg(long)]
+    distinct_shapes: bool,
+
+    /// Draw a sky matching the lighting instead of a flat background
+    #[arg(long)]
+    skybox: bool,
+
+    /// Fade far parts of the graph into the background, the more the higher
+    /// the density (e.g. 0.02); 0 for no fog
+    #[arg(long, value_name = "DENSITY", default_value_t = 0.0)]
+    fog: f32,
+
+    /// Multisampling of the 3D view: off, 2, 4 or 8 samples per pixel; some
+    /// GPUs only run with it off
+    #[arg(long, value_name = "SAMPLES", default_value = "4", value_parser = antialiasing::parse_msaa)]
+    msaa: Msaa,
+
+    /// Smooth jagged edges with temporal anti-aliasing instead of
+    /// multisampling, which calms the shimmer of thin edges
+    #[arg(long)]
+    taa: bool,
+
+    /// Let the lights cast shadows
+    #[arg(long)]
+    shadows: bool,
+
+    /// Preset for shadows, anti-aliasing, animations and the node counts at
+    /// which big graphs are simplified: low, medium or high; options given
+    /// on their own win over it
+    #[arg(long, value_name = "LEVEL", default_value = "medium")]
+    quality: Quality,
+
+    /// Leave out motion that only draws the eye: pulsing nodes, blinking
+    /// alerts and particles flowing along edges
+    #[arg(long)]
+    reduced_motion: bool,
+
+    /// Scale the overlays, settings windows and node labels by FACTOR, from
+    /// 0.5 to 3 (Ctrl+= and Ctrl+- change it)
+    #[arg(long, value_name = "FACTOR", default_value = "1", value_parser = accessibility::parse_ui_scale)]
+    ui_scale: f32,
+
+    /// Opaque panels, outlined labels that don't fade with distance and
+    /// thicker highlight outlines (Shift+T toggles)
+    #[arg(long)]
+    high_contrast: bool,
+
+    /// Draw nodes of a type with a glTF/GLB model, as TYPE=PATH (repeatable);
+    /// nodes keep their default shape if the model can't be loaded
+    #[arg(long = "model", value_name = "TYPE=PATH", value_parser = models::parse_model_mapping)]
+    models: Vec<(String, PathBuf)>,
+
+    /// Hide the reference grid under the graph (press G to toggle)
+    #[arg(long)]
+    no_grid: bool,
+
+    /// Show XYZ axes at the origin (press X to toggle)
+    #[arg(long)]
+    axes: bool,
+
+    /// Let the camera orbit under the ground, hiding the grid while it's
+    /// there, instead of stopping at it
+    #[arg(long)]
+    below_ground: bool,
+
+    /// Open the window borderless fullscreen
+    #[arg(long)]
+    fullscreen: bool,
+
+    /// Window size in pixels, e.g. 1280x720
+    #[arg(long, value_name = "WxH", value_parser = window::parse_window_size)]
+    window_size: Option<(u32, u32)>,
+
+    /// Open the window on this monitor, numbered from 0
+    #[arg(long, value_name = "N")]
+    monitor: Option<usize>,
+
+    /// Unattended mode for wall displays: while idle, slowly orbit the graph,
+    /// light up its best-connected nodes in turn and hide the UI
+    #[arg(long)]
+    kiosk: bool,
+
+    /// Seconds without input before kiosk mode starts again
+    #[arg(long, value_name = "SECS", default_value_t = kiosk::DEFAULT_IDLE_SECS)]
+    kiosk_idle: f32,
+
+    /// Step through this tour file with Page Down and Page Up
+    #[arg(long, value_name = "FILE")]
+    tour: Option<PathBuf>,
+
+    /// Command the inspector opens the input file in at a node's line, with
+    /// {file} and {line} in it (e.g. "code --goto {file}:{line}"), or an
+    /// editor that takes +LINE FILE; defaults to $VISUAL or $EDITOR
+    #[arg(long, value_name = "COMMAND")]
+    editor: Option<String>,
+
+    /// Font file to take characters the built-in font lacks from, such as
+    /// CJK, emoji or right-to-left scripts; repeat to try several in order
+    #[arg(long = "font", value_name = "PATH")]
+    fonts: Vec<PathBuf>,
+}
+
+/// The app window, composited with the desktop if the background is see-through
+fn primary_window(display: &DisplayArgs) -> Window {
+    let transparent = display.background().alpha() < 1.0;
+    let mut window = Window {
+        transparent,
+        #[cfg(target_os = "macos")]
+        composite_alpha_mode: if transparent {
+            bevy::window::CompositeAlphaMode::PostMultiplied
+        } else {
+            bevy::window::CompositeAlphaMode::Auto
+        },
+        ..default()
+    };
+    display.placement().apply(&mut window);
+    window
 }
 
 fn main() {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let registry = SourceRegistry::default();
+    let config = load_config(&registry, args.profile.as_deref());
 
-    // Read dot content from file or stdin
-    let dot_content = args.file.map_or_else(
-        || {
-            if io::stdin().is_terminal() {
-                eprintln!("Error: No input provided. Either specify a file or pipe data to stdin.");
-                eprintln!("Usage: dotspace [FILE] or command | dotspace");
-                std::process::exit(1);
-            } else {
-                // Read from stdin if it's piped
-                let mut buffer = String::new();
-                io::stdin().read_to_string(&mut buffer).unwrap_or_else(|e| {
-                    eprintln!("Error reading from stdin: {e}");
-                    std::process::exit(1);
-                });
-                buffer
-            }
-        },
-        |filename| {
-            std::fs::read_to_string(&filename).unwrap_or_else(|e| {
-                eprintln!("Error reading file '{filename}': {e}");
-                std::process::exit(1);
+    let mut command = args.command.unwrap_or(Command::View(args.view));
+    if let Some(display) = command.display_mut() {
+        // The subcommand's own options, or the top-level ones for `dotspace FILE`
+        let matches = matches
+            .subcommand()
+            .map_or(&matches, |(_, matches)| matches);
+        display.apply_config(&config, matches);
+        display.apply_quality(&config, matches);
+    }
+
+    match command {
+        Command::View(view) => run_view(view, registry, &config),
+        Command::Render {
+            file,
+            output,
+            format,
+            width,
+            height,
+            delay,
+            display,
+        } => {
+            let format = format_for(&config, checked_format(&registry, format), file.as_deref());
+            let mut window = primary_window(&display);
+            window.resolution = WindowResolution::new(width as f32, height as f32);
+            let mut app = viewer_app(display, window, &config);
+            open_file(&mut app, &registry, file, format, false);
+            app.insert_resource(CaptureRequest {
+                path: output,
+                delay,
             })
-        },
+            .insert_resource(registry)
+            .run();
+        }
+        Command::Diff {
+            old,
+            new,
+            format,
+            delay,
+            display,
+        } => run_diff(&old, &new, format, delay, display, registry, &config),
+        Command::Convert {
+            file,
+            output,
+            from,
+            to,
+        } => {
+            let from = format_for(&config, checked_format(&registry, from), file.as_deref());
+            run_convert(&registry, file, output, from.as_deref(), to);
+        }
+        Command::Validate {
+            files,
+            format,
+            json,
+            deny_warnings,
+        } => {
+            let format = checked_format(&registry, format);
+            run_validate(&registry, &config, files, format, json, deny_warnings);
+        }
+        Command::Generate {
+            nodes,
+            edges,
+            model,
+            seed,
+            output,
+        } => {
+            let dot = generate::generate_dot(model, nodes, edges, seed);
+            write_output(output.as_deref(), &dot);
+        }
+        Command::Schema => print!("{}", events::EVENT_SCHEMA),
+        Command::Replay {
+            session,
+            speed,
+            display,
+        } => run_replay(&session, speed, display, registry, &config),
+    }
+}
+
+/// Shows one diagram turning into another in the viewer
+fn run_diff(
+    old: &str,
+    new: &str,
+    format: String,
+    delay: f32,
+    display: DisplayArgs,
+    registry: SourceRegistry,
+    config: &Config,
+) {
+    let format = checked_format(&registry, format);
+    let load = |location: &str| {
+        let format = format_for(config, format.clone(), Some(location));
+        let content = read_input(Some(location));
+        load_input(
+            &registry,
+            Some(location),
+            content,
+            format.as_deref(),
+            &mut |_| {},
+        )
+        .map_or_else(
+            |e| {
+                eprintln!("Error in '{location}': {e}");
+                std::process::exit(1);
+            },
+            |(state, ..)| state,
+        )
+    };
+    let source = DiffSource::new(
+        &load(old),
+        &load(new),
+        Duration::from_secs_f32(delay.max(0.0)),
     );
+    println!("{old} -> {new}: {}", source.summary());
+    let window = primary_window(&display);
+    let mut app = viewer_app(display, window, config);
+    if let Err(e) = app.add_live_source(source) {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+    app.insert_resource(registry).run();
+}
 
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .insert_resource(DotContent(dot_content))
-        .insert_resource(CameraSettings {
-            distance: args.distance,
-            speed: args.speed,
-        })
-        .insert_resource(LabelSettings {
-            visibility_distance: args.label_distance,
-            show_all_labels: false,
-        })
-        .insert_resource(SearchState::default())
-        .add_plugins(CameraPlugin)
-        .add_systems(Startup, setup)
-        .add_systems(Update, toggle_label_visibility)
-        .add_systems(Update, toggle_search)
-        .add_systems(Update, handle_search_input)
-        .add_systems(Update, update_node_highlighting)
-        .add_systems(Update, apply_highlight_visuals)
-        .add_systems(Update, update_edge_positions)
-        .add_systems(Update, create_node_labels)
-        .add_systems(Update, update_node_label_positions)
-        .run();
-}
-
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    dot_content: Res<DotContent>,
-    camera_settings: Res<CameraSettings>,
+/// Replays a session recording in the viewer
+fn run_replay(
+    session: &str,
+    speed: f32,
+    display: DisplayArgs,
+    registry: SourceRegistry,
+    config: &Config,
 ) {
-    // Detect format and create appropriate source
-    let format = detect_format(&dot_content.0).unwrap_or_else(|| {
-        eprintln!("Warning: Could not detect diagram format, assuming DOT");
-        "dot"
+    let window = primary_window(&display);
+    let mut app = viewer_app(display, window, config);
+    // The graph starts empty and is rebuilt as the events come due
+    let content = read_input(Some(session));
+    if let Err(e) = app.add_live_source(SessionSource::replay(&content, speed)) {
+        eprintln!("Error replaying '{session}': {e}");
+        std::process::exit(1);
+    }
+    eprintln!("Replaying {session} at {speed}x");
+    app.insert_resource(registry).run();
+}
+
+/// Reads the config files, exiting if they are broken
+fn load_config(registry: &SourceRegistry, profile: Option<&str>) -> Config {
+    let config = Config::load(profile).unwrap_or_else(|e| {
+        eprintln!("Error in config: {e}");
+        std::process::exit(1);
+    });
+    if let Some(format) = config.formats.values().find(|f| registry.get(f).is_none()) {
+        eprintln!("Error in config: unknown format '{format}' in [formats]");
+        std::process::exit(1);
+    }
+    config
+}
+
+/// Views a file or live source, the default command
+fn run_view(args: ViewArgs, registry: SourceRegistry, config: &Config) {
+    if args.list_formats {
+        for format in registry.formats() {
+            println!("{:<10} {}", format.name, format.description);
+        }
+        return;
+    }
+    let graph_from_stdin = args.files.is_empty() && args.otlp.is_none() && args.mqtt.is_none();
+    if graph_from_stdin && args.flash_from.as_deref() == Some("-") {
+        eprintln!("Error: --flash-from - needs a graph file, as the graph is read from stdin");
+        std::process::exit(1);
+    }
+    if args.files.len() > 1 && args.merge.is_none() {
+        eprintln!("Error: several files are shown as one graph with --merge");
+        std::process::exit(1);
+    }
+    if args.files.is_empty() && args.merge.is_some() {
+        eprintln!("Error: --merge needs the files to merge");
+        std::process::exit(1);
+    }
+
+    let format = checked_format(&registry, args.format);
+    let merged = args.merge.map(|mode| MergedInputs {
+        mode,
+        inputs: args
+            .files
+            .iter()
+            .map(|file| (file.clone(), format_for(config, format.clone(), Some(file))))
+            .collect(),
+    });
+    let file = args.files.into_iter().next();
+    let format = format_for(config, format, file.as_deref());
+    let window = primary_window(&args.display);
+    let mut app = viewer_app(args.display, window, config);
+    app.insert_resource(Staleness {
+        ttl: args.stale_after,
+        remove: args.remove_stale,
     });
 
-    let events = if format == "plantuml" {
-        let source = PlantUMLSource::from_content(&dot_content.0);
-        source.events().expect("Failed to parse PlantUML file")
+    if let Some(addr) = args.otlp {
+        // The service graph starts empty and is filled in by live events
+        if let Err(e) = app.add_live_source(OtelSource::new(addr)) {
+            eprintln!("Error starting OTLP listener on {addr}: {e}");
+            std::process::exit(1);
+        }
+        eprintln!("Listening for OTLP/HTTP traces on http://{addr}/v1/traces");
+    } else if let Some(broker) = &args.mqtt {
+        let source = MqttSource::new(broker, &args.mqtt_topic);
+        if let Err(e) = source.and_then(|source| app.add_live_source(source).map(|_| ())) {
+            eprintln!("Error subscribing to MQTT broker {broker}: {e}");
+            std::process::exit(1);
+        }
+        eprintln!("Subscribed to {} on MQTT broker {broker}", args.mqtt_topic);
+    } else if let Some(merged) = merged {
+        open_merged(&mut app, &registry, merged, args.watch);
     } else {
-        let source = DotSource::from_content(&dot_content.0);
-        source.events().expect("Failed to parse DOT file")
+        open_file(&mut app, &registry, file, format, args.watch);
+    }
+
+    if let Some(mapping) = &args.prometheus {
+        let source = PrometheusSource::from_mapping(&read_input(Some(mapping)));
+        if let Err(e) = source.and_then(|source| app.add_live_source(source).map(|_| ())) {
+            eprintln!("Error in Prometheus mapping '{mapping}': {e}");
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = args.flash_from {
+        app.insert_resource(FlashFeed::open(path));
+    }
+
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = args.grpc {
+        if let Err(e) = app.add_grpc_server(addr) {
+            eprintln!("Error starting gRPC server on {addr}: {e}");
+            std::process::exit(1);
+        }
+        eprintln!("Serving the gRPC control API on {addr}");
+    }
+
+    #[cfg(feature = "audio")]
+    if args.audio || !args.audio_cues.is_empty() {
+        let cues = if args.audio_cues.is_empty() {
+            audio::AudioCues::default()
+        } else {
+            audio::AudioCues(args.audio_cues.into_iter().collect())
+        };
+        app.insert_resource(cues)
+            .add_plugins(audio::AudioCuesPlugin);
+    }
+
+    app.insert_resource(registry).run();
+}
+
+/// Reads a diagram in one format and writes it in another
+fn run_convert(
+    registry: &SourceRegistry,
+    file: Option<String>,
+    output: Option<PathBuf>,
+    from: Option<&str>,
+    to: Option<OutputFormat>,
+) {
+    let Some(to) = to.or_else(|| output.as_deref().and_then(OutputFormat::from_path)) else {
+        eprintln!("Error: can't tell which format to write; use --to dot, json or graphml");
+        std::process::exit(1);
     };
+    let content = read_input(file.as_deref());
+    let loaded = load_input(registry, file.as_deref(), content, from, &mut |_| {});
+    let (state, ..) = loaded.unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    });
+    // DOT graphs are named after the file they are written to
+    let name = output
+        .as_deref()
+        .and_then(Path::file_stem)
+        .map_or_else(|| "graph".into(), |stem| stem.to_string_lossy());
+    let converted = to.write(&state.as_graph_data(), &name);
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, converted) {
+                eprintln!("Error writing '{}': {e}", path.display());
+                std::process::exit(1);
+            }
+            eprintln!(
+                "Wrote {} nodes and {} edges to {}",
+                state.node_count(),
+                state.edge_count(),
+                path.display()
+            );
+        }
+        None => print!("{converted}"),
+    }
+}
 
-    let mut graph_state = GraphState::new();
-    graph_state.process_events(events);
+/// Writes `text` to the file, or to stdout if there's none, exiting on failure
+fn write_output(path: Option<&Path>, text: &str) {
+    match path {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, text) {
+                eprintln!("Error writing '{}': {e}", path.display());
+                std::process::exit(1);
+            }
+        }
+        None => print!("{text}"),
+    }
+}
 
-    // Convert to GraphData for compatibility
-    let graph_data = types::GraphData(graph_state.as_graph_data());
+/// Validates each file, prints the reports and exits with 1 if any failed
+fn run_validate(
+    registry: &SourceRegistry,
+    config: &Config,
+    files: Vec<String>,
+    format: Option<String>,
+    json: bool,
+    deny_warnings: bool,
+) {
+    let inputs = if files.is_empty() {
+        vec![None]
+    } else {
+        files.into_iter().map(Some).collect()
+    };
+    let reports: Vec<_> = inputs
+        .into_iter()
+        .map(|file| {
+            let content = read_input(file.as_deref());
+            let name = file.as_deref().unwrap_or("<stdin>");
+            let format = format_for(config, format.clone(), file.as_deref());
+            let mut progress = ProgressLine::new(name, content.len());
+            let report =
+                validate::validate(registry, name, &content, format.as_deref(), &mut |p| {
+                    progress.report(p);
+                });
+            progress.finish();
+            report
+        })
+        .collect();
 
-    // Setup camera
-    setup_camera(
-        &mut commands,
-        camera_settings.distance,
-        camera_settings.speed,
-    );
+    if json {
+        let reports: Vec<_> = reports.iter().map(validate::Report::to_json).collect();
+        println!("{}", serde_json::Value::Array(reports));
+    } else {
+        for report in &reports {
+            println!("{report}");
+        }
+    }
+    let failed = reports.iter().any(|report| {
+        report.count(Severity::Error) > 0 || (deny_warnings && report.count(Severity::Warning) > 0)
+    });
+    if failed {
+        std::process::exit(1);
+    }
+}
 
-    // Light
-    commands.spawn((
-        DirectionalLight {
-            illuminance: 10000.0,
-            ..default()
-        },
-        Transform::from_rotation(Quat::from_euler(EulerRot::XYZ, -0.5, -0.5, 0.0)),
-    ));
-
-    // Ground plane for reference
-    commands.spawn((
-        Mesh3d(meshes.add(Plane3d::default().mesh().size(20.0, 20.0))),
-        MeshMaterial3d(materials.add(StandardMaterial {
-            base_color: Color::srgb(0.3, 0.2, 0.2),
+impl Command {
+    /// Display options of the commands that open the viewer
+    const fn display_mut(&mut self) -> Option<&mut DisplayArgs> {
+        match self {
+            Self::View(ViewArgs { display, .. })
+            | Self::Render { display, .. }
+            | Self::Diff { display, .. }
+            | Self::Replay { display, .. } => Some(display),
+            Self::Convert { .. } | Self::Validate { .. } | Self::Generate { .. } | Self::Schema => {
+                None
+            }
+        }
+    }
+}
+
+impl DisplayArgs {
+    fn background(&self) -> Color {
+        self.background.unwrap_or_else(|| self.theme.background())
+    }
+
+    const fn placement(&self) -> WindowPlacement {
+        WindowPlacement {
+            fullscreen: self.fullscreen,
+            size: self.window_size,
+            monitor: self.monitor,
+        }
+    }
+
+    /// Takes the quality preset's value for every option it covers that
+    /// neither the command line nor the config sets
+    fn apply_quality(&mut self, config: &Config, matches: &ArgMatches) {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        let preset = self.quality.preset();
+        // Flags can only be turned on, by the preset or by hand
+        self.shadows |= preset.shadows;
+        self.edge_lines |= preset.edge_lines;
+        self.reduced_motion |= preset.reduced_motion;
+        if unset("msaa") {
+            self.msaa = preset.msaa;
+        }
+        if unset("label_distance") && config.label_distance.is_none() {
+            self.label_distance = preset.label_distance;
+        }
+        if unset("atlas_labels_above") {
+            self.atlas_labels_above = preset.atlas_labels_above;
+        }
+        if unset("aggregate_above") {
+            self.aggregate_above = preset.aggregate_above;
+        }
+    }
+
+    /// Takes the config's value for every option not given on the command line
+    fn apply_config(&mut self, config: &Config, matches: &ArgMatches) {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        let distance = config.distance.filter(|_| unset("distance"));
+        self.distance = distance.unwrap_or(self.distance);
+        let speed = config.speed.filter(|_| unset("speed"));
+        self.speed = speed.unwrap_or(self.speed);
+        let label_distance = config.label_distance.filter(|_| unset("label_distance"));
+        self.label_distance = label_distance.unwrap_or(self.label_distance);
+        let label_width = config.label_width.filter(|_| unset("label_width"));
+        self.label_width = label_width.unwrap_or(self.label_width);
+        let wrap_labels = config.wrap_labels.filter(|_| unset("wrap_labels"));
+        self.wrap_labels = wrap_labels.unwrap_or(self.wrap_labels);
+        let pan_sensitivity = config.pan_sensitivity.filter(|_| unset("pan_sensitivity"));
+        self.pan_sensitivity = pan_sensitivity.unwrap_or(self.pan_sensitivity);
+        let orbit_sensitivity = config
+            .orbit_sensitivity
+            .filter(|_| unset("orbit_sensitivity"));
+        self.orbit_sensitivity = orbit_sensitivity.unwrap_or(self.orbit_sensitivity);
+        let zoom_sensitivity = config
+            .zoom_sensitivity
+            .filter(|_| unset("zoom_sensitivity"));
+        self.zoom_sensitivity = zoom_sensitivity.unwrap_or(self.zoom_sensitivity);
+        let smoothness = config.smoothness.filter(|_| unset("smoothness"));
+        self.smoothness = smoothness.unwrap_or(self.smoothness);
+        let pitch_limit = config.pitch_limit.filter(|_| unset("pitch_limit"));
+        self.pitch_limit = pitch_limit.unwrap_or(self.pitch_limit);
+        let inertia = config.inertia.filter(|_| unset("inertia"));
+        self.inertia = inertia.unwrap_or(self.inertia);
+        let theme = config.theme.filter(|_| unset("theme"));
+        self.theme = theme.unwrap_or(self.theme);
+        if unset("background") {
+            self.background = config.background.or(self.background);
+        }
+        let lighting = config.lighting.filter(|_| unset("lighting"));
+        self.lighting = lighting.unwrap_or(self.lighting);
+        let skybox = config.skybox.filter(|_| unset("skybox"));
+        self.skybox = skybox.unwrap_or(self.skybox);
+        let fog = config.fog.filter(|_| unset("fog"));
+        self.fog = fog.unwrap_or(self.fog);
+        let palette = config.palette.filter(|_| unset("palette"));
+        self.palette = palette.unwrap_or(self.palette);
+        let distinct_shapes = config.distinct_shapes.filter(|_| unset("distinct_shapes"));
+        self.distinct_shapes = distinct_shapes.unwrap_or(self.distinct_shapes);
+        let quality = config.quality.filter(|_| unset("quality"));
+        self.quality = quality.unwrap_or(self.quality);
+        let reduced_motion = config.reduced_motion.filter(|_| unset("reduced_motion"));
+        self.reduced_motion = reduced_motion.unwrap_or(self.reduced_motion);
+        let ui_scale = config.ui_scale.filter(|_| unset("ui_scale"));
+        self.ui_scale = ui_scale.unwrap_or(self.ui_scale);
+        let high_contrast = config.high_contrast.filter(|_| unset("high_contrast"));
+        self.high_contrast = high_contrast.unwrap_or(self.high_contrast);
+        let layout = config.layout.filter(|_| unset("layout"));
+        self.layout = layout.unwrap_or(self.layout);
+        if unset("group_by") {
+            self.group_by = config.group_by.or(self.group_by);
+        }
+        let levels = config.levels.filter(|_| unset("levels"));
+        self.levels = levels.unwrap_or(self.levels);
+        let fullscreen = config.fullscreen.filter(|_| unset("fullscreen"));
+        self.fullscreen = fullscreen.unwrap_or(self.fullscreen);
+        let kiosk = config.kiosk.filter(|_| unset("kiosk"));
+        self.kiosk = kiosk.unwrap_or(self.kiosk);
+        if unset("window_size") {
+            self.window_size = config.window_size.or(self.window_size);
+        }
+        if unset("monitor") {
+            self.monitor = config.monitor.or(self.monitor);
+        }
+        if unset("editor") {
+            self.editor = config.editor.clone().or_else(|| self.editor.take());
+        }
+        if unset("fonts") && !config.fonts.is_empty() {
+            self.fonts.clone_from(&config.fonts);
+        }
+        // Merged by type, with the command line's models going last to win
+        if !config.models.is_empty() {
+            let given = std::mem::take(&mut self.models);
+            self.models = config.models.clone().into_iter().chain(given).collect();
+        }
+    }
+}
+
+/// The viewer app, set up to draw the graph as `display` asks
+fn viewer_app(display: DisplayArgs, window: Window, config: &Config) -> App {
+    let mut app = App::new();
+    let mut bindings = config.key_bindings();
+    insert_display_files(&mut app, &display, &mut bindings);
+    insert_appearance(&mut app, &display);
+    app.add_plugins((
+        DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(window),
             ..default()
-        })),
-    ));
+        }),
+        DotspacePlugin,
+    ))
+    .insert_resource(GraphState::with_type_rules(TypeRules(
+        config.type_rules.clone(),
+    )))
+    .insert_resource(NodeModels(display.models.into_iter().collect()))
+    .insert_resource(Aggregation {
+        threshold: display.aggregate_above,
+        ..default()
+    })
+    .insert_resource(LayoutSettings {
+        mode: display.layout,
+        group_by: display.group_by,
+        levels: display.levels,
+        ..default()
+    })
+    .insert_resource(ViewSettings {
+        merge_edges: display.merge_edges,
+        palette: display.palette,
+        distinct_shapes: display.distinct_shapes,
+        ..default()
+    })
+    .insert_resource(EdgeLines {
+        enabled: display.edge_lines,
+    })
+    .insert_resource(bindings)
+    .insert_resource(GridSettings {
+        show_grid: !display.no_grid,
+        show_axes: display.axes,
+        below_ground: display.below_ground,
+    })
+    .insert_resource(Kiosk::new(display.kiosk, display.kiosk_idle.max(0.0)))
+    .insert_resource(CameraSettings {
+        distance: display.distance,
+        speed: display.speed,
+        pan_sensitivity: display.pan_sensitivity,
+        orbit_sensitivity: display.orbit_sensitivity,
+        zoom_sensitivity: display.zoom_sensitivity,
+        // The camera never settles at 1
+        smoothness: display.smoothness.clamp(0.0, 0.99),
+        pitch_limit: display.pitch_limit.clamp(0.0, 89.0).to_radians(),
+        inertia: display.inertia.max(0.0),
+    })
+    .insert_resource(LabelSettings {
+        visibility_distance: display.label_distance,
+        show_all_labels: false,
+        max_width: display.label_width,
+        wrap: display.wrap_labels,
+        atlas_above: display.atlas_labels_above,
+    })
+    .insert_resource(GaugeBindings {
+        color: display.metric_color,
+        pulse: display.metric_pulse,
+        flow: display.edge_flow,
+    });
+    app
+}
 
-    // Create nodes and edges
-    create_graph_visualization(&mut commands, &mut meshes, &mut materials, &graph_data);
+/// Loads the tour, editor command and fonts `display` names, exiting if one
+/// can't be read
+fn insert_display_files(app: &mut App, display: &DisplayArgs, bindings: &mut KeyBindings) {
+    if let Some(path) = &display.tour {
+        match Tour::load(path) {
+            Ok(tour) => {
+                // Page Up and Page Down step through the tour instead of zooming
+                bindings.give_priority(Action::NextTourStep);
+                bindings.give_priority(Action::PreviousTourStep);
+                app.insert_resource(tour);
+            }
+            Err(e) => {
+                eprintln!("Error in tour {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(command) = &display.editor {
+        app.insert_resource(EditorCommand(command.clone()));
+    }
+    match FontStack::open(display.fonts.clone()) {
+        Ok(fonts) => {
+            app.insert_resource(fonts);
+        }
+        Err(e) => {
+            eprintln!("Error loading font {e}");
+            std::process::exit(1);
+        }
+    }
+}
 
-    // Store graph data as a resource for later use
-    commands.insert_resource(graph_data);
+/// Inserts how the scene looks: background, theme, lighting, anti-aliasing,
+/// motion and accessibility
+fn insert_appearance(app: &mut App, display: &DisplayArgs) {
+    app.insert_resource(ClearColor(display.background()))
+        .insert_resource(display.theme)
+        .insert_resource(Lighting {
+            preset: display.lighting,
+            skybox: display.skybox,
+            shadows: display.shadows,
+            fog: display.fog.max(0.0),
+        })
+        .insert_resource(AntiAliasing {
+            msaa: display.msaa,
+            taa: display.taa,
+        })
+        .insert_resource(ReducedMotion {
+            enabled: display.reduced_motion,
+        })
+        .insert_resource(Accessibility {
+            ui_scale: display
+                .ui_scale
+                .clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end()),
+            high_contrast: display.high_contrast,
+        });
+}
+
+/// The format named on the command line, or None for `auto`; exits if the
+/// registry doesn't know it
+fn checked_format(registry: &SourceRegistry, format: String) -> Option<String> {
+    // `auto` leaves the format to detection
+    let format = (!format.eq_ignore_ascii_case("auto")).then_some(format);
+    if let Some(name) = &format
+        && registry.get(name).is_none()
+    {
+        eprintln!(
+            "Error: unknown format '{name}'. Use --list-formats to see the supported formats."
+        );
+        std::process::exit(1);
+    }
+    format
+}
 
-    // Setup UI
-    setup_ui(&mut commands);
-    setup_search_ui(&mut commands);
+/// The format to read `location` as: the one given on the command line,
+/// else the one the config maps its extension to, else None to detect it
+fn format_for(config: &Config, format: Option<String>, location: Option<&str>) -> Option<String> {
+    format.or_else(|| {
+        location
+            .and_then(|location| config.format_for(location))
+            .map(str::to_string)
+    })
+}
+
+/// Loads the diagram to show, along with its sidecar of pinned labels and notes
+///
+/// A diagram that fails to parse still opens the window, so the error can be
+/// shown and the file reloaded once it's fixed.
+fn open_file(
+    app: &mut App,
+    registry: &SourceRegistry,
+    file: Option<String>,
+    format: Option<String>,
+    watch: bool,
+) {
+    let content = read_input(file.as_deref());
+    let mut input_source = InputSource::new(file.clone(), format, watch);
+    let mut progress = ProgressLine::new(file.as_deref().unwrap_or("<stdin>"), content.len());
+    let loaded = load_input(
+        registry,
+        file.as_deref(),
+        content,
+        input_source.format.as_deref(),
+        &mut |p| progress.report(p),
+    );
+    progress.finish();
+    let loaded = loaded
+        .map(|(graph_state, loaded_format, included)| {
+            input_source.loaded_format = Some(loaded_format);
+            input_source.set_included(included);
+            graph_state
+        })
+        .map_err(|e| {
+            eprintln!("Error: {e}");
+            if matches!(e, SourceError::UnknownFormat) {
+                eprintln!("Use --format to specify the diagram format explicitly.");
+            }
+            e.to_string()
+        });
+    show_loaded(app, input_source, loaded);
+}
+
+/// Loads several inputs as one graph, which they are reloaded into together
+fn open_merged(app: &mut App, registry: &SourceRegistry, merged: MergedInputs, watch: bool) {
+    let loaded = load_merged_inputs(registry, &merged);
+    let mut input_source = InputSource::new(None, None, watch).merging(merged);
+    let loaded = loaded
+        .map(|(graph_state, loaded_format, included)| {
+            input_source.loaded_format = Some(loaded_format);
+            input_source.set_included(included);
+            graph_state
+        })
+        .inspect_err(|e| eprintln!("Error: {e}"));
+    show_loaded(app, input_source, loaded);
+}
+
+/// Puts a freshly loaded graph, or why it couldn't be loaded, in front of the
+/// viewer, along with the notes, pins and settings saved beside the input
+fn show_loaded(app: &mut App, input_source: InputSource, loaded: Result<GraphState, String>) {
+    match loaded {
+        Ok(mut graph_state) => {
+            // Keep the config's type rules that `viewer_app` set up
+            let rules = app.world().resource::<GraphState>().type_rules().clone();
+            graph_state.set_type_rules(rules);
+            app.insert_resource(graph_state);
+        }
+        Err(e) => {
+            app.insert_resource(LoadError(Some(e)));
+        }
+    }
+    if let Some(sidecar) = input_source
+        .location
+        .as_deref()
+        .and_then(SidecarFile::for_input)
+    {
+        let world = app.world_mut();
+        let mut layout = world.resource::<LayoutSettings>().clone();
+        let mut view = world.resource::<ViewSettings>().clone();
+        SavedSettings::load(&sidecar).apply(&mut layout, &mut view);
+        app.insert_resource(layout)
+            .insert_resource(view)
+            .insert_resource(sidecar.pinned_labels())
+            .insert_resource(sidecar.node_notes())
+            .insert_resource(sidecar);
+    }
+    app.insert_resource(input_source);
+}
+
+/// Reads diagram content from the given file or URL, or from stdin when piped
+fn read_input(file: Option<&str>) -> String {
+    file.map_or_else(
+        || {
+            if io::stdin().is_terminal() {
+                eprintln!("Error: No input provided. Either specify a file or pipe data to stdin.");
+                eprintln!("Usage: dotspace [FILE] or command | dotspace");
+                std::process::exit(1);
+            } else {
+                // Read from stdin if it's piped
+                input::read_from(io::stdin()).unwrap_or_else(|e| {
+                    eprintln!("Error reading from stdin: {e}");
+                    std::process::exit(1);
+                })
+            }
+        },
+        |location| {
+            input::read_location(location).unwrap_or_else(|e| {
+                eprintln!("Error reading '{location}': {e}");
+                std::process::exit(1);
+            })
+        },
+    )
 }
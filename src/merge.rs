@@ -0,0 +1,201 @@
+use crate::events::{EventNodeInfo, GraphEvent};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Separates a merged input's name from the node ids in it, as in `billing/db`
+pub const NAMESPACE_SEPARATOR: char = '/';
+
+/// How nodes of different inputs come together in a merged graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeMode {
+    /// Nodes with the same id are one node, whichever inputs mention it
+    #[default]
+    Unify,
+    /// Each input's ids are prefixed with its file name, as in `billing/db`;
+    /// an input reaches another's node by writing the prefixed id
+    Namespace,
+}
+
+impl MergeMode {
+    pub const ALL: [Self; 2] = [Self::Unify, Self::Namespace];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Unify => "unify",
+            Self::Namespace => "namespace",
+        }
+    }
+}
+
+impl FromStr for MergeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown merge mode '{s}' (expected unify or namespace)"))
+    }
+}
+
+/// Several inputs shown as one graph, so they can be loaded again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergedInputs {
+    pub mode: MergeMode,
+    /// Location and format (`None` to detect it) of each input, in order
+    pub inputs: Vec<(String, Option<String>)>,
+}
+
+/// The namespace of each input: its file name without the extension, made
+/// unique with a number where two inputs share one
+pub fn namespaces<'a>(locations: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut taken = HashSet::new();
+    locations
+        .into_iter()
+        .map(|location| {
+            let stem = Path::new(location.trim_end_matches('/'))
+                .file_stem()
+                .map_or_else(
+                    || "input".to_string(),
+                    |stem| stem.to_string_lossy().into_owned(),
+                )
+                .replace(NAMESPACE_SEPARATOR, "_");
+            let mut namespace = stem.clone();
+            for n in 2.. {
+                if taken.insert(namespace.clone()) {
+                    break;
+                }
+                namespace = format!("{stem}-{n}");
+            }
+            namespace
+        })
+        .collect()
+}
+
+/// The id a node of the input `namespace` has in the merged graph; ids that
+/// already start with one of `namespaces` name another input's node
+#[allow(clippy::implicit_hasher)]
+pub fn namespaced(id: &str, namespace: &str, namespaces: &HashSet<&str>) -> String {
+    match id.split_once(NAMESPACE_SEPARATOR) {
+        Some((prefix, _)) if namespaces.contains(prefix) => id.to_string(),
+        _ => format!("{namespace}{NAMESPACE_SEPARATOR}{id}"),
+    }
+}
+
+/// Combines the events of several inputs, each with its namespace, into one
+/// batch that builds the merged graph
+///
+/// A node added by more than one input is added once, where it first
+/// appears, taking its name, type and level from whichever input gives them,
+/// so a file that only draws an edge to a node doesn't hide how the file
+/// defining it describes it.
+pub fn merge_events(inputs: Vec<(String, Vec<GraphEvent>)>, mode: MergeMode) -> Vec<GraphEvent> {
+    let all: Vec<String> = inputs
+        .iter()
+        .map(|(namespace, _)| namespace.clone())
+        .collect();
+    let all: HashSet<&str> = all.iter().map(String::as_str).collect();
+
+    let mut merged = vec![GraphEvent::BatchStart];
+    let mut added: HashMap<String, usize> = HashMap::new();
+    for (namespace, events) in inputs {
+        for mut event in events {
+            if matches!(
+                event,
+                GraphEvent::BatchStart | GraphEvent::BatchEnd | GraphEvent::Clear
+            ) {
+                continue;
+            }
+            if mode == MergeMode::Namespace {
+                for id in event.node_ids_mut() {
+                    *id = namespaced(id, &namespace, &all);
+                }
+            }
+            if let GraphEvent::AddNode { id, info } = &event {
+                if let Some(&at) = added.get(id) {
+                    if let GraphEvent::AddNode { info: first, .. } = &mut merged[at] {
+                        fill_in(first, info, id);
+                    }
+                    continue;
+                }
+                added.insert(id.clone(), merged.len());
+            }
+            merged.push(event);
+        }
+    }
+    merged.push(GraphEvent::BatchEnd);
+    merged
+}
+
+/// Completes what one input says of a node with what another says
+fn fill_in(info: &mut EventNodeInfo, other: &EventNodeInfo, id: &str) {
+    // A node named by its id was most likely only mentioned, as in an edge
+    if info.name == id && other.name != id {
+        info.name.clone_from(&other.name);
+    }
+    if info.node_type.is_none() {
+        info.node_type.clone_from(&other.node_type);
+    }
+    if info.level == 0 {
+        info.level = other.level;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_state::{GraphQuery, GraphState};
+    use crate::sources::GraphEventSource;
+    use crate::sources::dot::DotSource;
+
+    fn events(dot: &str) -> Vec<GraphEvent> {
+        DotSource::from_content(dot).events().unwrap()
+    }
+
+    #[test]
+    fn test_merged_inputs_join_across_files() {
+        let gateway = "digraph {\n  api [type=\"service\"];\n  api -> db;\n  api -> auth;\n}";
+        let billing =
+            "digraph {\n  db [label=\"Orders DB\", type=\"database\"];\n  billing -> db;\n}";
+        let names = namespaces(["arch/gateway.dot", "billing.puml"]);
+        assert_eq!(names, ["gateway", "billing"]);
+        assert_eq!(namespaces(["a/x.dot", "b/x.dot"]), ["x", "x-2"]);
+
+        // Unified, both files' `db` is the one the billing file describes
+        let mut state = GraphState::new();
+        state.process_events(merge_events(
+            vec![
+                (names[0].clone(), events(gateway)),
+                (names[1].clone(), events(billing)),
+            ],
+            MergeMode::Unify,
+        ));
+        assert_eq!(state.node_count(), 4);
+        let db = state.get_node("db").unwrap();
+        assert_eq!(db.name, "Orders DB");
+        assert_eq!(db.node_type.as_deref(), Some("database"));
+        assert_eq!(state.predecessors("db"), ["api", "billing"]);
+
+        // Namespaced, files only share nodes they name with a prefix
+        let gateway = "digraph {\n  api -> db;\n  api -> \"billing/db\";\n}";
+        let mut state = GraphState::new();
+        state.process_events(merge_events(
+            vec![
+                (names[0].clone(), events(gateway)),
+                (names[1].clone(), events(billing)),
+            ],
+            MergeMode::Namespace,
+        ));
+        assert_eq!(state.node_count(), 4);
+        assert!(state.get_node("gateway/db").is_some());
+        assert_eq!(state.get_node("billing/db").unwrap().name, "Orders DB");
+        assert_eq!(
+            state.predecessors("billing/db"),
+            ["billing/billing", "gateway/api"]
+        );
+
+        assert_eq!("Namespace".parse(), Ok(MergeMode::Namespace));
+        assert!("union".parse::<MergeMode>().is_err());
+    }
+}
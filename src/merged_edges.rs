@@ -0,0 +1,164 @@
+//! All edges of the scene drawn as one mesh of lines, for graphs too big for
+//! an entity per edge
+//!
+//! Merged edges can't be hovered, faded or animated one by one; the mesh is
+//! only rebuilt when nodes move.
+
+use crate::types::{GraphNode, PortOffsets};
+use crate::visualization::refresh_graph_scene;
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::mesh::PrimitiveTopology;
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+
+/// Length of the strokes of an arrow head
+const ARROW_LENGTH: f32 = 0.3;
+
+/// How far the strokes of an arrow head spread out from their edge
+const ARROW_SPREAD: f32 = 0.12;
+
+/// Keeps the merged edge mesh in step with the nodes
+pub struct MergedEdgesPlugin;
+
+impl Plugin for MergedEdgesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, rebuild_merged_edges.after(refresh_graph_scene));
+    }
+}
+
+/// An edge drawn as part of the merged mesh
+#[derive(Clone, Copy)]
+pub struct MergedEdge {
+    pub from: NodeIndex,
+    pub to: NodeIndex,
+    pub ports: PortOffsets,
+    pub color: Color,
+    /// Whether it ends in an arrow head
+    pub arrow: bool,
+}
+
+/// The edges the entity's mesh draws
+#[derive(Component)]
+pub struct MergedEdges(pub Vec<MergedEdge>);
+
+/// Spawns the entity drawing all of `edges`; its mesh is filled in once
+/// their nodes are spawned
+pub fn spawn_merged_edges(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    edges: Vec<MergedEdge>,
+) {
+    let mesh = meshes.add(line_mesh(&edges, &HashMap::new()));
+    // Colors come from the vertices
+    let material = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        unlit: true,
+        ..default()
+    });
+    commands.spawn((
+        Mesh3d(mesh),
+        MeshMaterial3d(material),
+        Transform::default(),
+        MergedEdges(edges),
+    ));
+}
+
+/// Redraws the merged edges when they are new or any node moved
+pub fn rebuild_merged_edges(
+    mut meshes: ResMut<Assets<Mesh>>,
+    node_query: Query<(&Transform, &GraphNode)>,
+    moved_query: Query<(), (With<GraphNode>, Changed<Transform>)>,
+    merged_query: Query<(&Mesh3d, Ref<MergedEdges>)>,
+) {
+    let Ok((mesh, edges)) = merged_query.single() else {
+        return;
+    };
+    if !edges.is_added() && moved_query.is_empty() {
+        return;
+    }
+    let nodes: HashMap<_, _> = node_query
+        .iter()
+        .map(|(transform, node)| (node.index, *transform))
+        .collect();
+    if let Some(mesh) = meshes.get_mut(&mesh.0) {
+        *mesh = line_mesh(&edges.0, &nodes);
+    }
+}
+
+/// Lines for the edges between `nodes`, leaving out those with an end that
+/// isn't there
+fn line_mesh(edges: &[MergedEdge], nodes: &HashMap<NodeIndex, Transform>) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut colors: Vec<[f32; 4]> = Vec::new();
+    for edge in edges {
+        let (Some(from), Some(to)) = (nodes.get(&edge.from), nodes.get(&edge.to)) else {
+            continue;
+        };
+        let from = from.transform_point(edge.ports.from);
+        let to = to.transform_point(edge.ports.to);
+        let mut points = vec![from, to];
+        if edge.arrow
+            && let Some(direction) = (to - from).try_normalize()
+        {
+            // Slightly before the target node, like the cones of single edges
+            let tip = to - direction * 0.5;
+            let back = tip - direction * ARROW_LENGTH;
+            let side = direction.any_orthonormal_vector() * ARROW_SPREAD;
+            points.extend([tip, back + side, tip, back - side]);
+        }
+        let color = edge.color.to_linear().to_f32_array();
+        colors.extend(std::iter::repeat_n(color, points.len()));
+        positions.extend(points.into_iter().map(|v| v.to_array()));
+    }
+    Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregate::Aggregation;
+    use crate::graph_state::GraphState;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+    use crate::types::{GraphEdge, LayoutSettings, Timeline, ViewSettings};
+
+    #[test]
+    fn test_merged_edges_are_one_mesh() {
+        let content = r#"{
+            "nodes": [{"id": "a"}, {"id": "b"}, {"id": "c"}],
+            "edges": [{"from": "a", "to": "b"}, {"from": "b", "to": "c"}]
+        }"#;
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("json")).unwrap();
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<LayoutSettings>()
+            .insert_resource(ViewSettings {
+                merge_edges: true,
+                ..default()
+            })
+            .init_resource::<Timeline>()
+            .init_resource::<Aggregation>()
+            .insert_resource(state)
+            .add_systems(Update, (refresh_graph_scene, rebuild_merged_edges).chain());
+        app.world_mut().resource_mut::<GraphState>().set_changed();
+        app.update();
+
+        let world = app.world_mut();
+        assert_eq!(world.query::<&GraphEdge>().iter(world).count(), 0);
+        let mesh = world
+            .query_filtered::<&Mesh3d, With<MergedEdges>>()
+            .single(world)
+            .unwrap()
+            .0
+            .clone();
+        let mesh = world.resource::<Assets<Mesh>>().get(&mesh).unwrap();
+        // A line and the two strokes of an arrow head for each edge
+        assert_eq!(mesh.count_vertices(), 2 * 6);
+    }
+}
@@ -0,0 +1,331 @@
+use crate::graph_state;
+use crate::keybindings::{Action, KeyBindings};
+use crate::settings::settings_panel_ui;
+use crate::types::{GraphData, GraphNode, RankingPanel, SearchState, SelectedNodes};
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use bevy_panorbit_camera::PanOrbitCamera;
+use petgraph::Direction;
+use petgraph::graph::NodeIndex;
+use std::collections::{HashMap, VecDeque};
+
+/// Probability of following an edge rather than jumping to a random node
+const PAGE_RANK_DAMPING: f32 = 0.85;
+
+const PAGE_RANK_ITERATIONS: usize = 50;
+
+/// Camera distance when flying to a ranked node
+const FOCUS_RADIUS: f32 = 12.0;
+
+/// A per-node score nodes can be ranked by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Metric {
+    #[default]
+    OutDegree,
+    InDegree,
+    Betweenness,
+    PageRank,
+}
+
+impl Metric {
+    pub const ALL: [Self; 4] = [
+        Self::OutDegree,
+        Self::InDegree,
+        Self::Betweenness,
+        Self::PageRank,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::OutDegree => "Out-degree",
+            Self::InDegree => "In-degree",
+            Self::Betweenness => "Betweenness",
+            Self::PageRank => "PageRank",
+        }
+    }
+
+    /// Score of every node, indexed by `NodeIndex::index`
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn compute(self, data: &graph_state::GraphData) -> Vec<f32> {
+        let degrees = |direction| {
+            data.graph
+                .node_indices()
+                .map(|node| data.graph.edges_directed(node, direction).count() as f32)
+                .collect()
+        };
+        match self {
+            Self::OutDegree => degrees(Direction::Outgoing),
+            Self::InDegree => degrees(Direction::Incoming),
+            Self::Betweenness => betweenness(data),
+            Self::PageRank => page_rank(data),
+        }
+    }
+}
+
+/// Scores computed so far for the current graph
+///
+/// Each metric is computed the first time it is asked for and kept until the
+/// graph changes, so expensive ones cost nothing unless they are used.
+#[derive(Resource, Default)]
+pub struct GraphMetrics {
+    scores: HashMap<Metric, Vec<f32>>,
+}
+
+impl GraphMetrics {
+    pub fn scores(&mut self, data: &graph_state::GraphData, metric: Metric) -> &[f32] {
+        self.scores
+            .entry(metric)
+            .or_insert_with(|| metric.compute(data))
+    }
+
+    pub fn clear(&mut self) {
+        self.scores.clear();
+    }
+}
+
+/// The node ranking panel, backed by lazily computed graph metrics
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GraphMetrics>()
+            .init_resource::<RankingPanel>()
+            .add_systems(
+                Update,
+                (
+                    toggle_ranking_panel,
+                    clear_metrics.run_if(resource_exists_and_changed::<GraphData>),
+                ),
+            )
+            .add_systems(EguiContextPass, ranking_panel_ui.after(settings_panel_ui));
+    }
+}
+
+/// Share of shortest paths between other nodes that pass through each node
+/// (Brandes' algorithm, following edge direction)
+#[allow(clippy::cast_precision_loss)]
+fn betweenness(data: &graph_state::GraphData) -> Vec<f32> {
+    let n = data.graph.node_count();
+    let mut centrality = vec![0.0; n];
+    for start in data.graph.node_indices() {
+        let mut order = Vec::with_capacity(n);
+        let mut parents: Vec<Vec<usize>> = vec![Vec::new(); n];
+        let mut paths = vec![0.0f32; n];
+        let mut distance = vec![usize::MAX; n];
+        paths[start.index()] = 1.0;
+        distance[start.index()] = 0;
+
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            let v = node.index();
+            order.push(v);
+            for neighbor in data.graph.neighbors_directed(node, Direction::Outgoing) {
+                let w = neighbor.index();
+                if distance[w] == usize::MAX {
+                    distance[w] = distance[v] + 1;
+                    queue.push_back(neighbor);
+                }
+                if distance[w] == distance[v] + 1 {
+                    paths[w] += paths[v];
+                    parents[w].push(v);
+                }
+            }
+        }
+
+        let mut dependency = vec![0.0f32; n];
+        for &w in order.iter().rev() {
+            for &v in &parents[w] {
+                dependency[v] += paths[v] / paths[w] * (1.0 + dependency[w]);
+            }
+            if w != start.index() {
+                centrality[w] += dependency[w];
+            }
+        }
+    }
+    centrality
+}
+
+/// Stationary probability of a random walk along the edges, with nodes
+/// that have no outgoing edges linking to every node
+#[allow(clippy::cast_precision_loss)]
+fn page_rank(data: &graph_state::GraphData) -> Vec<f32> {
+    let n = data.graph.node_count();
+    if n == 0 {
+        return Vec::new();
+    }
+    let share = 1.0 / n as f32;
+    let mut rank = vec![share; n];
+    for _ in 0..PAGE_RANK_ITERATIONS {
+        let mut next = vec![(1.0 - PAGE_RANK_DAMPING) * share; n];
+        let mut dangling = 0.0;
+        for node in data.graph.node_indices() {
+            let out_degree = data.graph.edges(node).count();
+            if out_degree == 0 {
+                dangling += rank[node.index()];
+                continue;
+            }
+            let passed = PAGE_RANK_DAMPING * rank[node.index()] / out_degree as f32;
+            for neighbor in data.graph.neighbors(node) {
+                next[neighbor.index()] += passed;
+            }
+        }
+        let spread = PAGE_RANK_DAMPING * dangling * share;
+        for value in &mut next {
+            *value += spread;
+        }
+        rank = next;
+    }
+    rank
+}
+
+/// Nodes with the highest (or lowest) scores, best first
+pub fn top_nodes(scores: &[f32], count: usize, ascending: bool) -> Vec<(NodeIndex, f32)> {
+    let mut ranked: Vec<_> = scores
+        .iter()
+        .enumerate()
+        .map(|(i, &score)| (NodeIndex::new(i), score))
+        .collect();
+    ranked.sort_by(|a, b| {
+        let order = b.1.total_cmp(&a.1);
+        // Break ties by index so the list doesn't shuffle between frames
+        let order = if ascending { order.reverse() } else { order };
+        order.then(a.0.cmp(&b.0))
+    });
+    ranked.truncate(count);
+    ranked
+}
+
+pub fn toggle_ranking_panel(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    search_state: Res<SearchState>,
+    mut panel: ResMut<RankingPanel>,
+) {
+    if !search_state.active && bindings.just_pressed(Action::ToggleRankings, &keyboard_input) {
+        panel.open = !panel.open;
+    }
+}
+
+/// Drops scores computed for a previous version of the graph
+pub fn clear_metrics(mut metrics: ResMut<GraphMetrics>) {
+    metrics.clear();
+}
+
+/// Lists the top nodes by the chosen metric; clicking one selects it and
+/// flies the camera to it
+pub fn ranking_panel_ui(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<RankingPanel>,
+    mut metrics: ResMut<GraphMetrics>,
+    graph_data: Option<Res<GraphData>>,
+    node_query: Query<(&GraphNode, &GlobalTransform)>,
+    mut selection: ResMut<SelectedNodes>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    if !panel.open {
+        return;
+    }
+    let Some(data) = graph_data else {
+        return;
+    };
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let mut metric = panel.metric;
+    let mut count = panel.count;
+    let mut ascending = panel.ascending;
+    let mut open = true;
+    let mut clicked = None;
+    egui::Window::new("Rankings")
+        .open(&mut open)
+        .default_pos([300.0, 400.0])
+        .default_width(260.0)
+        .show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for option in Metric::ALL {
+                    ui.selectable_value(&mut metric, option, option.name());
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(egui::Slider::new(&mut count, 5..=50).text("nodes"));
+                ui.selectable_value(&mut ascending, false, "Highest");
+                ui.selectable_value(&mut ascending, true, "Lowest");
+            });
+            ui.separator();
+
+            let ranked = top_nodes(metrics.scores(&data, metric), count, ascending);
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("ranking_rows")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (rank, (node, score)) in ranked.into_iter().enumerate() {
+                                ui.label(format!("{}.", rank + 1));
+                                let name = &data.graph[node].name;
+                                if ui.selectable_label(false, name).clicked() {
+                                    clicked = Some(node);
+                                }
+                                ui.label(match metric {
+                                    Metric::OutDegree | Metric::InDegree => format!("{score}"),
+                                    Metric::Betweenness => format!("{score:.1}"),
+                                    Metric::PageRank => format!("{score:.4}"),
+                                });
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+
+    if (metric, count, ascending, open) != (panel.metric, panel.count, panel.ascending, true) {
+        panel.metric = metric;
+        panel.count = count;
+        panel.ascending = ascending;
+        panel.open = open;
+    }
+    let Some((node, transform)) =
+        clicked.and_then(|index| node_query.iter().find(|(node, _)| node.index == index))
+    else {
+        return;
+    };
+    selection.0.clear();
+    selection.0.insert(node.id.clone());
+    for mut camera in &mut camera_query {
+        camera.target_focus = transform.translation();
+        camera.target_radius = FOCUS_RADIUS;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_metrics_rank_hubs_first() {
+        // A chain through a bridge, plus a popular sink
+        let content = r"digraph {
+            a -> bridge
+            bridge -> b
+            a -> sink
+            b -> sink
+            bridge -> sink
+        }";
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+        let index = |id: &str| data.node_map[id];
+        let top = |metric: Metric| top_nodes(&metric.compute(&data), 1, false)[0].0;
+
+        assert_eq!(top(Metric::InDegree), index("sink"));
+        assert_eq!(top(Metric::Betweenness), index("bridge"));
+        assert_eq!(top(Metric::PageRank), index("sink"));
+        let ranks = Metric::PageRank.compute(&data);
+        assert!((ranks.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+
+        let lowest = top_nodes(&Metric::InDegree.compute(&data), 1, true);
+        assert_eq!(lowest[0].0, index("a"));
+    }
+}
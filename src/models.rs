@@ -0,0 +1,163 @@
+use crate::types::{GraphData, GraphNode};
+use bevy::asset::LoadState;
+use bevy::gltf::Gltf;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// glTF/GLB models to draw instead of the primitive shape, keyed by node type
+///
+/// Models are placed at the node's position and scale, so they should be
+/// modeled around the origin at roughly one unit across.
+#[derive(Resource, Default, Clone)]
+pub struct NodeModels(pub HashMap<String, PathBuf>);
+
+/// A node waiting for its model to finish loading
+#[derive(Component)]
+pub struct PendingModel {
+    scene: Handle<Scene>,
+    /// The whole file, which is marked as failed when it can't be read or
+    /// parsed; the scene inside it may not be
+    gltf: Handle<Gltf>,
+}
+
+/// Parses a `--model` value of the form `TYPE=PATH`
+pub fn parse_model_mapping(s: &str) -> Result<(String, PathBuf), String> {
+    let (node_type, path) = s
+        .split_once('=')
+        .filter(|(node_type, path)| !node_type.is_empty() && !path.is_empty())
+        .ok_or_else(|| format!("expected TYPE=PATH, got '{s}'"))?;
+    // Resolved against the working directory, not Bevy's asset folder
+    let path = std::path::absolute(path).map_err(|e| format!("invalid path '{path}': {e}"))?;
+    Ok((node_type.to_string(), path))
+}
+
+/// Starts loading the model for each new node whose type has one
+pub fn request_node_models(
+    mut commands: Commands,
+    models: Res<NodeModels>,
+    asset_server: Res<AssetServer>,
+    graph_data: Option<Res<GraphData>>,
+    node_query: Query<(Entity, &GraphNode), Added<GraphNode>>,
+) {
+    if models.0.is_empty() {
+        return;
+    }
+    let Some(graph_data) = graph_data else {
+        return;
+    };
+
+    for (entity, node) in &node_query {
+        let Some(path) = graph_data
+            .graph
+            .node_weight(node.index)
+            .and_then(|info| info.node_type.as_ref())
+            .and_then(|node_type| models.0.get(node_type))
+        else {
+            continue;
+        };
+        // Paths come from the user, so they may lie outside the asset folder
+        let gltf = asset_server.load_override(path.clone());
+        let scene = asset_server.load_override(GltfAssetLabel::Scene(0).from_asset(path.clone()));
+        commands.entity(entity).insert(PendingModel { scene, gltf });
+    }
+}
+
+/// Replaces a node's primitive shape with its model once loaded; nodes whose
+/// model fails to load keep the primitive shape
+pub fn swap_in_node_models(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    node_query: Query<(Entity, &PendingModel)>,
+    mut reported: Local<Vec<AssetId<Gltf>>>,
+) {
+    for (entity, pending) in &node_query {
+        if asset_server.is_loaded_with_dependencies(&pending.scene) {
+            commands
+                .entity(entity)
+                .remove::<(PendingModel, Mesh3d)>()
+                .with_child(SceneRoot(pending.scene.clone()));
+        } else if let Some(err) = [pending.gltf.id().untyped(), pending.scene.id().untyped()]
+            .into_iter()
+            .find_map(|id| match asset_server.get_load_state(id) {
+                Some(LoadState::Failed(err)) => Some(err),
+                _ => None,
+            })
+        {
+            // Warn once per model rather than once per node
+            if !reported.contains(&pending.gltf.id()) {
+                reported.push(pending.gltf.id());
+                eprintln!("Warning: using the default shape because a model failed to load: {err}");
+            }
+            commands.entity(entity).remove::<PendingModel>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EventNodeInfo, GraphEvent};
+    use crate::graph_state::GraphState;
+
+    #[test]
+    fn test_parse_model_mapping() {
+        let (node_type, path) = parse_model_mapping("database=models/rack.glb").unwrap();
+        assert_eq!(node_type, "database");
+        assert!(path.is_absolute());
+        assert!(path.ends_with("models/rack.glb"));
+
+        assert!(parse_model_mapping("database").is_err());
+        assert!(parse_model_mapping("=rack.glb").is_err());
+    }
+
+    #[test]
+    fn test_nodes_keep_their_shape_when_the_model_is_missing() {
+        let mut state = GraphState::new();
+        state.process_event(GraphEvent::AddNode {
+            id: "ledger".to_string(),
+            info: EventNodeInfo {
+                name: "ledger".to_string(),
+                node_type: Some("database".to_string()),
+                ..default()
+            },
+        });
+        let data = state.as_graph_data();
+        let index = data.node_map["ledger"];
+
+        let missing = std::env::temp_dir().join("bevydot-missing-model.glb");
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Scene>()
+            .init_asset::<Gltf>()
+            .insert_resource(NodeModels(HashMap::from([(
+                "database".to_string(),
+                missing,
+            )])))
+            .insert_resource(GraphData(data))
+            .add_systems(Update, (request_node_models, swap_in_node_models).chain());
+        let node = app
+            .world_mut()
+            .spawn((
+                GraphNode {
+                    id: "ledger".to_string(),
+                    name: "ledger".to_string(),
+                    index,
+                },
+                Mesh3d::default(),
+            ))
+            .id();
+
+        app.update();
+        assert!(app.world().get::<PendingModel>(node).is_some());
+        for _ in 0..100 {
+            if app.world().get::<PendingModel>(node).is_none() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            app.update();
+        }
+        assert!(app.world().get::<PendingModel>(node).is_none());
+        assert!(app.world().get::<Mesh3d>(node).is_some());
+    }
+}
@@ -0,0 +1,246 @@
+use crate::editor::EditorCommand;
+use crate::graph_state::{GraphQuery, GraphState};
+use crate::input::is_url;
+use crate::keybindings::{Action, KeyBindings};
+use crate::loader::InputSource;
+use crate::settings::settings_panel_ui;
+use crate::sidecar::{SidecarFile, save_node_notes};
+use crate::status::StatusMessage;
+use crate::types::{
+    GraphData, GraphNode, HoverState, InspectorPanel, NodeNotes, NoteMarker, SearchState,
+};
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Where a note marker sits relative to its node's center
+const MARKER_OFFSET: Vec3 = Vec3::new(0.55, 0.55, 0.0);
+
+const MARKER_RADIUS: f32 = 0.12;
+
+/// Notes on nodes, their markers, and the inspector panel they are edited in
+pub struct NotesPlugin;
+
+impl Plugin for NotesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NodeNotes>()
+            .init_resource::<InspectorPanel>()
+            .init_resource::<EditorCommand>()
+            .add_systems(Update, (toggle_inspector, sync_note_markers))
+            .add_systems(
+                Update,
+                save_node_notes.run_if(
+                    resource_exists::<SidecarFile>
+                        .and(resource_changed::<NodeNotes>)
+                        .and(not(resource_added::<NodeNotes>)),
+                ),
+            )
+            .add_systems(EguiContextPass, inspector_panel_ui.after(settings_panel_ui));
+    }
+}
+
+/// Opens the inspector on the hovered node, or closes it when nothing is hovered
+pub fn toggle_inspector(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    hover: Res<HoverState>,
+    node_query: Query<&GraphNode>,
+    mut panel: ResMut<InspectorPanel>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::InspectNode, &keyboard_input) {
+        return;
+    }
+    panel.node = hover
+        .node
+        .and_then(|entity| node_query.get(entity).ok())
+        .map(|node| node.id.clone());
+}
+
+/// Shows the inspected node's details and lets its note be edited, with a
+/// button opening its statement in an editor when the input is a file
+#[allow(clippy::too_many_arguments)]
+pub fn inspector_panel_ui(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<InspectorPanel>,
+    mut notes: ResMut<NodeNotes>,
+    graph_data: Option<Res<GraphData>>,
+    graph_state: Res<GraphState>,
+    input: Option<Res<InputSource>>,
+    editor: Res<EditorCommand>,
+    time: Res<Time>,
+    mut message: ResMut<StatusMessage>,
+) {
+    let Some(id) = panel.node.clone() else {
+        return;
+    };
+    // The node may have been removed by an edit or a reload
+    let Some((data, index)) = graph_data
+        .as_deref()
+        .and_then(|data| Some((data, *data.node_map.get(&id)?)))
+    else {
+        panel.node = None;
+        return;
+    };
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let info = &data.graph[index];
+    let incoming = data.predecessors(&id).len();
+    let outgoing = data.successors(&id).len();
+    let mut note = notes.0.get(&id).cloned().unwrap_or_default();
+    let source = input
+        .as_deref()
+        .and_then(|input| input.location.as_deref())
+        .filter(|location| !is_url(location))
+        .map(Path::new)
+        .zip(graph_state.source_lines().nodes.get(&id).copied());
+    let mut open = true;
+
+    egui::Window::new("Inspector")
+        .open(&mut open)
+        .default_pos([10.0, 80.0])
+        .default_width(260.0)
+        .show(ctx, |ui| {
+            ui.heading(&info.name);
+            egui::Grid::new("inspector_fields").show(ui, |ui| {
+                ui.label("Id");
+                ui.label(&id);
+                ui.end_row();
+                ui.label("Type");
+                ui.label(info.node_type.as_deref().unwrap_or("none"));
+                ui.end_row();
+                ui.label("Level");
+                ui.label(info.level.to_string());
+                ui.end_row();
+                ui.label("Degree");
+                ui.label(format!("{incoming} in, {outgoing} out"));
+                ui.end_row();
+                if let Some((file, line)) = source {
+                    ui.label("Source");
+                    let name = file.file_name().unwrap_or(file.as_os_str());
+                    ui.label(format!("{}:{line}", name.to_string_lossy()));
+                    ui.end_row();
+                }
+            });
+            if let Some((file, line)) = source
+                && ui.button("Open in editor").clicked()
+                && let Err(e) = editor.open(file, line)
+            {
+                message.show(format!("Can't open the editor: {e}"), &time);
+            }
+
+            ui.separator();
+            ui.label("Notes");
+            if ui
+                .add(
+                    egui::TextEdit::multiline(&mut note)
+                        .hint_text("Add a note for this node")
+                        .desired_rows(6),
+                )
+                .changed()
+            {
+                if note.is_empty() {
+                    notes.0.remove(&id);
+                } else {
+                    notes.0.insert(id.clone(), note);
+                }
+            }
+        });
+
+    if !open {
+        panel.node = None;
+    }
+}
+
+/// Adds a marker to nodes with a note and removes it from nodes without one
+pub fn sync_note_markers(
+    mut commands: Commands,
+    notes: Res<NodeNotes>,
+    node_query: Query<(Entity, &GraphNode)>,
+    marker_query: Query<(Entity, &ChildOf), With<NoteMarker>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut marker_assets: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+) {
+    let mut markers: HashMap<Entity, Entity> = marker_query
+        .iter()
+        .map(|(marker, child_of)| (child_of.parent(), marker))
+        .collect();
+
+    for (entity, node) in &node_query {
+        let has_note = notes.0.contains_key(&node.id);
+        match (has_note, markers.remove(&entity)) {
+            (true, None) => {
+                let (mesh, material) = marker_assets.get_or_insert_with(|| {
+                    (
+                        meshes.add(Sphere::new(MARKER_RADIUS)),
+                        materials.add(StandardMaterial {
+                            base_color: Color::srgb(1.0, 0.85, 0.3),
+                            unlit: true,
+                            ..default()
+                        }),
+                    )
+                });
+                commands.entity(entity).with_child((
+                    Mesh3d(mesh.clone()),
+                    MeshMaterial3d(material.clone()),
+                    Transform::from_translation(MARKER_OFFSET),
+                    NotShadowCaster,
+                    Pickable::IGNORE,
+                    NoteMarker,
+                ));
+            }
+            (false, Some(marker)) => commands.entity(marker).despawn(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::NodeIndex;
+
+    #[test]
+    fn test_markers_follow_notes() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<NodeNotes>()
+            .add_systems(Update, sync_note_markers);
+        let node = app
+            .world_mut()
+            .spawn(GraphNode {
+                id: "api".to_string(),
+                name: "api".to_string(),
+                index: NodeIndex::new(0),
+            })
+            .id();
+        let marker_count = |app: &mut App| {
+            app.world_mut()
+                .query_filtered::<&ChildOf, With<NoteMarker>>()
+                .iter(app.world())
+                .filter(|child_of| child_of.parent() == node)
+                .count()
+        };
+
+        app.update();
+        assert_eq!(marker_count(&mut app), 0);
+
+        app.world_mut()
+            .resource_mut::<NodeNotes>()
+            .0
+            .insert("api".to_string(), "Rate limited".to_string());
+        app.update();
+        app.update();
+        assert_eq!(marker_count(&mut app), 1);
+
+        app.world_mut().resource_mut::<NodeNotes>().0.clear();
+        app.update();
+        assert_eq!(marker_count(&mut app), 0);
+    }
+}
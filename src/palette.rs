@@ -0,0 +1,158 @@
+//! Colors node types are drawn in, including palettes that stay apart for
+//! the common kinds of color blindness
+
+use crate::aggregate::CLUSTER_TYPE;
+use crate::visualization::get_node_appearance;
+use bevy::prelude::*;
+use std::str::FromStr;
+
+/// Color of untyped nodes and types no palette slot is kept for
+const NEUTRAL: [u8; 3] = [153, 153, 153];
+
+/// Colors of the Okabe-Ito palette, told apart without red-green vision
+const OKABE_ITO: [[u8; 3]; 7] = [
+    [213, 94, 0],    // Vermillion
+    [230, 159, 0],   // Orange
+    [86, 180, 233],  // Sky blue
+    [0, 158, 115],   // Bluish green
+    [204, 121, 167], // Reddish purple
+    [0, 114, 178],   // Blue
+    [240, 228, 66],  // Yellow
+];
+
+/// Colors of the IBM palette, with a teal and a navy added; none lean on
+/// the reds protanopes see as dark
+const IBM: [[u8; 3]; 7] = [
+    [220, 38, 127],  // Magenta
+    [254, 97, 0],    // Orange
+    [100, 143, 255], // Blue
+    [34, 163, 159],  // Teal
+    [120, 94, 240],  // Purple
+    [27, 42, 107],   // Navy
+    [255, 176, 0],   // Gold
+];
+
+/// Colors of Paul Tol's vibrant palette, which keeps blues and yellows
+/// apart by lightness for tritanopes
+const TOL_VIBRANT: [[u8; 3]; 7] = [
+    [204, 51, 17],   // Red
+    [238, 119, 51],  // Orange
+    [51, 187, 238],  // Cyan
+    [0, 153, 136],   // Teal
+    [238, 51, 119],  // Magenta
+    [0, 119, 187],   // Blue
+    [221, 221, 221], // Pale grey
+];
+
+/// Colors node types are drawn in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Palette {
+    /// The viewer's own colors
+    #[default]
+    Standard,
+    /// Safe for red-green color blindness with weak green
+    Deuteranopia,
+    /// Safe for red-green color blindness with weak red
+    Protanopia,
+    /// Safe for blue-yellow color blindness
+    Tritanopia,
+}
+
+impl Palette {
+    pub const ALL: [Self; 4] = [
+        Self::Standard,
+        Self::Deuteranopia,
+        Self::Protanopia,
+        Self::Tritanopia,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Standard => "standard",
+            Self::Deuteranopia => "deuteranopia",
+            Self::Protanopia => "protanopia",
+            Self::Tritanopia => "tritanopia",
+        }
+    }
+
+    /// Color of nodes of `node_type`
+    pub fn node_color(self, node_type: Option<&str>) -> Color {
+        let colors = match self {
+            Self::Standard => return get_node_appearance(node_type).0,
+            Self::Deuteranopia => OKABE_ITO,
+            Self::Protanopia => IBM,
+            Self::Tritanopia => TOL_VIBRANT,
+        };
+        let [r, g, b] = color_slot(node_type).map_or(NEUTRAL, |slot| colors[slot]);
+        Srgba::rgb_u8(r, g, b).into()
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|palette| palette.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!(
+                    "unknown palette '{s}' (expected standard, deuteranopia, protanopia or \
+                     tritanopia)"
+                )
+            })
+    }
+}
+
+/// Which of a palette's colors a node type takes, or None for the neutral
+/// color; related types share a slot as they do a hue in the standard colors
+fn color_slot(node_type: Option<&str>) -> Option<usize> {
+    Some(match node_type? {
+        "organization" => 0,
+        "line_of_business" | CLUSTER_TYPE => 1,
+        "site" => 2,
+        "team" | "actor:participant" => 3,
+        "user" | "external" => 4,
+        "database" => 5,
+        "process" => 6,
+        t if t.starts_with("actor:") => 1,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_from_str() {
+        assert_eq!("Tritanopia".parse(), Ok(Palette::Tritanopia));
+        assert!("sepia".parse::<Palette>().is_err());
+    }
+
+    #[test]
+    fn test_palettes_keep_the_known_types_apart() {
+        let types = [
+            "organization",
+            "line_of_business",
+            "site",
+            "team",
+            "user",
+            "database",
+            "process",
+        ];
+        for palette in Palette::ALL {
+            let mut colors: Vec<_> = types
+                .iter()
+                .map(|&t| palette.node_color(Some(t)).to_srgba().to_u8_array())
+                .collect();
+            colors.sort_unstable();
+            colors.dedup();
+            assert_eq!(colors.len(), types.len(), "{}", palette.name());
+        }
+        assert_eq!(
+            Palette::Standard.node_color(Some("team")),
+            get_node_appearance(Some("team")).0
+        );
+    }
+}
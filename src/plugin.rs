@@ -0,0 +1,368 @@
+use crate::accessibility::AccessibilityPlugin;
+use crate::aggregate::{AggregatePlugin, Aggregation};
+use crate::antialiasing::AntiAliasingPlugin;
+use crate::camera::{CameraPlugin, setup_camera};
+use crate::captions::CaptionsPlugin;
+use crate::capture::CapturePlugin;
+use crate::clipboard::ClipboardPlugin;
+use crate::cycles::CyclesPlugin;
+use crate::describe::DescribePlugin;
+use crate::diagnostics::DiagnosticsOverlayPlugin;
+use crate::edge_lines::EdgeLinesPlugin;
+use crate::edit::EditPlugin;
+use crate::export::{export_subgraph, save_graph, save_session};
+use crate::flash::FlashPlugin;
+use crate::fonts::FontsPlugin;
+use crate::graph_state::GraphState;
+use crate::grid::{draw_reference_gizmos, toggle_reference_gizmos};
+use crate::highlight::HighlightPlugin;
+use crate::history::{EditHistory, record_layout_changes, undo_redo};
+use crate::hover::{setup_hover_tooltip, update_hover_tooltip, update_hovered_node};
+use crate::keybindings::KeyBindings;
+use crate::kiosk::KioskPlugin;
+use crate::label_atlas::LabelAtlasPlugin;
+use crate::layout::LayoutPlugin;
+use crate::legend::LegendPlugin;
+use crate::lighting::{Lighting, apply_fog, apply_lighting, fog_changed};
+use crate::live::{LiveEvents, LivePlugin, LiveStatus, apply_live_events};
+use crate::loader::{InputSource, LoadError, reload_input};
+use crate::merged_edges::MergedEdgesPlugin;
+use crate::metrics::MetricsPlugin;
+use crate::models::{NodeModels, request_node_models, swap_in_node_models};
+use crate::notes::NotesPlugin;
+use crate::reachability::ReachabilityPlugin;
+use crate::scene_build::SceneBuildPlugin;
+use crate::screen_clusters::ScreenClustersPlugin;
+use crate::scripting::ScriptingPlugin;
+use crate::search::{SearchPlugin, setup_search_ui};
+use crate::selection::SelectionPlugin;
+use crate::settings::SettingsPlugin;
+use crate::sources::{GraphEventSource, SourceError, SourceFormat, SourceRegistry};
+use crate::staleness::NodeActivity;
+use crate::status::{StatusMessage, setup_status_bar, update_status_bar};
+use crate::theme::{Theme, ThemePlugin};
+use crate::timeline::TimelinePlugin;
+use crate::tour::TourPlugin;
+use crate::types::{
+    self, CameraSettings, ExportSettings, GridSettings, HoverState, LabelSettings, LayoutSettings,
+    PinnedLabels, ReducedMotion, ScreenClusters, SearchState, SettingsPanel, ViewSettings,
+};
+use crate::ui::{
+    NodeLabelsPlugin, setup_ui, toggle_help_overlay, update_error_panel, update_help_overlay,
+    yield_keyboard_to_egui,
+};
+use crate::visualization::{
+    create_graph_visualization, refresh_graph_scene, scene_needs_refresh, update_edge_positions,
+};
+use crate::xray::XrayPlugin;
+use bevy::input::InputSystem;
+use bevy::picking::mesh_picking::MeshPickingPlugin;
+use bevy::prelude::*;
+use bevy::text::DEFAULT_FONT_DATA;
+use bevy_egui::EguiPlugin;
+use bevy_rich_text3d::{LoadFonts, Text3dPlugin};
+
+/// The dotspace viewer: scene setup, live updates, labels, search, and camera
+///
+/// Resources that aren't already present are initialized with defaults, so an
+/// app only needs to insert the ones it wants to configure (usually `GraphState`).
+pub struct DotspacePlugin;
+
+impl Plugin for DotspacePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin {
+                enable_multipass_for_primary_context: true,
+            });
+        }
+        // World-space node labels, rendered with bevy's built-in font and the FontStack
+        if !app.is_plugin_added::<Text3dPlugin>() {
+            app.add_plugins(Text3dPlugin::default());
+        }
+        app.world_mut()
+            .get_resource_or_init::<LoadFonts>()
+            .font_embedded
+            .push(DEFAULT_FONT_DATA);
+        // Lets labels be clicked to pin them
+        if !app.is_plugin_added::<MeshPickingPlugin>() {
+            app.add_plugins(MeshPickingPlugin);
+        }
+
+        init_resources(app);
+        app.add_plugins((
+            (
+                AccessibilityPlugin,
+                AggregatePlugin,
+                AntiAliasingPlugin,
+                CameraPlugin,
+                CaptionsPlugin,
+                CapturePlugin,
+                ClipboardPlugin,
+                CyclesPlugin,
+                DescribePlugin,
+                DiagnosticsOverlayPlugin,
+                EdgeLinesPlugin,
+                EditPlugin,
+            ),
+            (
+                FlashPlugin,
+                FontsPlugin,
+                HighlightPlugin,
+                KioskPlugin,
+                LabelAtlasPlugin,
+                LayoutPlugin,
+                LegendPlugin,
+                LivePlugin,
+                MergedEdgesPlugin,
+                MetricsPlugin,
+                NodeLabelsPlugin,
+            ),
+            (
+                NotesPlugin,
+                ReachabilityPlugin,
+                SceneBuildPlugin,
+                ScreenClustersPlugin,
+                ScriptingPlugin,
+                SearchPlugin,
+                SelectionPlugin,
+                SettingsPlugin,
+                ThemePlugin,
+                TimelinePlugin,
+                TourPlugin,
+                XrayPlugin,
+            ),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            (
+                apply_live_events.run_if(resource_exists::<LiveEvents>),
+                reload_input.run_if(resource_exists::<InputSource>),
+                refresh_graph_scene.run_if(scene_needs_refresh),
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            update_error_panel.run_if(resource_changed::<LoadError>),
+        )
+        .add_systems(Update, (update_hovered_node, update_hover_tooltip).chain())
+        .add_systems(PreUpdate, yield_keyboard_to_egui.after(InputSystem))
+        .add_systems(Update, toggle_help_overlay)
+        .add_systems(Update, update_status_bar)
+        .add_systems(Update, (export_subgraph, save_graph, save_session))
+        .add_systems(Update, (undo_redo, record_layout_changes).chain())
+        .add_systems(Update, (request_node_models, swap_in_node_models).chain())
+        .add_systems(Update, apply_lighting.run_if(resource_changed::<Lighting>))
+        .add_systems(Update, apply_fog.run_if(fog_changed))
+        .add_systems(
+            Update,
+            (toggle_reference_gizmos, draw_reference_gizmos).chain(),
+        )
+        .add_systems(
+            Update,
+            update_help_overlay.run_if(resource_changed::<KeyBindings>),
+        )
+        .add_systems(Update, update_edge_positions);
+    }
+}
+
+/// Initializes the resources the app didn't insert with their defaults
+fn init_resources(app: &mut App) {
+    app.init_resource::<GraphState>()
+        .init_resource::<CameraSettings>()
+        .init_resource::<LabelSettings>()
+        .init_resource::<SearchState>()
+        .init_resource::<LoadError>()
+        .init_resource::<SourceRegistry>()
+        .init_resource::<LayoutSettings>()
+        .init_resource::<ViewSettings>()
+        .init_resource::<SettingsPanel>()
+        .init_resource::<HoverState>()
+        .init_resource::<PinnedLabels>()
+        .init_resource::<KeyBindings>()
+        .init_resource::<Theme>()
+        .init_resource::<GridSettings>()
+        .init_resource::<Lighting>()
+        .init_resource::<NodeModels>()
+        .init_resource::<ExportSettings>()
+        .init_resource::<StatusMessage>()
+        .init_resource::<EditHistory>()
+        .init_resource::<ScreenClusters>()
+        .init_resource::<ReducedMotion>();
+}
+
+/// Registration hooks for sources implemented outside this crate
+pub trait DotspaceAppExt {
+    /// Adds a format that is detected and loaded like the built-in ones
+    #[allow(dead_code)] // Used by crates that embed the viewer
+    fn register_source_format(&mut self, format: SourceFormat) -> &mut Self;
+
+    /// Applies a source's initial events to the graph and subscribes it for
+    /// live updates. Sources can also be added once the app is running by
+    /// calling `LiveEvents::subscribe` from a system.
+    fn add_live_source(
+        &mut self,
+        source: impl GraphEventSource + 'static,
+    ) -> Result<&mut Self, SourceError>;
+
+    /// Serves the gRPC control API on `addr`, so other tools can send events
+    /// and query the viewer
+    #[cfg(feature = "grpc")]
+    fn add_grpc_server(&mut self, addr: std::net::SocketAddr) -> Result<&mut Self, SourceError>;
+}
+
+impl DotspaceAppExt for App {
+    fn register_source_format(&mut self, format: SourceFormat) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<SourceRegistry>()
+            .register(format);
+        self
+    }
+
+    fn add_live_source(
+        &mut self,
+        source: impl GraphEventSource + 'static,
+    ) -> Result<&mut Self, SourceError> {
+        let events = source.events()?;
+        let world = self.world_mut();
+        world
+            .get_resource_or_init::<GraphState>()
+            .process_events(events);
+        world
+            .get_resource_or_init::<LiveEvents>()
+            .subscribe(&source)?;
+        world
+            .get_resource_or_init::<LiveStatus>()
+            .sources
+            .push(source.source_name());
+        world.init_resource::<NodeActivity>();
+        Ok(self)
+    }
+
+    #[cfg(feature = "grpc")]
+    fn add_grpc_server(&mut self, addr: std::net::SocketAddr) -> Result<&mut Self, SourceError> {
+        let (source, queries) = crate::grpc::GrpcSource::new(addr);
+        self.add_live_source(source)?
+            .insert_resource(queries)
+            .add_systems(Update, crate::grpc::answer_grpc_queries);
+        Ok(self)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    graph_state: Res<GraphState>,
+    camera_settings: Res<CameraSettings>,
+    layout: Res<LayoutSettings>,
+    view: Res<ViewSettings>,
+    aggregation: Res<Aggregation>,
+) {
+    // Convert to GraphData for compatibility
+    let graph_data = types::GraphData(aggregation.shown(graph_state.as_graph_data()));
+
+    // Setup camera
+    setup_camera(&mut commands, &camera_settings);
+
+    // Create nodes and edges
+    create_graph_visualization(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &graph_data,
+        &layout,
+        &view,
+        None,
+    );
+
+    // Store graph data as a resource for later use
+    commands.insert_resource(graph_data);
+
+    // Setup UI
+    setup_ui(&mut commands);
+    setup_search_ui(&mut commands);
+    setup_hover_tooltip(&mut commands);
+    setup_status_bar(&mut commands);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captions::ShowCaption;
+    use crate::events::{EventNodeInfo, GraphEvent};
+    use crate::live::apply_live_events;
+    use std::sync::mpsc::Sender;
+
+    /// Sends one node up front and another once subscribed
+    struct TestSource;
+
+    fn node(id: &str) -> GraphEvent {
+        GraphEvent::AddNode {
+            id: id.to_string(),
+            info: EventNodeInfo {
+                name: id.to_string(),
+                node_type: None,
+                level: 0,
+                ..Default::default()
+            },
+        }
+    }
+
+    impl GraphEventSource for TestSource {
+        fn source_name(&self) -> &'static str {
+            "Test"
+        }
+
+        fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+            Ok(vec![node("initial")])
+        }
+
+        fn subscribe(&self, sender: Sender<GraphEvent>) -> Result<(), SourceError> {
+            [
+                node("live"),
+                GraphEvent::ShowCaption {
+                    text: "Live now".to_string(),
+                    duration: Some(5.0),
+                    anchor_node: Some("live".to_string()),
+                },
+            ]
+            .into_iter()
+            .try_for_each(|event| {
+                sender
+                    .send(event)
+                    .map_err(|e| SourceError::InvalidInput(e.to_string()))
+            })
+        }
+    }
+
+    #[test]
+    fn test_external_sources_register_with_app() {
+        let mut app = App::new();
+        app.register_source_format(SourceFormat {
+            name: "test",
+            description: "Test format",
+            detect: |_| 1.0,
+            create: |_| Box::new(TestSource),
+        });
+        app.add_live_source(TestSource).unwrap();
+        assert_eq!(app.world().resource::<GraphState>().node_count(), 1);
+
+        app.init_resource::<Time>()
+            .add_event::<ShowCaption>()
+            .add_systems(Update, apply_live_events);
+        app.update();
+        assert_eq!(app.world().resource::<GraphState>().node_count(), 2);
+        // Captions from live sources are passed on to be shown
+        let captions = app.world().resource::<Events<ShowCaption>>();
+        let mut cursor = captions.get_cursor();
+        let shown: Vec<_> = cursor.read(captions).collect();
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].anchor_node.as_deref(), Some("live"));
+
+        let registry = app.world().resource::<SourceRegistry>();
+        assert_eq!(registry.detect("anything").unwrap().format, "test");
+    }
+}
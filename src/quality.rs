@@ -0,0 +1,110 @@
+//! Presets trading looks for speed, so slow machines and big graphs can be
+//! set up with one `--quality` option
+
+use crate::aggregate;
+use crate::types::LabelSettings;
+use bevy::prelude::Msaa;
+use std::str::FromStr;
+
+/// How much drawing work the viewer takes on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quality {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+/// What a quality level sets; options given on their own win over it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityPreset {
+    pub shadows: bool,
+    pub msaa: Msaa,
+    /// Leave out pulses, flowing particles and blinking
+    pub reduced_motion: bool,
+    /// Draw edges as lines instead of tubes
+    pub edge_lines: bool,
+    pub label_distance: f32,
+    /// Nodes above which labels come from a texture atlas
+    pub atlas_labels_above: usize,
+    /// Nodes above which the graph opens as hubs and clusters
+    pub aggregate_above: usize,
+}
+
+impl Quality {
+    pub const ALL: [Self; 3] = [Self::Low, Self::Medium, Self::High];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+
+    /// The settings of the level; medium is the viewer's defaults
+    pub const fn preset(self) -> QualityPreset {
+        match self {
+            Self::Low => QualityPreset {
+                shadows: false,
+                msaa: Msaa::Off,
+                reduced_motion: true,
+                edge_lines: true,
+                label_distance: 10.0,
+                atlas_labels_above: 500,
+                aggregate_above: 10_000,
+            },
+            Self::Medium => QualityPreset {
+                shadows: false,
+                msaa: Msaa::Sample4,
+                reduced_motion: false,
+                edge_lines: false,
+                label_distance: 15.0,
+                atlas_labels_above: LabelSettings::DEFAULT_ATLAS_ABOVE,
+                aggregate_above: aggregate::DEFAULT_THRESHOLD,
+            },
+            Self::High => QualityPreset {
+                shadows: true,
+                msaa: Msaa::Sample8,
+                reduced_motion: false,
+                edge_lines: false,
+                label_distance: 30.0,
+                atlas_labels_above: 10_000,
+                aggregate_above: 200_000,
+            },
+        }
+    }
+}
+
+impl FromStr for Quality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|quality| quality.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown quality '{s}' (expected low, medium or high)"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality_from_str() {
+        assert_eq!("HIGH".parse(), Ok(Quality::High));
+        assert!("ultra".parse::<Quality>().is_err());
+    }
+
+    #[test]
+    fn test_medium_quality_is_the_defaults() {
+        let medium = Quality::Medium.preset();
+        assert_eq!(
+            medium.atlas_labels_above,
+            LabelSettings::default().atlas_above
+        );
+        assert_eq!(medium.aggregate_above, aggregate::DEFAULT_THRESHOLD);
+        assert_eq!(medium.msaa, Msaa::default());
+    }
+}
@@ -0,0 +1,186 @@
+use crate::graph_state;
+use crate::keybindings::{Action, KeyBindings};
+use crate::status::StatusMessage;
+use crate::types::{GraphData, GraphNode, HoverState, Reachability, SearchState, SelectedNodes};
+use bevy::prelude::*;
+use petgraph::Direction;
+use petgraph::graph::NodeIndex;
+use std::collections::{HashMap, VecDeque};
+
+/// Shade of the nodes a source reaches
+const DOWNSTREAM_COLOR: Color = Color::srgb(1.0, 0.35, 0.1);
+
+/// Shade of the nodes a source depends on
+const UPSTREAM_COLOR: Color = Color::srgb(0.35, 0.45, 1.0);
+
+/// How much dimmer each hop away from the source is shaded; direct neighbors
+/// are at full intensity
+const FADE_PER_HOP: f32 = 0.2;
+
+/// Intensity of the farthest shaded nodes
+const MIN_INTENSITY: f32 = 0.25;
+
+/// Shading what a node reaches (downstream) and what reaches it (upstream)
+pub struct ReachabilityPlugin;
+
+impl Plugin for ReachabilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Reachability>().add_systems(
+            Update,
+            (
+                toggle_reachability,
+                find_reachable_nodes.run_if(
+                    resource_changed::<Reachability>.or(resource_exists_and_changed::<GraphData>),
+                ),
+            )
+                .chain(),
+        );
+    }
+}
+
+impl Reachability {
+    /// Outline color and intensity of a shaded node, fading with distance
+    /// from the source
+    #[allow(clippy::cast_precision_loss)]
+    pub fn node_shade(&self, node: NodeIndex) -> Option<(Color, f32)> {
+        let (color, hops) = if let Some(&hops) = self.downstream.get(&node) {
+            (DOWNSTREAM_COLOR, hops)
+        } else {
+            (UPSTREAM_COLOR, *self.upstream.get(&node)?)
+        };
+        let intensity = FADE_PER_HOP.mul_add(-((hops - 1) as f32), 1.0);
+        Some((color, intensity.max(MIN_INTENSITY)))
+    }
+}
+
+/// Hops from `start` to every node reachable from it along edges in `direction`
+pub fn reachable_from(
+    data: &graph_state::GraphData,
+    start: NodeIndex,
+    direction: Direction,
+) -> HashMap<NodeIndex, usize> {
+    let mut hops = HashMap::from([(start, 0)]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        let next = hops[&node] + 1;
+        for neighbor in data.graph.neighbors_directed(node, direction) {
+            hops.entry(neighbor).or_insert_with(|| {
+                queue.push_back(neighbor);
+                next
+            });
+        }
+    }
+    // The start is shaded as selected, not as reachable from itself
+    hops.remove(&start);
+    hops
+}
+
+/// Shades from the selected node (or the hovered one, with no selection);
+/// pressing again on the same node turns the shading off
+pub fn toggle_reachability(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    search_state: Res<SearchState>,
+    hover: Res<HoverState>,
+    selection: Res<SelectedNodes>,
+    node_query: Query<&GraphNode>,
+    mut reachability: ResMut<Reachability>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::ToggleReachability, &keyboard_input) {
+        return;
+    }
+    let hovered = hover
+        .node
+        .and_then(|entity| node_query.get(entity).ok())
+        .map(|node| node.id.clone());
+    let only_selected = (selection.0.len() == 1)
+        .then(|| selection.0.iter().next().cloned())
+        .flatten();
+    // With several nodes selected, the hovered one picks between them
+    let source = hovered
+        .clone()
+        .filter(|id| selection.0.contains(id))
+        .or(only_selected)
+        .or_else(|| hovered.filter(|_| selection.0.is_empty()));
+
+    reachability.source = source.filter(|id| reachability.source.as_ref() != Some(id));
+}
+
+/// Recomputes the shaded nodes when the source or the graph changes
+pub fn find_reachable_nodes(
+    graph_data: Option<Res<GraphData>>,
+    time: Res<Time>,
+    mut reachability: ResMut<Reachability>,
+    mut message: ResMut<StatusMessage>,
+) {
+    let chosen = reachability.is_changed();
+    // Bypass change detection so writing the results doesn't re-trigger this system
+    let reachability = reachability.bypass_change_detection();
+    let start = reachability
+        .source
+        .as_ref()
+        .zip(graph_data.as_deref())
+        .and_then(|(id, data)| Some((data, *data.node_map.get(id)?)));
+    let Some((data, start)) = start else {
+        // Off, or the source node was removed
+        reachability.source = None;
+        reachability.downstream.clear();
+        reachability.upstream.clear();
+        return;
+    };
+
+    reachability.downstream = reachable_from(data, start, Direction::Outgoing);
+    reachability.upstream = reachable_from(data, start, Direction::Incoming);
+    if chosen {
+        message.show(
+            format!(
+                "{}: {} downstream, {} upstream",
+                data.graph[start].name,
+                reachability.downstream.len(),
+                reachability.upstream.len()
+            ),
+            &time,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_reachability_in_both_directions() {
+        let content = r"digraph {
+            web -> api
+            api -> db
+            api -> cache
+            db -> disk
+            batch -> db
+        }";
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+        let index = |id: &str| data.node_map[id];
+
+        let downstream = reachable_from(&data, index("api"), Direction::Outgoing);
+        let upstream = reachable_from(&data, index("api"), Direction::Incoming);
+        assert_eq!(downstream.len(), 3);
+        assert_eq!(downstream[&index("disk")], 2);
+        assert!(!downstream.contains_key(&index("batch")));
+        assert_eq!(upstream.len(), 1);
+        assert_eq!(upstream[&index("web")], 1);
+
+        // Shading fades from full intensity next to the source
+        let reachability = Reachability {
+            source: Some("api".to_string()),
+            downstream,
+            upstream,
+        };
+        let (_, near) = reachability.node_shade(index("db")).unwrap();
+        let (_, far) = reachability.node_shade(index("disk")).unwrap();
+        assert!((near - 1.0).abs() < f32::EPSILON);
+        assert!(far < near);
+        assert!(reachability.node_shade(index("batch")).is_none());
+    }
+}
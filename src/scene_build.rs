@@ -0,0 +1,337 @@
+//! Spawning a built scene a chunk at a time, so a big graph builds in over a
+//! few frames behind a progress bar instead of holding up the first frame
+
+use crate::types::{
+    Appearing, BuildProgress, BuildProgressFill, BuildProgressText, EdgeArrowHead, EdgeFrom,
+    EdgeShaft, EdgeTo, GraphData, GraphEdge, GraphNode, LayoutTransition, NodeChange, PortOffsets,
+    ThemedPanel, ThemedText,
+};
+use crate::visualization::refresh_graph_scene;
+use bevy::prelude::*;
+use petgraph::graph::NodeIndex;
+use std::collections::{HashMap, VecDeque};
+
+/// Most entities spawned each frame while a scene builds
+pub const SPAWN_PER_FRAME: usize = 2_000;
+
+/// Width of the progress bar's track
+const BAR_WIDTH: f32 = 240.0;
+
+/// Spawns queued scenes a chunk a frame, showing how far along they are
+pub struct SceneBuildPlugin;
+
+impl Plugin for SceneBuildPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_build_progress).add_systems(
+            Update,
+            (
+                spawn_scene_chunk.after(refresh_graph_scene),
+                update_build_progress,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// A node with its look worked out, waiting to be spawned
+pub struct QueuedNode {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+    pub transform: Transform,
+    pub node: GraphNode,
+    pub change: Option<NodeChange>,
+    pub appearing: Option<Appearing>,
+    pub transition: Option<LayoutTransition>,
+}
+
+/// An edge with its look worked out, waiting for its nodes to be spawned
+pub struct QueuedEdge {
+    pub edge: GraphEdge,
+    pub shaft: Handle<Mesh>,
+    /// Left off edges the graph has nothing on
+    pub arrow_head: Option<Handle<Mesh>>,
+    pub material: Handle<StandardMaterial>,
+    pub appearing: Option<Appearing>,
+    pub ports: Option<PortOffsets>,
+}
+
+impl QueuedEdge {
+    /// The edge and its parts
+    fn entities(&self) -> usize {
+        2 + usize::from(self.arrow_head.is_some())
+    }
+}
+
+/// The nodes and edges of a scene still to be spawned, nodes first
+///
+/// Rebuilding the scene replaces it, dropping whatever was left.
+#[derive(Resource, Default)]
+pub struct SceneBuild {
+    nodes: VecDeque<QueuedNode>,
+    edges: VecDeque<QueuedEdge>,
+    node_entities: HashMap<NodeIndex, Entity>,
+    total: usize,
+    spawned: usize,
+}
+
+impl SceneBuild {
+    pub fn new(nodes: Vec<QueuedNode>, edges: Vec<QueuedEdge>) -> Self {
+        let total = nodes.len() + edges.iter().map(QueuedEdge::entities).sum::<usize>();
+        Self {
+            nodes: nodes.into(),
+            edges: edges.into(),
+            node_entities: HashMap::new(),
+            total,
+            spawned: 0,
+        }
+    }
+
+    /// Share of the scene's entities spawned so far, from 0 to 1
+    #[allow(clippy::cast_precision_loss)]
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.spawned as f32 / self.total as f32
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.nodes.is_empty() && self.edges.is_empty()
+    }
+
+    /// Spawns queued nodes, then edges, until about `budget` entities are
+    /// spawned or none are left
+    pub fn spawn_chunk(&mut self, commands: &mut Commands, budget: usize) {
+        let mut spawned = 0;
+        while spawned < budget
+            && let Some(queued) = self.nodes.pop_front()
+        {
+            let index = queued.node.index;
+            let name = Name::new(queued.node.name.clone());
+            let mut node = commands.spawn((
+                Mesh3d(queued.mesh),
+                MeshMaterial3d(queued.material),
+                queued.transform,
+                queued.node,
+                name,
+            ));
+            if let Some(change) = queued.change {
+                node.insert(change);
+            }
+            if let Some(appearing) = queued.appearing {
+                node.insert(appearing);
+            }
+            if let Some(transition) = queued.transition {
+                node.insert(transition);
+            }
+            self.node_entities.insert(index, node.id());
+            spawned += 1;
+        }
+        while spawned < budget
+            && let Some(queued) = self.edges.pop_front()
+        {
+            spawned += queued.entities();
+            let (Some(&from), Some(&to)) = (
+                self.node_entities.get(&queued.edge.from),
+                self.node_entities.get(&queued.edge.to),
+            ) else {
+                continue;
+            };
+            // The shaft and arrow head are children, so they move, hide and
+            // despawn with the edge, which itself despawns with either of its nodes
+            let mut edge = commands.spawn((
+                queued.edge,
+                EdgeFrom(from),
+                EdgeTo(to),
+                Transform::default(),
+                Visibility::default(),
+            ));
+            edge.with_child((
+                Mesh3d(queued.shaft),
+                MeshMaterial3d(queued.material.clone()),
+                Transform::default(),
+                EdgeShaft,
+            ));
+            // Add arrow head for directional edges
+            if let Some(arrow_head) = queued.arrow_head {
+                edge.with_child((
+                    Mesh3d(arrow_head),
+                    MeshMaterial3d(queued.material),
+                    Transform::default(),
+                    EdgeArrowHead,
+                ));
+            }
+            if let Some(appearing) = queued.appearing {
+                edge.insert(appearing);
+            }
+            if let Some(ports) = queued.ports {
+                edge.insert(ports);
+            }
+        }
+        self.spawned = (self.spawned + spawned).min(self.total);
+    }
+}
+
+/// Spawns the next chunk of the scene being built, and lets it go once all
+/// of it is spawned
+///
+/// The frame after the last chunk, the graph data is marked changed, so what
+/// restyles nodes when the graph changes also sees those spawned late.
+pub fn spawn_scene_chunk(
+    mut commands: Commands,
+    build: Option<ResMut<SceneBuild>>,
+    graph_data: Option<ResMut<GraphData>>,
+) {
+    let Some(mut build) = build else {
+        return;
+    };
+    if build.is_done() {
+        commands.remove_resource::<SceneBuild>();
+        if let Some(mut graph_data) = graph_data {
+            graph_data.set_changed();
+        }
+    } else if !build.is_added() {
+        // The first chunk was spawned along with queueing the rest
+        build.spawn_chunk(&mut commands, SPAWN_PER_FRAME);
+    }
+}
+
+fn setup_build_progress(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                // Centered, above the status bar
+                bottom: Val::Px(48.0),
+                left: Val::Percent(50.0),
+                margin: UiRect::left(Val::Px(-BAR_WIDTH / 2.0 - 10.0)),
+                padding: UiRect::all(Val::Px(10.0)),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.05, 0.05, 0.1, 0.85)),
+            Visibility::Hidden,
+            BuildProgress,
+            ThemedPanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                BuildProgressText,
+                ThemedText,
+            ));
+            panel
+                .spawn((
+                    Node {
+                        width: Val::Px(BAR_WIDTH),
+                        height: Val::Px(6.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.15)),
+                ))
+                .with_child((
+                    Node {
+                        width: Val::Percent(0.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.3, 0.6, 0.9)),
+                    BuildProgressFill,
+                ));
+        });
+}
+
+/// Shows the progress bar while a scene too big for one frame builds in
+fn update_build_progress(
+    build: Option<Res<SceneBuild>>,
+    mut panel_query: Query<&mut Visibility, With<BuildProgress>>,
+    mut text_query: Query<&mut Text, With<BuildProgressText>>,
+    mut fill_query: Query<&mut Node, With<BuildProgressFill>>,
+) {
+    let Ok(mut visibility) = panel_query.single_mut() else {
+        return;
+    };
+    let Some(build) = build.filter(|build| !build.is_done()) else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+    visibility.set_if_neq(Visibility::Inherited);
+    if let Ok(mut text) = text_query.single_mut() {
+        text.0 = progress_text(build.spawned, build.total);
+    }
+    if let Ok(mut fill) = fill_query.single_mut() {
+        fill.width = Val::Percent(build.progress() * 100.0);
+    }
+}
+
+/// The line over the progress bar
+pub fn progress_text(spawned: usize, total: usize) -> String {
+    format!("Building scene: {spawned} of {total} entities")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregate::Aggregation;
+    use crate::generate::{GraphModel, generate_dot};
+    use crate::graph_state::GraphState;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+    use crate::types::{LayoutSettings, Timeline, ViewSettings};
+
+    #[test]
+    fn test_big_scenes_build_in_over_several_frames() {
+        let dot = generate_dot(GraphModel::Tree, 1_500, 1_499, 0);
+        let (state, _) = load_graph(&SourceRegistry::default(), &dot, Some("dot")).unwrap();
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<LayoutSettings>()
+            .init_resource::<ViewSettings>()
+            .init_resource::<Timeline>()
+            .init_resource::<Aggregation>()
+            .insert_resource(state)
+            .add_systems(Update, refresh_graph_scene.run_if(run_once))
+            .add_plugins(SceneBuildPlugin);
+        app.world_mut().resource_mut::<GraphState>().set_changed();
+
+        // 1,500 nodes and 1,499 edges of three entities each
+        app.update();
+        let world = app.world_mut();
+        assert_eq!(world.query::<&GraphNode>().iter(world).count(), 1_500);
+        assert_eq!(world.query::<&GraphEdge>().iter(world).count(), 167);
+        let build = world.resource::<SceneBuild>();
+        assert_eq!(build.total, 1_500 + 1_499 * 3);
+        assert_eq!(build.spawned, 2_001);
+        let visibility = world
+            .query_filtered::<&Visibility, With<BuildProgress>>()
+            .single(world)
+            .unwrap();
+        assert_eq!(*visibility, Visibility::Inherited);
+
+        // The rest in two more frames, let go of the frame after
+        for _ in 0..3 {
+            app.update();
+        }
+        let world = app.world_mut();
+        assert_eq!(world.query::<&GraphEdge>().iter(world).count(), 1_499);
+        assert!(!world.contains_resource::<SceneBuild>());
+        let visibility = world
+            .query_filtered::<&Visibility, With<BuildProgress>>()
+            .single(world)
+            .unwrap();
+        assert_eq!(*visibility, Visibility::Hidden);
+        assert_eq!(
+            progress_text(2_001, 5_997),
+            "Building scene: 2001 of 5997 entities"
+        );
+    }
+}
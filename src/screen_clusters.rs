@@ -0,0 +1,215 @@
+use crate::alerts::{AlertFilter, filter_alerting_nodes};
+use crate::keybindings::{Action, KeyBindings};
+use crate::theme::Theme;
+use crate::types::{
+    ClusterBadge, GraphNode, HoverState, LabelSettings, NodeHighlight, ScreenCluster,
+    ScreenClusters, SearchState, SelectedNodes, ThemedPanel, ThemedText,
+};
+use bevy::prelude::*;
+use petgraph::graph::NodeIndex;
+use std::collections::{BTreeMap, HashSet};
+
+/// Side in pixels of the screen cells nodes are gathered in
+const CELL_SIZE: f32 = 40.0;
+
+/// Fewest nodes in one cell that are drawn as a badge
+const MIN_CLUSTER: usize = 4;
+
+/// Height of a badge in pixels
+const BADGE_SIZE: f32 = 26.0;
+
+/// Folds crowds of distant nodes that land on the same spot of the screen
+/// into a badge with their count, like marker clustering on a map
+///
+/// Nodes within label distance, and hovered, selected or matched ones, are
+/// never folded, so crowds open up as the camera comes closer.
+pub struct ScreenClustersPlugin;
+
+impl Plugin for ScreenClustersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                toggle_screen_clusters,
+                update_screen_clusters.before(filter_alerting_nodes),
+                draw_cluster_badges.run_if(resource_changed::<ScreenClusters>),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Groups screen positions by cell, making a badge of each cell holding at
+/// least `min` of them
+///
+/// Returns the nodes the badges stand for, and the badges at the average
+/// position of their nodes.
+pub fn cluster_points(
+    points: &[(NodeIndex, Vec2)],
+    cell: f32,
+    min: usize,
+) -> (HashSet<NodeIndex>, Vec<ScreenCluster>) {
+    let mut cells: BTreeMap<(i32, i32), Vec<(NodeIndex, Vec2)>> = BTreeMap::new();
+    for &(node, position) in points {
+        let key = (position / cell).floor().as_ivec2();
+        cells
+            .entry((key.y, key.x))
+            .or_default()
+            .push((node, position));
+    }
+    let mut hidden = HashSet::new();
+    let mut badges = Vec::new();
+    for members in cells.into_values().filter(|members| members.len() >= min) {
+        #[allow(clippy::cast_precision_loss)]
+        let center =
+            members.iter().map(|(_, position)| *position).sum::<Vec2>() / members.len() as f32;
+        badges.push(ScreenCluster {
+            position: center,
+            count: members.len(),
+        });
+        hidden.extend(members.into_iter().map(|(node, _)| node));
+    }
+    (hidden, badges)
+}
+
+pub fn toggle_screen_clusters(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    mut clusters: ResMut<ScreenClusters>,
+) {
+    if !search_state.active && bindings.just_pressed(Action::ToggleScreenClusters, &keyboard_input)
+    {
+        clusters.enabled = !clusters.enabled;
+    }
+}
+
+/// Finds the crowds of distant nodes in the current view
+///
+/// Nothing is folded while the alert filter is on, as it already leaves few
+/// nodes on screen.
+#[allow(clippy::too_many_arguments)]
+pub fn update_screen_clusters(
+    camera_query: Query<(&Camera, &GlobalTransform, &Projection)>,
+    node_query: Query<(Entity, &GraphNode, &GlobalTransform)>,
+    highlight_query: Query<(), With<NodeHighlight>>,
+    hover: Res<HoverState>,
+    selection: Res<SelectedNodes>,
+    search_state: Res<SearchState>,
+    labels: Res<LabelSettings>,
+    filter: Res<AlertFilter>,
+    mut clusters: ResMut<ScreenClusters>,
+) {
+    let Ok((camera, camera_transform, projection)) = camera_query.single() else {
+        return;
+    };
+    let mut points = Vec::new();
+    if clusters.enabled && !filter.active {
+        for (entity, node, transform) in &node_query {
+            let position = transform.translation();
+            // Through an orthographic lens everything is as far as the view is high
+            let distance = match projection {
+                Projection::Orthographic(ortho) => ortho.scale,
+                _ => camera_transform.translation().distance(position),
+            };
+            let in_focus = hover.node == Some(entity)
+                || search_state.selected_node == Some(entity)
+                || highlight_query.contains(entity)
+                || selection.0.contains(&node.id);
+            if distance <= labels.visibility_distance || in_focus {
+                continue;
+            }
+            if let Ok(on_screen) = camera.world_to_viewport(camera_transform, position) {
+                points.push((node.index, on_screen));
+            }
+        }
+    }
+    let (hidden, badges) = cluster_points(&points, CELL_SIZE, MIN_CLUSTER);
+    // Only written when it changes, as nodes are shown and hidden on change
+    if clusters.hidden != hidden || clusters.badges != badges {
+        clusters.hidden = hidden;
+        clusters.badges = badges;
+    }
+}
+
+/// Places a badge over each crowd, reusing the badges already spawned
+pub fn draw_cluster_badges(
+    mut commands: Commands,
+    clusters: Res<ScreenClusters>,
+    theme: Res<Theme>,
+    mut badge_query: Query<(&mut Node, &mut Text, &mut Visibility), With<ClusterBadge>>,
+) {
+    let mut spare = badge_query.iter_mut();
+    for cluster in &clusters.badges {
+        let left = Val::Px(cluster.position.x - BADGE_SIZE / 2.0);
+        let top = Val::Px(cluster.position.y - BADGE_SIZE / 2.0);
+        let count = cluster.count.to_string();
+        if let Some((mut node, mut text, mut visibility)) = spare.next() {
+            node.left = left;
+            node.top = top;
+            if text.0 != count {
+                text.0 = count;
+            }
+            visibility.set_if_neq(Visibility::Visible);
+            continue;
+        }
+        commands.spawn((
+            Text::new(count),
+            TextFont {
+                font_size: 14.0,
+                ..default()
+            },
+            TextLayout::new_with_justify(JustifyText::Center),
+            TextColor(theme.text()),
+            Node {
+                position_type: PositionType::Absolute,
+                left,
+                top,
+                min_width: Val::Px(BADGE_SIZE),
+                height: Val::Px(BADGE_SIZE),
+                padding: UiRect::axes(Val::Px(6.0), Val::Px(4.0)),
+                ..default()
+            },
+            BackgroundColor(theme.panel()),
+            BorderRadius::all(Val::Px(BADGE_SIZE / 2.0)),
+            ClusterBadge,
+            ThemedText,
+            ThemedPanel,
+        ));
+    }
+    for (_, _, mut visibility) in spare {
+        visibility.set_if_neq(Visibility::Hidden);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crowded_cells_become_badges() {
+        let node = NodeIndex::new;
+        let points = [
+            // Four nodes crowding one cell
+            (node(0), Vec2::new(10.0, 10.0)),
+            (node(1), Vec2::new(12.0, 14.0)),
+            (node(2), Vec2::new(30.0, 20.0)),
+            (node(3), Vec2::new(20.0, 36.0)),
+            // Three in the next cell, too few for a badge
+            (node(4), Vec2::new(50.0, 10.0)),
+            (node(5), Vec2::new(52.0, 12.0)),
+            (node(6), Vec2::new(54.0, 14.0)),
+        ];
+        let (hidden, badges) = cluster_points(&points, 40.0, 4);
+
+        assert_eq!(hidden, (0..4).map(node).collect());
+        assert_eq!(
+            badges,
+            [ScreenCluster {
+                position: Vec2::new(18.0, 20.0),
+                count: 4,
+            }]
+        );
+        assert!(cluster_points(&points, 10.0, 4).1.is_empty());
+    }
+}
@@ -0,0 +1,685 @@
+//! Rhai scripts run against the graph from the scripting console
+//!
+//! Scripts read the live graph and the selection, and ask for changes that
+//! are applied once they finish:
+//!
+//! | Function | Does |
+//! |---|---|
+//! | `nodes()` | Ids of every node, sorted |
+//! | `edges()` | Every edge as `#{from, to, label}` |
+//! | `node(id)` | `#{id, name, type, level, state, metrics, properties}`, or `()` if there's no such node |
+//! | `successors(id)`, `predecessors(id)` | Ids of the nodes an edge leads to or comes from |
+//! | `neighbors(id)` | Ids joined to a node by an edge either way |
+//! | `ancestors(id)`, `descendants(id)` | Ids with a path to a node, or that a path from it leads to |
+//! | `path(from, to)` | Ids along a shortest path following the edges, or `()` if there's none |
+//! | `selection()` | Ids of the selected nodes |
+//! | `select(ids)`, `select(id)`, `clear_selection()` | Replace the selection |
+//! | `recolor(id, "#rrggbb")`, `reset_colors()` | Paint nodes, or undo all painting |
+//! | `emit(event)` | Apply an event in its session JSON form, e.g. `#{type: "add_node", id: "cache"}` |
+//! | `focus(id)`, `zoom(radius)` | Move the camera to a node, or to a distance |
+//! | `caption(text)`, `caption(text, secs)` | Show a caption, until the next one or for a while |
+//! | `caption_at(id, text)`, `caption_at(id, text, secs)` | Show a caption pointing at a node |
+//! | `print(value)` | Write to the console |
+//!
+//! Emitted events are applied as one undoable edit. A script that fails
+//! changes nothing.
+
+use crate::captions::ShowCaption;
+use crate::events::{AlertState, GraphEvent};
+use crate::gauges::color_nodes_by_metric;
+use crate::graph_state::{GraphData as StateGraphData, GraphQuery, GraphState};
+use crate::history::EditHistory;
+use crate::keybindings::{Action, KeyBindings};
+use crate::settings::settings_panel_ui;
+use crate::staleness::fade_stale_nodes;
+use crate::types::{
+    GraphData, GraphNode, NodeChange, OriginalMaterial, ScriptConsole, SearchState, SelectedNodes,
+};
+use crate::visualization::refresh_graph_scene;
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use bevy_panorbit_camera::PanOrbitCamera;
+use petgraph::graph::NodeIndex;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Operations a script may run before it is stopped, so a runaway loop
+/// doesn't hang the viewer
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Most lines kept in the console's output
+const OUTPUT_LIMIT: usize = 500;
+
+/// Closest and farthest the camera may be sent by `zoom`
+const ZOOM_RANGE: (f32, f32) = (2.0, 100.0);
+
+const API_HELP: &str = "\
+nodes()                     ids of every node
+edges()                     #{from, to, label} of every edge
+node(id)                    #{id, name, type, level, state, metrics, properties}
+successors(id)              ids an edge from id leads to
+predecessors(id)            ids with an edge to id
+neighbors(id)               ids joined to id either way
+ancestors(id)               ids with a path to id
+descendants(id)             ids a path from id leads to
+path(from, to)              ids along a shortest path, or ()
+selection()                 selected ids
+select(ids) / select(id)    replace the selection
+clear_selection()
+recolor(id, \"#rrggbb\")      paint a node
+reset_colors()
+emit(event)                 e.g. emit(#{type: \"add_node\", id: \"cache\"})
+focus(id) / zoom(radius)    move the camera
+caption(text[, secs])       show a caption
+caption_at(id, text[, secs]) show a caption pointing at id
+print(value)";
+
+/// Node colors set by scripts, by node id
+#[derive(Resource, Default)]
+pub struct ScriptColors(pub HashMap<String, Color>);
+
+/// A change a script asked for, applied after it finishes
+#[derive(Debug, Clone)]
+pub enum ScriptEffect {
+    Select(Vec<String>),
+    Recolor(String, Color),
+    ResetColors,
+    Emit(GraphEvent),
+    Focus(String),
+    Zoom(f32),
+    Caption(ShowCaption),
+}
+
+/// What a script printed, and the changes it asked for or why it failed
+pub struct ScriptRun {
+    pub output: Vec<String>,
+    pub result: Result<Vec<ScriptEffect>, String>,
+}
+
+/// The graph a script runs against, with ids looked up by index
+struct Snapshot {
+    data: StateGraphData,
+    ids: HashMap<NodeIndex, String>,
+}
+
+impl Snapshot {
+    fn id_of(&self, index: NodeIndex) -> Option<String> {
+        self.ids.get(&index).cloned()
+    }
+}
+
+/// A console for running Rhai scripts against the graph
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScriptConsole>()
+            .init_resource::<ScriptColors>()
+            .add_systems(
+                Update,
+                (
+                    toggle_console,
+                    run_console_script,
+                    apply_script_colors
+                        .run_if(resource_exists_and_changed::<GraphData>)
+                        .after(refresh_graph_scene)
+                        .after(color_nodes_by_metric)
+                        .before(fade_stale_nodes),
+                ),
+            )
+            .add_systems(EguiContextPass, console_ui.after(settings_panel_ui));
+    }
+}
+
+/// Runs a script against a snapshot of the graph and the selection
+pub fn run_script(
+    source: &str,
+    data: &StateGraphData,
+    selection: impl IntoIterator<Item = String>,
+) -> ScriptRun {
+    let data = Rc::new(Snapshot {
+        ids: data
+            .node_map
+            .iter()
+            .map(|(id, &index)| (index, id.clone()))
+            .collect(),
+        data: data.clone(),
+    });
+    let mut selected: Vec<_> = selection.into_iter().collect();
+    selected.sort_unstable();
+    let selected = Rc::new(RefCell::new(selected));
+    let effects = Rc::new(RefCell::new(Vec::new()));
+    let output = Rc::new(RefCell::new(Vec::new()));
+
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    let printed = output.clone();
+    engine.on_print(move |text| printed.borrow_mut().push(text.to_string()));
+    let printed = output.clone();
+    engine.on_debug(move |text, _, _| printed.borrow_mut().push(text.to_string()));
+    register_graph_api(&mut engine, &data);
+    register_scene_api(&mut engine, &data, &selected, &effects);
+
+    let result = engine
+        .eval::<Dynamic>(source)
+        .map(|value| {
+            // Like a REPL, show what the script ends with
+            if !value.is_unit() {
+                output.borrow_mut().push(value.to_string());
+            }
+            effects.take()
+        })
+        .map_err(|err| err.to_string());
+    ScriptRun {
+        output: output.take(),
+        result,
+    }
+}
+
+/// Functions that read the graph
+fn register_graph_api(engine: &mut Engine, data: &Rc<Snapshot>) {
+    let graph = data.clone();
+    engine.register_fn("nodes", move || -> Array {
+        graph
+            .data
+            .nodes_matching(|_, _| true)
+            .into_iter()
+            .map(Dynamic::from)
+            .collect()
+    });
+
+    let graph = data.clone();
+    engine.register_fn("edges", move || -> Array {
+        graph
+            .data
+            .graph
+            .edge_indices()
+            .filter_map(|edge| {
+                let (from, to) = graph.data.graph.edge_endpoints(edge)?;
+                let mut map = Map::new();
+                map.insert("from".into(), graph.id_of(from)?.into());
+                map.insert("to".into(), graph.id_of(to)?.into());
+                map.insert(
+                    "label".into(),
+                    graph.data.graph[edge]
+                        .label
+                        .clone()
+                        .map_or(Dynamic::UNIT, Dynamic::from),
+                );
+                Some(Dynamic::from_map(map))
+            })
+            .collect()
+    });
+
+    let graph = data.clone();
+    engine.register_fn("node", move |id: &str| -> Dynamic {
+        let Some(info) = graph
+            .data
+            .node_map
+            .get(id)
+            .map(|&index| &graph.data.graph[index])
+        else {
+            return Dynamic::UNIT;
+        };
+        let mut map = Map::new();
+        map.insert("id".into(), id.into());
+        map.insert("name".into(), info.name.clone().into());
+        map.insert(
+            "type".into(),
+            info.node_type.clone().map_or(Dynamic::UNIT, Dynamic::from),
+        );
+        map.insert("level".into(), rhai::INT::from(info.level).into());
+        map.insert(
+            "state".into(),
+            info.state
+                .map(AlertState::name)
+                .map_or(Dynamic::UNIT, Dynamic::from),
+        );
+        let metrics: Map = info
+            .metrics
+            .iter()
+            .map(|(key, &value)| (key.into(), Dynamic::from_float(value.into())))
+            .collect();
+        map.insert("metrics".into(), Dynamic::from_map(metrics));
+        let properties: Map = info
+            .properties
+            .iter()
+            .map(|(key, value)| (key.into(), value.clone().into()))
+            .collect();
+        map.insert("properties".into(), Dynamic::from_map(properties));
+        Dynamic::from_map(map)
+    });
+
+    register_query(engine, data, "successors", GraphQuery::successors);
+    register_query(engine, data, "predecessors", GraphQuery::predecessors);
+    register_query(engine, data, "neighbors", GraphQuery::neighbors);
+    register_query(engine, data, "ancestors", GraphQuery::ancestors);
+    register_query(engine, data, "descendants", GraphQuery::descendants);
+
+    let graph = data.clone();
+    engine.register_fn("path", move |from: &str, to: &str| -> Dynamic {
+        graph
+            .data
+            .find_path(from, to)
+            .map_or(Dynamic::UNIT, |path| {
+                Dynamic::from_array(path.into_iter().map(Dynamic::from).collect())
+            })
+    });
+}
+
+/// Registers a `GraphQuery` method as a script function from an id to ids
+fn register_query(
+    engine: &mut Engine,
+    data: &Rc<Snapshot>,
+    name: &str,
+    query: fn(&StateGraphData, &str) -> Vec<String>,
+) {
+    let graph = data.clone();
+    engine.register_fn(name, move |id: &str| -> Array {
+        query(&graph.data, id)
+            .into_iter()
+            .map(Dynamic::from)
+            .collect()
+    });
+}
+
+/// Functions that change the selection, the scene, the camera or the graph
+fn register_scene_api(
+    engine: &mut Engine,
+    data: &Rc<Snapshot>,
+    selected: &Rc<RefCell<Vec<String>>>,
+    effects: &Rc<RefCell<Vec<ScriptEffect>>>,
+) {
+    let current = selected.clone();
+    engine.register_fn("selection", move || -> Array {
+        current
+            .borrow()
+            .iter()
+            .cloned()
+            .map(Dynamic::from)
+            .collect()
+    });
+
+    let (current, requested) = (selected.clone(), effects.clone());
+    engine.register_fn("select", move |ids: Array| {
+        let ids: Vec<_> = ids.into_iter().map(|id| id.to_string()).collect();
+        current.replace(ids.clone());
+        requested.borrow_mut().push(ScriptEffect::Select(ids));
+    });
+    let (current, requested) = (selected.clone(), effects.clone());
+    engine.register_fn("select", move |id: &str| {
+        current.replace(vec![id.to_string()]);
+        requested
+            .borrow_mut()
+            .push(ScriptEffect::Select(vec![id.to_string()]));
+    });
+    let (current, requested) = (selected.clone(), effects.clone());
+    engine.register_fn("clear_selection", move || {
+        current.borrow_mut().clear();
+        requested
+            .borrow_mut()
+            .push(ScriptEffect::Select(Vec::new()));
+    });
+
+    let requested = effects.clone();
+    engine.register_fn(
+        "recolor",
+        move |id: &str, color: &str| -> Result<(), Box<EvalAltResult>> {
+            let color = Srgba::hex(color).map_err(|err| format!("bad color \"{color}\": {err}"))?;
+            requested
+                .borrow_mut()
+                .push(ScriptEffect::Recolor(id.to_string(), color.into()));
+            Ok(())
+        },
+    );
+    let requested = effects.clone();
+    engine.register_fn("reset_colors", move || {
+        requested.borrow_mut().push(ScriptEffect::ResetColors);
+    });
+
+    let requested = effects.clone();
+    engine.register_fn(
+        "emit",
+        move |event: Map| -> Result<(), Box<EvalAltResult>> {
+            let event = GraphEvent::from_json(&json_of(Dynamic::from_map(event)))?;
+            requested.borrow_mut().push(ScriptEffect::Emit(event));
+            Ok(())
+        },
+    );
+
+    let (graph, requested) = (data.clone(), effects.clone());
+    engine.register_fn("focus", move |id: &str| -> Result<(), Box<EvalAltResult>> {
+        if !graph.data.node_map.contains_key(id) {
+            return Err(format!("no node \"{id}\"").into());
+        }
+        requested
+            .borrow_mut()
+            .push(ScriptEffect::Focus(id.to_string()));
+        Ok(())
+    });
+    let requested = effects.clone();
+    #[allow(clippy::cast_possible_truncation)]
+    engine.register_fn("zoom", move |radius: rhai::FLOAT| {
+        requested.borrow_mut().push(ScriptEffect::Zoom(
+            (radius as f32).clamp(ZOOM_RANGE.0, ZOOM_RANGE.1),
+        ));
+    });
+
+    register_caption(engine, effects);
+}
+
+/// Registers `caption` and `caption_at`, each with and without a duration
+fn register_caption(engine: &mut Engine, effects: &Rc<RefCell<Vec<ScriptEffect>>>) {
+    #[allow(clippy::cast_possible_truncation)]
+    let show = |effects: &Rc<RefCell<Vec<ScriptEffect>>>| {
+        let requested = effects.clone();
+        move |anchor_node: Option<&str>, text: &str, secs: Option<rhai::FLOAT>| {
+            requested
+                .borrow_mut()
+                .push(ScriptEffect::Caption(ShowCaption {
+                    text: text.to_string(),
+                    duration: secs.map(|secs| secs as f32),
+                    anchor_node: anchor_node.map(str::to_string),
+                }));
+        }
+    };
+    let caption = show(effects);
+    engine.register_fn("caption", move |text: &str| caption(None, text, None));
+    let caption = show(effects);
+    engine.register_fn("caption", move |text: &str, secs: rhai::FLOAT| {
+        caption(None, text, Some(secs));
+    });
+    let caption = show(effects);
+    engine.register_fn("caption_at", move |id: &str, text: &str| {
+        caption(Some(id), text, None);
+    });
+    let caption = show(effects);
+    engine.register_fn(
+        "caption_at",
+        move |id: &str, text: &str, secs: rhai::FLOAT| caption(Some(id), text, Some(secs)),
+    );
+}
+
+/// A script value as JSON, for reading events with the session format's parser
+fn json_of(value: Dynamic) -> Value {
+    if value.is_unit() {
+        Value::Null
+    } else if let Ok(flag) = value.as_bool() {
+        flag.into()
+    } else if let Ok(int) = value.as_int() {
+        int.into()
+    } else if let Ok(float) = value.as_float() {
+        float.into()
+    } else if value.is_array() {
+        value.cast::<Array>().into_iter().map(json_of).collect()
+    } else if value.is_map() {
+        Value::Object(
+            value
+                .cast::<Map>()
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), json_of(value)))
+                .collect(),
+        )
+    } else {
+        Value::String(value.to_string())
+    }
+}
+
+/// Opens and closes the scripting console
+pub fn toggle_console(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    search_state: Res<SearchState>,
+    mut console: ResMut<ScriptConsole>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::ToggleConsole, &keyboard_input) {
+        return;
+    }
+    console.open = !console.open;
+}
+
+/// Runs the script the console submitted and applies what it asked for
+#[allow(clippy::too_many_arguments)]
+pub fn run_console_script(
+    mut console: ResMut<ScriptConsole>,
+    mut graph_state: ResMut<GraphState>,
+    mut history: ResMut<EditHistory>,
+    mut selection: ResMut<SelectedNodes>,
+    mut colors: ResMut<ScriptColors>,
+    node_query: Query<(&GraphNode, &Transform)>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+    mut captions: EventWriter<ShowCaption>,
+) {
+    let Some(source) = console.pending.take() else {
+        return;
+    };
+    let run = run_script(
+        &source,
+        &graph_state.as_graph_data(),
+        selection.0.iter().cloned(),
+    );
+    console.output.extend(run.output);
+    let effects = match run.result {
+        Ok(effects) => effects,
+        Err(err) => {
+            console.output.push(format!("Error: {err}"));
+            return;
+        }
+    };
+
+    let mut events = Vec::new();
+    let mut recolored = false;
+    for effect in effects {
+        match effect {
+            ScriptEffect::Select(ids) => selection.0 = ids.into_iter().collect(),
+            ScriptEffect::Recolor(id, color) => {
+                colors.0.insert(id, color);
+                recolored = true;
+            }
+            ScriptEffect::ResetColors => {
+                colors.0.clear();
+                recolored = true;
+            }
+            ScriptEffect::Emit(event) => match ShowCaption::from_event(&event) {
+                // Emitted captions are shown rather than kept in the edit
+                Some(caption) => {
+                    captions.write(caption);
+                }
+                None => events.push(event),
+            },
+            ScriptEffect::Focus(id) => {
+                let Some((_, transform)) = node_query.iter().find(|(node, _)| node.id == id) else {
+                    continue;
+                };
+                for mut camera in &mut camera_query {
+                    camera.target_focus = transform.translation;
+                }
+            }
+            ScriptEffect::Zoom(radius) => {
+                for mut camera in &mut camera_query {
+                    camera.target_radius = radius;
+                }
+            }
+            ScriptEffect::Caption(caption) => {
+                captions.write(caption);
+            }
+        }
+    }
+
+    if !events.is_empty() {
+        let count = events.len();
+        let results = history.apply(&mut graph_state, events);
+        let rejected = results
+            .iter()
+            .filter(|result| !matches!(result, crate::events::EventResult::Success))
+            .count();
+        console.output.push(if rejected == 0 {
+            format!("Applied {count} events")
+        } else {
+            format!("Applied {} events, {rejected} rejected", count - rejected)
+        });
+    } else if recolored {
+        // Rebuild the scene so painted nodes get fresh materials
+        graph_state.set_changed();
+    }
+    let excess = console.output.len().saturating_sub(OUTPUT_LIMIT);
+    console.output.drain(..excess);
+}
+
+/// Paints nodes in the colors scripts gave them, on materials of their own
+#[allow(clippy::type_complexity)]
+pub fn apply_script_colors(
+    colors: Res<ScriptColors>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut node_query: Query<
+        (&GraphNode, &mut MeshMaterial3d<StandardMaterial>),
+        (Without<OriginalMaterial>, Without<NodeChange>),
+    >,
+) {
+    if colors.0.is_empty() {
+        return;
+    }
+    for (node, mut material) in &mut node_query {
+        let Some(&color) = colors.0.get(&node.id) else {
+            continue;
+        };
+        let Some(copy) = materials.get(&material.0).cloned() else {
+            continue;
+        };
+        material.0 = materials.add(StandardMaterial {
+            base_color: color,
+            ..copy
+        });
+    }
+}
+
+/// Script editor, output and API reference
+pub fn console_ui(mut contexts: EguiContexts, mut console: ResMut<ScriptConsole>) {
+    if !console.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let mut open = true;
+    let mut run = false;
+    let mut clear = false;
+    let mut input = console.input.clone();
+
+    egui::Window::new("Script Console")
+        .open(&mut open)
+        .default_pos([300.0, 80.0])
+        .default_width(480.0)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in &console.output {
+                        ui.monospace(line);
+                    }
+                });
+            ui.separator();
+            let editor = ui.add(
+                egui::TextEdit::multiline(&mut input)
+                    .code_editor()
+                    .hint_text("select(nodes().filter(|id| node(id).level > 1))")
+                    .desired_rows(6)
+                    .desired_width(f32::INFINITY),
+            );
+            run = editor.has_focus()
+                && ui.input(|input| input.modifiers.command && input.key_pressed(egui::Key::Enter));
+            ui.horizontal(|ui| {
+                run |= ui.button("Run").on_hover_text("Ctrl+Enter").clicked();
+                clear = ui.button("Clear output").clicked();
+            });
+            ui.collapsing("API", |ui| ui.monospace(API_HELP));
+        });
+
+    if input != console.input {
+        console.input = input;
+    }
+    if run && !console.input.trim().is_empty() {
+        let source = console.input.clone();
+        console.output.push(format!("> {}", source.trim()));
+        console.pending = Some(source);
+    }
+    if clear {
+        console.output.clear();
+    }
+    if !open {
+        console.open = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_scripts_query_the_graph_and_request_changes() {
+        let content = r"digraph {
+            web -> api
+            api -> db
+            api -> cache
+        }";
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+
+        let run = run_script(
+            r##"
+            let leaves = nodes().filter(|id| successors(id).is_empty());
+            select(leaves);
+            print(selection());
+            recolor("db", "#ff0000");
+            emit(#{type: "add_edge", from: "web", to: "cache"});
+            focus("api");
+            caption_at("api", "Everything goes through here", 5.0);
+            print(path("web", "db"));
+            print(path("db", "web"));
+            print(descendants("web"));
+            predecessors("api")
+            "##,
+            &data,
+            [],
+        );
+        assert_eq!(
+            run.output,
+            [
+                r#"["cache", "db"]"#,
+                r#"["web", "api", "db"]"#,
+                "",
+                r#"["api", "cache", "db"]"#,
+                r#"["web"]"#
+            ]
+        );
+        let effects = run.result.unwrap();
+        assert!(matches!(&effects[..], [
+            ScriptEffect::Select(selected),
+            ScriptEffect::Recolor(painted, color),
+            ScriptEffect::Emit(GraphEvent::AddEdge { from, to }),
+            ScriptEffect::Focus(focused),
+            ScriptEffect::Caption(caption),
+        ] if selected == &["cache", "db"]
+            && painted == "db"
+            && *color == Color::srgb(1.0, 0.0, 0.0)
+            && (from.as_str(), to.as_str()) == ("web", "cache")
+            && focused == "api"
+            && caption.anchor_node.as_deref() == Some("api")
+            && caption.duration == Some(5.0)));
+
+        // Failing scripts report why and change nothing
+        let run = run_script(r#"select("db"); focus("nowhere")"#, &data, []);
+        assert!(run.result.unwrap_err().contains("nowhere"));
+        let run = run_script("loop {}", &data, []);
+        assert!(run.result.is_err());
+        let run = run_script(r#"recolor("db", "red-ish")"#, &data, []);
+        assert!(run.result.is_err());
+    }
+}
@@ -1,5 +1,169 @@
-use crate::types::{GraphNode, NodeHighlight, SearchBox, SearchState};
+use crate::graph_state::{EdgeInfo, NodeInfo};
+use crate::keybindings::{Action, KeyBindings};
+use crate::types::{
+    EdgeHighlight, GraphData, GraphEdge, GraphNode, NodeHighlight, SearchBox, SearchModifiers,
+    SearchState, ThemedPanel, ThemedText,
+};
 use bevy::prelude::*;
+use petgraph::graph::{DiGraph, NodeIndex};
+use std::borrow::Cow;
+
+/// How the text of a search is compared, toggled from the search box
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// Tell upper and lower case apart
+    pub match_case: bool,
+    /// Only match whole words, not parts of them
+    pub whole_word: bool,
+}
+
+impl MatchOptions {
+    /// Whether `pattern` occurs in `text`
+    pub fn contains(self, text: &str, pattern: &str) -> bool {
+        let (text, pattern) = self.folded(text, pattern);
+        if !self.whole_word {
+            return text.contains(&*pattern);
+        }
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        text.match_indices(&*pattern).any(|(start, found)| {
+            let before = text[..start].chars().next_back();
+            let after = text[start + found.len()..].chars().next();
+            !before.is_some_and(is_word) && !after.is_some_and(is_word)
+        })
+    }
+
+    /// Whether `text` is `pattern`
+    pub fn equals(self, text: &str, pattern: &str) -> bool {
+        let (text, pattern) = self.folded(text, pattern);
+        text == pattern
+    }
+
+    fn folded<'a>(self, text: &'a str, pattern: &'a str) -> (Cow<'a, str>, Cow<'a, str>) {
+        if self.match_case {
+            (Cow::Borrowed(text), Cow::Borrowed(pattern))
+        } else {
+            (
+                Cow::Owned(text.to_lowercase()),
+                Cow::Owned(pattern.to_lowercase()),
+            )
+        }
+    }
+}
+
+/// What a search looks for, picked by its prefix
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    /// Nodes whose name contains the text
+    Name(String),
+    /// `e:text`: edges whose label or type contains the text
+    Edge(String),
+    /// `a:key=value`: nodes whose attribute `key` is `value`, or that have
+    /// the attribute at all when no value is given
+    Attribute { key: String, value: Option<String> },
+}
+
+/// A search as typed in the search box, for anything that picks nodes or
+/// edges with the same query language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    target: Target,
+    options: MatchOptions,
+}
+
+impl SearchQuery {
+    /// Parses a query as typed; `None` if there's nothing to search for yet
+    pub fn parse(query: &str, options: MatchOptions) -> Option<Self> {
+        let prefix = query.get(..2).map(str::to_ascii_lowercase);
+        let rest = query.get(2..).unwrap_or_default();
+        let target = match prefix.as_deref() {
+            Some("e:") => Target::Edge(rest.to_string()),
+            Some("a:") => {
+                let (key, value) = rest
+                    .split_once('=')
+                    .map_or((rest, None), |(key, value)| (key, Some(value)));
+                Target::Attribute {
+                    key: key.trim().to_lowercase(),
+                    value: value.map(|value| value.trim().to_string()),
+                }
+            }
+            _ => Target::Name(query.to_string()),
+        };
+        let empty = match &target {
+            Target::Name(text) | Target::Edge(text) => text.is_empty(),
+            Target::Attribute { key, .. } => key.is_empty(),
+        };
+        (!empty).then_some(Self { target, options })
+    }
+
+    pub fn matches_node(&self, info: &NodeInfo) -> bool {
+        match &self.target {
+            Target::Name(text) => self.options.contains(&info.name, text),
+            Target::Edge(_) => false,
+            Target::Attribute { key, value } => match (node_attribute(info, key), value) {
+                (Some(actual), Some(value)) => self.options.equals(&actual, value),
+                (actual, None) => actual.is_some(),
+                (None, Some(_)) => false,
+            },
+        }
+    }
+
+    pub fn matches_edge(&self, label: Option<&str>, edge_type: Option<&str>) -> bool {
+        let Target::Edge(text) = &self.target else {
+            return false;
+        };
+        [label, edge_type]
+            .into_iter()
+            .flatten()
+            .any(|value| self.options.contains(value, text))
+    }
+
+    /// The nodes of `graph` the query matches
+    #[allow(dead_code)] // Part of the library API
+    pub fn matching_nodes(&self, graph: &DiGraph<NodeInfo, EdgeInfo>) -> Vec<NodeIndex> {
+        graph
+            .node_indices()
+            .filter(|&node| self.matches_node(&graph[node]))
+            .collect()
+    }
+}
+
+/// A node's `type`, `level` or `state`, or one of its live metrics
+fn node_attribute(info: &NodeInfo, key: &str) -> Option<String> {
+    match key {
+        "type" => info.node_type.clone(),
+        "level" => Some(info.level.to_string()),
+        "state" => info.state.map(|state| state.name().to_string()),
+        _ => info
+            .metrics
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.to_string())
+            .or_else(|| {
+                info.properties
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                    .map(|(_, value)| value.clone())
+            }),
+    }
+}
+
+/// Finding nodes by name or attribute, and edges by label, and highlighting
+/// the matches
+pub struct SearchPlugin;
+
+impl Plugin for SearchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SearchState>().add_systems(
+            Update,
+            (
+                toggle_search,
+                toggle_search_modifiers,
+                handle_search_input,
+                update_node_highlighting,
+            ),
+        );
+    }
+}
 
 pub fn setup_search_ui(commands: &mut Commands) {
     // Create search box (initially hidden)
@@ -20,28 +184,77 @@ pub fn setup_search_ui(commands: &mut Commands) {
         BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
         Visibility::Hidden,
         SearchBox,
+        ThemedText,
+        ThemedPanel,
+        children![(
+            TextSpan::default(),
+            TextFont {
+                font_size: 14.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+            SearchModifiers,
+            ThemedText,
+        )],
     ));
 }
 
+/// Toggles matching case and whole words while searching, and shows which
+/// of them are on after the query
+pub fn toggle_search_modifiers(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut search_state: ResMut<SearchState>,
+    mut modifiers_query: Query<&mut TextSpan, With<SearchModifiers>>,
+) {
+    if !search_state.active {
+        return;
+    }
+    let mut options = search_state.options;
+    if bindings.just_pressed(Action::ToggleMatchCase, &keyboard_input) {
+        options.match_case = !options.match_case;
+    }
+    if bindings.just_pressed(Action::ToggleWholeWord, &keyboard_input) {
+        options.whole_word = !options.whole_word;
+    }
+    search_state.options = options;
+
+    let indicators: String = [
+        (options.match_case, "  [Aa]"),
+        (options.whole_word, "  [word]"),
+    ]
+    .into_iter()
+    .filter_map(|(on, indicator)| on.then_some(indicator))
+    .collect();
+    if let Ok(mut span) = modifiers_query.single_mut()
+        && span.0 != indicators
+    {
+        span.0 = indicators;
+    }
+}
+
 pub fn toggle_search(
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mut search_state: ResMut<SearchState>,
     mut search_box_query: Query<&mut Visibility, With<SearchBox>>,
+    bindings: Res<KeyBindings>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Slash) && !search_state.active {
+    if bindings.just_pressed(Action::OpenSearch, &keyboard_input) && !search_state.active {
         // Activate search
         search_state.active = true;
         search_state.query.clear();
         search_state.matching_nodes.clear();
+        search_state.matching_edges.clear();
 
         if let Ok(mut visibility) = search_box_query.single_mut() {
             *visibility = Visibility::Visible;
         }
-    } else if keyboard_input.just_pressed(KeyCode::Escape) && search_state.active {
+    } else if bindings.just_pressed(Action::CloseSearch, &keyboard_input) && search_state.active {
         // Deactivate search
         search_state.active = false;
         search_state.query.clear();
         search_state.matching_nodes.clear();
+        search_state.matching_edges.clear();
         // Don't clear selected_node here - let fly_to_selected_node handle it once
 
         if let Ok(mut visibility) = search_box_query.single_mut() {
@@ -55,12 +268,86 @@ pub fn handle_search_input(
     mut search_state: ResMut<SearchState>,
     mut search_box_query: Query<&mut Text, With<SearchBox>>,
     node_query: Query<(Entity, &GraphNode, &GlobalTransform)>,
+    edge_query: Query<(Entity, &GraphEdge)>,
+    graph_data: Option<Res<GraphData>>,
     mut commands: Commands,
 ) {
     if !search_state.active {
         return;
     }
 
+    if let Some(ch) = typed_char(&keyboard_input) {
+        search_state.query.push(ch);
+    }
+
+    // Handle backspace
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        search_state.query.pop();
+    }
+
+    // Update search box text
+    if let Ok(mut text) = search_box_query.single_mut() {
+        text.0 = format!("Search: {}_", search_state.query);
+    }
+
+    // Find matching nodes and edges
+    search_state.matching_nodes.clear();
+    search_state.matching_edges.clear();
+    if let Some(query) = SearchQuery::parse(&search_state.query, search_state.options) {
+        for (entity, node, _) in &node_query {
+            let info = graph_data
+                .as_ref()
+                .and_then(|data| data.graph.node_weight(node.index));
+            if info.is_some_and(|info| query.matches_node(info)) {
+                search_state.matching_nodes.push(entity);
+            }
+        }
+        for (entity, edge) in &edge_query {
+            if query.matches_edge(edge.label.as_deref(), edge.edge_type.as_deref()) {
+                search_state.matching_edges.push(entity);
+            }
+        }
+    }
+
+    // Select the first matching node
+    search_state.selected_node = search_state.matching_nodes.first().copied();
+
+    // Update highlighting
+    for (entity, _, _) in &node_query {
+        if search_state.matching_nodes.contains(&entity) {
+            // Add highlight component if not present
+            commands
+                .entity(entity)
+                .try_insert(NodeHighlight { fade_timer: 1.0 });
+        } else {
+            // Remove highlight if present
+            commands.entity(entity).remove::<NodeHighlight>();
+        }
+    }
+    for (entity, _) in &edge_query {
+        if search_state.matching_edges.contains(&entity) {
+            commands
+                .entity(entity)
+                .try_insert(EdgeHighlight { fade_timer: 1.0 });
+        } else {
+            commands.entity(entity).remove::<EdgeHighlight>();
+        }
+    }
+}
+
+/// The character a key pressed this frame types into the search box
+fn typed_char(keyboard_input: &ButtonInput<KeyCode>) -> Option<char> {
+    // Ctrl chords toggle search modifiers instead of typing
+    if keyboard_input.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ]) {
+        return None;
+    }
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
     // Check for letter keys
     for (key, ch) in [
         (KeyCode::KeyA, 'a'),
@@ -92,56 +379,32 @@ pub fn handle_search_input(
         (KeyCode::Space, ' '),
     ] {
         if keyboard_input.just_pressed(key) {
-            if keyboard_input.pressed(KeyCode::ShiftLeft)
-                || keyboard_input.pressed(KeyCode::ShiftRight)
-            {
-                search_state.query.push(ch.to_ascii_uppercase());
-            } else {
-                search_state.query.push(ch);
-            }
-            break;
+            return Some(if shift { ch.to_ascii_uppercase() } else { ch });
         }
     }
 
-    // Handle backspace
-    if keyboard_input.just_pressed(KeyCode::Backspace) {
-        search_state.query.pop();
-    }
-
-    // Update search box text
-    if let Ok(mut text) = search_box_query.single_mut() {
-        text.0 = format!("Search: {}_", search_state.query);
-    }
-
-    // Find matching nodes
-    search_state.matching_nodes.clear();
-    if !search_state.query.is_empty() {
-        for (entity, node, _) in &node_query {
-            if node
-                .name
-                .to_lowercase()
-                .contains(&search_state.query.to_lowercase())
-            {
-                search_state.matching_nodes.push(entity);
-            }
-        }
-    }
-
-    // Select the first matching node
-    search_state.selected_node = search_state.matching_nodes.first().copied();
-
-    // Update highlighting
-    for (entity, _, _) in &node_query {
-        if search_state.matching_nodes.contains(&entity) {
-            // Add highlight component if not present
-            commands
-                .entity(entity)
-                .try_insert(NodeHighlight { fade_timer: 1.0 });
-        } else {
-            // Remove highlight if present
-            commands.entity(entity).remove::<NodeHighlight>();
+    // Digits and the punctuation of `e:` and `a:key=value` queries
+    for (key, ch, shifted) in [
+        (KeyCode::Digit0, '0', ')'),
+        (KeyCode::Digit1, '1', '!'),
+        (KeyCode::Digit2, '2', '@'),
+        (KeyCode::Digit3, '3', '#'),
+        (KeyCode::Digit4, '4', '$'),
+        (KeyCode::Digit5, '5', '%'),
+        (KeyCode::Digit6, '6', '^'),
+        (KeyCode::Digit7, '7', '&'),
+        (KeyCode::Digit8, '8', '*'),
+        (KeyCode::Digit9, '9', '('),
+        (KeyCode::Semicolon, ';', ':'),
+        (KeyCode::Equal, '=', '+'),
+        (KeyCode::Minus, '-', '_'),
+        (KeyCode::Period, '.', '>'),
+    ] {
+        if keyboard_input.just_pressed(key) {
+            return Some(if shift { shifted } else { ch });
         }
     }
+    None
 }
 
 // Removed fly_to_selected_node - search now only highlights nodes
@@ -149,42 +412,105 @@ pub fn handle_search_input(
 pub fn update_node_highlighting(
     mut commands: Commands,
     mut highlight_query: Query<(Entity, &mut NodeHighlight)>,
+    mut edge_highlight_query: Query<(Entity, &mut EdgeHighlight)>,
     time: Res<Time>,
     search_state: Res<SearchState>,
 ) {
     let delta = time.delta_secs();
-
-    for (entity, mut highlight) in &mut highlight_query {
+    // Returns whether the highlight has faded out
+    let fade = |fade_timer: &mut f32| {
         // Don't fade if search is active
         if search_state.active {
-            highlight.fade_timer = 1.0;
+            *fade_timer = 1.0;
         } else {
             // Fade out over 20 seconds (10x slower)
-            highlight.fade_timer -= delta * 0.05;
+            *fade_timer -= delta * 0.05;
+        }
+        *fade_timer <= 0.0
+    };
 
-            if highlight.fade_timer <= 0.0 {
-                commands.entity(entity).remove::<NodeHighlight>();
-            }
+    for (entity, mut highlight) in &mut highlight_query {
+        if fade(&mut highlight.fade_timer) {
+            commands.entity(entity).remove::<NodeHighlight>();
+        }
+    }
+    for (entity, mut highlight) in &mut edge_highlight_query {
+        if fade(&mut highlight.fade_timer) {
+            commands.entity(entity).remove::<EdgeHighlight>();
         }
     }
 }
 
-pub fn apply_highlight_visuals(
-    node_query: Query<(&MeshMaterial3d<StandardMaterial>, Option<&NodeHighlight>), With<GraphNode>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    for (material, highlight) in &node_query {
-        let material_handle = &material.0;
-        if let Some(material) = materials.get_mut(material_handle) {
-            if let Some(highlight) = highlight {
-                // Apply highlight effect (emissive glow)
-                let intensity = highlight.fade_timer;
-                material.emissive =
-                    LinearRgba::new(intensity * 0.5, intensity * 0.5, intensity * 0.0, 1.0);
-            } else {
-                // Remove highlight
-                material.emissive = LinearRgba::BLACK;
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::AlertState;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_queries_match_names_edges_and_attributes() {
+        let info = NodeInfo {
+            id: "orders".to_string(),
+            name: "Orders DB".to_string(),
+            node_type: Some("database".to_string()),
+            level: 2,
+            metrics: BTreeMap::from([("cpu".to_string(), 0.5)]),
+            state: Some(AlertState::Warn),
+            properties: BTreeMap::from([("owner".to_string(), "payments".to_string())]),
+        };
+        let matches_with = |query: &str, options| {
+            let query = SearchQuery::parse(query, options).unwrap();
+            (
+                query.matches_node(&info),
+                query.matches_edge(Some("Place order"), Some("async")),
+            )
+        };
+        let matches = |query: &str| matches_with(query, MatchOptions::default());
+
+        assert_eq!(matches("orders"), (true, false));
+        assert_eq!(matches("e:ORDER"), (false, true));
+        assert_eq!(matches("E:async"), (false, true));
+        assert_eq!(matches("e:sync"), (false, true));
+        assert_eq!(matches("e:return"), (false, false));
+        assert_eq!(matches("a:type=Database"), (true, false));
+        assert_eq!(matches("a:level=2"), (true, false));
+        assert_eq!(matches("a:state=warn"), (true, false));
+        assert_eq!(matches("a:cpu=0.5"), (true, false));
+        assert_eq!(matches("a:CPU"), (true, false));
+        assert_eq!(matches("a:type=site"), (false, false));
+        assert_eq!(matches("a:owner=payments"), (true, false));
+        assert_eq!(matches("a:team"), (false, false));
+
+        assert_eq!(SearchQuery::parse("", MatchOptions::default()), None);
+        assert_eq!(SearchQuery::parse("e:", MatchOptions::default()), None);
+        assert_eq!(SearchQuery::parse("a:=x", MatchOptions::default()), None);
+
+        let match_case = MatchOptions {
+            match_case: true,
+            ..default()
+        };
+        assert_eq!(matches_with("orders", match_case), (false, false));
+        assert_eq!(matches_with("Orders", match_case), (true, false));
+        assert_eq!(matches_with("a:type=Database", match_case), (false, false));
+        let whole_word = MatchOptions {
+            whole_word: true,
+            ..default()
+        };
+        assert_eq!(matches_with("order", whole_word), (false, false));
+        assert_eq!(matches_with("orders db", whole_word), (true, false));
+        assert_eq!(matches_with("e:sync", whole_word), (false, false));
+        assert_eq!(matches_with("e:order", whole_word), (false, true));
+
+        let mut graph = DiGraph::new();
+        let other = NodeInfo {
+            id: "web".to_string(),
+            name: "Web".to_string(),
+            node_type: None,
+            ..info.clone()
+        };
+        graph.add_node(other);
+        let db = graph.add_node(info.clone());
+        let query = SearchQuery::parse("a:type", MatchOptions::default()).unwrap();
+        assert_eq!(query.matching_nodes(&graph), [db]);
     }
 }
@@ -0,0 +1,405 @@
+use crate::graph_state;
+use crate::highlight::apply_edge_highlight_materials;
+use crate::hover::update_hovered_node;
+use crate::types::{
+    Dimmed, EdgeArrowHead, EdgeDeclutter, EdgeShaft, EditState, GraphData, GraphEdge, GraphNode,
+    HoverState, OriginalMaterial, PathTrace, SearchState, SelectedNodes,
+};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_panorbit_camera::EguiWantsFocus;
+use petgraph::graph::NodeIndex;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// How far the cursor may move between press and release for a click,
+/// rather than an orbit drag
+pub const CLICK_DISTANCE: f32 = 4.0;
+
+/// Opacity of nodes and edges off the traced paths
+const DIMMED_ALPHA: f32 = 0.12;
+
+/// Click selection, and tracing the paths that connect the selected nodes
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedNodes>()
+            .init_resource::<PathTrace>()
+            .init_resource::<EdgeDeclutter>()
+            .add_systems(
+                Update,
+                (
+                    select_clicked_nodes.after(update_hovered_node),
+                    trace_selected_paths.run_if(
+                        resource_changed::<SelectedNodes>
+                            .or(resource_exists_and_changed::<GraphData>),
+                    ),
+                    apply_path_dimming.after(apply_edge_highlight_materials),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Clicking a node selects it, Shift+click adds or removes it, and clicking
+/// empty space clears the selection
+#[allow(clippy::too_many_arguments)]
+pub fn select_clicked_nodes(
+    mouse: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    egui_focus: Option<Res<EguiWantsFocus>>,
+    hover: Res<HoverState>,
+    edit: Res<EditState>,
+    node_query: Query<&GraphNode>,
+    mut selection: ResMut<SelectedNodes>,
+    mut pressed_at: Local<Option<Vec2>>,
+) {
+    let cursor = window_query.single().ok().and_then(Window::cursor_position);
+    // Edit mode uses clicks for its own gestures
+    if edit.active || egui_focus.is_some_and(|focus| focus.curr) {
+        *pressed_at = None;
+        return;
+    }
+    if mouse.just_pressed(MouseButton::Left) {
+        *pressed_at = cursor;
+    }
+    if !mouse.just_released(MouseButton::Left) {
+        return;
+    }
+    let Some((from, to)) = pressed_at.take().zip(cursor) else {
+        return;
+    };
+    if from.distance(to) > CLICK_DISTANCE {
+        return;
+    }
+
+    let clicked = hover
+        .node
+        .and_then(|entity| node_query.get(entity).ok())
+        .map(|node| node.id.clone());
+    let toggle = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    match (clicked, toggle) {
+        (Some(id), true) => {
+            if !selection.0.remove(&id) {
+                selection.0.insert(id);
+            }
+        }
+        (Some(id), false) => {
+            selection.0.clear();
+            selection.0.insert(id);
+        }
+        (None, false) if !selection.0.is_empty() => selection.0.clear(),
+        (None, _) => {}
+    }
+}
+
+/// Recomputes the traced paths when the selection or the graph changes
+pub fn trace_selected_paths(
+    selection: Res<SelectedNodes>,
+    graph_data: Option<Res<GraphData>>,
+    mut trace: ResMut<PathTrace>,
+) {
+    let seeds: Vec<NodeIndex> = graph_data
+        .as_deref()
+        .map(|data| {
+            selection
+                .0
+                .iter()
+                .filter_map(|id| data.node_map.get(id).copied())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    *trace = match graph_data {
+        Some(data) if seeds.len() >= 2 => {
+            let (nodes, edges) = trace_paths(&data, &seeds);
+            PathTrace { nodes, edges }
+        }
+        _ => PathTrace::default(),
+    };
+}
+
+/// The seeds plus every node and edge on a shortest path between two of them,
+/// ignoring edge direction
+pub fn trace_paths(
+    data: &graph_state::GraphData,
+    seeds: &[NodeIndex],
+) -> (HashSet<NodeIndex>, HashSet<(NodeIndex, NodeIndex)>) {
+    let mut nodes: HashSet<NodeIndex> = seeds.iter().copied().collect();
+    let mut edges = HashSet::new();
+    for (i, &start) in seeds.iter().enumerate() {
+        let parents = shortest_path_tree(data, start);
+        for &end in &seeds[i + 1..] {
+            // Unreachable seeds are still shown, just without a path
+            if !parents.contains_key(&end) {
+                continue;
+            }
+            let mut node = end;
+            while node != start {
+                let parent = parents[&node];
+                // The path may run against the edge's direction
+                let edge = if data.graph.find_edge(parent, node).is_some() {
+                    (parent, node)
+                } else {
+                    (node, parent)
+                };
+                edges.insert(edge);
+                nodes.insert(parent);
+                node = parent;
+            }
+        }
+    }
+    (nodes, edges)
+}
+
+/// Breadth-first parent of every node reachable from `start`
+fn shortest_path_tree(
+    data: &graph_state::GraphData,
+    start: NodeIndex,
+) -> HashMap<NodeIndex, NodeIndex> {
+    let mut parents = HashMap::from([(start, start)]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        for neighbor in data.graph.neighbors_undirected(node) {
+            if let Entry::Vacant(entry) = parents.entry(neighbor) {
+                entry.insert(node);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    parents
+}
+
+/// Fades everything off the traced paths, and edges touching none of the
+/// nodes in focus when decluttering, through translucent copies of the shared
+/// materials
+///
+/// Highlighted nodes are left alone while their highlight material is in
+/// place, so the two material swaps never undo each other.
+#[allow(
+    clippy::type_complexity,
+    clippy::implicit_hasher,
+    clippy::too_many_arguments
+)]
+pub fn apply_path_dimming(
+    mut commands: Commands,
+    trace: Res<PathTrace>,
+    declutter: Res<EdgeDeclutter>,
+    hover: Res<HoverState>,
+    selected: Res<SelectedNodes>,
+    search_state: Res<SearchState>,
+    all_nodes: Query<(Entity, &GraphNode)>,
+    mut node_query: Query<
+        (
+            Entity,
+            &GraphNode,
+            &mut MeshMaterial3d<StandardMaterial>,
+            Option<&Dimmed>,
+        ),
+        Without<OriginalMaterial>,
+    >,
+    edge_query: Query<&GraphEdge>,
+    mut edge_part_query: Query<
+        (
+            Entity,
+            &ChildOf,
+            &mut MeshMaterial3d<StandardMaterial>,
+            Option<&Dimmed>,
+        ),
+        (
+            Or<(With<EdgeShaft>, With<EdgeArrowHead>)>,
+            Without<GraphNode>,
+            Without<OriginalMaterial>,
+        ),
+    >,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut dimmed_copies: Local<HashMap<(AssetId<StandardMaterial>, u32), Handle<StandardMaterial>>>,
+) {
+    // Copies at an opacity nothing is dimmed to any more would pile up
+    dimmed_copies.retain(|&(_, alpha), _| {
+        alpha == DIMMED_ALPHA.to_bits() || alpha == declutter.opacity.to_bits()
+    });
+    let mut set_dimmed = |entity,
+                          material: &mut MeshMaterial3d<StandardMaterial>,
+                          dimmed: Option<&Dimmed>,
+                          alpha: Option<f32>| {
+        let original = dimmed.map_or_else(|| material.0.clone(), |dimmed| dimmed.0.clone());
+        let Some(alpha) = alpha else {
+            material.0 = original;
+            commands.entity(entity).remove::<Dimmed>();
+            return;
+        };
+        let copy = match dimmed_copies.entry((original.id(), alpha.to_bits())) {
+            Entry::Occupied(entry) => entry.get().clone(),
+            Entry::Vacant(entry) => {
+                let Some(mut copy) = materials.get(&original).cloned() else {
+                    return;
+                };
+                copy.base_color.set_alpha(copy.base_color.alpha() * alpha);
+                copy.emissive *= alpha;
+                copy.alpha_mode = AlphaMode::Blend;
+                entry.insert(materials.add(copy)).clone()
+            }
+        };
+        material.0 = copy;
+        commands.entity(entity).insert(Dimmed(original, alpha));
+    };
+
+    let active = trace.is_active();
+    for (entity, node, mut material, dimmed) in &mut node_query {
+        let alpha = (active && !trace.nodes.contains(&node.index)).then_some(DIMMED_ALPHA);
+        // Only touch the material when the state flips, to keep change detection quiet
+        if alpha != dimmed.map(|dimmed| dimmed.1) {
+            set_dimmed(entity, &mut material, dimmed, alpha);
+        }
+    }
+    let focus = declutter_focus(*declutter, &hover, &selected, &search_state, &all_nodes);
+    for (entity, child_of, mut material, dimmed) in &mut edge_part_query {
+        let Ok(edge) = edge_query.get(child_of.parent()) else {
+            continue;
+        };
+        let alpha = if active && !trace.edges.contains(&(edge.from, edge.to)) {
+            Some(DIMMED_ALPHA)
+        } else {
+            focus
+                .as_ref()
+                .filter(|focus| !focus.contains(&edge.from) && !focus.contains(&edge.to))
+                .map(|_| declutter.opacity)
+        };
+        if alpha != dimmed.map(|dimmed| dimmed.1) {
+            set_dimmed(entity, &mut material, dimmed, alpha);
+        }
+    }
+
+    // Copies of materials from earlier scenes would otherwise pile up
+    if !active && focus.is_none() {
+        dimmed_copies.clear();
+    }
+}
+
+/// The hovered, selected and search-matched nodes, whose edges stay opaque
+/// while the rest fade; None if decluttering is off or no node is in focus
+fn declutter_focus(
+    declutter: EdgeDeclutter,
+    hover: &HoverState,
+    selected: &SelectedNodes,
+    search_state: &SearchState,
+    all_nodes: &Query<(Entity, &GraphNode)>,
+) -> Option<HashSet<NodeIndex>> {
+    if declutter.opacity >= 1.0 {
+        return None;
+    }
+    let mut focus: HashSet<NodeIndex> = all_nodes
+        .iter_many(hover.node.iter().chain(&search_state.matching_nodes))
+        .map(|(_, node)| node.index)
+        .collect();
+    if !selected.0.is_empty() {
+        focus.extend(
+            all_nodes
+                .iter()
+                .filter(|(_, node)| selected.0.contains(&node.id))
+                .map(|(_, node)| node.index),
+        );
+    }
+    (!focus.is_empty()).then_some(focus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_trace_paths_joins_selected_nodes() {
+        // Two branches off a hub, plus a detour and a node off to the side
+        let content = r"digraph {
+            A -> Hub
+            Hub -> B
+            Hub -> C
+            A -> X
+            X -> Y
+            Y -> B
+            C -> Side
+        }";
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let data = state.as_graph_data();
+        let index = |id: &str| data.node_map[id];
+
+        let (nodes, edges) = trace_paths(&data, &[index("A"), index("B"), index("C")]);
+        let mut ids: Vec<_> = data
+            .node_map
+            .iter()
+            .filter(|(_, index)| nodes.contains(index))
+            .map(|(id, _)| id.as_str())
+            .collect();
+        ids.sort_unstable();
+        assert_eq!(ids, ["A", "B", "C", "Hub"]);
+        assert_eq!(edges.len(), 3);
+        // Edges keep the graph's direction even when walked backwards
+        assert!(edges.contains(&(index("Hub"), index("C"))));
+    }
+
+    #[test]
+    fn test_declutter_fades_edges_away_from_the_focus() {
+        let mut app = App::new();
+        app.init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<PathTrace>()
+            .init_resource::<HoverState>()
+            .init_resource::<SelectedNodes>()
+            .init_resource::<SearchState>()
+            .insert_resource(EdgeDeclutter { opacity: 0.25 })
+            .add_systems(Update, apply_path_dimming);
+
+        let shared = app
+            .world_mut()
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial::default());
+        let mut nodes = Vec::new();
+        for (index, id) in ["a", "b", "c"].into_iter().enumerate() {
+            let node = GraphNode {
+                id: id.to_string(),
+                name: id.to_string(),
+                index: NodeIndex::new(index),
+            };
+            nodes.push(app.world_mut().spawn(node).id());
+        }
+        let mut spawn_edge = |from, to| {
+            app.world_mut()
+                .spawn(GraphEdge {
+                    from: NodeIndex::new(from),
+                    to: NodeIndex::new(to),
+                    ..default()
+                })
+                .with_child((EdgeShaft, MeshMaterial3d(shared.clone())))
+                .id()
+        };
+        let near = spawn_edge(0, 1);
+        let far = spawn_edge(1, 2);
+        let alpha = |app: &App, edge| {
+            let shaft = app.world().get::<Children>(edge).unwrap()[0];
+            let handle = &app
+                .world()
+                .get::<MeshMaterial3d<StandardMaterial>>(shaft)
+                .unwrap()
+                .0;
+            let materials = app.world().resource::<Assets<StandardMaterial>>();
+            materials.get(handle).unwrap().base_color.alpha()
+        };
+
+        // Nothing in focus leaves every edge opaque
+        app.update();
+        assert!((alpha(&app, far) - 1.0).abs() < f32::EPSILON);
+
+        app.world_mut().resource_mut::<HoverState>().node = Some(nodes[0]);
+        app.update();
+        assert!((alpha(&app, near) - 1.0).abs() < f32::EPSILON);
+        assert!((alpha(&app, far) - 0.25).abs() < f32::EPSILON);
+
+        app.world_mut().resource_mut::<HoverState>().node = None;
+        app.update();
+        assert!((alpha(&app, far) - 1.0).abs() < f32::EPSILON);
+    }
+}
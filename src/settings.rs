@@ -0,0 +1,473 @@
+use crate::accessibility::{Accessibility, UI_SCALE_RANGE, UI_SCALE_STEP};
+use crate::antialiasing::{AntiAliasing, MSAA_CHOICES, msaa_name};
+use crate::edge_lines::EdgeLines;
+use crate::keybindings::{Action, KeyBindings};
+use crate::lighting::{Lighting, LightingPreset};
+use crate::palette::Palette;
+use crate::sidecar::SidecarFile;
+use crate::theme::Theme;
+use crate::types::{
+    CameraSettings, EdgeDeclutter, ExportSettings, GraphData, GridSettings, LabelSettings,
+    LayoutMode, LayoutSettings, ReducedMotion, SearchState, SettingsPanel, ViewSettings,
+};
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use petgraph::algo::is_cyclic_directed;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+
+/// Sidecar key the panel's per-graph settings are saved under
+const SIDECAR_KEY: &str = "settings";
+
+/// The settings window, opened with Tab, and saving what it sets per graph
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, toggle_settings_panel)
+            .add_systems(EguiContextPass, settings_panel_ui)
+            .add_systems(
+                Update,
+                save_panel_settings.run_if(
+                    resource_exists::<SidecarFile>
+                        .and(
+                            resource_changed::<LayoutSettings>.or(resource_changed::<ViewSettings>),
+                        )
+                        .and(not(resource_added::<LayoutSettings>)),
+                ),
+            );
+    }
+}
+
+/// The panel's settings that belong to one graph rather than to the user,
+/// kept in the input's sidecar: layout spacing and hidden node types
+///
+/// None of them has a command-line option, so restoring them never overrides
+/// what was asked for at startup.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedSettings {
+    pub base_radius: f32,
+    pub ring_spacing: f32,
+    pub level_height: f32,
+    pub flatten: bool,
+    pub hidden_types: BTreeSet<String>,
+}
+
+impl Default for SavedSettings {
+    fn default() -> Self {
+        Self::new(&LayoutSettings::default(), &ViewSettings::default())
+    }
+}
+
+impl SavedSettings {
+    pub fn new(layout: &LayoutSettings, view: &ViewSettings) -> Self {
+        Self {
+            base_radius: layout.base_radius,
+            ring_spacing: layout.ring_spacing,
+            level_height: layout.level_height,
+            flatten: layout.flatten,
+            hidden_types: view.hidden_types.iter().cloned().collect(),
+        }
+    }
+
+    /// Puts the saved values into the settings resources
+    pub fn apply(&self, layout: &mut LayoutSettings, view: &mut ViewSettings) {
+        layout.base_radius = self.base_radius;
+        layout.ring_spacing = self.ring_spacing;
+        layout.level_height = self.level_height;
+        layout.flatten = self.flatten;
+        view.hidden_types = self.hidden_types.iter().cloned().collect();
+    }
+
+    /// The settings saved in `sidecar`, or the defaults when it has none or
+    /// they can't be read
+    pub fn load(sidecar: &SidecarFile) -> Self {
+        let Some(value) = sidecar.read_or_warn().remove(SIDECAR_KEY) else {
+            return Self::default();
+        };
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            eprintln!(
+                "Ignoring saved settings in {}: {e}",
+                sidecar.path().display()
+            );
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, sidecar: &SidecarFile) -> std::io::Result<()> {
+        sidecar.write_key(SIDECAR_KEY, serde_json::to_value(self)?)
+    }
+}
+
+/// Writes the per-graph settings to the sidecar whenever they change
+pub fn save_panel_settings(
+    sidecar: Res<SidecarFile>,
+    layout: Res<LayoutSettings>,
+    view: Res<ViewSettings>,
+) {
+    if let Err(e) = SavedSettings::new(&layout, &view).save(&sidecar) {
+        eprintln!("Failed to save {}: {e}", sidecar.path().display());
+    }
+}
+
+pub fn toggle_settings_panel(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    mut panel: ResMut<SettingsPanel>,
+) {
+    if !search_state.active && bindings.just_pressed(Action::ToggleSettings, &keyboard_input) {
+        panel.open = !panel.open;
+    }
+}
+
+/// Draws the settings window and applies edits to the settings resources
+///
+/// Edits are made on copies and written back only when something changed, so
+/// the scene is rebuilt only when a layout or view setting actually moves.
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+pub fn settings_panel_ui(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<SettingsPanel>,
+    mut layout: ResMut<LayoutSettings>,
+    mut view: ResMut<ViewSettings>,
+    mut labels: ResMut<LabelSettings>,
+    mut camera: ResMut<CameraSettings>,
+    mut clear_color: ResMut<ClearColor>,
+    mut theme: ResMut<Theme>,
+    mut grid: ResMut<GridSettings>,
+    mut lighting: ResMut<Lighting>,
+    mut anti_aliasing: ResMut<AntiAliasing>,
+    mut export: ResMut<ExportSettings>,
+    mut declutter: ResMut<EdgeDeclutter>,
+    mut edge_lines: ResMut<EdgeLines>,
+    // Paired up to stay within Bevy's limit of 16 system parameters
+    (mut motion, mut accessibility): (ResMut<ReducedMotion>, ResMut<Accessibility>),
+    graph_data: Option<Res<GraphData>>,
+) {
+    if !panel.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let mut new_layout = layout.clone();
+    let mut new_view = view.clone();
+    let mut new_labels = labels.clone();
+    let mut new_camera = camera.clone();
+    let mut background = clear_color.0.to_srgba().to_f32_array_no_alpha();
+    let mut background_changed = false;
+    let mut new_theme = *theme;
+    let mut new_grid = grid.clone();
+    let mut new_lighting = lighting.clone();
+    let mut new_anti_aliasing = *anti_aliasing;
+    let mut export_radius = export.radius;
+    let mut new_edge_lines = edge_lines.clone();
+    let mut new_declutter = *declutter;
+    let mut new_motion = motion.clone();
+    let mut new_accessibility = *accessibility;
+    let mut open = panel.open;
+
+    // Node types present in the graph, for the filter checkboxes
+    let node_types: BTreeSet<String> = graph_data
+        .iter()
+        .flat_map(|data| data.graph.node_weights())
+        .map(|node| node.node_type.clone().unwrap_or_default())
+        .collect();
+    // Only acyclic graphs have a topological order
+    let acyclic = graph_data
+        .as_deref()
+        .is_none_or(|data| !is_cyclic_directed(&data.graph));
+
+    egui::Window::new("Settings")
+        .open(&mut open)
+        .resizable(false)
+        .default_pos([10.0, 240.0])
+        .show(ctx, |ui| {
+            ui.heading("Layout");
+            ui.horizontal(|ui| {
+                for mode in [LayoutMode::Hierarchical, LayoutMode::Topological] {
+                    let enabled = acyclic || mode != LayoutMode::Topological;
+                    ui.add_enabled_ui(enabled, |ui| {
+                        ui.selectable_value(&mut new_layout.mode, mode, mode.name());
+                    });
+                }
+            });
+            ui.add(egui::Slider::new(&mut new_layout.base_radius, 1.0..=30.0).text("Base radius"));
+            ui.add(
+                egui::Slider::new(&mut new_layout.ring_spacing, 0.0..=10.0).text("Ring spacing"),
+            );
+            ui.add(
+                egui::Slider::new(&mut new_layout.level_height, 0.0..=10.0).text("Level height"),
+            );
+            ui.checkbox(&mut new_layout.flatten, "Flat 2D view");
+
+            ui.separator();
+            ui.heading("Labels");
+            ui.add(
+                egui::Slider::new(&mut new_labels.visibility_distance, 1.0..=100.0)
+                    .text("Visibility distance"),
+            );
+            ui.checkbox(&mut new_labels.show_all_labels, "Show all labels");
+            ui.add(egui::Slider::new(&mut new_labels.max_width, 0..=80).text("Max width"))
+                .on_hover_text("Characters per line before a label is cut short; 0 for no limit");
+            ui.checkbox(&mut new_labels.wrap, "Wrap long labels");
+
+            ui.separator();
+            ui.heading("Colors");
+            ui.horizontal(|ui| {
+                ui.label("Theme");
+                ui.selectable_value(&mut new_theme, Theme::Dark, Theme::Dark.name());
+                ui.selectable_value(&mut new_theme, Theme::Light, Theme::Light.name());
+            });
+            ui.horizontal(|ui| {
+                ui.label("Palette");
+                for palette in Palette::ALL {
+                    ui.selectable_value(&mut new_view.palette, palette, palette.name());
+                }
+            });
+            ui.checkbox(&mut new_view.distinct_shapes, "A shape for every type")
+                .on_hover_text("Tell node types apart by shape rather than color");
+            ui.horizontal(|ui| {
+                background_changed = ui.color_edit_button_rgb(&mut background).changed();
+                ui.label("Background");
+            });
+            ui.horizontal(|ui| {
+                let mut edge = new_view.edge_color.to_srgba().to_f32_array_no_alpha();
+                if ui.color_edit_button_rgb(&mut edge).changed() {
+                    new_view.edge_color = Color::srgb_from_array(edge);
+                }
+                ui.label("Edges");
+            });
+            ui.add(
+                egui::Slider::new(&mut new_declutter.opacity, 0.0..=1.0)
+                    .text("Unfocused edge opacity"),
+            )
+            .on_hover_text("Fade edges touching none of the hovered, selected or matched nodes");
+            ui.checkbox(&mut new_edge_lines.enabled, "Draw edges as lines")
+                .on_hover_text("Thin lines instead of tubes, cheaper for big graphs");
+            ui.checkbox(&mut new_view.merge_edges, "Merge edges into one mesh")
+                .on_hover_text(
+                    "Draw edges as plain lines in a single mesh, much faster for huge graphs \
+                     but without hovering or fading them one by one",
+                );
+
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut new_grid.show_grid, "Grid");
+                ui.checkbox(&mut new_grid.show_axes, "Axes");
+            });
+            ui.checkbox(&mut new_grid.below_ground, "Orbit below ground")
+                .on_hover_text(
+                    "Hide the grid while the camera is under it instead of stopping there",
+                );
+
+            ui.separator();
+            ui.heading("Lighting");
+            lighting_controls(ui, &mut new_lighting);
+
+            ui.separator();
+            ui.heading("Anti-aliasing");
+            anti_aliasing_controls(ui, &mut new_anti_aliasing);
+
+            ui.separator();
+            ui.heading("Accessibility");
+            ui.checkbox(&mut new_motion.enabled, "Reduce motion")
+                .on_hover_text("Stop nodes pulsing, alerts blinking and particles flowing");
+            ui.checkbox(&mut new_accessibility.high_contrast, "High contrast")
+                .on_hover_text("Opaque panels, outlined labels and thicker outlines");
+            ui.add(
+                egui::Slider::new(&mut new_accessibility.ui_scale, UI_SCALE_RANGE)
+                    .step_by(f64::from(UI_SCALE_STEP))
+                    .text("UI scale"),
+            )
+            .on_hover_text("Size of the overlays, these windows and node labels");
+
+            ui.separator();
+            ui.heading("Node types");
+            node_type_filters(ui, &node_types, &mut new_view.hidden_types);
+
+            ui.separator();
+            ui.heading("Camera");
+            camera_controls(ui, &mut new_camera);
+
+            ui.separator();
+            ui.heading("Export");
+            ui.add(egui::Slider::new(&mut export_radius, 0..=5).text("Radius (hops)"));
+        });
+
+    panel.open = open;
+    if new_layout != *layout {
+        *layout = new_layout;
+    }
+    if new_view != *view {
+        *view = new_view;
+    }
+    if new_labels != *labels {
+        *labels = new_labels;
+    }
+    if new_camera != *camera {
+        *camera = new_camera;
+    }
+    // Only written when edited so a transparent background survives
+    if background_changed {
+        clear_color.0 = Color::srgb_from_array(background);
+    }
+    if export_radius != export.radius {
+        export.radius = export_radius;
+    }
+    if new_lighting != *lighting {
+        *lighting = new_lighting;
+    }
+    if new_anti_aliasing != *anti_aliasing {
+        *anti_aliasing = new_anti_aliasing;
+    }
+    if new_declutter != *declutter {
+        *declutter = new_declutter;
+    }
+    if new_edge_lines != *edge_lines {
+        *edge_lines = new_edge_lines;
+    }
+    if new_motion != *motion {
+        *motion = new_motion;
+    }
+    if new_accessibility != *accessibility {
+        *accessibility = new_accessibility;
+    }
+    if new_grid != *grid {
+        *grid = new_grid;
+    }
+    if new_theme != *theme {
+        *theme = new_theme;
+    }
+}
+
+/// Sliders tuning how the camera moves and settles
+fn camera_controls(ui: &mut egui::Ui, camera: &mut CameraSettings) {
+    ui.add(egui::Slider::new(&mut camera.speed, 0.5..=50.0).text("Movement speed"));
+    ui.add(egui::Slider::new(&mut camera.pan_sensitivity, 0.1..=5.0).text("Pan sensitivity"));
+    ui.add(egui::Slider::new(&mut camera.orbit_sensitivity, 0.1..=5.0).text("Orbit sensitivity"));
+    ui.add(egui::Slider::new(&mut camera.zoom_sensitivity, 0.1..=5.0).text("Zoom sensitivity"));
+    ui.add(egui::Slider::new(&mut camera.smoothness, 0.0..=0.99).text("Smoothness"));
+    ui.add(
+        egui::Slider::new(&mut camera.pitch_limit, 0.0..=89f32.to_radians())
+            .custom_formatter(|radians, _| format!("{:.0}°", radians.to_degrees()))
+            .text("Pitch limit"),
+    );
+    ui.add(egui::Slider::new(&mut camera.inertia, 0.0..=2.0).text("Inertia (s)"));
+}
+
+/// One checkbox per node type; unchecked types are added to `hidden_types`
+fn node_type_filters(
+    ui: &mut egui::Ui,
+    node_types: &BTreeSet<String>,
+    hidden_types: &mut HashSet<String>,
+) {
+    for node_type in node_types {
+        let name = if node_type.is_empty() {
+            "(untyped)"
+        } else {
+            node_type
+        };
+        let mut shown = !hidden_types.contains(node_type);
+        if ui.checkbox(&mut shown, name).changed() {
+            if shown {
+                hidden_types.remove(node_type);
+            } else {
+                hidden_types.insert(node_type.clone());
+            }
+        }
+    }
+}
+
+fn lighting_controls(ui: &mut egui::Ui, lighting: &mut Lighting) {
+    ui.horizontal(|ui| {
+        for preset in LightingPreset::ALL {
+            ui.selectable_value(&mut lighting.preset, preset, preset.name());
+        }
+    });
+    ui.checkbox(&mut lighting.skybox, "Skybox");
+    ui.checkbox(&mut lighting.shadows, "Shadows");
+    ui.add(egui::Slider::new(&mut lighting.fog, 0.0..=0.1).text("Fog density"));
+}
+
+fn anti_aliasing_controls(ui: &mut egui::Ui, anti_aliasing: &mut AntiAliasing) {
+    ui.add_enabled_ui(!anti_aliasing.taa, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("MSAA");
+            for msaa in MSAA_CHOICES {
+                ui.selectable_value(&mut anti_aliasing.msaa, msaa, msaa_name(msaa));
+            }
+        });
+    });
+    ui.checkbox(&mut anti_aliasing.taa, "Temporal anti-aliasing")
+        .on_hover_text("Calms the shimmer of thin edges, in place of MSAA; not in the flat view");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn sidecar(name: &str) -> SidecarFile {
+        let input = std::env::temp_dir().join(format!(
+            "dotspace-settings-{name}-{}.dot",
+            std::process::id()
+        ));
+        SidecarFile::for_input(input.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_saved_settings_round_trip() {
+        let sidecar = sidecar("round-trip");
+        let layout = LayoutSettings {
+            base_radius: 12.0,
+            level_height: 0.5,
+            flatten: true,
+            ..default()
+        };
+        let view = ViewSettings {
+            hidden_types: HashSet::from(["database".to_string(), String::new()]),
+            ..default()
+        };
+        sidecar
+            .write_key("pinned_labels", vec!["A"].into())
+            .unwrap();
+        SavedSettings::new(&layout, &view).save(&sidecar).unwrap();
+
+        let mut restored_layout = LayoutSettings::default();
+        let mut restored_view = ViewSettings::default();
+        SavedSettings::load(&sidecar).apply(&mut restored_layout, &mut restored_view);
+        assert!(restored_layout == layout);
+        assert!(restored_view == view);
+        // The rest of the sidecar is left alone
+        assert!(sidecar.pinned_labels().0.contains("A"));
+
+        fs::remove_file(sidecar.path()).unwrap();
+    }
+
+    #[test]
+    fn test_missing_or_corrupt_settings_fall_back_to_defaults() {
+        let sidecar = sidecar("corrupt");
+        assert_eq!(SavedSettings::load(&sidecar), SavedSettings::default());
+
+        fs::write(sidecar.path(), "{ not json").unwrap();
+        assert_eq!(SavedSettings::load(&sidecar), SavedSettings::default());
+
+        fs::write(
+            sidecar.path(),
+            r#"{ "settings": { "base_radius": "wide" } }"#,
+        )
+        .unwrap();
+        assert_eq!(SavedSettings::load(&sidecar), SavedSettings::default());
+
+        // Settings saved before one was added keep the default for it
+        fs::write(sidecar.path(), r#"{ "settings": { "flatten": true } }"#).unwrap();
+        let saved = SavedSettings::load(&sidecar);
+        assert!(saved.flatten);
+        assert!((saved.base_radius - LayoutSettings::default().base_radius).abs() < f32::EPSILON);
+
+        fs::remove_file(sidecar.path()).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use crate::input;
+use crate::types::{NodeNotes, PinnedLabels};
+use bevy::prelude::*;
+use serde_json::{Map, Value};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Suffix appended to the input file name to get its sidecar
+const SIDECAR_SUFFIX: &str = ".dotspace.json";
+
+/// View state kept next to a local input file, e.g. `graph.dot.dotspace.json`
+///
+/// The file is a JSON object; each feature owns one top-level key and leaves
+/// the others untouched when it saves.
+#[derive(Resource)]
+pub struct SidecarFile {
+    path: PathBuf,
+}
+
+impl SidecarFile {
+    /// The sidecar for a local input file; URLs and stdin don't get one
+    pub fn for_input(location: &str) -> Option<Self> {
+        if input::is_url(location) {
+            return None;
+        }
+        let mut path = PathBuf::from(location).into_os_string();
+        path.push(SIDECAR_SUFFIX);
+        Some(Self { path: path.into() })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the sidecar's top-level object; a missing file is an empty object
+    pub fn read(&self) -> io::Result<Map<String, Value>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => match serde_json::from_str(&content)? {
+                Value::Object(map) => Ok(map),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "sidecar is not a JSON object",
+                )),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Map::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replaces one top-level key, keeping everything else in the file
+    pub fn write_key(&self, key: &str, value: Value) -> io::Result<()> {
+        let mut map = self.read()?;
+        map.insert(key.to_string(), value);
+        let json = serde_json::to_string_pretty(&Value::Object(map))?;
+        fs::write(&self.path, json + "\n")
+    }
+
+    /// Like `read`, but a broken sidecar is reported and treated as empty
+    pub fn read_or_warn(&self) -> Map<String, Value> {
+        self.read().unwrap_or_else(|e| {
+            eprintln!("Ignoring sidecar {}: {e}", self.path.display());
+            Map::new()
+        })
+    }
+
+    /// Node ids whose labels were pinned in an earlier session
+    pub fn pinned_labels(&self) -> PinnedLabels {
+        let ids = self
+            .read_or_warn()
+            .get("pinned_labels")
+            .and_then(Value::as_array)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        PinnedLabels(ids)
+    }
+
+    /// Notes attached to nodes in an earlier session
+    pub fn node_notes(&self) -> NodeNotes {
+        let notes = self
+            .read_or_warn()
+            .get("notes")
+            .and_then(Value::as_object)
+            .map(|notes| {
+                notes
+                    .iter()
+                    .filter_map(|(id, note)| Some((id.clone(), note.as_str()?.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default();
+        NodeNotes(notes)
+    }
+}
+
+/// Writes pinned labels to the sidecar whenever they change
+pub fn save_pinned_labels(sidecar: Res<SidecarFile>, pinned: Res<PinnedLabels>) {
+    let mut ids: Vec<_> = pinned.0.iter().cloned().collect();
+    ids.sort();
+    if let Err(e) = sidecar.write_key("pinned_labels", Value::from(ids)) {
+        eprintln!("Failed to save {}: {e}", sidecar.path().display());
+    }
+}
+
+/// Writes node notes to the sidecar whenever they change
+pub fn save_node_notes(sidecar: Res<SidecarFile>, notes: Res<NodeNotes>) {
+    let notes: Map<String, Value> = notes
+        .0
+        .iter()
+        .map(|(id, note)| (id.clone(), Value::from(note.as_str())))
+        .collect();
+    if let Err(e) = sidecar.write_key("notes", Value::Object(notes)) {
+        eprintln!("Failed to save {}: {e}", sidecar.path().display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pinned_labels_round_trip() {
+        let input =
+            std::env::temp_dir().join(format!("dotspace-sidecar-{}.dot", std::process::id()));
+        let sidecar = SidecarFile::for_input(input.to_str().unwrap()).unwrap();
+        assert!(
+            sidecar
+                .path()
+                .to_string_lossy()
+                .ends_with(".dot.dotspace.json")
+        );
+        assert!(sidecar.pinned_labels().0.is_empty());
+
+        // Other keys survive an update
+        sidecar.write_key("other", Value::from(1)).unwrap();
+        sidecar
+            .write_key("pinned_labels", Value::from(vec!["A", "B"]))
+            .unwrap();
+        let pinned = sidecar.pinned_labels();
+        assert!(pinned.0.contains("A") && pinned.0.contains("B"));
+        assert_eq!(sidecar.read().unwrap()["other"], 1);
+
+        sidecar
+            .write_key("notes", serde_json::json!({ "A": "Owned by payments" }))
+            .unwrap();
+        assert_eq!(sidecar.node_notes().0["A"], "Owned by payments");
+        assert!(sidecar.pinned_labels().0.contains("A"));
+
+        fs::remove_file(sidecar.path()).unwrap();
+        assert!(SidecarFile::for_input("https://example.com/g.dot").is_none());
+    }
+}
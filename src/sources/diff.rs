@@ -0,0 +1,157 @@
+use super::{GraphEventSource, SourceError};
+use crate::events::GraphEvent;
+use crate::graph_state::GraphState;
+use std::fmt::Write;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+/// Live source that shows one graph, then changes it into another
+///
+/// The changes arrive together after `delay`, so added nodes and edges grow
+/// in, removed ones shrink away and changed nodes pulse.
+pub struct DiffSource {
+    before: Vec<GraphEvent>,
+    changes: Vec<GraphEvent>,
+    delay: Duration,
+}
+
+impl DiffSource {
+    pub fn new(before: &GraphState, after: &GraphState, delay: Duration) -> Self {
+        Self {
+            before: GraphState::new().changes_to(before),
+            changes: before.changes_to(after),
+            delay,
+        }
+    }
+
+    /// Counts of what changed, e.g. "2 nodes added, 1 changed; 1 edge removed"
+    pub fn summary(&self) -> String {
+        let count = |kind: fn(&GraphEvent) -> bool| self.changes.iter().filter(|e| kind(e)).count();
+        let describe = |noun: &str, counts: [(usize, &str); 3]| {
+            let mut counts = counts.into_iter().filter(|&(n, _)| n > 0);
+            let (n, verb) = counts.next()?;
+            let plural = if n == 1 { "" } else { "s" };
+            let mut text = format!("{n} {noun}{plural} {verb}");
+            for (n, verb) in counts {
+                let _ = write!(text, ", {n} {verb}");
+            }
+            Some(text)
+        };
+        let nodes = describe(
+            "node",
+            [
+                (count(|e| matches!(e, GraphEvent::AddNode { .. })), "added"),
+                (
+                    count(|e| matches!(e, GraphEvent::RemoveNode { .. })),
+                    "removed",
+                ),
+                (
+                    count(|e| matches!(e, GraphEvent::UpdateNode { .. })),
+                    "changed",
+                ),
+            ],
+        );
+        let edges = describe(
+            "edge",
+            [
+                (
+                    count(|e| matches!(e, GraphEvent::AddRichEdge { .. })),
+                    "added",
+                ),
+                (
+                    count(|e| matches!(e, GraphEvent::RemoveEdge { .. })),
+                    "removed",
+                ),
+                (
+                    count(|e| matches!(e, GraphEvent::UpdateEdge { .. })),
+                    "changed",
+                ),
+            ],
+        );
+        let parts: Vec<_> = [nodes, edges].into_iter().flatten().collect();
+        if parts.is_empty() {
+            "no changes".to_string()
+        } else {
+            parts.join("; ")
+        }
+    }
+}
+
+impl GraphEventSource for DiffSource {
+    fn source_name(&self) -> &'static str {
+        "Diff"
+    }
+
+    fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+        let mut events = vec![GraphEvent::BatchStart];
+        events.extend(self.before.iter().cloned());
+        events.push(GraphEvent::BatchEnd);
+        Ok(events)
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    fn subscribe(&self, sender: Sender<GraphEvent>) -> Result<(), SourceError> {
+        let mut changes = vec![GraphEvent::BatchStart];
+        changes.extend(self.changes.iter().cloned());
+        changes.push(GraphEvent::BatchEnd);
+        let delay = self.delay;
+        thread::Builder::new()
+            .name("diff".to_string())
+            .spawn(move || {
+                thread::sleep(delay);
+                for event in changes {
+                    // The app has exited once the receiving end is gone
+                    if sender.send(event).is_err() {
+                        return;
+                    }
+                }
+            })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_diff_starts_from_the_old_graph() {
+        let load = |content| {
+            load_graph(&SourceRegistry::default(), content, Some("dot"))
+                .unwrap()
+                .0
+        };
+        let before = load(
+            r"digraph {
+            web -> api
+            api -> cache
+        }",
+        );
+        let after = load(
+            r"digraph {
+            web -> api
+            api -> db
+            api -> queue
+        }",
+        );
+        let source = DiffSource::new(&before, &after, Duration::ZERO);
+
+        let mut shown = GraphState::new();
+        shown.process_events(source.events().unwrap());
+        assert!(shown.changes_to(&before).is_empty());
+        assert_eq!(
+            source.summary(),
+            "2 nodes added, 1 removed; 2 edges added, 1 removed"
+        );
+        assert_eq!(
+            DiffSource::new(&after, &after, Duration::ZERO).summary(),
+            "no changes"
+        );
+    }
+}
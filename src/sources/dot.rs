@@ -1,6 +1,18 @@
-use super::{GraphEventSource, SourceError};
-use crate::events::{EventNodeInfo, GraphEvent};
+pub use super::dot_labels::{FIELDS_PROPERTY, PORT_PROPERTY_PREFIX};
+use super::dot_labels::{node_label, take_html_labels};
+use super::{GraphEventSource, SourceError, UnknownAttribute, line_column};
+use crate::events::{EventEdgeInfo, EventNodeInfo, GraphEvent};
+use crate::graph_state::{LayoutHints, RankDir, SourceLines};
+use bevy::math::Vec3;
 use dotparser::dot;
+use std::collections::{HashMap, HashSet};
+
+/// Node attributes read as layout hints rather than by the parser
+const LAYOUT_ATTRIBUTES: [&str; 4] = ["pos", "pin", "layer", "group"];
+
+/// Scene units per point of a `pos`; 72 points make an inch, and an inch
+/// is two units
+const POS_SCALE: f32 = 1.0 / 36.0;
 
 /// Source for DOT format diagrams
 pub struct DotSource {
@@ -8,6 +20,12 @@ pub struct DotSource {
 }
 
 impl DotSource {
+    /// The parser's events, and the HTML-like labels it saw as placeholders
+    fn parsed(&self) -> (Vec<dotparser::GraphEvent>, Vec<String>) {
+        let (content, html_labels) = take_html_labels(&self.content);
+        (dot::parse(&content), html_labels)
+    }
+
     /// Creates a new DOT source from content
     pub fn new(content: String) -> Self {
         Self { content }
@@ -25,8 +43,18 @@ impl GraphEventSource for DotSource {
     }
 
     fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+        // The DOT parser skips what it can't read, so catch the most common
+        // breakage (an unbalanced brace) ourselves and report where it is
+        check_braces(&self.content)?;
+
         // Parse the DOT content directly to events
-        let dotparser_events = dot::parse(&self.content);
+        let (dotparser_events, html_labels) = self.parsed();
+
+        // The parser adds a node for each `node:port` endpoint of an edge
+        // too; those stand for the node, once
+        let quoted = quoted_strings(&self.content);
+        let mut added = HashSet::new();
+        let mut added_by_port = HashSet::new();
 
         // Convert dotparser events to our internal events
         let mut events = Vec::new();
@@ -45,6 +73,20 @@ impl GraphEventSource for DotSource {
                     node_type,
                     properties,
                 } => {
+                    let mut custom = properties.custom.into_iter().collect();
+                    // The parser labels nodes it only saw in edges with their id
+                    let label = label
+                        .filter(|label| *label != id)
+                        .map(|label| node_label(&label, &html_labels, &mut custom));
+                    let (id, port) = endpoint(&id, &quoted);
+                    let known = added.contains(&id);
+                    if known && (port.is_some() || added_by_port.contains(&id)) {
+                        continue;
+                    }
+                    added.insert(id.clone());
+                    if port.is_some() {
+                        added_by_port.insert(id.clone());
+                    }
                     // Convert to our EventNodeInfo
                     let info = EventNodeInfo {
                         name: label.unwrap_or_else(|| id.clone()),
@@ -56,12 +98,24 @@ impl GraphEventSource for DotSource {
                             Some(dotparser::Position::Layer { level }) => level,
                             _ => 0,
                         },
+                        properties: custom,
                     };
 
                     events.push(GraphEvent::AddNode { id, info });
                 }
                 dotparser::GraphEvent::AddEdge { from, to, .. } => {
-                    events.push(GraphEvent::AddEdge { from, to });
+                    let ((from, from_port), (to, to_port)) =
+                        (endpoint(&from, &quoted), endpoint(&to, &quoted));
+                    if from_port.is_none() && to_port.is_none() {
+                        events.push(GraphEvent::AddEdge { from, to });
+                    } else {
+                        let info = EventEdgeInfo {
+                            from_port,
+                            to_port,
+                            ..Default::default()
+                        };
+                        events.push(GraphEvent::AddRichEdge { from, to, info });
+                    }
                 }
                 _ => {
                     // Ignore other event types for now
@@ -71,12 +125,343 @@ impl GraphEventSource for DotSource {
 
         Ok(events)
     }
+
+    fn unknown_attributes(&self) -> Vec<UnknownAttribute> {
+        // The parser takes out the attributes it understands (type, level and
+        // label) and leaves the rest as custom properties, layout hints included
+        let mut unknown: Vec<_> = self
+            .parsed()
+            .0
+            .into_iter()
+            .filter_map(|event| match event {
+                dotparser::GraphEvent::AddNode { id, properties, .. } => Some((id, properties)),
+                _ => None,
+            })
+            .flat_map(|(id, properties)| {
+                properties
+                    .custom
+                    .into_keys()
+                    .filter(|name| !LAYOUT_ATTRIBUTES.contains(&name.as_str()))
+                    .map(move |name| UnknownAttribute {
+                        node: id.clone(),
+                        name,
+                    })
+            })
+            .collect();
+        unknown.sort_unstable_by(|a, b| (&a.node, &a.name).cmp(&(&b.node, &b.name)));
+        unknown
+    }
+
+    fn layout_hints(&self) -> LayoutHints {
+        let mut hints = LayoutHints {
+            rank_dir: rank_dir(&self.content),
+            same_rank: same_rank_groups(&self.content),
+            ..LayoutHints::default()
+        };
+        for (id, attributes) in node_attribute_lists(&take_html_labels(&self.content).0) {
+            let value = |key| {
+                attributes
+                    .iter()
+                    .rev()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.as_str())
+            };
+            let pin = value("pin").is_some_and(|pin| pin.eq_ignore_ascii_case("true"));
+            if let Some(position) = value("pos").and_then(|pos| pinned_position(pos, pin)) {
+                hints.pinned.insert(id.clone(), position);
+            }
+            if let Some(layer) = value("layer").and_then(|layer| layer.parse().ok()) {
+                hints.layers.insert(id.clone(), layer);
+            }
+            if let Some(group) = value("group") {
+                hints.groups.insert(id, group.to_string());
+            }
+        }
+        hints
+    }
+
+    fn source_lines(&self) -> SourceLines {
+        statement_lines(&self.content)
+    }
+}
+
+/// The line each node and edge is written on
+///
+/// Like the parser, this reads statements a line at a time. A node without
+/// a statement of its own is placed at the first edge naming it.
+fn statement_lines(content: &str) -> SourceLines {
+    let mut lines = SourceLines::default();
+    let mut mentioned = HashMap::new();
+    let quoted = quoted_strings(content);
+    for (number, line) in (1..).zip(content.lines()) {
+        let line = line.trim();
+        if line.starts_with("//") || line.starts_with('#') {
+            continue;
+        }
+        for statement in split_outside_quotes(line, &[';']) {
+            // Leave out the attribute list and the braces of blocks
+            let statement = statement.split('[').next().unwrap_or_default();
+            let ids: Vec<&str> = statement
+                .split("->")
+                .flat_map(|part| part.split("--"))
+                .map(|id| id.trim_matches(|c: char| c.is_whitespace() || c == '{' || c == '}'))
+                .collect();
+            if !ids.iter().all(|id| is_node_id(id)) {
+                continue;
+            }
+            let id = |raw: &str| endpoint(raw, &quoted).0;
+            if let [node] = ids[..] {
+                lines.nodes.entry(id(node)).or_insert(number);
+            }
+            for pair in ids.windows(2) {
+                lines
+                    .edges
+                    .entry((id(pair[0]), id(pair[1])))
+                    .or_insert(number);
+                for &node in pair {
+                    mentioned.entry(id(node)).or_insert(number);
+                }
+            }
+        }
+    }
+    for (id, number) in mentioned {
+        lines.nodes.entry(id).or_insert(number);
+    }
+    lines
+}
+
+/// Whether a statement part is a node id rather than a keyword, a setting or
+/// the head of a block
+fn is_node_id(part: &str) -> bool {
+    if part.len() > 1 && part.starts_with('"') && (part.ends_with('"') || part.contains("\":")) {
+        return true;
+    }
+    !part.is_empty()
+        && !part.contains(|c: char| c.is_whitespace() || c == '=' || c == '"')
+        && !["graph", "node", "edge"].contains(&part)
+}
+
+/// The scene position of a `pos="x,y[,z]"`, if it is pinned: by a
+/// trailing `!` or by the node's `pin=true`
+fn pinned_position(pos: &str, pin: bool) -> Option<Vec3> {
+    let pos = pos.trim();
+    let (coordinates, bang) = pos
+        .strip_suffix('!')
+        .map_or((pos, false), |rest| (rest, true));
+    if !(pin || bang) {
+        return None;
+    }
+    let numbers: Vec<f32> = coordinates
+        .split(',')
+        .map(|n| n.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers[..] {
+        [x, y] => Some(Vec3::new(x, y, 0.0) * POS_SCALE),
+        [x, y, z] => Some(Vec3::new(x, y, z) * POS_SCALE),
+        _ => None,
+    }
+}
+
+/// Each node statement's id and attributes, with quoted values kept whole
+///
+/// Like the parser, this reads one statement per line.
+fn node_attribute_lists(content: &str) -> Vec<(String, Vec<(String, String)>)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.contains("->") && !line.contains("--"))
+        .filter_map(|line| {
+            let (id, rest) = line.split_once('[')?;
+            let id = id.trim().trim_matches('"');
+            if id.is_empty() || ["graph", "node", "edge"].contains(&id) {
+                return None;
+            }
+            let list = &rest[..rest.rfind(']')?];
+            let attributes = split_outside_quotes(list, &[',', ';'])
+                .into_iter()
+                .filter_map(|attribute| {
+                    let (key, value) = attribute.split_once('=')?;
+                    Some((
+                        key.trim().to_string(),
+                        value.trim().trim_matches('"').to_string(),
+                    ))
+                })
+                .collect();
+            Some((id.to_string(), attributes))
+        })
+        .collect()
+}
+
+/// An id as written, without the quotes the parser leaves on quoted edge
+/// endpoints such as `"billing/db"`
+fn unquoted(id: String) -> String {
+    if id.contains('"') {
+        id.trim_matches('"').to_string()
+    } else {
+        id
+    }
+}
+
+/// The node id and port of an edge endpoint such as `table:out` or
+/// `table:out:e`, unless it is one of `ids`, as a quoted `"host:8080"` is
+fn endpoint(raw: &str, ids: &HashSet<String>) -> (String, Option<String>) {
+    let whole = unquoted(raw.to_string());
+    if ids.contains(&whole) {
+        return (whole, None);
+    }
+    let split = raw
+        .split_once("\":")
+        .or_else(|| raw.split_once(':'))
+        .filter(|(id, _)| !id.is_empty());
+    let Some((id, port)) = split else {
+        return (whole, None);
+    };
+    // A compass point after the port, as in `out:e`, only says which side
+    let port = port.split(':').next().unwrap_or_default().trim_matches('"');
+    (
+        unquoted(id.to_string()),
+        (!port.is_empty()).then(|| port.to_string()),
+    )
+}
+
+/// Every quoted string, without its quotes
+fn quoted_strings(content: &str) -> HashSet<String> {
+    content
+        .split('"')
+        .skip(1)
+        .step_by(2)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Splits on any of `separators` that isn't inside a quoted string
+fn split_outside_quotes<'a>(s: &'a str, separators: &[char]) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if !quoted && separators.contains(&c) => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// The graph's `rankdir`, set on its own or in a `graph [...]` statement
+fn rank_dir(content: &str) -> Option<RankDir> {
+    content.match_indices("rankdir").find_map(|(i, key)| {
+        let value = content[i + key.len()..]
+            .trim_start()
+            .strip_prefix('=')?
+            .trim_start()
+            .trim_start_matches('"');
+        let end = value
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(value.len());
+        RankDir::parse(&value[..end])
+    })
+}
+
+/// Node ids of every `{rank=same; ...}` block
+///
+/// Only innermost blocks are read, so a `rank=same` in a subgraph with
+/// subgraphs of its own is ignored.
+fn same_rank_groups(content: &str) -> Vec<Vec<String>> {
+    let mut groups = Vec::new();
+    for (open, _) in content.match_indices('{') {
+        let rest = &content[open + 1..];
+        let Some(close) = rest
+            .find(['{', '}'])
+            .filter(|&i| rest[i..].starts_with('}'))
+        else {
+            continue;
+        };
+        let statements: Vec<&str> = rest[..close].split([';', '\n']).map(str::trim).collect();
+        if !statements
+            .iter()
+            .any(|s| s.replace([' ', '"'], "") == "rank=same")
+        {
+            continue;
+        }
+        // Node statements, leaving out their attribute lists
+        let ids: Vec<String> = statements
+            .iter()
+            .filter_map(|s| s.split('[').next())
+            .filter(|s| !s.contains('='))
+            .flat_map(str::split_whitespace)
+            .map(|id| id.trim_matches('"').to_string())
+            .filter(|id| !id.is_empty())
+            .collect();
+        if ids.len() > 1 {
+            groups.push(ids);
+        }
+    }
+    groups
+}
+
+/// Verifies `{`/`}` pairs, ignoring braces in strings and comments
+fn check_braces(content: &str) -> Result<(), SourceError> {
+    let mut open = Vec::new();
+    let mut chars = content.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '#' => {
+                chars.find(|&(_, c)| c == '\n');
+            }
+            '/' if chars.peek().is_some_and(|&(_, c)| c == '/') => {
+                chars.find(|&(_, c)| c == '\n');
+            }
+            '/' if chars.peek().is_some_and(|&(_, c)| c == '*') => {
+                chars.next();
+                let mut prev = ' ';
+                for (_, c) in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            '{' => open.push(i),
+            '}' if open.pop().is_none() => return Err(brace_error(content, i, "unexpected '}'")),
+            _ => {}
+        }
+    }
+
+    open.pop()
+        .map_or(Ok(()), |i| Err(brace_error(content, i, "unclosed '{'")))
+}
+
+fn brace_error(content: &str, offset: usize, message: &str) -> SourceError {
+    let (line, column) = line_column(content, offset);
+    SourceError::SyntaxError {
+        line,
+        column,
+        message: message.to_string(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::graph_state::GraphState;
+    use crate::graph_state::{GraphQuery, GraphState};
     use std::collections::HashSet;
 
     #[test]
@@ -144,7 +529,7 @@ mod tests {
         // Test that our event system properly handles node attributes
         let dot_content = r#"
             digraph {
-                A [type="team", level="2"];
+                A [type="team", level="2", owner="platform"];
                 B [type="user", level="1"];
                 C [type="user", level="1"];
                 A -> B;
@@ -175,6 +560,7 @@ mod tests {
         assert_eq!(a_node.name, "A");
         assert_eq!(a_node.node_type, Some("team".to_string()));
         assert_eq!(a_node.level, 2);
+        assert_eq!(a_node.properties["owner"], "platform");
 
         let b_idx = event_graph.node_map["B"];
         let b_node = &event_graph.graph[b_idx];
@@ -182,4 +568,169 @@ mod tests {
         assert_eq!(b_node.node_type, Some("user".to_string()));
         assert_eq!(b_node.level, 1);
     }
+
+    #[test]
+    fn test_reads_html_and_record_labels() {
+        let content = r#"digraph {
+            api [label=<<b>API</b><br/>v2, "public">, layer=1];
+            orders [label=<
+              <table>
+                <tr><td port="in">Orders</td></tr>
+                <tr><td port="out">total &gt; 0</td></tr>
+              </table>>];
+            queue [shape=record, label="<head> head|<tail> tail"];
+            api -> orders;
+        }"#;
+        let source = DotSource::from_content(content);
+        let mut state = GraphState::new();
+        state.process_events(source.events().unwrap());
+        assert_eq!(state.node_count(), 3);
+        assert_eq!(state.get_node("api").unwrap().name, "API\nv2, \"public\"");
+        let orders = state.get_node("orders").unwrap();
+        assert_eq!(orders.name, "Orders\ntotal > 0");
+        assert_eq!(orders.properties["port.out"], "1");
+        let queue = state.get_node("queue").unwrap();
+        assert_eq!(queue.name, "head\ntail");
+        assert_eq!(queue.properties["fields"], "head\ntail");
+        assert_eq!(source.layout_hints().layers["api"], 1);
+    }
+
+    #[test]
+    fn test_reads_ports_of_edge_endpoints() {
+        let content = r#"digraph {
+            table [shape=record, label="<in> in|<out> out"];
+            "host:8080" [type="service"];
+            table:out:e -> "host:8080";
+            "api":req -> table:in;
+            api -> table;
+        }"#;
+        let source = DotSource::from_content(content);
+        let events = source.events().unwrap();
+        let mut state = GraphState::new();
+        state.process_events(events.clone());
+        let mut ids: Vec<_> = state.nodes_matching(|_, _| true);
+        ids.sort();
+        assert_eq!(ids, ["api", "host:8080", "table"]);
+        let ports: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                GraphEvent::AddRichEdge { from, to, info } => Some((
+                    from.as_str(),
+                    info.from_port.as_deref(),
+                    to.as_str(),
+                    info.to_port.as_deref(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            ports,
+            [
+                ("table", Some("out"), "host:8080", None),
+                ("api", Some("req"), "table", Some("in")),
+            ]
+        );
+        assert!(
+            source
+                .source_lines()
+                .edges
+                .contains_key(&("api".to_string(), "table".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_reads_rankdir_and_same_ranks() {
+        let content = r#"digraph {
+            graph [rankdir="LR", splines=ortho];
+            { rank=same; api; "worker" [color=red] }
+            subgraph storage {
+                rank = same
+                db
+                cache
+            }
+            api -> db
+            worker -> cache
+        }"#;
+        let hints = DotSource::from_content(content).layout_hints();
+        assert_eq!(hints.rank_dir, Some(RankDir::LeftToRight));
+        assert_eq!(hints.same_rank, [["api", "worker"], ["db", "cache"]]);
+
+        let plain = DotSource::from_content("digraph {\n  a -> b\n}").layout_hints();
+        assert_eq!(plain, LayoutHints::default());
+    }
+
+    #[test]
+    fn test_reads_pins_layers_and_groups() {
+        let content = r#"digraph {
+            gateway [pos="72,144!", group="edge"]
+            api [pos="36,0,72", pin=true, layer=2]
+            db [pos="10,10", layer="x", group=data]
+            cache [label="a, b", group="data"; layer=1]
+            gateway -> api
+        }"#;
+        let source = DotSource::from_content(content);
+        let hints = source.layout_hints();
+        assert_eq!(hints.pinned.len(), 2);
+        assert_eq!(hints.pinned["gateway"], Vec3::new(2.0, 4.0, 0.0));
+        assert_eq!(hints.pinned["api"], Vec3::new(1.0, 0.0, 2.0));
+        assert_eq!(hints.layers.len(), 2);
+        assert_eq!(hints.layers["api"], 2);
+        assert_eq!(hints.layers["cache"], 1);
+        assert_eq!(hints.groups["gateway"], "edge");
+        assert_eq!(hints.groups["db"], "data");
+        assert_eq!(hints.groups["cache"], "data");
+        // Layout hints aren't reported as ignored
+        assert!(source.unknown_attributes().is_empty());
+    }
+
+    #[test]
+    fn test_reads_statement_lines() {
+        let content = r#"digraph G {
+            rankdir=LR
+            gateway -> api -> db
+            // db [label="not this one"]
+            api [label="API"]
+            "orders db" -- cache; { rank=same; cache }
+        }"#;
+        let lines = DotSource::from_content(content).source_lines();
+        assert_eq!(lines.nodes["gateway"], 3);
+        assert_eq!(lines.nodes["db"], 3);
+        // A node's own statement wins over the edges naming it
+        assert_eq!(lines.nodes["api"], 5);
+        assert_eq!(lines.nodes["orders db"], 6);
+        assert_eq!(lines.nodes.len(), 5);
+        assert_eq!(lines.edges[&("api".to_string(), "db".to_string())], 3);
+        assert_eq!(
+            lines.edges[&("orders db".to_string(), "cache".to_string())],
+            6
+        );
+        assert_eq!(lines.edges.len(), 3);
+    }
+
+    #[test]
+    fn test_reports_unbalanced_braces() {
+        let unclosed = "digraph {\n  A -> B;\n  subgraph x {\n    C;\n}";
+        assert!(matches!(
+            DotSource::from_content(unclosed).events(),
+            Err(SourceError::SyntaxError {
+                line: 1,
+                column: 9,
+                ..
+            })
+        ));
+
+        let extra = "digraph {\n  A -> B;\n}\n}";
+        assert!(matches!(
+            DotSource::from_content(extra).events(),
+            Err(SourceError::SyntaxError {
+                line: 4,
+                column: 1,
+                ..
+            })
+        ));
+
+        // Braces inside labels and comments don't count
+        let quoted = "digraph {\n  A [label=\"{x\"]; // }\n  /* { */\n}";
+        assert!(DotSource::from_content(quoted).events().is_ok());
+    }
 }
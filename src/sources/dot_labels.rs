@@ -0,0 +1,358 @@
+//! Text of DOT labels: escapes, HTML-like labels and record fields
+//!
+//! The DOT parser reads attribute lists one line at a time, splitting on
+//! `,` and `=`, so HTML-like labels are swapped for placeholders before it
+//! sees them and turned into plain text afterwards.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Starts the placeholder an HTML-like label is swapped for
+const HTML_LABEL_MARKER: char = '\u{1}';
+
+/// Property listing the fields of a record or rows of an HTML table, one
+/// to a line
+pub const FIELDS_PROPERTY: &str = "fields";
+
+/// Prefix of the properties giving the field each port is in, counting
+/// from 0, as in `port.out = "2"`
+pub const PORT_PROPERTY_PREFIX: &str = "port.";
+
+/// The content with each HTML-like label, `label=<...>`, swapped for a
+/// quoted placeholder, and the labels' markup in order
+pub fn take_html_labels(content: &str) -> (String, Vec<String>) {
+    let mut text = String::with_capacity(content.len());
+    let mut labels = Vec::new();
+    let mut rest = content;
+    while let Some(start) = html_label_start(rest) {
+        let Some(end) = closing_bracket(&rest[start..]) else {
+            break;
+        };
+        text.push_str(&rest[..start]);
+        let _ = write!(text, "\"{HTML_LABEL_MARKER}{}\"", labels.len());
+        labels.push(rest[start + 1..start + end].to_string());
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+    (text, labels)
+}
+
+/// Where the `<` opening the first HTML-like label value is
+fn html_label_start(content: &str) -> Option<usize> {
+    content.match_indices("label").find_map(|(i, key)| {
+        let whole_word = !content[..i].ends_with(|c: char| c.is_alphanumeric() || c == '_');
+        let value = content[i + key.len()..]
+            .trim_start()
+            .strip_prefix('=')?
+            .trim_start();
+        (whole_word && value.starts_with('<')).then(|| content.len() - value.len())
+    })
+}
+
+/// Where the `>` closing the `<` that `s` starts with is
+fn closing_bracket(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The text shown for a node's label as the parser read it, adding the
+/// fields and ports of a record or HTML table to `properties`
+pub fn node_label(
+    label: &str,
+    html_labels: &[String],
+    properties: &mut BTreeMap<String, String>,
+) -> String {
+    if let Some(html) = label
+        .strip_prefix(HTML_LABEL_MARKER)
+        .and_then(|n| html_labels.get(n.parse::<usize>().ok()?))
+    {
+        let rows = html_rows(html);
+        if !rows.is_empty() {
+            add_fields(&rows, properties);
+        }
+        return html_text(html);
+    }
+    let record = properties
+        .get("shape")
+        .is_some_and(|shape| shape.eq_ignore_ascii_case("record") || shape == "Mrecord");
+    if record {
+        let fields = record_fields(label);
+        add_fields(&fields, properties);
+        return fields
+            .iter()
+            .map(|field| field.text.as_str())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    unescaped(label)
+}
+
+/// A field of a record label, or a row of an HTML table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Field {
+    ports: Vec<String>,
+    text: String,
+}
+
+fn add_fields(fields: &[Field], properties: &mut BTreeMap<String, String>) {
+    let texts: Vec<&str> = fields.iter().map(|field| field.text.as_str()).collect();
+    properties.insert(FIELDS_PROPERTY.to_string(), texts.join("\n"));
+    for (i, field) in fields.iter().enumerate() {
+        for port in &field.ports {
+            properties.insert(format!("{PORT_PROPERTY_PREFIX}{port}"), i.to_string());
+        }
+    }
+}
+
+/// A quoted label's text, with its `\n`, `\l` and `\r` line breaks
+fn unescaped(label: &str) -> String {
+    let mut text = String::with_capacity(label.len());
+    let mut chars = label.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'l' | 'r') => text.push('\n'),
+            Some(escaped) => text.push(escaped),
+            None => {}
+        }
+    }
+    // A break ends a line rather than starting an empty one
+    text.truncate(text.trim_end_matches('\n').len());
+    text
+}
+
+/// The fields of a record label such as `<in> in|{body|more}|<out> out`,
+/// flattened in the order they're written
+fn record_fields(label: &str) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = label.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                field.push(c);
+                field.extend(chars.next());
+            }
+            '|' | '{' | '}' => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+        .iter()
+        .map(|field| {
+            let field = field.trim();
+            let (ports, text) = field
+                .strip_prefix('<')
+                .and_then(|rest| rest.split_once('>'))
+                .map_or((Vec::new(), field), |(port, text)| {
+                    (vec![port.trim().to_string()], text)
+                });
+            Field {
+                ports,
+                text: unescaped(text.trim()).replace('\n', " "),
+            }
+        })
+        .filter(|field| !field.ports.is_empty() || !field.text.is_empty())
+        .collect()
+}
+
+/// A piece of HTML-like markup
+enum Token<'a> {
+    /// A tag's lowercase name, whether it closes, and its attributes
+    Tag(String, bool, &'a str),
+    Text(&'a str),
+}
+
+fn tokens(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        let Some(end) = rest[start..].find('>') else {
+            tokens.push(Token::Text(&rest[start..]));
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim_end_matches('/').trim();
+        let (closing, tag) = tag
+            .strip_prefix('/')
+            .map_or((false, tag), |tag| (true, tag));
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        tokens.push(Token::Tag(name.to_ascii_lowercase(), closing, attributes));
+        rest = &rest[start + end + 1..];
+    }
+    tokens
+}
+
+/// The plain text of HTML-like markup: tags dropped, `<br/>` and table rows
+/// as line breaks, and entities decoded
+fn html_text(html: &str) -> String {
+    let mut text = String::new();
+    for token in tokens(html) {
+        match token {
+            Token::Tag(name, closing, _) => match name.as_str() {
+                "br" => text.push('\n'),
+                "tr" if closing => text.push('\n'),
+                "td" if closing => text.push(' '),
+                _ => {}
+            },
+            Token::Text(part) => text.push_str(&decoded(part)),
+        }
+    }
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The rows of the HTML table in a label, with the ports of their cells;
+/// empty if there's no table
+fn html_rows(html: &str) -> Vec<Field> {
+    let mut rows = Vec::new();
+    let mut row: Option<Field> = None;
+    for token in tokens(html) {
+        match (token, row.as_mut()) {
+            (Token::Tag(name, false, _), _) if name == "tr" => row = Some(Field::default()),
+            (Token::Tag(name, true, _), Some(_)) if name == "tr" => {
+                rows.extend(row.take());
+            }
+            (Token::Tag(name, false, attributes), Some(row)) if name == "td" => {
+                row.ports.extend(attribute(attributes, "port"));
+            }
+            (Token::Tag(name, _, _), Some(row)) if name == "td" || name == "br" => {
+                row.text.push(' ');
+            }
+            (Token::Text(part), Some(row)) => row.text.push_str(&decoded(part)),
+            _ => {}
+        }
+    }
+    for row in &mut rows {
+        row.text = row.text.split_whitespace().collect::<Vec<_>>().join(" ");
+    }
+    rows
+}
+
+/// The value of a tag attribute, as in `port="out"`
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    attributes.match_indices(name).find_map(|(i, _)| {
+        let before = attributes[..i].chars().next_back();
+        if before.is_some_and(|c| !c.is_whitespace()) {
+            return None;
+        }
+        let value = attributes[i + name.len()..]
+            .trim_start()
+            .strip_prefix('=')?
+            .trim_start();
+        let quote = value.chars().next().filter(|&c| c == '"' || c == '\'')?;
+        let value = &value[1..];
+        Some(value[..value.find(quote)?].to_string())
+    })
+}
+
+/// Text with its character entities, like `&amp;` and `&#8594;`, decoded
+fn decoded(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((entity(&rest[1..end])?, end)));
+        if let Some((c, end)) = entity {
+            decoded.push(c);
+            rest = &rest[end + 1..];
+        } else {
+            decoded.push('&');
+            rest = &rest[1..];
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_become_plain_text_with_fields_and_ports() {
+        let content = "a [label=<<b>Service</b><br/>v2 &amp; up>, color=red];\nb [label=\"x\"]";
+        let (content, html) = take_html_labels(content);
+        assert_eq!(content, "a [label=\"\u{1}0\", color=red];\nb [label=\"x\"]");
+        let mut properties = BTreeMap::new();
+        assert_eq!(
+            node_label("\u{1}0", &html, &mut properties),
+            "Service\nv2 & up"
+        );
+        assert!(properties.is_empty());
+
+        let table = r#"<table><tr><td port="in">orders</td></tr>
+            <tr><td>id</td><td port="out">total</td></tr></table>"#;
+        let mut properties = BTreeMap::new();
+        assert_eq!(
+            node_label("\u{1}0", &[table.to_string()], &mut properties),
+            "orders\nid total"
+        );
+        assert_eq!(properties[FIELDS_PROPERTY], "orders\nid total");
+        assert_eq!(properties["port.in"], "0");
+        assert_eq!(properties["port.out"], "1");
+
+        let mut properties = BTreeMap::from([("shape".to_string(), "record".to_string())]);
+        assert_eq!(
+            node_label("<f0> left|{<f1> mid\\|dle|right}", &[], &mut properties),
+            "left\nmid|dle\nright"
+        );
+        assert_eq!(properties[FIELDS_PROPERTY], "left\nmid|dle\nright");
+        assert_eq!(properties["port.f1"], "1");
+
+        let mut properties = BTreeMap::new();
+        assert_eq!(
+            node_label("Orders\\nservice\\l", &[], &mut properties),
+            "Orders\nservice"
+        );
+    }
+}
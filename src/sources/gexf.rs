@@ -0,0 +1,233 @@
+use super::xml::{scan_tags, syntax_error};
+use super::{GraphEventSource, Progress, SourceError};
+use crate::events::{EventEdgeInfo, EventNodeInfo, GraphEvent};
+use std::collections::HashMap;
+
+/// Source for GEXF (Gephi) format graphs
+pub struct GexfSource {
+    content: String,
+}
+
+impl GexfSource {
+    /// Creates a new GEXF source from content
+    pub fn new(content: String) -> Self {
+        Self { content }
+    }
+
+    /// Creates a new GEXF source from a string slice
+    pub fn from_content(content: &str) -> Self {
+        Self::new(content.to_string())
+    }
+}
+
+/// Node being assembled while its `<attvalues>` children are read
+struct PendingNode {
+    id: String,
+    label: Option<String>,
+    values: HashMap<String, String>,
+}
+
+impl GraphEventSource for GexfSource {
+    fn source_name(&self) -> &'static str {
+        "GEXF"
+    }
+
+    fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+        self.events_with_progress(&mut |_| {})
+    }
+
+    fn events_with_progress(
+        &self,
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<Vec<GraphEvent>, SourceError> {
+        let tags = scan_tags(&self.content)?;
+
+        if !tags.iter().any(|t| t.name == "gexf") {
+            return Err(SourceError::InvalidInput(
+                "missing <gexf> root element".to_string(),
+            ));
+        }
+
+        // Attribute ids are declared up front and referenced by `for=` in attvalues
+        let mut node_attr_titles = HashMap::new();
+        let mut in_node_attributes = false;
+
+        let mut events = vec![GraphEvent::BatchStart];
+        let mut pending: Option<PendingNode> = None;
+
+        for tag in tags {
+            progress(Progress {
+                bytes: tag.offset,
+                events: events.len(),
+            });
+            match (tag.name.as_str(), tag.closing) {
+                ("attributes", false) => {
+                    in_node_attributes = tag.attrs.get("class").is_some_and(|c| c == "node");
+                }
+                ("attributes", true) => in_node_attributes = false,
+                ("attribute", false) if in_node_attributes => {
+                    if let (Some(id), Some(title)) = (tag.attrs.get("id"), tag.attrs.get("title")) {
+                        node_attr_titles.insert(id.clone(), title.clone());
+                    }
+                }
+                ("node", false) => {
+                    let id = tag.attrs.get("id").cloned().ok_or_else(|| {
+                        syntax_error(&self.content, tag.offset, "<node> without id")
+                    })?;
+                    let node = PendingNode {
+                        id,
+                        label: tag.attrs.get("label").cloned(),
+                        values: HashMap::new(),
+                    };
+                    if tag.self_closing {
+                        events.push(node_event(node));
+                    } else {
+                        pending = Some(node);
+                    }
+                }
+                ("attvalue", false) => {
+                    if let Some(node) = pending.as_mut()
+                        && let (Some(key), Some(value)) =
+                            (tag.attrs.get("for"), tag.attrs.get("value"))
+                    {
+                        let title = node_attr_titles.get(key).unwrap_or(key);
+                        node.values.insert(title.clone(), value.clone());
+                    }
+                }
+                ("node", true) => {
+                    if let Some(node) = pending.take() {
+                        events.push(node_event(node));
+                    }
+                }
+                ("edge", false) => {
+                    let (Some(from), Some(to)) = (tag.attrs.get("source"), tag.attrs.get("target"))
+                    else {
+                        return Err(syntax_error(
+                            &self.content,
+                            tag.offset,
+                            "<edge> without source or target",
+                        ));
+                    };
+                    let (from, to) = (from.clone(), to.clone());
+
+                    if let Some(label) = tag.attrs.get("label") {
+                        events.push(GraphEvent::AddRichEdge {
+                            from,
+                            to,
+                            info: EventEdgeInfo {
+                                label: Some(label.clone()),
+                                ..Default::default()
+                            },
+                        });
+                    } else {
+                        events.push(GraphEvent::AddEdge { from, to });
+                    }
+                }
+                _ => {
+                    // Ignore viz:*, meta, and other elements for now
+                }
+            }
+        }
+
+        events.push(GraphEvent::BatchEnd);
+        Ok(events)
+    }
+}
+
+/// Converts a parsed node into an `AddNode` event, honoring `type`/`level` attributes
+fn node_event(node: PendingNode) -> GraphEvent {
+    let info = EventNodeInfo {
+        name: node.label.unwrap_or_else(|| node.id.clone()),
+        node_type: node.values.get("type").cloned(),
+        level: node
+            .values
+            .get("level")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(0),
+        properties: super::other_properties(&node.values, &["type", "level"]),
+    };
+    GraphEvent::AddNode { id: node.id, info }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <gexf xmlns="http://gexf.net/1.3" version="1.3">
+          <graph defaultedgetype="directed">
+            <attributes class="node">
+              <attribute id="0" title="type" type="string"/>
+              <attribute id="1" title="level" type="integer"/>
+            </attributes>
+            <nodes>
+              <node id="n0" label="Gateway">
+                <attvalues>
+                  <attvalue for="0" value="team"/>
+                  <attvalue for="1" value="2"/>
+                </attvalues>
+              </node>
+              <node id="n1" label="Users &amp; Roles"/>
+              <node id="n2"/>
+            </nodes>
+            <edges>
+              <edge id="e0" source="n0" target="n1" label="auth"/>
+              <edge id="e1" source="n1" target="n2"/>
+            </edges>
+          </graph>
+        </gexf>"#;
+
+    #[test]
+    fn test_gexf_to_events() {
+        let events = GexfSource::from_content(SAMPLE).events().unwrap();
+
+        assert!(matches!(events.first(), Some(GraphEvent::BatchStart)));
+        assert!(matches!(events.last(), Some(GraphEvent::BatchEnd)));
+
+        let nodes: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, info } => Some((id.as_str(), info)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(nodes.len(), 3);
+
+        let (_, gateway) = nodes[0];
+        assert_eq!(gateway.name, "Gateway");
+        assert_eq!(gateway.node_type.as_deref(), Some("team"));
+        assert_eq!(gateway.level, 2);
+
+        assert_eq!(nodes[1].1.name, "Users & Roles");
+        assert_eq!(nodes[2].1.name, "n2");
+
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::AddRichEdge { info, .. } if info.label.as_deref() == Some("auth")
+        )));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::AddEdge { from, to } if from == "n1" && to == "n2"
+        )));
+    }
+
+    #[test]
+    fn test_reports_error_position() {
+        let content = "<gexf>\n  <graph>\n    <edge source=\"a\"/>";
+        let result = GexfSource::from_content(content).events();
+        assert!(matches!(
+            result,
+            Err(SourceError::SyntaxError {
+                line: 3,
+                column: 5,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_non_gexf() {
+        let result = GexfSource::from_content("<graphml></graphml>").events();
+        assert!(matches!(result, Err(SourceError::InvalidInput(_))));
+    }
+}
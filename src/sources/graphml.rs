@@ -0,0 +1,240 @@
+use super::xml::{Tag, scan_tags, syntax_error, unescape};
+use super::{GraphEventSource, Progress, SourceError};
+use crate::events::{EventEdgeInfo, EventNodeInfo, GraphEvent};
+use std::collections::HashMap;
+
+/// Source for `GraphML` XML graphs, as written by yEd, Gephi and networkx
+///
+/// `<data>` values are matched to their `<key>` by `attr.name`: nodes read
+/// `label` (or `name`), `type` and `level`, edges read `label`, `type`,
+/// `sequence`, `rate` and `error_rate`.
+pub struct GraphMlSource {
+    content: String,
+}
+
+impl GraphMlSource {
+    /// Creates a new `GraphML` source from content
+    pub fn new(content: String) -> Self {
+        Self { content }
+    }
+
+    /// Creates a new `GraphML` source from a string slice
+    pub fn from_content(content: &str) -> Self {
+        Self::new(content.to_string())
+    }
+}
+
+/// Node or edge being assembled while its `<data>` children are read
+enum Pending {
+    Node {
+        id: String,
+        values: HashMap<String, String>,
+    },
+    Edge {
+        from: String,
+        to: String,
+        values: HashMap<String, String>,
+    },
+}
+
+impl Pending {
+    fn values(&mut self) -> &mut HashMap<String, String> {
+        match self {
+            Self::Node { values, .. } | Self::Edge { values, .. } => values,
+        }
+    }
+
+    fn into_event(self) -> GraphEvent {
+        match self {
+            Self::Node { id, values } => node_event(id, &values),
+            Self::Edge { from, to, values } => edge_event(from, to, &values),
+        }
+    }
+}
+
+impl GraphEventSource for GraphMlSource {
+    fn source_name(&self) -> &'static str {
+        "GraphML"
+    }
+
+    fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+        self.events_with_progress(&mut |_| {})
+    }
+
+    fn events_with_progress(
+        &self,
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<Vec<GraphEvent>, SourceError> {
+        let tags = scan_tags(&self.content)?;
+
+        if !tags.iter().any(|t| t.name == "graphml") {
+            return Err(SourceError::InvalidInput(
+                "missing <graphml> root element".to_string(),
+            ));
+        }
+
+        // Keys are declared up front and referenced by `key=` in data elements
+        let mut key_names = HashMap::new();
+        let mut events = vec![GraphEvent::BatchStart];
+        let mut pending: Option<Pending> = None;
+        // The open `<data>` tag whose text is being read
+        let mut data: Option<&Tag> = None;
+
+        for tag in &tags {
+            progress(Progress {
+                bytes: tag.offset,
+                events: events.len(),
+            });
+            match (tag.name.as_str(), tag.closing) {
+                ("key", false) => {
+                    if let Some(id) = tag.attrs.get("id") {
+                        let name = tag.attrs.get("attr.name").unwrap_or(id);
+                        key_names.insert(id.clone(), name.clone());
+                    }
+                }
+                ("node", false) => {
+                    let id = tag.attrs.get("id").cloned().ok_or_else(|| {
+                        syntax_error(&self.content, tag.offset, "<node> without id")
+                    })?;
+                    let node = Pending::Node {
+                        id,
+                        values: HashMap::new(),
+                    };
+                    if tag.self_closing {
+                        events.push(node.into_event());
+                    } else {
+                        pending = Some(node);
+                    }
+                }
+                ("edge", false) => {
+                    let (Some(from), Some(to)) = (tag.attrs.get("source"), tag.attrs.get("target"))
+                    else {
+                        return Err(syntax_error(
+                            &self.content,
+                            tag.offset,
+                            "<edge> without source or target",
+                        ));
+                    };
+                    // Ports are attributes of the edge rather than `<data>`
+                    let values = ["sourceport", "targetport"]
+                        .into_iter()
+                        .filter_map(|port| Some((port.to_string(), tag.attrs.get(port)?.clone())))
+                        .collect();
+                    let edge = Pending::Edge {
+                        from: from.clone(),
+                        to: to.clone(),
+                        values,
+                    };
+                    if tag.self_closing {
+                        events.push(edge.into_event());
+                    } else {
+                        pending = Some(edge);
+                    }
+                }
+                ("data", false) if !tag.self_closing => data = Some(tag),
+                ("data", true) => {
+                    if let (Some(open), Some(item)) = (data.take(), pending.as_mut())
+                        && let Some(key) = open.attrs.get("key")
+                    {
+                        let name = key_names.get(key).unwrap_or(key);
+                        let text = unescape(self.content[open.end..tag.offset].trim());
+                        item.values().insert(name.clone(), text);
+                    }
+                }
+                ("node" | "edge", true) => {
+                    if let Some(item) = pending.take() {
+                        events.push(item.into_event());
+                    }
+                }
+                _ => {
+                    // Ignore <graph>, <desc>, yEd graphics and other elements
+                }
+            }
+        }
+
+        events.push(GraphEvent::BatchEnd);
+        Ok(events)
+    }
+}
+
+fn node_event(id: String, values: &HashMap<String, String>) -> GraphEvent {
+    let info = EventNodeInfo {
+        name: values
+            .get("label")
+            .or_else(|| values.get("name"))
+            .cloned()
+            .unwrap_or_else(|| id.clone()),
+        node_type: values.get("type").cloned(),
+        level: values
+            .get("level")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(0),
+        properties: super::other_properties(values, &["label", "name", "type", "level"]),
+    };
+    GraphEvent::AddNode { id, info }
+}
+
+fn edge_event(from: String, to: String, values: &HashMap<String, String>) -> GraphEvent {
+    let info = EventEdgeInfo {
+        label: values.get("label").cloned(),
+        edge_type: values.get("type").cloned(),
+        sequence: values.get("sequence").and_then(|s| s.parse().ok()),
+        rate: values.get("rate").and_then(|r| r.parse().ok()),
+        error_rate: values.get("error_rate").and_then(|r| r.parse().ok()),
+        from_port: values.get("sourceport").cloned(),
+        to_port: values.get("targetport").cloned(),
+    };
+    if info == EventEdgeInfo::default() {
+        GraphEvent::AddEdge { from, to }
+    } else {
+        GraphEvent::AddRichEdge { from, to, info }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+          <key id="d0" for="node" attr.name="label" attr.type="string"/>
+          <key id="d1" for="node" attr.name="type" attr.type="string"/>
+          <key id="d2" for="node" attr.name="level" attr.type="int"/>
+          <key id="d3" for="edge" attr.name="rate" attr.type="double"/>
+          <graph id="G" edgedefault="directed">
+            <node id="n0">
+              <data key="d0">Gateway &amp; Auth</data>
+              <data key="d1">team</data>
+              <data key="d2">2</data>
+            </node>
+            <node id="n1"/>
+            <edge source="n0" target="n1"><data key="d3">12.5</data></edge>
+            <edge source="n1" target="n0"/>
+          </graph>
+        </graphml>"#;
+
+    #[test]
+    fn test_graphml_to_events() {
+        let events = GraphMlSource::from_content(SAMPLE).events().unwrap();
+
+        let GraphEvent::AddNode { id, info } = &events[1] else {
+            panic!("expected a node, got {}", events[1]);
+        };
+        assert_eq!(id, "n0");
+        assert_eq!(info.name, "Gateway & Auth");
+        assert_eq!(info.node_type.as_deref(), Some("team"));
+        assert_eq!(info.level, 2);
+        assert!(matches!(&events[2], GraphEvent::AddNode { info, .. } if info.name == "n1"));
+        assert!(matches!(
+            &events[3],
+            GraphEvent::AddRichEdge { info, .. } if info.rate == Some(12.5)
+        ));
+        assert!(matches!(
+            &events[4],
+            GraphEvent::AddEdge { from, to } if from == "n1" && to == "n0"
+        ));
+
+        let result = GraphMlSource::from_content("<gexf></gexf>").events();
+        assert!(matches!(result, Err(SourceError::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,162 @@
+use super::{GraphEventSource, SourceError};
+use crate::events::{EventEdgeInfo, EventNodeInfo, GraphEvent};
+use serde_json::Value;
+
+/// Source for node-link JSON: a `nodes` array and an `edges` (or `links`) array
+///
+/// Reads what `save` and `export` write as well as the networkx and D3
+/// spelling, where edges are `links` with `source` and `target`.
+pub struct JsonSource {
+    content: String,
+}
+
+impl JsonSource {
+    /// Creates a new JSON source from content
+    pub fn new(content: String) -> Self {
+        Self { content }
+    }
+
+    /// Creates a new JSON source from a string slice
+    pub fn from_content(content: &str) -> Self {
+        Self::new(content.to_string())
+    }
+}
+
+impl GraphEventSource for JsonSource {
+    fn source_name(&self) -> &'static str {
+        "JSON"
+    }
+
+    fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+        let value: Value =
+            serde_json::from_str(&self.content).map_err(|e| SourceError::SyntaxError {
+                line: e.line(),
+                column: e.column(),
+                message: e.to_string(),
+            })?;
+        let Some(nodes) = value.get("nodes").and_then(Value::as_array) else {
+            return Err(SourceError::InvalidInput(
+                "missing \"nodes\" array".to_string(),
+            ));
+        };
+        let edges = value
+            .get("edges")
+            .or_else(|| value.get("links"))
+            .and_then(Value::as_array)
+            .map_or(&[][..], Vec::as_slice);
+
+        let mut events = vec![GraphEvent::BatchStart];
+        for (i, node) in nodes.iter().enumerate() {
+            let id = field(node, &["id"])
+                .ok_or_else(|| SourceError::InvalidInput(format!("node {i} has no id")))?;
+            let info = EventNodeInfo {
+                name: field(node, &["name", "label"]).unwrap_or_else(|| id.clone()),
+                node_type: field(node, &["type"]),
+                level: node
+                    .get("level")
+                    .and_then(Value::as_u64)
+                    .and_then(|level| level.try_into().ok())
+                    .unwrap_or(0),
+                properties: node
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter(|(name, _)| !NODE_FIELDS.contains(&name.as_str()))
+                    .filter_map(|(name, value)| match value {
+                        Value::String(s) => Some((name.clone(), s.clone())),
+                        Value::Number(_) | Value::Bool(_) => {
+                            Some((name.clone(), value.to_string()))
+                        }
+                        _ => None,
+                    })
+                    .collect(),
+            };
+            events.push(GraphEvent::AddNode { id, info });
+        }
+        for (i, edge) in edges.iter().enumerate() {
+            let (Some(from), Some(to)) = (
+                field(edge, &["from", "source"]),
+                field(edge, &["to", "target"]),
+            ) else {
+                return Err(SourceError::InvalidInput(format!(
+                    "edge {i} needs from and to"
+                )));
+            };
+            let number = |name| edge.get(name).and_then(Value::as_f64);
+            #[allow(clippy::cast_possible_truncation)] // Rates are shown, not computed with
+            let info = EventEdgeInfo {
+                label: field(edge, &["label"]),
+                edge_type: field(edge, &["type"]),
+                sequence: edge
+                    .get("sequence")
+                    .and_then(Value::as_u64)
+                    .and_then(|sequence| sequence.try_into().ok()),
+                rate: number("rate").map(|rate| rate as f32),
+                error_rate: number("error_rate").map(|rate| rate as f32),
+                from_port: field(edge, &["from_port"]),
+                to_port: field(edge, &["to_port"]),
+            };
+            if info == EventEdgeInfo::default() {
+                events.push(GraphEvent::AddEdge { from, to });
+            } else {
+                events.push(GraphEvent::AddRichEdge { from, to, info });
+            }
+        }
+        events.push(GraphEvent::BatchEnd);
+        Ok(events)
+    }
+}
+
+/// Node fields read into the node itself rather than kept as properties
+const NODE_FIELDS: [&str; 5] = ["id", "name", "label", "type", "level"];
+
+/// The first of `names` that is set, as a string; networkx writes numeric ids
+fn field(value: &Value, names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| value.get(name))
+        .and_then(|field| match field {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_link_json_to_events() {
+        let content = r#"{
+            "nodes": [
+                {"id": "api", "name": "API", "type": "service", "level": 1, "replicas": 3},
+                {"id": 2, "label": "Database"}
+            ],
+            "links": [
+                {"source": "api", "target": 2, "rate": 3.5},
+                {"source": 2, "target": "api", "label": null}
+            ]
+        }"#;
+        let events = JsonSource::from_content(content).events().unwrap();
+
+        assert!(matches!(
+            &events[1],
+            GraphEvent::AddNode { id, info }
+                if id == "api" && info.name == "API" && info.level == 1
+                    && info.properties["replicas"] == "3" && info.properties.len() == 1
+        ));
+        assert!(matches!(
+            &events[2],
+            GraphEvent::AddNode { id, info } if id == "2" && info.name == "Database"
+        ));
+        assert!(matches!(
+            &events[3],
+            GraphEvent::AddRichEdge { to, info, .. } if to == "2" && info.rate == Some(3.5)
+        ));
+        assert!(matches!(&events[4], GraphEvent::AddEdge { from, .. } if from == "2"));
+
+        let result = JsonSource::from_content(r#"{"edges": []}"#).events();
+        assert!(matches!(result, Err(SourceError::InvalidInput(_))));
+    }
+}
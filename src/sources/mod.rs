@@ -1,8 +1,23 @@
 use crate::events::GraphEvent;
+use crate::graph_state::{LayoutHints, SourceLines};
+use bevy::prelude::Resource;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::sync::mpsc::Sender;
 
+pub mod diff;
 pub mod dot;
+mod dot_labels;
+pub mod gexf;
+pub mod graphml;
+pub mod json;
+pub mod mqtt;
+pub mod otel;
+pub mod pajek;
 pub mod plantuml;
+pub mod prometheus;
+pub mod session;
+mod xml;
 
 /// Errors that can occur during source processing
 #[derive(Debug)]
@@ -16,6 +31,12 @@ pub enum SourceError {
     IoError(std::io::Error),
     /// Parser-specific error
     ParseError(String),
+    /// Parser error at a known position in the input (1-based)
+    SyntaxError {
+        line: usize,
+        column: usize,
+        message: String,
+    },
 }
 
 impl fmt::Display for SourceError {
@@ -25,6 +46,11 @@ impl fmt::Display for SourceError {
             Self::InvalidInput(msg) => write!(f, "Invalid input: {msg}"),
             Self::IoError(err) => write!(f, "IO error: {err}"),
             Self::ParseError(msg) => write!(f, "Parse error: {msg}"),
+            Self::SyntaxError {
+                line,
+                column,
+                message,
+            } => write!(f, "Syntax error at line {line}, column {column}: {message}"),
         }
     }
 }
@@ -53,76 +79,405 @@ pub trait GraphEventSource: Send + Sync {
     /// For live sources, this may return events over time.
     fn events(&self) -> Result<Vec<GraphEvent>, SourceError>;
 
+    /// Like `events`, calling `progress` now and then as the input is read
+    ///
+    /// Sources that can't tell how far they've got parse in one go and
+    /// report nothing, which is the default.
+    fn events_with_progress(
+        &self,
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<Vec<GraphEvent>, SourceError> {
+        let _ = progress;
+        self.events()
+    }
+
     /// Returns true if this source can handle live updates
     #[allow(dead_code)] // Will be used for live sources like twintalk
     fn is_live(&self) -> bool {
         false
     }
+
+    /// Starts delivering live events to `sender` in the background
+    ///
+    /// Called once after the initial `events()` have been applied. Static
+    /// sources have nothing more to send, so the default does nothing.
+    fn subscribe(&self, sender: Sender<GraphEvent>) -> Result<(), SourceError> {
+        drop(sender);
+        Ok(())
+    }
+
+    /// Attributes in the input that the viewer doesn't use, for `validate`
+    fn unknown_attributes(&self) -> Vec<UnknownAttribute> {
+        Vec::new()
+    }
+
+    /// How the input asks for the graph to be arranged, e.g. DOT's `rankdir`
+    fn layout_hints(&self) -> LayoutHints {
+        LayoutHints::default()
+    }
+
+    /// Where in the input each node and edge is written, for opening it in an
+    /// editor; sources that don't track it know of no lines
+    fn source_lines(&self) -> SourceLines {
+        SourceLines::default()
+    }
+}
+
+/// How far a source has got through its input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Bytes of the input parsed so far
+    pub bytes: usize,
+    /// Events emitted so far
+    pub events: usize,
+}
+
+/// An attribute a source read but has no use for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownAttribute {
+    /// The node it was set on
+    pub node: String,
+    pub name: String,
 }
 
-/// Registry for managing multiple event sources
-#[allow(dead_code)] // Will be used when we add format detection
+/// A diagram format the registry can detect and parse
+#[derive(Clone, Copy)]
+pub struct SourceFormat {
+    /// Name used with `--format`, e.g. "dot"
+    pub name: &'static str,
+    /// One-line description shown by `--list-formats`
+    pub description: &'static str,
+    /// Scores how likely content is in this format, from 0.0 (not at all) to 1.0 (certain)
+    pub detect: fn(&str) -> f32,
+    /// Creates a source that parses content in this format
+    pub create: fn(&str) -> Box<dyn GraphEventSource>,
+}
+
+/// Registry of the diagram formats available for loading
+///
+/// The built-in formats are registered by `SourceRegistry::default()`; library
+/// users can `register` their own before handing the registry to the app.
+#[derive(Resource)]
 pub struct SourceRegistry {
-    sources: Vec<Box<dyn GraphEventSource>>,
+    formats: Vec<SourceFormat>,
 }
 
-#[allow(dead_code)] // Will be used when we add format detection
 impl SourceRegistry {
     /// Creates a new empty registry
     pub fn new() -> Self {
         Self {
-            sources: Vec::new(),
+            formats: Vec::new(),
         }
     }
 
-    /// Registers a new event source
-    pub fn register(&mut self, source: Box<dyn GraphEventSource>) {
-        self.sources.push(source);
+    /// Registers a format, replacing any existing format with the same name
+    pub fn register(&mut self, format: SourceFormat) {
+        self.formats.retain(|f| f.name != format.name);
+        self.formats.push(format);
     }
 
-    /// Returns the number of registered sources
+    /// Returns the number of registered formats
+    #[allow(dead_code)] // Part of the library API
     pub fn len(&self) -> usize {
-        self.sources.len()
+        self.formats.len()
     }
 
-    /// Returns true if no sources are registered
+    /// Returns true if no formats are registered
+    #[allow(dead_code)] // Part of the library API
     pub fn is_empty(&self) -> bool {
-        self.sources.is_empty()
+        self.formats.is_empty()
+    }
+
+    /// Iterates over the registered formats in registration order
+    pub fn formats(&self) -> impl Iterator<Item = &SourceFormat> {
+        self.formats.iter()
+    }
+
+    /// Gets a format by name
+    pub fn get(&self, name: &str) -> Option<&SourceFormat> {
+        self.formats
+            .iter()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Creates the source that parses `content` in the named format
+    pub fn source_for(
+        &self,
+        format: &str,
+        content: &str,
+    ) -> Result<Box<dyn GraphEventSource>, SourceError> {
+        self.get(format)
+            .map(|f| (f.create)(content))
+            .ok_or(SourceError::UnknownFormat)
     }
 
-    /// Gets a source by name
-    pub fn get_source(&self, name: &str) -> Option<&dyn GraphEventSource> {
-        self.sources
+    /// Detects the format of diagram content
+    ///
+    /// Each format is scored independently and the most confident match wins.
+    /// Content that no format recognizes is reported as `SourceError::UnknownFormat`
+    /// rather than assumed to be DOT.
+    pub fn detect(&self, content: &str) -> Result<Detection, SourceError> {
+        let trimmed = content.trim();
+
+        self.formats
             .iter()
-            .find(|s| s.source_name().eq_ignore_ascii_case(name))
-            .map(std::convert::AsRef::as_ref)
+            .map(|f| (f.name, (f.detect)(trimmed)))
+            .filter(|&(_, confidence)| confidence > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(format, confidence)| Detection { format, confidence })
+            .ok_or(SourceError::UnknownFormat)
     }
 }
 
 impl Default for SourceRegistry {
     fn default() -> Self {
-        Self::new()
+        let mut registry = Self::new();
+        registry.register(SourceFormat {
+            name: "gexf",
+            description: "GEXF (Gephi) XML graphs",
+            detect: gexf_confidence,
+            create: |content| Box::new(gexf::GexfSource::from_content(content)),
+        });
+        registry.register(SourceFormat {
+            name: "graphml",
+            description: "GraphML XML graphs",
+            detect: graphml_confidence,
+            create: |content| Box::new(graphml::GraphMlSource::from_content(content)),
+        });
+        registry.register(SourceFormat {
+            name: "json",
+            description: "Node-link JSON (nodes plus edges or links)",
+            detect: json_confidence,
+            create: |content| Box::new(json::JsonSource::from_content(content)),
+        });
+        registry.register(SourceFormat {
+            name: "pajek",
+            description: "Pajek .net networks",
+            detect: pajek_confidence,
+            create: |content| Box::new(pajek::PajekSource::from_content(content)),
+        });
+        registry.register(SourceFormat {
+            name: "plantuml",
+            description: "PlantUML sequence diagrams",
+            detect: plantuml_confidence,
+            create: |content| Box::new(plantuml::PlantUMLSource::from_content(content)),
+        });
+        registry.register(SourceFormat {
+            name: "session",
+            description: "Recorded event sessions (JSON lines, see Ctrl+Shift+S)",
+            detect: session_confidence,
+            create: |content| Box::new(session::SessionSource::from_content(content)),
+        });
+        registry.register(SourceFormat {
+            name: "dot",
+            description: "Graphviz DOT graphs",
+            detect: dot_confidence,
+            create: |content| Box::new(dot::DotSource::from_content(content)),
+        });
+        registry
+    }
+}
+
+/// A node's attributes other than the ones in `known`, which the source
+/// reads into fields of their own, to keep as its properties
+fn other_properties<'a>(
+    attributes: impl IntoIterator<Item = (&'a String, &'a String)>,
+    known: &[&str],
+) -> BTreeMap<String, String> {
+    attributes
+        .into_iter()
+        .filter(|(name, _)| !known.contains(&name.as_str()))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Converts a byte offset into a 1-based (line, column) pair
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rfind('\n')
+        .map_or(before, |nl| &before[nl + 1..])
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+/// Detection results below this confidence are guesses and worth a warning
+pub const LOW_CONFIDENCE: f32 = 0.6;
+
+/// Outcome of format detection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    /// Format name, e.g. "dot" or "plantuml"
+    pub format: &'static str,
+    /// How certain detection is, from 0.0 (barely plausible) to 1.0 (unambiguous)
+    pub confidence: f32,
+}
+
+fn gexf_confidence(content: &str) -> f32 {
+    if content.contains("<gexf") { 1.0 } else { 0.0 }
+}
+
+fn graphml_confidence(content: &str) -> f32 {
+    if content.contains("<graphml") {
+        1.0
+    } else {
+        0.0
     }
 }
 
-/// Detects the format of diagram content
-pub fn detect_format(content: &str) -> Option<&'static str> {
-    let trimmed = content.trim();
+fn json_confidence(content: &str) -> f32 {
+    let is_node_link = serde_json::from_str::<serde_json::Value>(content)
+        .is_ok_and(|value| value.get("nodes").is_some_and(serde_json::Value::is_array));
+    if is_node_link { 0.95 } else { 0.0 }
+}
 
-    // Check for PlantUML markers
-    if trimmed.contains("@startuml") || trimmed.contains("@startsequence") {
-        return Some("plantuml");
+fn pajek_confidence(content: &str) -> f32 {
+    // Pajek files start with a *Vertices (or *Network) section header
+    let first_line = content
+        .lines()
+        .find(|l| !l.trim_start().starts_with('%'))
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if first_line.starts_with("*vertices") || first_line.starts_with("*network") {
+        0.95
+    } else {
+        0.0
     }
+}
 
-    // Check for DOT/Graphviz markers
-    if trimmed.contains("digraph") || trimmed.contains("graph") || trimmed.contains("->") {
-        return Some("dot");
+fn plantuml_confidence(content: &str) -> f32 {
+    if content.contains("@startuml") || content.contains("@startsequence") {
+        1.0
+    } else {
+        0.0
     }
+}
+
+fn session_confidence(content: &str) -> f32 {
+    // Every line is a JSON object naming its event type
+    let first_line = content.lines().next().unwrap_or_default();
+    let is_event = serde_json::from_str::<serde_json::Value>(first_line)
+        .is_ok_and(|value| value.get("type").is_some_and(serde_json::Value::is_string));
+    if is_event { 0.95 } else { 0.0 }
+}
+
+fn dot_confidence(content: &str) -> f32 {
+    // Skip leading comments to find the graph header
+    let body = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with("//") && !l.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let header = body.to_ascii_lowercase();
+    let header = header.strip_prefix("strict ").unwrap_or(&header);
 
-    // Default to DOT if we see common DOT patterns
-    if trimmed.contains('[') && trimmed.contains(']') {
-        return Some("dot");
+    if !body.contains('{') || !body.contains('}') {
+        return 0.0;
     }
 
-    None
+    if header.starts_with("digraph") || header.starts_with("graph") {
+        0.95
+    } else if body.contains("->") || body.contains("--") {
+        // Braces and edges without a graph header: probably a sloppy DOT snippet
+        0.4
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect_format(content: &str) -> Result<Detection, SourceError> {
+        SourceRegistry::default().detect(content)
+    }
+
+    fn detected(content: &str) -> Option<&'static str> {
+        detect_format(content).ok().map(|d| d.format)
+    }
+
+    #[test]
+    fn test_line_column() {
+        let content = "digraph {\n  A -> B\n}";
+        assert_eq!(line_column(content, 0), (1, 1));
+        assert_eq!(line_column(content, 12), (2, 3));
+        assert_eq!(line_column(content, content.len()), (3, 2));
+    }
+
+    #[test]
+    fn test_detects_each_format() {
+        assert_eq!(detected("digraph G { A -> B }"), Some("dot"));
+        assert_eq!(detected("// deps\nstrict graph { a -- b }"), Some("dot"));
+        assert_eq!(detected("@startuml\nA -> B: hi\n@enduml"), Some("plantuml"));
+        assert_eq!(
+            detected("<?xml version=\"1.0\"?><gexf><graph></graph></gexf>"),
+            Some("gexf")
+        );
+        assert_eq!(
+            detected("<graphml><graph><node id=\"a\"/></graph></graphml>"),
+            Some("graphml")
+        );
+        assert_eq!(
+            detected("{\"nodes\": [{\"id\": \"a\"}], \"edges\": []}"),
+            Some("json")
+        );
+        assert_eq!(
+            detected("% comment\n*Vertices 2\n1 \"a\"\n2 \"b\""),
+            Some("pajek")
+        );
+    }
+
+    #[test]
+    fn test_rejects_text_that_merely_mentions_dot_syntax() {
+        // These used to be classified as DOT
+        assert!(matches!(
+            detect_format("see the graph -> it is [nice]"),
+            Err(SourceError::UnknownFormat)
+        ));
+        assert!(matches!(
+            detect_format("hello world"),
+            Err(SourceError::UnknownFormat)
+        ));
+    }
+
+    #[test]
+    fn test_headerless_dot_is_low_confidence() {
+        let detection = detect_format("{ A -> B }").unwrap();
+        assert_eq!(detection.format, "dot");
+        assert!(detection.confidence < LOW_CONFIDENCE);
+
+        let detection = detect_format("digraph { A -> B }").unwrap();
+        assert!(detection.confidence >= LOW_CONFIDENCE);
+    }
+
+    #[test]
+    fn test_registered_formats_take_part_in_detection() {
+        let mut registry = SourceRegistry::default();
+        let builtin = registry.len();
+
+        registry.register(SourceFormat {
+            name: "tgf",
+            description: "Trivial Graph Format",
+            detect: |content| if content.contains("\n#\n") { 0.9 } else { 0.0 },
+            create: |content| Box::new(dot::DotSource::from_content(content)),
+        });
+        assert_eq!(registry.len(), builtin + 1);
+        assert_eq!(registry.detect("1 A\n2 B\n#\n1 2").unwrap().format, "tgf");
+        assert!(registry.get("TGF").is_some());
+
+        // Re-registering a name replaces the earlier format
+        registry.register(SourceFormat {
+            name: "tgf",
+            description: "Trivial Graph Format",
+            detect: |_| 0.0,
+            create: |content| Box::new(dot::DotSource::from_content(content)),
+        });
+        assert_eq!(registry.len(), builtin + 1);
+        assert!(registry.source_for("missing", "").is_err());
+    }
 }
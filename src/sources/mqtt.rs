@@ -0,0 +1,152 @@
+use super::{GraphEventSource, SourceError};
+use crate::events::GraphEvent;
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use serde_json::Value;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+/// Port used when the broker address doesn't name one
+pub const DEFAULT_PORT: u16 = 1883;
+/// How long to wait before reconnecting after the broker drops us
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Live source that subscribes to an MQTT topic of JSON graph events
+///
+/// Each message holds one event, or an array of them, in the JSON form used
+/// by session recordings, e.g. `{"type": "add_node", "id": "sensor-1"}`.
+/// Topic filters may use the `+` and `#` wildcards, so every device can
+/// publish to a topic of its own.
+pub struct MqttSource {
+    host: String,
+    port: u16,
+    topic: String,
+}
+
+impl MqttSource {
+    /// Creates a source for the broker at `broker` (`host` or `host:port`)
+    pub fn new(broker: &str, topic: &str) -> Result<Self, SourceError> {
+        let (host, port) = match broker.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse().map_err(|_| {
+                    SourceError::InvalidInput(format!("invalid MQTT port \"{port}\""))
+                })?,
+            ),
+            None => (broker, DEFAULT_PORT),
+        };
+        if host.is_empty() || topic.is_empty() {
+            return Err(SourceError::InvalidInput(
+                "MQTT needs a broker host and a topic".into(),
+            ));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            topic: topic.to_string(),
+        })
+    }
+}
+
+impl GraphEventSource for MqttSource {
+    fn source_name(&self) -> &'static str {
+        "MQTT"
+    }
+
+    fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+        // The graph starts empty and is filled in as messages arrive
+        Ok(Vec::new())
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    fn subscribe(&self, sender: Sender<GraphEvent>) -> Result<(), SourceError> {
+        let client_id = format!("dotspace-{}", std::process::id());
+        let mut options = MqttOptions::new(client_id, &self.host, self.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        let (client, mut connection) = Client::new(options, 64);
+        let topic = self.topic.clone();
+
+        thread::Builder::new()
+            .name("mqtt-subscriber".to_string())
+            .spawn(move || {
+                for notification in connection.iter() {
+                    match notification {
+                        // Subscriptions don't outlive a clean session, so
+                        // subscribe again on every (re)connect
+                        Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                            if let Err(err) = client.subscribe(&topic, QoS::AtLeastOnce) {
+                                eprintln!("MQTT subscribe to {topic} failed: {err}");
+                            }
+                        }
+                        Ok(Event::Incoming(Packet::Publish(message))) => {
+                            match events_from_payload(&message.payload) {
+                                Ok(events) => {
+                                    for event in events {
+                                        // The app has exited once the receiving end is gone
+                                        if sender.send(event).is_err() {
+                                            return;
+                                        }
+                                    }
+                                }
+                                Err(err) => {
+                                    eprintln!("Ignoring MQTT message on {}: {err}", message.topic);
+                                }
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            eprintln!("MQTT connection error: {err}");
+                            thread::sleep(RECONNECT_DELAY);
+                        }
+                    }
+                }
+            })?;
+
+        Ok(())
+    }
+}
+
+/// Reads the events in one message: a single JSON event or an array of them
+pub fn events_from_payload(payload: &[u8]) -> Result<Vec<GraphEvent>, String> {
+    let value: Value = serde_json::from_slice(payload).map_err(|err| err.to_string())?;
+    match &value {
+        Value::Array(events) => events.iter().map(GraphEvent::from_json).collect(),
+        _ => Ok(vec![GraphEvent::from_json(&value)?]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payloads_and_broker_addresses() {
+        let events = events_from_payload(
+            br#"[
+                {"type": "add_node", "id": "gateway"},
+                {"type": "add_node", "id": "sensor-1", "node_type": "sensor"},
+                {"type": "add_edge", "from": "gateway", "to": "sensor-1"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[2], GraphEvent::AddEdge { from, .. } if from == "gateway"));
+
+        let single = events_from_payload(br#"{"type": "remove_node", "id": "sensor-1"}"#).unwrap();
+        assert!(matches!(&single[..], [GraphEvent::RemoveNode { id }] if id == "sensor-1"));
+        assert!(events_from_payload(b"not json").is_err());
+        assert!(events_from_payload(br#"{"type": "explode"}"#).is_err());
+
+        let source = MqttSource::new("broker.local", "devices/#").unwrap();
+        assert_eq!(
+            (source.host.as_str(), source.port),
+            ("broker.local", DEFAULT_PORT)
+        );
+        assert_eq!(MqttSource::new("10.0.0.5:8883", "t").unwrap().port, 8883);
+        assert!(MqttSource::new("host:port", "t").is_err());
+        assert!(MqttSource::new("host", "").is_err());
+    }
+}
@@ -0,0 +1,475 @@
+use super::{GraphEventSource, SourceError};
+use crate::events::{EventEdgeInfo, EventNodeInfo, GraphEvent};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How far back call and error rates are averaged
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+/// How often edge rates are re-sent to the app
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+/// Upper bound on remembered span ids before the oldest are forgotten
+const MAX_TRACKED_SPANS: usize = 100_000;
+/// Largest request body we are willing to buffer
+const MAX_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+/// Live source that builds a service dependency graph from OTLP/HTTP trace exports
+///
+/// Listens for `POST /v1/traces` requests using the OTLP JSON encoding. Every
+/// span whose parent belongs to a different service adds a call to the edge
+/// between those services; edges carry the call rate and error rate over the
+/// last few seconds.
+pub struct OtelSource {
+    addr: SocketAddr,
+}
+
+impl OtelSource {
+    /// Creates a new OTLP source listening on `addr` once subscribed
+    pub fn new(addr: SocketAddr) -> Self {
+        Self { addr }
+    }
+}
+
+impl GraphEventSource for OtelSource {
+    fn source_name(&self) -> &'static str {
+        "OTLP"
+    }
+
+    fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+        // The service graph starts empty and is filled in as spans arrive
+        Ok(Vec::new())
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    fn subscribe(&self, sender: Sender<GraphEvent>) -> Result<(), SourceError> {
+        let listener = TcpListener::bind(self.addr)?;
+        listener.set_nonblocking(true)?;
+
+        thread::Builder::new()
+            .name("otlp-listener".to_string())
+            .spawn(move || serve(&listener, &sender))?;
+
+        Ok(())
+    }
+}
+
+/// Accepts export requests and periodically forwards graph changes
+fn serve(listener: &TcpListener, sender: &Sender<GraphEvent>) {
+    let mut graph = ServiceGraph::default();
+    let mut last_update = Instant::now();
+
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => match handle_connection(stream) {
+                Ok(Some(payload)) => graph.ingest(&payload, Instant::now()),
+                Ok(None) => {}
+                Err(err) => eprintln!("OTLP request failed: {err}"),
+            },
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(err) => {
+                eprintln!("OTLP listener stopped: {err}");
+                return;
+            }
+        }
+
+        let now = Instant::now();
+        if now.duration_since(last_update) >= UPDATE_INTERVAL {
+            last_update = now;
+            for event in graph.drain_events(now) {
+                // The app has exited once the receiving end is gone
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// A parsed HTTP request
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    content_type: String,
+    body: Vec<u8>,
+}
+
+/// Reads one request from the stream, answers it, and returns the JSON payload if any
+fn handle_connection(stream: TcpStream) -> io::Result<Option<Value>> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut writer = stream.try_clone()?;
+
+    let request = match read_request(&mut BufReader::new(stream)) {
+        Ok(request) => request,
+        Err(err) => {
+            respond(&mut writer, "400 Bad Request", &err.to_string())?;
+            return Ok(None);
+        }
+    };
+
+    if request.method != "POST" || request.path != "/v1/traces" {
+        respond(
+            &mut writer,
+            "404 Not Found",
+            "only POST /v1/traces is supported",
+        )?;
+        return Ok(None);
+    }
+
+    if !request.content_type.starts_with("application/json") {
+        respond(
+            &mut writer,
+            "415 Unsupported Media Type",
+            "only the OTLP JSON encoding is supported",
+        )?;
+        return Ok(None);
+    }
+
+    match serde_json::from_slice(&request.body) {
+        Ok(payload) => {
+            respond(&mut writer, "200 OK", "")?;
+            Ok(Some(payload))
+        }
+        Err(err) => {
+            respond(&mut writer, "400 Bad Request", &err.to_string())?;
+            Ok(None)
+        }
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> io::Result<Request> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().ok_or_else(|| invalid("empty request"))?;
+    let path = parts
+        .next()
+        .ok_or_else(|| invalid("missing request path"))?;
+
+    let mut content_length = 0;
+    let mut content_type = String::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => {
+                    content_length = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| invalid("bad content-length"))?;
+                }
+                "content-type" => content_type = value.trim().to_ascii_lowercase(),
+                _ => {}
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_BYTES {
+        return Err(invalid("request body too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        content_type,
+        body,
+    })
+}
+
+fn respond(stream: &mut impl Write, status: &str, message: &str) -> io::Result<()> {
+    let body = if message.is_empty() {
+        "{}".to_string()
+    } else {
+        serde_json::json!({ "message": message }).to_string()
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Calls observed on one service-to-service edge
+#[derive(Default)]
+struct EdgeStats {
+    /// Timestamp of each call within the rate window and whether it failed
+    calls: VecDeque<(Instant, bool)>,
+    /// Last (rate, `error_rate`) sent, to avoid re-sending unchanged values
+    reported: Option<(f32, f32)>,
+}
+
+/// A child span whose parent has not been seen yet
+struct Orphan {
+    parent_span_id: String,
+    service: String,
+    error: bool,
+    seen: Instant,
+}
+
+/// Aggregates spans into services and weighted call edges
+#[derive(Default)]
+struct ServiceGraph {
+    services: HashSet<String>,
+    edges: HashMap<(String, String), EdgeStats>,
+    span_services: HashMap<String, String>,
+    span_order: VecDeque<String>,
+    orphans: Vec<Orphan>,
+    /// Structural events waiting for the next update
+    pending: Vec<GraphEvent>,
+}
+
+impl ServiceGraph {
+    /// Records all spans from an OTLP `ExportTraceServiceRequest`
+    fn ingest(&mut self, payload: &Value, now: Instant) {
+        let mut children = std::mem::take(&mut self.orphans);
+
+        for resource_spans in array(&payload["resourceSpans"]) {
+            let service = service_name(&resource_spans["resource"]);
+            self.add_service(&service);
+
+            // Older exporters still use the pre-1.0 field name
+            let scopes = array(&resource_spans["scopeSpans"])
+                .chain(array(&resource_spans["instrumentationLibrarySpans"]));
+            for span in scopes.flat_map(|scope| array(&scope["spans"])) {
+                if let Some(span_id) = span["spanId"].as_str() {
+                    self.remember_span(span_id, &service);
+                }
+                if let Some(parent) = span["parentSpanId"].as_str().filter(|p| !p.is_empty()) {
+                    children.push(Orphan {
+                        parent_span_id: parent.to_string(),
+                        service: service.clone(),
+                        error: is_error(&span["status"]),
+                        seen: now,
+                    });
+                }
+            }
+        }
+
+        // Resolve after the whole batch so parents later in the payload are known
+        for child in children {
+            if let Some(parent_service) = self.span_services.get(&child.parent_span_id).cloned() {
+                if parent_service != child.service {
+                    self.record_call(parent_service, child.service, child.error, now);
+                }
+            } else if now.duration_since(child.seen) < RATE_WINDOW {
+                self.orphans.push(child);
+            }
+        }
+    }
+
+    fn add_service(&mut self, service: &str) {
+        if self.services.insert(service.to_string()) {
+            self.pending.push(GraphEvent::AddNode {
+                id: service.to_string(),
+                info: EventNodeInfo {
+                    name: service.to_string(),
+                    node_type: None,
+                    level: 0,
+                    ..Default::default()
+                },
+            });
+        }
+    }
+
+    fn remember_span(&mut self, span_id: &str, service: &str) {
+        if self
+            .span_services
+            .insert(span_id.to_string(), service.to_string())
+            .is_none()
+        {
+            self.span_order.push_back(span_id.to_string());
+        }
+        while self.span_order.len() > MAX_TRACKED_SPANS {
+            if let Some(oldest) = self.span_order.pop_front() {
+                self.span_services.remove(&oldest);
+            }
+        }
+    }
+
+    fn record_call(&mut self, from: String, to: String, error: bool, now: Instant) {
+        let key = (from, to);
+        if !self.edges.contains_key(&key) {
+            self.pending.push(GraphEvent::AddRichEdge {
+                from: key.0.clone(),
+                to: key.1.clone(),
+                info: EventEdgeInfo::default(),
+            });
+        }
+        self.edges
+            .entry(key)
+            .or_default()
+            .calls
+            .push_back((now, error));
+    }
+
+    /// Returns new services and edges plus updated rates for edges that changed
+    fn drain_events(&mut self, now: Instant) -> Vec<GraphEvent> {
+        let mut events = std::mem::take(&mut self.pending);
+
+        for ((from, to), stats) in &mut self.edges {
+            while stats
+                .calls
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) > RATE_WINDOW)
+            {
+                stats.calls.pop_front();
+            }
+
+            let calls = stats.calls.len() as f32;
+            let errors = stats.calls.iter().filter(|(_, error)| *error).count() as f32;
+            let rate = calls / RATE_WINDOW.as_secs_f32();
+            let error_rate = if calls > 0.0 { errors / calls } else { 0.0 };
+
+            if stats.reported != Some((rate, error_rate)) {
+                stats.reported = Some((rate, error_rate));
+                events.push(GraphEvent::UpdateEdge {
+                    from: from.clone(),
+                    to: to.clone(),
+                    info: EventEdgeInfo {
+                        rate: Some(rate),
+                        error_rate: Some(error_rate),
+                        ..Default::default()
+                    },
+                });
+            }
+        }
+
+        events
+    }
+}
+
+fn array(value: &Value) -> impl Iterator<Item = &Value> {
+    value.as_array().into_iter().flatten()
+}
+
+/// Extracts the `service.name` resource attribute
+fn service_name(resource: &Value) -> String {
+    array(&resource["attributes"])
+        .find(|attr| attr["key"] == "service.name")
+        .and_then(|attr| attr["value"]["stringValue"].as_str())
+        .unwrap_or("unknown_service")
+        .to_string()
+}
+
+/// Status code 2 (`STATUS_CODE_ERROR`) may be encoded as a number or enum name
+fn is_error(status: &Value) -> bool {
+    status["code"] == 2 || status["code"] == "STATUS_CODE_ERROR"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn export(service: &str, spans: &Value) -> Value {
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        { "key": "service.name", "value": { "stringValue": service } }
+                    ]
+                },
+                "scopeSpans": [{ "spans": spans }]
+            }]
+        })
+    }
+
+    #[test]
+    fn test_spans_build_service_edges() {
+        let mut graph = ServiceGraph::default();
+        let now = Instant::now();
+
+        // Child arrives before its parent, as is common with batching exporters
+        graph.ingest(
+            &export(
+                "checkout",
+                &json!([
+                    { "spanId": "b1", "parentSpanId": "a1", "status": { "code": 2 } },
+                    { "spanId": "b2", "parentSpanId": "a2" }
+                ]),
+            ),
+            now,
+        );
+        graph.ingest(
+            &export(
+                "frontend",
+                &json!([{ "spanId": "a1" }, { "spanId": "a2", "parentSpanId": "" }]),
+            ),
+            now,
+        );
+
+        let events = graph.drain_events(now);
+
+        let nodes: HashSet<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(nodes, HashSet::from(["checkout", "frontend"]));
+
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GraphEvent::AddRichEdge { from, to, .. } if from == "frontend" && to == "checkout"
+        )));
+
+        let update = events.iter().find_map(|e| match e {
+            GraphEvent::UpdateEdge { info, .. } => Some(info),
+            _ => None,
+        });
+        let update = update.expect("edge rates should be reported");
+        assert_eq!(update.rate, Some(2.0 / RATE_WINDOW.as_secs_f32()));
+        assert_eq!(update.error_rate, Some(0.5));
+
+        // Nothing changed, so nothing is re-sent
+        assert!(graph.drain_events(now).is_empty());
+    }
+
+    #[test]
+    fn test_calls_within_a_service_are_ignored() {
+        let mut graph = ServiceGraph::default();
+        let now = Instant::now();
+
+        graph.ingest(
+            &export(
+                "api",
+                &json!([{ "spanId": "a" }, { "spanId": "b", "parentSpanId": "a" }]),
+            ),
+            now,
+        );
+
+        let events = graph.drain_events(now);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], GraphEvent::AddNode { id, .. } if id == "api"));
+    }
+
+    #[test]
+    fn test_read_request() {
+        let raw = b"POST /v1/traces HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{}";
+        let request = read_request(&mut &raw[..]).unwrap();
+
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/v1/traces");
+        assert_eq!(request.content_type, "application/json");
+        assert_eq!(request.body, b"{}");
+    }
+}
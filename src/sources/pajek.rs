@@ -0,0 +1,230 @@
+use super::{GraphEventSource, Progress, SourceError};
+use crate::events::{EventNodeInfo, GraphEvent};
+use std::collections::HashSet;
+
+/// Source for Pajek `.net` format graphs
+pub struct PajekSource {
+    content: String,
+}
+
+impl PajekSource {
+    /// Creates a new Pajek source from content
+    pub fn new(content: String) -> Self {
+        Self { content }
+    }
+
+    /// Creates a new Pajek source from a string slice
+    pub fn from_content(content: &str) -> Self {
+        Self::new(content.to_string())
+    }
+}
+
+/// Section of a Pajek file currently being read
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+    None,
+    Vertices,
+    /// `*Arcs` / `*Edges`: one `from to [weight]` pair per line
+    Pairs,
+    /// `*Arcslist` / `*Edgeslist`: `from to1 to2 ...` per line
+    Lists,
+    /// Sections we don't understand (e.g. `*Matrix`)
+    Unsupported,
+}
+
+impl GraphEventSource for PajekSource {
+    fn source_name(&self) -> &'static str {
+        "Pajek"
+    }
+
+    fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+        self.events_with_progress(&mut |_| {})
+    }
+
+    fn events_with_progress(
+        &self,
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<Vec<GraphEvent>, SourceError> {
+        let mut events = vec![GraphEvent::BatchStart];
+        let mut section = Section::None;
+        // Vertex numbers as they appear in the file; edges may also reference
+        // vertices that were only implied by the `*Vertices N` count
+        let mut declared = HashSet::new();
+
+        let mut bytes = 0;
+
+        for (line_no, line) in self.content.split_inclusive('\n').enumerate() {
+            bytes += line.len();
+            progress(Progress {
+                bytes,
+                events: events.len(),
+            });
+            let line = line.trim_end_matches(['\r', '\n']);
+            let trimmed = line.trim();
+
+            // Skip comments and empty lines
+            if trimmed.is_empty() || trimmed.starts_with('%') {
+                continue;
+            }
+
+            if trimmed.starts_with('*') {
+                let keyword = trimmed
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                section = match keyword.as_str() {
+                    "*vertices" => Section::Vertices,
+                    "*arcs" | "*edges" => Section::Pairs,
+                    "*arcslist" | "*edgeslist" => Section::Lists,
+                    "*network" => Section::None,
+                    _ => Section::Unsupported,
+                };
+                continue;
+            }
+
+            let tokens = tokenize(trimmed);
+            match section {
+                Section::Vertices => {
+                    let Some(id) = tokens.first() else { continue };
+                    let name = tokens.get(1).cloned().unwrap_or_else(|| id.clone());
+                    declared.insert(id.clone());
+                    events.push(GraphEvent::AddNode {
+                        id: id.clone(),
+                        info: EventNodeInfo {
+                            name,
+                            node_type: None,
+                            level: 0,
+                            ..Default::default()
+                        },
+                    });
+                }
+                Section::Pairs | Section::Lists => {
+                    let mut ids = tokens.iter();
+                    let Some(from) = ids.next() else { continue };
+                    // In pair sections only the second token is a vertex; the rest is weight
+                    let targets: Vec<&String> = if section == Section::Pairs {
+                        ids.take(1).collect()
+                    } else {
+                        ids.collect()
+                    };
+                    if targets.is_empty() {
+                        return Err(SourceError::SyntaxError {
+                            line: line_no + 1,
+                            column: line.len() - line.trim_start().len() + 1,
+                            message: "edge without target".to_string(),
+                        });
+                    }
+
+                    for to in targets {
+                        for id in [from, to] {
+                            if declared.insert(id.clone()) {
+                                events.push(GraphEvent::AddNode {
+                                    id: id.clone(),
+                                    info: EventNodeInfo {
+                                        name: id.clone(),
+                                        node_type: None,
+                                        level: 0,
+                                        ..Default::default()
+                                    },
+                                });
+                            }
+                        }
+                        events.push(GraphEvent::AddEdge {
+                            from: from.clone(),
+                            to: to.clone(),
+                        });
+                    }
+                }
+                Section::None => {
+                    return Err(SourceError::InvalidInput(format!(
+                        "line {}: data before any *Vertices section",
+                        line_no + 1
+                    )));
+                }
+                Section::Unsupported => {}
+            }
+        }
+
+        events.push(GraphEvent::BatchEnd);
+        Ok(events)
+    }
+}
+
+/// Splits a line on whitespace, keeping double-quoted labels together
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            tokens.push(chars.by_ref().take_while(|&c| c != '"').collect());
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pajek_to_events() {
+        let content = r#"
+            % a small network
+            *Vertices 4
+            1 "Load Balancer" 0.1 0.2 0.5
+            2 "Web Server"
+            3 Database
+            *Arcs
+            1 2 1.0
+            2 3
+            *Edgeslist
+            3 1 4
+        "#;
+
+        let events = PajekSource::from_content(content).events().unwrap();
+
+        assert!(matches!(events.first(), Some(GraphEvent::BatchStart)));
+        assert!(matches!(events.last(), Some(GraphEvent::BatchEnd)));
+
+        let names: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddNode { info, .. } => Some(info.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        // Vertex 4 is only implied by the count and an edge reference
+        assert_eq!(names, ["Load Balancer", "Web Server", "Database", "4"]);
+
+        let edges: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                GraphEvent::AddEdge { from, to } => Some((from.as_str(), to.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(edges, [("1", "2"), ("2", "3"), ("3", "1"), ("3", "4")]);
+    }
+
+    #[test]
+    fn test_rejects_data_without_header() {
+        let result = PajekSource::from_content("1 2\n").events();
+        assert!(matches!(result, Err(SourceError::InvalidInput(_))));
+    }
+}
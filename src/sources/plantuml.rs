@@ -26,7 +26,7 @@ impl GraphEventSource for PlantUMLSource {
 
     fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
         // Parse the PlantUML content
-        let dotparser_events = plantuml::parse(&self.content).map_err(SourceError::ParseError)?;
+        let dotparser_events = plantuml::parse(&self.content).map_err(|e| syntax_error(&e))?;
 
         // Convert dotparser events to our internal events
         let mut events = Vec::new();
@@ -63,6 +63,7 @@ impl GraphEventSource for PlantUMLSource {
                             Some(dotparser::Position::Layer { level }) => level,
                             _ => 1,
                         },
+                        properties: properties.custom.into_iter().collect(),
                     };
 
                     events.push(GraphEvent::AddNode { id, info });
@@ -98,6 +99,7 @@ impl GraphEventSource for PlantUMLSource {
                         label,
                         edge_type: edge_type_str,
                         sequence: sequence_num,
+                        ..Default::default()
                     };
 
                     events.push(GraphEvent::AddRichEdge {
@@ -116,6 +118,29 @@ impl GraphEventSource for PlantUMLSource {
     }
 }
 
+/// Pulls the position out of a parser message like `Parse error:  --> 3:5 ...`
+fn syntax_error(message: &str) -> SourceError {
+    let position = message.split_once("--> ").and_then(|(_, rest)| {
+        let (line, rest) = rest.split_once(':')?;
+        let column: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        Some((line.trim().parse().ok()?, column.parse().ok()?))
+    });
+
+    match position {
+        Some((line, column)) => SourceError::SyntaxError {
+            line,
+            column,
+            // The expectation line is the useful part of the parser's report
+            message: message
+                .lines()
+                .find_map(|l| l.trim().strip_prefix("= "))
+                .unwrap_or("invalid PlantUML")
+                .to_string(),
+        },
+        None => SourceError::ParseError(message.to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -154,4 +179,14 @@ mod tests {
         assert_eq!(node_count, 2);
         assert_eq!(edge_count, 2);
     }
+
+    #[test]
+    fn test_parse_error_position() {
+        let message = "Parse error:  --> 3:5\n  |\n3 | foo\n  |     ^---\n  |\n  = expected arrow";
+        assert!(matches!(
+            syntax_error(message),
+            SourceError::SyntaxError { line: 3, column: 5, ref message } if message == "expected arrow"
+        ));
+        assert!(matches!(syntax_error("oops"), SourceError::ParseError(_)));
+    }
 }
@@ -0,0 +1,557 @@
+use super::{GraphEventSource, SourceError};
+use crate::events::GraphEvent;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+use toml::{Table, Value};
+
+/// Scrape interval when the mapping file doesn't set one
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(15);
+/// Largest scrape response we are willing to read
+const MAX_SCRAPE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Live source that scrapes a Prometheus metrics endpoint and sets node and
+/// edge metrics from the samples
+///
+/// Which samples land on which nodes and edges is described by a TOML mapping:
+///
+/// ```toml
+/// url = "http://localhost:9090/metrics"
+/// interval = 15
+///
+/// [[node]]
+/// metric = "process_cpu_usage"
+/// key = "cpu"
+/// id_label = "service"
+///
+/// [[edge]]
+/// metric = "http_client_requests_total"
+/// key = "rps"
+/// from_label = "service"
+/// to_label = "peer"
+/// rate = true
+/// ```
+///
+/// Ids can be fixed with `id`, `from` and `to` instead of taken from labels,
+/// and a `[node.labels]` or `[edge.labels]` table keeps only samples with
+/// those label values. Samples that map to the same node or edge are summed;
+/// `rate = true` turns counters into per-second rates between scrapes.
+pub struct PrometheusSource {
+    config: ScrapeConfig,
+}
+
+impl PrometheusSource {
+    /// Creates a source from the text of a TOML mapping file
+    pub fn from_mapping(content: &str) -> Result<Self, SourceError> {
+        Ok(Self {
+            config: ScrapeConfig::parse(content)?,
+        })
+    }
+}
+
+impl GraphEventSource for PrometheusSource {
+    fn source_name(&self) -> &'static str {
+        "Prometheus"
+    }
+
+    fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+        // Metrics only decorate the graph loaded alongside the source
+        Ok(Vec::new())
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    fn subscribe(&self, sender: Sender<GraphEvent>) -> Result<(), SourceError> {
+        let config = self.config.clone();
+        thread::Builder::new()
+            .name("prometheus-scraper".to_string())
+            .spawn(move || scrape_loop(&config, &sender))?;
+        Ok(())
+    }
+}
+
+/// Scrapes on every interval until the app exits
+fn scrape_loop(config: &ScrapeConfig, sender: &Sender<GraphEvent>) {
+    let mut counters = CounterRates::default();
+    loop {
+        let started = Instant::now();
+        match scrape(&config.url) {
+            Ok(text) => {
+                let samples = parse_samples(&text);
+                for event in config.events(&samples, &mut counters, started) {
+                    // The app has exited once the receiving end is gone
+                    if sender.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(err) => eprintln!("Prometheus scrape of {} failed: {err}", config.url),
+        }
+        thread::sleep(config.interval.saturating_sub(started.elapsed()));
+    }
+}
+
+fn scrape(url: &str) -> Result<String, ureq::Error> {
+    ureq::get(url)
+        .call()?
+        .body_mut()
+        .with_config()
+        .limit(MAX_SCRAPE_BYTES)
+        .read_to_string()
+}
+
+/// One sample from the text exposition format
+#[derive(Debug, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: HashMap<String, String>,
+    pub value: f64,
+}
+
+/// Parses the Prometheus text exposition format, skipping comments and any
+/// line it can't read
+pub fn parse_samples(text: &str) -> Vec<Sample> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(parse_sample)
+        .collect()
+}
+
+fn parse_sample(line: &str) -> Option<Sample> {
+    let (name, labels, rest) = if let Some(open) = line.find('{') {
+        let close = line.rfind('}')?;
+        (
+            &line[..open],
+            parse_labels(&line[open + 1..close])?,
+            &line[close + 1..],
+        )
+    } else {
+        let (name, rest) = line.split_once(char::is_whitespace)?;
+        (name, HashMap::new(), rest)
+    };
+    // A timestamp may follow the value
+    let value = rest.split_whitespace().next()?.parse().ok()?;
+    Some(Sample {
+        name: name.trim().to_string(),
+        labels,
+        value,
+    })
+}
+
+/// Parses `a="x",b="y"`, unescaping values
+fn parse_labels(text: &str) -> Option<HashMap<String, String>> {
+    let mut labels = HashMap::new();
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        if chars.peek().is_none() {
+            return Some(labels);
+        }
+        let name: String = chars.by_ref().take_while(|&c| c != '=').collect();
+        if chars.next()? != '"' {
+            return None;
+        }
+        let mut value = String::new();
+        loop {
+            match chars.next()? {
+                '"' => break,
+                '\\' => match chars.next()? {
+                    'n' => value.push('\n'),
+                    escaped => value.push(escaped),
+                },
+                c => value.push(c),
+            }
+        }
+        labels.insert(name.trim().to_string(), value);
+    }
+}
+
+/// Where a mapped sample's node or edge id comes from
+#[derive(Debug, Clone)]
+enum IdSource {
+    Fixed(String),
+    Label(String),
+}
+
+impl IdSource {
+    fn from_rule(rule: &Table, fixed: &str, label: &str) -> Result<Self, SourceError> {
+        match (text_field(rule, fixed)?, text_field(rule, label)?) {
+            (Some(id), None) => Ok(Self::Fixed(id)),
+            (None, Some(label)) => Ok(Self::Label(label)),
+            _ => Err(SourceError::InvalidInput(format!(
+                "mapping rules need exactly one of \"{fixed}\" and \"{label}\""
+            ))),
+        }
+    }
+
+    fn resolve(&self, sample: &Sample) -> Option<String> {
+        match self {
+            Self::Fixed(id) => Some(id.clone()),
+            Self::Label(label) => sample.labels.get(label).cloned(),
+        }
+    }
+}
+
+/// What a mapping rule sets the metric on
+#[derive(Debug, Clone)]
+enum Target {
+    Node(IdSource),
+    Edge(IdSource, IdSource),
+}
+
+/// Maps samples of one metric onto a node or edge metric
+#[derive(Debug, Clone)]
+struct MappingRule {
+    metric: String,
+    key: String,
+    labels: Vec<(String, String)>,
+    rate: bool,
+    target: Target,
+}
+
+impl MappingRule {
+    fn parse(rule: &Table, edge: bool) -> Result<Self, SourceError> {
+        let metric = text_field(rule, "metric")?
+            .ok_or_else(|| SourceError::InvalidInput("mapping rule without \"metric\"".into()))?;
+        let labels = match rule.get("labels") {
+            None => Vec::new(),
+            Some(Value::Table(labels)) => labels
+                .iter()
+                .map(|(name, value)| {
+                    value
+                        .as_str()
+                        .map(|value| (name.clone(), value.to_string()))
+                        .ok_or_else(|| {
+                            SourceError::InvalidInput(format!("label \"{name}\" must be a string"))
+                        })
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => {
+                return Err(SourceError::InvalidInput(
+                    "\"labels\" must be a table".into(),
+                ));
+            }
+        };
+        let target = if edge {
+            Target::Edge(
+                IdSource::from_rule(rule, "from", "from_label")?,
+                IdSource::from_rule(rule, "to", "to_label")?,
+            )
+        } else {
+            Target::Node(IdSource::from_rule(rule, "id", "id_label")?)
+        };
+        Ok(Self {
+            key: text_field(rule, "key")?.unwrap_or_else(|| metric.clone()),
+            metric,
+            labels,
+            rate: rule
+                .get("rate")
+                .and_then(Value::as_bool)
+                .unwrap_or_default(),
+            target,
+        })
+    }
+
+    fn matches(&self, sample: &Sample) -> bool {
+        sample.name == self.metric
+            && self
+                .labels
+                .iter()
+                .all(|(name, value)| sample.labels.get(name) == Some(value))
+    }
+
+    /// Node id, or source and target ids, a sample maps to
+    fn ids(&self, sample: &Sample) -> Option<(String, Option<String>)> {
+        match &self.target {
+            Target::Node(id) => Some((id.resolve(sample)?, None)),
+            Target::Edge(from, to) => Some((from.resolve(sample)?, Some(to.resolve(sample)?))),
+        }
+    }
+}
+
+fn text_field(table: &Table, key: &str) -> Result<Option<String>, SourceError> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::String(text)) => Ok(Some(text.clone())),
+        Some(_) => Err(SourceError::InvalidInput(format!(
+            "\"{key}\" must be a string"
+        ))),
+    }
+}
+
+/// Counter values from the previous scrape, for turning counters into rates
+#[derive(Default)]
+struct CounterRates {
+    previous: HashMap<(usize, String, Option<String>), (f64, Instant)>,
+}
+
+impl CounterRates {
+    /// Per-second increase since the last scrape; none on the first one or
+    /// after a counter reset
+    fn rate(
+        &mut self,
+        target: (usize, String, Option<String>),
+        value: f64,
+        now: Instant,
+    ) -> Option<f64> {
+        let (last, then) = self.previous.insert(target, (value, now))?;
+        let secs = now.duration_since(then).as_secs_f64();
+        (value >= last && secs > 0.0).then(|| (value - last) / secs)
+    }
+}
+
+/// A parsed mapping file
+#[derive(Debug, Clone)]
+pub struct ScrapeConfig {
+    url: String,
+    interval: Duration,
+    rules: Vec<MappingRule>,
+}
+
+impl ScrapeConfig {
+    pub fn parse(content: &str) -> Result<Self, SourceError> {
+        let table: Table = content
+            .parse()
+            .map_err(|err: toml::de::Error| SourceError::ParseError(err.message().to_string()))?;
+        let url = text_field(&table, "url")?
+            .ok_or_else(|| SourceError::InvalidInput("mapping without \"url\"".into()))?;
+        let interval = match table.get("interval") {
+            None => DEFAULT_INTERVAL,
+            Some(Value::Integer(secs)) if *secs > 0 => Duration::from_secs(secs.unsigned_abs()),
+            Some(Value::Float(secs)) if *secs > 0.0 => Duration::from_secs_f64(*secs),
+            Some(_) => {
+                return Err(SourceError::InvalidInput(
+                    "\"interval\" must be a positive number of seconds".into(),
+                ));
+            }
+        };
+
+        let mut rules = Vec::new();
+        for (section, edge) in [("node", false), ("edge", true)] {
+            let Some(section) = table.get(section) else {
+                continue;
+            };
+            let Some(entries) = section.as_array() else {
+                return Err(SourceError::InvalidInput(format!(
+                    "use [[{section}]] for {section} mappings"
+                )));
+            };
+            for entry in entries {
+                let rule = entry.as_table().ok_or_else(|| {
+                    SourceError::InvalidInput("mapping rules must be tables".into())
+                })?;
+                rules.push(MappingRule::parse(rule, edge)?);
+            }
+        }
+        if rules.is_empty() {
+            return Err(SourceError::InvalidInput(
+                "mapping has no [[node]] or [[edge]] rules".into(),
+            ));
+        }
+        Ok(Self {
+            url,
+            interval,
+            rules,
+        })
+    }
+
+    /// Metric events for the samples of one scrape taken at `now`
+    #[allow(clippy::cast_possible_truncation)]
+    fn events(
+        &self,
+        samples: &[Sample],
+        counters: &mut CounterRates,
+        now: Instant,
+    ) -> Vec<GraphEvent> {
+        let mut totals = BTreeMap::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            for sample in samples.iter().filter(|sample| rule.matches(sample)) {
+                if let Some((id, to)) = rule.ids(sample) {
+                    *totals.entry((index, id, to)).or_default() += sample.value;
+                }
+            }
+        }
+
+        totals
+            .into_iter()
+            .filter_map(|(target, total)| {
+                let rule = &self.rules[target.0];
+                let value = if rule.rate {
+                    counters.rate(target.clone(), total, now)?
+                } else {
+                    total
+                };
+                let (_, id, to) = target;
+                let key = rule.key.clone();
+                let value = value as f32;
+                Some(match to {
+                    None => GraphEvent::SetNodeMetric { id, key, value },
+                    Some(to) => GraphEvent::SetEdgeMetric {
+                        from: id,
+                        to,
+                        key,
+                        value,
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventResult;
+    use crate::graph_state::GraphState;
+
+    #[test]
+    fn test_samples_map_onto_nodes_and_edges() {
+        let mapping = r#"
+            url = "http://localhost:9100/metrics"
+            interval = 5
+
+            [[node]]
+            metric = "cpu_usage"
+            key = "cpu"
+            id_label = "service"
+
+            [[edge]]
+            metric = "requests_total"
+            key = "rps"
+            from_label = "service"
+            to_label = "peer"
+            rate = true
+            [edge.labels]
+            method = "GET"
+        "#;
+        let config = ScrapeConfig::parse(mapping).unwrap();
+        assert_eq!(config.interval, Duration::from_secs(5));
+
+        let scrape = |requests: u32| {
+            parse_samples(&format!(
+                r#"
+                # HELP cpu_usage CPU load
+                # TYPE cpu_usage gauge
+                cpu_usage{{service="api"}} 0.5
+                cpu_usage{{service="db",note="a \"quoted\" value"}} 0.25 1700000000000
+                requests_total{{service="api",peer="db",method="GET",code="200"}} {requests}
+                requests_total{{service="api",peer="db",method="GET",code="500"}} 10
+                requests_total{{service="api",peer="db",method="POST"}} 1000
+                up 1
+                "#
+            ))
+        };
+        let samples = scrape(90);
+        assert_eq!(samples.len(), 6);
+        assert_eq!(samples[1].labels["note"], "a \"quoted\" value");
+
+        let mut counters = CounterRates::default();
+        let start = Instant::now();
+        // Counters need two scrapes before they have a rate
+        let events = config.events(&samples, &mut counters, start);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            &events[0],
+            GraphEvent::SetNodeMetric { id, key, value } if id == "api" && key == "cpu" && (*value - 0.5).abs() < f32::EPSILON
+        ));
+
+        let events = config.events(&scrape(140), &mut counters, start + Duration::from_secs(5));
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            &events[2],
+            GraphEvent::SetEdgeMetric { from, to, key, value }
+                if from == "api" && to == "db" && key == "rps" && (*value - 10.0).abs() < f32::EPSILON
+        ));
+
+        assert!(ScrapeConfig::parse("url = \"http://x\"").is_err());
+        assert!(ScrapeConfig::parse("[[node]]\nmetric = \"m\"\nid = \"a\"").is_err());
+    }
+
+    #[test]
+    fn test_samples_without_a_matching_id_are_skipped() {
+        let mapping = r#"
+            url = "http://localhost:9100/metrics"
+
+            [[node]]
+            metric = "cpu_usage"
+            id_label = "service"
+
+            [[edge]]
+            metric = "latency"
+            from_label = "service"
+            to_label = "peer"
+        "#;
+        let config = ScrapeConfig::parse(mapping).unwrap();
+        let samples = parse_samples(
+            r#"
+            cpu_usage{instance="host:9100"} 0.5
+            latency{service="api"} 12
+            cpu_usage{service="ghost"} 0.75
+            "#,
+        );
+        let events = config.events(&samples, &mut CounterRates::default(), Instant::now());
+        // Only the sample with its id label makes an event
+        assert_eq!(events.len(), 1);
+
+        // A node the graph doesn't have is reported, not a failure
+        let mut state = GraphState::new();
+        for event in events {
+            assert!(matches!(
+                state.process_event(event),
+                EventResult::NodeNotFound
+            ));
+        }
+        assert_eq!(state.node_count(), 0);
+    }
+
+    #[test]
+    fn test_malformed_mappings_are_rejected() {
+        assert!(matches!(
+            ScrapeConfig::parse("url = \"http://x\"\n[[node]\nmetric ="),
+            Err(SourceError::ParseError(_))
+        ));
+        for mapping in [
+            "url = 9100\n[[node]]\nmetric = \"m\"\nid = \"a\"",
+            "url = \"http://x\"\ninterval = -5\n[[node]]\nmetric = \"m\"\nid = \"a\"",
+            "url = \"http://x\"\n[node]\nmetric = \"m\"\nid = \"a\"",
+            "url = \"http://x\"\n[[node]]\nmetric = \"m\"\nid = \"a\"\nid_label = \"b\"",
+            "url = \"http://x\"\n[[edge]]\nmetric = \"m\"\nfrom = \"a\"",
+            "url = \"http://x\"\n[[node]]\nmetric = \"m\"\nid = \"a\"\nlabels = \"x\"",
+        ] {
+            assert!(
+                matches!(
+                    ScrapeConfig::parse(mapping),
+                    Err(SourceError::InvalidInput(_))
+                ),
+                "{mapping}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_unparseable_scrape_lines_are_skipped() {
+        let samples = parse_samples(
+            r#"
+            <html><body>502 Bad Gateway</body></html>
+            cpu_usage{service="api" 0.5
+            cpu_usage{service="api"} not-a-number
+            cpu_usage{service=api} 0.5
+            cpu_usage
+            cpu_usage{service="db"} 0.25
+            "#,
+        );
+        assert_eq!(
+            samples,
+            [Sample {
+                name: "cpu_usage".to_string(),
+                labels: HashMap::from([("service".to_string(), "db".to_string())]),
+                value: 0.25,
+            }]
+        );
+        assert!(parse_samples("\u{0}\u{1}garbage{{{").is_empty());
+    }
+}
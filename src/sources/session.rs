@@ -0,0 +1,243 @@
+use super::{GraphEventSource, Progress, SourceError};
+use crate::events::GraphEvent;
+use crate::graph_state::LoggedEvent;
+use serde_json::{Value, json};
+use std::io::{self, Write};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+/// Source for session recordings: one JSON event per line, each stamped
+/// with the seconds since the recording started
+///
+/// Loaded as a file, the whole session is applied at once. Replayed, the
+/// events arrive live with their original spacing, divided by `speed`.
+pub struct SessionSource {
+    content: String,
+    speed: Option<f32>,
+}
+
+impl SessionSource {
+    /// Creates a source that applies the whole session at once
+    pub fn from_content(content: &str) -> Self {
+        Self {
+            content: content.to_string(),
+            speed: None,
+        }
+    }
+
+    /// Creates a source that replays the session in real time, `speed` times faster
+    pub fn replay(content: &str, speed: f32) -> Self {
+        Self {
+            content: content.to_string(),
+            speed: Some(speed),
+        }
+    }
+
+    fn entries(
+        &self,
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<Vec<(f64, GraphEvent)>, SourceError> {
+        let mut entries = Vec::new();
+        let mut bytes = 0;
+        for (line_no, line) in self.content.split_inclusive('\n').enumerate() {
+            bytes += line.len();
+            progress(Progress {
+                bytes,
+                events: entries.len(),
+            });
+            if line.trim().is_empty() {
+                continue;
+            }
+            let syntax_error = |message: String| SourceError::SyntaxError {
+                line: line_no + 1,
+                column: 1,
+                message,
+            };
+            let value: Value =
+                serde_json::from_str(line).map_err(|e| syntax_error(e.to_string()))?;
+            let at = value.get("at").and_then(Value::as_f64).unwrap_or_default();
+            entries.push((at, GraphEvent::from_json(&value).map_err(syntax_error)?));
+        }
+        Ok(entries)
+    }
+}
+
+impl GraphEventSource for SessionSource {
+    fn source_name(&self) -> &'static str {
+        if self.speed.is_some() {
+            "Replay"
+        } else {
+            "Session"
+        }
+    }
+
+    fn events(&self) -> Result<Vec<GraphEvent>, SourceError> {
+        self.events_with_progress(&mut |_| {})
+    }
+
+    fn events_with_progress(
+        &self,
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<Vec<GraphEvent>, SourceError> {
+        let entries = self.entries(progress)?;
+        if self.speed.is_some() {
+            return Ok(Vec::new());
+        }
+        let mut events = vec![GraphEvent::BatchStart];
+        events.extend(entries.into_iter().map(|(_, event)| event));
+        events.push(GraphEvent::BatchEnd);
+        Ok(events)
+    }
+
+    fn is_live(&self) -> bool {
+        self.speed.is_some()
+    }
+
+    fn subscribe(&self, sender: Sender<GraphEvent>) -> Result<(), SourceError> {
+        let Some(speed) = self.speed else {
+            return Ok(());
+        };
+        let entries = self.entries(&mut |_| {})?;
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            for (at, event) in entries {
+                let due = start + Duration::from_secs_f64(at.max(0.0) / f64::from(speed));
+                std::thread::sleep(due.saturating_duration_since(Instant::now()));
+                if sender.send(event).is_err() {
+                    // The app has closed
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Parses a replay speed such as `4x`, `0.5x` or `2`
+pub fn parse_speed(text: &str) -> Result<f32, String> {
+    let number = text.trim().trim_end_matches(['x', 'X']);
+    match number.parse::<f32>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        _ => Err(format!("expected a positive speed like 4x, got '{text}'")),
+    }
+}
+
+/// Writes logged events as a session recording, timed from the first event
+pub fn write_session(log: &[LoggedEvent], mut writer: impl Write) -> io::Result<()> {
+    let Some(first) = log.first() else {
+        return Ok(());
+    };
+    for entry in log {
+        let at = entry.at.duration_since(first.at).unwrap_or_default();
+        let mut line = entry.event.to_json();
+        line.insert("at".to_string(), json!(at.as_secs_f64()));
+        writeln!(writer, "{}", Value::Object(line))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{AlertState, EventEdgeInfo, EventNodeInfo};
+    use crate::graph_state::GraphState;
+
+    #[test]
+    fn test_session_round_trip() {
+        let mut state = GraphState::new();
+        state.process_events(vec![
+            GraphEvent::AddNode {
+                id: "api".to_string(),
+                info: EventNodeInfo {
+                    name: "API".to_string(),
+                    node_type: Some("service".to_string()),
+                    level: 1,
+                    ..Default::default()
+                },
+            },
+            GraphEvent::AddNode {
+                id: "db".to_string(),
+                info: EventNodeInfo {
+                    name: "db".to_string(),
+                    node_type: None,
+                    level: 2,
+                    ..Default::default()
+                },
+            },
+            GraphEvent::AddRichEdge {
+                from: "api".to_string(),
+                to: "db".to_string(),
+                info: EventEdgeInfo {
+                    label: Some("query".to_string()),
+                    rate: Some(12.5),
+                    ..Default::default()
+                },
+            },
+            GraphEvent::SetNodeMetric {
+                id: "db".to_string(),
+                key: "cpu".to_string(),
+                value: 0.75,
+            },
+            GraphEvent::SetNodeState {
+                id: "db".to_string(),
+                state: Some(AlertState::Critical),
+            },
+            GraphEvent::SetEdgeMetric {
+                from: "api".to_string(),
+                to: "db".to_string(),
+                key: "rps".to_string(),
+                value: 40.0,
+            },
+        ]);
+
+        let mut recording = Vec::new();
+        write_session(state.event_log(), &mut recording).unwrap();
+        let recording = String::from_utf8(recording).unwrap();
+        assert_eq!(recording.lines().count(), 6);
+        assert!(recording.lines().all(|line| line.contains("\"at\":")));
+
+        let mut replayed = GraphState::new();
+        replayed.process_events(SessionSource::from_content(&recording).events().unwrap());
+        assert_eq!((replayed.node_count(), replayed.edge_count()), (2, 1));
+        assert_eq!(
+            replayed.get_node("api").unwrap().node_type.as_deref(),
+            Some("service")
+        );
+        let data = replayed.as_graph_data();
+        let edge = data.graph.edge_indices().next().unwrap();
+        assert_eq!(data.graph[edge].label.as_deref(), Some("query"));
+        assert_eq!(data.graph[edge].rate, Some(12.5));
+        assert_eq!(data.graph[edge].metrics.get("rps"), Some(&40.0));
+        assert_eq!(
+            replayed.get_node("db").unwrap().metrics.get("cpu"),
+            Some(&0.75)
+        );
+        assert_eq!(
+            replayed.get_node("db").unwrap().state,
+            Some(AlertState::Critical)
+        );
+
+        // Replays deliver everything live instead
+        assert!(
+            SessionSource::replay(&recording, 4.0)
+                .events()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!(parse_speed("4x"), Ok(4.0));
+        assert_eq!(parse_speed("0.5"), Ok(0.5));
+        assert!(parse_speed("0x").is_err());
+        assert!(parse_speed("fast").is_err());
+    }
+
+    #[test]
+    fn test_bad_lines_report_their_position() {
+        let content = "{\"type\":\"clear\",\"at\":0}\n{\"type\":\"explode\"}\n";
+        let error = SessionSource::from_content(content).events().unwrap_err();
+        assert!(matches!(error, SourceError::SyntaxError { line: 2, .. }));
+    }
+}
@@ -0,0 +1,115 @@
+use super::{SourceError, line_column};
+use std::collections::HashMap;
+
+/// A single XML start, end, or empty-element tag
+#[derive(Debug)]
+pub(super) struct Tag {
+    pub name: String,
+    pub attrs: HashMap<String, String>,
+    pub closing: bool,
+    pub self_closing: bool,
+    /// Byte offset of the opening `<`, for error positions
+    pub offset: usize,
+    /// Byte offset just past the closing `>`, where any text content starts
+    pub end: usize,
+}
+
+pub(super) fn syntax_error(content: &str, offset: usize, message: &str) -> SourceError {
+    let (line, column) = line_column(content, offset);
+    SourceError::SyntaxError {
+        line,
+        column,
+        message: message.to_string(),
+    }
+}
+
+/// Splits XML content into tags, skipping text, comments, and declarations
+pub(super) fn scan_tags(content: &str) -> Result<Vec<Tag>, SourceError> {
+    let mut tags = Vec::new();
+    let mut pos = 0;
+
+    while let Some(start) = content[pos..].find('<') {
+        pos += start;
+        let rest = &content[pos..];
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after
+                .find("-->")
+                .ok_or_else(|| syntax_error(content, pos, "unterminated comment"))?;
+            pos += 4 + end + 3;
+            continue;
+        }
+
+        let end =
+            find_tag_end(rest).ok_or_else(|| syntax_error(content, pos, "unterminated tag"))?;
+        let body = &rest[1..end];
+
+        // Skip <?xml ...?> and <!DOCTYPE ...>
+        if !body.starts_with('?') && !body.starts_with('!') {
+            tags.push(parse_tag(body, pos, pos + end + 1));
+        }
+        pos += end + 1;
+    }
+
+    Ok(tags)
+}
+
+/// Finds the closing `>` of a tag, ignoring any inside quoted attribute values
+fn find_tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_tag(body: &str, offset: usize, end: usize) -> Tag {
+    let closing = body.starts_with('/');
+    let self_closing = body.ends_with('/');
+    let body = body.trim_start_matches('/').trim_end_matches('/').trim();
+
+    let name_end = body.find(char::is_whitespace).unwrap_or(body.len());
+    let name = body[..name_end].to_string();
+
+    let mut attrs = HashMap::new();
+    let mut rest = &body[name_end..];
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim().to_string();
+        let value_part = rest[eq + 1..].trim_start();
+        let Some(quote) = value_part
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+        else {
+            break;
+        };
+        let Some(value_end) = value_part[1..].find(quote) else {
+            break;
+        };
+        attrs.insert(key, unescape(&value_part[1..=value_end]));
+        rest = &value_part[value_end + 2..];
+    }
+
+    Tag {
+        name,
+        attrs,
+        closing,
+        self_closing,
+        offset,
+        end,
+    }
+}
+
+pub(super) fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
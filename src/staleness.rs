@@ -0,0 +1,198 @@
+use crate::events::GraphEvent;
+use crate::graph_state::GraphState;
+use crate::types::{GraphData, GraphNode, NodeChange, OriginalMaterial, Timeline};
+use crate::visualization::refresh_graph_scene;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// Opacity of a node once it has fully faded
+const MIN_ALPHA: f32 = 0.3;
+
+/// How long live nodes may go without events before they fade, and whether
+/// fully faded nodes are removed
+#[derive(Resource, Default, Clone)]
+pub struct Staleness {
+    /// Seconds before a quiet node starts to fade; None turns aging off
+    pub ttl: Option<f32>,
+    pub remove: bool,
+}
+
+/// App time in seconds when each node last appeared in a live event
+///
+/// Nodes that never have count from startup.
+#[derive(Resource, Default)]
+pub struct NodeActivity {
+    last_seen: HashMap<String, f32>,
+}
+
+impl NodeActivity {
+    /// Marks the nodes an event names as active at app time `now`
+    pub fn record(&mut self, event: &GraphEvent, now: f32) {
+        match event {
+            GraphEvent::AddNode { id, .. }
+            | GraphEvent::UpdateNode { id, .. }
+            | GraphEvent::SetNodeMetric { id, .. }
+            | GraphEvent::SetNodeState { id, .. } => {
+                self.last_seen.insert(id.clone(), now);
+            }
+            GraphEvent::RemoveNode { id } => {
+                self.last_seen.remove(id);
+            }
+            GraphEvent::AddEdge { from, to }
+            | GraphEvent::AddRichEdge { from, to, .. }
+            | GraphEvent::UpdateEdge { from, to, .. }
+            | GraphEvent::SetEdgeMetric { from, to, .. }
+            | GraphEvent::RemoveEdge { from, to } => {
+                self.last_seen.insert(from.clone(), now);
+                self.last_seen.insert(to.clone(), now);
+            }
+            GraphEvent::Clear => self.last_seen.clear(),
+            GraphEvent::ShowCaption { .. } | GraphEvent::BatchStart | GraphEvent::BatchEnd => {}
+        }
+    }
+
+    /// How faded a node is at app time `now`: 0 until it has been quiet for
+    /// `ttl` seconds, rising to 1 at twice that
+    pub fn staleness(&self, id: &str, now: f32, ttl: f32) -> f32 {
+        let last = self.last_seen.get(id).copied().unwrap_or_default();
+        ((now - last - ttl) / ttl).clamp(0.0, 1.0)
+    }
+}
+
+/// Color a stale node had before it started to fade
+#[derive(Component)]
+pub struct StaleMaterial {
+    color: Color,
+}
+
+/// Fading live nodes that stop receiving events
+pub struct StalenessPlugin;
+
+impl Plugin for StalenessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Staleness>()
+            .init_resource::<NodeActivity>()
+            .add_systems(
+                Update,
+                (
+                    remove_stale_nodes,
+                    fade_stale_nodes.after(refresh_graph_scene),
+                ),
+            );
+    }
+}
+
+/// Desaturates and fades nodes that have been quiet for longer than the TTL
+///
+/// Fading pauses on highlighted and changing nodes, which have materials of
+/// their own.
+#[allow(clippy::type_complexity)]
+pub fn fade_stale_nodes(
+    mut commands: Commands,
+    staleness: Res<Staleness>,
+    activity: Res<NodeActivity>,
+    timeline: Res<Timeline>,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut node_query: Query<
+        (
+            Entity,
+            &GraphNode,
+            &mut MeshMaterial3d<StandardMaterial>,
+            Option<&StaleMaterial>,
+        ),
+        (Without<OriginalMaterial>, Without<NodeChange>),
+    >,
+) {
+    // The past is shown as it was, not as it has aged since
+    let Some(ttl) = staleness.ttl.filter(|_| timeline.position.is_none()) else {
+        return;
+    };
+    let now = time.elapsed_secs();
+
+    for (entity, node, mut material, stale) in &mut node_query {
+        let amount = activity.staleness(&node.id, now, ttl);
+        let color = if let Some(stale) = stale {
+            stale.color
+        } else if amount > 0.0 {
+            // Node materials are shared by type, so fade a copy
+            let Some(copy) = materials.get(&material.0).cloned() else {
+                continue;
+            };
+            let color = copy.base_color;
+            material.0 = materials.add(StandardMaterial {
+                alpha_mode: AlphaMode::Blend,
+                ..copy
+            });
+            commands.entity(entity).insert(StaleMaterial { color });
+            color
+        } else {
+            continue;
+        };
+
+        let mut faded = Hsla::from(color);
+        faded.saturation *= 1.0 - amount;
+        faded.alpha = (MIN_ALPHA - 1.0).mul_add(amount, 1.0);
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.base_color = faded.into();
+        }
+    }
+}
+
+/// Removes nodes that have fully faded, when removal is turned on
+pub fn remove_stale_nodes(
+    staleness: Res<Staleness>,
+    activity: Res<NodeActivity>,
+    timeline: Res<Timeline>,
+    time: Res<Time>,
+    graph_data: Option<Res<GraphData>>,
+    mut graph_state: ResMut<GraphState>,
+) {
+    let (Some(ttl), true, None) = (staleness.ttl, staleness.remove, timeline.position) else {
+        return;
+    };
+    let Some(data) = graph_data else {
+        return;
+    };
+    let now = time.elapsed_secs();
+    let expired: Vec<_> = data
+        .node_map
+        .keys()
+        .filter(|id| activity.staleness(id, now, ttl) >= 1.0)
+        .map(|id| GraphEvent::RemoveNode { id: id.clone() })
+        .collect();
+    // Only touch the state (and trigger a scene refresh) when a node expired
+    if !expired.is_empty() {
+        graph_state.process_events(expired);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nodes_fade_after_ttl_without_events() {
+        let mut activity = NodeActivity::default();
+        activity.record(
+            &GraphEvent::AddEdge {
+                from: "api".to_string(),
+                to: "db".to_string(),
+            },
+            10.0,
+        );
+        activity.record(
+            &GraphEvent::RemoveNode {
+                id: "db".to_string(),
+            },
+            12.0,
+        );
+
+        // Still fresh within the TTL, half faded halfway through the next one
+        assert!(activity.staleness("api", 14.0, 5.0).abs() < f32::EPSILON);
+        assert!((activity.staleness("api", 17.5, 5.0) - 0.5).abs() < 1e-5);
+        assert!((activity.staleness("api", 30.0, 5.0) - 1.0).abs() < f32::EPSILON);
+        // Nodes with no events count from startup
+        assert!((activity.staleness("db", 10.0, 5.0) - 1.0).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::aggregate::Aggregation;
+use crate::graph_state::GraphState;
+use crate::input;
+use crate::live::LiveStatus;
+use crate::loader::InputSource;
+use crate::types::{
+    EditState, GraphData, LayoutSettings, SelectedNodes, StatusBar, ThemedPanel, ThemedText,
+    Timeline,
+};
+use bevy::prelude::*;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Seconds a status message stays on the status bar
+const MESSAGE_SECS: f32 = 6.0;
+
+/// Short-lived message appended to the status bar, e.g. the result of an export
+#[derive(Resource, Default)]
+pub struct StatusMessage {
+    text: String,
+    until: f32,
+}
+
+impl StatusMessage {
+    pub fn show(&mut self, text: impl Into<String>, time: &Time) {
+        self.text = text.into();
+        self.until = time.elapsed_secs() + MESSAGE_SECS;
+    }
+
+    fn current(&self, time: &Time) -> Option<&str> {
+        (time.elapsed_secs() < self.until).then_some(self.text.as_str())
+    }
+}
+
+pub fn setup_status_bar(commands: &mut Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        TextColor(Color::srgb(0.85, 0.85, 0.85)),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(0.0),
+            left: Val::Px(0.0),
+            right: Val::Px(0.0),
+            padding: UiRect::axes(Val::Px(10.0), Val::Px(5.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.05, 0.05, 0.1, 0.8)),
+        StatusBar,
+        ThemedText,
+        ThemedPanel,
+    ));
+}
+
+/// Shows where the graph came from, its size and the layout along the bottom edge
+#[allow(clippy::too_many_arguments)]
+pub fn update_status_bar(
+    input_source: Option<Res<InputSource>>,
+    live_status: Option<Res<LiveStatus>>,
+    graph_state: Res<GraphState>,
+    layout: Res<LayoutSettings>,
+    time: Res<Time>,
+    message: Res<StatusMessage>,
+    edit: Res<EditState>,
+    selection: Res<SelectedNodes>,
+    timeline: Res<Timeline>,
+    aggregation: Res<Aggregation>,
+    shown: Option<Res<GraphData>>,
+    mut bar_query: Query<&mut Text, With<StatusBar>>,
+) {
+    let Ok(mut text) = bar_query.single_mut() else {
+        return;
+    };
+
+    let mut parts = Vec::new();
+    if let Some(source) = input_source.as_deref() {
+        let mut name = source_name(source.location.as_deref());
+        if let Some(merged) = &source.merged {
+            // The other files merged into the graph
+            let _ = write!(name, " +{}", merged.inputs.len().saturating_sub(1));
+        }
+        parts.push(name);
+        parts.push(source.loaded_format.unwrap_or("not loaded").to_string());
+    }
+    if let Some(live) = live_status
+        .as_deref()
+        .filter(|live| !live.sources.is_empty())
+    {
+        parts.push(format!(
+            "{}: {}, {:.1} events/s",
+            live.sources.join(", "),
+            live.connection_state(time.elapsed_secs()),
+            live.events_per_sec
+        ));
+        if live.backlog > 0 {
+            parts.push(format!("{} events queued", live.backlog));
+        }
+    }
+    parts.push(format!(
+        "{} nodes, {} edges",
+        graph_state.node_count(),
+        graph_state.edge_count()
+    ));
+    if let Some(shown) = shown.filter(|_| aggregation.applies_to(graph_state.node_count())) {
+        parts.push(format!(
+            "aggregated to {} (U expands a cluster)",
+            shown.graph.node_count()
+        ));
+    }
+    parts.push(format!("layout: {}", layout.mode.name()));
+    if !selection.0.is_empty() {
+        parts.push(format!("{} selected", selection.0.len()));
+    }
+    if edit.active {
+        parts.push("EDIT MODE".to_string());
+    }
+    if let Some(position) = timeline.position {
+        parts.push(format!(
+            "VIEWING PAST: event {position} of {}",
+            graph_state.event_log().len()
+        ));
+    }
+    if let Some(message) = message.current(&time) {
+        parts.push(message.to_string());
+    }
+
+    let status = parts.join("  |  ");
+    if text.0 != status {
+        text.0 = status;
+    }
+}
+
+/// File name of a path, the whole location for URLs, or "stdin"
+fn source_name(location: Option<&str>) -> String {
+    match location {
+        None => "stdin".to_string(),
+        Some(location) if input::is_url(location) => location.to_string(),
+        Some(location) => Path::new(location).file_name().map_or_else(
+            || location.to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_name() {
+        assert_eq!(source_name(None), "stdin");
+        assert_eq!(source_name(Some("examples/org/graph.dot")), "graph.dot");
+        assert_eq!(
+            source_name(Some("https://example.com/graph.dot")),
+            "https://example.com/graph.dot"
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use crate::accessibility::Accessibility;
+use crate::keybindings::{Action, KeyBindings};
+use crate::settings::settings_panel_ui;
+use crate::types::{SearchState, ThemedPanel, ThemedText};
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use std::str::FromStr;
+
+/// Color scheme for the overlays, labels and reference grid
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const fn is_dark(self) -> bool {
+        matches!(self, Self::Dark)
+    }
+
+    #[must_use]
+    pub const fn toggled(self) -> Self {
+        match self {
+            Self::Dark => Self::Light,
+            Self::Light => Self::Dark,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Dark => "dark",
+            Self::Light => "light",
+        }
+    }
+
+    /// Window background used unless one is given with `--background`
+    pub const fn background(self) -> Color {
+        match self {
+            Self::Dark => Color::srgb(0.08, 0.08, 0.1),
+            Self::Light => Color::srgb(0.95, 0.95, 0.95),
+        }
+    }
+
+    pub const fn text(self) -> Color {
+        match self {
+            Self::Dark => Color::WHITE,
+            Self::Light => Color::srgb(0.1, 0.1, 0.12),
+        }
+    }
+
+    /// Background of the overlays behind text
+    pub const fn panel(self) -> Color {
+        match self {
+            Self::Dark => Color::srgba(0.05, 0.05, 0.1, 0.85),
+            Self::Light => Color::srgba(1.0, 1.0, 1.0, 0.9),
+        }
+    }
+
+    /// Lines of the reference grid
+    pub const fn grid(self) -> Color {
+        match self {
+            Self::Dark => Color::srgba(0.6, 0.6, 0.65, 0.35),
+            Self::Light => Color::srgba(0.2, 0.2, 0.25, 0.35),
+        }
+    }
+
+    /// Color of 3D node labels, before distance fading
+    pub const fn label(self) -> Color {
+        self.text()
+    }
+
+    /// Tint that makes pinned labels stand out
+    pub const fn pinned_label(self) -> Color {
+        match self {
+            Self::Dark => Color::srgb(1.0, 0.9, 0.5),
+            Self::Light => Color::srgb(0.7, 0.4, 0.0),
+        }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dark" => Ok(Self::Dark),
+            "light" => Ok(Self::Light),
+            _ => Err(format!("unknown theme '{s}' (expected 'dark' or 'light')")),
+        }
+    }
+}
+
+/// Parses a `--background` value: a hex color such as `#1e1e2e`, or `transparent`
+pub fn parse_background(s: &str) -> Result<Color, String> {
+    if s.eq_ignore_ascii_case("transparent") {
+        return Ok(Color::NONE);
+    }
+    Srgba::hex(s)
+        .map(Color::from)
+        .map_err(|e| format!("invalid color '{s}': {e}"))
+}
+
+/// Switching the theme and recoloring the overlays and egui windows for it
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, toggle_theme)
+            .add_systems(
+                Update,
+                apply_theme.run_if(resource_changed::<Theme>.or(resource_changed::<Accessibility>)),
+            )
+            .add_systems(EguiContextPass, sync_egui_theme.before(settings_panel_ui));
+    }
+}
+
+pub fn toggle_theme(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    mut theme: ResMut<Theme>,
+) {
+    if !search_state.active && bindings.just_pressed(Action::ToggleTheme, &keyboard_input) {
+        *theme = theme.toggled();
+    }
+}
+
+/// Recolors the overlays for the current theme and contrast
+///
+/// The window background only follows the theme once it is switched at
+/// runtime, so a `--background` given at startup is kept.
+pub fn apply_theme(
+    theme: Res<Theme>,
+    accessibility: Res<Accessibility>,
+    mut clear_color: ResMut<ClearColor>,
+    mut text_query: Query<&mut TextColor, With<ThemedText>>,
+    mut panel_query: Query<&mut BackgroundColor, With<ThemedPanel>>,
+) {
+    if theme.is_changed() && !theme.is_added() {
+        clear_color.0 = theme.background();
+    }
+    for mut color in &mut text_query {
+        color.0 = accessibility.text(*theme);
+    }
+    for mut color in &mut panel_query {
+        color.0 = accessibility.panel(*theme);
+    }
+}
+
+/// Keeps egui windows in step with the theme, with pure black or white
+/// text in high contrast
+pub fn sync_egui_theme(
+    mut contexts: EguiContexts,
+    theme: Res<Theme>,
+    accessibility: Res<Accessibility>,
+) {
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+    let text_color = accessibility.high_contrast.then(|| {
+        let [r, g, b, _] = accessibility.text(*theme).to_srgba().to_u8_array();
+        egui::Color32::from_rgb(r, g, b)
+    });
+    let visuals = ctx.style().visuals.clone();
+    if visuals.dark_mode != theme.is_dark() || visuals.override_text_color != text_color {
+        let mut visuals = if theme.is_dark() {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        };
+        visuals.override_text_color = text_color;
+        ctx.set_visuals(visuals);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_background() {
+        assert_eq!(parse_background("transparent"), Ok(Color::NONE));
+        assert_eq!(
+            parse_background("#ff0000"),
+            Ok(Color::from(Srgba::rgb(1.0, 0.0, 0.0)))
+        );
+        assert_eq!(parse_background("fff"), Ok(Color::from(Srgba::WHITE)));
+        assert!(parse_background("not-a-color").is_err());
+    }
+
+    #[test]
+    fn test_theme_from_str() {
+        assert_eq!("Light".parse::<Theme>(), Ok(Theme::Light));
+        assert_eq!(Theme::Dark.toggled(), Theme::Light);
+        assert!("sepia".parse::<Theme>().is_err());
+    }
+}
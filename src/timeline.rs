@@ -0,0 +1,139 @@
+use crate::graph_state::{GraphState, LoggedEvent};
+use crate::keybindings::{Action, KeyBindings};
+use crate::settings::settings_panel_ui;
+use crate::types::{SearchState, Timeline, TimelinePanel};
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use std::time::SystemTime;
+
+/// How many events leading up to the viewed point are listed
+const LISTED_EVENTS: usize = 8;
+
+/// Scrubbing through the event log to see the graph as it was
+pub struct TimelinePlugin;
+
+impl Plugin for TimelinePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Timeline>()
+            .init_resource::<TimelinePanel>()
+            .add_systems(Update, toggle_timeline)
+            .add_systems(EguiContextPass, timeline_panel_ui.after(settings_panel_ui));
+    }
+}
+
+/// Opens the timeline, or closes it and returns to the live graph
+pub fn toggle_timeline(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    search_state: Res<SearchState>,
+    mut panel: ResMut<TimelinePanel>,
+    mut timeline: ResMut<Timeline>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::ToggleTimeline, &keyboard_input) {
+        return;
+    }
+    panel.open = !panel.open;
+    if !panel.open && timeline.position.is_some() {
+        timeline.position = None;
+    }
+}
+
+/// Seconds between two log entries, for showing when events happened
+fn seconds_between(first: &LoggedEvent, entry: &LoggedEvent) -> f32 {
+    entry
+        .at
+        .duration_since(first.at)
+        .unwrap_or_default()
+        .as_secs_f32()
+}
+
+/// Slider and step buttons over the event log, with the events leading up
+/// to the viewed point
+pub fn timeline_panel_ui(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<TimelinePanel>,
+    mut timeline: ResMut<Timeline>,
+    graph_state: Res<GraphState>,
+) {
+    if !panel.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let log = graph_state.event_log();
+    // The log may have been trimmed or replaced by a reload
+    let mut position = timeline.position.filter(|&n| n < log.len());
+    let mut shown = position.unwrap_or(log.len());
+    let mut open = true;
+
+    egui::Window::new("Timeline")
+        .open(&mut open)
+        .default_pos([300.0, 600.0])
+        .default_width(480.0)
+        .show(ctx, |ui| {
+            if log.is_empty() {
+                ui.label("No events yet");
+                return;
+            }
+            ui.horizontal(|ui| {
+                if ui.button("⏮").on_hover_text("Empty graph").clicked() {
+                    shown = 0;
+                }
+                if ui.button("◀").on_hover_text("Step back").clicked() {
+                    shown = shown.saturating_sub(1);
+                }
+                if ui.button("▶").on_hover_text("Step forward").clicked() {
+                    shown = (shown + 1).min(log.len());
+                }
+                if ui.button("⏭").on_hover_text("Back to live").clicked() {
+                    shown = log.len();
+                }
+                ui.add(egui::Slider::new(&mut shown, 0..=log.len()).text("events"));
+            });
+
+            let first = &log[0];
+            let when = shown
+                .checked_sub(1)
+                .map_or(0.0, |last| seconds_between(first, &log[last]));
+            let age = SystemTime::now()
+                .duration_since(log[shown.saturating_sub(1)].at)
+                .unwrap_or_default()
+                .as_secs_f32();
+            ui.label(if shown == log.len() {
+                format!("Live: {} events", log.len())
+            } else {
+                format!(
+                    "Event {shown} of {}, +{when:.1}s ({age:.0}s ago)",
+                    log.len()
+                )
+            });
+
+            ui.separator();
+            for (i, entry) in log
+                .iter()
+                .enumerate()
+                .take(shown)
+                .skip(shown.saturating_sub(LISTED_EVENTS))
+            {
+                ui.monospace(format!(
+                    "{:>6} +{:>7.1}s  {}",
+                    i + 1,
+                    seconds_between(first, entry),
+                    entry.event
+                ));
+            }
+        });
+
+    // The end of the log is the live graph
+    position = (shown < log.len()).then_some(shown);
+    if !open {
+        panel.open = false;
+        position = None;
+    }
+    // Only touch the timeline when it moves, since that rebuilds the scene
+    if position != timeline.position {
+        timeline.position = position;
+    }
+}
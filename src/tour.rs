@@ -0,0 +1,193 @@
+use crate::alerts::AlertFilter;
+use crate::captions::ShowCaption;
+use crate::keybindings::{Action, KeyBindings};
+use crate::status::StatusMessage;
+use crate::types::{GraphNode, SearchState, SelectedNodes, ViewSettings};
+use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// One stop of a tour; whatever a step leaves out stays as it was
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TourStep {
+    /// Id of the node the camera moves to and orbits
+    pub focus: Option<String>,
+    /// How far the camera settles from the focus
+    pub distance: Option<f32>,
+    /// Text shown beside the focus, or at the bottom of the screen
+    pub caption: Option<String>,
+    /// Ids of the nodes to select, for their outlines and paths
+    pub select: Option<Vec<String>>,
+    /// Node types to leave out of the scene
+    pub hide_types: Option<Vec<String>>,
+    /// Whether only alerting nodes and their neighbors are shown
+    pub alerts: Option<bool>,
+}
+
+/// A guided walkthrough of the graph, stepped through with Page Down and
+/// Page Up, read from a TOML file of `[[step]]` tables:
+///
+/// ```toml
+/// [[step]]
+/// focus = "gateway"
+/// distance = 12
+/// caption = "Every request comes in through the gateway"
+///
+/// [[step]]
+/// focus = "orders-db"
+/// select = ["orders", "orders-db"]
+/// hide_types = ["cache"]
+/// ```
+#[derive(Resource, Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tour {
+    #[serde(rename = "step", default)]
+    pub steps: Vec<TourStep>,
+    /// The step on screen; none until the tour starts
+    #[serde(skip)]
+    pub current: Option<usize>,
+}
+
+impl Tour {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let tour: Self = toml::from_str(content).map_err(|e| e.message().to_string())?;
+        if tour.steps.is_empty() {
+            return Err("the tour has no [[step]]s".to_string());
+        }
+        Ok(tour)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&content).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Moves one step on, or back, staying within the tour; returns the new
+    /// step if it moved
+    pub fn advance(&mut self, forward: bool) -> Option<&TourStep> {
+        let next = match (self.current, forward) {
+            (None, _) => 0,
+            (Some(step), true) if step + 1 < self.steps.len() => step + 1,
+            (Some(step), false) if step > 0 => step - 1,
+            _ => return None,
+        };
+        self.current = Some(next);
+        self.steps.get(next)
+    }
+}
+
+/// Steps through a tour loaded with `--tour`, if there is one
+pub struct TourPlugin;
+
+impl Plugin for TourPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, step_through_tour.run_if(resource_exists::<Tour>));
+    }
+}
+
+/// Moves to the next or previous step and sets the scene up as it says,
+/// with the camera easing over to its focus
+#[allow(clippy::too_many_arguments)]
+pub fn step_through_tour(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    time: Res<Time>,
+    node_query: Query<(&GraphNode, &GlobalTransform)>,
+    mut tour: ResMut<Tour>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+    mut captions: EventWriter<ShowCaption>,
+    mut view: ResMut<ViewSettings>,
+    mut selection: ResMut<SelectedNodes>,
+    mut alerts: ResMut<AlertFilter>,
+    mut message: ResMut<StatusMessage>,
+) {
+    if search_state.active {
+        return;
+    }
+    let forward = if bindings.just_pressed(Action::NextTourStep, &keyboard_input) {
+        true
+    } else if bindings.just_pressed(Action::PreviousTourStep, &keyboard_input) {
+        false
+    } else {
+        return;
+    };
+    let count = tour.steps.len();
+    let Some(step) = tour.advance(forward).cloned() else {
+        return;
+    };
+    let number = tour.current.map_or(0, |current| current + 1);
+
+    let mut anchor_node = None;
+    if let Some(id) = &step.focus {
+        match node_query.iter().find(|(node, _)| &node.id == id) {
+            Some((_, transform)) => {
+                anchor_node = Some(id.clone());
+                for mut cam in &mut cameras {
+                    cam.target_focus = transform.translation();
+                    if let Some(distance) = step.distance {
+                        cam.target_radius = distance.max(1.0);
+                    }
+                }
+            }
+            None => message.show(format!("Tour step {number}: no node '{id}'"), &time),
+        }
+    }
+    if let Some(select) = step.select {
+        selection.0 = select.into_iter().collect();
+    }
+    if let Some(hide_types) = step.hide_types {
+        let hidden = hide_types.into_iter().collect();
+        if view.hidden_types != hidden {
+            view.hidden_types = hidden;
+        }
+    }
+    if let Some(active) = step.alerts.filter(|&active| active != alerts.active) {
+        alerts.active = active;
+    }
+    captions.write(ShowCaption {
+        text: format!("{number}/{count}  {}", step.caption.unwrap_or_default()),
+        duration: None,
+        anchor_node,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tours_step_within_bounds() {
+        let mut tour = Tour::parse(
+            r#"
+            [[step]]
+            focus = "gateway"
+            distance = 12
+            caption = "Requests come in here"
+
+            [[step]]
+            select = ["orders", "orders-db"]
+            hide_types = ["cache"]
+            alerts = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(tour.steps.len(), 2);
+        assert_eq!(tour.steps[0].focus.as_deref(), Some("gateway"));
+        assert_eq!(tour.steps[1].alerts, Some(true));
+
+        // Going back before the start begins the tour, like going forward
+        assert_eq!(tour.advance(false).unwrap().distance, Some(12.0));
+        assert!(tour.advance(false).is_none());
+        assert!(tour.advance(true).unwrap().select.is_some());
+        assert!(tour.advance(true).is_none());
+        assert_eq!(tour.current, Some(1));
+
+        assert!(Tour::parse("").is_err());
+        let err = Tour::parse("[[step]]\nzoom = 2").unwrap_err();
+        assert!(err.contains("zoom"), "{err}");
+    }
+}
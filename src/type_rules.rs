@@ -0,0 +1,172 @@
+use crate::events::EventNodeInfo;
+use regex::Regex;
+use std::str::FromStr;
+
+/// Node attribute a rule looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Id,
+    Name,
+    Type,
+    Level,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Equals(String),
+    Matches(Regex),
+}
+
+/// Gives the nodes it matches a type, and with it their color and shape,
+/// e.g. `name ~ ".*-db$" -> database`
+///
+/// `=` compares the whole value, `~` searches it with a regular expression.
+/// The fields are `id`, `name`, `type` and `level`.
+#[derive(Debug, Clone)]
+pub struct TypeRule {
+    field: Field,
+    pattern: Pattern,
+    node_type: String,
+}
+
+impl TypeRule {
+    fn matches(&self, id: &str, info: &EventNodeInfo) -> bool {
+        let level;
+        let value = match self.field {
+            Field::Id => id,
+            Field::Name => &info.name,
+            Field::Type => info.node_type.as_deref().unwrap_or(""),
+            Field::Level => {
+                level = info.level.to_string();
+                &level
+            }
+        };
+        match &self.pattern {
+            Pattern::Equals(expected) => value == expected,
+            Pattern::Matches(regex) => regex.is_match(value),
+        }
+    }
+}
+
+impl FromStr for TypeRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("invalid type rule '{s}' (expected e.g. 'name ~ \"-db$\" -> database')");
+        let (condition, node_type) = s.rsplit_once("->").ok_or_else(invalid)?;
+        let split = condition.find(['=', '~']).ok_or_else(invalid)?;
+        let field = match condition[..split].trim() {
+            "id" => Field::Id,
+            "name" => Field::Name,
+            "type" => Field::Type,
+            "level" => Field::Level,
+            other => {
+                return Err(format!(
+                    "unknown field '{other}' in type rule '{s}' (expected id, name, type or level)"
+                ));
+            }
+        };
+        let value = unquote(&condition[split + 1..]);
+        let pattern = if condition[split..].starts_with('~') {
+            Pattern::Matches(
+                Regex::new(value).map_err(|e| format!("invalid regex in type rule '{s}': {e}"))?,
+            )
+        } else {
+            Pattern::Equals(value.to_string())
+        };
+        let node_type = unquote(node_type);
+        if node_type.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            field,
+            pattern,
+            node_type: node_type.to_string(),
+        })
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    let s = s.trim();
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .or_else(|| s.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')))
+        .unwrap_or(s)
+}
+
+/// Type rules from the config, applied to nodes as they are added or updated
+///
+/// Later rules win over earlier ones, so a project's rules override the
+/// user's; nodes no rule matches keep the type their source gave them.
+#[derive(Debug, Clone, Default)]
+pub struct TypeRules(pub Vec<TypeRule>);
+
+impl TypeRules {
+    /// The node with the type of the last rule that matches it
+    pub fn apply(&self, id: &str, mut info: EventNodeInfo) -> EventNodeInfo {
+        if let Some(rule) = self.0.iter().rev().find(|rule| rule.matches(id, &info)) {
+            info.node_type = Some(rule.node_type.clone());
+        }
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, node_type: Option<&str>) -> EventNodeInfo {
+        EventNodeInfo {
+            name: name.to_string(),
+            node_type: node_type.map(str::to_string),
+            level: 2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rules_retype_matching_nodes() {
+        let rules = TypeRules(
+            [
+                r#"name ~ ".*-db$" -> database"#,
+                "type = svc -> process",
+                "level = 2 -> 'team'",
+                r#"id ~ "^ext\." -> external"#,
+            ]
+            .into_iter()
+            .map(|rule| rule.parse().unwrap())
+            .collect(),
+        );
+        let type_of = |id, info| rules.apply(id, info).node_type;
+
+        assert_eq!(
+            type_of("ext.pay", node("payments", None)).as_deref(),
+            Some("external")
+        );
+        // The last matching rule wins
+        assert_eq!(
+            type_of("o", node("orders-db", None)).as_deref(),
+            Some("team")
+        );
+        let level_one = EventNodeInfo {
+            level: 1,
+            ..node("orders-db", None)
+        };
+        assert_eq!(type_of("o", level_one).as_deref(), Some("database"));
+        let service = EventNodeInfo {
+            level: 0,
+            ..node("api", Some("svc"))
+        };
+        assert_eq!(type_of("api", service).as_deref(), Some("process"));
+        let unmatched = EventNodeInfo {
+            level: 0,
+            ..node("web", Some("site"))
+        };
+        assert_eq!(type_of("web", unmatched).as_deref(), Some("site"));
+
+        assert!("name ~ db".parse::<TypeRule>().is_err());
+        assert!("colour = red -> site".parse::<TypeRule>().is_err());
+        assert!("name ~ ( -> site".parse::<TypeRule>().is_err());
+    }
+}
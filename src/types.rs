@@ -1,6 +1,10 @@
-use crate::graph_state::GraphData as StateGraphData;
+use crate::graph_state::{EdgeInfo, GraphData as StateGraphData, NodeInfo};
+use crate::palette::Palette;
+use crate::search::MatchOptions;
 use bevy::prelude::*;
 use petgraph::graph::NodeIndex;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 
 // Re-export types from dotparser for use in other modules
 // NodeType is no longer needed - it's now just Option<String>
@@ -24,32 +28,98 @@ impl std::ops::DerefMut for GraphData {
     }
 }
 
-#[derive(Resource)]
-pub struct DotContent(pub String);
-
 #[derive(Component)]
 pub struct GraphNode {
+    /// Node id from the source, stable across reloads
+    pub id: String,
     pub name: String,
     pub index: NodeIndex,
 }
 
-#[derive(Component)]
+/// An edge in the scene, with what the graph knows about it
+///
+/// Library users can query this to read edge metadata without going back to
+/// `GraphData`.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
 pub struct GraphEdge {
     pub from: NodeIndex,
     pub to: NodeIndex,
-    #[allow(dead_code)] // Will be used for edge labels
     pub label: Option<String>,
-    #[allow(dead_code)] // Used for edge appearance
     pub edge_type: Option<String>,
-    #[allow(dead_code)] // Will be used for temporal visualization
+    /// Position in a sequence diagram's message order
     pub sequence: Option<u32>,
+    /// Live calls per second
+    pub rate: Option<f32>,
+    /// Share of live calls that fail, from 0 to 1
+    pub error_rate: Option<f32>,
+    /// Port of the source node the edge leaves from
+    pub from_port: Option<String>,
+    /// Port of the target node the edge arrives at
+    pub to_port: Option<String>,
 }
 
-#[derive(Component)]
-pub struct EdgeArrowHead {
-    pub edge: Entity,
+impl GraphEdge {
+    pub fn new(from: NodeIndex, to: NodeIndex, info: Option<&EdgeInfo>) -> Self {
+        let Some(info) = info else {
+            return Self {
+                from,
+                to,
+                ..default()
+            };
+        };
+        Self {
+            from,
+            to,
+            label: info.label.clone(),
+            edge_type: info.edge_type.clone(),
+            sequence: info.sequence,
+            rate: info.rate,
+            error_rate: info.error_rate,
+            from_port: info.from_port.clone(),
+            to_port: info.to_port.clone(),
+        }
+    }
 }
 
+/// Where an edge between ports of record nodes attaches to each end,
+/// relative to the node's center and before its scale
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct PortOffsets {
+    pub from: Vec3,
+    pub to: Vec3,
+}
+
+/// The node an edge starts at; despawning the node despawns the edge
+#[derive(Component)]
+#[relationship(relationship_target = OutgoingEdges)]
+pub struct EdgeFrom(pub Entity);
+
+/// The node an edge ends at; despawning the node despawns the edge
+#[derive(Component)]
+#[relationship(relationship_target = IncomingEdges)]
+pub struct EdgeTo(pub Entity);
+
+/// Edges that start at a node
+#[derive(Component)]
+#[relationship_target(relationship = EdgeFrom, linked_spawn)]
+pub struct OutgoingEdges(Vec<Entity>);
+
+/// Edges that end at a node
+#[derive(Component)]
+#[relationship_target(relationship = EdgeTo, linked_spawn)]
+pub struct IncomingEdges(Vec<Entity>);
+
+/// The cylinder drawn for an edge, a child of its `GraphEdge`
+///
+/// Edges sit at their source node, pointing at the target, so the shaft
+/// and arrow head are placed along the edge's local Y axis.
+#[derive(Component)]
+pub struct EdgeShaft;
+
+/// The cone at the target end of an edge, a child of its `GraphEdge`
+#[derive(Component)]
+pub struct EdgeArrowHead;
+
 #[derive(Component)]
 pub struct NodeLabel {
     pub node_entity: Entity,
@@ -61,29 +131,291 @@ pub struct LabelVisibilityIndicator;
 #[derive(Component)]
 pub struct SearchBox;
 
+#[derive(Component)]
+pub struct ErrorPanel;
+
+#[derive(Component)]
+pub struct HoverTooltip;
+
+/// Text box shown next to a node while it is being renamed
+#[derive(Component)]
+pub struct RenameBox;
+
+#[derive(Component)]
+pub struct HelpOverlay;
+
+/// Panel listing the node types in the graph
+#[derive(Component)]
+pub struct Legend;
+
+/// Overlay of frame rate, entity counts and event processing time
+#[derive(Component)]
+pub struct DiagnosticsOverlay;
+
+/// Panel showing how far a big scene has got building in
+#[derive(Component)]
+pub struct BuildProgress;
+
+/// Line of the build progress panel saying how many entities are spawned
+#[derive(Component)]
+pub struct BuildProgressText;
+
+/// Filled part of the build progress bar
+#[derive(Component)]
+pub struct BuildProgressFill;
+
+/// Column along the bottom of the screen holding captions that point at
+/// no node
+#[derive(Component)]
+pub struct CaptionBar;
+
+/// A caption on screen, beside the node it points at or in the caption bar
+#[derive(Component, Debug, Clone)]
+pub struct Callout {
+    pub anchor_node: Option<String>,
+    /// App time the caption goes away at; sticky captions have none
+    pub until: Option<f32>,
+}
+
+#[derive(Component)]
+pub struct StatusBar;
+
+/// Glowing shell around a node, shown while the node is highlighted
+#[derive(Component)]
+pub struct NodeOutline;
+
+/// Shell drawn where something hides its node, while the node is selected
+/// or matched
+#[derive(Component)]
+pub struct XrayGhost;
+
+/// Light belonging to the current lighting preset
+#[derive(Component)]
+pub struct SceneLight;
+
+/// Text whose color follows the theme
+#[derive(Component)]
+pub struct ThemedText;
+
+/// Overlay whose background follows the theme
+#[derive(Component)]
+pub struct ThemedPanel;
+
+/// Node ids whose labels stay visible regardless of distance
+#[derive(Resource, Default)]
+pub struct PinnedLabels(pub HashSet<String>);
+
+/// Node ids picked by clicking; Shift+click adds and removes nodes
+#[derive(Resource, Default)]
+pub struct SelectedNodes(pub HashSet<String>);
+
+/// Nodes and edges on the shortest paths between the selected nodes
+///
+/// Empty unless at least two nodes are selected, in which case everything
+/// else is dimmed.
+#[derive(Resource, Default)]
+pub struct PathTrace {
+    pub nodes: HashSet<NodeIndex>,
+    /// Edges as (source, target) in graph direction
+    pub edges: HashSet<(NodeIndex, NodeIndex)>,
+}
+
+impl PathTrace {
+    pub fn is_active(&self) -> bool {
+        !self.nodes.is_empty()
+    }
+}
+
+/// Material an entity had before it was dimmed by a path trace or edge
+/// decluttering, and the opacity it was dimmed to
+#[derive(Component)]
+pub struct Dimmed(pub Handle<StandardMaterial>, pub f32);
+
+/// Opacity of the edges touching none of the hovered, selected or
+/// search-matched nodes, so theirs stand out of a hairball
+///
+/// 1 leaves every edge opaque; nothing fades while no node is in focus.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct EdgeDeclutter {
+    pub opacity: f32,
+}
+
+impl Default for EdgeDeclutter {
+    fn default() -> Self {
+        Self { opacity: 1.0 }
+    }
+}
+
+/// Whether motion that only draws the eye is left out, for people it
+/// distracts or unsettles
+///
+/// Changed nodes still ease to their new look and metric nodes still take
+/// their color, but nothing pulses, blinks or streams along edges.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReducedMotion {
+    pub enabled: bool,
+}
+
+/// Cycles found by strongly connected component analysis
+#[derive(Resource, Default)]
+pub struct CycleAnalysis {
+    pub active: bool,
+    /// Components with a cycle, largest first
+    pub components: Vec<Vec<NodeIndex>>,
+    /// Which component each node belongs to
+    pub membership: HashMap<NodeIndex, usize>,
+}
+
+/// Nodes reachable from, and leading to, a chosen node
+#[derive(Resource, Default)]
+pub struct Reachability {
+    /// Id of the node the shading starts from; None when off
+    pub source: Option<String>,
+    /// Hops from the source to each node downstream of it
+    pub downstream: HashMap<NodeIndex, usize>,
+    /// Hops from each node upstream of the source to it
+    pub upstream: HashMap<NodeIndex, usize>,
+}
+
+/// Free-text notes attached to nodes, keyed by node id
+#[derive(Resource, Default)]
+pub struct NodeNotes(pub HashMap<String, String>);
+
+/// Small marker shown next to nodes that have a note
+#[derive(Component)]
+pub struct NoteMarker;
+
+/// The node under the cursor and how long it has been there
+#[derive(Resource, Default)]
+pub struct HoverState {
+    pub node: Option<Entity>,
+    pub elapsed: f32,
+}
+
+/// Runtime graph editing, toggled with E
+#[derive(Resource, Default)]
+pub struct EditState {
+    pub active: bool,
+    /// Id of the node an edge is being dragged from
+    pub drag_from: Option<String>,
+    /// Id of the node being renamed
+    pub renaming: Option<String>,
+    /// Name typed so far while renaming
+    pub name: String,
+}
+
 #[derive(Resource, Default)]
 pub struct SearchState {
     pub active: bool,
     pub query: String,
     pub matching_nodes: Vec<Entity>,
+    /// Edges matched by an `e:` search
+    pub matching_edges: Vec<Entity>,
     pub selected_node: Option<Entity>,
+    /// Modifiers toggled in the search box, kept from one search to the next
+    pub options: MatchOptions,
 }
 
+/// The small modifier indicators after the search box's query
+#[derive(Component)]
+pub struct SearchModifiers;
+
 #[derive(Component)]
 pub struct NodeHighlight {
     pub fade_timer: f32,
 }
 
-#[derive(Resource)]
+/// Glow of a search-matched edge, fading like a node's
+#[derive(Component)]
+pub struct EdgeHighlight {
+    pub fade_timer: f32,
+}
+
+/// Material a highlighted node or edge part had before its highlight copy
+/// was swapped in
+#[derive(Component)]
+pub struct OriginalMaterial(pub Handle<StandardMaterial>);
+
+#[derive(Resource, Debug, Clone, PartialEq)]
 pub struct CameraSettings {
     pub distance: f32,
     pub speed: f32,
+    pub pan_sensitivity: f32,
+    pub orbit_sensitivity: f32,
+    pub zoom_sensitivity: f32,
+    /// How much of its last movement the camera keeps each frame, from 0
+    /// (none, so it snaps) to just under 1
+    pub smoothness: f32,
+    /// How far the camera orbits over or under the graph, in radians
+    pub pitch_limit: f32,
+    /// Seconds a drag's motion takes to die down after the mouse is
+    /// released; 0 stops at once
+    pub inertia: f32,
 }
 
-#[derive(Resource)]
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            distance: 25.0,
+            speed: 5.0,
+            pan_sensitivity: 1.0,
+            orbit_sensitivity: 1.0,
+            zoom_sensitivity: 0.5,
+            smoothness: 0.8,
+            pitch_limit: 80f32.to_radians(),
+            inertia: 0.3,
+        }
+    }
+}
+
+/// A crowd of distant nodes drawn as one badge
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenCluster {
+    /// Where on the screen the badge goes, in logical pixels
+    pub position: Vec2,
+    pub count: usize,
+}
+
+/// Nodes folded into badges because they crowd one spot of the screen
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct ScreenClusters {
+    pub enabled: bool,
+    /// Nodes hidden behind a badge
+    pub hidden: HashSet<NodeIndex>,
+    pub badges: Vec<ScreenCluster>,
+}
+
+impl Default for ScreenClusters {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hidden: HashSet::new(),
+            badges: Vec::new(),
+        }
+    }
+}
+
+/// Count shown over a crowd of distant nodes
+#[derive(Component)]
+pub struct ClusterBadge;
+
+#[derive(Resource, Clone, PartialEq)]
 pub struct LabelSettings {
     pub visibility_distance: f32,
     pub show_all_labels: bool,
+    /// Characters a label line may take before it's cut short or wrapped;
+    /// 0 for no limit
+    pub max_width: usize,
+    /// Wrap long lines at spaces instead of cutting them short
+    pub wrap: bool,
+    /// Draw labels from a texture atlas instead of as text once the scene
+    /// has more nodes than this; 0 never does
+    pub atlas_above: usize,
+}
+
+impl LabelSettings {
+    pub const DEFAULT_MAX_WIDTH: usize = 32;
+    pub const DEFAULT_ATLAS_ABOVE: usize = 2_000;
 }
 
 impl Default for LabelSettings {
@@ -91,6 +423,312 @@ impl Default for LabelSettings {
         Self {
             visibility_distance: 10.0, // Reduced from 15.0 for more noticeable toggle effect
             show_all_labels: false,
+            max_width: Self::DEFAULT_MAX_WIDTH,
+            wrap: false,
+            atlas_above: Self::DEFAULT_ATLAS_ABOVE,
+        }
+    }
+}
+
+/// How node positions are computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LayoutMode {
+    /// Nodes on rings, one ring per level, stacked vertically
+    #[default]
+    Hierarchical,
+    /// Nodes along the X axis in dependency order; only for acyclic graphs
+    Topological,
+}
+
+impl LayoutMode {
+    pub const ALL: [Self; 2] = [Self::Hierarchical, Self::Topological];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Hierarchical => "hierarchical",
+            Self::Topological => "topological",
+        }
+    }
+}
+
+impl FromStr for LayoutMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown layout '{s}' (expected hierarchical or topological)"))
+    }
+}
+
+/// Where the hierarchical layout gets node levels from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LevelSource {
+    /// The `level` attribute, or depth in the graph if no node has one
+    #[default]
+    Auto,
+    /// The `level` attribute, 0 where it's missing
+    Attr,
+    /// Every node on level 0
+    None,
+}
+
+impl LevelSource {
+    pub const ALL: [Self; 3] = [Self::Auto, Self::Attr, Self::None];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Attr => "attr",
+            Self::None => "none",
+        }
+    }
+}
+
+impl FromStr for LevelSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|source| source.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown level source '{s}' (expected auto, attr or none)"))
+    }
+}
+
+/// Node attribute that `--group-by` splits the layout into lanes by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Type,
+    Level,
+    State,
+}
+
+impl GroupBy {
+    pub const ALL: [Self; 3] = [Self::Type, Self::Level, Self::State];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Type => "type",
+            Self::Level => "level",
+            Self::State => "state",
+        }
+    }
+
+    /// The lane a node goes in
+    /// The group a node belongs to, given the level it's laid out on
+    pub fn value_of(self, info: &NodeInfo, level: u32) -> String {
+        match self {
+            Self::Type => info
+                .node_type
+                .clone()
+                .unwrap_or_else(|| "untyped".to_string()),
+            Self::Level => format!("level {level}"),
+            Self::State => info
+                .state
+                .map_or_else(|| "no state".to_string(), |state| state.name().to_string()),
+        }
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|group_by| group_by.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown attribute '{s}' (expected type, level or state)"))
+    }
+}
+
+/// A translucent plane between two `--group-by` lanes, or a lane's label
+#[derive(Component)]
+pub struct GroupLane;
+
+/// Moves a node from where it was in the previous layout to its new position
+#[derive(Component)]
+pub struct LayoutTransition {
+    pub from: Vec3,
+    pub to: Vec3,
+    pub elapsed: f32,
+}
+
+/// Grows a node or edge that was just added into view
+#[derive(Component)]
+pub struct Appearing {
+    pub elapsed: f32,
+    /// Full size of a node; edges grow along their length instead
+    pub size: f32,
+}
+
+/// Shrinks a node or edge that was removed out of view, then despawns it
+///
+/// Vanishing entities have lost their graph components, so the rest of the
+/// app no longer sees them.
+#[derive(Component)]
+pub struct Vanishing {
+    pub elapsed: f32,
+    pub scale: Vec3,
+}
+
+/// Eases a node from its look before an update changed its type or level
+#[derive(Component, Clone)]
+pub struct NodeChange {
+    pub from_color: Color,
+    pub to_color: Color,
+    pub from_scale: f32,
+    pub to_scale: f32,
+    /// Material of the node's own, so the animation doesn't recolor its whole type
+    pub material: Handle<StandardMaterial>,
+    pub elapsed: f32,
+}
+
+/// Parameters of the hierarchical ring layout
+#[derive(Resource, Clone, PartialEq)]
+pub struct LayoutSettings {
+    pub mode: LayoutMode,
+    /// Lay out each group of nodes on its own, side by side
+    pub group_by: Option<GroupBy>,
+    pub levels: LevelSource,
+    /// Radius of the ring for level 0
+    pub base_radius: f32,
+    /// How much wider each level's ring is than the one below it
+    pub ring_spacing: f32,
+    /// Vertical distance between levels
+    pub level_height: f32,
+    /// Lay the graph out on the ground, for the top-down 2D view
+    pub flatten: bool,
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        Self {
+            mode: LayoutMode::default(),
+            group_by: None,
+            levels: LevelSource::default(),
+            base_radius: 5.0,
+            ring_spacing: 2.0,
+            level_height: 2.0,
+            flatten: false,
         }
     }
 }
+
+/// What is drawn and how, independent of the layout
+#[derive(Resource, Clone, PartialEq)]
+pub struct ViewSettings {
+    pub edge_color: Color,
+    /// Node types to leave out of the scene; untyped nodes use the empty string
+    pub hidden_types: HashSet<String>,
+    /// Draw all edges as one mesh of lines, which can't be hovered or faded
+    /// one by one, instead of an entity each
+    pub merge_edges: bool,
+    /// Colors node types are drawn in
+    pub palette: Palette,
+    /// Give every known node type a shape of its own, so types can be told
+    /// apart without their colors
+    pub distinct_shapes: bool,
+}
+
+impl Default for ViewSettings {
+    fn default() -> Self {
+        Self {
+            edge_color: Color::srgb(0.4, 0.4, 0.4),
+            hidden_types: HashSet::new(),
+            merge_edges: false,
+            palette: Palette::default(),
+            distinct_shapes: false,
+        }
+    }
+}
+
+/// How much of the graph around the selection is exported
+#[derive(Resource)]
+pub struct ExportSettings {
+    /// Neighbors up to this many hops from a selected node are included
+    pub radius: u32,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self { radius: 1 }
+    }
+}
+
+/// Reference gizmos drawn around the graph
+#[derive(Resource, Clone, PartialEq, Eq)]
+pub struct GridSettings {
+    pub show_grid: bool,
+    pub show_axes: bool,
+    /// Lets the camera orbit under the ground, hiding the grid while it's
+    /// there, rather than stopping at it
+    pub below_ground: bool,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            show_grid: true,
+            show_axes: false,
+            below_ground: false,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SettingsPanel {
+    pub open: bool,
+}
+
+/// The node ranking panel and what it ranks by
+#[derive(Resource)]
+pub struct RankingPanel {
+    pub open: bool,
+    pub metric: crate::metrics::Metric,
+    /// How many of the top nodes are listed
+    pub count: usize,
+    /// List the lowest-ranked nodes instead
+    pub ascending: bool,
+}
+
+impl Default for RankingPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            metric: crate::metrics::Metric::default(),
+            count: 10,
+            ascending: false,
+        }
+    }
+}
+
+/// Point in the event log the scene shows
+#[derive(Resource, Default)]
+pub struct Timeline {
+    /// Number of logged events the scene shows the graph after; None follows
+    /// the live graph
+    pub position: Option<usize>,
+}
+
+#[derive(Resource, Default)]
+pub struct TimelinePanel {
+    pub open: bool,
+}
+
+/// Node shown in the inspector panel, if it is open
+#[derive(Resource, Default)]
+pub struct InspectorPanel {
+    pub node: Option<String>,
+}
+
+/// The scripting console: the script being edited, what runs printed, and a
+/// script waiting to run
+#[derive(Resource, Default)]
+pub struct ScriptConsole {
+    pub open: bool,
+    pub input: String,
+    pub output: Vec<String>,
+    pub pending: Option<String>,
+}
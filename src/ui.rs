@@ -1,10 +1,42 @@
-use crate::types::{GraphNode, LabelSettings, LabelVisibilityIndicator, NodeLabel, SearchState};
+use crate::accessibility::Accessibility;
+use crate::keybindings::{Action, KeyBindings};
+use crate::label_atlas::LabelBackend;
+use crate::loader::{InputSource, LoadError};
+use crate::sidecar::{SidecarFile, save_pinned_labels};
+use crate::theme::Theme;
+use crate::types::{
+    ErrorPanel, GraphNode, HelpOverlay, HoverState, LabelSettings, LabelVisibilityIndicator,
+    NodeLabel, PinnedLabels, SearchState, SelectedNodes, ThemedPanel, ThemedText,
+};
 use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use bevy_rich_text3d::{Text3d, Text3dStyling, TextAnchor, TextAtlas};
+use std::num::NonZeroU32;
+
+/// World-space node labels: creating and restyling them, the keys that show
+/// and pin them, and saving the pins
+pub struct NodeLabelsPlugin;
+
+impl Plugin for NodeLabelsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (toggle_label_pins, toggle_label_visibility))
+            .add_systems(Update, (create_node_labels, update_label_text).chain())
+            .add_systems(Update, update_node_label_positions)
+            .add_systems(
+                Update,
+                save_pinned_labels.run_if(
+                    resource_exists::<SidecarFile>
+                        .and(resource_changed::<PinnedLabels>)
+                        .and(not(resource_added::<PinnedLabels>)),
+                ),
+            );
+    }
+}
 
 pub fn setup_ui(commands: &mut Commands) {
-    // Add control instructions
+    // Point at the help overlay; the full key reference lives there
     commands.spawn((
-        Text::new("Controls:\nArrows: Pan camera\nShift+Arrows: Orbit view\n+/- or Scroll: Zoom\nLeft drag: Orbit\nRight drag: Pan\nL: Show all labels\n/: Search nodes\nESC: Close search\nQ: Exit"),
+        Text::new("Press ? for help"),
         TextFont {
             font_size: 16.0,
             ..default()
@@ -16,8 +48,36 @@ pub fn setup_ui(commands: &mut Commands) {
             left: Val::Px(10.0),
             ..default()
         },
+        ThemedText,
     ));
 
+    // Full-screen key reference, filled in from the key bindings
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.85)),
+            GlobalZIndex(10),
+            Visibility::Hidden,
+            HelpOverlay,
+            ThemedPanel,
+        ))
+        .with_child((
+            Text::new(""),
+            TextFont {
+                font_size: 18.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+            ThemedText,
+        ));
+
     // Add label visibility indicator
     commands.spawn((
         Text::new(""),
@@ -28,34 +88,316 @@ pub fn setup_ui(commands: &mut Commands) {
         TextColor(Color::srgba(1.0, 1.0, 0.0, 0.8)),
         Node {
             position_type: PositionType::Absolute,
-            bottom: Val::Px(10.0),
+            bottom: Val::Px(40.0),
             left: Val::Px(10.0),
             ..default()
         },
         LabelVisibilityIndicator,
     ));
+
+    // Load error panel (hidden until a parse fails)
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            max_width: Val::Percent(45.0),
+            padding: UiRect::all(Val::Px(10.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.6, 0.05, 0.05, 0.9)),
+        Visibility::Hidden,
+        ErrorPanel,
+    ));
+}
+
+/// Shows the latest load error, or hides the panel once a load succeeds
+pub fn update_error_panel(
+    load_error: Res<LoadError>,
+    input_source: Option<Res<InputSource>>,
+    mut panel_query: Query<(&mut Text, &mut Visibility), With<ErrorPanel>>,
+) {
+    let Ok((mut text, mut visibility)) = panel_query.single_mut() else {
+        return;
+    };
+
+    let Some(message) = &load_error.0 else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    let hint = match input_source.as_deref() {
+        Some(source) if source.watch => "Fix the file to reload automatically, or press F5",
+        Some(source) if source.can_reload() => "Fix the file and press F5 to reload",
+        _ => "Input from stdin can't be reloaded",
+    };
+    text.0 = format!("Failed to load diagram\n{message}\n\n{hint}");
+    *visibility = Visibility::Visible;
+}
+
+pub fn toggle_help_overlay(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    mut overlay_query: Query<&mut Visibility, With<HelpOverlay>>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::ToggleHelp, &keyboard_input) {
+        return;
+    }
+    if let Ok(mut visibility) = overlay_query.single_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+/// Regenerates the help text whenever the key bindings change
+pub fn update_help_overlay(
+    bindings: Res<KeyBindings>,
+    overlay_query: Query<&Children, With<HelpOverlay>>,
+    mut text_query: Query<&mut Text>,
+) {
+    for children in &overlay_query {
+        let mut text_iter = text_query.iter_many_mut(children);
+        while let Some(mut text) = text_iter.fetch_next() {
+            text.0 = format!(
+                "Keyboard and mouse controls\n\n{}Click a label to pin it",
+                bindings.help_text()
+            );
+        }
+    }
+}
+
+/// Family name of the font embedded for node labels
+pub const LABEL_FONT: &str = "Fira Mono";
+
+/// Height of label text above the node's center, before the node's scale
+pub const LABEL_OFFSET: f32 = 0.9;
+
+/// Height of a line of label text in the scene at a UI scale of 1
+pub const LABEL_WORLD_SCALE: f32 = 0.35;
+
+/// Width of the halo around label text in high contrast, in percent of the
+/// font size
+const LABEL_HALO: Option<NonZeroU32> = NonZeroU32::new(15);
+
+/// Lines a wrapped label may take before the rest is cut off
+const MAX_WRAPPED_LINES: usize = 3;
+
+/// A label's text fit to the max width: lines longer than it are cut short
+/// with an ellipsis, or wrapped at spaces into a few lines if wrapping is on
+pub fn fitted_label(name: &str, settings: &LabelSettings) -> String {
+    let width = settings.max_width;
+    if width == 0 || name.lines().all(|line| line.chars().count() <= width) {
+        return name.to_string();
+    }
+    if !settings.wrap {
+        return name
+            .lines()
+            .map(|line| {
+                if line.chars().count() <= width {
+                    line.to_string()
+                } else {
+                    ellipsized(line, width)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    let mut lines: Vec<String> = name.lines().flat_map(|line| wrapped(line, width)).collect();
+    if lines.len() > MAX_WRAPPED_LINES {
+        lines.truncate(MAX_WRAPPED_LINES);
+        if let Some(last) = lines.last_mut() {
+            *last = ellipsized(last, width);
+        }
+    }
+    lines.join("\n")
 }
 
+/// The start of a line, ending in an ellipsis within `width` characters
+fn ellipsized(line: &str, width: usize) -> String {
+    let mut text: String = line.chars().take(width.saturating_sub(1)).collect();
+    text.push('…');
+    text
+}
+
+/// A line broken at spaces into lines of at most `width` characters, with
+/// words longer than that split across lines
+fn wrapped(line: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current: Vec<char> = Vec::new();
+    for word in line.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        if !current.is_empty() && current.len() + 1 + word.len() <= width {
+            current.push(' ');
+            current.append(&mut word);
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current).into_iter().collect());
+        }
+        while word.len() > width {
+            lines.push(word.drain(..width).collect());
+        }
+        current = word;
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current.into_iter().collect());
+    }
+    lines
+}
+
+/// Spawns a world-space label as a child of each new node, or of every node
+/// when labels switch back from the atlas
+///
+/// Labels are counter-scaled so text is the same size on every node, and are
+/// turned to face the camera by `update_node_label_positions`.
 pub fn create_node_labels(
     mut commands: Commands,
-    node_query: Query<(Entity, &GraphNode), Added<GraphNode>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    node_query: Query<(Entity, &GraphNode, &Transform)>,
+    new_node_query: Query<(Entity, &GraphNode, &Transform), Added<GraphNode>>,
+    label_settings: Res<LabelSettings>,
+    backend: Res<LabelBackend>,
 ) {
-    for (node_entity, graph_node) in &node_query {
-        // Create a UI text element for this node
-        commands.spawn((
-            Text::new(graph_node.name.clone()),
-            TextFont {
-                font_size: 20.0,
-                ..default()
-            },
-            TextColor(Color::WHITE),
-            Node {
-                position_type: PositionType::Absolute,
-                ..default()
-            },
-            NodeLabel { node_entity },
-            Visibility::Hidden, // Start hidden, will be shown by update system if in range
-        ));
+    if *backend != LabelBackend::Text {
+        return;
+    }
+    let nodes: Vec<_> = if backend.is_changed() {
+        node_query.iter().collect()
+    } else {
+        new_node_query.iter().collect()
+    };
+    for (node_entity, graph_node, transform) in nodes {
+        let node_scale = transform.scale.max_element().max(f32::EPSILON);
+
+        // Each label gets its own material so it can fade independently
+        let material = materials.add(StandardMaterial {
+            base_color_texture: Some(TextAtlas::DEFAULT_IMAGE.clone_weak()),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            cull_mode: None,
+            ..default()
+        });
+
+        let label = commands
+            .spawn((
+                Text3d::new(fitted_label(&graph_node.name, &label_settings)),
+                Text3dStyling {
+                    size: 48.0,
+                    font: LABEL_FONT.into(),
+                    anchor: TextAnchor::BOTTOM_CENTER,
+                    world_scale: Some(Vec2::splat(LABEL_WORLD_SCALE)),
+                    ..default()
+                },
+                Mesh3d::default(),
+                MeshMaterial3d(material),
+                Transform::from_xyz(0.0, LABEL_OFFSET, 0.0)
+                    .with_scale(Vec3::splat(1.0 / node_scale)),
+                NodeLabel { node_entity },
+                Visibility::Hidden, // Start hidden, will be shown by update system if in range
+            ))
+            .observe(pin_clicked_label)
+            .id();
+        commands.entity(node_entity).add_child(label);
+    }
+}
+
+/// Shows the labels of the hovered and selected nodes in full and fits the
+/// rest to the label settings
+pub fn update_label_text(
+    hover: Res<HoverState>,
+    selected: Res<SelectedNodes>,
+    label_settings: Res<LabelSettings>,
+    mut last_hovered: Local<Option<Entity>>,
+    added: Query<(), Added<NodeLabel>>,
+    node_query: Query<&GraphNode>,
+    mut label_query: Query<(&mut Text3d, &NodeLabel)>,
+) {
+    // The hover state changes every frame a node is hovered; only a new node
+    // or new settings change what labels show
+    if *last_hovered == hover.node
+        && !selected.is_changed()
+        && !label_settings.is_changed()
+        && added.is_empty()
+    {
+        return;
+    }
+    *last_hovered = hover.node;
+    for (mut text, label) in &mut label_query {
+        let Ok(graph_node) = node_query.get(label.node_entity) else {
+            continue;
+        };
+        let full = hover.node == Some(label.node_entity) || selected.0.contains(&graph_node.id);
+        let shown = if full {
+            graph_node.name.clone()
+        } else {
+            fitted_label(&graph_node.name, &label_settings)
+        };
+        if text.get_single() != Some(shown.as_str()) {
+            *text = Text3d::new(shown);
+        }
+    }
+}
+
+/// Pins or unpins a label when it is clicked
+fn pin_clicked_label(
+    trigger: Trigger<Pointer<Click>>,
+    label_query: Query<&NodeLabel>,
+    node_query: Query<&GraphNode>,
+    mut pinned: ResMut<PinnedLabels>,
+) {
+    if trigger.event().button != PointerButton::Primary {
+        return;
+    }
+    if let Ok(label) = label_query.get(trigger.target())
+        && let Ok(graph_node) = node_query.get(label.node_entity)
+    {
+        toggle_pin(&mut pinned, &graph_node.id);
+    }
+}
+
+/// Hands the keyboard to egui while one of its text fields has focus, so
+/// typing into it doesn't also trigger shortcuts
+pub fn yield_keyboard_to_egui(
+    mut contexts: EguiContexts,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+) {
+    if contexts
+        .try_ctx_mut()
+        .is_some_and(|ctx| ctx.wants_keyboard_input())
+    {
+        keyboard_input.reset_all();
+    }
+}
+
+/// Pins or unpins the hovered node's label when P is pressed
+pub fn toggle_label_pins(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    hover: Res<HoverState>,
+    bindings: Res<KeyBindings>,
+    node_query: Query<&GraphNode>,
+    mut pinned: ResMut<PinnedLabels>,
+) {
+    if search_state.active || !bindings.just_pressed(Action::PinLabel, &keyboard_input) {
+        return;
+    }
+    if let Some(graph_node) = hover.node.and_then(|node| node_query.get(node).ok()) {
+        toggle_pin(&mut pinned, &graph_node.id);
+    }
+}
+
+fn toggle_pin(pinned: &mut PinnedLabels, id: &str) {
+    if !pinned.0.remove(id) {
+        pinned.0.insert(id.to_string());
     }
 }
 
@@ -64,13 +406,14 @@ pub fn toggle_label_visibility(
     mut label_settings: ResMut<LabelSettings>,
     mut indicator_query: Query<&mut Text, With<LabelVisibilityIndicator>>,
     search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
 ) {
     // Don't toggle labels when searching
     if search_state.active {
         return;
     }
     // Toggle show all labels with 'L' key
-    if keyboard_input.just_pressed(KeyCode::KeyL) {
+    if bindings.just_pressed(Action::ShowAllLabels, &keyboard_input) {
         label_settings.show_all_labels = !label_settings.show_all_labels;
         if let Ok(mut text) = indicator_query.single_mut() {
             text.0 = if label_settings.show_all_labels {
@@ -82,56 +425,155 @@ pub fn toggle_label_visibility(
     }
 }
 
+/// Faces labels toward the camera and fades them out with distance
+///
+/// Through an orthographic lens every node is as far away as the view is
+/// high, so zooming out of the flat view fades them all together.
+#[allow(clippy::too_many_arguments)]
 pub fn update_node_label_positions(
-    camera_query: Query<(&Camera, &GlobalTransform)>,
-    node_query: Query<&GlobalTransform, With<GraphNode>>,
-    mut label_query: Query<(&mut Node, &mut Visibility, &mut TextColor, &NodeLabel)>,
+    camera_query: Query<(&GlobalTransform, &Projection), With<Camera3d>>,
+    node_query: Query<(&GlobalTransform, &GraphNode)>,
+    mut label_query: Query<(
+        &mut Transform,
+        &mut Visibility,
+        &mut Text3dStyling,
+        &MeshMaterial3d<StandardMaterial>,
+        &NodeLabel,
+    )>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
     label_settings: Res<LabelSettings>,
+    pinned: Res<PinnedLabels>,
+    theme: Res<Theme>,
+    accessibility: Res<Accessibility>,
 ) {
-    let Ok((camera, camera_transform)) = camera_query.single() else {
+    let Ok((camera_transform, projection)) = camera_query.single() else {
         return;
     };
+    let camera_rotation = camera_transform.compute_transform().rotation;
+
+    let world_scale = Some(Vec2::splat(LABEL_WORLD_SCALE * accessibility.ui_scale));
+    let halo_color = theme.background().to_srgba();
 
-    for (mut node_style, mut visibility, mut text_color, label) in &mut label_query {
-        let Ok(node_transform) = node_query.get(label.node_entity) else {
+    for (mut label_transform, mut visibility, mut styling, material, label) in &mut label_query {
+        let Ok((node_transform, graph_node)) = node_query.get(label.node_entity) else {
             continue;
         };
 
+        // Billboard: cancel the node's rotation and match the camera's
+        let (_, node_rotation, node_position) = node_transform.to_scale_rotation_translation();
+        let rotation = node_rotation.inverse() * camera_rotation;
+        if label_transform.rotation != rotation {
+            label_transform.rotation = rotation;
+        }
+
         // Calculate distance from camera to node
-        let distance = camera_transform
-            .translation()
-            .distance(node_transform.translation());
+        let distance = match projection {
+            Projection::Orthographic(ortho) => ortho.scale,
+            _ => camera_transform.translation().distance(node_position),
+        };
+        let is_pinned = pinned.0.contains(&graph_node.id);
+        let Some(color) = label_color(distance, is_pinned, &label_settings, *theme, *accessibility)
+        else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        visibility.set_if_neq(Visibility::Inherited);
 
-        // Show label if within distance threshold or if show_all_labels is true
-        if label_settings.show_all_labels || distance <= label_settings.visibility_distance {
-            *visibility = Visibility::Visible;
+        // The material tints the text and its halo alike, so in high contrast
+        // the text takes the label's color itself and the material only fades it
+        let (tint, fill, halo) = if accessibility.high_contrast {
+            (
+                Color::WHITE.with_alpha(color.alpha()),
+                color.with_alpha(1.0).to_srgba(),
+                LABEL_HALO,
+            )
+        } else {
+            (color, Srgba::WHITE, None)
+        };
+        // Restyled text is laid out again, so only restyle when something differs
+        if styling.color != fill
+            || styling.stroke != halo
+            || styling.stroke_color != halo_color
+            || styling.world_scale != world_scale
+        {
+            styling.color = fill;
+            styling.stroke = halo;
+            styling.stroke_color = halo_color;
+            styling.world_scale = world_scale;
+        }
+        // Only touch the material when the color actually changes
+        if materials
+            .get(&material.0)
+            .is_some_and(|m| m.base_color != tint)
+            && let Some(material) = materials.get_mut(&material.0)
+        {
+            material.base_color = tint;
+        }
+    }
+}
 
-            // Fade labels based on distance (closer = more opaque)
-            let alpha = if label_settings.show_all_labels {
-                // When forcing all labels visible, make them fully opaque
+/// Color of a label `distance` from the camera, or None if it's hidden
+///
+/// Pinned labels ignore distance and are tinted so they stand out; others
+/// show within the threshold or when all labels are forced on, and only fade
+/// out with distance when contrast is normal.
+pub fn label_color(
+    distance: f32,
+    is_pinned: bool,
+    label_settings: &LabelSettings,
+    theme: Theme,
+    accessibility: Accessibility,
+) -> Option<Color> {
+    if is_pinned {
+        Some(theme.pinned_label())
+    } else if label_settings.show_all_labels || distance <= label_settings.visibility_distance {
+        // Fade labels based on distance (closer = more opaque)
+        let alpha = if label_settings.show_all_labels || accessibility.high_contrast {
+            // When forcing all labels visible, make them fully opaque
+            1.0
+        } else {
+            // Normal distance-based fading
+            let fade_start = label_settings.visibility_distance * 0.7;
+            if distance < fade_start {
                 1.0
             } else {
-                // Normal distance-based fading
-                let fade_start = label_settings.visibility_distance * 0.7;
-                if distance < fade_start {
-                    1.0
-                } else {
-                    1.0 - ((distance - fade_start)
-                        / (label_settings.visibility_distance - fade_start))
-                }
-            };
+                1.0 - ((distance - fade_start) / (label_settings.visibility_distance - fade_start))
+            }
+        };
+        Some(theme.label().with_alpha(alpha.clamp(0.0, 1.0)))
+    } else {
+        None
+    }
+}
 
-            text_color.0 = Color::srgba(1.0, 1.0, 1.0, alpha.clamp(0.0, 1.0));
-        } else {
-            *visibility = Visibility::Hidden;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Project 3D position to screen coordinates
-        if let Ok(viewport_position) =
-            camera.world_to_viewport(camera_transform, node_transform.translation())
-        {
-            node_style.left = Val::Px(viewport_position.x);
-            node_style.top = Val::Px(viewport_position.y);
-        }
+    #[test]
+    fn test_long_labels_are_cut_short_or_wrapped() {
+        let mut settings = LabelSettings {
+            max_width: 12,
+            ..default()
+        };
+        assert_eq!(fitted_label("payments", &settings), "payments");
+        assert_eq!(
+            fitted_label("payments-reconciliation-worker\nv2", &settings),
+            "payments-re…\nv2"
+        );
+
+        settings.wrap = true;
+        assert_eq!(
+            fitted_label("order history export service", &settings),
+            "order\nhistory\nexport…"
+        );
+        assert_eq!(
+            fitted_label("payments-reconciliation", &settings),
+            "payments-rec\nonciliation"
+        );
+
+        settings.max_width = 0;
+        let name = "a".repeat(100);
+        assert_eq!(fitted_label(&name, &settings), name);
     }
 }
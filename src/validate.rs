@@ -0,0 +1,272 @@
+use crate::events::{EventResult, GraphEvent};
+use crate::graph_state::GraphState;
+use crate::sources::{LOW_CONFIDENCE, Progress, SourceError, SourceRegistry};
+use serde_json::{Value, json};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+/// A problem found in a diagram
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Short machine-readable name, e.g. "duplicate-id"
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl Issue {
+    fn error(kind: &'static str, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            kind,
+            message,
+        }
+    }
+
+    fn warning(kind: &'static str, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            kind,
+            message,
+        }
+    }
+}
+
+/// What `validate` found in one diagram
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Where the diagram came from, as given on the command line
+    pub input: String,
+    /// Format it was read as; None if that couldn't be determined
+    pub format: Option<&'static str>,
+    pub nodes: usize,
+    pub edges: usize,
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .count()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "input": self.input,
+            "format": self.format,
+            "nodes": self.nodes,
+            "edges": self.edges,
+            "errors": self.count(Severity::Error),
+            "warnings": self.count(Severity::Warning),
+            "issues": self.issues.iter().map(|issue| json!({
+                "severity": issue.severity.name(),
+                "kind": issue.kind,
+                "message": issue.message,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.input)?;
+        match self.format {
+            Some(format) => write!(f, "{format}, {} nodes, {} edges", self.nodes, self.edges)?,
+            None => write!(f, "unreadable")?,
+        }
+        if self.issues.is_empty() {
+            return write!(f, ", ok");
+        }
+        for issue in &self.issues {
+            write!(
+                f,
+                "\n  {}: {}: {}",
+                issue.severity.name(),
+                issue.kind,
+                issue.message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a diagram without showing it and reports what is wrong with it
+///
+/// Duplicate node ids and edges to nodes that don't exist are errors, as is
+/// input that can't be parsed at all. Duplicate edges, attributes the viewer
+/// ignores and uncertain format detection are warnings.
+pub fn validate(
+    registry: &SourceRegistry,
+    input: &str,
+    content: &str,
+    format: Option<&str>,
+    progress: &mut dyn FnMut(Progress),
+) -> Report {
+    let mut report = Report {
+        input: input.to_string(),
+        format: None,
+        nodes: 0,
+        edges: 0,
+        issues: Vec::new(),
+    };
+    let format = match format {
+        Some(name) => registry.get(name).map(|format| format.name),
+        None => match registry.detect(content) {
+            Ok(detection) => {
+                if detection.confidence < LOW_CONFIDENCE {
+                    report.issues.push(Issue::warning(
+                        "uncertain-format",
+                        format!(
+                            "looks like {} but detection is uncertain; use --format",
+                            detection.format
+                        ),
+                    ));
+                }
+                Some(detection.format)
+            }
+            Err(_) => None,
+        },
+    };
+    let Some(format) = format else {
+        report.issues.push(Issue::error(
+            "unknown-format",
+            SourceError::UnknownFormat.to_string(),
+        ));
+        return report;
+    };
+    report.format = Some(format);
+
+    let parsed = registry
+        .source_for(format, content)
+        .and_then(|source| Ok((source.events_with_progress(progress)?, source)));
+    let (events, source) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            report
+                .issues
+                .push(Issue::error("parse-error", err.to_string()));
+            return report;
+        }
+    };
+
+    let mut state = GraphState::new();
+    for event in events {
+        // Batched events all report success, so apply them one at a time
+        if matches!(event, GraphEvent::BatchStart | GraphEvent::BatchEnd) {
+            continue;
+        }
+        let result = state.process_event(event.clone());
+        report.issues.extend(issue_for(&state, &event, &result));
+    }
+    report
+        .issues
+        .extend(source.unknown_attributes().into_iter().map(|attribute| {
+            Issue::warning(
+                "unknown-attribute",
+                format!(
+                    "node \"{}\" has attribute \"{}\", which is ignored",
+                    attribute.node, attribute.name
+                ),
+            )
+        }));
+    report.nodes = state.node_count();
+    report.edges = state.edge_count();
+    report
+}
+
+/// The problem an event that failed to apply points to
+fn issue_for(state: &GraphState, event: &GraphEvent, result: &EventResult) -> Option<Issue> {
+    match (event, result) {
+        (_, EventResult::Success) => None,
+        (GraphEvent::AddNode { id, .. }, EventResult::NodeExists) => Some(Issue::error(
+            "duplicate-id",
+            format!("node \"{id}\" is declared more than once"),
+        )),
+        (
+            GraphEvent::AddEdge { from, to } | GraphEvent::AddRichEdge { from, to, .. },
+            EventResult::NodeNotFound,
+        ) => {
+            let missing = if state.get_node(from).is_none() {
+                from
+            } else {
+                to
+            };
+            Some(Issue::error(
+                "dangling-edge",
+                format!("edge {from} -> {to} refers to missing node \"{missing}\""),
+            ))
+        }
+        (
+            GraphEvent::AddEdge { from, to } | GraphEvent::AddRichEdge { from, to, .. },
+            EventResult::EdgeExists,
+        ) => Some(Issue::warning(
+            "duplicate-edge",
+            format!("edge {from} -> {to} is declared more than once"),
+        )),
+        (event, result) => Some(Issue::error(
+            "invalid-event",
+            format!("{event} failed: {result:?}"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_reports_problems() {
+        let registry = SourceRegistry::default();
+        let content = r#"digraph {
+            api [type="service", color="red"]
+            api [type="service"]
+            web -> api
+            web -> api
+        }"#;
+        let report = validate(&registry, "services.dot", content, None, &mut |_| {});
+        assert_eq!(report.format, Some("dot"));
+        assert_eq!((report.nodes, report.edges), (2, 1));
+        let kinds: Vec<_> = report.issues.iter().map(|issue| issue.kind).collect();
+        assert_eq!(
+            kinds,
+            ["duplicate-id", "duplicate-edge", "unknown-attribute"]
+        );
+        assert_eq!(report.count(Severity::Error), 1);
+        assert_eq!(report.to_json()["warnings"], 2);
+
+        // Edges to nodes that were never declared
+        let gexf = r#"<gexf><graph>
+            <nodes><node id="a" label="a"/></nodes>
+            <edges><edge source="a" target="ghost"/></edges>
+        </graph></gexf>"#;
+        let report = validate(&registry, "g.gexf", gexf, None, &mut |_| {});
+        assert_eq!(report.issues[0].kind, "dangling-edge");
+        assert!(report.issues[0].message.contains("\"ghost\""));
+
+        let report = validate(
+            &registry,
+            "x",
+            "digraph {\n  a -> b\n",
+            Some("dot"),
+            &mut |_| {},
+        );
+        assert_eq!(report.issues[0].kind, "parse-error");
+        assert!(report.to_string().contains("error: parse-error"));
+    }
+}
@@ -1,8 +1,25 @@
-use crate::types::{GraphData, GraphEdge, GraphNode};
+use crate::aggregate::{Aggregation, CLUSTER_TYPE};
+use crate::graph_state::{GraphState, NodeInfo};
+use crate::layout::{NodeLayout, layout_nodes, par_map};
+use crate::merged_edges::{MergedEdge, MergedEdges, spawn_merged_edges};
+use crate::palette::Palette;
+use crate::scene_build::{QueuedEdge, QueuedNode, SPAWN_PER_FRAME, SceneBuild};
+use crate::sources::dot::{FIELDS_PROPERTY, PORT_PROPERTY_PREFIX};
+use crate::types::{
+    Appearing, EdgeArrowHead, EdgeFrom, EdgeShaft, EdgeTo, GraphData, GraphEdge, GraphNode,
+    GroupLane, LayoutSettings, LayoutTransition, NodeChange, PortOffsets, Timeline, Vanishing,
+    ViewSettings,
+};
+use crate::ui::LABEL_FONT;
+use bevy::color::Mix;
 use bevy::prelude::*;
-use petgraph::graph::NodeIndex;
+use bevy_rich_text3d::{Text3d, Text3dStyling, TextAnchor, TextAtlas};
+use petgraph::graph::{EdgeIndex, NodeIndex};
 use std::collections::HashMap;
 
+/// Room left around the nodes by the planes between lanes
+const LANE_MARGIN: f32 = 1.5;
+
 #[must_use]
 pub fn get_node_appearance(node_type: Option<&str>) -> (Color, f32) {
     // Returns (color, size_multiplier)
@@ -21,214 +38,795 @@ pub fn get_node_appearance(node_type: Option<&str>) -> (Color, f32) {
         Some("process") => (Color::srgb(0.7, 0.7, 0.2), 0.8),                    // Yellow
         Some("external") => (Color::srgb(0.5, 0.2, 0.7), 0.9),                   // Purple
 
+        // Clusters of huge graphs, drawn large
+        Some(CLUSTER_TYPE) => (Color::srgb(0.9, 0.75, 0.3), 1.8), // Amber
+
         _ => (Color::srgb(0.5, 0.5, 0.5), 0.7), // Gray (default)
     }
 }
 
+/// Shape for a node type, one no other known type has if `distinct`
+#[must_use]
+pub fn get_node_mesh(node_type: Option<&str>, distinct: bool) -> Mesh {
+    node_shape(node_type, distinct).1
+}
+
+/// Name of a node type's shape, e.g. "cube", as shown in the legend
+#[must_use]
+pub fn get_node_shape_name(node_type: Option<&str>, distinct: bool) -> &'static str {
+    node_shape(node_type, distinct).0
+}
+
+fn node_shape(node_type: Option<&str>, distinct: bool) -> (&'static str, Mesh) {
+    if distinct && let Some(shape) = distinct_node_shape(node_type) {
+        return shape;
+    }
+    match node_type {
+        // DOT diagram shapes
+        Some("organization") => ("cube", Cuboid::new(1.0, 1.0, 1.0).into()),
+        Some("line_of_business") => ("cylinder", Cylinder::new(0.5, 1.0).into()),
+        Some("site") => ("torus", Torus::new(0.3, 0.5).into()),
+        Some("team") => ("sphere", Sphere::new(0.6).into()),
+        Some("user") => ("capsule", Capsule3d::new(0.3, 0.4).into()),
+
+        // PlantUML sequence diagram shapes
+        Some("database") => ("wide cylinder", Cylinder::new(0.6, 0.8).into()),
+        Some("actor:participant") => ("cube", Cuboid::new(0.8, 0.8, 0.8).into()), // Services
+        // Actors as a humanoid shape
+        Some(t) if t.starts_with("actor:") => ("capsule", Capsule3d::new(0.4, 0.6).into()),
+        Some("process") => ("sphere", Sphere::new(0.5).into()),
+        Some("external") => ("torus", Torus::new(0.25, 0.5).into()),
+
+        Some(CLUSTER_TYPE) => ("icosphere", Sphere::new(0.6).mesh().ico(1).unwrap()),
+
+        _ => ("sphere", Sphere::new(0.4).into()),
+    }
+}
+
+/// Shapes for the known types that share one by default, so that every
+/// type can be told apart by shape alone
+fn distinct_node_shape(node_type: Option<&str>) -> Option<(&'static str, Mesh)> {
+    Some(match node_type? {
+        "team" => (
+            "pentagonal prism",
+            Extrusion::new(RegularPolygon::new(0.6, 5), 0.8).into(),
+        ),
+        "database" => (
+            "hexagonal prism",
+            Extrusion::new(RegularPolygon::new(0.6, 6), 0.8).into(),
+        ),
+        "actor:participant" => ("cone", Cone::new(0.5, 1.0).into()),
+        t if t.starts_with("actor:") => ("tetrahedron", Tetrahedron::default().into()),
+        "process" => (
+            "triangular prism",
+            Extrusion::new(RegularPolygon::new(0.6, 3), 0.8).into(),
+        ),
+        "external" => (
+            "frustum",
+            ConicalFrustum {
+                radius_top: 0.25,
+                radius_bottom: 0.5,
+                height: 0.8,
+            }
+            .into(),
+        ),
+        _ => return None,
+    })
+}
+
+/// Width of each field of a record node
+const RECORD_FIELD_WIDTH: f32 = 0.6;
+
+/// Room between the fields of a record node
+const RECORD_FIELD_GAP: f32 = 0.06;
+
+/// How many fields a record node or HTML table has, if more than one
+fn record_fields(info: &NodeInfo) -> Option<usize> {
+    let fields = info.properties.get(FIELDS_PROPERTY)?.split('\n').count();
+    (fields > 1).then_some(fields)
+}
+
+/// Distance along X from a record node's center to the middle of a field
+#[allow(clippy::cast_precision_loss)]
+fn field_x(field: usize, fields: usize) -> f32 {
+    (field as f32 - (fields - 1) as f32 / 2.0) * (RECORD_FIELD_WIDTH + RECORD_FIELD_GAP)
+}
+
+/// A row of boxes, one for each field of a record node
+fn record_mesh(fields: usize) -> Mesh {
+    let field = Cuboid::new(RECORD_FIELD_WIDTH, 0.5, 0.5);
+    let mut mesh = Mesh::from(field).translated_by(Vec3::X * field_x(0, fields));
+    for i in 1..fields {
+        let next = Mesh::from(field).translated_by(Vec3::X * field_x(i, fields));
+        mesh.merge(&next)
+            .expect("boxes have the same vertex attributes");
+    }
+    mesh
+}
+
+/// Where on a record node the field a port names is, relative to its center
+pub fn port_offset(info: &NodeInfo, port: &str) -> Option<Vec3> {
+    let fields = record_fields(info)?;
+    let field: usize = info
+        .properties
+        .get(&format!("{PORT_PROPERTY_PREFIX}{port}"))?
+        .parse()
+        .ok()?;
+    (field < fields).then(|| Vec3::X * field_x(field, fields))
+}
+
+/// Where a node was and what it was before the scene was rebuilt
+pub struct PreviousNode {
+    pub position: Vec3,
+    pub node_type: Option<String>,
+    pub level: u32,
+    /// A type or level change that was still being animated
+    pub change: Option<NodeChange>,
+    /// How long the node had been growing in, if it still was
+    pub appearing: Option<f32>,
+}
+
+/// What the scene showed before it was rebuilt, so changes can be animated
+#[derive(Default)]
+pub struct PreviousScene {
+    pub nodes: HashMap<String, PreviousNode>,
+    /// Edges keyed by the ids of their ends, with how long each had been
+    /// growing in, if it still was
+    pub edges: HashMap<(String, String), Option<f32>>,
+}
+
+/// What a node's mesh is built from, so nodes that look alike share one
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum NodeMeshKey<'a> {
+    Shape(Option<&'a str>),
+    Record(usize),
+}
+
+/// Everything a node is spawned with that takes working out, found for all
+/// nodes at once on the task pool
+struct NodeSpawn {
+    index: NodeIndex,
+    position: Vec3,
+    color: Color,
+    size: f32,
+    fields: Option<usize>,
+    /// Where it was before the scene was rebuilt
+    from: Option<Vec3>,
+    appearing: Option<f32>,
+    change: Option<(Color, f32, f32)>,
+}
+
+/// Spawns the nodes and edges of the graph
+///
+/// A scene too big for one frame spawns its first chunk here and queues the
+/// rest as a [`SceneBuild`], which builds it in over the next frames.
+///
+/// Nodes found in `previous`, keyed by id, start where they were and animate
+/// to their place in the layout, and to their new look if their type or
+/// level changed. Nodes and edges missing from it grow in. With no previous
+/// scene, everything is shown in place.
+///
+/// Positions and looks are worked out in parallel before anything is
+/// queued, and nodes and edges that look alike share meshes and materials.
 pub fn create_graph_visualization(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
     graph_data: &GraphData,
-) -> HashMap<NodeIndex, Entity> {
-    let mut node_entities = HashMap::new();
-    let mut level_counts = HashMap::new();
-    let mut level_indices = HashMap::new();
-
-    // Count nodes at each level
-    for node_idx in graph_data.graph.node_indices() {
-        let node_info = &graph_data.graph[node_idx];
-        *level_counts.entry(node_info.level).or_insert(0) += 1;
-    }
-
-    // Create nodes with proper positioning
-    for node_idx in graph_data.graph.node_indices() {
-        let node_info = &graph_data.graph[node_idx];
-        let (color, size_mult) = get_node_appearance(node_info.node_type.as_deref());
-
-        // Get current index at this level
-        let level_idx = level_indices.entry(node_info.level).or_insert(0);
-        let count_at_level = level_counts[&node_info.level];
-
-        // Calculate position with hierarchical layout
-        let level_radius = (node_info.level as f32).mul_add(2.0, 5.0);
-        let angle = 2.0 * std::f32::consts::PI * (*level_idx as f32) / count_at_level as f32;
-        let x = level_radius * angle.cos();
-        let z = level_radius * angle.sin();
-        let y = node_info.level as f32 * 2.0; // Vertical spacing by level
-
-        *level_idx += 1;
-
-        // Create material for this node type
-        let node_material = materials.add(StandardMaterial {
-            base_color: color,
-            emissive: LinearRgba::BLACK,
-            ..default()
-        });
+    layout: &LayoutSettings,
+    view: &ViewSettings,
+    previous: Option<&PreviousScene>,
+) {
+    // Nodes of a type share one material; highlights swap in a copy
+    let mut node_materials: HashMap<Option<&str>, Handle<StandardMaterial>> = HashMap::new();
+    let mut node_meshes: HashMap<NodeMeshKey, Handle<Mesh>> = HashMap::new();
 
-        // Create mesh based on node type
-        let mesh = match node_info.node_type.as_deref() {
-            // DOT diagram shapes
-            Some("organization") => meshes.add(Cuboid::new(1.0, 1.0, 1.0)), // Cube
-            Some("line_of_business") => meshes.add(Cylinder::new(0.5, 1.0)), // Cylinder
-            Some("site") => meshes.add(Torus::new(0.3, 0.5)),               // Torus
-            Some("team") => meshes.add(Sphere::new(0.6)),                   // Sphere
-            Some("user") => meshes.add(Capsule3d::new(0.3, 0.4)),           // Capsule
-
-            // PlantUML sequence diagram shapes
-            Some("database") => meshes.add(Cylinder::new(0.6, 0.8)), // Wide cylinder for DB
-            Some("actor:participant") => meshes.add(Cuboid::new(0.8, 0.8, 0.8)), // Cube for services
-            Some(t) if t.starts_with("actor:") => {
-                // Actor as a humanoid shape (capsule)
-                meshes.add(Capsule3d::new(0.4, 0.6))
-            }
-            Some("process") => meshes.add(Sphere::new(0.5)), // Sphere for process
-            Some("external") => meshes.add(Torus::new(0.25, 0.5)), // Torus for external
+    // Filtered-out nodes don't take a slot in the layout
+    let visible_nodes = visible_nodes(graph_data, view);
+    let node_layout = layout_nodes(graph_data, &visible_nodes, layout);
+    spawn_lanes(commands, meshes, materials, &node_layout);
+    let positions = node_layout.positions;
 
-            _ => meshes.add(Sphere::new(0.4)), // Default sphere
+    let mut nodes = Vec::with_capacity(visible_nodes.len());
+    for spawn in par_map(&visible_nodes, |&index| {
+        plan_node(graph_data, view, index, positions[&index], previous)
+    }) {
+        let node_info = &graph_data.graph[spawn.index];
+        let node_material = if let Some((from_color, ..)) = spawn.change {
+            materials.add(StandardMaterial {
+                base_color: from_color,
+                ..default()
+            })
+        } else {
+            node_materials
+                .entry(node_info.node_type.as_deref())
+                .or_insert_with(|| {
+                    materials.add(StandardMaterial {
+                        base_color: spawn.color,
+                        emissive: LinearRgba::BLACK,
+                        ..default()
+                    })
+                })
+                .clone()
         };
 
+        let mesh_key = spawn.fields.map_or(
+            NodeMeshKey::Shape(node_info.node_type.as_deref()),
+            NodeMeshKey::Record,
+        );
+        let mesh = node_meshes
+            .entry(mesh_key)
+            .or_insert_with(|| {
+                meshes.add(match mesh_key {
+                    NodeMeshKey::Shape(node_type) => get_node_mesh(node_type, view.distinct_shapes),
+                    NodeMeshKey::Record(fields) => record_mesh(fields),
+                })
+            })
+            .clone();
+
         // Spawn node with appropriate shape
-        let node_entity = commands
-            .spawn((
-                Mesh3d(mesh),
-                MeshMaterial3d(node_material),
-                Transform::from_xyz(x, y, z).with_scale(Vec3::splat(size_mult)),
-                GraphNode {
-                    name: node_info.name.clone(),
-                    index: node_idx,
-                },
-                Name::new(node_info.name.clone()),
-            ))
-            .id();
-
-        node_entities.insert(node_idx, node_entity);
-    }
-
-    // Create edges
-    let edge_material = materials.add(StandardMaterial {
-        base_color: Color::srgb(0.4, 0.4, 0.4),
-        ..default()
-    });
+        let position = spawn.position;
+        let scale = if spawn.appearing.is_some() {
+            0.0
+        } else {
+            spawn.size
+        };
+        nodes.push(QueuedNode {
+            mesh,
+            material: node_material.clone(),
+            transform: Transform::from_translation(spawn.from.unwrap_or(position))
+                .with_scale(Vec3::splat(scale)),
+            node: GraphNode {
+                id: node_info.id.clone(),
+                name: node_info.name.clone(),
+                index: spawn.index,
+            },
+            change: spawn
+                .change
+                .map(|(from_color, from_scale, elapsed)| NodeChange {
+                    from_color,
+                    to_color: spawn.color,
+                    from_scale,
+                    to_scale: spawn.size,
+                    material: node_material,
+                    elapsed,
+                }),
+            appearing: spawn.appearing.map(|elapsed| Appearing {
+                elapsed,
+                size: spawn.size,
+            }),
+            transition: spawn
+                .from
+                .filter(|&from| from != position)
+                .map(|from| LayoutTransition {
+                    from,
+                    to: position,
+                    elapsed: 0.0,
+                }),
+        });
+    }
 
-    for edge in graph_data.graph.edge_indices() {
-        if let Some((from_idx, to_idx)) = graph_data.graph.edge_endpoints(edge) {
-            if let (Some(&from_entity), Some(&to_entity)) =
-                (node_entities.get(&from_idx), node_entities.get(&to_idx))
-            {
-                let edge_info = graph_data.graph.edge_weight(edge);
-                spawn_edge(
-                    commands,
-                    meshes,
-                    materials,
-                    edge_material.clone(),
-                    from_entity,
-                    to_entity,
-                    from_idx,
-                    to_idx,
-                    edge_info,
-                );
-            }
-        }
+    let edges = if view.merge_edges {
+        let edges = merged_edges(graph_data, view, &positions);
+        spawn_merged_edges(commands, meshes, materials, edges);
+        Vec::new()
+    } else {
+        queue_edges(meshes, materials, graph_data, view, &positions, previous)
+    };
+    let mut build = SceneBuild::new(nodes, edges);
+    build.spawn_chunk(commands, SPAWN_PER_FRAME);
+    // Whatever was left of an earlier scene is dropped either way
+    if build.is_done() {
+        commands.remove_resource::<SceneBuild>();
+    } else {
+        commands.insert_resource(build);
+    }
+}
+
+/// How a node at `position` is spawned, given what the scene showed before
+fn plan_node(
+    graph_data: &GraphData,
+    view: &ViewSettings,
+    index: NodeIndex,
+    position: Vec3,
+    previous: Option<&PreviousScene>,
+) -> NodeSpawn {
+    let node_info = &graph_data.graph[index];
+    let node_type = node_info.node_type.as_deref();
+    let (_, size) = get_node_appearance(node_type);
+    let color = view.palette.node_color(node_type);
+    let before = previous.and_then(|previous| previous.nodes.get(&node_info.id));
+    NodeSpawn {
+        index,
+        position,
+        color,
+        size,
+        fields: record_fields(node_info),
+        from: before.map(|before| before.position),
+        appearing: before.map_or_else(
+            || previous.is_some().then_some(0.0),
+            |before| before.appearing,
+        ),
+        change: before.and_then(|before| node_change(before, node_info, view.palette)),
     }
+}
 
-    node_entities
+/// Nodes whose type isn't hidden
+fn visible_nodes(graph_data: &GraphData, view: &ViewSettings) -> Vec<NodeIndex> {
+    graph_data
+        .graph
+        .node_indices()
+        .filter(|&idx| {
+            let node_type = graph_data.graph[idx]
+                .node_type
+                .as_deref()
+                .unwrap_or_default();
+            !view.hidden_types.contains(node_type)
+        })
+        .collect()
 }
 
-#[allow(clippy::too_many_arguments)]
-fn spawn_edge(
+/// Spawns a translucent plane between each pair of neighboring lanes, and a
+/// label naming each lane
+fn spawn_lanes(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
-    default_material: Handle<StandardMaterial>,
-    _from_entity: Entity,
-    _to_entity: Entity,
-    from_idx: NodeIndex,
-    to_idx: NodeIndex,
-    edge_info: Option<&crate::graph_state::EdgeInfo>,
+    node_layout: &NodeLayout,
 ) {
-    let (color, thickness) = edge_info.map_or_else(
-        || (Color::srgb(0.4, 0.4, 0.4), 0.02), // Default gray
-        |info| {
-            match info.edge_type.as_deref() {
-                Some("sync") => (Color::srgb(0.2, 0.4, 0.8), 0.03), // Blue, thick
-                Some("async") => (Color::srgb(0.8, 0.4, 0.2), 0.02), // Orange, normal
-                Some("return") => (Color::srgb(0.4, 0.8, 0.4), 0.015), // Green, thin
-                _ => (Color::srgb(0.4, 0.4, 0.4), 0.02),            // Gray default
-            }
-        },
+    if node_layout.lanes.is_empty() {
+        return;
+    }
+    let (min, max) = node_layout
+        .positions
+        .values()
+        .fold((Vec3::MAX, Vec3::MIN), |(min, max), &p| {
+            (min.min(p), max.max(p))
+        });
+    let (min, max) = (
+        min - Vec3::splat(LANE_MARGIN),
+        max + Vec3::splat(LANE_MARGIN),
     );
+    let center = (min + max) / 2.0;
+    let axis = node_layout.lane_axis;
+    // Planes face along the lane axis and span the other two
+    let up = if axis.y == 0.0 { Vec3::Y } else { Vec3::Z };
+    let width_axis = axis.cross(up).abs();
+    let size = Vec2::new((max - min).dot(width_axis), (max - min).dot(up));
+    let across = center - axis * center.dot(axis);
+
+    let plane_material = materials.add(StandardMaterial {
+        base_color: Color::srgba(0.6, 0.7, 0.9, 0.08),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        cull_mode: None,
+        ..default()
+    });
+    let plane_mesh = meshes.add(Rectangle::from_size(size));
+    for pair in node_layout.lanes.windows(2) {
+        let between = f32::midpoint(pair[0].end, pair[1].start);
+        commands.spawn((
+            Mesh3d(plane_mesh.clone()),
+            MeshMaterial3d(plane_material.clone()),
+            Transform::from_translation(across + axis * between).looking_to(axis, up),
+            GroupLane,
+        ));
+    }
 
-    // Create material for this edge type
-    let edge_material = if edge_info.is_some() {
-        materials.add(StandardMaterial {
-            base_color: color,
-            emissive: LinearRgba::from(color) * 0.2, // Slight glow for sequence edges
+    // Labels sit above side-by-side lanes and to the left of stacked ones
+    let (label_at, anchor) = if axis.y == 0.0 {
+        (Vec3::Y * max.y, TextAnchor::BOTTOM_CENTER)
+    } else {
+        (Vec3::X * min.x, TextAnchor::CENTER_RIGHT)
+    };
+    for lane in &node_layout.lanes {
+        let material = materials.add(StandardMaterial {
+            base_color_texture: Some(TextAtlas::DEFAULT_IMAGE.clone_weak()),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            cull_mode: None,
             ..default()
-        })
+        });
+        let middle = f32::midpoint(lane.start, lane.end);
+        commands.spawn((
+            Text3d::new(lane.name.clone()),
+            Text3dStyling {
+                size: 64.0,
+                font: LABEL_FONT.into(),
+                anchor,
+                world_scale: Some(Vec2::splat(0.6)),
+                ..default()
+            },
+            Mesh3d::default(),
+            MeshMaterial3d(material),
+            Transform::from_translation(label_at + axis * middle),
+            GroupLane,
+        ));
+    }
+}
+
+/// Starting color, starting size and elapsed time of a node's change of look,
+/// if its type or level changed or it was still changing
+///
+/// A change still being animated carries on from where it got to.
+fn node_change(
+    before: &PreviousNode,
+    info: &NodeInfo,
+    palette: Palette,
+) -> Option<(Color, f32, f32)> {
+    if before.node_type != info.node_type || before.level != info.level {
+        let node_type = before.node_type.as_deref();
+        let (_, from_scale) = get_node_appearance(node_type);
+        Some((palette.node_color(node_type), from_scale, 0.0))
     } else {
-        default_material
+        let change = before.change.as_ref()?;
+        Some((change.from_color, change.from_scale, change.elapsed))
+    }
+}
+
+/// Everything an edge is spawned with that takes working out, found for all
+/// edges at once on the task pool
+struct EdgeSpawn {
+    edge: GraphEdge,
+    color: Color,
+    thickness: f32,
+    /// Whether it has an arrow head and its own look; edges the graph has
+    /// nothing on are drawn plainly
+    styled: bool,
+    appearing: Option<f32>,
+    ports: Option<PortOffsets>,
+}
+
+/// The edges between shown nodes, ready to spawn, growing in those that are new
+fn queue_edges(
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    graph_data: &GraphData,
+    view: &ViewSettings,
+    positions: &HashMap<NodeIndex, Vec3>,
+    previous: Option<&PreviousScene>,
+) -> Vec<QueuedEdge> {
+    let edge_material = materials.add(StandardMaterial {
+        base_color: view.edge_color,
+        ..default()
+    });
+    // Shafts and arrow heads share a mesh per thickness, and styled edges a
+    // material per color
+    let mut edge_meshes: HashMap<u32, (Handle<Mesh>, Handle<Mesh>)> = HashMap::new();
+    let mut edge_materials: HashMap<[u32; 4], Handle<StandardMaterial>> = HashMap::new();
+
+    let edges: Vec<_> = graph_data.graph.edge_indices().collect();
+    let spawns = par_map(&edges, |&edge| {
+        let (from, to) = graph_data.graph.edge_endpoints(edge)?;
+        if !positions.contains_key(&from) || !positions.contains_key(&to) {
+            return None;
+        }
+        Some(plan_edge(graph_data, from, to, edge, previous))
+    });
+    let mut queued = Vec::with_capacity(spawns.len());
+    for spawn in spawns.into_iter().flatten() {
+        let (shaft, arrow_head) = edge_meshes
+            .entry(spawn.thickness.to_bits())
+            .or_insert_with(|| {
+                (
+                    meshes.add(Cylinder::new(spawn.thickness, 1.0)),
+                    meshes.add(Cone {
+                        radius: spawn.thickness * 3.0,
+                        height: spawn.thickness * 8.0,
+                    }),
+                )
+            })
+            .clone();
+        let material = if spawn.styled {
+            let key = spawn.color.to_srgba().to_f32_array().map(f32::to_bits);
+            edge_materials
+                .entry(key)
+                .or_insert_with(|| {
+                    materials.add(StandardMaterial {
+                        base_color: spawn.color,
+                        emissive: LinearRgba::from(spawn.color) * 0.2, // Slight glow for sequence edges
+                        ..default()
+                    })
+                })
+                .clone()
+        } else {
+            edge_material.clone()
+        };
+
+        queued.push(QueuedEdge {
+            edge: spawn.edge,
+            shaft,
+            arrow_head: spawn.styled.then_some(arrow_head),
+            material,
+            appearing: spawn
+                .appearing
+                .map(|elapsed| Appearing { elapsed, size: 1.0 }),
+            ports: spawn.ports,
+        });
+    }
+    queued
+}
+
+/// The edges between shown nodes as drawn by a single merged mesh, which
+/// shows them in place
+fn merged_edges(
+    graph_data: &GraphData,
+    view: &ViewSettings,
+    positions: &HashMap<NodeIndex, Vec3>,
+) -> Vec<MergedEdge> {
+    let edges: Vec<_> = graph_data.graph.edge_indices().collect();
+    par_map(&edges, |&edge| {
+        let (from, to) = graph_data.graph.edge_endpoints(edge)?;
+        if !positions.contains_key(&from) || !positions.contains_key(&to) {
+            return None;
+        }
+        let spawn = plan_edge(graph_data, from, to, edge, None);
+        Some(MergedEdge {
+            from,
+            to,
+            ports: spawn.ports.unwrap_or_default(),
+            color: if spawn.styled {
+                spawn.color
+            } else {
+                view.edge_color
+            },
+            arrow: spawn.styled,
+        })
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// How the edge from `from_idx` to `to_idx` is spawned, given what the scene
+/// showed before
+fn plan_edge(
+    graph_data: &GraphData,
+    from_idx: NodeIndex,
+    to_idx: NodeIndex,
+    edge: EdgeIndex,
+    previous: Option<&PreviousScene>,
+) -> EdgeSpawn {
+    let edge_info = graph_data.graph.edge_weight(edge);
+    let edge_component = GraphEdge::new(from_idx, to_idx, edge_info);
+    let (color, thickness) = match edge_component.edge_type.as_deref() {
+        Some("sync") => (Color::srgb(0.2, 0.4, 0.8), 0.03), // Blue, thick
+        Some("async") => (Color::srgb(0.8, 0.4, 0.2), 0.02), // Orange, normal
+        Some("return") => (Color::srgb(0.4, 0.8, 0.4), 0.015), // Green, thin
+        _ => (Color::srgb(0.4, 0.4, 0.4), 0.02),            // Gray default
     };
 
-    let edge_component = GraphEdge {
-        from: from_idx,
-        to: to_idx,
-        label: edge_info.and_then(|info| info.label.clone()),
-        edge_type: edge_info.and_then(|info| info.edge_type.clone()),
-        sequence: edge_info.and_then(|info| info.sequence),
+    // Live traffic overrides the static edge-type styling
+    let (color, thickness) = edge_component.rate.map_or((color, thickness), |rate| {
+        traffic_appearance(rate, edge_component.error_rate.unwrap_or(0.0))
+    });
+
+    let key = (
+        graph_data.graph[from_idx].id.clone(),
+        graph_data.graph[to_idx].id.clone(),
+    );
+    let appearing = previous.and_then(|previous| {
+        previous
+            .edges
+            .get(&key)
+            .map_or(Some(0.0), |&elapsed| elapsed)
+    });
+
+    // Edges between record fields attach to the fields' boxes
+    let offset = |node: NodeIndex, port: Option<&String>| {
+        port.and_then(|port| port_offset(&graph_data.graph[node], port))
     };
+    let (from, to) = edge_info.map_or((None, None), |info| {
+        (
+            offset(from_idx, info.from_port.as_ref()),
+            offset(to_idx, info.to_port.as_ref()),
+        )
+    });
+    let ports = (from.is_some() || to.is_some()).then(|| PortOffsets {
+        from: from.unwrap_or_default(),
+        to: to.unwrap_or_default(),
+    });
+
+    EdgeSpawn {
+        edge: edge_component,
+        color,
+        thickness,
+        styled: edge_info.is_some(),
+        appearing,
+        ports,
+    }
+}
+
+/// Edge color and thickness for live traffic: thicker with more calls, redder with more errors
+fn traffic_appearance(rate: f32, error_rate: f32) -> (Color, f32) {
+    let healthy = Srgba::rgb(0.3, 0.7, 0.4);
+    let failing = Srgba::rgb(0.9, 0.15, 0.1);
+    let color = healthy.mix(&failing, error_rate.clamp(0.0, 1.0));
+    let thickness = 0.01f32.mul_add(rate.ln_1p(), 0.015).min(0.08);
+    (color.into(), thickness)
+}
+
+/// Whether the graph or the settings that shape the scene changed after startup
+///
+/// Changes to the live graph are ignored while the timeline shows the past.
+pub fn scene_needs_refresh(
+    graph_state: Res<GraphState>,
+    layout: Res<LayoutSettings>,
+    view: Res<ViewSettings>,
+    timeline: Res<Timeline>,
+    aggregation: Res<Aggregation>,
+) -> bool {
+    (graph_state.is_changed() && !graph_state.is_added() && timeline.position.is_none())
+        || (aggregation.is_changed() && !aggregation.is_added())
+        || (timeline.is_changed() && !timeline.is_added())
+        || (layout.is_changed() && !layout.is_added())
+        || (view.is_changed() && !view.is_added())
+}
+
+/// Rebuilds all graph entities after the graph state or scene settings changed at runtime
+///
+/// Nodes that are still in the graph animate from where they were, and nodes
+/// and edges that were removed shrink away.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn refresh_graph_scene(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    graph_state: Res<GraphState>,
+    layout: Res<LayoutSettings>,
+    view: Res<ViewSettings>,
+    timeline: Res<Timeline>,
+    aggregation: Res<Aggregation>,
+    shown_data: Option<Res<GraphData>>,
+    // Labels are children of their nodes, and shafts and arrow heads of
+    // their edges, and go with them
+    node_query: Query<(
+        Entity,
+        &GraphNode,
+        &Transform,
+        Option<&NodeChange>,
+        Option<&Appearing>,
+    )>,
+    edge_query: Query<(Entity, &GraphEdge, &Transform, Option<&Appearing>)>,
+    scenery_query: Query<Entity, Or<(With<GroupLane>, With<MergedEdges>)>>,
+) {
+    let graph_data = GraphData(aggregation.shown(timeline.position.map_or_else(
+        || graph_state.as_graph_data(),
+        |count| graph_state.state_at(count).as_graph_data(),
+    )));
 
-    // Create main edge cylinder
-    let edge_entity = commands
-        .spawn((
-            Mesh3d(meshes.add(Cylinder::new(thickness, 1.0))),
-            MeshMaterial3d(edge_material.clone()),
-            Transform::default(),
-            edge_component,
+    // The scene was spawned from the graph data that is still shown
+    let edge_ids = |edge: &GraphEdge| {
+        let graph = &shown_data.as_deref()?.graph;
+        Some((
+            graph.node_weight(edge.from)?.id.clone(),
+            graph.node_weight(edge.to)?.id.clone(),
         ))
-        .id();
+    };
+    let previous = shown_data.as_deref().map(|shown| PreviousScene {
+        nodes: node_query
+            .iter()
+            .filter_map(|(_, node, transform, change, appearing)| {
+                let info = shown.graph.node_weight(node.index)?;
+                let before = PreviousNode {
+                    position: transform.translation,
+                    node_type: info.node_type.clone(),
+                    level: info.level,
+                    change: change.cloned(),
+                    appearing: appearing.map(|appearing| appearing.elapsed),
+                };
+                Some((node.id.clone(), before))
+            })
+            .collect(),
+        edges: edge_query
+            .iter()
+            .filter_map(|(_, edge, _, appearing)| {
+                Some((
+                    edge_ids(edge)?,
+                    appearing.map(|appearing| appearing.elapsed),
+                ))
+            })
+            .collect(),
+    });
 
-    // Add arrow head for directional edges
-    if edge_info.is_some() {
-        commands.spawn((
-            Mesh3d(meshes.add(Cone {
-                radius: thickness * 3.0,
-                height: thickness * 8.0,
-            })),
-            MeshMaterial3d(edge_material),
-            Transform::default(),
-            crate::types::EdgeArrowHead { edge: edge_entity },
-        ));
+    // Everything is respawned, except what was removed, which shrinks away.
+    // Edges go first, and vanishing ones are unlinked from their nodes, so
+    // despawning the nodes doesn't take them along.
+    for (entity, edge, transform, _) in &edge_query {
+        let removed = edge_ids(edge).is_some_and(|(from, to)| {
+            let ends = graph_data
+                .node_map
+                .get(&from)
+                .zip(graph_data.node_map.get(&to));
+            ends.is_none_or(|(&from, &to)| graph_data.graph.find_edge(from, to).is_none())
+        });
+        if removed {
+            vanish::<(GraphEdge, EdgeFrom, EdgeTo, Appearing)>(&mut commands, entity, transform);
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+    for entity in &scenery_query {
+        commands.entity(entity).despawn();
     }
+    for (entity, node, transform, ..) in &node_query {
+        if previous.is_some() && !graph_data.node_map.contains_key(&node.id) {
+            vanish::<(GraphNode, NodeChange, Appearing, LayoutTransition)>(
+                &mut commands,
+                entity,
+                transform,
+            );
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    create_graph_visualization(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &graph_data,
+        &layout,
+        &view,
+        previous.as_ref(),
+    );
+    commands.insert_resource(graph_data);
 }
 
+/// Takes `B` off an entity so the rest of the app stops seeing it, and
+/// shrinks it away
+fn vanish<B: Bundle>(commands: &mut Commands, entity: Entity, transform: &Transform) {
+    commands.entity(entity).remove::<B>().insert(Vanishing {
+        elapsed: 0.0,
+        scale: transform.scale,
+    });
+}
+
+/// Points each edge from its source node at its target, and stretches its
+/// shaft and moves its arrow head to match its length
 #[allow(clippy::type_complexity)]
 pub fn update_edge_positions(
     node_query: Query<(&Transform, &GraphNode)>,
-    mut edge_query: Query<(Entity, &mut Transform, &GraphEdge), Without<GraphNode>>,
-    mut arrow_query: Query<
-        (&mut Transform, &crate::types::EdgeArrowHead),
-        (Without<GraphEdge>, Without<GraphNode>),
+    mut edge_query: Query<
+        (
+            &mut Transform,
+            &GraphEdge,
+            &Children,
+            Option<&Appearing>,
+            Option<&PortOffsets>,
+        ),
+        Without<GraphNode>,
+    >,
+    mut part_query: Query<
+        (&mut Transform, Has<EdgeArrowHead>),
+        (
+            Or<(With<EdgeShaft>, With<EdgeArrowHead>)>,
+            Without<GraphEdge>,
+            Without<GraphNode>,
+        ),
     >,
-    _graph_data: Res<GraphData>,
 ) {
-    // Create a map of node indices to positions
-    let mut node_positions = HashMap::new();
+    // Create a map of node indices to transforms
+    let mut node_transforms = HashMap::new();
     for (transform, graph_node) in &node_query {
-        node_positions.insert(graph_node.index, transform.translation);
+        node_transforms.insert(graph_node.index, *transform);
     }
 
-    // Update edge positions
-    for (edge_entity, mut edge_transform, graph_edge) in &mut edge_query {
-        if let (Some(&from_pos), Some(&to_pos)) = (
-            node_positions.get(&graph_edge.from),
-            node_positions.get(&graph_edge.to),
+    for (mut edge_transform, graph_edge, children, appearing, ports) in &mut edge_query {
+        if let (Some(from), Some(to)) = (
+            node_transforms.get(&graph_edge.from),
+            node_transforms.get(&graph_edge.to),
         ) {
+            let offsets = ports.copied().unwrap_or_default();
+            let from_pos = from.transform_point(offsets.from);
+            let to_pos = to.transform_point(offsets.to);
+            // New edges grow out from their source
+            let grown = appearing.map_or(1.0, Appearing::progress).max(0.01);
+            let to_pos = from_pos.lerp(to_pos, grown);
             let direction = to_pos - from_pos;
             let distance = direction.length();
-            let midpoint = from_pos + direction * 0.5;
 
-            // Calculate rotation to align cylinder with edge direction
+            // Calculate rotation to align the edge's Y axis with its direction
             let up = Vec3::Y;
             let rotation = if direction.normalize().dot(up).abs() > 0.999 {
                 // Edge is nearly vertical, use a different approach
@@ -237,19 +835,213 @@ pub fn update_edge_positions(
                 Quat::from_rotation_arc(up, direction.normalize())
             };
 
-            edge_transform.translation = midpoint;
+            edge_transform.translation = from_pos;
             edge_transform.rotation = rotation;
-            edge_transform.scale = Vec3::new(1.0, distance, 1.0);
-
-            // Update arrow head position if this edge has one
-            for (mut arrow_transform, arrow_head) in &mut arrow_query {
-                if arrow_head.edge == edge_entity {
-                    // Position arrow at the end of the edge, slightly before the target node
-                    let arrow_offset = direction.normalize() * 0.5; // Offset from target
-                    arrow_transform.translation = to_pos - arrow_offset;
-                    arrow_transform.rotation = rotation;
+
+            let mut parts = part_query.iter_many_mut(children);
+            while let Some((mut transform, is_arrow_head)) = parts.fetch_next() {
+                if is_arrow_head {
+                    // Slightly before the target node
+                    transform.translation = Vec3::Y * (distance - 0.5);
+                } else {
+                    transform.translation = Vec3::Y * (distance * 0.5);
+                    transform.scale = Vec3::new(1.0, distance, 1.0);
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_graph;
+    use crate::sources::SourceRegistry;
+
+    #[test]
+    fn test_distinct_shapes_tell_every_known_type_apart() {
+        let types = [
+            Some("organization"),
+            Some("line_of_business"),
+            Some("site"),
+            Some("team"),
+            Some("user"),
+            Some("database"),
+            Some("actor:participant"),
+            Some("actor:customer"),
+            Some("process"),
+            Some("external"),
+            Some(CLUSTER_TYPE),
+            None,
+        ];
+        let mut shapes: Vec<_> = types
+            .iter()
+            .map(|&node_type| get_node_shape_name(node_type, true))
+            .collect();
+        shapes.sort_unstable();
+        shapes.dedup();
+        assert_eq!(shapes.len(), types.len());
+        assert_eq!(get_node_shape_name(Some("team"), false), "sphere");
+    }
+
+    #[test]
+    fn test_edge_parts_follow_their_edge() {
+        let content = r#"{
+            "nodes": [{"id": "a"}, {"id": "b"}],
+            "edges": [{"from": "a", "to": "b", "label": "calls", "rate": 4.0}]
+        }"#;
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("json")).unwrap();
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<LayoutSettings>()
+            .init_resource::<ViewSettings>()
+            .init_resource::<Timeline>()
+            .init_resource::<Aggregation>()
+            .insert_resource(state)
+            .add_systems(Update, (refresh_graph_scene, update_edge_positions).chain());
+        app.world_mut().resource_mut::<GraphState>().set_changed();
+        app.update();
+        app.update();
+
+        let world = app.world_mut();
+        let positions: HashMap<_, _> = world
+            .query::<(&GraphNode, &Transform)>()
+            .iter(world)
+            .map(|(node, transform)| (node.id.clone(), transform.translation))
+            .collect();
+        let (edge, graph_edge, children) = world
+            .query::<(&Transform, &GraphEdge, &Children)>()
+            .single(world)
+            .unwrap();
+        assert_eq!(edge.translation, positions["a"]);
+        assert_eq!(graph_edge.label.as_deref(), Some("calls"));
+        assert_eq!(graph_edge.rate, Some(4.0));
+        assert_eq!(children.len(), 2);
+        let length = positions["a"].distance(positions["b"]);
+        let shaft = world
+            .query_filtered::<&Transform, With<EdgeShaft>>()
+            .single(world)
+            .unwrap();
+        assert!((shaft.scale.y - length).abs() < 1e-4);
+        let arrow = world
+            .query_filtered::<&Transform, With<EdgeArrowHead>>()
+            .single(world)
+            .unwrap();
+        assert!((arrow.translation.y - (length - 0.5)).abs() < 1e-4);
+
+        // Despawning an edge takes its parts with it
+        let edge = world
+            .query_filtered::<Entity, With<GraphEdge>>()
+            .single(world)
+            .unwrap();
+        world.entity_mut(edge).despawn();
+        assert_eq!(world.query::<&EdgeShaft>().iter(world).count(), 0);
+        assert_eq!(world.query::<&EdgeArrowHead>().iter(world).count(), 0);
+    }
+
+    #[test]
+    fn test_port_edges_attach_to_record_fields() {
+        let content = r#"digraph {
+            queue [shape=record, label="<head> head|<mid> mid|<tail> tail"];
+            worker;
+            queue:tail -> worker;
+        }"#;
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let queue = state.get_node("queue").unwrap();
+        assert_eq!(port_offset(queue, "mid"), Some(Vec3::ZERO));
+        let tail = port_offset(queue, "tail").unwrap();
+        assert!(tail.x > 0.0);
+        assert_eq!(port_offset(queue, "nope"), None);
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<LayoutSettings>()
+            .init_resource::<ViewSettings>()
+            .init_resource::<Timeline>()
+            .init_resource::<Aggregation>()
+            .insert_resource(state)
+            .add_systems(Update, (refresh_graph_scene, update_edge_positions).chain());
+        app.world_mut().resource_mut::<GraphState>().set_changed();
+        app.update();
+        app.update();
+
+        let world = app.world_mut();
+        let queue = world
+            .query::<(&GraphNode, &Transform)>()
+            .iter(world)
+            .find_map(|(node, transform)| (node.id == "queue").then_some(*transform))
+            .unwrap();
+        let (edge, graph_edge) = world
+            .query::<(&Transform, &GraphEdge)>()
+            .single(world)
+            .unwrap();
+        assert_eq!(graph_edge.from_port.as_deref(), Some("tail"));
+        assert!(edge.translation.distance(queue.transform_point(tail)) < 1e-4);
+    }
+
+    #[test]
+    fn test_look_alike_nodes_and_edges_share_assets() {
+        let dot = crate::generate::generate_dot(crate::generate::GraphModel::Grid, 100, 180, 0);
+        let (state, _) = load_graph(&SourceRegistry::default(), &dot, Some("dot")).unwrap();
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<LayoutSettings>()
+            .init_resource::<ViewSettings>()
+            .init_resource::<Timeline>()
+            .init_resource::<Aggregation>()
+            .insert_resource(state)
+            .add_systems(Update, refresh_graph_scene);
+        app.world_mut().resource_mut::<GraphState>().set_changed();
+        app.update();
+
+        let world = app.world_mut();
+        assert_eq!(world.query::<&GraphNode>().iter(world).count(), 100);
+        assert_eq!(world.query::<&GraphEdge>().iter(world).count(), 180);
+        // One node shape, and a shaft and an arrow head of one thickness
+        assert_eq!(world.resource::<Assets<Mesh>>().len(), 3);
+        // The nodes' material, the plain edges' and the styled edges'
+        assert_eq!(world.resource::<Assets<StandardMaterial>>().len(), 3);
+    }
+
+    #[test]
+    fn test_despawning_a_node_despawns_its_edges() {
+        let content = r"digraph {
+            a -> b
+            b -> c
+            c -> a
+        }";
+        let (state, _) = load_graph(&SourceRegistry::default(), content, Some("dot")).unwrap();
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<LayoutSettings>()
+            .init_resource::<ViewSettings>()
+            .init_resource::<Timeline>()
+            .init_resource::<Aggregation>()
+            .insert_resource(state)
+            .add_systems(Update, refresh_graph_scene);
+        app.world_mut().resource_mut::<GraphState>().set_changed();
+        app.update();
+
+        let world = app.world_mut();
+        assert_eq!(world.query::<&GraphEdge>().iter(world).count(), 3);
+        let b = world
+            .query::<(Entity, &GraphNode)>()
+            .iter(world)
+            .find_map(|(entity, node)| (node.id == "b").then_some(entity))
+            .unwrap();
+        world.entity_mut(b).despawn();
+
+        // Only c -> a is left, with its shaft and arrow head
+        assert_eq!(world.query::<&GraphEdge>().iter(world).count(), 1);
+        assert_eq!(world.query::<&EdgeShaft>().iter(world).count(), 1);
+        assert_eq!(world.query::<&EdgeArrowHead>().iter(world).count(), 1);
+    }
+}
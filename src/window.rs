@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use bevy::window::{MonitorSelection, WindowMode, WindowPosition, WindowResolution};
+
+/// Where the viewer window opens and how big it is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WindowPlacement {
+    /// Borderless fullscreen, for presentations and wall dashboards
+    pub fullscreen: bool,
+    /// Size in logical pixels; ignored when fullscreen
+    pub size: Option<(u32, u32)>,
+    /// Monitor to open on, as numbered by the OS from 0; the primary one if None
+    pub monitor: Option<usize>,
+}
+
+impl WindowPlacement {
+    pub fn apply(self, window: &mut Window) {
+        let monitor = self
+            .monitor
+            .map_or(MonitorSelection::Primary, MonitorSelection::Index);
+        if let Some((width, height)) = self.size {
+            window.resolution = WindowResolution::new(width as f32, height as f32);
+        }
+        if self.monitor.is_some() {
+            window.position = WindowPosition::Centered(monitor);
+        }
+        if self.fullscreen {
+            window.mode = WindowMode::BorderlessFullscreen(monitor);
+        }
+    }
+}
+
+/// Parses a `--window-size` value such as `1280x720`
+pub fn parse_window_size(s: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid window size '{s}' (expected WIDTHxHEIGHT, e.g. 1280x720)");
+    let (width, height) = s.split_once(['x', 'X', '×']).ok_or_else(invalid)?;
+    let parse = |n: &str| n.trim().parse::<u32>().ok().filter(|&n| n > 0);
+    parse(width).zip(parse(height)).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placement_sets_up_the_window() {
+        assert_eq!(parse_window_size("1280x720"), Ok((1280, 720)));
+        assert_eq!(parse_window_size("800 X 600"), Ok((800, 600)));
+        assert!(parse_window_size("1280").is_err());
+        assert!(parse_window_size("0x720").is_err());
+
+        let mut window = Window::default();
+        WindowPlacement {
+            fullscreen: true,
+            size: Some((640, 480)),
+            monitor: Some(1),
+        }
+        .apply(&mut window);
+        assert_eq!(
+            window.mode,
+            WindowMode::BorderlessFullscreen(MonitorSelection::Index(1))
+        );
+        assert_eq!(
+            window.position,
+            WindowPosition::Centered(MonitorSelection::Index(1))
+        );
+        assert_eq!(window.resolution.physical_width(), 640);
+
+        let mut window = Window::default();
+        WindowPlacement::default().apply(&mut window);
+        assert_eq!(window.mode, WindowMode::Windowed);
+        assert_eq!(window.position, WindowPosition::Automatic);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::highlight::{HighlightKind, OUTLINE_SCALE};
+use crate::keybindings::{Action, KeyBindings};
+use crate::types::{GraphNode, NodeHighlight, SearchState, SelectedNodes, XrayGhost};
+use bevy::pbr::{
+    ExtendedMaterial, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline,
+    NotShadowCaster,
+};
+use bevy::prelude::*;
+use bevy::render::mesh::MeshVertexBufferLayoutRef;
+use bevy::render::render_resource::{
+    AsBindGroup, CompareFunction, RenderPipelineDescriptor, SpecializedMeshPipelineError,
+};
+
+/// Opacity of a ghost at full highlight
+const GHOST_ALPHA: f32 = 0.55;
+
+/// A standard material drawn only where something is in front of it
+pub type XrayMaterial = ExtendedMaterial<StandardMaterial, XrayExtension>;
+
+/// Turns the depth test around, so a mesh shows through what hides it and
+/// nowhere else
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone, Default)]
+pub struct XrayExtension {}
+
+impl MaterialExtension for XrayExtension {
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // Depth is reversed, so nearer fragments have greater depths
+        if let Some(depth_stencil) = &mut descriptor.depth_stencil {
+            depth_stencil.depth_compare = CompareFunction::Less;
+            depth_stencil.depth_write_enabled = false;
+        }
+        Ok(())
+    }
+}
+
+/// Whether selected and search-matched nodes show through the nodes and
+/// edges in front of them
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct Xray {
+    pub enabled: bool,
+}
+
+impl Default for Xray {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Draws hidden selected and search-matched nodes through whatever is in
+/// front of them, so they're never lost in a dense graph
+pub struct XrayPlugin;
+
+impl Plugin for XrayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<XrayMaterial>::default())
+            .init_resource::<Xray>()
+            .add_systems(
+                Update,
+                (toggle_xray, spawn_xray_ghosts, apply_xray_ghosts).chain(),
+            );
+    }
+}
+
+pub fn toggle_xray(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    search_state: Res<SearchState>,
+    bindings: Res<KeyBindings>,
+    mut xray: ResMut<Xray>,
+) {
+    if !search_state.active && bindings.just_pressed(Action::ToggleXray, &keyboard_input) {
+        xray.enabled = !xray.enabled;
+    }
+}
+
+/// Gives each new node a hidden ghost the size of its outline, with a
+/// material of its own
+///
+/// Only the ghost's front faces are drawn, so where the node is in plain
+/// sight the node itself hides its ghost.
+pub fn spawn_xray_ghosts(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<XrayMaterial>>,
+    node_query: Query<(Entity, &Mesh3d), Added<GraphNode>>,
+) {
+    for (entity, mesh) in &node_query {
+        let material = materials.add(XrayMaterial {
+            base: StandardMaterial {
+                unlit: true,
+                alpha_mode: AlphaMode::Blend,
+                ..default()
+            },
+            extension: XrayExtension {},
+        });
+        commands.entity(entity).with_child((
+            Mesh3d(mesh.0.clone()),
+            MeshMaterial3d(material),
+            Transform::from_scale(Vec3::splat(OUTLINE_SCALE)),
+            Visibility::Hidden,
+            NotShadowCaster,
+            Pickable::IGNORE,
+            XrayGhost,
+        ));
+    }
+}
+
+/// Shows the ghosts of selected and search-matched nodes in their outline's
+/// color, fading search matches with their highlight
+pub fn apply_xray_ghosts(
+    xray: Res<Xray>,
+    search_state: Res<SearchState>,
+    selection: Res<SelectedNodes>,
+    node_query: Query<&GraphNode>,
+    highlight_query: Query<&NodeHighlight>,
+    mut ghost_query: Query<
+        (&ChildOf, &mut Visibility, &MeshMaterial3d<XrayMaterial>),
+        With<XrayGhost>,
+    >,
+    mut materials: ResMut<Assets<XrayMaterial>>,
+) {
+    for (child_of, mut visibility, material) in &mut ghost_query {
+        let node = child_of.parent();
+        let selected = search_state.selected_node == Some(node)
+            || node_query
+                .get(node)
+                .is_ok_and(|graph_node| selection.0.contains(&graph_node.id));
+        let highlight = if selected {
+            Some((HighlightKind::Selection, 1.0))
+        } else {
+            highlight_query
+                .get(node)
+                .ok()
+                .map(|highlight| (HighlightKind::Search, highlight.fade_timer.clamp(0.0, 1.0)))
+        }
+        .filter(|_| xray.enabled);
+
+        let Some((kind, intensity)) = highlight else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        visibility.set_if_neq(Visibility::Inherited);
+        let color = kind.color().with_alpha(GHOST_ALPHA * intensity);
+        // Only touch the material when the color actually changes
+        if materials
+            .get(&material.0)
+            .is_some_and(|m| m.base.base_color != color)
+            && let Some(material) = materials.get_mut(&material.0)
+        {
+            material.base.base_color = color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::NodeIndex;
+
+    #[test]
+    fn test_selected_and_matched_nodes_get_ghosts() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<XrayMaterial>>()
+            .init_resource::<SearchState>()
+            .init_resource::<SelectedNodes>()
+            .init_resource::<Xray>()
+            .add_systems(Update, (spawn_xray_ghosts, apply_xray_ghosts).chain());
+
+        let mesh = app
+            .world_mut()
+            .resource_mut::<Assets<Mesh>>()
+            .add(Sphere::new(0.5));
+        let mut spawn_node = |name: &str, index| {
+            app.world_mut()
+                .spawn((
+                    Mesh3d(mesh.clone()),
+                    GraphNode {
+                        id: name.to_string(),
+                        name: name.to_string(),
+                        index: NodeIndex::new(index),
+                    },
+                ))
+                .id()
+        };
+        let selected = spawn_node("a", 0);
+        let matched = spawn_node("b", 1);
+        let other = spawn_node("c", 2);
+        app.world_mut()
+            .resource_mut::<SelectedNodes>()
+            .0
+            .insert("a".to_string());
+        app.world_mut()
+            .entity_mut(matched)
+            .insert(NodeHighlight { fade_timer: 1.0 });
+
+        // Ghosts are spawned on the first update and shown on the next
+        app.update();
+        app.update();
+
+        let ghosts = |app: &mut App| {
+            let mut query = app
+                .world_mut()
+                .query_filtered::<(&ChildOf, &Visibility), With<XrayGhost>>();
+            let mut ghosts: Vec<_> = query
+                .iter(app.world())
+                .map(|(child_of, visibility)| (child_of.parent(), *visibility))
+                .collect();
+            ghosts.sort_unstable_by_key(|(node, _)| *node);
+            ghosts
+        };
+        assert_eq!(
+            ghosts(&mut app),
+            [
+                (selected, Visibility::Inherited),
+                (matched, Visibility::Inherited),
+                (other, Visibility::Hidden),
+            ]
+        );
+
+        app.world_mut().resource_mut::<Xray>().enabled = false;
+        app.update();
+        assert!(
+            ghosts(&mut app)
+                .iter()
+                .all(|(_, visibility)| *visibility == Visibility::Hidden)
+        );
+    }
+}